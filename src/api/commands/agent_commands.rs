@@ -18,6 +18,9 @@ use crate::api::dto::{
     ApiResponse, AgentSummary, CreateAgentRequest
 };
 use crate::api::error::{ApiResult, map_result};
+use crate::api::commands::audit_commands::audit_actor;
+use crate::api::middleware::AuthMiddleware;
+use crate::infrastructure::security::{AuditCategory, AuditEvent, AuditLog};
 
 /// Create a new agent
 #[tauri::command]
@@ -146,7 +149,9 @@ pub async fn list_agents(
 pub async fn update_agent_configuration(
     agent_id: String,
     configuration: Value,
+    auth: State<'_, AuthMiddleware>,
     agent_service: State<'_, Arc<AgentService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
 ) -> ApiResult<bool> {
     let id = Uuid::parse_str(&agent_id)
         .map_err(|e| crate::api::error::to_api_error(e))?;
@@ -163,10 +168,15 @@ pub async fn update_agent_configuration(
         .and_then(|v| v.as_u64())
         .map(|v| v as usize);
     
+    let audit_event = AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "update_agent_configuration")
+        .with_target(agent_id)
+        .with_details(configuration.clone());
+    
     // Update the agent configuration
     let result = agent_service.update_agent_configuration(
         id, model, memory_enabled, max_tokens, Some(configuration)
     ).await;
+    audit_log.record_or_log(audit_event.with_result(&result)).await;
     
     map_result(result)?;
     
@@ -179,7 +189,9 @@ pub async fn set_agent_capability(
     agent_id: String,
     capability: String,
     enabled: bool,
+    auth: State<'_, AuthMiddleware>,
     agent_service: State<'_, Arc<AgentService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
 ) -> ApiResult<bool> {
     let id = Uuid::parse_str(&agent_id)
         .map_err(|e| crate::api::error::to_api_error(e))?;
@@ -196,6 +208,12 @@ pub async fn set_agent_capability(
     
     // Update the capability
     let result = agent_service.set_capability(id, capability_type, enabled).await;
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "set_agent_capability")
+            .with_target(agent_id)
+            .with_details(serde_json::json!({ "capability": capability, "enabled": enabled }))
+            .with_result(&result)
+    ).await;
     map_result(result)?;
     
     Ok(true)
@@ -205,12 +223,19 @@ pub async fn set_agent_capability(
 #[tauri::command]
 pub async fn delete_agent(
     agent_id: String,
+    auth: State<'_, AuthMiddleware>,
     agent_service: State<'_, Arc<AgentService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
 ) -> ApiResult<bool> {
     let id = Uuid::parse_str(&agent_id)
         .map_err(|e| crate::api::error::to_api_error(e))?;
     
     let result = agent_service.delete_agent(id).await;
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "delete_agent")
+            .with_target(agent_id)
+            .with_result(&result)
+    ).await;
     map_result(result)?;
     
     Ok(true)
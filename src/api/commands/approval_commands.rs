@@ -0,0 +1,148 @@
+//! Tauri commands for tool operation approvals
+//!
+//! This module provides Tauri commands for operators to review and
+//! decide on tool operations held for approval.
+
+use tauri::async_runtime::JoinHandle;
+use tauri::{State, Window, WindowEvent};
+use uuid::Uuid;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::api::error::{ApiError, ApiResult};
+use crate::api::middleware::AuthMiddleware;
+use crate::infrastructure::tools::{ApprovalEvent, ApprovalManager, ApprovalRequest};
+
+/// Roles allowed to see and decide on held operations
+const APPROVER_ROLES: &[&str] = &["approver", "admin"];
+
+/// Approval event forwarders by window label
+#[derive(Default)]
+pub struct ApprovalSubscriptions {
+    forwarders: Mutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl ApprovalSubscriptions {
+    /// Track the forwarder of a window, stopping the one it replaces
+    fn insert(&self, label: String, forwarder: JoinHandle<()>) {
+        if let Some(previous) = self.forwarders.lock().unwrap().insert(label, forwarder) {
+            previous.abort();
+        }
+    }
+
+    /// Stop the forwarder of a window; returns whether one was running
+    fn remove(&self, label: &str) -> bool {
+        match self.forwarders.lock().unwrap().remove(label) {
+            Some(forwarder) => {
+                forwarder.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Forward approval events to the window
+///
+/// New requests are emitted as `tool:approval_requested` events and
+/// decisions as `tool:approval_resolved` events. Each window has at most
+/// one forwarder; it stops when the window is destroyed or unsubscribes.
+/// Held parameters may carry file contents and register values, so only
+/// approvers can subscribe.
+#[tauri::command]
+pub async fn subscribe_approval_requests(
+    window: Window,
+    auth: State<'_, AuthMiddleware>,
+    approvals: State<'_, Arc<ApprovalManager>>,
+    subscriptions: State<'_, Arc<ApprovalSubscriptions>>,
+) -> ApiResult<Vec<ApprovalRequest>> {
+    auth.require_any_role(APPROVER_ROLES).map_err(ApiError::from)?;
+
+    // Subscribe before listing so no request is missed
+    let mut receiver = approvals.subscribe();
+    let label = window.label().to_string();
+
+    let forwarder_window = window.clone();
+    let forwarder = tauri::async_runtime::spawn(async move {
+        loop {
+            let emitted = match receiver.recv().await {
+                Ok(ApprovalEvent::Requested(request)) => {
+                    forwarder_window.emit("tool:approval_requested", request)
+                }
+                Ok(event @ ApprovalEvent::Resolved { .. }) => {
+                    forwarder_window.emit("tool:approval_resolved", event)
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+            // The window is gone
+            if emitted.is_err() {
+                break;
+            }
+        }
+    });
+    subscriptions.insert(label.clone(), forwarder);
+
+    let subscriptions = subscriptions.inner().clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Destroyed = event {
+            subscriptions.remove(&label);
+        }
+    });
+
+    Ok(approvals.pending())
+}
+
+/// Stop forwarding approval events to the window
+#[tauri::command]
+pub async fn unsubscribe_approval_requests(
+    window: Window,
+    subscriptions: State<'_, Arc<ApprovalSubscriptions>>,
+) -> ApiResult<bool> {
+    Ok(subscriptions.remove(window.label()))
+}
+
+/// List operations waiting for approval
+#[tauri::command]
+pub async fn list_pending_approvals(
+    auth: State<'_, AuthMiddleware>,
+    approvals: State<'_, Arc<ApprovalManager>>,
+) -> ApiResult<Vec<ApprovalRequest>> {
+    auth.require_any_role(APPROVER_ROLES).map_err(ApiError::from)?;
+    Ok(approvals.pending())
+}
+
+/// Approve a held operation
+#[tauri::command]
+pub async fn approve_tool_execution(
+    request_id: String,
+    auth: State<'_, AuthMiddleware>,
+    approvals: State<'_, Arc<ApprovalManager>>,
+) -> ApiResult<bool> {
+    let approver = auth.require_any_role(APPROVER_ROLES).map_err(ApiError::from)?;
+    let id = Uuid::parse_str(&request_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    approvals.approve(id, &approver.username).await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    Ok(true)
+}
+
+/// Reject a held operation
+#[tauri::command]
+pub async fn reject_tool_execution(
+    request_id: String,
+    reason: Option<String>,
+    auth: State<'_, AuthMiddleware>,
+    approvals: State<'_, Arc<ApprovalManager>>,
+) -> ApiResult<bool> {
+    let approver = auth.require_any_role(APPROVER_ROLES).map_err(ApiError::from)?;
+    let id = Uuid::parse_str(&request_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    approvals.reject(id, &approver.username, reason).await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    Ok(true)
+}
@@ -0,0 +1,55 @@
+//! Tauri commands for the audit log
+//!
+//! This module provides Tauri commands for reading and verifying the
+//! hash-chained audit log, and the helpers other commands use to
+//! attribute the events they record.
+
+use tauri::State;
+use std::sync::Arc;
+
+use crate::api::error::ApiResult;
+use crate::api::middleware::AuthMiddleware;
+use crate::infrastructure::security::{AuditEntry, AuditLog, AuditVerification};
+
+/// Actor recorded when nobody is signed in
+const LOCAL_ACTOR: &str = "local";
+
+/// Default number of entries returned by `list_audit_entries`
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Name to record as the actor of an audited command: the user of the
+/// server-side session
+pub(crate) fn audit_actor(auth: &AuthMiddleware) -> String {
+    auth.current_session()
+        .map(|context| context.username)
+        .unwrap_or_else(|| LOCAL_ACTOR.to_string())
+}
+
+/// List audit entries after a sequence number
+#[tauri::command]
+pub async fn list_audit_entries(
+    after_sequence: Option<u64>,
+    limit: Option<usize>,
+    audit_log: State<'_, Arc<AuditLog>>,
+) -> ApiResult<Vec<AuditEntry>> {
+    audit_log.list(after_sequence.unwrap_or(0), limit.unwrap_or(DEFAULT_PAGE_SIZE)).await
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
+
+/// Verify the audit chain and report the first tampered entry, if any
+#[tauri::command]
+pub async fn verify_audit_log(
+    audit_log: State<'_, Arc<AuditLog>>,
+) -> ApiResult<AuditVerification> {
+    let verification = audit_log.verify().await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    if !verification.valid {
+        tracing::error!(
+            "Audit log verification failed at entry {:?}: {:?}",
+            verification.first_invalid_sequence, verification.error
+        );
+    }
+
+    Ok(verification)
+}
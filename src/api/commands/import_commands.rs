@@ -0,0 +1,100 @@
+//! Tauri commands for historical data import
+//!
+//! This module provides Tauri commands for bulk-loading historical
+//! readings from CSV/Parquet files into a twin's sensor series.
+
+use tauri::{State, Window};
+use uuid::Uuid;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::infrastructure::import::{
+    ImportJobManager, ImportReport, ImportRequest, ImportStatus,
+};
+use crate::api::error::ApiResult;
+
+/// Start a historical import job
+///
+/// Progress is emitted as `import:progress` events and the final report
+/// as an `import:completed` event.
+#[tauri::command]
+pub async fn start_historical_import(
+    request: ImportRequest,
+    window: Window,
+    imports: State<'_, Arc<ImportJobManager>>,
+) -> ApiResult<String> {
+    // Subscribe before starting so no event of this job is missed
+    let mut receiver = imports.subscribe();
+
+    let job_id = imports.start(request)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    let manager = imports.inner().clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let progress = match receiver.recv().await {
+                Ok(progress) => progress,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::debug!("Import progress listener skipped {} events", skipped);
+                    // The final event of this job may have been among them
+                    match manager.report(job_id) {
+                        Ok(report) if report.status != ImportStatus::Running => report.progress_event(),
+                        _ => continue,
+                    }
+                },
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            if progress.job_id != job_id {
+                continue;
+            }
+
+            let _ = window.emit("import:progress", &progress);
+
+            if progress.status != ImportStatus::Running {
+                if let Ok(report) = manager.report(job_id) {
+                    let _ = window.emit("import:completed", report);
+                }
+                break;
+            }
+        }
+    });
+
+    Ok(job_id.to_string())
+}
+
+/// Cancel a running historical import job
+#[tauri::command]
+pub async fn cancel_historical_import(
+    job_id: String,
+    imports: State<'_, Arc<ImportJobManager>>,
+) -> ApiResult<bool> {
+    let id = Uuid::parse_str(&job_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    imports.cancel(id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    Ok(true)
+}
+
+/// Get the report of a historical import job
+#[tauri::command]
+pub async fn get_historical_import(
+    job_id: String,
+    imports: State<'_, Arc<ImportJobManager>>,
+) -> ApiResult<ImportReport> {
+    let id = Uuid::parse_str(&job_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    imports.report(id)
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
+
+/// List historical import jobs
+#[tauri::command]
+pub async fn list_historical_imports(
+    imports: State<'_, Arc<ImportJobManager>>,
+) -> ApiResult<Vec<ImportReport>> {
+    Ok(imports.list())
+}
@@ -0,0 +1,54 @@
+//! Tauri commands for LLM provider routing
+//!
+//! This module provides Tauri commands for inspecting the health of the
+//! LLM providers behind the router, resetting their circuit breakers and
+//! managing the response cache.
+
+use tauri::State;
+use std::sync::Arc;
+
+use crate::api::error::{ApiError, ApiResult, ErrorCode};
+use crate::core::application::services::{CacheStats, CachingClient};
+use crate::infrastructure::llm::{LLMRouter, ProviderHealth};
+
+/// Get circuit breaker and health state of every LLM provider
+#[tauri::command]
+pub async fn get_llm_provider_health(
+    router: State<'_, Arc<LLMRouter>>,
+) -> ApiResult<Vec<ProviderHealth>> {
+    Ok(router.health())
+}
+
+/// Close a provider's circuit so requests are sent to it again
+#[tauri::command]
+pub async fn reset_llm_provider(
+    provider: String,
+    router: State<'_, Arc<LLMRouter>>,
+) -> ApiResult<Vec<ProviderHealth>> {
+    if !router.reset_provider(&provider) {
+        return Err(ApiError {
+            code: ErrorCode::NotFound.as_str().to_string(),
+            message: format!("LLM provider '{}' is not configured", provider),
+            details: None,
+        });
+    }
+    tracing::info!("Reset circuit breaker of LLM provider '{}'", provider);
+    Ok(router.health())
+}
+
+/// Get response cache hit and miss counts
+#[tauri::command]
+pub async fn get_llm_cache_stats(
+    cache: State<'_, Arc<CachingClient>>,
+) -> ApiResult<CacheStats> {
+    Ok(cache.stats().await)
+}
+
+/// Remove every cached response; returns the number removed
+#[tauri::command]
+pub async fn clear_llm_cache(
+    cache: State<'_, Arc<CachingClient>>,
+) -> ApiResult<usize> {
+    cache.clear().await
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
@@ -13,6 +13,14 @@ pub mod agent_commands;
 pub mod twin_commands;
 pub mod simulation_commands;
 pub mod tool_commands;
+pub mod import_commands;
+pub mod secret_commands;
+pub mod audit_commands;
+pub mod approval_commands;
+pub mod llm_commands;
+pub mod usage_commands;
+pub mod orchestration_commands;
+pub mod prompt_template_commands;
 
 // Re-export all commands for convenient access
 pub use conversation_commands::*;
@@ -20,6 +28,14 @@ pub use agent_commands::*;
 pub use twin_commands::*;
 pub use simulation_commands::*;
 pub use tool_commands::*;
+pub use import_commands::*;
+pub use secret_commands::*;
+pub use audit_commands::*;
+pub use approval_commands::*;
+pub use llm_commands::*;
+pub use usage_commands::*;
+pub use orchestration_commands::*;
+pub use prompt_template_commands::*;
 
 // Legacy commands - kept for backward compatibility
 // These will be removed in a future version
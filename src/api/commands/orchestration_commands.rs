@@ -0,0 +1,49 @@
+//! Tauri commands for multi-agent orchestration
+//!
+//! This module provides a Tauri command for sending a user message to
+//! several agents of a conversation at once, coordinated by a router,
+//! hand-off sequence or supervisor plan.
+
+use tauri::State;
+use uuid::Uuid;
+use std::sync::Arc;
+
+use crate::api::error::{ApiError, ApiResult, ErrorCode};
+use crate::core::application::services::{
+    AgentOrchestrator, OrchestrationError, OrchestrationOutcome, OrchestrationPlan,
+};
+use crate::core::domain::models::conversation::Message;
+use crate::core::domain::traits::repository::RepositoryError;
+
+fn orchestration_error(error: OrchestrationError) -> ApiError {
+    let code = match &error {
+        OrchestrationError::Repository(RepositoryError::NotFound { .. }) => ErrorCode::NotFound,
+        OrchestrationError::NotParticipant(_)
+        | OrchestrationError::AgentUnavailable(_)
+        | OrchestrationError::Inactive(_)
+        | OrchestrationError::InvalidPlan(_) => ErrorCode::Validation,
+        _ => return crate::api::error::to_api_error(error),
+    };
+    ApiError {
+        code: code.as_str().to_string(),
+        message: error.to_string(),
+        details: None,
+    }
+}
+
+/// Send a user message to the agents of a conversation under a plan
+#[tauri::command]
+pub async fn run_agent_orchestration(
+    conversation_id: String,
+    user_id: String,
+    content: String,
+    plan: OrchestrationPlan,
+    orchestrator: State<'_, Arc<AgentOrchestrator>>,
+) -> ApiResult<OrchestrationOutcome> {
+    let conversation_id = Uuid::parse_str(&conversation_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    let message = Message::from_user(conversation_id, user_id, content);
+    orchestrator.run(conversation_id, message, &plan).await
+        .map_err(orchestration_error)
+}
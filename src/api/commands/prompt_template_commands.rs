@@ -0,0 +1,82 @@
+//! Tauri commands for prompt templates
+//!
+//! This module provides Tauri commands for managing prompt templates with
+//! typed variables and partials, and for previewing a template rendered
+//! with the live state of a digital twin.
+
+use tauri::State;
+use uuid::Uuid;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::api::error::{ApiError, ApiResult, ErrorCode};
+use crate::core::application::services::{
+    PromptTemplate, PromptTemplateError, PromptTemplates, RenderedPrompt,
+};
+use crate::core::domain::traits::repository::RepositoryError;
+
+fn template_error(error: PromptTemplateError) -> ApiError {
+    let code = match &error {
+        PromptTemplateError::NotFound(_)
+        | PromptTemplateError::Repository(RepositoryError::NotFound { .. }) => ErrorCode::NotFound,
+        PromptTemplateError::Repository(_) => return crate::api::error::to_api_error(error),
+        _ => ErrorCode::Validation,
+    };
+    ApiError {
+        code: code.as_str().to_string(),
+        message: error.to_string(),
+        details: None,
+    }
+}
+
+/// List prompt templates by name
+#[tauri::command]
+pub async fn list_prompt_templates(
+    templates: State<'_, Arc<PromptTemplates>>,
+) -> ApiResult<Vec<PromptTemplate>> {
+    templates.list().await.map_err(template_error)
+}
+
+/// Get a prompt template by name
+#[tauri::command]
+pub async fn get_prompt_template(
+    name: String,
+    templates: State<'_, Arc<PromptTemplates>>,
+) -> ApiResult<PromptTemplate> {
+    templates.get(&name).await.map_err(template_error)
+}
+
+/// Validate and save a prompt template, creating a new version
+#[tauri::command]
+pub async fn save_prompt_template(
+    template: PromptTemplate,
+    templates: State<'_, Arc<PromptTemplates>>,
+) -> ApiResult<PromptTemplate> {
+    templates.save(template).await.map_err(template_error)
+}
+
+/// Delete a prompt template that no other template includes
+#[tauri::command]
+pub async fn delete_prompt_template(
+    name: String,
+    templates: State<'_, Arc<PromptTemplates>>,
+) -> ApiResult<()> {
+    templates.delete(&name).await.map_err(template_error)
+}
+
+/// Render a prompt template, saved or not, for an optional twin
+#[tauri::command]
+pub async fn preview_prompt_template(
+    template: PromptTemplate,
+    variables: Option<HashMap<String, serde_json::Value>>,
+    twin_id: Option<String>,
+    templates: State<'_, Arc<PromptTemplates>>,
+) -> ApiResult<RenderedPrompt> {
+    let twin_id = twin_id
+        .map(|id| Uuid::parse_str(&id))
+        .transpose()
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    templates.preview(&template, &variables.unwrap_or_default(), twin_id).await
+        .map_err(template_error)
+}
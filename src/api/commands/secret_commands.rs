@@ -0,0 +1,108 @@
+//! Tauri commands for the credential vault
+//!
+//! This module provides Tauri commands for managing data source
+//! credentials and the keys that protect them. Responses only ever
+//! contain secret metadata; plaintext values are accepted on write but
+//! never returned.
+
+use serde::Serialize;
+use tauri::State;
+use uuid::Uuid;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::infrastructure::config::{AppConfig, MasterKeySource};
+use crate::infrastructure::security::{CredentialVault, KeyManager, MasterKey, SecretMetadata};
+use crate::api::error::ApiResult;
+
+/// Store new credentials in the vault
+#[tauri::command]
+pub async fn create_secret(
+    name: String,
+    values: HashMap<String, String>,
+    vault: State<'_, Arc<CredentialVault>>,
+) -> ApiResult<SecretMetadata> {
+    vault.store(&name, values).await
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
+
+/// Replace the values of stored credentials
+#[tauri::command]
+pub async fn update_secret(
+    secret_id: String,
+    values: HashMap<String, String>,
+    vault: State<'_, Arc<CredentialVault>>,
+) -> ApiResult<SecretMetadata> {
+    let id = Uuid::parse_str(&secret_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    vault.update(id, values).await
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
+
+/// Delete stored credentials
+#[tauri::command]
+pub async fn delete_secret(
+    secret_id: String,
+    vault: State<'_, Arc<CredentialVault>>,
+) -> ApiResult<bool> {
+    let id = Uuid::parse_str(&secret_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    vault.delete(id).await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    Ok(true)
+}
+
+/// List stored credentials (metadata only)
+#[tauri::command]
+pub async fn list_secrets(
+    vault: State<'_, Arc<CredentialVault>>,
+) -> ApiResult<Vec<SecretMetadata>> {
+    vault.list().await
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
+
+/// Result of a data key rotation
+#[derive(Debug, Serialize)]
+pub struct KeyRotationReport {
+    /// ID of the new active data key
+    pub active_key_id: String,
+
+    /// Number of secrets re-encrypted with the new key
+    pub reencrypted: usize,
+}
+
+/// Create a new data key and re-encrypt stored credentials with it
+#[tauri::command]
+pub async fn rotate_data_key(
+    key_manager: State<'_, Arc<KeyManager>>,
+    vault: State<'_, Arc<CredentialVault>>,
+) -> ApiResult<KeyRotationReport> {
+    let active_key_id = key_manager.rotate_data_key().await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    let reencrypted = vault.reencrypt().await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    Ok(KeyRotationReport { active_key_id, reencrypted })
+}
+
+/// Re-wrap all data keys with a new master key
+///
+/// The new key source is stored with the re-wrapped keys and used on the
+/// next start. Returns the number of data keys re-wrapped.
+#[tauri::command]
+pub async fn rotate_master_key(
+    source: MasterKeySource,
+    passphrase: Option<String>,
+    key_manager: State<'_, Arc<KeyManager>>,
+    config: State<'_, AppConfig>,
+) -> ApiResult<usize> {
+    let master = MasterKey::load(&source, &config.security.secret_key, passphrase.as_deref())
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    key_manager.rotate_master_key(master, &source).await
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
@@ -22,6 +22,9 @@ use crate::api::dto::{
     ToolExecutionResult
 };
 use crate::api::error::{ApiResult, map_result};
+use crate::api::commands::audit_commands::audit_actor;
+use crate::api::middleware::AuthMiddleware;
+use crate::infrastructure::security::{AuditCategory, AuditEvent, AuditLog};
 
 /// Register a new tool
 #[tauri::command]
@@ -31,7 +34,9 @@ pub async fn register_tool(
     tool_type: String,
     parameters: Vec<Value>,
     permissions: Vec<String>,
+    auth: State<'_, AuthMiddleware>,
     tool_service: State<'_, Arc<ToolService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
 ) -> ApiResult<ToolSummary> {
     // Parse tool type
     let tool_type = match tool_type.as_str() {
@@ -117,6 +122,12 @@ pub async fn register_tool(
         concurrency_config: None,
     };
     
+    let audit_details = serde_json::json!({
+        "name": name,
+        "tool_type": format!("{:?}", tool_type),
+        "permissions": permissions,
+    });
+    
     // Create tool registration
     let registration = ToolRegistration {
         name,
@@ -129,6 +140,11 @@ pub async fn register_tool(
     
     // Register the tool
     let result = tool_service.register_tool(registration).await;
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "register_tool")
+            .with_details(audit_details)
+            .with_result(&result)
+    ).await;
     let tool = map_result(result)?;
     
     // Convert to DTO
@@ -224,8 +240,14 @@ pub async fn list_tools(
 #[tauri::command]
 pub async fn execute_tool(
     request: ToolExecutionRequestDto,
+    auth: State<'_, AuthMiddleware>,
     tool_service: State<'_, Arc<ToolService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
 ) -> ApiResult<ToolExecutionResult> {
+    let audit_event = AuditEvent::new(AuditCategory::ToolExecution, audit_actor(&auth), "execute_tool")
+        .with_target(request.tool_id.to_string())
+        .with_details(serde_json::json!({ "parameters": request.parameters }));
+    
     // Create execution request
     let execution_request = ToolExecutionRequest {
         tool_id: request.tool_id,
@@ -235,6 +257,15 @@ pub async fn execute_tool(
     
     // Execute the tool
     let result = tool_service.execute_tool(execution_request).await;
+    let audit_event = match &result {
+        Ok(execution_result) => {
+            let mut event = audit_event.with_result(&result);
+            event.details["status"] = Value::String(format!("{:?}", execution_result.status));
+            event
+        }
+        Err(_) => audit_event.with_result(&result),
+    };
+    audit_log.record_or_log(audit_event).await;
     let execution_result = map_result(result)?;
     
     // Convert to DTO
@@ -367,12 +398,20 @@ pub async fn update_tool_configuration(
     tool_id: String,
     timeout_ms: Option<u64>,
     enabled: Option<bool>,
+    auth: State<'_, AuthMiddleware>,
     tool_service: State<'_, Arc<ToolService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
 ) -> ApiResult<bool> {
     let id = Uuid::parse_str(&tool_id)
         .map_err(|e| crate::api::error::to_api_error(e))?;
     
     let result = tool_service.update_tool_configuration(id, timeout_ms, enabled).await;
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "update_tool_configuration")
+            .with_target(tool_id)
+            .with_details(serde_json::json!({ "timeout_ms": timeout_ms, "enabled": enabled }))
+            .with_result(&result)
+    ).await;
     map_result(result)?;
     
     Ok(true)
@@ -382,12 +421,19 @@ pub async fn update_tool_configuration(
 #[tauri::command]
 pub async fn delete_tool(
     tool_id: String,
+    auth: State<'_, AuthMiddleware>,
     tool_service: State<'_, Arc<ToolService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
 ) -> ApiResult<bool> {
     let id = Uuid::parse_str(&tool_id)
         .map_err(|e| crate::api::error::to_api_error(e))?;
     
     let result = tool_service.delete_tool(id).await;
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "delete_tool")
+            .with_target(tool_id)
+            .with_result(&result)
+    ).await;
     map_result(result)?;
     
     Ok(true)
@@ -3,13 +3,14 @@
 //! This module provides Tauri commands for creating, configuring,
 //! and interacting with digital twins.
 
-use tauri::State;
+use tauri::{State, Window, WindowEvent};
 use uuid::Uuid;
 use serde::{Serialize, Deserialize};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::core::application::services::TwinService;
+use crate::core::application::services::{SemanticMemory, TwinService};
 use crate::core::domain::models::{
     DigitalTwin, TwinType, DataSource, DataSourceType, 
     ConnectionConfig, SyncConfiguration, SyncMode, RetentionPolicy
@@ -18,12 +19,26 @@ use crate::api::dto::{
     ApiResponse, TwinSummary, CreateTwinRequest
 };
 use crate::api::error::{ApiResult, map_result};
+use crate::api::commands::audit_commands::audit_actor;
+use crate::api::middleware::AuthMiddleware;
+use crate::infrastructure::config::AppConfig;
+use crate::infrastructure::security::{AuditCategory, AuditEvent, AuditLog, CredentialVault};
+use crate::infrastructure::tools::modbus_registers::RegisterMap;
+use crate::infrastructure::tools::modbus_server::{
+    ModbusSimulator, ModbusSimulatorRegistry, SimulatorInfo,
+};
+use crate::infrastructure::tools::{
+    MqttToolExecutor, SparkplugIngestor, VirtualSensorDriver, SPARKPLUG_SOURCE_ID,
+};
+use crate::infrastructure::transform::MappingIngestor;
 
 /// Create a new digital twin
 #[tauri::command]
 pub async fn create_digital_twin(
     request: CreateTwinRequest,
     twin_service: State<'_, Arc<TwinService>>,
+    semantic_memory: State<'_, Arc<SemanticMemory>>,
+    config: State<'_, AppConfig>,
 ) -> ApiResult<TwinSummary> {
     // Parse twin type
     let twin_type = match request.twin_type.as_str() {
@@ -41,11 +56,25 @@ pub async fn create_digital_twin(
     ).await;
     
     let twin = map_result(result)?;
+    index_documentation(&twin, &semantic_memory, &config).await;
     
     // Convert to DTO
     Ok(crate::api::dto::converters::twin_to_summary(&twin))
 }
 
+/// Make a twin's documentation retrievable by agents
+///
+/// Only with long-term memory enabled; failures are logged, as the twin
+/// itself was stored.
+async fn index_documentation(twin: &DigitalTwin, memory: &SemanticMemory, config: &AppConfig) {
+    if !config.agent.long_term_memory.enabled {
+        return;
+    }
+    if let Err(e) = memory.index_twin_documentation(twin).await {
+        tracing::warn!("Failed to index documentation of twin {}: {}", twin.id, e);
+    }
+}
+
 /// Get digital twin by ID
 #[tauri::command]
 pub async fn get_digital_twin(
@@ -66,11 +95,11 @@ pub async fn get_digital_twin(
                 "name": ds.name,
                 "type": format!("{:?}", ds.source_type),
                 "connection": {
-                    "url": ds.connection.url,
-                    "credentials": ds.connection.credentials.is_some(),
-                    "parameters": ds.connection.parameters,
+                    "endpoint": ds.connection_config.endpoint,
+                    "credentials_secret_id": ds.connection_config.credentials_secret_id,
+                    "parameters": ds.connection_config.custom_params,
                 },
-                "enabled": ds.enabled,
+                "enabled": ds.active,
             })
         })
         .collect::<Vec<_>>();
@@ -119,13 +148,29 @@ pub async fn update_digital_twin(
     name: Option<String>,
     description: Option<String>,
     configuration: Option<Value>,
+    auth: State<'_, AuthMiddleware>,
     twin_service: State<'_, Arc<TwinService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
+    semantic_memory: State<'_, Arc<SemanticMemory>>,
+    config: State<'_, AppConfig>,
 ) -> ApiResult<TwinSummary> {
     let id = Uuid::parse_str(&twin_id)
         .map_err(|e| crate::api::error::to_api_error(e))?;
     
+    let audit_details = serde_json::json!({
+        "name": name,
+        "description": description,
+        "configuration": configuration,
+    });
     let result = twin_service.update_twin(id, name, description, configuration).await;
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "update_digital_twin")
+            .with_target(twin_id)
+            .with_details(audit_details)
+            .with_result(&result)
+    ).await;
     let twin = map_result(result)?;
+    index_documentation(&twin, &semantic_memory, &config).await;
     
     // Convert to DTO
     Ok(crate::api::dto::converters::twin_to_summary(&twin))
@@ -135,12 +180,19 @@ pub async fn update_digital_twin(
 #[tauri::command]
 pub async fn delete_digital_twin(
     twin_id: String,
+    auth: State<'_, AuthMiddleware>,
     twin_service: State<'_, Arc<TwinService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
 ) -> ApiResult<bool> {
     let id = Uuid::parse_str(&twin_id)
         .map_err(|e| crate::api::error::to_api_error(e))?;
     
     let result = twin_service.delete_twin(id).await;
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "delete_digital_twin")
+            .with_target(twin_id)
+            .with_result(&result)
+    ).await;
     map_result(result)?;
     
     Ok(true)
@@ -154,8 +206,13 @@ pub async fn add_data_source(
     source_type: String,
     connection_url: String,
     parameters: Value,
-    credentials: Option<Value>,
+    credentials: Option<HashMap<String, String>>,
+    auth: State<'_, AuthMiddleware>,
     twin_service: State<'_, Arc<TwinService>>,
+    vault: State<'_, Arc<CredentialVault>>,
+    audit_log: State<'_, Arc<AuditLog>>,
+    virtual_sensors: State<'_, Arc<VirtualSensorDriver>>,
+    mqtt: State<'_, Arc<MqttToolExecutor>>,
 ) -> ApiResult<Value> {
     let id = Uuid::parse_str(&twin_id)
         .map_err(|e| crate::api::error::to_api_error(e))?;
@@ -168,22 +225,65 @@ pub async fn add_data_source(
         "rest" => DataSourceType::Rest,
         "database" => DataSourceType::Database,
         "file" => DataSourceType::File,
-        custom => DataSourceType::Custom(custom.to_string()),
+        custom => DataSourceType::Custom { source_id: custom.to_string() },
+    };
+    
+    // Credentials go to the vault; the connection only keeps the secret ID
+    let credentials_secret_id = match credentials {
+        Some(values) => Some(
+            vault.store(&format!("{} credentials", name), values).await
+                .map_err(|e| crate::api::error::to_api_error(e))?
+                .id
+        ),
+        None => None,
     };
     
     // Create connection config
     let connection = ConnectionConfig {
-        url: connection_url,
-        credentials,
-        parameters,
+        endpoint: connection_url,
+        credentials_secret_id,
+        timeout_seconds: 30,
+        retry_config: Default::default(),
+        custom_params: serde_json::from_value(parameters).unwrap_or_default(),
     };
     
+    // Credential values are never written to the audit log
+    let audit_details = serde_json::json!({
+        "name": name,
+        "type": format!("{:?}", source_type),
+        "endpoint": connection.endpoint,
+        "credentials_secret_id": connection.credentials_secret_id,
+    });
+    
     // Add the data source
     let result = twin_service.add_data_source(
         id, name, source_type, connection
     ).await;
-    
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "add_data_source")
+            .with_target(twin_id)
+            .with_details(audit_details)
+            .with_result(&result)
+    ).await;
+
+    // No data source references the secret of a failed addition
+    if let (Err(_), Some(secret_id)) = (&result, credentials_secret_id) {
+        if let Err(e) = vault.delete(secret_id).await {
+            tracing::warn!("Failed to delete unused credentials secret {}: {}", secret_id, e);
+        }
+    }
+
     let data_source = map_result(result)?;
+
+    // Virtual sensors start streaming as soon as they are attached
+    if VirtualSensorDriver::supports(&data_source) && data_source.active {
+        virtual_sensors.attach(id, &data_source).await
+            .map_err(|e| crate::api::error::to_api_error(e))?;
+    }
+    
+    // Message queue sources are mapped onto the twin as messages arrive
+    mqtt.follow_source(id, &data_source).await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
     
     // Convert to JSON response
     let response = serde_json::json!({
@@ -191,11 +291,11 @@ pub async fn add_data_source(
         "name": data_source.name,
         "type": format!("{:?}", data_source.source_type),
         "connection": {
-            "url": data_source.connection.url,
-            "credentials": data_source.connection.credentials.is_some(),
-            "parameters": data_source.connection.parameters,
+            "endpoint": data_source.connection_config.endpoint,
+            "credentials_secret_id": data_source.connection_config.credentials_secret_id,
+            "parameters": data_source.connection_config.custom_params,
         },
-        "enabled": data_source.enabled,
+        "enabled": data_source.active,
     });
     
     Ok(response)
@@ -206,7 +306,12 @@ pub async fn add_data_source(
 pub async fn remove_data_source(
     twin_id: String,
     data_source_id: String,
+    auth: State<'_, AuthMiddleware>,
     twin_service: State<'_, Arc<TwinService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
+    virtual_sensors: State<'_, Arc<VirtualSensorDriver>>,
+    mqtt: State<'_, Arc<MqttToolExecutor>>,
+    sparkplug: State<'_, Arc<SparkplugIngestor>>,
 ) -> ApiResult<bool> {
     let twin_id = Uuid::parse_str(&twin_id)
         .map_err(|e| crate::api::error::to_api_error(e))?;
@@ -215,8 +320,55 @@ pub async fn remove_data_source(
         .map_err(|e| crate::api::error::to_api_error(e))?;
     
     let result = twin_service.remove_data_source(twin_id, data_source_id).await;
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "remove_data_source")
+            .with_target(twin_id.to_string())
+            .with_details(serde_json::json!({ "data_source_id": data_source_id }))
+            .with_result(&result)
+    ).await;
     map_result(result)?;
     
+    virtual_sensors.detach(data_source_id).await;
+    sparkplug.detach(data_source_id).await;
+    mqtt.unfollow_source(data_source_id).await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+    
+    Ok(true)
+}
+
+/// Forward data source transform failures to the window
+///
+/// Values whose mapping or transform fails are emitted as
+/// `twin:transform_failed` events until the window is destroyed.
+#[tauri::command]
+pub async fn subscribe_transform_failures(
+    window: Window,
+    mapping: State<'_, Arc<MappingIngestor>>,
+) -> ApiResult<bool> {
+    let mut receiver = mapping.subscribe();
+
+    let forwarder_window = window.clone();
+    let forwarder = tauri::async_runtime::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(failure) => {
+                    // The window is gone
+                    if forwarder_window.emit("twin:transform_failed", failure).is_err() {
+                        break;
+                    }
+                },
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    window.on_window_event(move |event| {
+        if let WindowEvent::Destroyed = event {
+            forwarder.abort();
+        }
+    });
+
     Ok(true)
 }
 
@@ -227,7 +379,9 @@ pub async fn configure_twin_sync(
     mode: String,
     interval_seconds: Option<u64>,
     enabled: bool,
+    auth: State<'_, AuthMiddleware>,
     twin_service: State<'_, Arc<TwinService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
 ) -> ApiResult<bool> {
     let id = Uuid::parse_str(&twin_id)
         .map_err(|e| crate::api::error::to_api_error(e))?;
@@ -253,8 +407,20 @@ pub async fn configure_twin_sync(
         retention_policy: RetentionPolicy::KeepAll,
     };
     
+    let audit_details = serde_json::json!({
+        "mode": mode,
+        "interval_seconds": sync_config.interval_seconds,
+        "enabled": enabled,
+    });
+    
     // Update the sync configuration
     let result = twin_service.configure_sync(id, sync_config).await;
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "configure_twin_sync")
+            .with_target(twin_id)
+            .with_details(audit_details)
+            .with_result(&result)
+    ).await;
     map_result(result)?;
     
     Ok(true)
@@ -305,12 +471,21 @@ pub async fn update_twin_property(
     twin_id: String,
     property_path: String,
     value: Value,
+    auth: State<'_, AuthMiddleware>,
     twin_service: State<'_, Arc<TwinService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
 ) -> ApiResult<bool> {
     let id = Uuid::parse_str(&twin_id)
         .map_err(|e| crate::api::error::to_api_error(e))?;
     
+    let audit_details = serde_json::json!({ "property": property_path, "value": value });
     let result = twin_service.update_property(id, property_path, value).await;
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "update_twin_property")
+            .with_target(twin_id)
+            .with_details(audit_details)
+            .with_result(&result)
+    ).await;
     map_result(result)?;
     
     Ok(true)
@@ -337,4 +512,148 @@ pub async fn export_twin_model(
     };
     
     map_result(result)
-}
\ No newline at end of file
+}
+
+/// Start a Modbus TCP server exposing the twin's properties as registers
+///
+/// The register map defaults to the first data source carrying a
+/// `register_map` custom parameter. Registers are refreshed from the twin
+/// periodically and client writes to writable registers update the twin.
+#[tauri::command]
+pub async fn start_modbus_simulator(
+    twin_id: String,
+    bind_address: Option<String>,
+    register_map: Option<Value>,
+    refresh_interval_ms: Option<u64>,
+    twin_service: State<'_, Arc<TwinService>>,
+    simulators: State<'_, Arc<ModbusSimulatorRegistry>>,
+) -> ApiResult<SimulatorInfo> {
+    let id = Uuid::parse_str(&twin_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    let twin = map_result(twin_service.get_twin(id).await)?;
+
+    let register_map = register_map
+        .or_else(|| twin.data_sources.iter()
+            .find_map(|ds| ds.connection_config.custom_params.get("register_map").cloned()))
+        .ok_or_else(|| crate::api::error::ApiError {
+            code: "MISSING_REGISTER_MAP".to_string(),
+            message: "No register map configured for this twin".to_string(),
+            details: None,
+        })?;
+    let register_map = RegisterMap::from_value(&register_map)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    let simulator = Arc::new(ModbusSimulator::new(id, register_map)
+        .map_err(|e| crate::api::error::to_api_error(e))?);
+    simulator.apply_twin(&twin);
+
+    let addr = bind_address.as_deref().unwrap_or("127.0.0.1:5020")
+        .parse()
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+    let handle = simulator.clone().serve(addr).await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    let service = twin_service.inner().clone();
+    let refreshed = simulator.clone();
+    let mut writes = simulator.subscribe_writes();
+    let interval = std::time::Duration::from_millis(refresh_interval_ms.unwrap_or(1000));
+    let refresh = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if let Ok(twin) = service.get_twin(id).await {
+                        refreshed.apply_twin(&twin);
+                    }
+                },
+                write = writes.recv() => match write {
+                    Ok(write) => {
+                        if let Err(e) = service.update_property(id, write.property.clone(), write.value).await {
+                            tracing::warn!("Failed to apply Modbus write to {}: {}", write.property, e);
+                        }
+                    },
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+    });
+
+    Ok(simulators.insert(simulator, handle, Some(refresh)).await)
+}
+
+/// Stop the Modbus TCP simulator for a twin
+#[tauri::command]
+pub async fn stop_modbus_simulator(
+    twin_id: String,
+    simulators: State<'_, Arc<ModbusSimulatorRegistry>>,
+) -> ApiResult<bool> {
+    let id = Uuid::parse_str(&twin_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    Ok(simulators.stop(&id).await)
+}
+
+/// List running Modbus TCP simulators
+#[tauri::command]
+pub async fn list_modbus_simulators(
+    simulators: State<'_, Arc<ModbusSimulatorRegistry>>,
+) -> ApiResult<Vec<SimulatorInfo>> {
+    Ok(simulators.list().await)
+}
+
+/// Store Sparkplug B metrics of an edge node, or one of its devices, as
+/// sensor data of a twin
+///
+/// The binding is added to the twin as a data source, so it is restored
+/// at startup and removed with `remove_data_source`.
+#[tauri::command]
+pub async fn bind_sparkplug_source(
+    twin_id: String,
+    group_id: String,
+    edge_node_id: String,
+    device_id: Option<String>,
+    auth: State<'_, AuthMiddleware>,
+    twin_service: State<'_, Arc<TwinService>>,
+    audit_log: State<'_, Arc<AuditLog>>,
+    ingestor: State<'_, Arc<SparkplugIngestor>>,
+) -> ApiResult<Value> {
+    let id = Uuid::parse_str(&twin_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    let mut scope = format!("{}/{}", group_id, edge_node_id);
+    if let Some(device_id) = &device_id {
+        scope = format!("{}/{}", scope, device_id);
+    }
+    let connection = ConnectionConfig {
+        endpoint: format!("spBv1.0/{}", scope),
+        credentials_secret_id: None,
+        timeout_seconds: 30,
+        retry_config: Default::default(),
+        custom_params: SparkplugIngestor::binding_params(&group_id, &edge_node_id, device_id.as_deref()),
+    };
+
+    let result = twin_service.add_data_source(
+        id,
+        format!("Sparkplug B {}", scope),
+        DataSourceType::Custom { source_id: SPARKPLUG_SOURCE_ID.to_string() },
+        connection,
+    ).await;
+    audit_log.record_or_log(
+        AuditEvent::new(AuditCategory::ConfigChange, audit_actor(&auth), "bind_sparkplug_source")
+            .with_target(twin_id)
+            .with_details(serde_json::json!({ "scope": scope }))
+            .with_result(&result)
+    ).await;
+
+    let data_source = map_result(result)?;
+    ingestor.attach(id, &data_source).await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    Ok(serde_json::json!({
+        "id": data_source.id,
+        "name": data_source.name,
+        "scope": scope,
+    }))
+}
@@ -0,0 +1,115 @@
+//! Tauri commands for LLM usage and cost accounting
+//!
+//! This module provides Tauri commands for reporting token usage and cost
+//! by period, agent, conversation, provider and model, and for managing
+//! model prices and monthly agent budgets.
+
+use tauri::State;
+use uuid::Uuid;
+use std::sync::Arc;
+
+use crate::api::error::{ApiError, ApiResult, ErrorCode};
+use crate::core::application::services::{
+    AgentBudget, AgentCost, PricingEntry, UsageAccountant, UsageFilter,
+};
+use crate::core::domain::traits::llm_client::{UsagePeriod, UsageStats};
+use crate::infrastructure::llm::LLMRouter;
+
+fn validation_error(message: &str) -> ApiError {
+    ApiError {
+        code: ErrorCode::Validation.as_str().to_string(),
+        message: message.to_string(),
+        details: None,
+    }
+}
+
+/// Get LLM usage and cost over a period
+#[tauri::command]
+pub async fn get_llm_usage(
+    period: UsagePeriod,
+    filter: Option<UsageFilter>,
+    accountant: State<'_, Arc<UsageAccountant>>,
+) -> ApiResult<UsageStats> {
+    accountant.stats(period, &filter.unwrap_or_default()).await
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
+
+/// Get LLM cost per agent over a period, most expensive first
+#[tauri::command]
+pub async fn get_llm_costs_by_agent(
+    period: UsagePeriod,
+    accountant: State<'_, Arc<UsageAccountant>>,
+) -> ApiResult<Vec<AgentCost>> {
+    accountant.costs_by_agent(period).await
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
+
+/// List model prices used for cost computation
+#[tauri::command]
+pub async fn list_llm_pricing(
+    accountant: State<'_, Arc<UsageAccountant>>,
+) -> ApiResult<Vec<PricingEntry>> {
+    accountant.pricing().await
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
+
+/// Set the price of a provider's model; cost-aware routing uses it from
+/// then on
+#[tauri::command]
+pub async fn set_llm_pricing(
+    entry: PricingEntry,
+    accountant: State<'_, Arc<UsageAccountant>>,
+    router: State<'_, Arc<LLMRouter>>,
+) -> ApiResult<PricingEntry> {
+    if entry.input_cost_per_1k < 0.0 || entry.output_cost_per_1k < 0.0 {
+        return Err(validation_error("Prices must not be negative"));
+    }
+
+    accountant.set_pricing(entry.clone()).await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+    router.refresh_costs().await;
+
+    Ok(entry)
+}
+
+/// Get the monthly budget of an agent
+#[tauri::command]
+pub async fn get_agent_budget(
+    agent_id: String,
+    accountant: State<'_, Arc<UsageAccountant>>,
+) -> ApiResult<Option<AgentBudget>> {
+    let agent_id = Uuid::parse_str(&agent_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    accountant.budget(agent_id).await
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
+
+/// Set the monthly budget of an agent
+#[tauri::command]
+pub async fn set_agent_budget(
+    budget: AgentBudget,
+    accountant: State<'_, Arc<UsageAccountant>>,
+) -> ApiResult<AgentBudget> {
+    if budget.monthly_limit < 0.0 {
+        return Err(validation_error("Budget limit must not be negative"));
+    }
+
+    accountant.set_budget(budget.clone()).await
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    Ok(budget)
+}
+
+/// Remove the monthly budget of an agent
+#[tauri::command]
+pub async fn remove_agent_budget(
+    agent_id: String,
+    accountant: State<'_, Arc<UsageAccountant>>,
+) -> ApiResult<bool> {
+    let agent_id = Uuid::parse_str(&agent_id)
+        .map_err(|e| crate::api::error::to_api_error(e))?;
+
+    accountant.remove_budget(agent_id).await
+        .map_err(|e| crate::api::error::to_api_error(e))
+}
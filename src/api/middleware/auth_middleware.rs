@@ -3,12 +3,14 @@
 //! This module provides middleware for authenticating users
 //! and checking permissions for Tauri commands.
 
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use tauri::{command, State};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::infrastructure::security::{AuthService, PermissionManager, AuthError};
+use crate::infrastructure::security::{
+    AuditCategory, AuditEvent, AuditLog, AuthService, PermissionManager, AuthError, InMemoryAuditStore,
+};
 use crate::api::error::ApiError;
 
 /// Authentication middleware error
@@ -65,6 +67,13 @@ pub struct AuthMiddleware {
     
     /// Permission manager
     permission_manager: Arc<PermissionManager>,
+    
+    /// Audit log for authentication events
+    audit_log: Arc<AuditLog>,
+    
+    /// Signed-in user of this application instance; commands take the
+    /// acting user from here, never from client-supplied arguments
+    session: RwLock<Option<AuthContext>>,
 }
 
 impl AuthMiddleware {
@@ -73,39 +82,67 @@ impl AuthMiddleware {
         Self {
             auth_service,
             permission_manager,
+            audit_log: Arc::new(AuditLog::new(Arc::new(InMemoryAuditStore::new()))),
+            session: RwLock::new(None),
         }
     }
     
+    /// Record authentication events in the given audit log
+    pub fn with_audit_log(mut self, audit_log: Arc<AuditLog>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+    
+    /// Permission manager used for authorization checks
+    pub fn permission_manager(&self) -> Arc<PermissionManager> {
+        self.permission_manager.clone()
+    }
+    
     /// Authenticate a user with username and password
     pub async fn login(&self, username: &str, password: &str) -> Result<AuthContext, AuthMiddlewareError> {
         // Authenticate the user
-        let user = self.auth_service.authenticate(username, password)
-            .map_err(AuthMiddlewareError::AuthError)?;
+        let result = self.auth_service.authenticate(username, password).await;
+        self.audit_log.record_or_log(
+            AuditEvent::new(AuditCategory::Authentication, username, "login").with_result(&result)
+        ).await;
+        let user = result.map_err(AuthMiddlewareError::AuthError)?;
         
         // Get user permissions
-        let permissions = self.permission_manager.get_user_permissions(&user.id.to_string())
+        let permissions = self.permission_manager.get_user_permissions(&user.id.to_string()).await
             .map_err(|e| AuthMiddlewareError::Internal(e.to_string()))?;
         
         // Get user roles
-        let roles = self.permission_manager.get_user_roles(&user.id.to_string())
+        let roles = self.permission_manager.get_user_roles(&user.id.to_string()).await
             .map_err(|e| AuthMiddlewareError::Internal(e.to_string()))?;
         
-        Ok(AuthContext {
+        let context = AuthContext {
             user_id: user.id.to_string(),
             username: user.username,
             roles: roles.into_iter().collect(),
             permissions: permissions.into_iter().collect(),
-        })
+        };
+        *self.session.write().unwrap() = Some(context.clone());
+        
+        Ok(context)
     }
     
     /// Authenticate a user with an API key
     pub async fn login_with_api_key(&self, api_key: &str) -> Result<AuthContext, AuthMiddlewareError> {
         // Authenticate with API key
-        let (user, api_key_info) = self.auth_service.authenticate_with_api_key(api_key)
-            .map_err(AuthMiddlewareError::AuthError)?;
+        let result = self.auth_service.authenticate_with_api_key(api_key).await;
+        let mut event = AuditEvent::new(
+            AuditCategory::Authentication,
+            result.as_ref().map(|(user, _)| user.username.as_str()).unwrap_or("unknown"),
+            "api_key_login",
+        ).with_result(&result);
+        if let Ok((_, key)) = &result {
+            event = event.with_target(key.id.to_string());
+        }
+        self.audit_log.record_or_log(event).await;
+        let (user, api_key_info) = result.map_err(AuthMiddlewareError::AuthError)?;
         
         // Get user permissions (including API key permissions)
-        let mut permissions = self.permission_manager.get_user_permissions(&user.id.to_string())
+        let mut permissions = self.permission_manager.get_user_permissions(&user.id.to_string()).await
             .map_err(|e| AuthMiddlewareError::Internal(e.to_string()))?;
         
         // Add API key specific permissions
@@ -114,15 +151,38 @@ impl AuthMiddleware {
         }
         
         // Get user roles
-        let roles = self.permission_manager.get_user_roles(&user.id.to_string())
+        let roles = self.permission_manager.get_user_roles(&user.id.to_string()).await
             .map_err(|e| AuthMiddlewareError::Internal(e.to_string()))?;
         
-        Ok(AuthContext {
+        let context = AuthContext {
             user_id: user.id.to_string(),
             username: user.username,
             roles: roles.into_iter().collect(),
             permissions: permissions.into_iter().collect(),
-        })
+        };
+        *self.session.write().unwrap() = Some(context.clone());
+        
+        Ok(context)
+    }
+    
+    /// Context of the signed-in user, if any
+    pub fn current_session(&self) -> Option<AuthContext> {
+        self.session.read().unwrap().clone()
+    }
+    
+    /// End the current session
+    pub fn logout(&self) -> Option<AuthContext> {
+        self.session.write().unwrap().take()
+    }
+    
+    /// Context of the signed-in user, who must have one of the given roles
+    pub fn require_any_role(&self, roles: &[&str]) -> Result<AuthContext, AuthMiddlewareError> {
+        let context = self.current_session().ok_or(AuthMiddlewareError::Unauthorized)?;
+        if roles.iter().any(|role| context.roles.iter().any(|r| r == role)) {
+            Ok(context)
+        } else {
+            Err(AuthMiddlewareError::Forbidden(format!("Requires one of the roles: {}", roles.join(", "))))
+        }
     }
     
     /// Check if a user has a permission
@@ -185,6 +245,17 @@ pub async fn authenticate(
     }
 }
 
+/// End the session started by [`authenticate`]
+#[command]
+pub async fn logout(
+    auth_middleware: State<'_, AuthMiddleware>,
+) -> Result<(), ApiError> {
+    if let Some(context) = auth_middleware.logout() {
+        tracing::info!("User {} signed out", context.username);
+    }
+    Ok(())
+}
+
 /// Require permission middleware function
 ///
 /// This function can be used as a middleware for Tauri commands
@@ -7,36 +7,60 @@ mod auth_middleware;
 mod rate_limit_middleware;
 mod validation_middleware;
 
-pub use auth_middleware::{AuthMiddleware, authenticate, require_permission};
+pub use auth_middleware::{AuthContext, AuthMiddleware, authenticate, logout, require_permission};
 pub use rate_limit_middleware::{RateLimitMiddleware, rate_limit};
 pub use validation_middleware::{ValidationMiddleware, validate_input};
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::infrastructure::config::SecurityConfig;
-use crate::infrastructure::security::{ApiKeyManager, AuthService, EncryptionService, PermissionManager, RateLimiter};
+use crate::infrastructure::security::{
+    ApiKeyManager, AuditLog, AuthService, EncryptionService, PermissionManager, RateLimiter, SecurityRepository,
+};
+
+/// How often API key last-use times are written to storage
+const API_KEY_USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often idle rate limit buckets are dropped
+const RATE_LIMIT_EVICTION_INTERVAL: Duration = Duration::from_secs(300);
 
 /// Initialize middleware components
-pub fn init(config: &SecurityConfig) -> (AuthMiddleware, RateLimitMiddleware, ValidationMiddleware) {
+///
+/// Users, API keys, roles and grants are persisted through `repository`;
+/// authentication events are written to `audit_log`.
+pub async fn init(
+    config: &SecurityConfig,
+    repository: Arc<dyn SecurityRepository>,
+    audit_log: Arc<AuditLog>,
+) -> anyhow::Result<(AuthMiddleware, RateLimitMiddleware, ValidationMiddleware)> {
     // Initialize encryption service
-    let encryption = Arc::new(EncryptionService::new(&config.secret_key).expect("Failed to initialize encryption service"));
+    let encryption = Arc::new(EncryptionService::new(&config.secret_key)?);
     
     // Initialize API key manager
-    let api_key_manager = Arc::new(ApiKeyManager::new(encryption.clone()));
+    let api_key_manager = Arc::new(ApiKeyManager::new(encryption.clone()).with_repository(repository.clone()));
+    api_key_manager.start_last_used_flusher(API_KEY_USAGE_FLUSH_INTERVAL);
     
     // Initialize auth service
-    let auth_service = Arc::new(AuthService::new(api_key_manager, encryption, config.clone()));
+    let auth_service = Arc::new(
+        AuthService::new(api_key_manager, encryption, config.clone()).with_repository(repository.clone())
+    );
     
     // Initialize permission manager
-    let permission_manager = Arc::new(PermissionManager::new());
+    let permission_manager = PermissionManager::new()
+        .with_repository(repository)
+        .with_auth_service(auth_service.clone());
+    permission_manager.load().await?;
+    let permission_manager = Arc::new(permission_manager);
     
     // Initialize rate limiter
     let rate_limiter = Arc::new(RateLimiter::new(config));
+    rate_limiter.start_idle_eviction(RATE_LIMIT_EVICTION_INTERVAL);
     
     // Create middleware components
-    let auth_middleware = AuthMiddleware::new(auth_service, permission_manager);
+    let auth_middleware = AuthMiddleware::new(auth_service, permission_manager).with_audit_log(audit_log);
     let rate_limit_middleware = RateLimitMiddleware::new(rate_limiter);
     let validation_middleware = ValidationMiddleware::new();
     
-    (auth_middleware, rate_limit_middleware, validation_middleware)
+    Ok((auth_middleware, rate_limit_middleware, validation_middleware))
 }
\ No newline at end of file
@@ -0,0 +1,1304 @@
+//! Multi-agent orchestration within a single conversation.
+//!
+//! A plan names the participating agents and how they cooperate: a router
+//! that delegates to specialists, a fixed sequence of hand-offs, or
+//! specialists answering independently with a supervisor merging their
+//! replies. Every reply is stored as a message from its agent, requests
+//! carry the agent in their conversation context so usage is accounted
+//! per agent, and turn limits keep agents from delegating back and forth
+//! forever. Attachments are sent as content parts, and conversations about
+//! sensitive twins only reach local providers.
+
+use crate::core::application::services::memory_manager::MemoryManager;
+use crate::core::application::services::structured_output::{StructuredOutput, StructuredOutputError};
+use crate::core::domain::models::agent::Agent;
+use crate::core::domain::models::conversation::{Message, MessageSender};
+use crate::core::domain::models::{AgentId, ConversationId};
+use crate::core::domain::traits::llm_client::{
+    AttachmentRendering, ChatCompletionRequest, ChatCompletionResponse, ChatMessage, ContentPart,
+    ConversationContext, LLMClient, LLMError, MessageRole, TokenUsage, LOCAL_ONLY_PARAM,
+};
+use crate::core::domain::traits::repository::{
+    AgentRepository, ConversationRepository, RepositoryError, TwinRepository,
+};
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Agent replies per run, including router decisions
+pub const DEFAULT_MAX_TURNS: u32 = 8;
+
+/// Repairs of a router reply that does not match the decision schema
+const ROUTER_REPAIRS: u32 = 1;
+
+/// Replies per agent per run
+pub const DEFAULT_MAX_TURNS_PER_AGENT: u32 = 3;
+
+/// Conversation messages sent to each agent without a memory manager
+const HISTORY_MESSAGES: usize = 20;
+
+/// Orchestration error types
+#[derive(Debug, Error)]
+pub enum OrchestrationError {
+    /// Loading or storing the conversation or an agent failed
+    #[error("Repository error: {0}")]
+    Repository(#[from] RepositoryError),
+
+    /// An agent's request to the model failed
+    #[error("LLM request failed: {0}")]
+    Llm(#[from] LLMError),
+
+    /// The plan names an agent that is not in the conversation
+    #[error("Agent {0} does not participate in the conversation")]
+    NotParticipant(AgentId),
+
+    /// The plan names an agent that is not active
+    #[error("Agent '{0}' is not available")]
+    AgentUnavailable(String),
+
+    /// The conversation does not accept messages
+    #[error("Conversation {0} is not active")]
+    Inactive(ConversationId),
+
+    /// The plan is empty, repeats agents or cannot finish within the limits
+    #[error("Invalid orchestration plan: {0}")]
+    InvalidPlan(String),
+
+    /// A router decision could not be requested
+    #[error("Router decision failed: {0}")]
+    Decision(StructuredOutputError),
+}
+
+/// Result type for orchestration operations
+pub type OrchestrationResult<T> = Result<T, OrchestrationError>;
+
+/// How the agents of a conversation cooperate on a user message
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "pattern", rename_all = "snake_case")]
+pub enum OrchestrationPlan {
+    /// The router delegates to specialists until it answers itself
+    Router {
+        router_id: AgentId,
+        specialist_ids: Vec<AgentId>,
+    },
+
+    /// Each agent replies in turn, building on the earlier replies
+    Sequential { agent_ids: Vec<AgentId> },
+
+    /// Specialists answer independently; the supervisor merges their replies
+    Supervisor {
+        supervisor_id: AgentId,
+        specialist_ids: Vec<AgentId>,
+    },
+}
+
+impl OrchestrationPlan {
+    /// Name of the pattern, as recorded on messages
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            OrchestrationPlan::Router { .. } => "router",
+            OrchestrationPlan::Sequential { .. } => "sequential",
+            OrchestrationPlan::Supervisor { .. } => "supervisor",
+        }
+    }
+
+    /// Every agent of the plan, coordinator first
+    pub fn agent_ids(&self) -> Vec<AgentId> {
+        match self {
+            OrchestrationPlan::Router { router_id: lead, specialist_ids }
+            | OrchestrationPlan::Supervisor { supervisor_id: lead, specialist_ids } => {
+                std::iter::once(*lead).chain(specialist_ids.iter().copied()).collect()
+            }
+            OrchestrationPlan::Sequential { agent_ids } => agent_ids.clone(),
+        }
+    }
+}
+
+/// Bounds on the replies of one run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrchestrationLimits {
+    /// Agent replies in total, including router decisions
+    pub max_turns: u32,
+
+    /// Replies of any one agent
+    pub max_turns_per_agent: u32,
+}
+
+impl Default for OrchestrationLimits {
+    fn default() -> Self {
+        Self {
+            max_turns: DEFAULT_MAX_TURNS,
+            max_turns_per_agent: DEFAULT_MAX_TURNS_PER_AGENT,
+        }
+    }
+}
+
+/// Why a run ended
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// The plan ran to its end
+    Completed,
+
+    /// The run used its turns
+    TurnLimit,
+
+    /// The router delegated to an agent that used its turns
+    AgentTurnLimit,
+
+    /// The router repeated its previous delegation
+    RepeatedDelegation,
+}
+
+/// Tokens an agent used in a run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgentUsage {
+    pub agent_id: AgentId,
+    pub turns: u32,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+/// Messages and usage of a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrchestrationOutcome {
+    /// Agent and system messages added to the conversation, in order
+    pub messages: Vec<Message>,
+
+    /// Usage per agent, in order of first reply
+    pub usage: Vec<AgentUsage>,
+
+    pub turns: u32,
+    pub stop_reason: StopReason,
+}
+
+/// A router's choice between delegating and answering
+#[derive(Debug, PartialEq)]
+enum RouterDecision {
+    Delegate { agent: String, instruction: String },
+    Respond { response: String },
+}
+
+impl RouterDecision {
+    /// The decision of a reply matching [`router_decision_schema`]; `None`
+    /// if the fields of its action are missing
+    fn from_reply(value: serde_json::Value) -> Option<Self> {
+        let reply: RouterReply = serde_json::from_value(value).ok()?;
+        match reply.action {
+            RouterAction::Delegate => Some(Self::Delegate {
+                agent: reply.agent?,
+                instruction: reply.instruction?,
+            }),
+            RouterAction::Respond => Some(Self::Respond { response: reply.response? }),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RouterAction {
+    Delegate,
+    Respond,
+}
+
+/// A router reply as the schema allows it
+#[derive(Debug, Deserialize)]
+struct RouterReply {
+    action: RouterAction,
+    agent: Option<String>,
+    instruction: Option<String>,
+    response: Option<String>,
+}
+
+/// JSON Schema of a router reply
+///
+/// Providers' structured output modes require an object root, so the
+/// fields of both actions are optional here and
+/// [`RouterDecision::from_reply`] checks those of the chosen action.
+fn router_decision_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "action": { "type": "string", "enum": ["delegate", "respond"] },
+            "agent": { "type": "string" },
+            "instruction": { "type": "string" },
+            "response": { "type": "string" }
+        },
+        "required": ["action"]
+    })
+}
+
+/// State of one run
+struct Run {
+    conversation_id: ConversationId,
+    twin_ids: Vec<Uuid>,
+    /// Whether a twin of the conversation is sensitive
+    local_only: bool,
+    user_message_id: Uuid,
+    pattern: &'static str,
+    agents: HashMap<AgentId, Agent>,
+    history: Vec<Message>,
+    /// Rendered attachments by message
+    parts: HashMap<Uuid, Vec<ContentPart>>,
+    added: Vec<Message>,
+    usage: Vec<AgentUsage>,
+    turns: u32,
+}
+
+/// The part of a run's history the agents see
+struct HistoryWindow {
+    /// Index of the first message sent
+    start: usize,
+    /// Summary of the messages before `start`
+    summary: Option<String>,
+    /// Earlier messages and twin documentation relevant to the last message
+    retrieved: Vec<String>,
+}
+
+impl Run {
+    fn agent_turns(&self, agent_id: AgentId) -> u32 {
+        self.usage.iter().find(|u| u.agent_id == agent_id).map_or(0, |u| u.turns)
+    }
+
+    fn name(&self, agent_id: AgentId) -> String {
+        self.agents.get(&agent_id).map_or_else(|| format!("agent {}", agent_id), |a| a.name.clone())
+    }
+
+    fn finish(self, stop_reason: StopReason) -> OrchestrationOutcome {
+        OrchestrationOutcome {
+            messages: self.added,
+            usage: self.usage,
+            turns: self.turns,
+            stop_reason,
+        }
+    }
+}
+
+/// Runs orchestration plans over the agents of a conversation
+pub struct AgentOrchestrator {
+    conversations: Arc<dyn ConversationRepository>,
+    agents: Arc<dyn AgentRepository>,
+    client: Arc<dyn LLMClient>,
+    limits: OrchestrationLimits,
+    memory: Option<MemoryManager>,
+    twins: Option<Arc<dyn TwinRepository>>,
+    attachments: Option<Arc<dyn AttachmentRendering>>,
+}
+
+impl AgentOrchestrator {
+    pub fn new(
+        conversations: Arc<dyn ConversationRepository>,
+        agents: Arc<dyn AgentRepository>,
+        client: Arc<dyn LLMClient>,
+    ) -> Self {
+        Self {
+            conversations,
+            agents,
+            client,
+            limits: OrchestrationLimits::default(),
+            memory: None,
+            twins: None,
+            attachments: None,
+        }
+    }
+
+    pub fn with_limits(mut self, limits: OrchestrationLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Choose the history sent to agents with `memory`, rather than the
+    /// most recent messages
+    pub fn with_memory(mut self, memory: MemoryManager) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Look up the conversation's twins in `twins`, pinning requests about
+    /// sensitive ones to local providers
+    pub fn with_twins(mut self, twins: Arc<dyn TwinRepository>) -> Self {
+        self.twins = Some(twins);
+        self
+    }
+
+    /// Send message attachments rendered by `attachments`, rather than
+    /// only the message text
+    pub fn with_attachments(mut self, attachments: Arc<dyn AttachmentRendering>) -> Self {
+        self.attachments = Some(attachments);
+        self
+    }
+
+    /// Add `user_message` to the conversation and have the plan's agents reply
+    pub async fn run(
+        &self,
+        conversation_id: ConversationId,
+        user_message: Message,
+        plan: &OrchestrationPlan,
+    ) -> OrchestrationResult<OrchestrationOutcome> {
+        let conversation = self.conversations.get_by_id(conversation_id).await?;
+        if !conversation.is_active() {
+            return Err(OrchestrationError::Inactive(conversation_id));
+        }
+
+        let agent_ids = plan.agent_ids();
+        self.validate(plan, &agent_ids)?;
+        let mut agents = HashMap::new();
+        for agent_id in agent_ids {
+            if !conversation.participant_agent_ids.contains(&agent_id) {
+                return Err(OrchestrationError::NotParticipant(agent_id));
+            }
+            let agent = self.agents.get_by_id(agent_id).await?;
+            if !agent.is_available() {
+                return Err(OrchestrationError::AgentUnavailable(agent.name));
+            }
+            agents.insert(agent_id, agent);
+        }
+
+        let twin_ids = conversation.twin_ids();
+        let local_only = self.local_only(&twin_ids).await?;
+        let user_message = self.conversations.add_message(conversation_id, user_message).await?;
+        if let Some(memory) = &self.memory {
+            memory.remember(&user_message, local_only).await;
+        }
+        let mut history = conversation.messages;
+        history.push(user_message.clone());
+        let mut run = Run {
+            conversation_id,
+            twin_ids,
+            local_only,
+            user_message_id: user_message.id,
+            pattern: plan.pattern(),
+            agents,
+            history,
+            parts: HashMap::new(),
+            added: Vec::new(),
+            usage: Vec::new(),
+            turns: 0,
+        };
+
+        let stop_reason = match plan {
+            OrchestrationPlan::Router { router_id, specialist_ids } => {
+                self.run_router(&mut run, *router_id, specialist_ids).await?
+            }
+            OrchestrationPlan::Sequential { agent_ids } => self.run_sequential(&mut run, agent_ids).await?,
+            OrchestrationPlan::Supervisor { supervisor_id, specialist_ids } => {
+                self.run_supervisor(&mut run, *supervisor_id, specialist_ids).await?
+            }
+        };
+
+        if stop_reason != StopReason::Completed {
+            tracing::warn!("Orchestration in conversation {} stopped: {:?}", conversation_id, stop_reason);
+            let note = Message::system(conversation_id, format!(
+                "Agent orchestration stopped after {} turns: {}",
+                run.turns,
+                match stop_reason {
+                    StopReason::TurnLimit => "the turn limit was reached",
+                    StopReason::AgentTurnLimit => "an agent reached its turn limit",
+                    _ => "the router repeated a delegation",
+                }
+            ));
+            self.post(&mut run, note).await?;
+        }
+        Ok(run.finish(stop_reason))
+    }
+
+    fn validate(&self, plan: &OrchestrationPlan, agent_ids: &[AgentId]) -> OrchestrationResult<()> {
+        let invalid = |message: &str| Err(OrchestrationError::InvalidPlan(message.to_string()));
+        match plan {
+            OrchestrationPlan::Router { specialist_ids, .. } | OrchestrationPlan::Supervisor { specialist_ids, .. } => {
+                if specialist_ids.is_empty() {
+                    return invalid("no specialist agents");
+                }
+                let mut unique = agent_ids.to_vec();
+                unique.sort();
+                unique.dedup();
+                if unique.len() != agent_ids.len() {
+                    return invalid("an agent appears more than once");
+                }
+            }
+            OrchestrationPlan::Sequential { agent_ids } => {
+                if agent_ids.is_empty() {
+                    return invalid("no agents");
+                }
+                let most = agent_ids.iter()
+                    .map(|id| agent_ids.iter().filter(|other| *other == id).count())
+                    .max()
+                    .unwrap_or(0);
+                if most as u32 > self.limits.max_turns_per_agent {
+                    return invalid("an agent appears more often than its turn limit");
+                }
+            }
+        }
+        if matches!(plan, OrchestrationPlan::Supervisor { .. }) && agent_ids.len() as u32 > self.limits.max_turns {
+            return invalid("more agents than turns");
+        }
+        Ok(())
+    }
+
+    async fn run_router(
+        &self,
+        run: &mut Run,
+        router_id: AgentId,
+        specialist_ids: &[AgentId],
+    ) -> OrchestrationResult<StopReason> {
+        let roster: Vec<String> = specialist_ids
+            .iter()
+            .map(|id| format!("- {}: {}", run.agents[id].name, run.agents[id].description))
+            .collect();
+        let instructions = format!(
+            "You coordinate these specialist agents:\n{}\n\n\
+             Reply with a JSON object. To ask a specialist, reply \
+             {{\"action\": \"delegate\", \"agent\": \"<name>\", \"instruction\": \"<what to do>\"}}. \
+             When the specialists' replies answer the user, reply \
+             {{\"action\": \"respond\", \"response\": \"<your answer to the user>\"}}.",
+            roster.join("\n")
+        );
+
+        let mut last_delegation: Option<(AgentId, String)> = None;
+        loop {
+            if run.turns >= self.limits.max_turns {
+                return Ok(StopReason::TurnLimit);
+            }
+            let (decision, reply, usage) = self.decide(run, router_id, &instructions).await?;
+
+            let (agent, instruction) = match decision {
+                Some(RouterDecision::Delegate { agent, instruction }) => (agent, instruction),
+                Some(RouterDecision::Respond { response }) => {
+                    self.post_reply(run, router_id, response, usage, None).await?;
+                    return Ok(StopReason::Completed);
+                }
+                // A router that ignores the protocol has answered in prose
+                None => {
+                    self.post_reply(run, router_id, reply, usage, None).await?;
+                    return Ok(StopReason::Completed);
+                }
+            };
+
+            let target = specialist_ids.iter().copied().find(|id| {
+                run.agents[id].name.eq_ignore_ascii_case(agent.trim()) || id.to_string() == agent.trim()
+            });
+            let Some(target) = target else {
+                self.post_reply(run, router_id, reply, usage, None).await?;
+                let note = Message::system(run.conversation_id, format!(
+                    "There is no specialist named '{}'. Choose one of: {}.",
+                    agent,
+                    specialist_ids.iter().map(|id| run.agents[id].name.as_str()).collect::<Vec<_>>().join(", ")
+                ));
+                self.post(run, note).await?;
+                continue;
+            };
+
+            let delegation = (target, instruction.trim().to_string());
+            if last_delegation.as_ref() == Some(&delegation) {
+                return Ok(StopReason::RepeatedDelegation);
+            }
+            if run.agent_turns(target) >= self.limits.max_turns_per_agent {
+                return Ok(StopReason::AgentTurnLimit);
+            }
+            let announcement = format!("Asking {}: {}", run.name(target), delegation.1);
+            self.post_reply(run, router_id, announcement, usage, None).await?;
+            if run.turns >= self.limits.max_turns {
+                return Ok(StopReason::TurnLimit);
+            }
+
+            let instructions = format!(
+                "{} coordinates this conversation and asks you: {}",
+                run.name(router_id),
+                delegation.1
+            );
+            let (reply, usage) = self.complete(run, target, &instructions).await?;
+            self.post_reply(run, target, reply, usage, Some(router_id)).await?;
+            last_delegation = Some(delegation);
+        }
+    }
+
+    async fn run_sequential(&self, run: &mut Run, agent_ids: &[AgentId]) -> OrchestrationResult<StopReason> {
+        let chain = agent_ids.iter().map(|id| run.name(*id)).collect::<Vec<_>>().join(" -> ");
+        for (index, agent_id) in agent_ids.iter().enumerate() {
+            if run.turns >= self.limits.max_turns {
+                return Ok(StopReason::TurnLimit);
+            }
+            let next = match agent_ids.get(index + 1) {
+                Some(next) => format!("Your reply is handed to {}.", run.name(*next)),
+                None => "Yours is the final reply to the user.".to_string(),
+            };
+            let instructions = format!(
+                "Agents answer the user's last message in turn: {}. Build on the earlier agents' replies. {}",
+                chain, next
+            );
+            let (reply, usage) = self.complete(run, *agent_id, &instructions).await?;
+            let previous = index.checked_sub(1).map(|i| agent_ids[i]);
+            self.post_reply(run, *agent_id, reply, usage, previous).await?;
+        }
+        Ok(StopReason::Completed)
+    }
+
+    async fn run_supervisor(
+        &self,
+        run: &mut Run,
+        supervisor_id: AgentId,
+        specialist_ids: &[AgentId],
+    ) -> OrchestrationResult<StopReason> {
+        // Specialists answer from the same history, independently
+        let instructions = format!(
+            "Answer the user's last message from your expertise; {} will merge your reply with other specialists'.",
+            run.name(supervisor_id)
+        );
+        let history = self.history(run, supervisor_id).await;
+        let requests: Vec<ChatCompletionRequest> = specialist_ids
+            .iter()
+            .map(|id| self.request(run, &history, *id, &instructions))
+            .collect();
+        let responses = try_join_all(requests.into_iter().map(|request| self.client.chat_complete(request))).await?;
+        for (agent_id, response) in specialist_ids.iter().zip(responses) {
+            let usage = self.record(run, *agent_id, &response.usage);
+            self.post_reply(run, *agent_id, reply_text(response), usage, Some(supervisor_id)).await?;
+        }
+
+        let names = specialist_ids.iter().map(|id| run.name(*id)).collect::<Vec<_>>().join(", ");
+        let instructions = format!(
+            "You supervise {}, who have each answered the user's last message. Merge their replies into one \
+             answer for the user, resolving disagreements and keeping each specialist's key points.",
+            names
+        );
+        let (reply, usage) = self.complete(run, supervisor_id, &instructions).await?;
+        self.post_reply(run, supervisor_id, reply, usage, None).await?;
+        Ok(StopReason::Completed)
+    }
+
+    /// Whether any of `twin_ids` is sensitive; deleted twins are skipped
+    async fn local_only(&self, twin_ids: &[Uuid]) -> OrchestrationResult<bool> {
+        let Some(twins) = &self.twins else {
+            return Ok(false);
+        };
+        for twin_id in twin_ids {
+            match twins.get_by_id(*twin_id).await {
+                Ok(twin) if twin.is_sensitive() => return Ok(true),
+                Ok(_) | Err(RepositoryError::NotFound { .. }) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(false)
+    }
+
+    /// The part of the history sent next, with its attachments rendered
+    ///
+    /// The window follows the memory configuration of `agent_id`.
+    async fn history(&self, run: &mut Run, agent_id: AgentId) -> HistoryWindow {
+        let window = self.window(run, agent_id).await;
+        self.render_attachments(run, window.start).await;
+        window
+    }
+
+    async fn window(&self, run: &Run, agent_id: AgentId) -> HistoryWindow {
+        match &self.memory {
+            Some(memory) => {
+                let window = memory
+                    .for_agent(&run.agents[&agent_id])
+                    .build_conversation_context(run.conversation_id, &run.twin_ids, &run.history, run.local_only)
+                    .await;
+                HistoryWindow {
+                    start: window.window_start_index,
+                    summary: window.summary.map(|summary| summary.content),
+                    retrieved: window.retrieved.into_iter().map(|chunk| chunk.content).collect(),
+                }
+            }
+            None => HistoryWindow {
+                start: run.history.len().saturating_sub(HISTORY_MESSAGES),
+                summary: None,
+                retrieved: Vec::new(),
+            },
+        }
+    }
+
+    /// Render the attachments of `run.history[start..]` not rendered yet
+    ///
+    /// File excerpts of the user's new message are also remembered, so
+    /// later turns can retrieve them.
+    async fn render_attachments(&self, run: &mut Run, start: usize) {
+        let Some(renderer) = &self.attachments else {
+            return;
+        };
+        for message in &run.history[start..] {
+            if message.attachments.is_empty() || run.parts.contains_key(&message.id) {
+                continue;
+            }
+            let memory = self.memory.as_ref().filter(|_| message.id == run.user_message_id);
+            let mut parts = Vec::new();
+            for attachment in &message.attachments {
+                let rendered = renderer.render_attachment(attachment).await;
+                if let Some(memory) = memory {
+                    for part in &rendered {
+                        if let ContentPart::File { excerpt, .. } = part {
+                            memory.remember_attachment(run.conversation_id, attachment, excerpt, run.local_only).await;
+                        }
+                    }
+                }
+                parts.extend(rendered);
+            }
+            run.parts.insert(message.id, parts);
+        }
+    }
+
+    /// The request an agent answers, seeing the conversation from its side
+    fn request(
+        &self,
+        run: &Run,
+        history: &HistoryWindow,
+        agent_id: AgentId,
+        instructions: &str,
+    ) -> ChatCompletionRequest {
+        let agent = &run.agents[&agent_id];
+        let messages = run.history[history.start..]
+            .iter()
+            .map(|message| {
+                let (role, content) = match &message.sender {
+                    MessageSender::Agent { agent_id: sender } if *sender == agent_id => {
+                        (MessageRole::Assistant, message.content.clone())
+                    }
+                    MessageSender::Agent { agent_id: sender } => {
+                        (MessageRole::User, format!("[{}]: {}", run.name(*sender), message.content))
+                    }
+                    MessageSender::User { .. } => (MessageRole::User, message.content.clone()),
+                    // Providers such as Anthropic only accept a system role
+                    // in `system`, so notes are attributed like agents' replies
+                    MessageSender::System => (MessageRole::User, format!("[System]: {}", message.content)),
+                };
+                ChatMessage {
+                    role,
+                    content: Some(content),
+                    name: None,
+                    tool_call_id: None,
+                    tool_calls: None,
+                    parts: run.parts.get(&message.id).cloned(),
+                }
+            })
+            .collect();
+
+        ChatCompletionRequest {
+            request_id: Uuid::new_v4().to_string(),
+            messages,
+            model: agent.configuration.model.clone(),
+            temperature: Some(agent.configuration.temperature),
+            max_tokens: Some(agent.configuration.max_tokens),
+            top_p: agent.configuration.top_p,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop_sequences: None,
+            n: None,
+            system: Some(system_prompt(&agent.system_prompt, instructions, history)),
+            tools: None,
+            tool_choice: None,
+            response_format: None,
+            user_id: None,
+            conversation_context: Some(ConversationContext {
+                conversation_id: run.conversation_id,
+                agent_id,
+                parent_message_id: Some(run.user_message_id),
+                metadata: HashMap::new(),
+            }),
+            extra_params: if run.local_only {
+                HashMap::from([(LOCAL_ONLY_PARAM.to_string(), serde_json::Value::Bool(true))])
+            } else {
+                HashMap::new()
+            },
+        }
+    }
+
+    async fn complete(
+        &self,
+        run: &mut Run,
+        agent_id: AgentId,
+        instructions: &str,
+    ) -> OrchestrationResult<(String, AgentUsage)> {
+        let history = self.history(run, agent_id).await;
+        let request = self.request(run, &history, agent_id, instructions);
+        let response = self.client.chat_complete(request).await?;
+        let usage = self.record(run, agent_id, &response.usage);
+        Ok((reply_text(response), usage))
+    }
+
+    /// The router's next decision, with the reply it was read from
+    ///
+    /// The reply must match the decision schema, allowing a repair; a
+    /// router that still ignores it has answered in prose.
+    async fn decide(
+        &self,
+        run: &mut Run,
+        router_id: AgentId,
+        instructions: &str,
+    ) -> OrchestrationResult<(Option<RouterDecision>, String, AgentUsage)> {
+        let history = self.history(run, router_id).await;
+        let request = self.request(run, &history, router_id, instructions);
+        let structured = StructuredOutput::new(self.client.clone()).with_max_repairs(ROUTER_REPAIRS);
+        match structured.generate_reply(request, &router_decision_schema()).await {
+            Ok(reply) => {
+                let usage = self.record(run, router_id, &reply.usage);
+                let text = reply.value.to_string();
+                Ok((RouterDecision::from_reply(reply.value), text, usage))
+            }
+            Err(StructuredOutputError::Invalid { last_reply, usage, .. }) => {
+                let usage = self.record(run, router_id, &usage);
+                Ok((None, last_reply, usage))
+            }
+            Err(StructuredOutputError::Llm(e)) => Err(e.into()),
+            Err(e) => Err(OrchestrationError::Decision(e)),
+        }
+    }
+
+    /// Count a reply's tokens against the run and its agent
+    fn record(&self, run: &mut Run, agent_id: AgentId, usage: &TokenUsage) -> AgentUsage {
+        run.turns += 1;
+        let turn = AgentUsage {
+            agent_id,
+            turns: 1,
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+        };
+        match run.usage.iter_mut().find(|u| u.agent_id == agent_id) {
+            Some(total) => {
+                total.turns += 1;
+                total.prompt_tokens += turn.prompt_tokens;
+                total.completion_tokens += turn.completion_tokens;
+            }
+            None => run.usage.push(turn.clone()),
+        }
+        turn
+    }
+
+    async fn post_reply(
+        &self,
+        run: &mut Run,
+        agent_id: AgentId,
+        content: String,
+        usage: AgentUsage,
+        requested_by: Option<AgentId>,
+    ) -> OrchestrationResult<()> {
+        let model = run.agents[&agent_id].configuration.model.clone();
+        let mut message = Message::from_agent(run.conversation_id, agent_id, content, Some(model));
+        message.metadata.token_count = Some(usage.completion_tokens);
+        message.metadata.custom_fields.insert("orchestration".to_string(), serde_json::json!({
+            "pattern": run.pattern,
+            "turn": run.turns,
+            "prompt_tokens": usage.prompt_tokens,
+            "requested_by": requested_by,
+        }));
+        self.post(run, message).await
+    }
+
+    async fn post(&self, run: &mut Run, message: Message) -> OrchestrationResult<()> {
+        let message = self.conversations.add_message(run.conversation_id, message).await?;
+        if let Some(memory) = &self.memory {
+            memory.remember(&message, run.local_only).await;
+        }
+        run.history.push(message.clone());
+        run.added.push(message);
+        Ok(())
+    }
+}
+
+/// The text of a response's first choice
+fn reply_text(response: ChatCompletionResponse) -> String {
+    response.choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.message.content)
+        .unwrap_or_default()
+}
+
+/// An agent's system prompt with the run instructions and remembered context
+fn system_prompt(agent_prompt: &str, instructions: &str, history: &HistoryWindow) -> String {
+    let mut prompt = format!("{}\n\n{}", agent_prompt, instructions);
+    if let Some(summary) = &history.summary {
+        prompt.push_str("\n\nSummary of the earlier conversation:\n");
+        prompt.push_str(summary);
+    }
+    if !history.retrieved.is_empty() {
+        prompt.push_str("\n\nRelevant notes from earlier messages and twin documentation:");
+        for chunk in &history.retrieved {
+            prompt.push_str("\n- ");
+            prompt.push_str(chunk);
+        }
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::models::agent::{AgentState, MemoryType};
+    use crate::core::domain::models::conversation::{
+        Attachment, AttachmentType, Conversation, ConversationState, TWIN_IDS_FIELD,
+    };
+    use crate::core::application::services::memory_manager::MemoryConfig;
+    use crate::core::application::services::semantic_memory::{InMemoryVectorStore, LLMEmbedder, SemanticMemory};
+    use crate::core::domain::models::digital_twin::SENSITIVE_TAG;
+    use crate::core::domain::models::{DigitalTwin, TwinType};
+    use crate::core::domain::traits::mock_llm_client::MockLLMClient;
+    use crate::core::domain::traits::mock_repositories::InMemoryTwinRepository;
+    use crate::core::domain::traits::repository::{
+        FilterCriteria, PaginatedResult, Pagination, RepositoryResult, SortCriteria,
+    };
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct InMemoryConversations {
+        conversations: Mutex<HashMap<ConversationId, Conversation>>,
+    }
+
+    #[async_trait]
+    impl ConversationRepository for InMemoryConversations {
+        async fn create(&self, conversation: Conversation) -> RepositoryResult<Conversation> {
+            self.conversations.lock().unwrap().insert(conversation.id, conversation.clone());
+            Ok(conversation)
+        }
+
+        async fn get_by_id(&self, id: ConversationId) -> RepositoryResult<Conversation> {
+            self.conversations.lock().unwrap().get(&id).cloned().ok_or(RepositoryError::NotFound {
+                entity_type: "Conversation".to_string(),
+                id: id.to_string(),
+            })
+        }
+
+        async fn update(&self, conversation: Conversation) -> RepositoryResult<Conversation> {
+            self.create(conversation).await
+        }
+
+        async fn delete(&self, id: ConversationId) -> RepositoryResult<()> {
+            self.conversations.lock().unwrap().remove(&id);
+            Ok(())
+        }
+
+        async fn find(
+            &self,
+            _filters: Vec<FilterCriteria>,
+            _sort: Vec<SortCriteria>,
+            _pagination: Pagination,
+        ) -> RepositoryResult<PaginatedResult<Conversation>> {
+            unimplemented!()
+        }
+
+        async fn get_by_agent_id(
+            &self,
+            _agent_id: AgentId,
+            _pagination: Pagination,
+        ) -> RepositoryResult<PaginatedResult<Conversation>> {
+            unimplemented!()
+        }
+
+        async fn get_by_state(
+            &self,
+            _state: ConversationState,
+            _pagination: Pagination,
+        ) -> RepositoryResult<PaginatedResult<Conversation>> {
+            unimplemented!()
+        }
+
+        async fn add_message(&self, conversation_id: ConversationId, message: Message) -> RepositoryResult<Message> {
+            let mut conversations = self.conversations.lock().unwrap();
+            conversations.get_mut(&conversation_id).unwrap().add_message(message.clone());
+            Ok(message)
+        }
+
+        async fn get_messages(
+            &self,
+            _conversation_id: ConversationId,
+            _pagination: Pagination,
+        ) -> RepositoryResult<PaginatedResult<Message>> {
+            unimplemented!()
+        }
+
+        async fn search(
+            &self,
+            _query: &str,
+            _filters: Vec<FilterCriteria>,
+            _pagination: Pagination,
+        ) -> RepositoryResult<PaginatedResult<Conversation>> {
+            unimplemented!()
+        }
+    }
+
+    #[derive(Default)]
+    struct InMemoryAgents {
+        agents: Mutex<HashMap<AgentId, Agent>>,
+    }
+
+    #[async_trait]
+    impl AgentRepository for InMemoryAgents {
+        async fn create(&self, agent: Agent) -> RepositoryResult<Agent> {
+            self.agents.lock().unwrap().insert(agent.id, agent.clone());
+            Ok(agent)
+        }
+
+        async fn get_by_id(&self, id: AgentId) -> RepositoryResult<Agent> {
+            self.agents.lock().unwrap().get(&id).cloned().ok_or(RepositoryError::NotFound {
+                entity_type: "Agent".to_string(),
+                id: id.to_string(),
+            })
+        }
+
+        async fn update(&self, agent: Agent) -> RepositoryResult<Agent> {
+            self.create(agent).await
+        }
+
+        async fn delete(&self, id: AgentId) -> RepositoryResult<()> {
+            self.agents.lock().unwrap().remove(&id);
+            Ok(())
+        }
+
+        async fn find(
+            &self,
+            _filters: Vec<FilterCriteria>,
+            _sort: Vec<SortCriteria>,
+            _pagination: Pagination,
+        ) -> RepositoryResult<PaginatedResult<Agent>> {
+            unimplemented!()
+        }
+
+        async fn get_by_state(&self, _state: AgentState, _pagination: Pagination) -> RepositoryResult<PaginatedResult<Agent>> {
+            unimplemented!()
+        }
+
+        async fn get_by_capability(
+            &self,
+            _capability_type: &str,
+            _pagination: Pagination,
+        ) -> RepositoryResult<PaginatedResult<Agent>> {
+            unimplemented!()
+        }
+
+        async fn search(&self, _query: &str, _pagination: Pagination) -> RepositoryResult<PaginatedResult<Agent>> {
+            unimplemented!()
+        }
+
+        async fn update_state(&self, _id: AgentId, _state: AgentState) -> RepositoryResult<()> {
+            Ok(())
+        }
+    }
+
+    /// Replies by agent, taking the agent's name from its system prompt;
+    /// an agent's last reply repeats once its script runs out
+    fn scripted_agents(replies: Vec<(&'static str, Vec<&'static str>)>) -> Arc<MockLLMClient> {
+        let turns = Mutex::new(HashMap::new());
+        Arc::new(MockLLMClient::responding(move |request, _| {
+            let system = request.system.as_deref().unwrap_or_default();
+            let (name, script) = replies.iter()
+                .find(|(name, _)| system.starts_with(&format!("You are {}.", name)))
+                .unwrap();
+            let mut turns = turns.lock().unwrap();
+            let turn = turns.entry(*name).or_insert(0);
+            *turn += 1;
+            script[(*turn - 1).min(script.len() - 1)].to_string()
+        }).with_usage(100, 10))
+    }
+
+    struct Fixture {
+        orchestrator: AgentOrchestrator,
+        client: Arc<MockLLMClient>,
+        conversations: Arc<InMemoryConversations>,
+        agents: Arc<InMemoryAgents>,
+        conversation_id: ConversationId,
+        ids: Vec<AgentId>,
+    }
+
+    async fn fixture(names: &[&str], replies: Vec<(&'static str, Vec<&'static str>)>) -> Fixture {
+        let conversations = Arc::new(InMemoryConversations::default());
+        let agents = Arc::new(InMemoryAgents::default());
+        let mut ids = Vec::new();
+        for name in names {
+            let agent = Agent::new(name.to_string(), format!("{} agent", name), format!("You are {}.", name));
+            ids.push(agents.create(agent).await.unwrap().id);
+        }
+        let conversation = conversations
+            .create(Conversation::new("Chiller 2".to_string(), ids.clone()))
+            .await
+            .unwrap();
+        let client = scripted_agents(replies);
+
+        Fixture {
+            orchestrator: AgentOrchestrator::new(conversations.clone(), agents.clone(), client.clone()),
+            client,
+            conversations,
+            agents,
+            conversation_id: conversation.id,
+            ids,
+        }
+    }
+
+    fn question(conversation_id: ConversationId) -> Message {
+        Message::from_user(conversation_id, "operator".to_string(), "Chiller 2 trips at noon. Why?".to_string())
+    }
+
+    /// Renders an attachment as its name, counting the renders
+    #[derive(Default)]
+    struct NameRenderer {
+        renders: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl AttachmentRendering for NameRenderer {
+        async fn render_attachment(&self, attachment: &Attachment) -> Vec<ContentPart> {
+            *self.renders.lock().unwrap() += 1;
+            vec![ContentPart::Text { text: attachment.name.clone() }]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_router_delegates_then_responds() {
+        let f = fixture(&["Coordinator", "HVAC expert", "Maintenance planner"], vec![
+            ("Coordinator", vec![
+                r#"{"action": "delegate", "agent": "hvac expert", "instruction": "Diagnose the trips"}"#,
+                r#"{"action": "respond", "response": "The condenser fouls under peak load."}"#,
+            ]),
+            ("HVAC expert", vec!["High head pressure at peak load; the condenser is fouled."]),
+            ("Maintenance planner", vec!["unused"]),
+        ]).await;
+        let plan = OrchestrationPlan::Router { router_id: f.ids[0], specialist_ids: f.ids[1..].to_vec() };
+
+        let outcome = f.orchestrator.run(f.conversation_id, question(f.conversation_id), &plan).await.unwrap();
+        assert_eq!(outcome.stop_reason, StopReason::Completed);
+        assert_eq!(outcome.turns, 3);
+
+        let senders: Vec<_> = outcome.messages.iter().map(|m| m.sender.clone()).collect();
+        assert_eq!(senders, vec![
+            MessageSender::Agent { agent_id: f.ids[0] },
+            MessageSender::Agent { agent_id: f.ids[1] },
+            MessageSender::Agent { agent_id: f.ids[0] },
+        ]);
+        assert_eq!(outcome.messages[0].content, "Asking HVAC expert: Diagnose the trips");
+        assert_eq!(outcome.messages[2].content, "The condenser fouls under peak load.");
+        assert_eq!(outcome.messages[1].metadata.custom_fields["orchestration"]["requested_by"], serde_json::json!(f.ids[0]));
+
+        // Usage is attributed to each agent, in its requests and the outcome
+        assert_eq!(outcome.usage, vec![
+            AgentUsage { agent_id: f.ids[0], turns: 2, prompt_tokens: 200, completion_tokens: 20 },
+            AgentUsage { agent_id: f.ids[1], turns: 1, prompt_tokens: 100, completion_tokens: 10 },
+        ]);
+        let requests = f.client.requests();
+        assert_eq!(requests[1].conversation_context.as_ref().unwrap().agent_id, f.ids[1]);
+        assert!(requests[0].response_format.is_some());
+
+        // The specialist sees the coordinator's message attributed by name
+        let seen = requests[1].messages.last().unwrap();
+        assert_eq!(seen.role, MessageRole::User);
+        assert_eq!(seen.content.as_deref(), Some("[Coordinator]: Asking HVAC expert: Diagnose the trips"));
+
+        let stored = f.conversations.get_by_id(f.conversation_id).await.unwrap();
+        assert_eq!(stored.messages.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_router_prose_is_repaired_once_then_posted() {
+        let f = fixture(&["Coordinator", "HVAC expert"], vec![
+            ("Coordinator", vec!["The condenser is fouled."]),
+            ("HVAC expert", vec!["unused"]),
+        ]).await;
+        let plan = OrchestrationPlan::Router { router_id: f.ids[0], specialist_ids: vec![f.ids[1]] };
+
+        let outcome = f.orchestrator.run(f.conversation_id, question(f.conversation_id), &plan).await.unwrap();
+        assert_eq!(outcome.stop_reason, StopReason::Completed);
+        assert_eq!(outcome.turns, 1);
+        assert_eq!(outcome.messages[0].content, "The condenser is fouled.");
+
+        // Both attempts count against the router
+        assert_eq!(outcome.usage, vec![
+            AgentUsage { agent_id: f.ids[0], turns: 1, prompt_tokens: 200, completion_tokens: 20 },
+        ]);
+        let requests = f.client.requests();
+        assert_eq!(requests.len(), 2);
+        let repair = requests[1].messages.last().unwrap().content.as_deref().unwrap();
+        assert!(repair.starts_with("Your reply does not conform to the JSON Schema"));
+    }
+
+    #[test]
+    fn test_router_decision_schema_has_object_root() {
+        let schema = router_decision_schema();
+        assert_eq!(schema["type"], "object");
+        assert!(schema.get("oneOf").is_none());
+
+        assert_eq!(
+            RouterDecision::from_reply(serde_json::json!({"action": "delegate", "agent": "HVAC expert", "instruction": "Check"})),
+            Some(RouterDecision::Delegate { agent: "HVAC expert".to_string(), instruction: "Check".to_string() })
+        );
+        assert_eq!(
+            RouterDecision::from_reply(serde_json::json!({"action": "respond", "response": "Fouled."})),
+            Some(RouterDecision::Respond { response: "Fouled.".to_string() })
+        );
+        // The fields of the chosen action are required
+        assert_eq!(RouterDecision::from_reply(serde_json::json!({"action": "delegate", "agent": "HVAC expert"})), None);
+        assert_eq!(RouterDecision::from_reply(serde_json::json!({"action": "respond", "agent": "HVAC expert"})), None);
+        assert_eq!(RouterDecision::from_reply(serde_json::json!({"action": "escalate"})), None);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_specialist_note_is_sent_as_user_text() {
+        let f = fixture(&["Coordinator", "HVAC expert"], vec![
+            ("Coordinator", vec![
+                r#"{"action": "delegate", "agent": "Plumber", "instruction": "Check the pipes"}"#,
+                r#"{"action": "respond", "response": "The condenser is fouled."}"#,
+            ]),
+            ("HVAC expert", vec!["unused"]),
+        ]).await;
+        let plan = OrchestrationPlan::Router { router_id: f.ids[0], specialist_ids: vec![f.ids[1]] };
+
+        let outcome = f.orchestrator.run(f.conversation_id, question(f.conversation_id), &plan).await.unwrap();
+        assert_eq!(outcome.stop_reason, StopReason::Completed);
+        assert_eq!(outcome.messages[1].sender, MessageSender::System);
+
+        let requests = f.client.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().flat_map(|r| &r.messages).all(|m| m.role != MessageRole::System));
+        let note = requests[1].messages.last().unwrap();
+        assert_eq!(note.role, MessageRole::User);
+        assert_eq!(
+            note.content.as_deref(),
+            Some("[System]: There is no specialist named 'Plumber'. Choose one of: HVAC expert.")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_router_ping_pong_is_stopped() {
+        let f = fixture(&["Coordinator", "HVAC expert"], vec![
+            ("Coordinator", vec![r#"{"action": "delegate", "agent": "HVAC expert", "instruction": "Check again"}"#]),
+            ("HVAC expert", vec!["Still fouled."]),
+        ]).await;
+        let plan = OrchestrationPlan::Router { router_id: f.ids[0], specialist_ids: vec![f.ids[1]] };
+
+        let outcome = f.orchestrator.run(f.conversation_id, question(f.conversation_id), &plan).await.unwrap();
+        assert_eq!(outcome.stop_reason, StopReason::RepeatedDelegation);
+        assert_eq!(outcome.turns, 3);
+        assert_eq!(outcome.messages.last().unwrap().sender, MessageSender::System);
+
+        // Varying instructions still run into the turn limits
+        let f = fixture(&["Coordinator", "HVAC expert"], vec![
+            ("Coordinator", vec![
+                r#"{"action": "delegate", "agent": "HVAC expert", "instruction": "Check 1"}"#,
+                r#"{"action": "delegate", "agent": "HVAC expert", "instruction": "Check 2"}"#,
+                r#"{"action": "delegate", "agent": "HVAC expert", "instruction": "Check 3"}"#,
+            ]),
+            ("HVAC expert", vec!["Still fouled."]),
+        ]).await;
+        let orchestrator = f.orchestrator.with_limits(OrchestrationLimits { max_turns: 20, max_turns_per_agent: 2 });
+        let outcome = orchestrator.run(f.conversation_id, question(f.conversation_id), &plan).await.unwrap();
+        assert_eq!(outcome.stop_reason, StopReason::AgentTurnLimit);
+        assert_eq!(outcome.usage[1].turns, 2);
+    }
+
+    #[tokio::test]
+    async fn test_sequential_and_supervisor() {
+        let f = fixture(&["HVAC expert", "Maintenance planner", "Supervisor"], vec![
+            ("HVAC expert", vec!["The condenser is fouled."]),
+            ("Maintenance planner", vec!["Clean the condenser on Saturday."]),
+            ("Supervisor", vec!["Fouled condenser; cleaning is scheduled for Saturday."]),
+        ]).await;
+
+        let plan = OrchestrationPlan::Sequential { agent_ids: f.ids[..2].to_vec() };
+        let outcome = f.orchestrator.run(f.conversation_id, question(f.conversation_id), &plan).await.unwrap();
+        assert_eq!(outcome.messages.len(), 2);
+        {
+            // The planner builds on the expert's reply
+            let requests = f.client.requests();
+            assert_eq!(requests[1].messages.last().unwrap().content.as_deref(), Some("[HVAC expert]: The condenser is fouled."));
+            assert!(requests[0].system.as_deref().unwrap().contains("handed to Maintenance planner"));
+        }
+
+        let plan = OrchestrationPlan::Supervisor { supervisor_id: f.ids[2], specialist_ids: f.ids[..2].to_vec() };
+        let outcome = f.orchestrator.run(f.conversation_id, question(f.conversation_id), &plan).await.unwrap();
+        assert_eq!(outcome.stop_reason, StopReason::Completed);
+        assert_eq!(outcome.messages.last().unwrap().sender, MessageSender::Agent { agent_id: f.ids[2] });
+        assert_eq!(outcome.usage.len(), 3);
+
+        let outsider = Uuid::new_v4();
+        let plan = OrchestrationPlan::Sequential { agent_ids: vec![outsider] };
+        assert!(matches!(
+            f.orchestrator.run(f.conversation_id, question(f.conversation_id), &plan).await,
+            Err(OrchestrationError::NotParticipant(id)) if id == outsider
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sensitive_twins_pin_requests_to_local_providers() {
+        let f = fixture(&["HVAC expert"], vec![("HVAC expert", vec!["The condenser is fouled."])]).await;
+        let twins = Arc::new(InMemoryTwinRepository::default());
+        let mut twin = DigitalTwin::new(
+            "Chiller 2".to_string(),
+            "Rooftop chiller".to_string(),
+            TwinType::Device { device_type: "chiller".to_string(), manufacturer: None, model: None },
+        );
+        twin.metadata.tags.push(SENSITIVE_TAG.to_string());
+        let mut twin = twins.create(twin).await.unwrap();
+
+        // A deleted twin does not fail the run
+        let mut conversation = f.conversations.get_by_id(f.conversation_id).await.unwrap();
+        conversation.metadata.custom_fields.insert(
+            TWIN_IDS_FIELD.to_string(),
+            serde_json::json!([Uuid::new_v4(), twin.id]),
+        );
+        f.conversations.update(conversation).await.unwrap();
+
+        // Messages are indexed and retrieved through the same provider
+        let semantic_memory = Arc::new(SemanticMemory::new(
+            Arc::new(LLMEmbedder::new(f.client.clone(), "nomic-embed-text")),
+            Arc::new(InMemoryVectorStore::default()),
+        ));
+        let mut agent = f.agents.get_by_id(f.ids[0]).await.unwrap();
+        agent.memory_config.memory_type = MemoryType::RAG;
+        f.agents.update(agent).await.unwrap();
+        let memory = MemoryManager::new(MemoryConfig::default()).with_semantic_memory(semantic_memory);
+        let orchestrator = f.orchestrator.with_twins(twins.clone()).with_memory(memory);
+        let plan = OrchestrationPlan::Sequential { agent_ids: f.ids.clone() };
+        orchestrator.run(f.conversation_id, question(f.conversation_id), &plan).await.unwrap();
+        let sensitive_embeddings = f.client.embed_requests().len();
+
+        twin.metadata.tags.clear();
+        twins.update(twin).await.unwrap();
+        orchestrator.run(f.conversation_id, question(f.conversation_id), &plan).await.unwrap();
+
+        let requests = f.client.requests();
+        assert_eq!(requests[0].extra_params.get(LOCAL_ONLY_PARAM), Some(&serde_json::Value::Bool(true)));
+        assert!(requests[1].extra_params.is_empty());
+
+        // The question, its retrieval and the reply are embedded locally
+        let embeddings = f.client.embed_requests();
+        assert_eq!(sensitive_embeddings, 3);
+        for (index, request) in embeddings.iter().enumerate() {
+            let pinned = request.extra_params.get(LOCAL_ONLY_PARAM) == Some(&serde_json::Value::Bool(true));
+            assert_eq!(pinned, index < sensitive_embeddings);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_attachments_are_sent_as_parts() {
+        let f = fixture(&["HVAC expert", "Maintenance planner"], vec![
+            ("HVAC expert", vec!["The condenser is fouled."]),
+            ("Maintenance planner", vec!["Clean it on Saturday."]),
+        ]).await;
+        let renderer = Arc::new(NameRenderer::default());
+        let orchestrator = f.orchestrator.with_attachments(renderer.clone());
+
+        let mut message = question(f.conversation_id);
+        message.attachments.push(Attachment {
+            id: Uuid::new_v4(),
+            attachment_type: AttachmentType::File,
+            name: "trip-log.csv".to_string(),
+            size: None,
+            mime_type: None,
+            url: Some("trip-log.csv".to_string()),
+        });
+        let plan = OrchestrationPlan::Sequential { agent_ids: f.ids.clone() };
+        orchestrator.run(f.conversation_id, message, &plan).await.unwrap();
+
+        // Rendered once, then sent to every agent
+        assert_eq!(*renderer.renders.lock().unwrap(), 1);
+        let requests = f.client.requests();
+        for request in requests.iter() {
+            let question = request.messages.iter().find(|m| m.parts.is_some()).unwrap();
+            assert_eq!(question.parts, Some(vec![ContentPart::Text { text: "trip-log.csv".to_string() }]));
+        }
+    }
+}
@@ -0,0 +1,557 @@
+//! Execution governor for tool calls
+//!
+//! Applies the limits configured on a [`Tool`] around each execution:
+//! rate limits per tool (and optionally per agent), a concurrency cap
+//! with a bounded wait queue, a per-attempt timeout and retries using the
+//! configured [`BackoffStrategy`]. Every attempt is recorded in the
+//! returned [`ExecutionMetrics`].
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+
+use crate::core::domain::errors::{
+    BusinessRuleError, DomainError, ResourceConstraintError, ValidationError,
+};
+use crate::core::domain::models::{AgentId, ToolId};
+use crate::core::domain::models::tool::{
+    BackoffStrategy, ExecutionMetrics, RateLimits, RetryConfig, Tool,
+};
+
+/// Window used for the burst allowance
+const BURST_WINDOW: Duration = Duration::from_secs(1);
+
+/// Rate limit windows checked for each key
+const WINDOWS: [(&str, Duration); 3] = [
+    ("minute", Duration::from_secs(60)),
+    ("hour", Duration::from_secs(3600)),
+    ("day", Duration::from_secs(86400)),
+];
+
+/// Key for rate limit history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RateKey {
+    Tool(ToolId),
+    Agent(AgentId),
+}
+
+/// Concurrency state of one tool
+struct ToolSlots {
+    max_concurrent: u32,
+    semaphore: Arc<Semaphore>,
+    waiting: Arc<AtomicU32>,
+}
+
+/// Failure of a single attempt
+#[derive(Debug)]
+enum AttemptError {
+    /// The attempt exceeded the tool's timeout
+    TimedOut,
+
+    /// The attempt returned an error
+    Failed(String),
+}
+
+impl std::fmt::Display for AttemptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttemptError::TimedOut => write!(f, "timeout"),
+            AttemptError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Result of a governed execution
+#[derive(Debug)]
+pub struct GovernedExecution<T> {
+    /// Value returned by the successful attempt
+    pub value: T,
+
+    /// Timing of all attempts
+    pub metrics: ExecutionMetrics,
+}
+
+/// Enforces rate limits, concurrency, timeouts and retries for tools
+pub struct ExecutionGovernor {
+    /// Limits applied to each agent across all tools
+    agent_limits: Option<RateLimits>,
+
+    /// Start times of recent executions (sliding log)
+    history: Mutex<HashMap<RateKey, VecDeque<Instant>>>,
+
+    /// Concurrency slots per tool
+    slots: Mutex<HashMap<ToolId, ToolSlots>>,
+}
+
+impl Default for ExecutionGovernor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecutionGovernor {
+    /// Create a governor that applies the limits configured on each tool
+    pub fn new() -> Self {
+        Self {
+            agent_limits: None,
+            history: Mutex::new(HashMap::new()),
+            slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Also limit how often each agent may execute tools
+    pub fn with_agent_limits(mut self, limits: RateLimits) -> Self {
+        self.agent_limits = Some(limits);
+        self
+    }
+
+    /// Run `attempt` under the tool's limits, retrying failed attempts
+    pub async fn execute<T, E, F, Fut>(
+        &self,
+        tool: &Tool,
+        agent_id: Option<AgentId>,
+        mut attempt: F,
+    ) -> Result<GovernedExecution<T>, DomainError>
+    where
+        E: Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let timeout = attempt_timeout(tool)?;
+        self.check_rate_limits(tool, agent_id)?;
+
+        let started = Instant::now();
+        let _permit = self.acquire_slot(tool).await?;
+
+        let mut metrics = ExecutionMetrics::default();
+        metrics.custom_metrics.insert(
+            "queue_wait_ms".to_string(),
+            started.elapsed().as_millis() as f64,
+        );
+
+        let retry = &tool.execution_config.retry;
+        let mut attempt_number = 0;
+
+        loop {
+            attempt_number += 1;
+            let attempt_started = Instant::now();
+
+            let outcome = match timeout {
+                None => attempt().await.map_err(|e| AttemptError::Failed(e.to_string())),
+                Some(timeout) => match tokio::time::timeout(timeout, attempt()).await {
+                    Ok(result) => result.map_err(|e| AttemptError::Failed(e.to_string())),
+                    Err(_) => Err(AttemptError::TimedOut),
+                },
+            };
+
+            metrics.custom_metrics.insert(
+                format!("attempt_{}_ms", attempt_number),
+                attempt_started.elapsed().as_millis() as f64,
+            );
+            metrics.custom_metrics.insert("attempts".to_string(), attempt_number as f64);
+            metrics.retry_count = attempt_number - 1;
+            metrics.duration_ms = started.elapsed().as_millis() as u64;
+
+            let error = match outcome {
+                Ok(value) => return Ok(GovernedExecution { value, metrics }),
+                Err(error) => error,
+            };
+
+            if attempt_number > retry.max_attempts || !is_retryable(retry, &error) {
+                tracing::warn!(
+                    "Tool '{}' failed after {} attempt(s): {}",
+                    tool.name, attempt_number, error
+                );
+                return Err(match error {
+                    AttemptError::TimedOut => DomainError::Timeout {
+                        duration: timeout.unwrap_or_default(),
+                    },
+                    AttemptError::Failed(error) => DomainError::ExternalService {
+                        service: tool.name.clone(),
+                        message: format!("failed after {} attempt(s): {}", attempt_number, error),
+                    },
+                });
+            }
+
+            let delay = backoff_delay(&retry.backoff_strategy, attempt_number);
+            tracing::debug!(
+                "Retrying tool '{}' in {:?} (attempt {} failed: {})",
+                tool.name, delay, attempt_number, error
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Reject output larger than the tool's `max_output_size`
+    pub fn check_output_size(&self, tool: &Tool, size: u64) -> Result<(), DomainError> {
+        match tool.execution_config.resource_limits.max_output_size {
+            Some(limit) if size > limit => Err(DomainError::ResourceConstraint(
+                ResourceConstraintError::StorageLimitExceeded { used: size, limit },
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check and record the execution against tool and agent limits
+    fn check_rate_limits(&self, tool: &Tool, agent_id: Option<AgentId>) -> Result<(), DomainError> {
+        let now = Instant::now();
+        let mut history = self.history.lock().unwrap();
+
+        let mut keys = vec![(RateKey::Tool(tool.id), &tool.security.rate_limits)];
+        if let (Some(agent_id), Some(limits)) = (agent_id, self.agent_limits.as_ref()) {
+            keys.push((RateKey::Agent(agent_id), limits));
+        }
+
+        // Check every key before recording so a rejection consumes nothing
+        for (key, limits) in &keys {
+            let log = history.entry(*key).or_default();
+            prune(log, now);
+            if let Some((limit, period)) = exceeded_limit(limits, log, now) {
+                return Err(DomainError::BusinessRule(BusinessRuleError::ToolExecutionLimitReached {
+                    tool_id: tool.id,
+                    limit,
+                    period: match key {
+                        RateKey::Tool(_) => period.to_string(),
+                        RateKey::Agent(agent_id) => format!("{} for agent {}", period, agent_id),
+                    },
+                }));
+            }
+        }
+
+        for (key, _) in &keys {
+            history.entry(*key).or_default().push_back(now);
+        }
+
+        Ok(())
+    }
+
+    /// Wait for a concurrency slot, rejecting when the queue is full
+    async fn acquire_slot(&self, tool: &Tool) -> Result<Option<tokio::sync::OwnedSemaphorePermit>, DomainError> {
+        let concurrency = &tool.execution_config.concurrency;
+        if concurrency.max_concurrent == 0 {
+            return Ok(None);
+        }
+
+        let (semaphore, waiting) = {
+            let mut slots = self.slots.lock().unwrap();
+            let entry = slots.entry(tool.id).or_insert_with(|| ToolSlots {
+                max_concurrent: concurrency.max_concurrent,
+                semaphore: Arc::new(Semaphore::new(concurrency.max_concurrent as usize)),
+                waiting: Arc::new(AtomicU32::new(0)),
+            });
+
+            // Configuration changed; executions holding old permits finish normally
+            if entry.max_concurrent != concurrency.max_concurrent {
+                entry.max_concurrent = concurrency.max_concurrent;
+                entry.semaphore = Arc::new(Semaphore::new(concurrency.max_concurrent as usize));
+            }
+
+            (entry.semaphore.clone(), entry.waiting.clone())
+        };
+
+        if let Ok(permit) = semaphore.clone().try_acquire_owned() {
+            return Ok(Some(permit));
+        }
+
+        let queued = waiting.fetch_add(1, Ordering::SeqCst);
+        if queued >= concurrency.queue_size {
+            waiting.fetch_sub(1, Ordering::SeqCst);
+            return Err(DomainError::ResourceConstraint(ResourceConstraintError::QueueFull {
+                queue: tool.name.clone(),
+                size: queued,
+                capacity: concurrency.queue_size,
+            }));
+        }
+
+        let permit = semaphore.acquire_owned().await;
+        waiting.fetch_sub(1, Ordering::SeqCst);
+
+        permit
+            .map(Some)
+            .map_err(|_| DomainError::Other(format!("Execution slots for tool '{}' were closed", tool.name)))
+    }
+}
+
+/// Per-attempt timeout of a tool; `None` when a zero timeout disables it
+fn attempt_timeout(tool: &Tool) -> Result<Option<Duration>, DomainError> {
+    let timeout = tool.execution_config.timeout.to_std().map_err(|_| {
+        ValidationError::OutOfRange {
+            field: "execution_config.timeout".to_string(),
+            min: "0s".to_string(),
+            max: "unbounded".to_string(),
+            actual: tool.execution_config.timeout.to_string(),
+        }
+    })?;
+    Ok((!timeout.is_zero()).then_some(timeout))
+}
+
+/// Drop entries older than the longest window
+fn prune(log: &mut VecDeque<Instant>, now: Instant) {
+    let longest = WINDOWS[WINDOWS.len() - 1].1;
+    while log.front().is_some_and(|t| now.duration_since(*t) >= longest) {
+        log.pop_front();
+    }
+}
+
+/// First limit that one more execution would exceed
+fn exceeded_limit(limits: &RateLimits, log: &VecDeque<Instant>, now: Instant) -> Option<(u32, &'static str)> {
+    let count_within = |window: Duration| {
+        log.iter().rev().take_while(|t| now.duration_since(**t) < window).count() as u32
+    };
+
+    if let Some(burst) = limits.burst_size {
+        if count_within(BURST_WINDOW) >= burst {
+            return Some((burst, "second"));
+        }
+    }
+
+    let configured = [limits.per_minute, limits.per_hour, limits.per_day];
+    WINDOWS.iter()
+        .zip(configured)
+        .find_map(|((period, window), limit)| match limit {
+            Some(limit) if count_within(*window) >= limit => Some((limit, *period)),
+            _ => None,
+        })
+}
+
+/// Whether a failed attempt may be retried
+///
+/// Only errors listed in `retryable_errors` are retried, since a tool may
+/// not be safe to run twice. A timeout matches a `timeout` entry; other
+/// errors must contain one of the entries.
+fn is_retryable(retry: &RetryConfig, error: &AttemptError) -> bool {
+    let error = match error {
+        AttemptError::TimedOut => "timeout".to_string(),
+        AttemptError::Failed(message) => message.to_lowercase(),
+    };
+    retry.retryable_errors.iter().any(|pattern| error.contains(&pattern.to_lowercase()))
+}
+
+/// Delay before the retry following the given failed attempt (1-based)
+pub fn backoff_delay(strategy: &BackoffStrategy, failed_attempt: u32) -> Duration {
+    let n = failed_attempt.saturating_sub(1);
+    let ms = match *strategy {
+        BackoffStrategy::Fixed { delay_ms } => delay_ms,
+        BackoffStrategy::Exponential { initial_ms, multiplier, max_ms } => {
+            let delay = initial_ms as f64 * (multiplier as f64).powi(n as i32);
+            delay.min(max_ms as f64) as u64
+        }
+        BackoffStrategy::Linear { initial_ms, increment_ms } => {
+            initial_ms.saturating_add(increment_ms.saturating_mul(n as u64))
+        }
+    };
+    Duration::from_millis(ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::models::tool::ToolType;
+    use std::sync::atomic::AtomicUsize;
+
+    fn tool() -> Tool {
+        let mut tool = Tool::new(
+            "governed".to_string(),
+            "Governed tool".to_string(),
+            ToolType::Custom {
+                category: "test".to_string(),
+                capabilities: HashMap::new(),
+            },
+        );
+        tool.execution_config.timeout = chrono::Duration::seconds(5);
+        tool.execution_config.retry = RetryConfig {
+            max_attempts: 0,
+            backoff_strategy: BackoffStrategy::Fixed { delay_ms: 10 },
+            retryable_errors: Vec::new(),
+        };
+        tool.execution_config.concurrency.max_concurrent = 0;
+        tool.security.rate_limits = RateLimits {
+            per_minute: None,
+            per_hour: None,
+            per_day: None,
+            burst_size: None,
+        };
+        tool
+    }
+
+    #[test]
+    fn test_backoff_strategies() {
+        let exponential = BackoffStrategy::Exponential { initial_ms: 100, multiplier: 2.0, max_ms: 500 };
+        assert_eq!(backoff_delay(&exponential, 1), Duration::from_millis(100));
+        assert_eq!(backoff_delay(&exponential, 3), Duration::from_millis(400));
+        assert_eq!(backoff_delay(&exponential, 4), Duration::from_millis(500));
+
+        let linear = BackoffStrategy::Linear { initial_ms: 100, increment_ms: 50 };
+        assert_eq!(backoff_delay(&linear, 3), Duration::from_millis(200));
+        assert_eq!(backoff_delay(&BackoffStrategy::Fixed { delay_ms: 30 }, 7), Duration::from_millis(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries_record_attempts() {
+        let governor = ExecutionGovernor::new();
+        let mut tool = tool();
+        tool.execution_config.retry.max_attempts = 3;
+        tool.execution_config.retry.retryable_errors = vec!["connection reset".to_string()];
+
+        let calls = AtomicUsize::new(0);
+        let result = governor.execute(&tool, None, || {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if call < 2 { Err("Connection reset by peer") } else { Ok(call) }
+            }
+        }).await.unwrap();
+
+        assert_eq!(result.value, 2);
+        assert_eq!(result.metrics.retry_count, 2);
+        assert_eq!(result.metrics.custom_metrics["attempts"], 3.0);
+        assert!(result.metrics.custom_metrics.contains_key("attempt_3_ms"));
+
+        // Errors that don't match are not retried
+        calls.store(0, Ordering::SeqCst);
+        let error = governor.execute(&tool, None, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err::<(), _>("invalid register") }
+        }).await.unwrap_err();
+        assert!(matches!(error, DomainError::ExternalService { .. }));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_attempt_timeout() {
+        let governor = ExecutionGovernor::new();
+        let mut tool = tool();
+        tool.execution_config.timeout = chrono::Duration::milliseconds(100);
+        tool.execution_config.retry.max_attempts = 1;
+
+        let error = governor.execute(&tool, None, || async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok::<_, String>(())
+        }).await.unwrap_err();
+
+        assert!(matches!(error, DomainError::Timeout { .. }));
+
+        // Timeouts are retried only when listed
+        let calls = AtomicUsize::new(0);
+        tool.execution_config.retry.retryable_errors = vec!["TIMEOUT".to_string()];
+        let error = governor.execute(&tool, None, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Ok::<_, String>(())
+            }
+        }).await.unwrap_err();
+        assert!(matches!(error, DomainError::Timeout { duration } if duration == Duration::from_millis(100)));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_retry_without_retryable_errors() {
+        let governor = ExecutionGovernor::new();
+        let mut tool = tool();
+        tool.execution_config.retry.max_attempts = 3;
+
+        let calls = AtomicUsize::new(0);
+        let error = governor.execute(&tool, None, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err::<(), _>("connection reset") }
+        }).await.unwrap_err();
+
+        assert!(matches!(error, DomainError::ExternalService { .. }));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_negative_timeout_is_rejected() {
+        let governor = ExecutionGovernor::new();
+        let mut tool = tool();
+        tool.execution_config.timeout = chrono::Duration::seconds(-1);
+
+        let calls = AtomicUsize::new(0);
+        let error = governor.execute(&tool, None, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Ok::<_, String>(()) }
+        }).await.unwrap_err();
+
+        assert!(matches!(error, DomainError::Validation(_)));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limits_per_tool_and_agent() {
+        let governor = ExecutionGovernor::new().with_agent_limits(RateLimits {
+            per_minute: Some(1),
+            per_hour: None,
+            per_day: None,
+            burst_size: None,
+        });
+        let mut tool = tool();
+        tool.security.rate_limits.per_minute = Some(3);
+        tool.security.rate_limits.burst_size = Some(2);
+        let run = |agent| governor.execute(&tool, agent, || async { Ok::<_, String>(()) });
+
+        assert!(run(None).await.is_ok());
+        assert!(run(None).await.is_ok());
+        // Burst of two per second
+        assert!(matches!(run(None).await, Err(DomainError::BusinessRule(_))));
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let agent = uuid::Uuid::new_v4();
+        assert!(run(Some(agent)).await.is_ok());
+
+        // The agent has used its one call per minute
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(matches!(run(Some(agent)).await, Err(DomainError::BusinessRule(_))));
+
+        // The tool has used its three calls per minute
+        assert!(matches!(run(None).await, Err(DomainError::BusinessRule(_))));
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert!(run(None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_cap_and_queue() {
+        let governor = Arc::new(ExecutionGovernor::new());
+        let mut tool = tool();
+        tool.execution_config.concurrency.max_concurrent = 1;
+        tool.execution_config.concurrency.queue_size = 1;
+        let tool = Arc::new(tool);
+
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let released = Arc::new(tokio::sync::Mutex::new(Some(released)));
+
+        let running = {
+            let (governor, tool, released) = (governor.clone(), tool.clone(), released.clone());
+            tokio::spawn(async move {
+                governor.execute(&tool, None, || {
+                    let released = released.clone();
+                    async move {
+                        let receiver = released.lock().await.take().unwrap();
+                        receiver.await.map_err(|e| e.to_string())
+                    }
+                }).await
+            })
+        };
+        tokio::task::yield_now().await;
+
+        let queued = {
+            let (governor, tool) = (governor.clone(), tool.clone());
+            tokio::spawn(async move {
+                governor.execute(&tool, None, || async { Ok::<_, String>(()) }).await
+            })
+        };
+        tokio::task::yield_now().await;
+
+        // One running, one queued: the next is rejected
+        let rejected = governor.execute(&tool, None, || async { Ok::<_, String>(()) }).await;
+        assert!(matches!(rejected, Err(DomainError::ResourceConstraint(_))));
+
+        release.send(()).unwrap();
+        assert!(running.await.unwrap().is_ok());
+        assert!(queued.await.unwrap().is_ok());
+    }
+}
@@ -3,14 +3,35 @@
 //! This service manages conversation memory with support for:
 //! - Context windowing with token counting
 //! - Sliding window memory strategy
+//! - Rolling summarization of older messages
+//! - Retrieval of relevant chunks from semantic memory
 //! - Token estimation for different models
 
-use crate::core::domain::models::conversation::{Message, MessageSender};
+use super::semantic_memory::SemanticMemory;
+use crate::core::domain::models::agent::{Agent, MemoryConfiguration, MemoryType};
+use crate::core::domain::models::conversation::{Attachment, Message, MessageSender};
+use crate::core::domain::services::tokenizer::TokenCounter;
+pub use crate::core::domain::services::tokenizer::TokenModel;
+use crate::core::domain::traits::llm_client::{
+    ChatCompletionRequest, ChatMessage, LLMClient, MessageRole, LOCAL_ONLY_PARAM,
+};
+use crate::core::domain::traits::summary_store::{ConversationSummary, SummaryStore};
+use crate::core::domain::traits::vector_store::{ChunkSource, MemoryScope};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
+/// Instructions given to the model when folding messages into a summary
+const SUMMARY_PROMPT: &str = "You maintain the running summary of a conversation. \
+Merge the previous summary with the new messages into a single concise summary. \
+Keep facts, decisions, open questions and any values the user provided. \
+Reply with the summary only.";
+
+/// Share of the context budget available to retrieved chunks (1/n)
+const RETRIEVAL_SHARE: u32 = 4;
+
 /// Configuration for memory management
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryConfig {
@@ -22,6 +43,20 @@ pub struct MemoryConfig {
     pub strategy: MemoryStrategy,
     /// Token estimation model
     pub token_model: TokenModel,
+    /// Maximum tokens of a conversation summary
+    #[serde(default = "default_summary_max_tokens")]
+    pub summary_max_tokens: u32,
+    /// Whether older messages may be folded into summaries
+    #[serde(default = "default_compression_enabled")]
+    pub compression_enabled: bool,
+}
+
+fn default_summary_max_tokens() -> u32 {
+    512
+}
+
+fn default_compression_enabled() -> bool {
+    true
 }
 
 /// Memory management strategies
@@ -37,17 +72,6 @@ pub enum MemoryStrategy {
     Hybrid,
 }
 
-/// Token estimation models
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
-pub enum TokenModel {
-    /// OpenAI GPT models (cl100k_base encoding)
-    GPT,
-    /// Claude models
-    Claude,
-    /// Generic estimation (4 chars per token)
-    Generic,
-}
-
 /// Represents a message with token metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContextMessage {
@@ -62,77 +86,132 @@ pub struct ContextMessage {
 /// Context window result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContextWindow {
+    /// Summary of the messages before the window, if any
+    #[serde(default)]
+    pub summary: Option<ConversationSummary>,
+    /// Chunks retrieved from semantic memory, best match first
+    #[serde(default)]
+    pub retrieved: Vec<RetrievedContext>,
     pub messages: Vec<ContextMessage>,
+    /// Tokens of the messages, the summary and the retrieved chunks
     pub total_tokens: u32,
     pub window_start_index: usize,
     pub window_end_index: usize,
 }
 
+/// Text retrieved from semantic memory for the context window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrievedContext {
+    pub source: ChunkSource,
+    pub source_id: Uuid,
+    pub content: String,
+    /// Similarity to the query
+    pub score: f32,
+    pub token_count: u32,
+}
+
 /// Memory manager for handling conversation context
 pub struct MemoryManager {
     config: MemoryConfig,
+    summarizer: Option<(Arc<dyn LLMClient>, String)>,
+    summary_store: Option<Arc<dyn SummaryStore>>,
+    semantic_memory: Option<Arc<SemanticMemory>>,
 }
 
 impl MemoryManager {
     /// Creates a new memory manager with the given configuration
     pub fn new(config: MemoryConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            summarizer: None,
+            summary_store: None,
+            semantic_memory: None,
+        }
     }
 
-    /// Estimates token count for a given text
-    pub fn estimate_tokens(&self, text: &str) -> u32 {
-        match self.config.token_model {
-            TokenModel::GPT => self.estimate_gpt_tokens(text),
-            TokenModel::Claude => self.estimate_claude_tokens(text),
-            TokenModel::Generic => self.estimate_generic_tokens(text),
+    /// Summarize with `client` using `model`, normally the agent's own
+    pub fn with_summarizer(mut self, client: Arc<dyn LLMClient>, model: impl Into<String>) -> Self {
+        self.summarizer = Some((client, model.into()));
+        self
+    }
+
+    /// Persist summaries so later turns can reuse them
+    pub fn with_summary_store(mut self, store: Arc<dyn SummaryStore>) -> Self {
+        self.summary_store = Some(store);
+        self
+    }
+
+    /// Retrieve from `memory` under the RAG strategy
+    pub fn with_semantic_memory(mut self, memory: Arc<SemanticMemory>) -> Self {
+        self.semantic_memory = Some(memory);
+        self
+    }
+
+    /// The memory of `agent`'s turns
+    ///
+    /// The agent's memory configuration picks the strategy and limits, and
+    /// its model writes the summaries. The message limit, summary size and
+    /// compression setting of this manager still apply.
+    pub fn for_agent(&self, agent: &Agent) -> Self {
+        let mut config = MemoryConfig::from(&agent.memory_config);
+        config.max_messages = config.max_messages.min(self.config.max_messages);
+        config.token_model = TokenModel::for_model(&agent.configuration.model);
+        config.summary_max_tokens = self.config.summary_max_tokens;
+        config.compression_enabled = self.config.compression_enabled;
+
+        Self {
+            config,
+            summarizer: self.summarizer
+                .as_ref()
+                .map(|(client, _)| (client.clone(), agent.configuration.model.clone())),
+            summary_store: self.summary_store.clone(),
+            semantic_memory: self.semantic_memory.clone(),
         }
     }
 
-    /// Estimates tokens using GPT tokenization (cl100k_base)
-    fn estimate_gpt_tokens(&self, text: &str) -> u32 {
-        // Rough estimation: ~4 characters per token for GPT models
-        // More accurate would require actual tokenizer
-        let char_count = text.len() as f32;
-        ((char_count / 4.0).ceil()) as u32
+    /// Counts tokens of a given text
+    pub fn estimate_tokens(&self, text: &str) -> u32 {
+        self.counter().count(text)
     }
 
-    /// Estimates tokens using Claude tokenization
-    fn estimate_claude_tokens(&self, text: &str) -> u32 {
-        // Claude uses similar tokenization to GPT
-        // Rough estimation: ~3.5 characters per token
-        let char_count = text.len() as f32;
-        ((char_count / 3.5).ceil()) as u32
+    /// Counts tokens of a message including its chat format overhead
+    pub fn message_tokens(&self, message: &Message) -> u32 {
+        self.counter().count_message(&message.content)
     }
 
-    /// Generic token estimation
-    fn estimate_generic_tokens(&self, text: &str) -> u32 {
-        // Generic: ~4 characters per token
-        let char_count = text.len() as f32;
-        ((char_count / 4.0).ceil()) as u32
+    fn counter(&self) -> TokenCounter {
+        TokenCounter::new(self.config.token_model)
     }
 
-    /// Builds a context window from messages using sliding window strategy
+    /// Builds a context window from messages outside of a conversation
+    ///
+    /// Summaries and retrieval belong to a conversation, so here the
+    /// summarization and RAG strategies keep a sliding window; use
+    /// [`Self::build_conversation_context`] to apply them.
     pub fn build_context_window(&self, messages: &[Message]) -> ContextWindow {
         match self.config.strategy {
-            MemoryStrategy::SlidingWindow => self.sliding_window_strategy(messages),
-            MemoryStrategy::Summarization => self.summarization_strategy(messages),
-            MemoryStrategy::RAG => self.rag_strategy(messages),
             MemoryStrategy::Hybrid => self.hybrid_strategy(messages),
+            _ => self.sliding_window_strategy(messages),
         }
     }
 
     /// Sliding window strategy: keep most recent messages within token limit
     fn sliding_window_strategy(&self, messages: &[Message]) -> ContextWindow {
+        self.window(messages, 0, self.config.max_context_tokens, self.config.max_messages)
+    }
+
+    /// Keep the most recent of `messages[offset..]` within the given limits
+    fn window(&self, messages: &[Message], offset: usize, max_tokens: u32, max_messages: usize) -> ContextWindow {
         let mut context_messages = Vec::new();
         let mut total_tokens = 0u32;
-        let mut window_start_index = 0;
+        let mut window_start_index = offset;
 
         // Process messages in reverse order (most recent first)
-        for (idx, message) in messages.iter().enumerate().rev() {
-            let token_count = self.estimate_tokens(&message.content);
+        for (idx, message) in messages.iter().enumerate().skip(offset).rev() {
+            let token_count = self.message_tokens(message);
 
             // Check if adding this message would exceed token limit
-            if total_tokens + token_count > self.config.max_context_tokens
+            if total_tokens + token_count > max_tokens
                 && !context_messages.is_empty()
             {
                 window_start_index = idx + 1;
@@ -140,25 +219,12 @@ impl MemoryManager {
             }
 
             // Check message count limit
-            if context_messages.len() >= self.config.max_messages {
+            if context_messages.len() >= max_messages {
                 window_start_index = idx + 1;
                 break;
             }
 
-            let sender = match &message.sender {
-                MessageSender::User { user_id } => format!("user:{}", user_id),
-                MessageSender::Agent { agent_id } => format!("agent:{}", agent_id),
-                MessageSender::System => "system".to_string(),
-            };
-
-            context_messages.push(ContextMessage {
-                id: message.id,
-                sender,
-                content: message.content.clone(),
-                token_count,
-                created_at: message.created_at,
-                model: message.metadata.model.clone(),
-            });
+            context_messages.push(self.context_message(message, token_count));
 
             total_tokens += token_count;
         }
@@ -168,6 +234,8 @@ impl MemoryManager {
         let window_end_index = messages.len();
 
         ContextWindow {
+            summary: None,
+            retrieved: Vec::new(),
             messages: context_messages,
             total_tokens,
             window_start_index,
@@ -175,18 +243,301 @@ impl MemoryManager {
         }
     }
 
-    /// Summarization strategy: summarize older messages to save tokens
-    fn summarization_strategy(&self, messages: &[Message]) -> ContextWindow {
-        // For now, fall back to sliding window
-        // In production, this would call a summarization service
-        self.sliding_window_strategy(messages)
+    fn context_message(&self, message: &Message, token_count: u32) -> ContextMessage {
+        ContextMessage {
+            id: message.id,
+            sender: sender_label(&message.sender),
+            content: message.content.clone(),
+            token_count,
+            created_at: message.created_at,
+            model: message.metadata.model.clone(),
+        }
     }
 
-    /// RAG strategy: retrieve relevant messages based on similarity
-    fn rag_strategy(&self, messages: &[Message]) -> ContextWindow {
-        // For now, fall back to sliding window
-        // In production, this would use semantic search
-        self.sliding_window_strategy(messages)
+    /// Builds the context window of a conversation with its strategy
+    ///
+    /// The summarization strategy folds older messages into a rolling
+    /// summary and the RAG strategy adds chunks of the conversation and of
+    /// the documentation of `twin_ids` retrieved for the latest message;
+    /// the others build the same window as [`Self::build_context_window`].
+    /// With `local_only` summaries and query embeddings are only requested
+    /// from local providers.
+    pub async fn build_conversation_context(
+        &self,
+        conversation_id: Uuid,
+        twin_ids: &[Uuid],
+        messages: &[Message],
+        local_only: bool,
+    ) -> ContextWindow {
+        match self.config.strategy {
+            MemoryStrategy::Summarization => {
+                self.summarization_strategy(conversation_id, messages, local_only).await
+            }
+            MemoryStrategy::RAG => self.rag_strategy(conversation_id, twin_ids, messages, local_only).await,
+            _ => self.build_context_window(messages),
+        }
+    }
+
+    /// Index a stored message so later turns can retrieve it
+    ///
+    /// Does nothing without semantic memory; failures are logged, as a
+    /// message that is not indexed is only missing from retrieval. With
+    /// `local_only` the message is only embedded by local providers.
+    pub async fn remember(&self, message: &Message, local_only: bool) {
+        if let Some(memory) = &self.semantic_memory {
+            if let Err(e) = memory.index_message(message, local_only).await {
+                tracing::warn!("Failed to index message {}: {}", message.id, e);
+            }
+        }
+    }
+
+    /// Index the text extracted from an attachment of a conversation
+    ///
+    /// Like [`Self::remember`], failures are only logged.
+    pub async fn remember_attachment(
+        &self,
+        conversation_id: Uuid,
+        attachment: &Attachment,
+        text: &str,
+        local_only: bool,
+    ) {
+        if let Some(memory) = &self.semantic_memory {
+            if let Err(e) = memory.index_attachment(conversation_id, attachment, text, local_only).await {
+                tracing::warn!("Failed to index attachment {}: {}", attachment.id, e);
+            }
+        }
+    }
+
+    /// Summarization strategy: fold messages that no longer fit into a summary
+    ///
+    /// The latest stored summary is reused, and only messages after it are
+    /// summarized, together with its text. When a fold is needed the recent
+    /// tail is cut to half of the space left beside the summary, so the
+    /// following turns fit without another call. If summarizing fails the
+    /// window falls back to the stored summary plus a sliding window. With
+    /// compression disabled only the sliding window applies.
+    async fn summarization_strategy(
+        &self,
+        conversation_id: Uuid,
+        messages: &[Message],
+        local_only: bool,
+    ) -> ContextWindow {
+        if !self.config.compression_enabled {
+            return self.sliding_window_strategy(messages);
+        }
+
+        let previous = match &self.summary_store {
+            Some(store) => store.latest_summary(conversation_id).await.unwrap_or_else(|e| {
+                tracing::warn!("Failed to load summary of conversation {}: {}", conversation_id, e);
+                None
+            }),
+            None => None,
+        };
+
+        // A summary whose last message is gone no longer matches the history
+        let (previous, covered_end) = match previous.and_then(|summary| {
+            messages.iter()
+                .position(|m| m.id == summary.through_message_id)
+                .map(|idx| (summary, idx + 1))
+        }) {
+            Some((summary, end)) => (Some(summary), end),
+            None => (None, 0),
+        };
+
+        let summary_tokens = previous.as_ref().map_or(0, |s| s.token_count);
+        let pending = &messages[covered_end..];
+        let pending_tokens: u32 = pending.iter().map(|m| self.message_tokens(m)).sum();
+
+        if summary_tokens + pending_tokens <= self.config.max_context_tokens
+            && pending.len() <= self.config.max_messages
+        {
+            return self.with_summary(messages, covered_end, previous);
+        }
+
+        let tail_budget = self.config.max_context_tokens.saturating_sub(self.config.summary_max_tokens) / 2;
+        let tail = self.window(messages, covered_end, tail_budget, (self.config.max_messages / 2).max(1));
+        let split = tail.window_start_index;
+
+        if split > covered_end {
+            let summary = self
+                .summarize(conversation_id, messages, previous.as_ref(), covered_end, split, local_only)
+                .await;
+            if let Some(summary) = summary {
+                return self.with_summary(messages, split, Some(summary));
+            }
+        }
+
+        self.with_summary(messages, covered_end, previous)
+    }
+
+    /// Builds a context window with chunks relevant to the latest message
+    ///
+    /// Searches the conversation and the documentation of `twin_ids`.
+    /// Retrieved chunks use at most a quarter of the token budget and skip
+    /// messages already in the window. If retrieval fails only the sliding
+    /// window is used. With `local_only` the query is only embedded by local
+    /// providers.
+    pub async fn build_retrieval_context(
+        &self,
+        conversation_id: Uuid,
+        twin_ids: &[Uuid],
+        messages: &[Message],
+        local_only: bool,
+    ) -> ContextWindow {
+        let Some(memory) = &self.semantic_memory else {
+            return self.sliding_window_strategy(messages);
+        };
+
+        let query = messages.last().map_or("", |m| m.content.as_str());
+        let scopes: Vec<MemoryScope> = std::iter::once(MemoryScope::Conversation(conversation_id))
+            .chain(twin_ids.iter().map(|id| MemoryScope::Twin(*id)))
+            .collect();
+        let chunks = memory.retrieve(query, &scopes, local_only).await.unwrap_or_else(|e| {
+            tracing::warn!("Failed to retrieve memory for conversation {}: {}", conversation_id, e);
+            Vec::new()
+        });
+
+        let reserve = self.config.max_context_tokens / RETRIEVAL_SHARE;
+        let max_messages = self.config.max_messages;
+        let window = self.window(messages, 0, self.config.max_context_tokens - reserve, max_messages);
+
+        let mut retrieved = Vec::new();
+        let mut retrieved_tokens = 0;
+        for scored in chunks {
+            if window.messages.iter().any(|m| m.id == scored.chunk.source_id) {
+                continue;
+            }
+            let token_count = self.counter().count_message(&scored.chunk.content);
+            if retrieved_tokens + token_count > reserve {
+                continue;
+            }
+            retrieved_tokens += token_count;
+            retrieved.push(RetrievedContext {
+                source: scored.chunk.source,
+                source_id: scored.chunk.source_id,
+                content: scored.chunk.content,
+                score: scored.score,
+                token_count,
+            });
+        }
+
+        // Give unused retrieval space back to the recent messages
+        let mut window = self.window(messages, 0, self.config.max_context_tokens - retrieved_tokens, max_messages);
+        retrieved.retain(|r| !window.messages.iter().any(|m| m.id == r.source_id));
+        window.total_tokens += retrieved.iter().map(|r| r.token_count).sum::<u32>();
+        window.retrieved = retrieved;
+        window
+    }
+
+    /// A window of `messages[start..]` below `summary`, within the limits
+    fn with_summary(&self, messages: &[Message], start: usize, summary: Option<ConversationSummary>) -> ContextWindow {
+        let summary_tokens = summary.as_ref().map_or(0, |s| s.token_count);
+        let mut window = self.window(
+            messages,
+            start,
+            self.config.max_context_tokens.saturating_sub(summary_tokens),
+            self.config.max_messages,
+        );
+        window.total_tokens += summary_tokens;
+        window.summary = summary;
+        window
+    }
+
+    /// Fold `messages[start..split]` into a new summary and store it
+    ///
+    /// `start` is where `previous` ends in `messages`, which is before its
+    /// stored range end once earlier messages were deleted.
+    async fn summarize(
+        &self,
+        conversation_id: Uuid,
+        messages: &[Message],
+        previous: Option<&ConversationSummary>,
+        start: usize,
+        split: usize,
+        local_only: bool,
+    ) -> Option<ConversationSummary> {
+        let (client, model) = self.summarizer.as_ref()?;
+
+        let mut prompt = String::new();
+        if let Some(previous) = previous {
+            prompt.push_str("Previous summary:\n");
+            prompt.push_str(&previous.content);
+            prompt.push_str("\n\n");
+        }
+        prompt.push_str("New messages:\n");
+        for message in &messages[start..split] {
+            prompt.push_str(&format!("{}: {}\n", sender_label(&message.sender), message.content));
+        }
+
+        let request = ChatCompletionRequest {
+            request_id: Uuid::new_v4().to_string(),
+            messages: vec![ChatMessage {
+                role: MessageRole::User,
+                content: Some(prompt),
+                name: None,
+                tool_call_id: None,
+                tool_calls: None,
+                parts: None,
+            }],
+            model: model.clone(),
+            temperature: Some(0.0),
+            max_tokens: Some(self.config.summary_max_tokens),
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop_sequences: None,
+            n: None,
+            system: Some(SUMMARY_PROMPT.to_string()),
+            tools: None,
+            tool_choice: None,
+            response_format: None,
+            user_id: None,
+            conversation_context: None,
+            extra_params: if local_only {
+                HashMap::from([(LOCAL_ONLY_PARAM.to_string(), serde_json::Value::Bool(true))])
+            } else {
+                HashMap::new()
+            },
+        };
+
+        let content = match client.chat_complete(request).await {
+            Ok(response) => response.choices.into_iter().next().and_then(|choice| choice.message.content),
+            Err(e) => {
+                tracing::warn!("Failed to summarize conversation {}: {}", conversation_id, e);
+                return None;
+            }
+        };
+        let content = content.filter(|c| !c.trim().is_empty())?;
+
+        let summary = ConversationSummary {
+            id: Uuid::new_v4(),
+            conversation_id,
+            through_message_id: messages[split - 1].id,
+            range_start: previous.map_or(0, |s| s.range_start),
+            range_end: split,
+            token_count: self.counter().count_message(&content),
+            content,
+            created_at: Utc::now(),
+        };
+
+        if let Some(store) = &self.summary_store {
+            if let Err(e) = store.save_summary(&summary).await {
+                tracing::warn!("Failed to store summary of conversation {}: {}", conversation_id, e);
+            }
+        }
+
+        Some(summary)
+    }
+
+    /// RAG strategy: add chunks relevant to the latest message
+    async fn rag_strategy(
+        &self,
+        conversation_id: Uuid,
+        twin_ids: &[Uuid],
+        messages: &[Message],
+        local_only: bool,
+    ) -> ContextWindow {
+        self.build_retrieval_context(conversation_id, twin_ids, messages, local_only).await
     }
 
     /// Hybrid strategy: combine multiple approaches
@@ -203,7 +554,7 @@ impl MemoryManager {
         let mut model_counts = HashMap::new();
 
         for message in messages {
-            let token_count = self.estimate_tokens(&message.content);
+            let token_count = self.message_tokens(message);
             total_tokens += token_count;
 
             let sender = match &message.sender {
@@ -247,6 +598,14 @@ impl MemoryManager {
     }
 }
 
+fn sender_label(sender: &MessageSender) -> String {
+    match sender {
+        MessageSender::User { user_id } => format!("user:{}", user_id),
+        MessageSender::Agent { agent_id } => format!("agent:{}", agent_id),
+        MessageSender::System => "system".to_string(),
+    }
+}
+
 /// Token statistics for a set of messages
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenStats {
@@ -264,6 +623,24 @@ impl Default for MemoryConfig {
             max_messages: 50,
             strategy: MemoryStrategy::SlidingWindow,
             token_model: TokenModel::GPT,
+            summary_max_tokens: default_summary_max_tokens(),
+            compression_enabled: default_compression_enabled(),
+        }
+    }
+}
+
+impl From<&MemoryConfiguration> for MemoryConfig {
+    fn from(memory: &MemoryConfiguration) -> Self {
+        Self {
+            max_context_tokens: memory.max_context_tokens,
+            max_messages: memory.max_context_messages,
+            strategy: match memory.memory_type {
+                MemoryType::SlidingWindow => MemoryStrategy::SlidingWindow,
+                MemoryType::Summarization => MemoryStrategy::Summarization,
+                MemoryType::RAG => MemoryStrategy::RAG,
+                MemoryType::Hybrid => MemoryStrategy::Hybrid,
+            },
+            ..Default::default()
         }
     }
 }
@@ -271,6 +648,53 @@ impl Default for MemoryConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::domain::traits::llm_client::LLMError;
+    use crate::core::domain::traits::mock_llm_client::MockLLMClient;
+    use crate::core::domain::traits::repository::RepositoryResult;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    fn summarizer() -> Arc<MockLLMClient> {
+        Arc::new(MockLLMClient::replying(vec!["pump P-101 ok"]))
+    }
+
+    #[derive(Default)]
+    struct MemorySummaryStore {
+        summaries: Mutex<Vec<ConversationSummary>>,
+    }
+
+    #[async_trait]
+    impl SummaryStore for MemorySummaryStore {
+        async fn latest_summary(&self, conversation_id: Uuid) -> RepositoryResult<Option<ConversationSummary>> {
+            Ok(self.summaries.lock().unwrap().iter()
+                .rev()
+                .find(|s| s.conversation_id == conversation_id)
+                .cloned())
+        }
+
+        async fn save_summary(&self, summary: &ConversationSummary) -> RepositoryResult<()> {
+            self.summaries.lock().unwrap().push(summary.clone());
+            Ok(())
+        }
+    }
+
+    fn summarizing_config() -> MemoryConfig {
+        MemoryConfig {
+            max_context_tokens: 100,
+            strategy: MemoryStrategy::Summarization,
+            token_model: TokenModel::Generic,
+            summary_max_tokens: 20,
+            ..Default::default()
+        }
+    }
+
+    /// Messages of 10 tokens each, format overhead included
+    fn push_messages(conversation_id: Uuid, messages: &mut Vec<Message>, count: usize) {
+        for _ in 0..count {
+            let content = format!("reading {:02} a b c d", messages.len());
+            messages.push(Message::from_user(conversation_id, "alice".to_string(), content));
+        }
+    }
 
     #[test]
     fn test_token_estimation() {
@@ -290,9 +714,10 @@ mod tests {
         };
         let manager = MemoryManager::new(config);
 
-        let text = "a".repeat(100);
-        let tokens = manager.estimate_gpt_tokens(&text);
-        assert_eq!(tokens, 25); // 100 / 4 = 25
+        assert_eq!(manager.estimate_tokens("hello world"), 2);
+
+        let message = Message::from_user(Uuid::new_v4(), "alice".to_string(), "hello world".to_string());
+        assert_eq!(manager.message_tokens(&message), 2 + crate::core::domain::services::tokenizer::MESSAGE_OVERHEAD_TOKENS);
     }
 
     #[test]
@@ -320,4 +745,188 @@ mod tests {
         assert!(manager.would_exceed_limit(500, 600));
         assert!(manager.would_exceed_limit(1000, 1));
     }
+
+    #[tokio::test]
+    async fn test_summaries_are_reused_and_extended() {
+        let llm = summarizer();
+        let store = Arc::new(MemorySummaryStore::default());
+        let manager = MemoryManager::new(summarizing_config())
+            .with_summarizer(llm.clone(), "gpt-4")
+            .with_summary_store(store.clone());
+        let conversation_id = Uuid::new_v4();
+        let mut messages = Vec::new();
+
+        // Fits: no summary
+        push_messages(conversation_id, &mut messages, 8);
+        let window = manager.build_conversation_context(conversation_id, &[], &messages, false).await;
+        assert!(window.summary.is_none());
+        assert_eq!(window.messages.len(), 8);
+
+        // Overflow folds all but the recent tail
+        push_messages(conversation_id, &mut messages, 4);
+        let window = manager.build_conversation_context(conversation_id, &[], &messages, false).await;
+        let summary = window.summary.clone().unwrap();
+        assert_eq!((summary.range_start, summary.range_end), (0, 8));
+        assert_eq!(summary.through_message_id, messages[7].id);
+        assert_eq!(window.window_start_index, 8);
+        assert!(window.total_tokens <= 100);
+        assert_eq!(llm.calls(), 1);
+
+        // Later turns reuse the stored summary
+        push_messages(conversation_id, &mut messages, 2);
+        let window = manager.build_conversation_context(conversation_id, &[], &messages, false).await;
+        assert_eq!(window.summary.unwrap().id, summary.id);
+        assert_eq!(window.messages.len(), 6);
+        assert_eq!(llm.calls(), 1);
+
+        // The next fold only sends the previous summary and the new span,
+        // here pinned to local providers
+        push_messages(conversation_id, &mut messages, 5);
+        let window = manager.build_conversation_context(conversation_id, &[], &messages, true).await;
+        let extended = window.summary.unwrap();
+        assert_eq!((extended.range_start, extended.range_end), (0, 15));
+
+        let prompts: Vec<String> = llm.requests().into_iter()
+            .map(|request| request.messages[0].content.clone().unwrap_or_default())
+            .collect();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains("Previous summary:\npump P-101 ok"));
+        assert!(prompts[1].contains("reading 08"));
+        assert!(!prompts[1].contains("reading 07"));
+        let pinned: Vec<bool> = llm.requests().iter()
+            .map(|request| request.extra_params.contains_key(LOCAL_ONLY_PARAM))
+            .collect();
+        assert_eq!(pinned, vec![false, true]);
+        assert_eq!(store.summaries.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_summary_extends_after_earlier_history_is_deleted() {
+        let llm = summarizer();
+        let store = Arc::new(MemorySummaryStore::default());
+        let manager = MemoryManager::new(summarizing_config())
+            .with_summarizer(llm.clone(), "gpt-4")
+            .with_summary_store(store.clone());
+        let conversation_id = Uuid::new_v4();
+        let mut messages = Vec::new();
+        push_messages(conversation_id, &mut messages, 12);
+        let window = manager.build_conversation_context(conversation_id, &[], &messages, false).await;
+        assert_eq!(window.summary.unwrap().range_end, 8);
+
+        // The summary now ends at index 1, before its stored range end
+        messages.drain(..7);
+        push_messages(conversation_id, &mut messages, 6);
+        let window = manager.build_conversation_context(conversation_id, &[], &messages, false).await;
+        let extended = window.summary.unwrap();
+        assert_eq!(extended.range_end, 7);
+        assert_eq!(extended.through_message_id, messages[6].id);
+        assert_eq!(window.window_start_index, 7);
+
+        let prompt = llm.requests()[1].messages[0].content.clone().unwrap();
+        assert!(prompt.contains("reading 08"));
+        assert!(!prompt.contains("reading 07"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_summarization_falls_back_to_window() {
+        let llm = Arc::new(
+            MockLLMClient::replying(vec!["pump P-101 ok"])
+                .with_errors(vec![LLMError::NetworkError("offline".to_string())])
+        );
+        let store = Arc::new(MemorySummaryStore::default());
+        let manager = MemoryManager::new(summarizing_config())
+            .with_summarizer(llm.clone(), "gpt-4")
+            .with_summary_store(store.clone());
+        let conversation_id = Uuid::new_v4();
+        let mut messages = Vec::new();
+        push_messages(conversation_id, &mut messages, 12);
+
+        let window = manager.build_conversation_context(conversation_id, &[], &messages, false).await;
+        assert!(window.summary.is_none());
+        assert_eq!(window.messages.len(), 10);
+        assert_eq!(window.window_start_index, 2);
+        assert_eq!(llm.calls(), 1);
+        assert!(store.summaries.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_compression_keeps_sliding_window() {
+        let llm = summarizer();
+        let store = Arc::new(MemorySummaryStore::default());
+        let manager = MemoryManager::new(MemoryConfig {
+            compression_enabled: false,
+            ..summarizing_config()
+        })
+        .with_summarizer(llm.clone(), "gpt-4")
+        .with_summary_store(store.clone());
+        let conversation_id = Uuid::new_v4();
+        let mut messages = Vec::new();
+        push_messages(conversation_id, &mut messages, 12);
+
+        let window = manager.build_conversation_context(conversation_id, &[], &messages, false).await;
+        assert!(window.summary.is_none());
+        assert_eq!(window.window_start_index, 2);
+        assert_eq!(llm.calls(), 0);
+        assert!(store.summaries.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_agent_memory_configuration_applies() {
+        let llm = summarizer();
+        let manager = MemoryManager::new(MemoryConfig { summary_max_tokens: 20, ..Default::default() })
+            .with_summarizer(llm.clone(), "gpt-4");
+        let mut agent = Agent::new("Analyst".to_string(), "Trend analysis".to_string(), "You analyse trends.".to_string());
+        agent.configuration.model = "gpt-4o-mini".to_string();
+        agent.memory_config.memory_type = MemoryType::Summarization;
+        agent.memory_config.max_context_tokens = 100;
+        let conversation_id = Uuid::new_v4();
+        let mut messages = Vec::new();
+        push_messages(conversation_id, &mut messages, 30);
+
+        let window = manager.for_agent(&agent).build_conversation_context(conversation_id, &[], &messages, false).await;
+        assert!(window.summary.is_some());
+        assert!(window.total_tokens <= 100);
+        assert_eq!(llm.requests()[0].model, "gpt-4o-mini");
+
+        // A sliding window agent is never summarized
+        agent.memory_config.memory_type = MemoryType::SlidingWindow;
+        let window = manager.for_agent(&agent).build_conversation_context(conversation_id, &[], &messages, false).await;
+        assert!(window.summary.is_none());
+        assert_eq!(llm.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_adds_relevant_history() {
+        use crate::core::application::services::semantic_memory::{HashEmbedder, InMemoryVectorStore};
+        use crate::core::domain::traits::vector_store::RagConfig;
+
+        let memory = Arc::new(
+            SemanticMemory::new(Arc::new(HashEmbedder::default()), Arc::new(InMemoryVectorStore::default()))
+                .with_config(RagConfig { similarity_threshold: 0.3, ..Default::default() })
+        );
+        let manager = MemoryManager::new(MemoryConfig {
+            strategy: MemoryStrategy::RAG,
+            ..summarizing_config()
+        })
+        .with_semantic_memory(memory.clone());
+
+        let conversation_id = Uuid::new_v4();
+        let mut messages = vec![Message::from_user(
+            conversation_id,
+            "alice".to_string(),
+            "The chiller setpoint is 6 degrees".to_string(),
+        )];
+        push_messages(conversation_id, &mut messages, 12);
+        messages.push(Message::from_user(conversation_id, "alice".to_string(), "what is the chiller setpoint".to_string()));
+        for message in &messages {
+            manager.remember(message, false).await;
+        }
+
+        let window = manager.build_conversation_context(conversation_id, &[], &messages, false).await;
+        assert_eq!(window.retrieved.len(), 1);
+        assert_eq!(window.retrieved[0].source_id, messages[0].id);
+        assert!(window.messages.iter().all(|m| m.id != messages[0].id));
+        assert_eq!(window.messages.last().unwrap().id, messages[13].id);
+        assert!(window.total_tokens <= 100);
+    }
 }
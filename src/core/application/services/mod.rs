@@ -8,8 +8,15 @@ pub mod agent_service;
 pub mod twin_service;
 pub mod simulation_service;
 pub mod tool_service;
+pub mod execution_governor;
 pub mod memory_manager;
+pub mod semantic_memory;
+pub mod response_cache;
+pub mod usage_accounting;
+pub mod structured_output;
+pub mod agent_orchestrator;
 pub mod prompt_manager;
+pub mod prompt_template;
 
 // Re-export services for convenient access
 pub use conversation_service::ConversationService;
@@ -24,10 +31,41 @@ pub use tool_service::{
     ToolService, ToolRegistration, ToolExecutionRequest,
     ToolValidationResult
 };
+pub use execution_governor::{ExecutionGovernor, GovernedExecution};
 pub use memory_manager::{
     MemoryManager, MemoryConfig, MemoryStrategy, TokenModel,
-    ContextWindow, TokenStats
+    ContextWindow, TokenStats, RetrievedContext
+};
+pub use crate::core::domain::traits::summary_store::{ConversationSummary, SummaryStore};
+pub use semantic_memory::{
+    SemanticMemory, InMemoryVectorStore, Embedder, LLMEmbedder, HashEmbedder
+};
+pub use crate::core::domain::traits::vector_store::{
+    RagConfig, MemoryScope, MemoryChunk, ScoredChunk, VectorStore
+};
+pub use crate::core::domain::services::TokenCounter;
+pub use response_cache::{CachingClient, CacheStats};
+pub use crate::core::domain::traits::response_cache_store::{
+    ResponseCacheConfig, ResponseCacheStore, CachedResponse
+};
+pub use usage_accounting::{UsageAccountant, AgentCost, MeteredClient};
+pub use crate::core::domain::traits::usage_store::{
+    UsageStore, UsageRecord, UsageFilter, PricingEntry, AgentBudget, BudgetAction
+};
+pub use structured_output::{
+    StructuredOutput, StructuredOutputError, StructuredOutputResult, StructuredReply
+};
+pub use agent_orchestrator::{
+    AgentOrchestrator, OrchestrationPlan, OrchestrationLimits, OrchestrationOutcome,
+    OrchestrationError, OrchestrationResult, AgentUsage, StopReason
 };
 pub use prompt_manager::{
     PromptManager, VersionedPrompt, PromptChange, PromptDiff
-};
\ No newline at end of file
+};
+pub use prompt_template::{
+    PromptTemplates, RenderedPrompt, PromptTemplateError, PromptTemplateResult, TwinContext, SensorSnapshot,
+    SYSTEM_PROMPT_TEMPLATE
+};
+pub use crate::core::domain::traits::prompt_template_store::{
+    PromptTemplate, TemplateVariable, VariableType, PromptTemplateStore
+};
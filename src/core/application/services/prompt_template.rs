@@ -0,0 +1,1109 @@
+//! Prompt templates with typed variables, partials and twin context.
+//!
+//! A [`PromptTemplate`] body is plain text with tags:
+//!
+//! - `{{name}}` or `{{site.name}}` inserts a variable or a field of one
+//! - `{{#if name}}…{{else}}…{{/if}}` renders a branch on a truthy variable
+//! - `{{#each items}}…{{this}}…{{this.field}}…{{@index}}…{{/each}}` repeats
+//!   a block for each item of a list
+//! - `{{> name}}` includes another template as a partial
+//! - `{{! note}}` is a comment and renders nothing
+//! - `{{twin_name}}`, `{{twin_properties}}`, `{{twin_readings}}` and
+//!   `{{twin_anomalies}}` insert live state of the twin the prompt is
+//!   rendered for; the last three take `max_tokens=N` to bound their size
+//!
+//! Templates are checked when saved: the body must parse, every variable
+//! it uses must be declared, defaults must match their declared types and
+//! partials must exist without including each other in a cycle.
+
+use crate::core::domain::models::{DigitalTwin, SensorReading, SensorValue, TwinId};
+use crate::core::domain::services::tokenizer::{TokenCounter, TokenModel};
+use crate::core::domain::traits::prompt_template_store::{
+    PromptTemplate, PromptTemplateStore, TemplateVariable, VariableType,
+};
+use crate::core::domain::traits::repository::{
+    Pagination, RepositoryError, SensorDataRepository, TwinRepository,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Token budget of a twin helper without `max_tokens`
+pub const DEFAULT_HELPER_TOKENS: u32 = 300;
+
+/// Name of the template an agent's system prompt is rendered from
+pub const SYSTEM_PROMPT_TEMPLATE: &str = "system_prompt";
+
+/// Body of the system prompt template used until one is saved
+const DEFAULT_SYSTEM_PROMPT: &str =
+    "{{instructions}}{{#if context}}\n\nContext: {{context}}{{/if}}";
+
+/// Partials nested deeper than this are treated as a cycle
+const MAX_PARTIAL_DEPTH: usize = 8;
+
+/// Sensors of a twin whose latest reading is rendered by `twin_readings`
+const MAX_SENSORS: usize = 50;
+
+/// Tokens kept free for the line noting omitted entries
+const OMISSION_TOKENS: u32 = 8;
+
+const TWIN_HELPERS: &[&str] = &["twin_name", "twin_properties", "twin_readings", "twin_anomalies"];
+
+/// Errors raised by prompt templates
+#[derive(Debug, Error)]
+pub enum PromptTemplateError {
+    #[error("Syntax error on line {line}: {message}")]
+    Syntax { line: usize, message: String },
+
+    #[error("Invalid template: {0}")]
+    Invalid(String),
+
+    #[error("Template uses undeclared variable '{0}'")]
+    UndeclaredVariable(String),
+
+    #[error("Invalid value for variable '{name}': {message}")]
+    InvalidVariable { name: String, message: String },
+
+    #[error("Template '{0}' not found")]
+    NotFound(String),
+
+    #[error("Partial '{0}' not found")]
+    UnknownPartial(String),
+
+    #[error("Partials include each other: {0}")]
+    PartialCycle(String),
+
+    #[error("Template '{name}' is included by '{used_by}'")]
+    InUse { name: String, used_by: String },
+
+    #[error("Template uses twin helpers but no twin was given")]
+    MissingTwin,
+
+    #[error("Repository error: {0}")]
+    Repository(#[from] RepositoryError),
+}
+
+pub type PromptTemplateResult<T> = Result<T, PromptTemplateError>;
+
+/// A rendered prompt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedPrompt {
+    pub text: String,
+    /// Token count of `text`
+    pub tokens: u32,
+    /// Partials included while rendering, in order of first use
+    pub partials: Vec<String>,
+}
+
+/// Live twin state available to the twin helpers
+#[derive(Debug, Clone)]
+pub struct TwinContext {
+    pub twin: DigitalTwin,
+    /// Latest reading of each sensor attached to the twin
+    pub readings: Vec<SensorSnapshot>,
+}
+
+/// Latest reading of one sensor
+#[derive(Debug, Clone)]
+pub struct SensorSnapshot {
+    pub sensor_id: String,
+    pub name: String,
+    pub reading: SensorReading,
+}
+
+/// Validates, stores and renders prompt templates
+pub struct PromptTemplates {
+    store: Arc<dyn PromptTemplateStore>,
+    twins: Option<(Arc<dyn TwinRepository>, Arc<dyn SensorDataRepository>)>,
+    token_model: TokenModel,
+}
+
+impl PromptTemplates {
+    pub fn new(store: Arc<dyn PromptTemplateStore>) -> Self {
+        Self {
+            store,
+            twins: None,
+            token_model: TokenModel::Generic,
+        }
+    }
+
+    /// Load twin state for the twin helpers from these repositories
+    pub fn with_twin_context(
+        mut self,
+        twins: Arc<dyn TwinRepository>,
+        sensor_data: Arc<dyn SensorDataRepository>,
+    ) -> Self {
+        self.twins = Some((twins, sensor_data));
+        self
+    }
+
+    /// Count tokens for budgets and rendered prompts with this model
+    pub fn with_token_model(mut self, token_model: TokenModel) -> Self {
+        self.token_model = token_model;
+        self
+    }
+
+    pub async fn get(&self, name: &str) -> PromptTemplateResult<PromptTemplate> {
+        self.store
+            .get(name)
+            .await?
+            .ok_or_else(|| PromptTemplateError::NotFound(name.to_string()))
+    }
+
+    pub async fn list(&self) -> PromptTemplateResult<Vec<PromptTemplate>> {
+        Ok(self.store.list().await?)
+    }
+
+    /// Validate and store a template, bumping its version
+    pub async fn save(&self, mut template: PromptTemplate) -> PromptTemplateResult<PromptTemplate> {
+        validate_declarations(&template)?;
+        let nodes = parse(&template.body)?;
+        let declared: HashSet<&str> = template.variables.iter().map(|v| v.name.as_str()).collect();
+        check_variables(&nodes, &declared, false)?;
+        self.check_partials(&template.name, &nodes).await?;
+
+        let now = Utc::now();
+        match self.store.get(&template.name).await? {
+            Some(existing) => {
+                template.version = existing.version + 1;
+                template.created_at = existing.created_at;
+            }
+            None => {
+                template.version = 1;
+                template.created_at = now;
+            }
+        }
+        template.updated_at = now;
+
+        self.store.save(&template).await?;
+        Ok(template)
+    }
+
+    /// Delete a template that no other template includes
+    pub async fn delete(&self, name: &str) -> PromptTemplateResult<()> {
+        for other in self.store.list().await? {
+            if other.name == name {
+                continue;
+            }
+            let mut partials = Vec::new();
+            if let Ok(nodes) = parse(&other.body) {
+                collect_partials(&nodes, &mut partials);
+            }
+            if partials.iter().any(|partial| partial == name) {
+                return Err(PromptTemplateError::InUse {
+                    name: name.to_string(),
+                    used_by: other.name,
+                });
+            }
+        }
+
+        if self.store.delete(name).await? {
+            Ok(())
+        } else {
+            Err(PromptTemplateError::NotFound(name.to_string()))
+        }
+    }
+
+    /// Render a stored template
+    pub async fn render(
+        &self,
+        name: &str,
+        variables: &HashMap<String, Value>,
+        twin_id: Option<TwinId>,
+    ) -> PromptTemplateResult<RenderedPrompt> {
+        let template = self.get(name).await?;
+        self.preview(&template, variables, twin_id).await
+    }
+
+    /// Render a template, saved or not, for a twin
+    ///
+    /// Partials are rendered from their stored versions.
+    pub async fn preview(
+        &self,
+        template: &PromptTemplate,
+        variables: &HashMap<String, Value>,
+        twin_id: Option<TwinId>,
+    ) -> PromptTemplateResult<RenderedPrompt> {
+        let nodes = parse(&template.body)?;
+        let values = resolve_variables(&template.variables, variables)?;
+        let partials = self.load_partials(&template.name, &nodes).await?;
+
+        let mut twin_helpers = uses_twin_helpers(&nodes);
+        for (_, partial_nodes) in partials.values() {
+            twin_helpers |= uses_twin_helpers(partial_nodes);
+        }
+        let twin = match (twin_helpers, twin_id) {
+            (true, Some(twin_id)) => Some(self.twin_context(twin_id).await?),
+            (true, None) => return Err(PromptTemplateError::MissingTwin),
+            (false, _) => None,
+        };
+
+        let counter = TokenCounter::new(self.token_model);
+        let renderer = Renderer {
+            partials: &partials,
+            twin: twin.as_ref(),
+            counter: &counter,
+        };
+        let mut text = String::new();
+        let mut used = Vec::new();
+        renderer.render(&nodes, &values, &mut Vec::new(), &mut text, &mut used, 0)?;
+
+        Ok(RenderedPrompt {
+            tokens: counter.count(&text),
+            text,
+            partials: used,
+        })
+    }
+
+    /// Render an agent's system prompt from [`SYSTEM_PROMPT_TEMPLATE`]
+    ///
+    /// `instructions` and `context` are passed to the template if it
+    /// declares them. Falls back to [`PromptTemplates::default_system_prompt`]
+    /// until a template is saved under that name.
+    pub async fn render_system_prompt(
+        &self,
+        instructions: &str,
+        context: Option<&str>,
+        twin_id: Option<TwinId>,
+    ) -> PromptTemplateResult<RenderedPrompt> {
+        let template = match self.store.get(SYSTEM_PROMPT_TEMPLATE).await? {
+            Some(template) => template,
+            None => Self::default_system_prompt(),
+        };
+
+        let mut variables = HashMap::new();
+        variables.insert("instructions".to_string(), Value::from(instructions));
+        if let Some(context) = context {
+            variables.insert("context".to_string(), Value::from(context));
+        }
+        variables.retain(|name, _| template.variables.iter().any(|v| &v.name == name));
+
+        self.preview(&template, &variables, twin_id).await
+    }
+
+    /// System prompt template: the agent instructions, then the conversation context
+    pub fn default_system_prompt() -> PromptTemplate {
+        let mut template = PromptTemplate::new(SYSTEM_PROMPT_TEMPLATE, DEFAULT_SYSTEM_PROMPT);
+        template.description = "System prompt of an agent".to_string();
+        template
+            .with_variable(TemplateVariable {
+                name: "instructions".to_string(),
+                var_type: VariableType::String,
+                required: false,
+                default: None,
+                description: Some("Instructions of the agent".to_string()),
+            })
+            .with_variable(TemplateVariable {
+                name: "context".to_string(),
+                var_type: VariableType::String,
+                required: false,
+                default: None,
+                description: Some("Context of the conversation".to_string()),
+            })
+    }
+
+    /// Load the state of a twin for the twin helpers
+    pub async fn twin_context(&self, twin_id: TwinId) -> PromptTemplateResult<TwinContext> {
+        let (twins, sensor_data) = self.twins.as_ref().ok_or(PromptTemplateError::MissingTwin)?;
+        let twin = twins.get_by_id(twin_id).await?;
+
+        let sensors = sensor_data
+            .get_by_twin_id(twin_id, Pagination { offset: 0, limit: MAX_SENSORS })
+            .await?;
+        let mut readings = Vec::new();
+        for sensor in sensors.items {
+            if let Some(reading) = sensor_data.get_latest_reading(sensor.id).await? {
+                readings.push(SensorSnapshot {
+                    sensor_id: sensor.sensor.sensor_id,
+                    name: sensor.sensor.name,
+                    reading,
+                });
+            }
+        }
+
+        Ok(TwinContext { twin, readings })
+    }
+
+    /// Check that the partials of a template being saved exist and do not
+    /// include it again
+    async fn check_partials(&self, name: &str, nodes: &[Node]) -> PromptTemplateResult<()> {
+        let mut direct = Vec::new();
+        collect_partials(nodes, &mut direct);
+
+        let mut stack: Vec<Vec<String>> = direct
+            .into_iter()
+            .map(|partial| vec![name.to_string(), partial])
+            .collect();
+        let mut checked = HashSet::new();
+        while let Some(path) = stack.pop() {
+            let current = path.last().cloned().unwrap_or_default();
+            if path[..path.len() - 1].contains(&current) {
+                return Err(PromptTemplateError::PartialCycle(path.join(" -> ")));
+            }
+            if path.len() > MAX_PARTIAL_DEPTH + 1 {
+                return Err(PromptTemplateError::PartialCycle(path.join(" -> ")));
+            }
+            if !checked.insert(path.clone()) {
+                continue;
+            }
+
+            let partial = self
+                .store
+                .get(&current)
+                .await?
+                .ok_or_else(|| PromptTemplateError::UnknownPartial(current.clone()))?;
+            let mut includes = Vec::new();
+            if let Ok(partial_nodes) = parse(&partial.body) {
+                collect_partials(&partial_nodes, &mut includes);
+            }
+            for include in includes {
+                let mut next = path.clone();
+                next.push(include);
+                stack.push(next);
+            }
+        }
+        Ok(())
+    }
+
+    /// Load every partial a template includes, directly or not
+    async fn load_partials(
+        &self,
+        name: &str,
+        nodes: &[Node],
+    ) -> PromptTemplateResult<HashMap<String, (PromptTemplate, Vec<Node>)>> {
+        let mut pending = Vec::new();
+        collect_partials(nodes, &mut pending);
+
+        let mut partials = HashMap::new();
+        while let Some(partial_name) = pending.pop() {
+            if partial_name == name || partials.contains_key(&partial_name) {
+                continue;
+            }
+            let partial = self
+                .store
+                .get(&partial_name)
+                .await?
+                .ok_or_else(|| PromptTemplateError::UnknownPartial(partial_name.clone()))?;
+            let partial_nodes = parse(&partial.body)?;
+            collect_partials(&partial_nodes, &mut pending);
+            partials.insert(partial_name, (partial, partial_nodes));
+        }
+        Ok(partials)
+    }
+}
+
+/// A parsed template element
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Variable(String),
+    Helper { name: String, max_tokens: Option<u32> },
+    Partial(String),
+    If { path: String, then: Vec<Node>, otherwise: Vec<Node> },
+    Each { path: String, body: Vec<Node> },
+}
+
+/// An open block while parsing
+enum Block {
+    If { path: String, then: Vec<Node>, otherwise: Option<Vec<Node>>, line: usize },
+    Each { path: String, body: Vec<Node>, line: usize },
+}
+
+fn syntax(line: usize, message: impl Into<String>) -> PromptTemplateError {
+    PromptTemplateError::Syntax { line, message: message.into() }
+}
+
+/// The node list new nodes are appended to
+fn target<'a>(root: &'a mut Vec<Node>, blocks: &'a mut [Block]) -> &'a mut Vec<Node> {
+    match blocks.last_mut() {
+        Some(Block::If { otherwise: Some(otherwise), .. }) => otherwise,
+        Some(Block::If { then, .. }) => then,
+        Some(Block::Each { body, .. }) => body,
+        None => root,
+    }
+}
+
+fn parse(body: &str) -> PromptTemplateResult<Vec<Node>> {
+    let mut root = Vec::new();
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut rest = body;
+    let mut line = 1;
+
+    while let Some(start) = rest.find("{{") {
+        let text = &rest[..start];
+        if !text.is_empty() {
+            target(&mut root, &mut blocks).push(Node::Text(text.to_string()));
+        }
+        line += text.matches('\n').count();
+
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or_else(|| syntax(line, "unclosed '{{'"))?;
+        let raw = &after[..end];
+        let tag = raw.trim();
+        let tag_line = line;
+        line += raw.matches('\n').count();
+        rest = &after[end + 2..];
+
+        if tag.starts_with('!') {
+            continue;
+        }
+        if let Some(path) = tag.strip_prefix("#if ") {
+            let path = check_path(path.trim(), tag_line)?;
+            blocks.push(Block::If { path, then: Vec::new(), otherwise: None, line: tag_line });
+        } else if let Some(path) = tag.strip_prefix("#each ") {
+            let path = check_path(path.trim(), tag_line)?;
+            blocks.push(Block::Each { path, body: Vec::new(), line: tag_line });
+        } else if tag == "else" {
+            match blocks.last_mut() {
+                Some(Block::If { otherwise: otherwise @ None, .. }) => *otherwise = Some(Vec::new()),
+                _ => return Err(syntax(tag_line, "'{{else}}' outside '{{#if}}'")),
+            }
+        } else if tag == "/if" {
+            match blocks.pop() {
+                Some(Block::If { path, then, otherwise, .. }) => {
+                    let node = Node::If { path, then, otherwise: otherwise.unwrap_or_default() };
+                    target(&mut root, &mut blocks).push(node);
+                }
+                _ => return Err(syntax(tag_line, "'{{/if}}' without '{{#if}}'")),
+            }
+        } else if tag == "/each" {
+            match blocks.pop() {
+                Some(Block::Each { path, body, .. }) => {
+                    target(&mut root, &mut blocks).push(Node::Each { path, body });
+                }
+                _ => return Err(syntax(tag_line, "'{{/each}}' without '{{#each}}'")),
+            }
+        } else if let Some(name) = tag.strip_prefix('>') {
+            let name = name.trim();
+            if !is_template_name(name) {
+                return Err(syntax(tag_line, format!("invalid partial name '{}'", name)));
+            }
+            target(&mut root, &mut blocks).push(Node::Partial(name.to_string()));
+        } else if tag.starts_with('#') || tag.starts_with('/') {
+            return Err(syntax(tag_line, format!("unknown block '{}'", tag)));
+        } else {
+            let node = parse_expression(tag, tag_line)?;
+            target(&mut root, &mut blocks).push(node);
+        }
+    }
+
+    if !rest.is_empty() {
+        target(&mut root, &mut blocks).push(Node::Text(rest.to_string()));
+    }
+    match blocks.last() {
+        Some(Block::If { line, .. }) => Err(syntax(*line, "'{{#if}}' is never closed")),
+        Some(Block::Each { line, .. }) => Err(syntax(*line, "'{{#each}}' is never closed")),
+        None => Ok(root),
+    }
+}
+
+/// A variable or helper tag
+fn parse_expression(tag: &str, line: usize) -> PromptTemplateResult<Node> {
+    let mut words = tag.split_whitespace();
+    let name = words.next().ok_or_else(|| syntax(line, "empty tag"))?;
+
+    if TWIN_HELPERS.contains(&name) {
+        let mut max_tokens = None;
+        for argument in words {
+            let value = argument
+                .strip_prefix("max_tokens=")
+                .ok_or_else(|| syntax(line, format!("unknown argument '{}' for '{}'", argument, name)))?;
+            let value = value
+                .parse::<u32>()
+                .map_err(|_| syntax(line, format!("max_tokens must be a positive integer, got '{}'", value)))?;
+            max_tokens = Some(value);
+        }
+        return Ok(Node::Helper { name: name.to_string(), max_tokens });
+    }
+
+    if words.next().is_some() {
+        return Err(syntax(line, format!("unexpected arguments in '{}'", tag)));
+    }
+    Ok(Node::Variable(check_path(name, line)?))
+}
+
+fn check_path(path: &str, line: usize) -> PromptTemplateResult<String> {
+    let valid = !path.is_empty()
+        && path.split('.').enumerate().all(|(i, segment)| {
+            (i == 0 && segment == "@index")
+                || (!segment.is_empty()
+                    && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        });
+    if valid {
+        Ok(path.to_string())
+    } else {
+        Err(syntax(line, format!("invalid variable '{}'", path)))
+    }
+}
+
+fn is_template_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn validate_declarations(template: &PromptTemplate) -> PromptTemplateResult<()> {
+    if !is_template_name(&template.name) {
+        return Err(PromptTemplateError::Invalid(format!(
+            "name '{}' may only contain letters, digits, '_', '-' and '.'",
+            template.name
+        )));
+    }
+
+    let mut names = HashSet::new();
+    for variable in &template.variables {
+        if check_path(&variable.name, 0).is_err() || variable.name.contains('.') {
+            return Err(PromptTemplateError::Invalid(format!(
+                "invalid variable name '{}'",
+                variable.name
+            )));
+        }
+        if matches!(variable.name.as_str(), "this" | "@index") || TWIN_HELPERS.contains(&variable.name.as_str()) {
+            return Err(PromptTemplateError::Invalid(format!(
+                "variable name '{}' is reserved",
+                variable.name
+            )));
+        }
+        if !names.insert(variable.name.as_str()) {
+            return Err(PromptTemplateError::Invalid(format!(
+                "variable '{}' is declared twice",
+                variable.name
+            )));
+        }
+        if let Some(default) = &variable.default {
+            if !variable.var_type.accepts(default) {
+                return Err(PromptTemplateError::InvalidVariable {
+                    name: variable.name.clone(),
+                    message: format!("default is not of type {:?}", variable.var_type),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that every variable a template uses is declared
+fn check_variables(nodes: &[Node], declared: &HashSet<&str>, in_each: bool) -> PromptTemplateResult<()> {
+    let check = |path: &str| {
+        let root = path.split('.').next().unwrap_or(path);
+        let known = if root == "this" || root == "@index" {
+            in_each
+        } else {
+            declared.contains(root)
+        };
+        if known {
+            Ok(())
+        } else {
+            Err(PromptTemplateError::UndeclaredVariable(root.to_string()))
+        }
+    };
+
+    for node in nodes {
+        match node {
+            Node::Variable(path) => check(path)?,
+            Node::If { path, then, otherwise } => {
+                check(path)?;
+                check_variables(then, declared, in_each)?;
+                check_variables(otherwise, declared, in_each)?;
+            }
+            Node::Each { path, body } => {
+                check(path)?;
+                check_variables(body, declared, true)?;
+            }
+            Node::Text(_) | Node::Helper { .. } | Node::Partial(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn collect_partials(nodes: &[Node], partials: &mut Vec<String>) {
+    for node in nodes {
+        match node {
+            Node::Partial(name) if !partials.contains(name) => partials.push(name.clone()),
+            Node::If { then, otherwise, .. } => {
+                collect_partials(then, partials);
+                collect_partials(otherwise, partials);
+            }
+            Node::Each { body, .. } => collect_partials(body, partials),
+            _ => {}
+        }
+    }
+}
+
+fn uses_twin_helpers(nodes: &[Node]) -> bool {
+    nodes.iter().any(|node| match node {
+        Node::Helper { .. } => true,
+        Node::If { then, otherwise, .. } => uses_twin_helpers(then) || uses_twin_helpers(otherwise),
+        Node::Each { body, .. } => uses_twin_helpers(body),
+        _ => false,
+    })
+}
+
+/// Check supplied values against declarations and fill in defaults
+fn resolve_variables(
+    declarations: &[TemplateVariable],
+    supplied: &HashMap<String, Value>,
+) -> PromptTemplateResult<Map<String, Value>> {
+    if let Some(name) = supplied.keys().find(|name| !declarations.iter().any(|v| &v.name == *name)) {
+        return Err(PromptTemplateError::UndeclaredVariable(name.clone()));
+    }
+
+    let mut values = Map::new();
+    for variable in declarations {
+        let value = match supplied.get(&variable.name).filter(|value| !value.is_null()) {
+            Some(value) if !variable.var_type.accepts(value) => {
+                return Err(PromptTemplateError::InvalidVariable {
+                    name: variable.name.clone(),
+                    message: format!("expected {:?}, got {}", variable.var_type, value),
+                });
+            }
+            Some(value) => value.clone(),
+            None => match &variable.default {
+                Some(default) => default.clone(),
+                None if variable.required => {
+                    return Err(PromptTemplateError::InvalidVariable {
+                        name: variable.name.clone(),
+                        message: "required but not given".to_string(),
+                    });
+                }
+                None => Value::Null,
+            },
+        };
+        values.insert(variable.name.clone(), value);
+    }
+    Ok(values)
+}
+
+struct Renderer<'a> {
+    partials: &'a HashMap<String, (PromptTemplate, Vec<Node>)>,
+    twin: Option<&'a TwinContext>,
+    counter: &'a TokenCounter,
+}
+
+impl Renderer<'_> {
+    /// `scope` holds the item and index of each enclosing `{{#each}}`
+    fn render(
+        &self,
+        nodes: &[Node],
+        values: &Map<String, Value>,
+        scope: &mut Vec<(Value, usize)>,
+        out: &mut String,
+        used: &mut Vec<String>,
+        depth: usize,
+    ) -> PromptTemplateResult<()> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Variable(path) => out.push_str(&display(&lookup(path, values, scope))),
+                Node::If { path, then, otherwise } => {
+                    let branch = if is_truthy(&lookup(path, values, scope)) { then } else { otherwise };
+                    self.render(branch, values, scope, out, used, depth)?;
+                }
+                Node::Each { path, body } => {
+                    if let Value::Array(items) = lookup(path, values, scope) {
+                        for (index, item) in items.into_iter().enumerate() {
+                            scope.push((item, index));
+                            let result = self.render(body, values, scope, out, used, depth);
+                            scope.pop();
+                            result?;
+                        }
+                    }
+                }
+                Node::Partial(name) => {
+                    if depth >= MAX_PARTIAL_DEPTH {
+                        return Err(PromptTemplateError::PartialCycle(name.clone()));
+                    }
+                    let (partial, partial_nodes) = self
+                        .partials
+                        .get(name)
+                        .ok_or_else(|| PromptTemplateError::UnknownPartial(name.clone()))?;
+                    if !used.contains(name) {
+                        used.push(name.clone());
+                    }
+                    // Partials see the caller's values, with their own defaults
+                    // for variables the caller does not have
+                    let mut partial_values = values.clone();
+                    for variable in &partial.variables {
+                        let missing = matches!(partial_values.get(&variable.name), None | Some(Value::Null));
+                        if let (true, Some(default)) = (missing, &variable.default) {
+                            partial_values.insert(variable.name.clone(), default.clone());
+                        }
+                    }
+                    self.render(partial_nodes, &partial_values, scope, out, used, depth + 1)?;
+                }
+                Node::Helper { name, max_tokens } => {
+                    let twin = self.twin.ok_or(PromptTemplateError::MissingTwin)?;
+                    let budget = max_tokens.unwrap_or(DEFAULT_HELPER_TOKENS);
+                    let text = match name.as_str() {
+                        "twin_name" => twin.twin.name.clone(),
+                        "twin_properties" => within_budget(property_lines(twin), budget, self.counter),
+                        "twin_readings" => within_budget(reading_lines(twin), budget, self.counter),
+                        _ => within_budget(anomaly_lines(twin), budget, self.counter),
+                    };
+                    out.push_str(&text);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn lookup(path: &str, values: &Map<String, Value>, scope: &[(Value, usize)]) -> Value {
+    let mut segments = path.split('.');
+    let root = segments.next().unwrap_or_default();
+    let mut value = match (root, scope.last()) {
+        ("this", Some((item, _))) => item.clone(),
+        ("@index", Some((_, index))) => Value::from(*index),
+        _ => values.get(root).cloned().unwrap_or(Value::Null),
+    };
+    for segment in segments {
+        value = match &value {
+            Value::Object(fields) => fields.get(segment).cloned().unwrap_or(Value::Null),
+            Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| items.get(index).cloned())
+                .unwrap_or(Value::Null),
+            _ => Value::Null,
+        };
+    }
+    value
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(flag) => *flag,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(fields) => !fields.is_empty(),
+    }
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(display).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+fn sensor_value(value: &SensorValue) -> String {
+    match value {
+        SensorValue::Numeric(number) => number.to_string(),
+        SensorValue::Boolean(flag) => flag.to_string(),
+        SensorValue::String(text) => text.clone(),
+        SensorValue::Vector(numbers) => format!(
+            "[{}]",
+            numbers.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", ")
+        ),
+        SensorValue::Json(json) => json.to_string(),
+        SensorValue::Binary(_) => "<binary>".to_string(),
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+/// Attributes, computed properties and KPIs of a twin
+fn property_lines(context: &TwinContext) -> Vec<String> {
+    let properties = &context.twin.properties;
+    let mut lines = vec![format!("- health score: {:.2}", properties.analytics.health_score)];
+
+    let mut attributes: Vec<_> = properties.attributes.iter().chain(properties.computed.iter()).collect();
+    attributes.sort_by(|a, b| a.0.cmp(b.0));
+    lines.extend(attributes.into_iter().map(|(name, value)| format!("- {}: {}", name, display(value))));
+
+    let mut kpis: Vec<_> = properties.analytics.kpis.iter().collect();
+    kpis.sort_by(|a, b| a.0.cmp(b.0));
+    lines.extend(kpis.into_iter().map(|(name, value)| format!("- {} (KPI): {}", name, value)));
+    lines
+}
+
+/// Measurements and latest sensor readings of a twin, newest first
+fn reading_lines(context: &TwinContext) -> Vec<String> {
+    let mut readings: Vec<(DateTime<Utc>, String)> = context
+        .twin
+        .properties
+        .measurements
+        .iter()
+        .map(|(name, measurement)| {
+            let unit = measurement.unit.as_deref().map(|unit| format!(" {}", unit)).unwrap_or_default();
+            (
+                measurement.timestamp,
+                format!("- {}: {}{} (at {})", name, display(&measurement.value), unit, timestamp(measurement.timestamp)),
+            )
+        })
+        .collect();
+    readings.extend(context.readings.iter().map(|snapshot| {
+        (
+            snapshot.reading.timestamp,
+            format!(
+                "- {} ({}): {} (at {})",
+                snapshot.name,
+                snapshot.sensor_id,
+                sensor_value(&snapshot.reading.value),
+                timestamp(snapshot.reading.timestamp)
+            ),
+        )
+    }));
+
+    if readings.is_empty() {
+        return vec!["No readings.".to_string()];
+    }
+    readings.sort_by(|a, b| b.0.cmp(&a.0));
+    readings.into_iter().map(|(_, line)| line).collect()
+}
+
+/// Anomalies of a twin, most severe first
+fn anomaly_lines(context: &TwinContext) -> Vec<String> {
+    let mut anomalies: Vec<_> = context.twin.properties.analytics.anomalies.iter().collect();
+    if anomalies.is_empty() {
+        return vec!["No open anomalies.".to_string()];
+    }
+    anomalies.sort_by(|a, b| {
+        b.severity
+            .partial_cmp(&a.severity)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(b.detected_at.cmp(&a.detected_at))
+    });
+    anomalies
+        .into_iter()
+        .map(|anomaly| {
+            let property = anomaly
+                .related_property
+                .as_deref()
+                .map(|property| format!(" on {}", property))
+                .unwrap_or_default();
+            format!(
+                "- [severity {:.2}] {}{}: {} (detected {})",
+                anomaly.severity,
+                anomaly.anomaly_type,
+                property,
+                anomaly.description,
+                timestamp(anomaly.detected_at)
+            )
+        })
+        .collect()
+}
+
+/// Join as many lines as fit in a token budget, noting how many were left out
+fn within_budget(lines: Vec<String>, budget: u32, counter: &TokenCounter) -> String {
+    let mut kept = Vec::new();
+    let mut used = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let remaining = lines.len() - index - 1;
+        let reserve = if remaining > 0 { OMISSION_TOKENS } else { 0 };
+        let cost = counter.count(line) + 1;
+        if used + cost + reserve > budget {
+            kept.push(format!("... {} more omitted", lines.len() - index));
+            break;
+        }
+        used += cost;
+        kept.push(line.clone());
+    }
+    kept.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::models::{Anomaly, Measurement, ReadingQuality, TwinType};
+    use crate::core::domain::traits::repository::RepositoryResult;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        templates: Mutex<HashMap<String, PromptTemplate>>,
+    }
+
+    #[async_trait]
+    impl PromptTemplateStore for MemoryStore {
+        async fn get(&self, name: &str) -> RepositoryResult<Option<PromptTemplate>> {
+            Ok(self.templates.lock().unwrap().get(name).cloned())
+        }
+
+        async fn list(&self) -> RepositoryResult<Vec<PromptTemplate>> {
+            let mut templates: Vec<_> = self.templates.lock().unwrap().values().cloned().collect();
+            templates.sort_by(|a, b| a.name.cmp(&b.name));
+            Ok(templates)
+        }
+
+        async fn save(&self, template: &PromptTemplate) -> RepositoryResult<()> {
+            self.templates.lock().unwrap().insert(template.name.clone(), template.clone());
+            Ok(())
+        }
+
+        async fn delete(&self, name: &str) -> RepositoryResult<bool> {
+            Ok(self.templates.lock().unwrap().remove(name).is_some())
+        }
+    }
+
+    fn variable(name: &str, var_type: VariableType, required: bool) -> TemplateVariable {
+        TemplateVariable { name: name.to_string(), var_type, required, default: None, description: None }
+    }
+
+    fn service() -> PromptTemplates {
+        PromptTemplates::new(Arc::new(MemoryStore::default()))
+    }
+
+    #[tokio::test]
+    async fn test_save_validates_syntax_variables_and_partials() {
+        let templates = service();
+
+        let unclosed = PromptTemplate::new("a", "Hi\n{{#if urgent}}now");
+        assert!(matches!(
+            templates.save(unclosed).await,
+            Err(PromptTemplateError::Syntax { line: 2, .. })
+        ));
+
+        let undeclared = PromptTemplate::new("a", "Hello {{operator}}");
+        assert!(matches!(
+            templates.save(undeclared).await,
+            Err(PromptTemplateError::UndeclaredVariable(name)) if name == "operator"
+        ));
+
+        let mut wrong_default = variable("limit", VariableType::Integer, false);
+        wrong_default.default = Some(json!("ten"));
+        let template = PromptTemplate::new("a", "{{limit}}").with_variable(wrong_default);
+        assert!(matches!(templates.save(template).await, Err(PromptTemplateError::InvalidVariable { .. })));
+
+        let missing_partial = PromptTemplate::new("a", "{{> safety}}");
+        assert!(matches!(templates.save(missing_partial).await, Err(PromptTemplateError::UnknownPartial(_))));
+
+        templates.save(PromptTemplate::new("safety", "Be careful.")).await.unwrap();
+        let saved = templates.save(PromptTemplate::new("a", "{{> safety}}")).await.unwrap();
+        assert_eq!(saved.version, 1);
+        assert_eq!(templates.save(saved).await.unwrap().version, 2);
+
+        // "safety" may not include "a", which already includes it
+        let cycle = PromptTemplate::new("safety", "{{> a}}");
+        assert!(matches!(templates.save(cycle).await, Err(PromptTemplateError::PartialCycle(_))));
+        assert!(matches!(templates.delete("safety").await, Err(PromptTemplateError::InUse { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_render_variables_blocks_and_partials() {
+        let templates = service();
+        let mut tone = variable("tone", VariableType::String, false);
+        tone.default = Some(json!("concise"));
+        templates
+            .save(PromptTemplate::new("style", "Answer in a {{tone}} tone.").with_variable(tone))
+            .await
+            .unwrap();
+
+        let template = PromptTemplate::new(
+            "operator",
+            "You assist {{operator.name}}.{{! not shown}}\n\
+             {{#if urgent}}Reply at once.{{else}}Take your time.{{/if}}\n\
+             {{#each sites}}{{@index}}. {{this.name}}\n{{/each}}{{> style}}",
+        )
+        .with_variable(variable("operator", VariableType::Object, true))
+        .with_variable(variable("urgent", VariableType::Boolean, false))
+        .with_variable(variable("sites", VariableType::List, false));
+        templates.save(template).await.unwrap();
+
+        let variables = HashMap::from([
+            ("operator".to_string(), json!({ "name": "Dana" })),
+            ("sites".to_string(), json!([{ "name": "Plant A" }, { "name": "Plant B" }])),
+        ]);
+        let rendered = templates.render("operator", &variables, None).await.unwrap();
+        assert_eq!(
+            rendered.text,
+            "You assist Dana.\nTake your time.\n0. Plant A\n1. Plant B\nAnswer in a concise tone."
+        );
+        assert_eq!(rendered.partials, vec!["style".to_string()]);
+        assert!(rendered.tokens > 0);
+
+        let wrong_type = HashMap::from([("operator".to_string(), json!("Dana"))]);
+        assert!(matches!(
+            templates.render("operator", &wrong_type, None).await,
+            Err(PromptTemplateError::InvalidVariable { .. })
+        ));
+        assert!(matches!(
+            templates.render("operator", &HashMap::new(), None).await,
+            Err(PromptTemplateError::InvalidVariable { .. })
+        ));
+    }
+
+    #[test]
+    fn test_twin_helpers_fit_token_budget() {
+        let mut twin = DigitalTwin::new("Pump 7".to_string(), "Cooling pump".to_string(), TwinType::Device {
+            device_type: "pump".to_string(),
+            manufacturer: None,
+            model: None,
+        });
+        twin.properties.attributes.insert("model".to_string(), json!("KSB-200"));
+        twin.properties.measurements.insert(
+            "temperature".to_string(),
+            Measurement { value: json!(81.5), unit: Some("°C".to_string()), quality: 1.0, timestamp: Utc::now(), source_id: None },
+        );
+        for i in 0..40 {
+            twin.properties.analytics.anomalies.push(Anomaly {
+                anomaly_type: "vibration".to_string(),
+                severity: i as f32 / 50.0,
+                description: format!("Vibration spike number {} above the configured threshold", i),
+                detected_at: Utc::now(),
+                related_property: Some("vibration".to_string()),
+            });
+        }
+        let context = TwinContext {
+            twin,
+            readings: vec![SensorSnapshot {
+                sensor_id: "flow-1".to_string(),
+                name: "Outlet flow".to_string(),
+                reading: SensorReading {
+                    id: uuid::Uuid::new_v4(),
+                    value: SensorValue::Numeric(12.5),
+                    timestamp: Utc::now(),
+                    quality: ReadingQuality::default(),
+                    context: None,
+                    alerts: Vec::new(),
+                },
+            }],
+        };
+        let counter = TokenCounter::new(TokenModel::Generic);
+        let nodes = parse("{{twin_name}}\n{{twin_properties}}\n{{twin_readings}}\n{{twin_anomalies max_tokens=120}}").unwrap();
+        let partials = HashMap::new();
+        let renderer = Renderer { partials: &partials, twin: Some(&context), counter: &counter };
+        let mut text = String::new();
+        renderer.render(&nodes, &Map::new(), &mut Vec::new(), &mut text, &mut Vec::new(), 0).unwrap();
+
+        assert!(text.starts_with("Pump 7\n- health score"));
+        assert!(text.contains("- model: KSB-200"));
+        assert!(text.contains("- temperature: 81.5 °C"));
+        assert!(text.contains("- Outlet flow (flow-1): 12.5"));
+        let anomalies = &text[text.find("[severity").unwrap()..];
+        assert!(anomalies.starts_with("[severity 0.78]"));
+        assert!(anomalies.trim_end().ends_with("more omitted"));
+        assert!(anomalies.lines().count() < 40);
+    }
+
+    #[tokio::test]
+    async fn test_render_system_prompt_uses_saved_template() {
+        let templates = service();
+        let rendered = templates
+            .render_system_prompt("Watch the pumps.", Some("Plant B"), None)
+            .await
+            .unwrap();
+        assert_eq!(rendered.text, "Watch the pumps.\n\nContext: Plant B");
+        let rendered = templates.render_system_prompt("Watch the pumps.", None, None).await.unwrap();
+        assert_eq!(rendered.text, "Watch the pumps.");
+
+        let template = PromptTemplate::new(SYSTEM_PROMPT_TEMPLATE, "Operator assistant. {{instructions}}")
+            .with_variable(variable("instructions", VariableType::String, true));
+        templates.save(template).await.unwrap();
+        let rendered = templates
+            .render_system_prompt("Watch the pumps.", Some("Plant B"), None)
+            .await
+            .unwrap();
+        assert_eq!(rendered.text, "Operator assistant. Watch the pumps.");
+    }
+}
@@ -0,0 +1,417 @@
+//! Response caching for deterministic chat completions.
+//!
+//! [`CachingClient`] wraps an [`LLMClient`] and answers repeated chat
+//! requests from a [`ResponseCacheStore`]. Requests are keyed by a SHA-256
+//! hash over everything that shapes the answer (model, messages, tools,
+//! sampling parameters), leaving out request IDs and attribution. Only
+//! requests at temperature 0 are cached unless configured otherwise.
+
+use crate::core::application::services::usage_accounting::{AGENT_ID_PARAM, CONVERSATION_ID_PARAM};
+use crate::core::domain::traits::llm_client::{
+    ChatCompletionRequest, ChatCompletionResponse, ChatCompletionStream, ChatMessage,
+    CompletionRequest, CompletionResponse, CompletionStream, EmbeddingRequest, EmbeddingResponse,
+    LLMClient, LLMResult, ModelInfo, ResponseFormat, ToolChoice, ToolDefinition, UsagePeriod,
+    UsageStats, LOCAL_ONLY_PARAM,
+};
+use crate::core::domain::traits::repository::RepositoryResult;
+use crate::core::domain::traits::response_cache_store::{CachedResponse, ResponseCacheConfig, ResponseCacheStore};
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Request parameter controlling the cache for one request
+///
+/// `false` bypasses the cache; `true` caches a request even if its
+/// temperature is not 0.
+pub const CACHE_PARAM: &str = "cache";
+
+/// Request parameters that do not change the answer and are left out of
+/// the cache key
+const UNKEYED_PARAMS: &[&str] = &[CACHE_PARAM, AGENT_ID_PARAM, CONVERSATION_ID_PARAM, LOCAL_ONLY_PARAM];
+
+/// Cache hit and miss counters since startup
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Requests not eligible for caching or bypassing it
+    pub bypassed: u64,
+    pub stored: u64,
+    pub evicted: u64,
+    /// Store failures; the request was sent to the model
+    pub errors: u64,
+    pub hit_rate: f64,
+    pub entries: usize,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    bypassed: AtomicU64,
+    stored: AtomicU64,
+    evicted: AtomicU64,
+    errors: AtomicU64,
+}
+
+/// Fields of a request that shape its answer
+#[derive(Serialize)]
+struct KeyFields<'a> {
+    model: &'a str,
+    system: Option<&'a str>,
+    messages: &'a [ChatMessage],
+    tools: Option<&'a [ToolDefinition]>,
+    tool_choice: &'a Option<ToolChoice>,
+    response_format: &'a Option<ResponseFormat>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    top_p: Option<f32>,
+    frequency_penalty: Option<f32>,
+    presence_penalty: Option<f32>,
+    stop_sequences: &'a Option<Vec<String>>,
+    n: Option<u32>,
+    extra_params: BTreeMap<&'a str, &'a serde_json::Value>,
+}
+
+/// Cache key of a chat request
+pub fn cache_key(request: &ChatCompletionRequest) -> String {
+    let fields = KeyFields {
+        model: &request.model,
+        system: request.system.as_deref(),
+        messages: &request.messages,
+        tools: request.tools.as_deref(),
+        tool_choice: &request.tool_choice,
+        response_format: &request.response_format,
+        temperature: request.temperature,
+        max_tokens: request.max_tokens,
+        top_p: request.top_p,
+        frequency_penalty: request.frequency_penalty,
+        presence_penalty: request.presence_penalty,
+        stop_sequences: &request.stop_sequences,
+        n: request.n,
+        extra_params: request.extra_params
+            .iter()
+            .filter(|(key, _)| !UNKEYED_PARAMS.contains(&key.as_str()))
+            .map(|(key, value)| (key.as_str(), value))
+            .collect(),
+    };
+    // Serializing these fields cannot fail: every map is keyed by strings
+    let bytes = serde_json::to_vec(&fields).unwrap_or_default();
+
+    digest(&SHA256, &bytes).as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// LLM client answering repeated chat requests from a cache
+pub struct CachingClient {
+    inner: Arc<dyn LLMClient>,
+    store: Arc<dyn ResponseCacheStore>,
+    config: ResponseCacheConfig,
+    counters: Counters,
+}
+
+impl CachingClient {
+    pub fn new(inner: Arc<dyn LLMClient>, store: Arc<dyn ResponseCacheStore>) -> Self {
+        Self {
+            inner,
+            store,
+            config: ResponseCacheConfig::default(),
+            counters: Counters::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: ResponseCacheConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Whether a request may be answered from the cache
+    fn cacheable(&self, request: &ChatCompletionRequest) -> bool {
+        if !self.config.enabled {
+            return false;
+        }
+        match request.extra_params.get(CACHE_PARAM).and_then(|v| v.as_bool()) {
+            Some(cache) => cache,
+            None => self.config.cache_nondeterministic || request.temperature == Some(0.0),
+        }
+    }
+
+    pub async fn stats(&self) -> CacheStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let (hits, misses) = (load(&self.counters.hits), load(&self.counters.misses));
+        CacheStats {
+            hits,
+            misses,
+            bypassed: load(&self.counters.bypassed),
+            stored: load(&self.counters.stored),
+            evicted: load(&self.counters.evicted),
+            errors: load(&self.counters.errors),
+            hit_rate: if hits + misses > 0 { hits as f64 / (hits + misses) as f64 } else { 0.0 },
+            entries: self.store.len().await.unwrap_or(0),
+        }
+    }
+
+    /// Remove every cached response
+    pub async fn clear(&self) -> RepositoryResult<usize> {
+        self.store.clear().await
+    }
+
+    async fn lookup(&self, key: &str) -> Option<ChatCompletionResponse> {
+        match self.store.get(key, Utc::now()).await {
+            Ok(entry) => entry.map(|entry| entry.response),
+            Err(e) => {
+                tracing::warn!("Response cache lookup failed: {}", e);
+                self.counters.errors.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    async fn store(&self, key: String, response: &ChatCompletionResponse) {
+        let now = Utc::now();
+        let entry = CachedResponse {
+            key,
+            model: response.model.clone(),
+            response: response.clone(),
+            created_at: now,
+            expires_at: now + Duration::seconds(self.config.ttl_seconds as i64),
+        };
+        let result = match self.store.put(&entry).await {
+            Ok(()) => {
+                self.counters.stored.fetch_add(1, Ordering::Relaxed);
+                self.store.prune(self.config.max_entries, now).await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(evicted) => {
+                self.counters.evicted.fetch_add(evicted as u64, Ordering::Relaxed);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to cache response: {}", e);
+                self.counters.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LLMClient for CachingClient {
+    async fn complete(&self, request: CompletionRequest) -> LLMResult<CompletionResponse> {
+        self.inner.complete(request).await
+    }
+
+    async fn chat_complete(&self, request: ChatCompletionRequest) -> LLMResult<ChatCompletionResponse> {
+        if !self.cacheable(&request) {
+            self.counters.bypassed.fetch_add(1, Ordering::Relaxed);
+            return self.inner.chat_complete(request).await;
+        }
+
+        let started = Instant::now();
+        let key = cache_key(&request);
+        if let Some(mut response) = self.lookup(&key).await {
+            self.counters.hits.fetch_add(1, Ordering::Relaxed);
+            response.request_id = request.request_id;
+            response.processing_time_ms = started.elapsed().as_millis() as u64;
+            return Ok(response);
+        }
+
+        self.counters.misses.fetch_add(1, Ordering::Relaxed);
+        let response = self.inner.chat_complete(request).await?;
+        self.store(key, &response).await;
+        Ok(response)
+    }
+
+    async fn stream_complete(&self, request: CompletionRequest) -> LLMResult<Box<dyn CompletionStream>> {
+        self.inner.stream_complete(request).await
+    }
+
+    async fn stream_chat_complete(&self, request: ChatCompletionRequest) -> LLMResult<Box<dyn ChatCompletionStream>> {
+        self.inner.stream_chat_complete(request).await
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> LLMResult<EmbeddingResponse> {
+        self.inner.embed(request).await
+    }
+
+    async fn list_models(&self) -> LLMResult<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model_info(&self, model_id: &str) -> LLMResult<ModelInfo> {
+        self.inner.get_model_info(model_id).await
+    }
+
+    async fn validate_credentials(&self) -> LLMResult<bool> {
+        self.inner.validate_credentials().await
+    }
+
+    async fn get_usage(&self, period: UsagePeriod) -> LLMResult<UsageStats> {
+        self.inner.get_usage(period).await
+    }
+
+    async fn cancel_request(&self, request_id: &str) -> LLMResult<()> {
+        self.inner.cancel_request(request_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::traits::llm_client::MessageRole;
+    use crate::core::domain::traits::mock_llm_client::MockLLMClient;
+    use chrono::DateTime;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemoryCacheStore {
+        entries: Mutex<Vec<CachedResponse>>,
+    }
+
+    #[async_trait]
+    impl ResponseCacheStore for MemoryCacheStore {
+        async fn get(&self, key: &str, now: DateTime<Utc>) -> RepositoryResult<Option<CachedResponse>> {
+            Ok(self.entries.lock().unwrap().iter().find(|e| e.key == key && e.expires_at > now).cloned())
+        }
+
+        async fn put(&self, entry: &CachedResponse) -> RepositoryResult<()> {
+            let mut entries = self.entries.lock().unwrap();
+            entries.retain(|e| e.key != entry.key);
+            entries.push(entry.clone());
+            Ok(())
+        }
+
+        async fn prune(&self, max_entries: usize, now: DateTime<Utc>) -> RepositoryResult<usize> {
+            let mut entries = self.entries.lock().unwrap();
+            let before = entries.len();
+            entries.retain(|e| e.expires_at > now);
+            let overflow = entries.len().saturating_sub(max_entries);
+            entries.drain(..overflow);
+            Ok(before - entries.len())
+        }
+
+        async fn clear(&self) -> RepositoryResult<usize> {
+            Ok(std::mem::take(&mut *self.entries.lock().unwrap()).len())
+        }
+
+        async fn len(&self) -> RepositoryResult<usize> {
+            Ok(self.entries.lock().unwrap().len())
+        }
+    }
+
+    /// Numbers its answers so repeated answers can be told apart
+    fn counting_client() -> Arc<MockLLMClient> {
+        Arc::new(
+            MockLLMClient::responding(|_, call| format!("answer {}", call))
+                .with_usage(10, 2)
+                .with_processing_time(800),
+        )
+    }
+
+    fn request(content: &str, temperature: Option<f32>) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            request_id: uuid::Uuid::new_v4().to_string(),
+            messages: vec![ChatMessage {
+                role: MessageRole::User,
+                content: Some(content.to_string()),
+                name: None,
+                tool_call_id: None,
+                tool_calls: None,
+                parts: None,
+            }],
+            model: "gpt-4o".to_string(),
+            temperature,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop_sequences: None,
+            n: None,
+            system: Some("You report on pump P-101.".to_string()),
+            tools: None,
+            tool_choice: None,
+            response_format: None,
+            user_id: None,
+            conversation_context: None,
+            extra_params: HashMap::new(),
+        }
+    }
+
+    async fn answer(client: &CachingClient, request: ChatCompletionRequest) -> String {
+        let response = client.chat_complete(request).await.unwrap();
+        response.choices[0].message.content.clone().unwrap()
+    }
+
+    #[test]
+    fn test_cache_key_ignores_request_identity() {
+        let mut a = request("Daily report", Some(0.0));
+        let mut b = request("Daily report", Some(0.0));
+        a.user_id = Some("alice".to_string());
+        a.extra_params.insert(AGENT_ID_PARAM.to_string(), serde_json::json!("a1"));
+        a.extra_params.insert(LOCAL_ONLY_PARAM.to_string(), serde_json::json!(true));
+        b.extra_params.insert(CACHE_PARAM.to_string(), serde_json::json!(true));
+        assert_eq!(cache_key(&a), cache_key(&b));
+
+        b.extra_params.insert("seed".to_string(), serde_json::json!(7));
+        assert_ne!(cache_key(&a), cache_key(&b));
+        assert_ne!(cache_key(&a), cache_key(&request("Weekly report", Some(0.0))));
+        assert_ne!(cache_key(&a), cache_key(&request("Daily report", Some(0.2))));
+    }
+
+    #[tokio::test]
+    async fn test_caches_deterministic_requests() {
+        let inner = counting_client();
+        let client = CachingClient::new(inner.clone(), Arc::new(MemoryCacheStore::default()));
+
+        assert_eq!(answer(&client, request("Daily report", Some(0.0))).await, "answer 1");
+        let cached = request("Daily report", Some(0.0));
+        let request_id = cached.request_id.clone();
+        let response = client.chat_complete(cached).await.unwrap();
+        assert_eq!(response.choices[0].message.content.as_deref(), Some("answer 1"));
+        assert_eq!(response.request_id, request_id);
+
+        // Sampled requests and explicit bypasses reach the model
+        assert_eq!(answer(&client, request("Daily report", Some(0.7))).await, "answer 2");
+        let mut bypass = request("Daily report", Some(0.0));
+        bypass.extra_params.insert(CACHE_PARAM.to_string(), serde_json::json!(false));
+        assert_eq!(answer(&client, bypass).await, "answer 3");
+
+        let stats = client.stats().await;
+        assert_eq!((stats.hits, stats.misses, stats.bypassed, stats.stored), (1, 1, 2, 1));
+        assert_eq!(stats.hit_rate, 0.5);
+        assert_eq!(stats.entries, 1);
+        assert_eq!(inner.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_expiry_and_eviction() {
+        let inner = counting_client();
+        let store = Arc::new(MemoryCacheStore::default());
+        let client = CachingClient::new(inner.clone(), store.clone()).with_config(ResponseCacheConfig {
+            max_entries: 2,
+            ..ResponseCacheConfig::default()
+        });
+
+        for report in ["pump", "valve", "motor"] {
+            answer(&client, request(report, Some(0.0))).await;
+        }
+        let stats = client.stats().await;
+        assert_eq!((stats.entries, stats.evicted), (2, 1));
+
+        // The oldest response was evicted
+        assert_eq!(answer(&client, request("pump", Some(0.0))).await, "answer 4");
+
+        let client = CachingClient::new(inner, store).with_config(ResponseCacheConfig {
+            ttl_seconds: 0,
+            ..ResponseCacheConfig::default()
+        });
+        answer(&client, request("compressor", Some(0.0))).await;
+        assert_eq!(answer(&client, request("compressor", Some(0.0))).await, "answer 6");
+        assert_eq!(client.clear().await.unwrap(), 2);
+    }
+}
@@ -0,0 +1,455 @@
+//! Semantic memory over conversations and twin documentation.
+//!
+//! Text is split into overlapping chunks, embedded and kept in a
+//! [`VectorStore`]. Retrieval embeds a query and returns the most similar
+//! chunks above the configured threshold, which the memory manager adds to
+//! the context window under the RAG strategy. Text of sensitive twins and of
+//! local-only conversations is only embedded by providers on this machine.
+
+use crate::core::domain::models::conversation::{Attachment, Message, MessageSender};
+use crate::core::domain::models::DigitalTwin;
+use crate::core::domain::traits::llm_client::{
+    EmbeddingRequest, LLMClient, LLMError, LLMResult, LOCAL_ONLY_PARAM,
+};
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+use crate::core::domain::traits::vector_store::{
+    cosine_similarity, ChunkSource, MemoryChunk, MemoryScope, RagConfig, ScoredChunk, VectorStore,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Semantic memory error types
+#[derive(Debug, Error)]
+pub enum SemanticMemoryError {
+    /// Embedding the text failed
+    #[error("Embedding failed: {0}")]
+    Embedding(#[from] LLMError),
+
+    /// The vector store failed
+    #[error("Vector store error: {0}")]
+    Storage(#[from] RepositoryError),
+}
+
+/// Result type for semantic memory operations
+pub type SemanticMemoryResult<T> = Result<T, SemanticMemoryError>;
+
+/// Vector store kept in memory, for tests and non-persistent use
+#[derive(Default)]
+pub struct InMemoryVectorStore {
+    chunks: RwLock<Vec<MemoryChunk>>,
+}
+
+#[async_trait]
+impl VectorStore for InMemoryVectorStore {
+    async fn upsert(&self, chunks: &[MemoryChunk]) -> RepositoryResult<()> {
+        let mut stored = self.chunks.write().unwrap();
+        stored.retain(|c| !chunks.iter().any(|n| n.source_id == c.source_id));
+        stored.extend_from_slice(chunks);
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        scopes: &[MemoryScope],
+        query: &[f32],
+        limit: usize,
+        min_score: f32,
+    ) -> RepositoryResult<Vec<ScoredChunk>> {
+        let mut scored: Vec<ScoredChunk> = self.chunks.read().unwrap().iter()
+            .filter(|c| scopes.contains(&c.scope))
+            .map(|c| ScoredChunk { score: cosine_similarity(&c.embedding, query), chunk: c.clone() })
+            .filter(|s| s.score >= min_score)
+            .collect();
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+
+    async fn remove_source(&self, source_id: Uuid) -> RepositoryResult<()> {
+        self.chunks.write().unwrap().retain(|c| c.source_id != source_id);
+        Ok(())
+    }
+
+    async fn prune(&self, scope: MemoryScope, max_entries: usize) -> RepositoryResult<usize> {
+        let mut stored = self.chunks.write().unwrap();
+        let in_scope = stored.iter().filter(|c| c.scope == scope).count();
+        let mut excess = in_scope.saturating_sub(max_entries);
+        let pruned = excess;
+        // Chunks are appended in indexing order, so the oldest come first
+        stored.retain(|c| {
+            if excess > 0 && c.scope == scope {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+        Ok(pruned)
+    }
+}
+
+/// Turns text into embedding vectors
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// One vector per input text, in order
+    ///
+    /// With `local_only` the texts must not leave this machine.
+    async fn embed(&self, texts: Vec<String>, local_only: bool) -> LLMResult<Vec<Vec<f32>>>;
+}
+
+/// Embeds through an LLM provider
+pub struct LLMEmbedder {
+    client: Arc<dyn LLMClient>,
+    model: String,
+}
+
+impl LLMEmbedder {
+    pub fn new(client: Arc<dyn LLMClient>, model: impl Into<String>) -> Self {
+        Self { client, model: model.into() }
+    }
+}
+
+#[async_trait]
+impl Embedder for LLMEmbedder {
+    async fn embed(&self, texts: Vec<String>, local_only: bool) -> LLMResult<Vec<Vec<f32>>> {
+        let count = texts.len();
+        let mut response = self.client.embed(EmbeddingRequest {
+            input: texts,
+            model: self.model.clone(),
+            encoding_format: None,
+            user_id: None,
+            extra_params: if local_only {
+                HashMap::from([(LOCAL_ONLY_PARAM.to_string(), serde_json::Value::Bool(true))])
+            } else {
+                HashMap::new()
+            },
+        }).await?;
+
+        if response.data.len() != count {
+            return Err(LLMError::ParseError(format!(
+                "expected {} embeddings, got {}", count, response.data.len()
+            )));
+        }
+        response.data.sort_by_key(|e| e.index);
+        Ok(response.data.into_iter().map(|e| e.embedding).collect())
+    }
+}
+
+/// Deterministic embedder hashing words into a fixed number of dimensions
+///
+/// Texts sharing words get similar vectors. Meant for tests and offline
+/// use, not semantic quality.
+pub struct HashEmbedder {
+    dimensions: usize,
+}
+
+impl HashEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self { dimensions: dimensions.max(1) }
+    }
+
+    /// Embed one text
+    pub fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            // FNV-1a, stable across platforms and releases
+            let hash = word.to_lowercase().bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+            });
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[(hash % self.dimensions as u64) as usize] += sign;
+        }
+
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        vector
+    }
+}
+
+impl Default for HashEmbedder {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+#[async_trait]
+impl Embedder for HashEmbedder {
+    async fn embed(&self, texts: Vec<String>, _local_only: bool) -> LLMResult<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+}
+
+/// Indexes and retrieves memory chunks
+pub struct SemanticMemory {
+    embedder: Arc<dyn Embedder>,
+    store: Arc<dyn VectorStore>,
+    config: RagConfig,
+}
+
+impl SemanticMemory {
+    pub fn new(embedder: Arc<dyn Embedder>, store: Arc<dyn VectorStore>) -> Self {
+        Self {
+            embedder,
+            store,
+            config: RagConfig::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: RagConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &RagConfig {
+        &self.config
+    }
+
+    /// Index a user or agent message of its conversation
+    ///
+    /// With `local_only` the message is only embedded on this machine.
+    pub async fn index_message(&self, message: &Message, local_only: bool) -> SemanticMemoryResult<usize> {
+        if message.sender == MessageSender::System {
+            return Ok(0);
+        }
+        self.index(
+            MemoryScope::Conversation(message.conversation_id),
+            ChunkSource::Message,
+            message.id,
+            &[message.content.as_str()],
+            local_only,
+        ).await
+    }
+
+    /// Index the documentation entries of a twin, replacing earlier ones
+    ///
+    /// Documentation of a sensitive twin is only embedded on this machine.
+    pub async fn index_twin_documentation(&self, twin: &DigitalTwin) -> SemanticMemoryResult<usize> {
+        let documents: Vec<&str> = twin.metadata.documentation.iter().map(String::as_str).collect();
+        self.store.remove_source(twin.id).await?;
+        self.index(
+            MemoryScope::Twin(twin.id),
+            ChunkSource::TwinDocumentation,
+            twin.id,
+            &documents,
+            twin.is_sensitive(),
+        ).await
+    }
+
+    /// Index the extracted text of an attachment uploaded to a conversation
+    ///
+    /// With `local_only` the text is only embedded on this machine.
+    pub async fn index_attachment(
+        &self,
+        conversation_id: Uuid,
+        attachment: &Attachment,
+        text: &str,
+        local_only: bool,
+    ) -> SemanticMemoryResult<usize> {
+        self.index(
+            MemoryScope::Conversation(conversation_id),
+            ChunkSource::Attachment,
+            attachment.id,
+            &[text],
+            local_only,
+        ).await
+    }
+
+    /// The chunks most similar to `query` within `scopes`
+    ///
+    /// With `local_only` the query is only embedded on this machine.
+    pub async fn retrieve(
+        &self,
+        query: &str,
+        scopes: &[MemoryScope],
+        local_only: bool,
+    ) -> SemanticMemoryResult<Vec<ScoredChunk>> {
+        if query.trim().is_empty() || scopes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let embedding = self.embedder.embed(vec![query.to_string()], local_only).await?
+            .pop()
+            .unwrap_or_default();
+
+        Ok(self.store
+            .search(scopes, &embedding, self.config.top_k, self.config.similarity_threshold)
+            .await?)
+    }
+
+    async fn index(
+        &self,
+        scope: MemoryScope,
+        source: ChunkSource,
+        source_id: Uuid,
+        documents: &[&str],
+        local_only: bool,
+    ) -> SemanticMemoryResult<usize> {
+        let texts: Vec<String> = documents
+            .iter()
+            .flat_map(|document| chunk_text(document, self.config.chunk_size, self.config.chunk_overlap))
+            .collect();
+        if texts.is_empty() {
+            return Ok(0);
+        }
+
+        let embeddings = self.embedder.embed(texts.clone(), local_only).await?;
+        let now = Utc::now();
+        let chunks: Vec<MemoryChunk> = texts
+            .into_iter()
+            .zip(embeddings)
+            .enumerate()
+            .map(|(chunk_index, (content, embedding))| MemoryChunk {
+                id: Uuid::new_v4(),
+                scope,
+                source,
+                source_id,
+                chunk_index,
+                content,
+                embedding,
+                created_at: now,
+            })
+            .collect();
+
+        self.store.upsert(&chunks).await?;
+        let pruned = self.store.prune(scope, self.config.max_entries).await?;
+        if pruned > 0 {
+            tracing::debug!("Pruned {} memory chunks of {:?}", pruned, scope);
+        }
+        Ok(chunks.len())
+    }
+}
+
+/// Split text into chunks of at most `size` characters sharing `overlap`
+///
+/// Chunks end at whitespace where possible.
+fn chunk_text(text: &str, size: usize, overlap: usize) -> Vec<String> {
+    let chars: Vec<char> = text.trim().chars().collect();
+    let size = size.max(1);
+    let overlap = overlap.min(size / 2);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < chars.len() {
+        let mut end = (start + size).min(chars.len());
+        if end < chars.len() {
+            if let Some(space) = chars[start + size / 2..end].iter().rposition(|c| c.is_whitespace()) {
+                end = start + size / 2 + space;
+            }
+        }
+
+        let chunk: String = chars[start..end].iter().collect();
+        if !chunk.trim().is_empty() {
+            chunks.push(chunk.trim().to_string());
+        }
+        if end == chars.len() {
+            break;
+        }
+        start = end.saturating_sub(overlap).max(start + 1);
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::models::conversation::AttachmentType;
+    use crate::core::domain::models::digital_twin::SENSITIVE_TAG;
+    use crate::core::domain::models::TwinType;
+    use crate::core::domain::traits::mock_llm_client::MockLLMClient;
+
+    #[test]
+    fn test_hash_embedder_is_deterministic() {
+        let embedder = HashEmbedder::new(64);
+        let a = embedder.embed_text("Pump P-101 bearing temperature");
+        assert_eq!(a, embedder.embed_text("pump p-101 BEARING temperature"));
+        assert!((cosine_similarity(&a, &a) - 1.0).abs() < 1e-5);
+        assert!(cosine_similarity(&a, &embedder.embed_text("quarterly invoice totals")) < 0.5);
+    }
+
+    #[test]
+    fn test_chunk_text_overlaps_at_whitespace() {
+        let text = "alpha beta gamma delta epsilon zeta eta theta";
+        let chunks = chunk_text(text, 20, 6);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 20));
+        assert!(chunks[0].starts_with("alpha"));
+        assert!(chunks.last().unwrap().ends_with("theta"));
+        assert!(chunk_text("   ", 20, 6).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_ranks_by_similarity_within_scope() {
+        let memory = SemanticMemory::new(Arc::new(HashEmbedder::default()), Arc::new(InMemoryVectorStore::default()))
+            .with_config(RagConfig { similarity_threshold: 0.3, ..Default::default() });
+        let conversation_id = Uuid::new_v4();
+        let other_conversation = Uuid::new_v4();
+
+        for (conversation, text) in [
+            (conversation_id, "The chiller setpoint was lowered to 6 degrees"),
+            (conversation_id, "Lunch is at noon"),
+            (other_conversation, "The chiller setpoint was raised to 9 degrees"),
+        ] {
+            let message = Message::from_user(conversation, "alice".to_string(), text.to_string());
+            assert_eq!(memory.index_message(&message, false).await.unwrap(), 1);
+        }
+
+        let results = memory
+            .retrieve("what is the chiller setpoint", &[MemoryScope::Conversation(conversation_id)], false)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].chunk.content.contains("lowered"));
+        assert_eq!(results[0].chunk.source, ChunkSource::Message);
+    }
+
+    #[tokio::test]
+    async fn test_sensitive_and_local_only_text_is_embedded_locally() {
+        let client = Arc::new(MockLLMClient::replying(vec!["unused"]));
+        let memory = SemanticMemory::new(
+            Arc::new(LLMEmbedder::new(client.clone(), "nomic-embed-text")),
+            Arc::new(InMemoryVectorStore::default()),
+        );
+        let pinned = |request: &EmbeddingRequest| {
+            request.extra_params.get(LOCAL_ONLY_PARAM) == Some(&serde_json::Value::Bool(true))
+        };
+
+        let mut twin = DigitalTwin::new(
+            "Chiller 2".to_string(),
+            "Rooftop chiller".to_string(),
+            TwinType::Device { device_type: "chiller".to_string(), manufacturer: None, model: None },
+        );
+        twin.metadata.documentation.push("Clean the condenser every spring".to_string());
+        twin.metadata.tags.push(SENSITIVE_TAG.to_string());
+        memory.index_twin_documentation(&twin).await.unwrap();
+
+        let conversation_id = Uuid::new_v4();
+        let message = Message::from_user(conversation_id, "alice".to_string(), "Chiller 2 trips".to_string());
+        memory.index_message(&message, true).await.unwrap();
+        let attachment = Attachment {
+            id: Uuid::new_v4(),
+            attachment_type: AttachmentType::File,
+            name: "trip-log.csv".to_string(),
+            size: None,
+            mime_type: None,
+            url: None,
+        };
+        memory.index_attachment(conversation_id, &attachment, "12:00 trip", true).await.unwrap();
+        memory.retrieve("why does it trip", &[MemoryScope::Twin(twin.id)], true).await.unwrap();
+
+        let requests = client.embed_requests();
+        assert_eq!(requests.len(), 4);
+        assert!(requests.iter().all(pinned));
+
+        // Documentation of other twins and other conversations may leave
+        twin.metadata.tags.clear();
+        memory.index_twin_documentation(&twin).await.unwrap();
+        memory.index_message(&message, false).await.unwrap();
+        assert!(client.embed_requests()[4..].iter().all(|request| !pinned(request)));
+    }
+}
@@ -0,0 +1,335 @@
+//! Structured output: chat completions that return JSON conforming to a
+//! JSON Schema.
+//!
+//! The schema is sent as the request's response format, which providers
+//! with a native JSON mode enforce, and spelled out in the system prompt
+//! for those without one. Replies are parsed and validated; an invalid
+//! reply is sent back to the model with the validation errors, up to a
+//! bounded number of repairs.
+
+use crate::core::application::services::usage_accounting::{add_usage, empty_usage};
+use crate::core::domain::traits::llm_client::{
+    ChatCompletionRequest, ChatMessage, LLMClient, LLMError, MessageRole, ResponseFormat, TokenUsage,
+};
+use jsonschema::JSONSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Repair attempts after the first reply
+pub const DEFAULT_MAX_REPAIRS: u32 = 2;
+
+/// Validation errors quoted back to the model in a repair prompt
+const MAX_REPORTED_ERRORS: usize = 10;
+
+/// Structured output error types
+#[derive(Debug, Error)]
+pub enum StructuredOutputError {
+    /// The request to the model failed
+    #[error("LLM request failed: {0}")]
+    Llm(#[from] LLMError),
+
+    /// The schema itself is not a valid JSON Schema
+    #[error("Invalid JSON Schema: {0}")]
+    InvalidSchema(String),
+
+    /// The model's replies did not validate, including after repairs
+    #[error("Reply does not match the schema after {attempts} attempts: {}", errors.join("; "))]
+    Invalid {
+        attempts: u32,
+        errors: Vec<String>,
+        last_reply: String,
+        /// Tokens used by all attempts
+        usage: TokenUsage,
+    },
+
+    /// The validated value does not deserialize into the requested type
+    #[error("Reply does not deserialize: {0}")]
+    Deserialize(String),
+}
+
+/// Result type for structured output operations
+pub type StructuredOutputResult<T> = Result<T, StructuredOutputError>;
+
+/// A validated value and what it took to get it
+#[derive(Debug, Clone)]
+pub struct StructuredReply {
+    pub value: Value,
+    /// Replies requested, including the first
+    pub attempts: u32,
+    /// Tokens used by all attempts
+    pub usage: TokenUsage,
+}
+
+/// Generates JSON values conforming to a schema
+pub struct StructuredOutput {
+    client: Arc<dyn LLMClient>,
+    max_repairs: u32,
+}
+
+impl StructuredOutput {
+    pub fn new(client: Arc<dyn LLMClient>) -> Self {
+        Self {
+            client,
+            max_repairs: DEFAULT_MAX_REPAIRS,
+        }
+    }
+
+    pub fn with_max_repairs(mut self, max_repairs: u32) -> Self {
+        self.max_repairs = max_repairs;
+        self
+    }
+
+    /// Complete `request` with a JSON value valid under `schema`
+    pub async fn generate(&self, request: ChatCompletionRequest, schema: &Value) -> StructuredOutputResult<Value> {
+        Ok(self.generate_reply(request, schema).await?.value)
+    }
+
+    /// Like [`Self::generate`], also reporting the attempts and their usage
+    pub async fn generate_reply(
+        &self,
+        mut request: ChatCompletionRequest,
+        schema: &Value,
+    ) -> StructuredOutputResult<StructuredReply> {
+        let validator = JSONSchema::compile(schema)
+            .map_err(|e| StructuredOutputError::InvalidSchema(e.to_string()))?;
+
+        let instructions = format!(
+            "Reply with only a JSON value, without any other text, that conforms to this JSON Schema:\n{}",
+            serde_json::to_string_pretty(schema).unwrap_or_default()
+        );
+        request.system = Some(match request.system.take() {
+            Some(system) => format!("{}\n\n{}", system, instructions),
+            None => instructions,
+        });
+        if request.response_format.is_none() {
+            request.response_format = Some(ResponseFormat::json_schema(schema.clone()));
+        }
+
+        let request_id = request.request_id.clone();
+        let mut attempts = 0;
+        let mut usage = empty_usage();
+        loop {
+            attempts += 1;
+            request.request_id = if attempts == 1 {
+                request_id.clone()
+            } else {
+                format!("{}-repair-{}", request_id, attempts - 1)
+            };
+
+            let response = self.client.chat_complete(request.clone()).await?;
+            add_usage(&mut usage, &response.usage);
+            let reply = response.choices
+                .into_iter()
+                .next()
+                .and_then(|choice| choice.message.content)
+                .unwrap_or_default();
+
+            let errors = match extract_json(&reply) {
+                Ok(value) => match validate(&validator, &value) {
+                    Ok(()) => return Ok(StructuredReply { value, attempts, usage }),
+                    Err(errors) => errors,
+                },
+                Err(error) => vec![error],
+            };
+
+            if attempts > self.max_repairs {
+                return Err(StructuredOutputError::Invalid {
+                    attempts,
+                    errors,
+                    last_reply: reply,
+                    usage,
+                });
+            }
+            tracing::debug!("Structured reply failed validation (attempt {}): {:?}", attempts, errors);
+            request.messages.push(message(MessageRole::Assistant, reply));
+            request.messages.push(message(MessageRole::User, repair_prompt(&errors)));
+        }
+    }
+
+    /// Complete `request` and deserialize the validated value into `T`
+    pub async fn generate_as<T: DeserializeOwned>(
+        &self,
+        request: ChatCompletionRequest,
+        schema: &Value,
+    ) -> StructuredOutputResult<T> {
+        let value = self.generate(request, schema).await?;
+        serde_json::from_value(value).map_err(|e| StructuredOutputError::Deserialize(e.to_string()))
+    }
+}
+
+fn message(role: MessageRole, content: String) -> ChatMessage {
+    ChatMessage {
+        role,
+        content: Some(content),
+        name: None,
+        tool_call_id: None,
+        tool_calls: None,
+        parts: None,
+    }
+}
+
+fn validate(validator: &JSONSchema, value: &Value) -> Result<(), Vec<String>> {
+    validator.validate(value).map_err(|errors| {
+        errors
+            .map(|error| {
+                let path = error.instance_path.to_string();
+                if path.is_empty() {
+                    error.to_string()
+                } else {
+                    format!("{}: {}", path, error)
+                }
+            })
+            .collect()
+    })
+}
+
+fn repair_prompt(errors: &[String]) -> String {
+    let mut prompt = String::from("Your reply does not conform to the JSON Schema:\n");
+    for error in errors.iter().take(MAX_REPORTED_ERRORS) {
+        prompt.push_str("- ");
+        prompt.push_str(error);
+        prompt.push('\n');
+    }
+    if errors.len() > MAX_REPORTED_ERRORS {
+        prompt.push_str(&format!("- and {} more\n", errors.len() - MAX_REPORTED_ERRORS));
+    }
+    prompt.push_str("Reply with only the corrected JSON.");
+    prompt
+}
+
+/// Parse the JSON value in a reply
+///
+/// Models without a native JSON mode often wrap the value in a Markdown
+/// code fence or a sentence; the outermost object or array is used.
+pub(crate) fn extract_json(reply: &str) -> Result<Value, String> {
+    let trimmed = reply.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Ok(value);
+    }
+
+    let unfenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .map(str::trim);
+    if let Some(Ok(value)) = unfenced.map(serde_json::from_str) {
+        return Ok(value);
+    }
+
+    let start = trimmed.find(['{', '[']);
+    let end = trimmed.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => serde_json::from_str(&trimmed[start..=end])
+            .map_err(|e| format!("the reply is not valid JSON: {}", e)),
+        _ => Err("the reply contains no JSON value".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::traits::llm_client::ResponseFormatType;
+    use crate::core::domain::traits::mock_llm_client::MockLLMClient;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct AlertRule {
+        sensor: String,
+        threshold: f64,
+    }
+
+    fn alert_rule_schema() -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "sensor": { "type": "string" },
+                "threshold": { "type": "number" }
+            },
+            "required": ["sensor", "threshold"]
+        })
+    }
+
+    fn request() -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            request_id: "rule-1".to_string(),
+            messages: vec![message(MessageRole::User, "Alert when the pump runs hot".to_string())],
+            model: "gpt-4o".to_string(),
+            temperature: Some(0.0),
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop_sequences: None,
+            n: None,
+            system: Some("You write alert rules.".to_string()),
+            tools: None,
+            tool_choice: None,
+            response_format: None,
+            user_id: None,
+            conversation_context: None,
+            extra_params: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_extract_json() {
+        assert_eq!(extract_json(" {\"a\": 1} ").unwrap(), serde_json::json!({ "a": 1 }));
+        assert_eq!(extract_json("```json\n[1, 2]\n```").unwrap(), serde_json::json!([1, 2]));
+        assert_eq!(
+            extract_json("Here is the rule: {\"a\": {\"b\": true}}. Let me know!").unwrap(),
+            serde_json::json!({ "a": { "b": true } })
+        );
+        assert!(extract_json("I cannot help with that.").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_repairs_invalid_reply() {
+        let client = Arc::new(MockLLMClient::replying(vec![
+            "```json\n{\"sensor\": \"temperature\", \"threshold\": \"high\"}\n```",
+            "{\"sensor\": \"temperature\", \"threshold\": 85.5}",
+        ]).with_usage(40, 10));
+        let structured = StructuredOutput::new(client.clone());
+
+        let rule: AlertRule = structured.generate_as(request(), &alert_rule_schema()).await.unwrap();
+        assert_eq!(rule, AlertRule { sensor: "temperature".to_string(), threshold: 85.5 });
+
+        let requests = client.requests();
+        assert_eq!(requests.len(), 2);
+        let format = requests[0].response_format.as_ref().unwrap();
+        assert_eq!(format.format_type, ResponseFormatType::JsonSchema);
+        assert!(requests[0].system.as_deref().unwrap().starts_with("You write alert rules."));
+
+        // The repair quotes the failing path back to the model
+        let repair = &requests[1].messages;
+        assert_eq!(repair.len(), 3);
+        assert_eq!(repair[1].role, MessageRole::Assistant);
+        assert!(repair[2].content.as_deref().unwrap().contains("/threshold"));
+        assert_eq!(requests[1].request_id, "rule-1-repair-1");
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_repairs() {
+        let client = Arc::new(MockLLMClient::replying(vec!["not json", "{\"sensor\": 1}"]).with_usage(40, 10));
+        let structured = StructuredOutput::new(client).with_max_repairs(1);
+
+        match structured.generate(request(), &alert_rule_schema()).await {
+            Err(StructuredOutputError::Invalid { attempts, errors, last_reply, usage }) => {
+                assert_eq!(attempts, 2);
+                assert_eq!(errors.len(), 2);
+                assert_eq!(last_reply, "{\"sensor\": 1}");
+                assert_eq!(usage.total_tokens, 100);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let invalid = serde_json::json!({ "type": "no-such-type" });
+        let structured = StructuredOutput::new(Arc::new(MockLLMClient::replying(vec!["{}"])));
+        assert!(matches!(
+            structured.generate(request(), &invalid).await,
+            Err(StructuredOutputError::InvalidSchema(_))
+        ));
+    }
+}
@@ -5,6 +5,8 @@ use crate::core::{
         traits::repository::ToolRepository,
         traits::tool_executor::ToolExecutor,
     },
+    domain::models::AgentId,
+    application::services::execution_governor::ExecutionGovernor,
 };
 use std::sync::Arc;
 use chrono::Utc;
@@ -15,6 +17,7 @@ use serde_json::Value;
 pub struct ToolService {
     tool_repo: Arc<dyn ToolRepository>,
     tool_executors: HashMap<String, Arc<dyn ToolExecutor>>,
+    governor: ExecutionGovernor,
 }
 
 /// Tool registration request
@@ -33,6 +36,8 @@ pub struct ToolRegistration {
 pub struct ToolExecutionRequest {
     pub tool_name: String,
     pub parameters: ToolParameters,
+    /// Agent requesting the execution, for per-agent rate limits
+    pub agent_id: Option<AgentId>,
 }
 
 /// Tool validation result
@@ -51,9 +56,16 @@ impl ToolService {
         Self {
             tool_repo,
             tool_executors,
+            governor: ExecutionGovernor::new(),
         }
     }
 
+    /// Use a custom execution governor, e.g. one with per-agent limits
+    pub fn with_governor(mut self, governor: ExecutionGovernor) -> Self {
+        self.governor = governor;
+        self
+    }
+
     /// Register a new tool
     pub async fn register_tool(
         &self,
@@ -182,13 +194,23 @@ impl ToolService {
         // Validate parameters
         self.validate_parameters(&tool, &request.parameters)?;
 
-        // Execute the tool
-        executor
-            .execute(&tool, &request.parameters)
-            .await
-            .map_err(|e| DomainError::ExternalServiceError(
-                format!("Tool execution failed: {}", e)
-            ))
+        // Execute the tool under its rate, concurrency, timeout and retry limits
+        let governed = self.governor
+            .execute(&tool, request.agent_id, || executor.execute(&tool, &request.parameters))
+            .await?;
+
+        let mut result = governed.value;
+        if let Ok(output) = serde_json::to_vec(&result.output) {
+            self.governor.check_output_size(&tool, output.len() as u64)?;
+        }
+
+        let custom_metrics = std::mem::take(&mut result.metrics.custom_metrics);
+        result.metrics.duration_ms = governed.metrics.duration_ms;
+        result.metrics.retry_count = governed.metrics.retry_count;
+        result.metrics.custom_metrics = governed.metrics.custom_metrics;
+        result.metrics.custom_metrics.extend(custom_metrics);
+
+        Ok(result)
     }
 
     /// Validate tool parameters
@@ -481,6 +503,7 @@ mod tests {
         let exec_request = ToolExecutionRequest {
             tool_name: "test_tool".to_string(),
             parameters,
+            agent_id: None,
         };
         
         let exec_result = service.execute_tool(exec_request).await;
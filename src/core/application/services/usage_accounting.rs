@@ -0,0 +1,896 @@
+//! LLM usage and cost accounting.
+//!
+//! [`UsageAccountant`] records the tokens of every request together with
+//! the agent, conversation, provider and model it served, prices them from
+//! the pricing table and enforces monthly agent budgets. [`MeteredClient`]
+//! wraps a provider client so that this happens for every request.
+
+use crate::core::domain::services::tokenizer::TokenCounter;
+use crate::core::domain::traits::llm_client::{
+    ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, ChatCompletionStream,
+    CompletionChunk, CompletionRequest, CompletionResponse, CompletionStream, EmbeddingRequest,
+    EmbeddingResponse, LLMClient, LLMError, LLMResult, ModelInfo, ModelUsage, TokenUsage,
+    UsagePeriod, UsageStats,
+};
+use crate::core::domain::traits::repository::RepositoryResult;
+use crate::core::domain::traits::usage_store::{
+    AgentBudget, BudgetAction, PricingEntry, UsageFilter, UsageMetering, UsageRecord, UsageStore,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use uuid::Uuid;
+
+/// Request parameter attributing a request to an agent
+///
+/// Chat requests are attributed through their conversation context; this
+/// covers plain completions and chat requests without one.
+pub const AGENT_ID_PARAM: &str = "agent_id";
+
+/// Request parameter attributing a request to a conversation
+pub const CONVERSATION_ID_PARAM: &str = "conversation_id";
+
+/// Currency of costs when no price is known
+const DEFAULT_CURRENCY: &str = "USD";
+
+/// Outcome of a budget check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BudgetDecision {
+    Allow,
+    Downgrade(String),
+}
+
+/// Usage and cost of one agent over a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentCost {
+    /// `None` for requests not made on behalf of an agent
+    pub agent_id: Option<Uuid>,
+    pub requests: u64,
+    pub tokens: TokenUsage,
+    pub cost: f64,
+    pub currency: String,
+    pub monthly_limit: Option<f64>,
+}
+
+/// Records, prices and aggregates LLM usage
+pub struct UsageAccountant {
+    store: Arc<dyn UsageStore>,
+    pricing: RwLock<Option<Arc<Vec<PricingEntry>>>>,
+}
+
+impl UsageAccountant {
+    pub fn new(store: Arc<dyn UsageStore>) -> Self {
+        Self {
+            store,
+            pricing: RwLock::new(None),
+        }
+    }
+
+    /// Record a request; failures are logged, never returned, so
+    /// accounting cannot fail a request
+    pub async fn record(&self, record: UsageRecord) {
+        if let Err(e) = self.store.record(&record).await {
+            tracing::error!("Failed to record LLM usage of request {}: {}", record.request_id, e);
+        }
+    }
+
+    /// Price the tokens of a request on a provider's model
+    ///
+    /// Unknown models cost nothing and are logged. For models named
+    /// `vendor/model`, as on OpenRouter, the vendor's price applies when the
+    /// provider has none.
+    pub async fn price(&self, provider: &str, model: &str, usage: &TokenUsage) -> (f64, String) {
+        let pricing = match self.pricing_table().await {
+            Ok(pricing) => pricing,
+            Err(e) => {
+                tracing::warn!("Failed to load LLM pricing: {}", e);
+                return (0.0, DEFAULT_CURRENCY.to_string());
+            }
+        };
+
+        match lookup_price(&pricing, provider, model) {
+            Some(entry) => (entry.cost(usage), entry.currency.clone()),
+            None => {
+                tracing::debug!("No price for model '{}' on provider '{}'", model, provider);
+                (0.0, DEFAULT_CURRENCY.to_string())
+            }
+        }
+    }
+
+    /// Check an agent's monthly budget before a request
+    ///
+    /// A budget that cannot be loaded is logged and allows the request.
+    /// Once a budget is loaded, failing to load the spending blocks the
+    /// request under a `Block` budget and downgrades it under a
+    /// `Downgrade` budget.
+    pub async fn check_budget(&self, agent_id: Uuid) -> LLMResult<BudgetDecision> {
+        let budget = match self.store.budget(agent_id).await {
+            Ok(Some(budget)) => budget,
+            Ok(None) => return Ok(BudgetDecision::Allow),
+            Err(e) => {
+                tracing::error!("Failed to load the budget of agent {}: {}", agent_id, e);
+                return Ok(BudgetDecision::Allow);
+            }
+        };
+
+        let (month_start, _) = UsagePeriod::ThisMonth.range(Utc::now());
+        let spent = match self.store.agent_cost(agent_id, month_start).await {
+            Ok(spent) => spent,
+            Err(e) => match budget.action {
+                BudgetAction::Block => return Err(storage_error(e)),
+                BudgetAction::Downgrade { model } => {
+                    tracing::error!("Failed to load the spending of agent {}: {}", agent_id, e);
+                    return Ok(BudgetDecision::Downgrade(model));
+                }
+            },
+        };
+        if spent < budget.monthly_limit {
+            return Ok(BudgetDecision::Allow);
+        }
+
+        match budget.action {
+            BudgetAction::Block => Err(LLMError::BudgetExceeded {
+                spent,
+                limit: budget.monthly_limit,
+                currency: budget.currency,
+            }),
+            BudgetAction::Downgrade { model } => Ok(BudgetDecision::Downgrade(model)),
+        }
+    }
+
+    /// Aggregate usage over a period
+    pub async fn stats(&self, period: UsagePeriod, filter: &UsageFilter) -> RepositoryResult<UsageStats> {
+        let (start, end) = period.range(Utc::now());
+        let records = self.store.records(start, end, filter).await?;
+        Ok(aggregate(period, &records))
+    }
+
+    /// Cost per agent over a period, most expensive first
+    pub async fn costs_by_agent(&self, period: UsagePeriod) -> RepositoryResult<Vec<AgentCost>> {
+        let (start, end) = period.range(Utc::now());
+        let records = self.store.records(start, end, &UsageFilter::default()).await?;
+        let budgets: HashMap<Uuid, f64> = self.store.budgets().await?
+            .into_iter()
+            .map(|budget| (budget.agent_id, budget.monthly_limit))
+            .collect();
+
+        let mut costs: HashMap<Option<Uuid>, AgentCost> = HashMap::new();
+        for record in &records {
+            let cost = costs.entry(record.agent_id).or_insert_with(|| AgentCost {
+                agent_id: record.agent_id,
+                requests: 0,
+                tokens: empty_usage(),
+                cost: 0.0,
+                currency: record.currency.clone(),
+                monthly_limit: record.agent_id.and_then(|id| budgets.get(&id).copied()),
+            });
+            cost.requests += 1;
+            add_usage(&mut cost.tokens, &record.usage);
+            cost.cost += record.cost;
+        }
+
+        let mut costs: Vec<AgentCost> = costs.into_values().collect();
+        costs.sort_by(|a, b| b.cost.total_cmp(&a.cost));
+        Ok(costs)
+    }
+
+    pub async fn pricing(&self) -> RepositoryResult<Vec<PricingEntry>> {
+        Ok(self.pricing_table().await?.as_ref().clone())
+    }
+
+    pub async fn set_pricing(&self, entry: PricingEntry) -> RepositoryResult<()> {
+        self.store.set_pricing(&entry).await?;
+        *self.pricing.write().unwrap() = None;
+        Ok(())
+    }
+
+    pub async fn budget(&self, agent_id: Uuid) -> RepositoryResult<Option<AgentBudget>> {
+        self.store.budget(agent_id).await
+    }
+
+    pub async fn set_budget(&self, budget: AgentBudget) -> RepositoryResult<()> {
+        self.store.set_budget(&budget).await
+    }
+
+    pub async fn remove_budget(&self, agent_id: Uuid) -> RepositoryResult<bool> {
+        self.store.remove_budget(agent_id).await
+    }
+
+    /// The pricing table, loaded once and reloaded after changes
+    async fn pricing_table(&self) -> RepositoryResult<Arc<Vec<PricingEntry>>> {
+        if let Some(pricing) = self.pricing.read().unwrap().clone() {
+            return Ok(pricing);
+        }
+        let pricing = Arc::new(self.store.pricing().await?);
+        *self.pricing.write().unwrap() = Some(pricing.clone());
+        Ok(pricing)
+    }
+}
+
+/// Price of a provider's model, falling back to the vendor's price for
+/// `vendor/model` names
+fn lookup_price<'a>(pricing: &'a [PricingEntry], provider: &str, model: &str) -> Option<&'a PricingEntry> {
+    find_price(pricing, provider, model).or_else(|| {
+        model.split_once('/').and_then(|(vendor, name)| find_price(pricing, vendor, name))
+    })
+}
+
+fn find_price<'a>(pricing: &'a [PricingEntry], provider: &str, model: &str) -> Option<&'a PricingEntry> {
+    let model = model.to_lowercase();
+    pricing
+        .iter()
+        .filter(|entry| entry.provider.eq_ignore_ascii_case(provider))
+        .filter(|entry| model.starts_with(&entry.model.to_lowercase()))
+        .max_by_key(|entry| entry.model.len())
+}
+
+fn storage_error(e: impl std::fmt::Display) -> LLMError {
+    LLMError::Other(format!("Usage accounting failed: {}", e))
+}
+
+pub(crate) fn empty_usage() -> TokenUsage {
+    TokenUsage {
+        prompt_tokens: 0,
+        completion_tokens: 0,
+        total_tokens: 0,
+        cached_tokens: None,
+    }
+}
+
+pub(crate) fn add_usage(total: &mut TokenUsage, usage: &TokenUsage) {
+    total.prompt_tokens += usage.prompt_tokens;
+    total.completion_tokens += usage.completion_tokens;
+    total.total_tokens += usage.total_tokens;
+    if let Some(cached) = usage.cached_tokens {
+        total.cached_tokens = Some(total.cached_tokens.unwrap_or(0) + cached);
+    }
+}
+
+/// Sum usage records into usage statistics
+fn aggregate(period: UsagePeriod, records: &[UsageRecord]) -> UsageStats {
+    let mut stats = UsageStats {
+        period,
+        total_requests: records.len() as u64,
+        successful_requests: 0,
+        failed_requests: 0,
+        total_tokens: empty_usage(),
+        total_cost: 0.0,
+        currency: records.first().map_or(DEFAULT_CURRENCY.to_string(), |r| r.currency.clone()),
+        usage_by_model: HashMap::new(),
+        avg_response_time_ms: 0.0,
+    };
+
+    let mut latency_total = 0u64;
+    for record in records {
+        if record.success {
+            stats.successful_requests += 1;
+            latency_total += record.latency_ms;
+        } else {
+            stats.failed_requests += 1;
+        }
+        add_usage(&mut stats.total_tokens, &record.usage);
+        stats.total_cost += record.cost;
+
+        let model = stats.usage_by_model
+            .entry(format!("{}/{}", record.provider, record.model))
+            .or_insert_with(|| ModelUsage {
+                requests: 0,
+                tokens: empty_usage(),
+                cost: 0.0,
+            });
+        model.requests += 1;
+        add_usage(&mut model.tokens, &record.usage);
+        model.cost += record.cost;
+    }
+
+    if stats.successful_requests > 0 {
+        stats.avg_response_time_ms = latency_total as f64 / stats.successful_requests as f64;
+    }
+    stats
+}
+
+/// Agent and conversation a request is made for
+#[derive(Debug, Clone, Copy, Default)]
+struct Attribution {
+    agent_id: Option<Uuid>,
+    conversation_id: Option<Uuid>,
+}
+
+impl Attribution {
+    fn from_params(extra_params: &HashMap<String, serde_json::Value>) -> Self {
+        let uuid = |key: &str| {
+            extra_params.get(key)
+                .and_then(|v| v.as_str())
+                .and_then(|v| Uuid::parse_str(v).ok())
+        };
+        Self {
+            agent_id: uuid(AGENT_ID_PARAM),
+            conversation_id: uuid(CONVERSATION_ID_PARAM),
+        }
+    }
+
+    fn for_chat(request: &ChatCompletionRequest) -> Self {
+        match &request.conversation_context {
+            Some(context) => Self {
+                agent_id: Some(context.agent_id),
+                conversation_id: Some(context.conversation_id),
+            },
+            None => Self::from_params(&request.extra_params),
+        }
+    }
+}
+
+#[async_trait]
+impl UsageMetering for UsageAccountant {
+    fn meter(self: Arc<Self>, provider: &str, client: Arc<dyn LLMClient>) -> Arc<dyn LLMClient> {
+        Arc::new(MeteredClient::new(client, provider, self))
+    }
+
+    async fn usage(&self, period: UsagePeriod) -> LLMResult<UsageStats> {
+        self.stats(period, &UsageFilter::default()).await.map_err(storage_error)
+    }
+
+    async fn cost_per_1k(&self, provider: &str, model: &str) -> Option<f64> {
+        let pricing = match self.pricing_table().await {
+            Ok(pricing) => pricing,
+            Err(e) => {
+                tracing::warn!("Failed to load LLM pricing: {}", e);
+                return None;
+            }
+        };
+        lookup_price(&pricing, provider, model).map(|entry| (entry.input_cost_per_1k + entry.output_cost_per_1k) / 2.0)
+    }
+}
+
+/// LLM client recording the usage of a provider's requests
+pub struct MeteredClient {
+    inner: Arc<dyn LLMClient>,
+    provider: String,
+    accountant: Arc<UsageAccountant>,
+}
+
+impl MeteredClient {
+    pub fn new(inner: Arc<dyn LLMClient>, provider: impl Into<String>, accountant: Arc<UsageAccountant>) -> Self {
+        Self {
+            inner,
+            provider: provider.into(),
+            accountant,
+        }
+    }
+
+    /// Apply the agent's budget; returns the model to use
+    async fn budgeted_model(&self, attribution: Attribution, model: &str) -> LLMResult<String> {
+        let Some(agent_id) = attribution.agent_id else {
+            return Ok(model.to_string());
+        };
+        match self.accountant.check_budget(agent_id).await? {
+            BudgetDecision::Allow => Ok(model.to_string()),
+            BudgetDecision::Downgrade(downgrade) => {
+                tracing::info!("Agent {} is over budget; using '{}' instead of '{}'", agent_id, downgrade, model);
+                Ok(downgrade)
+            }
+        }
+    }
+
+    async fn record<T>(
+        &self,
+        request_id: &str,
+        model: &str,
+        attribution: Attribution,
+        started: Instant,
+        result: &LLMResult<T>,
+        usage: impl FnOnce(&T) -> (TokenUsage, String),
+    ) {
+        let (usage, model) = match result {
+            Ok(response) => usage(response),
+            Err(_) => (empty_usage(), model.to_string()),
+        };
+        self.accountant.record(self.usage_record(
+            request_id,
+            model,
+            attribution,
+            usage,
+            result.is_ok(),
+            started.elapsed().as_millis() as u64,
+            false,
+        ).await).await;
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn usage_record(
+        &self,
+        request_id: &str,
+        model: String,
+        attribution: Attribution,
+        usage: TokenUsage,
+        success: bool,
+        latency_ms: u64,
+        estimated: bool,
+    ) -> UsageRecord {
+        let (cost, currency) = self.accountant.price(&self.provider, &model, &usage).await;
+        UsageRecord {
+            id: Uuid::new_v4(),
+            request_id: request_id.to_string(),
+            provider: self.provider.clone(),
+            model,
+            agent_id: attribution.agent_id,
+            conversation_id: attribution.conversation_id,
+            usage,
+            cost,
+            currency,
+            success,
+            latency_ms,
+            estimated,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn pending(&self, request_id: &str, model: &str, attribution: Attribution, prompt_tokens: u32) -> PendingUsage {
+        PendingUsage {
+            client: MeteredClient {
+                inner: self.inner.clone(),
+                provider: self.provider.clone(),
+                accountant: self.accountant.clone(),
+            },
+            request_id: request_id.to_string(),
+            model: model.to_string(),
+            attribution,
+            prompt_tokens,
+            text: String::new(),
+            started: Instant::now(),
+        }
+    }
+}
+
+#[async_trait]
+impl LLMClient for MeteredClient {
+    async fn complete(&self, mut request: CompletionRequest) -> LLMResult<CompletionResponse> {
+        let attribution = Attribution::from_params(&request.extra_params);
+        request.model = self.budgeted_model(attribution, &request.model).await?;
+
+        let started = Instant::now();
+        let (request_id, model) = (request.request_id.clone(), request.model.clone());
+        let result = self.inner.complete(request).await;
+        self.record(&request_id, &model, attribution, started, &result, |response| {
+            (response.usage.clone(), response.model.clone())
+        }).await;
+        result
+    }
+
+    async fn chat_complete(&self, mut request: ChatCompletionRequest) -> LLMResult<ChatCompletionResponse> {
+        let attribution = Attribution::for_chat(&request);
+        request.model = self.budgeted_model(attribution, &request.model).await?;
+
+        let started = Instant::now();
+        let (request_id, model) = (request.request_id.clone(), request.model.clone());
+        let result = self.inner.chat_complete(request).await;
+        self.record(&request_id, &model, attribution, started, &result, |response| {
+            (response.usage.clone(), response.model.clone())
+        }).await;
+        result
+    }
+
+    /// Streams report no usage, so tokens are counted as they arrive
+    async fn stream_complete(&self, mut request: CompletionRequest) -> LLMResult<Box<dyn CompletionStream>> {
+        let attribution = Attribution::from_params(&request.extra_params);
+        request.model = self.budgeted_model(attribution, &request.model).await?;
+
+        let prompt_tokens = TokenCounter::for_model(Some(&request.model)).count(&request.prompt);
+        let pending = self.pending(&request.request_id, &request.model, attribution, prompt_tokens);
+        match self.inner.stream_complete(request).await {
+            Ok(stream) => Ok(Box::new(MeteredCompletionStream { inner: stream, pending: Some(pending) })),
+            Err(e) => {
+                pending.finish(false).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Streams report no usage, so tokens are counted as they arrive
+    async fn stream_chat_complete(&self, mut request: ChatCompletionRequest) -> LLMResult<Box<dyn ChatCompletionStream>> {
+        let attribution = Attribution::for_chat(&request);
+        request.model = self.budgeted_model(attribution, &request.model).await?;
+
+        let prompt_tokens = TokenCounter::for_model(Some(&request.model))
+            .count_chat(&request.messages, request.tools.as_deref().unwrap_or(&[]));
+        let pending = self.pending(&request.request_id, &request.model, attribution, prompt_tokens);
+        match self.inner.stream_chat_complete(request).await {
+            Ok(stream) => Ok(Box::new(MeteredChatStream { inner: stream, pending: Some(pending) })),
+            Err(e) => {
+                pending.finish(false).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> LLMResult<EmbeddingResponse> {
+        let started = Instant::now();
+        let (request_id, model) = (Uuid::new_v4().to_string(), request.model.clone());
+        let result = self.inner.embed(request).await;
+        self.record(&request_id, &model, Attribution::default(), started, &result, |response| {
+            let usage = TokenUsage {
+                prompt_tokens: response.usage.prompt_tokens,
+                completion_tokens: 0,
+                total_tokens: response.usage.total_tokens,
+                cached_tokens: None,
+            };
+            (usage, response.model.clone())
+        }).await;
+        result
+    }
+
+    async fn list_models(&self) -> LLMResult<Vec<ModelInfo>> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model_info(&self, model_id: &str) -> LLMResult<ModelInfo> {
+        self.inner.get_model_info(model_id).await
+    }
+
+    async fn validate_credentials(&self) -> LLMResult<bool> {
+        self.inner.validate_credentials().await
+    }
+
+    /// Usage of this provider from the accounting records
+    async fn get_usage(&self, period: UsagePeriod) -> LLMResult<UsageStats> {
+        let filter = UsageFilter {
+            provider: Some(self.provider.clone()),
+            ..UsageFilter::default()
+        };
+        self.accountant.stats(period, &filter).await.map_err(storage_error)
+    }
+
+    async fn cancel_request(&self, request_id: &str) -> LLMResult<()> {
+        self.inner.cancel_request(request_id).await
+    }
+}
+
+/// Usage of a stream, recorded when the stream ends
+struct PendingUsage {
+    client: MeteredClient,
+    request_id: String,
+    model: String,
+    attribution: Attribution,
+    prompt_tokens: u32,
+    text: String,
+    started: Instant,
+}
+
+impl PendingUsage {
+    async fn finish(self, success: bool) {
+        let completion_tokens = TokenCounter::for_model(Some(&self.model)).count(&self.text);
+        let usage = TokenUsage {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens,
+            total_tokens: self.prompt_tokens + completion_tokens,
+            cached_tokens: None,
+        };
+        let latency_ms = self.started.elapsed().as_millis() as u64;
+        let record = self.client.usage_record(
+            &self.request_id,
+            self.model.clone(),
+            self.attribution,
+            usage,
+            success,
+            latency_ms,
+            true,
+        ).await;
+        self.client.accountant.record(record).await;
+    }
+}
+
+struct MeteredCompletionStream {
+    inner: Box<dyn CompletionStream>,
+    pending: Option<PendingUsage>,
+}
+
+#[async_trait]
+impl CompletionStream for MeteredCompletionStream {
+    async fn next_chunk(&mut self) -> Option<LLMResult<CompletionChunk>> {
+        let chunk = self.inner.next_chunk().await;
+        match &chunk {
+            Some(Ok(chunk)) => {
+                if let Some(pending) = &mut self.pending {
+                    pending.text.push_str(&chunk.text);
+                }
+                if chunk.finish_reason.is_some() {
+                    self.finish(true).await;
+                }
+            }
+            Some(Err(_)) => self.finish(false).await,
+            None => self.finish(true).await,
+        }
+        chunk
+    }
+
+    async fn cancel(&mut self) -> LLMResult<()> {
+        self.finish(true).await;
+        self.inner.cancel().await
+    }
+}
+
+impl MeteredCompletionStream {
+    async fn finish(&mut self, success: bool) {
+        if let Some(pending) = self.pending.take() {
+            pending.finish(success).await;
+        }
+    }
+}
+
+struct MeteredChatStream {
+    inner: Box<dyn ChatCompletionStream>,
+    pending: Option<PendingUsage>,
+}
+
+#[async_trait]
+impl ChatCompletionStream for MeteredChatStream {
+    async fn next_chunk(&mut self) -> Option<LLMResult<ChatCompletionChunk>> {
+        let chunk = self.inner.next_chunk().await;
+        match &chunk {
+            Some(Ok(chunk)) => {
+                if let (Some(pending), Some(content)) = (&mut self.pending, &chunk.delta.content) {
+                    pending.text.push_str(content);
+                }
+                if chunk.finish_reason.is_some() {
+                    self.finish(true).await;
+                }
+            }
+            Some(Err(_)) => self.finish(false).await,
+            None => self.finish(true).await,
+        }
+        chunk
+    }
+
+    async fn cancel(&mut self) -> LLMResult<()> {
+        self.finish(true).await;
+        self.inner.cancel().await
+    }
+}
+
+impl MeteredChatStream {
+    async fn finish(&mut self, success: bool) {
+        if let Some(pending) = self.pending.take() {
+            pending.finish(success).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::traits::llm_client::ConversationContext;
+    use crate::core::domain::traits::mock_llm_client::MockLLMClient;
+    use crate::core::domain::traits::repository::RepositoryError;
+    use chrono::DateTime;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemoryUsageStore {
+        records: Mutex<Vec<UsageRecord>>,
+        pricing: Mutex<Vec<PricingEntry>>,
+        budgets: Mutex<HashMap<Uuid, AgentBudget>>,
+        /// Store methods that fail, by name
+        failing: Mutex<Vec<&'static str>>,
+    }
+
+    impl MemoryUsageStore {
+        fn check(&self, method: &'static str) -> RepositoryResult<()> {
+            if self.failing.lock().unwrap().contains(&method) {
+                return Err(RepositoryError::DatabaseError("database is locked".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl UsageStore for MemoryUsageStore {
+        async fn record(&self, record: &UsageRecord) -> RepositoryResult<()> {
+            self.records.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+
+        async fn records(&self, start: DateTime<Utc>, end: DateTime<Utc>, filter: &UsageFilter) -> RepositoryResult<Vec<UsageRecord>> {
+            Ok(self.records.lock().unwrap()
+                .iter()
+                .filter(|r| r.created_at >= start && r.created_at <= end)
+                .filter(|r| filter.agent_id.is_none() || filter.agent_id == r.agent_id)
+                .filter(|r| filter.provider.is_none() || filter.provider.as_deref() == Some(r.provider.as_str()))
+                .cloned()
+                .collect())
+        }
+
+        async fn agent_cost(&self, agent_id: Uuid, since: DateTime<Utc>) -> RepositoryResult<f64> {
+            self.check("agent_cost")?;
+            Ok(self.records.lock().unwrap()
+                .iter()
+                .filter(|r| r.agent_id == Some(agent_id) && r.created_at >= since)
+                .map(|r| r.cost)
+                .sum())
+        }
+
+        async fn pricing(&self) -> RepositoryResult<Vec<PricingEntry>> {
+            Ok(self.pricing.lock().unwrap().clone())
+        }
+
+        async fn set_pricing(&self, entry: &PricingEntry) -> RepositoryResult<()> {
+            let mut pricing = self.pricing.lock().unwrap();
+            pricing.retain(|e| !(e.provider == entry.provider && e.model == entry.model));
+            pricing.push(entry.clone());
+            Ok(())
+        }
+
+        async fn budget(&self, agent_id: Uuid) -> RepositoryResult<Option<AgentBudget>> {
+            self.check("budget")?;
+            Ok(self.budgets.lock().unwrap().get(&agent_id).cloned())
+        }
+
+        async fn budgets(&self) -> RepositoryResult<Vec<AgentBudget>> {
+            Ok(self.budgets.lock().unwrap().values().cloned().collect())
+        }
+
+        async fn set_budget(&self, budget: &AgentBudget) -> RepositoryResult<()> {
+            self.budgets.lock().unwrap().insert(budget.agent_id, budget.clone());
+            Ok(())
+        }
+
+        async fn remove_budget(&self, agent_id: Uuid) -> RepositoryResult<bool> {
+            Ok(self.budgets.lock().unwrap().remove(&agent_id).is_some())
+        }
+    }
+
+    fn price(provider: &str, model: &str, input: f64, output: f64) -> PricingEntry {
+        PricingEntry {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            input_cost_per_1k: input,
+            output_cost_per_1k: output,
+            currency: "USD".to_string(),
+        }
+    }
+
+    fn request(model: &str, agent_id: Uuid) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            request_id: Uuid::new_v4().to_string(),
+            messages: Vec::new(),
+            model: model.to_string(),
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop_sequences: None,
+            n: None,
+            system: None,
+            tools: None,
+            tool_choice: None,
+            response_format: None,
+            user_id: None,
+            conversation_context: Some(ConversationContext {
+                conversation_id: Uuid::new_v4(),
+                agent_id,
+                parent_message_id: None,
+                metadata: HashMap::new(),
+            }),
+            extra_params: HashMap::new(),
+        }
+    }
+
+    async fn setup() -> (Arc<MemoryUsageStore>, Arc<UsageAccountant>, MeteredClient, Arc<MockLLMClient>) {
+        let store = Arc::new(MemoryUsageStore::default());
+        let accountant = Arc::new(UsageAccountant::new(store.clone()));
+        accountant.set_pricing(price("openai", "gpt-4o", 0.0025, 0.01)).await.unwrap();
+        accountant.set_pricing(price("openai", "gpt-4o-mini", 0.00015, 0.0006)).await.unwrap();
+        accountant.set_pricing(price("anthropic", "claude-3-5-sonnet", 0.003, 0.015)).await.unwrap();
+        let inner = Arc::new(MockLLMClient::replying(vec!["ok"]).with_usage(1000, 500));
+        let client = MeteredClient::new(inner.clone(), "openai", accountant.clone());
+        (store, accountant, client, inner)
+    }
+
+    #[tokio::test]
+    async fn test_prices_by_longest_prefix() {
+        let (_, accountant, _, _) = setup().await;
+        let usage = TokenUsage {
+            prompt_tokens: 1000,
+            completion_tokens: 1000,
+            total_tokens: 2000,
+            cached_tokens: None,
+        };
+
+        let (cost, currency) = accountant.price("openai", "gpt-4o-mini-2024-07-18", &usage).await;
+        assert!((cost - 0.00075).abs() < 1e-9);
+        assert_eq!(currency, "USD");
+        let (cost, _) = accountant.price("openai", "gpt-4o-2024-08-06", &usage).await;
+        assert!((cost - 0.0125).abs() < 1e-9);
+
+        // OpenRouter model names fall back to the vendor's price
+        let (cost, _) = accountant.price("openrouter", "anthropic/claude-3-5-sonnet", &usage).await;
+        assert!((cost - 0.018).abs() < 1e-9);
+        assert_eq!(accountant.price("openai", "davinci", &usage).await.0, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_records_and_aggregates_usage() {
+        let (_, accountant, client, _) = setup().await;
+        let (agent_a, agent_b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        client.chat_complete(request("gpt-4o", agent_a)).await.unwrap();
+        client.chat_complete(request("gpt-4o", agent_a)).await.unwrap();
+        client.chat_complete(request("gpt-4o-mini", agent_b)).await.unwrap();
+
+        let stats = accountant.stats(UsagePeriod::Today, &UsageFilter::default()).await.unwrap();
+        assert_eq!(stats.total_requests, 3);
+        assert_eq!(stats.successful_requests, 3);
+        assert_eq!(stats.total_tokens.total_tokens, 4500);
+        assert_eq!(stats.usage_by_model["openai/gpt-4o"].requests, 2);
+        assert!((stats.total_cost - (2.0 * 0.0075 + 0.00045)).abs() < 1e-9);
+
+        let stats = client.get_usage(UsagePeriod::ThisMonth).await.unwrap();
+        assert_eq!(stats.total_requests, 3);
+        let filter = UsageFilter { agent_id: Some(agent_b), ..UsageFilter::default() };
+        let stats = accountant.stats(UsagePeriod::Last30Days, &filter).await.unwrap();
+        assert_eq!(stats.total_requests, 1);
+
+        let costs = accountant.costs_by_agent(UsagePeriod::ThisMonth).await.unwrap();
+        assert_eq!(costs[0].agent_id, Some(agent_a));
+        assert_eq!(costs[0].requests, 2);
+        assert_eq!(costs[1].agent_id, Some(agent_b));
+    }
+
+    #[tokio::test]
+    async fn test_budgets_block_or_downgrade() {
+        let (_, accountant, client, inner) = setup().await;
+        let (blocked, downgraded) = (Uuid::new_v4(), Uuid::new_v4());
+        accountant.set_budget(AgentBudget {
+            agent_id: blocked,
+            monthly_limit: 0.01,
+            currency: "USD".to_string(),
+            action: BudgetAction::Block,
+        }).await.unwrap();
+        accountant.set_budget(AgentBudget {
+            agent_id: downgraded,
+            monthly_limit: 0.01,
+            currency: "USD".to_string(),
+            action: BudgetAction::Downgrade { model: "gpt-4o-mini".to_string() },
+        }).await.unwrap();
+
+        // 0.0075 per request: the second still fits, the third is over
+        for _ in 0..2 {
+            client.chat_complete(request("gpt-4o", blocked)).await.unwrap();
+            client.chat_complete(request("gpt-4o", downgraded)).await.unwrap();
+        }
+        let result = client.chat_complete(request("gpt-4o", blocked)).await;
+        assert!(matches!(result, Err(LLMError::BudgetExceeded { .. })));
+
+        let response = client.chat_complete(request("gpt-4o", downgraded)).await.unwrap();
+        assert_eq!(response.model, "gpt-4o-mini");
+        assert_eq!(inner.requests().last().unwrap().model, "gpt-4o-mini");
+    }
+
+    #[tokio::test]
+    async fn test_budget_store_failures() {
+        let (store, accountant, client, _) = setup().await;
+        let agent_id = Uuid::new_v4();
+        accountant.set_budget(AgentBudget {
+            agent_id,
+            monthly_limit: 0.0,
+            currency: "USD".to_string(),
+            action: BudgetAction::Block,
+        }).await.unwrap();
+        let result = client.chat_complete(request("gpt-4o", agent_id)).await;
+        assert!(matches!(result, Err(LLMError::BudgetExceeded { .. })));
+
+        // Unknown spending never lets a request past a blocking budget
+        store.failing.lock().unwrap().push("agent_cost");
+        assert!(accountant.check_budget(agent_id).await.is_err());
+        assert!(client.chat_complete(request("gpt-4o", agent_id)).await.is_err());
+
+        // A budget that cannot be loaded is not enforced
+        store.failing.lock().unwrap().push("budget");
+        assert_eq!(accountant.check_budget(agent_id).await.unwrap(), BudgetDecision::Allow);
+        let response = client.chat_complete(request("gpt-4o", agent_id)).await.unwrap();
+        assert_eq!(response.model, "gpt-4o");
+    }
+}
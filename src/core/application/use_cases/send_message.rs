@@ -7,7 +7,7 @@ use crate::core::domain::{
 };
 use crate::core::application::services::{
     MemoryManager, MemoryConfig, MemoryStrategy, TokenModel,
-    PromptManager, VersionedPrompt,
+    PromptManager, VersionedPrompt, SummaryStore, SemanticMemory, PromptTemplates,
 };
 use std::sync::Arc;
 use chrono::Utc;
@@ -34,8 +34,11 @@ pub struct SendMessageUseCase {
     conversation_repo: Arc<dyn ConversationRepository>,
     agent_repo: Arc<dyn AgentRepository>,
     llm_client: Arc<dyn LLMClient>,
-    memory_manager: MemoryManager,
+    memory_config: MemoryConfig,
+    summary_store: Option<Arc<dyn SummaryStore>>,
+    semantic_memory: Option<Arc<SemanticMemory>>,
     prompt_manager: PromptManager,
+    prompt_templates: Option<Arc<PromptTemplates>>,
 }
 
 impl SendMessageUseCase {
@@ -48,16 +51,70 @@ impl SendMessageUseCase {
             conversation_repo,
             agent_repo,
             llm_client,
-            memory_manager: MemoryManager::new(MemoryConfig::default()),
+            memory_config: MemoryConfig::default(),
+            summary_store: None,
+            semantic_memory: None,
             prompt_manager: PromptManager::new(),
+            prompt_templates: None,
         }
     }
 
     pub fn with_memory_config(mut self, config: MemoryConfig) -> Self {
-        self.memory_manager = MemoryManager::new(config);
+        self.memory_config = config;
         self
     }
 
+    /// Persist conversation summaries so later messages reuse them
+    pub fn with_summary_store(mut self, store: Arc<dyn SummaryStore>) -> Self {
+        self.summary_store = Some(store);
+        self
+    }
+
+    /// Index messages and retrieve relevant ones from `memory`
+    pub fn with_semantic_memory(mut self, memory: Arc<SemanticMemory>) -> Self {
+        self.semantic_memory = Some(memory);
+        self
+    }
+
+    /// Render system prompts from the templates of `templates`
+    pub fn with_prompt_templates(mut self, templates: Arc<PromptTemplates>) -> Self {
+        self.prompt_templates = Some(templates);
+        self
+    }
+
+    /// System prompt of `agent` in `conversation`
+    ///
+    /// Rendered from the system prompt template for the first twin of the
+    /// conversation; without templates, or if rendering fails, the agent
+    /// instructions are followed by the conversation context.
+    async fn system_prompt(&self, agent: &Agent, conversation: &Conversation) -> String {
+        let context = conversation.context.as_deref();
+        if let Some(templates) = &self.prompt_templates {
+            let twin_id = conversation.twin_ids().first().copied();
+            match templates.render_system_prompt(&agent.instructions, context, twin_id).await {
+                Ok(rendered) => return rendered.text,
+                Err(e) => tracing::warn!("Failed to render the system prompt: {}", e),
+            }
+        }
+        match context {
+            Some(context) => format!("{}\n\nContext: {}", agent.instructions, context),
+            None => agent.instructions.clone(),
+        }
+    }
+
+    /// Memory of a conversation with `agent`, summarizing with its model
+    fn memory_manager(&self, agent: &Agent) -> MemoryManager {
+        let mut manager = MemoryManager::new(self.memory_config.clone())
+            .with_summarizer(self.llm_client.clone(), agent.configuration.model.clone());
+        if let Some(store) = &self.summary_store {
+            manager = manager.with_summary_store(store.clone());
+        }
+        if let Some(memory) = &self.semantic_memory {
+            manager = manager.with_semantic_memory(memory.clone());
+        }
+        manager
+    }
+
     pub async fn execute(
         &self,
         command: SendMessageCommand,
@@ -77,9 +134,10 @@ impl SendMessageUseCase {
             .await
             .map_err(|e| DomainError::RepositoryError(e.to_string()))?
             .ok_or_else(|| DomainError::NotFound("Agent not found".to_string()))?;
+        let memory_manager = self.memory_manager(&agent);
 
         // Create user message with token count
-        let user_message_tokens = self.memory_manager.estimate_tokens(&command.content);
+        let user_message_tokens = memory_manager.estimate_tokens(&command.content);
         let user_message = Message {
             role: "user".to_string(),
             content: command.content.clone(),
@@ -94,18 +152,21 @@ impl SendMessageUseCase {
         conversation.messages.push(user_message.clone());
 
         // Build context window using memory manager
-        let context_window = self.memory_manager.build_context_window(&conversation.messages);
+        let context_window = memory_manager
+            .build_conversation_context(conversation.id, &conversation.twin_ids(), &conversation.messages, false)
+            .await;
         let context_window_size = context_window.messages.len();
 
         // Prepare messages for LLM
         let mut llm_messages = vec![];
         
-        // Add system message from agent instructions
-        if !agent.instructions.is_empty() {
-            let system_tokens = self.memory_manager.estimate_tokens(&agent.instructions);
+        // Add the system prompt rendered from the agent instructions and context
+        let system_prompt = self.system_prompt(&agent, &conversation).await;
+        if !system_prompt.trim().is_empty() {
+            let system_tokens = memory_manager.estimate_tokens(&system_prompt);
             llm_messages.push(Message {
                 role: "system".to_string(),
-                content: agent.instructions.clone(),
+                content: system_prompt,
                 timestamp: Utc::now(),
                 metadata: Some(serde_json::json!({
                     "token_count": system_tokens,
@@ -114,16 +175,15 @@ impl SendMessageUseCase {
             });
         }
 
-        // Add context if available
-        if let Some(context) = &conversation.context {
-            let context_tokens = self.memory_manager.estimate_tokens(context);
+        // Add the summary of the messages before the window
+        if let Some(summary) = &context_window.summary {
             llm_messages.push(Message {
                 role: "system".to_string(),
-                content: format!("Context: {}", context),
-                timestamp: Utc::now(),
+                content: format!("Summary of the earlier conversation: {}", summary.content),
+                timestamp: summary.created_at,
                 metadata: Some(serde_json::json!({
-                    "token_count": context_tokens,
-                    "type": "context"
+                    "token_count": summary.token_count,
+                    "type": "summary"
                 })),
             });
         }
@@ -152,7 +212,7 @@ impl SendMessageUseCase {
             .map_err(|e| DomainError::ExternalServiceError(e.to_string()))?;
 
         // Estimate tokens for response
-        let response_tokens = self.memory_manager.estimate_tokens(&llm_response);
+        let response_tokens = memory_manager.estimate_tokens(&llm_response);
 
         // Create assistant message with token metadata
         let assistant_message = Message {
@@ -176,9 +236,11 @@ impl SendMessageUseCase {
             .update(&conversation)
             .await
             .map_err(|e| DomainError::RepositoryError(e.to_string()))?;
+        memory_manager.remember(&user_message, false).await;
+        memory_manager.remember(&assistant_message, false).await;
 
         // Calculate token statistics
-        let token_stats = self.memory_manager.calculate_token_stats(&conversation.messages);
+        let token_stats = memory_manager.calculate_token_stats(&conversation.messages);
 
         Ok(SendMessageResponse {
             conversation,
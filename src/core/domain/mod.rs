@@ -6,6 +6,7 @@
 
 pub mod errors;
 pub mod models;
+pub mod services;
 pub mod traits;
 pub mod value_objects;
 
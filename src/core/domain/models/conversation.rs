@@ -7,6 +7,9 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Custom metadata field listing the twins a conversation is about
+pub const TWIN_IDS_FIELD: &str = "twin_ids";
+
 /// Represents a conversation between a user and one or more digital twin agents.
 ///
 /// A conversation maintains the full context and history of interactions,
@@ -259,6 +262,14 @@ impl Conversation {
     pub fn is_active(&self) -> bool {
         matches!(self.state, ConversationState::Active)
     }
+    
+    /// Gets the twins the conversation is about, from its metadata.
+    pub fn twin_ids(&self) -> Vec<Uuid> {
+        self.metadata.custom_fields
+            .get(TWIN_IDS_FIELD)
+            .and_then(|ids| serde_json::from_value(ids.clone()).ok())
+            .unwrap_or_default()
+    }
 }
 
 impl Message {
@@ -398,4 +409,17 @@ mod tests {
         assert_eq!(conversation.messages.len(), 1);
         assert!(conversation.updated_at > original_updated);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_conversation_twin_ids() {
+        let mut conversation = Conversation::new("Test".to_string(), vec![]);
+        assert!(conversation.twin_ids().is_empty());
+        
+        let twin_id = Uuid::new_v4();
+        conversation.metadata.custom_fields.insert(
+            TWIN_IDS_FIELD.to_string(),
+            serde_json::json!([twin_id]),
+        );
+        assert_eq!(conversation.twin_ids(), vec![twin_id]);
+    }
+}
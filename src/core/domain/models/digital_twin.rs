@@ -8,6 +8,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
+/// Tag marking a twin whose data must only be sent to local LLM providers
+pub const SENSITIVE_TAG: &str = "sensitive";
+
 /// Represents a Digital Twin - a virtual replica of a real-world entity.
 ///
 /// A Digital Twin maintains synchronized state with its physical counterpart,
@@ -193,8 +196,9 @@ pub struct ConnectionConfig {
     /// Connection string or endpoint
     pub endpoint: String,
     
-    /// Authentication credentials (encrypted)
-    pub credentials: Option<HashMap<String, String>>,
+    /// ID of the credentials secret in the vault (never the plaintext)
+    #[serde(default)]
+    pub credentials_secret_id: Option<Uuid>,
     
     /// Connection timeout in seconds
     pub timeout_seconds: u32,
@@ -584,6 +588,11 @@ impl DigitalTwin {
             .filter(|ds| ds.active)
             .collect()
     }
+    
+    /// Checks if the twin is tagged as sensitive, keeping its data local.
+    pub fn is_sensitive(&self) -> bool {
+        self.metadata.tags.iter().any(|tag| tag.eq_ignore_ascii_case(SENSITIVE_TAG))
+    }
 }
 
 impl Default for TwinProperties {
@@ -735,4 +744,20 @@ mod tests {
         twin.sync_config.mode = SyncMode::RealTime;
         assert!(!twin.needs_sync());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_sensitive_tag() {
+        let mut twin = DigitalTwin::new(
+            "Reactor".to_string(),
+            "Description".to_string(),
+            TwinType::System {
+                system_type: "test".to_string(),
+                components: vec![],
+            },
+        );
+        assert!(!twin.is_sensitive());
+        
+        twin.metadata.tags.push("Sensitive".to_string());
+        assert!(twin.is_sensitive());
+    }
+}
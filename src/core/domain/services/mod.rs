@@ -0,0 +1,8 @@
+//! Domain services for the Digital Twin Desktop application.
+//!
+//! Logic over domain types that holds no state of its own and that both
+//! the application and infrastructure layers rely on.
+
+pub mod tokenizer;
+
+pub use tokenizer::{TokenCounter, TokenModel};
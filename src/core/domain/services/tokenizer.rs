@@ -0,0 +1,296 @@
+//! Token counting for context budgeting.
+//!
+//! GPT models are counted with their BPE vocabularies (cl100k_base and
+//! o200k_base, bundled with the binary). Other models use an estimator
+//! that counts by character class, which holds up far better than a fixed
+//! bytes-per-token ratio on code, JSON and non-Latin scripts.
+
+use crate::core::domain::traits::llm_client::{ChatMessage, ContentPart, ToolDefinition};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+
+/// Tokens added per chat message for role and delimiters
+pub const MESSAGE_OVERHEAD_TOKENS: u32 = 3;
+
+/// Tokens added once per request to prime the assistant's reply
+pub const REPLY_PRIMING_TOKENS: u32 = 3;
+
+/// Tokens added per tool definition for its wrapper
+const TOOL_OVERHEAD_TOKENS: u32 = 8;
+
+/// Tokens charged per image part; a 512px image at high detail is 765
+/// tokens on OpenAI and about as many on Claude
+const IMAGE_TOKENS: u32 = 765;
+
+/// Token estimation models
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TokenModel {
+    /// OpenAI GPT-4 and GPT-3.5 models (cl100k_base encoding)
+    GPT,
+    /// OpenAI GPT-4o, GPT-4.1 and o-series models (o200k_base encoding)
+    GPT4o,
+    /// Claude models
+    Claude,
+    /// Generic estimation
+    Generic,
+}
+
+impl TokenModel {
+    /// The token model for a model name such as `gpt-4o-mini`
+    pub fn for_model(model: &str) -> Self {
+        let model = model.to_ascii_lowercase();
+        let model = model.rsplit('/').next().unwrap_or(&model);
+
+        if model.starts_with("gpt-4o")
+            || model.starts_with("gpt-4.1")
+            || model.starts_with("gpt-5")
+            || model.starts_with("chatgpt-4o")
+            || ["o1", "o3", "o4"].iter().any(|p| model == *p || model.starts_with(&format!("{}-", p)))
+        {
+            Self::GPT4o
+        } else if model.starts_with("gpt-4") || model.starts_with("gpt-3.5") || model.starts_with("text-embedding") {
+            Self::GPT
+        } else if model.starts_with("claude") {
+            Self::Claude
+        } else {
+            Self::Generic
+        }
+    }
+}
+
+/// Counts tokens of text and chat requests for a model
+#[derive(Debug, Clone, Copy)]
+pub struct TokenCounter {
+    model: TokenModel,
+}
+
+impl TokenCounter {
+    pub fn new(model: TokenModel) -> Self {
+        Self { model }
+    }
+
+    /// Counter for a model name, or the generic estimator if unknown
+    pub fn for_model(model: Option<&str>) -> Self {
+        Self::new(model.map_or(TokenModel::Generic, TokenModel::for_model))
+    }
+
+    pub fn model(&self) -> TokenModel {
+        self.model
+    }
+
+    /// Tokens of a piece of text
+    pub fn count(&self, text: &str) -> u32 {
+        if text.is_empty() {
+            return 0;
+        }
+        match self.model {
+            TokenModel::GPT => cl100k().encode_ordinary(text).len() as u32,
+            TokenModel::GPT4o => o200k().encode_ordinary(text).len() as u32,
+            // Claude's vocabulary splits somewhat finer than cl100k
+            TokenModel::Claude => (estimate(text) * 1.1).ceil() as u32,
+            TokenModel::Generic => estimate(text).ceil() as u32,
+        }
+    }
+
+    /// Tokens of a chat message with its format overhead
+    pub fn count_message(&self, content: &str) -> u32 {
+        MESSAGE_OVERHEAD_TOKENS + self.count(content)
+    }
+
+    /// Tokens of a chat request: messages, tool definitions and reply priming
+    pub fn count_chat(&self, messages: &[ChatMessage], tools: &[ToolDefinition]) -> u32 {
+        let messages: u32 = messages
+            .iter()
+            .map(|message| {
+                let mut tokens = self.count_message(message.content.as_deref().unwrap_or(""));
+                for part in message.parts.iter().flatten() {
+                    tokens += match part {
+                        ContentPart::Image { .. } => IMAGE_TOKENS,
+                        _ => self.count(&part.to_text()),
+                    };
+                }
+                if let Some(name) = &message.name {
+                    tokens += self.count(name) + 1;
+                }
+                for call in message.tool_calls.iter().flatten() {
+                    tokens += self.count(&serde_json::to_string(call).unwrap_or_default());
+                }
+                tokens
+            })
+            .sum();
+
+        messages + self.count_tools(tools) + REPLY_PRIMING_TOKENS
+    }
+
+    /// Tokens of tool definitions as sent to the model
+    pub fn count_tools(&self, tools: &[ToolDefinition]) -> u32 {
+        tools
+            .iter()
+            .map(|tool| {
+                TOOL_OVERHEAD_TOKENS
+                    + self.count(&tool.function.name)
+                    + self.count(&tool.function.description)
+                    + self.count(&tool.function.parameters.to_string())
+            })
+            .sum()
+    }
+}
+
+fn cl100k() -> &'static CoreBPE {
+    static BPE: OnceLock<CoreBPE> = OnceLock::new();
+    BPE.get_or_init(|| tiktoken_rs::cl100k_base().expect("bundled cl100k_base vocabulary"))
+}
+
+fn o200k() -> &'static CoreBPE {
+    static BPE: OnceLock<CoreBPE> = OnceLock::new();
+    BPE.get_or_init(|| tiktoken_rs::o200k_base().expect("bundled o200k_base vocabulary"))
+}
+
+/// Character classes with similar tokenization
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Letter,
+    Digit,
+    Punctuation,
+    Space,
+    Newline,
+    Cjk,
+    Other,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        match c {
+            '\n' => Self::Newline,
+            c if c.is_ascii_alphabetic() => Self::Letter,
+            c if c.is_ascii_digit() => Self::Digit,
+            c if c.is_whitespace() => Self::Space,
+            c if c.is_ascii() => Self::Punctuation,
+            c if is_cjk(c) => Self::Cjk,
+            _ => Self::Other,
+        }
+    }
+
+    /// Tokens of a run of `n` characters of this class
+    fn tokens(self, n: usize) -> usize {
+        match self {
+            // Common words are single tokens; long ones split
+            Self::Letter => n.div_ceil(6),
+            // Digits are grouped in threes
+            Self::Digit => n.div_ceil(3),
+            // Adjacent symbols merge in pairs, e.g. `":` or `);`
+            Self::Punctuation => n.div_ceil(2),
+            // A single space joins the following word; longer runs are indentation
+            Self::Space => (n - 1).div_ceil(4),
+            Self::Newline => n,
+            Self::Cjk => n,
+            Self::Other => n.div_ceil(2),
+        }
+    }
+}
+
+/// Estimate BPE tokens from runs of similar characters
+///
+/// Symbols dominate code and JSON and CJK characters take about a token
+/// each, both of which a fixed bytes-per-token ratio badly undercounts.
+fn estimate(text: &str) -> f32 {
+    let mut tokens = 0;
+    let mut run: Option<(CharClass, usize)> = None;
+
+    for class in text.chars().map(CharClass::of) {
+        run = match run {
+            Some((current, n)) if current == class => Some((current, n + 1)),
+            Some((current, n)) => {
+                tokens += current.tokens(n);
+                Some((class, 1))
+            }
+            None => Some((class, 1)),
+        };
+    }
+    if let Some((current, n)) = run {
+        tokens += current.tokens(n);
+    }
+
+    tokens as f32
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF      // Hiragana, Katakana
+        | 0x3400..=0x4DBF    // CJK extension A
+        | 0x4E00..=0x9FFF    // CJK unified ideographs
+        | 0xAC00..=0xD7AF    // Hangul syllables
+        | 0xF900..=0xFAFF    // CJK compatibility ideographs
+        | 0xFF00..=0xFFEF    // Full-width forms
+        | 0x20000..=0x2FFFF  // CJK extensions B and later
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bpe_counts() {
+        let gpt = TokenCounter::new(TokenModel::GPT);
+        assert_eq!(gpt.count("hello world"), 2);
+        assert_eq!(gpt.count("tiktoken is great!"), 6);
+        assert_eq!(gpt.count(""), 0);
+
+        let gpt4o = TokenCounter::new(TokenModel::GPT4o);
+        assert_eq!(gpt4o.count("hello world"), 2);
+    }
+
+    #[test]
+    fn test_model_names() {
+        assert_eq!(TokenModel::for_model("gpt-4o-mini"), TokenModel::GPT4o);
+        assert_eq!(TokenModel::for_model("openai/o3-mini"), TokenModel::GPT4o);
+        assert_eq!(TokenModel::for_model("gpt-4-turbo"), TokenModel::GPT);
+        assert_eq!(TokenModel::for_model("claude-3-5-sonnet-latest"), TokenModel::Claude);
+        assert_eq!(TokenModel::for_model("llama3"), TokenModel::Generic);
+    }
+
+    #[test]
+    fn test_estimate_handles_dense_text() {
+        let gpt = TokenCounter::new(TokenModel::GPT);
+        let generic = TokenCounter::new(TokenModel::Generic);
+
+        // JSON, where a bytes/4 ratio undercounts by almost half
+        let json = r#"{"id": 42, "tags": ["a", "b"], "ok": true, "ratio": 0.75}"#;
+        let ratio = generic.count(json) as f32 / gpt.count(json) as f32;
+        assert!((0.75..=1.35).contains(&ratio), "ratio {}", ratio);
+
+        // One token per CJK character, not per four bytes
+        assert_eq!(generic.count("温度センサー"), 6);
+        assert_eq!(generic.count("temperature sensor"), 3);
+        assert_eq!(generic.count("    x = 1;\n"), 6);
+    }
+
+    #[test]
+    fn test_chat_overhead() {
+        use crate::core::domain::traits::llm_client::{FunctionDefinition, MessageRole};
+
+        let counter = TokenCounter::new(TokenModel::GPT);
+        let message = ChatMessage {
+            role: MessageRole::User,
+            content: Some("hello world".to_string()),
+            name: None,
+            tool_call_id: None,
+            tool_calls: None,
+            parts: None,
+        };
+        assert_eq!(counter.count_chat(&[message.clone()], &[]), 2 + MESSAGE_OVERHEAD_TOKENS + REPLY_PRIMING_TOKENS);
+
+        let tool = ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "read_register".to_string(),
+                description: "Read a Modbus holding register".to_string(),
+                parameters: serde_json::json!({ "type": "object", "properties": { "address": { "type": "integer" } } }),
+                required: None,
+            },
+        };
+        assert!(counter.count_chat(&[message.clone()], &[tool]) > counter.count_chat(&[message], &[]) + TOOL_OVERHEAD_TOKENS);
+    }
+}
@@ -5,16 +5,23 @@
 //! (OpenAI, Anthropic, local models, etc.).
 
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
+use crate::core::domain::models::conversation::Attachment;
 use crate::core::domain::models::{AgentId, ConversationId, MessageId};
 
 /// Result type for LLM operations
 pub type LLMResult<T> = Result<T, LLMError>;
 
+/// Request parameter pinning a request to providers on this machine
+///
+/// Set it to `true` in `extra_params` for requests about sensitive twins,
+/// including embedding requests.
+pub const LOCAL_ONLY_PARAM: &str = "local_only";
+
 /// Errors that can occur during LLM operations
 #[derive(Debug, thiserror::Error)]
 pub enum LLMError {
@@ -57,6 +64,10 @@ pub enum LLMError {
     #[error("Content filtered: {0}")]
     ContentFiltered(String),
     
+    /// Spending limit reached
+    #[error("Budget exceeded: spent {spent:.2} of {limit:.2} {currency}")]
+    BudgetExceeded { spent: f64, limit: f64, currency: String },
+    
     /// Provider-specific error
     #[error("Provider error: {provider} - {message}")]
     ProviderError { provider: String, message: String },
@@ -291,6 +302,88 @@ pub struct ChatMessage {
     
     /// Tool calls (for assistant messages)
     pub tool_calls: Option<Vec<ToolCall>>,
+    
+    /// Content parts sent after `content`, such as images and file excerpts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parts: Option<Vec<ContentPart>>,
+}
+
+impl ChatMessage {
+    /// The message as text, for providers and counters without multimodal input
+    ///
+    /// Images are replaced by a placeholder.
+    pub fn text(&self) -> String {
+        let mut text = self.content.clone().unwrap_or_default();
+        for part in self.parts.iter().flatten() {
+            if !text.is_empty() {
+                text.push_str("\n\n");
+            }
+            text.push_str(&part.to_text());
+        }
+        text
+    }
+    
+    /// Whether the message carries image parts
+    pub fn has_images(&self) -> bool {
+        self.parts.iter().flatten().any(|part| matches!(part, ContentPart::Image { .. }))
+    }
+}
+
+/// A part of multimodal message content
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    /// Plain text
+    Text { text: String },
+    
+    /// An image, inline or by URL
+    Image { source: ImageSource },
+    
+    /// An excerpt of a file's text
+    File {
+        name: String,
+        mime_type: Option<String>,
+        excerpt: String,
+    },
+}
+
+impl ContentPart {
+    /// The part as text; file excerpts are labelled with the file name
+    pub fn to_text(&self) -> String {
+        match self {
+            ContentPart::Text { text } => text.clone(),
+            ContentPart::Image { .. } => "[image]".to_string(),
+            ContentPart::File { name, excerpt, .. } => format!("File {}:\n{}", name, excerpt),
+        }
+    }
+}
+
+/// Source of an image content part
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    /// Base64-encoded image data
+    Base64 { media_type: String, data: String },
+    
+    /// Image at an HTTP(S) URL
+    Url { url: String },
+}
+
+impl ImageSource {
+    /// The image as a URL, using a data URL for inline images
+    pub fn to_url(&self) -> String {
+        match self {
+            ImageSource::Base64 { media_type, data } => format!("data:{};base64,{}", media_type, data),
+            ImageSource::Url { url } => url.clone(),
+        }
+    }
+}
+
+/// Renders message attachments into content parts
+#[async_trait]
+pub trait AttachmentRendering: Send + Sync {
+    /// Content parts of `attachment`, or a note if it cannot be rendered
+    async fn render_attachment(&self, attachment: &Attachment) -> Vec<ContentPart>;
 }
 
 /// Message roles in chat
@@ -393,6 +486,24 @@ pub struct ResponseFormat {
     pub json_schema: Option<serde_json::Value>,
 }
 
+impl ResponseFormat {
+    /// Any JSON object
+    pub fn json_object() -> Self {
+        Self {
+            format_type: ResponseFormatType::JsonObject,
+            json_schema: None,
+        }
+    }
+    
+    /// JSON conforming to a schema
+    pub fn json_schema(schema: serde_json::Value) -> Self {
+        Self {
+            format_type: ResponseFormatType::JsonSchema,
+            json_schema: Some(schema),
+        }
+    }
+}
+
 /// Response format types
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -459,6 +570,10 @@ pub struct EmbeddingRequest {
     
     /// User identifier
     pub user_id: Option<String>,
+    
+    /// Additional parameters
+    #[serde(default)]
+    pub extra_params: HashMap<String, serde_json::Value>,
 }
 
 /// Embedding response
@@ -596,6 +711,25 @@ pub enum UsagePeriod {
     },
 }
 
+impl UsagePeriod {
+    /// Start and end of the period as of `now`, in UTC
+    ///
+    /// Weeks start on Monday.
+    pub fn range(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let midnight = now.date_naive().and_time(NaiveTime::MIN).and_utc();
+        match *self {
+            UsagePeriod::Today => (midnight, now),
+            UsagePeriod::ThisWeek => (
+                midnight - Duration::days(now.weekday().num_days_from_monday() as i64),
+                now,
+            ),
+            UsagePeriod::ThisMonth => (midnight - Duration::days(now.day0() as i64), now),
+            UsagePeriod::Last30Days => (now - Duration::days(30), now),
+            UsagePeriod::Custom { start, end } => (start, end),
+        }
+    }
+}
+
 /// Usage statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UsageStats {
@@ -817,4 +951,16 @@ mod tests {
         
         assert_eq!(usage.prompt_tokens + usage.completion_tokens, usage.total_tokens);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_usage_period_range() {
+        // A Thursday afternoon
+        let now = DateTime::parse_from_rfc3339("2025-11-20T15:30:00Z").unwrap().with_timezone(&Utc);
+        let start = |period: UsagePeriod| period.range(now).0.to_rfc3339();
+        
+        assert_eq!(start(UsagePeriod::Today), "2025-11-20T00:00:00+00:00");
+        assert_eq!(start(UsagePeriod::ThisWeek), "2025-11-17T00:00:00+00:00");
+        assert_eq!(start(UsagePeriod::ThisMonth), "2025-11-01T00:00:00+00:00");
+        assert_eq!(start(UsagePeriod::Last30Days), "2025-10-21T15:30:00+00:00");
+    }
+}
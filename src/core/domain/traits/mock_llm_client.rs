@@ -0,0 +1,183 @@
+//! Scripted LLM client for tests.
+//!
+//! Chat completions are answered from a script: queued errors first, then
+//! the replies of a responder. Embeddings are a single dimension holding the
+//! length of each text. Every request is kept so tests can inspect what was
+//! sent.
+
+use super::llm_client::{
+    ChatChoice, ChatCompletionRequest, ChatCompletionResponse, ChatCompletionStream, ChatMessage,
+    CompletionRequest, CompletionResponse, CompletionStream, Embedding, EmbeddingRequest,
+    EmbeddingResponse, EmbeddingUsage, FinishReason, LLMClient, LLMError, LLMResult, MessageRole, ModelInfo, TokenUsage, UsagePeriod,
+    UsageStats,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Reply text for a request, given the number of the call (from 1)
+type Responder = Box<dyn Fn(&ChatCompletionRequest, usize) -> String + Send + Sync>;
+
+/// LLM client answering chat completions from a script
+pub struct MockLLMClient {
+    responder: Responder,
+    errors: Mutex<VecDeque<LLMError>>,
+    requests: Mutex<Vec<ChatCompletionRequest>>,
+    embed_requests: Mutex<Vec<EmbeddingRequest>>,
+    usage: TokenUsage,
+    processing_time_ms: u64,
+}
+
+impl MockLLMClient {
+    /// Reply with `replies` in order, repeating the last one
+    pub fn replying(replies: Vec<&'static str>) -> Self {
+        assert!(!replies.is_empty(), "a scripted client needs a reply");
+        Self::responding(move |_, call| replies[(call - 1).min(replies.len() - 1)].to_string())
+    }
+
+    /// Reply with the text `responder` returns for each request
+    pub fn responding(
+        responder: impl Fn(&ChatCompletionRequest, usize) -> String + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            responder: Box::new(responder),
+            errors: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(Vec::new()),
+            embed_requests: Mutex::new(Vec::new()),
+            usage: TokenUsage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+                cached_tokens: None,
+            },
+            processing_time_ms: 0,
+        }
+    }
+
+    /// Fail the first requests with `errors`, in order
+    pub fn with_errors(self, errors: Vec<LLMError>) -> Self {
+        *self.errors.lock().unwrap() = errors.into();
+        self
+    }
+
+    /// Report the given token usage on every reply
+    pub fn with_usage(mut self, prompt_tokens: u32, completion_tokens: u32) -> Self {
+        self.usage = TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            cached_tokens: None,
+        };
+        self
+    }
+
+    /// Report the given processing time on every reply
+    pub fn with_processing_time(mut self, milliseconds: u64) -> Self {
+        self.processing_time_ms = milliseconds;
+        self
+    }
+
+    /// Requests received so far, failed ones included
+    pub fn requests(&self) -> Vec<ChatCompletionRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Number of requests received so far
+    pub fn calls(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// Embedding requests received so far, failed ones included
+    pub fn embed_requests(&self) -> Vec<EmbeddingRequest> {
+        self.embed_requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl LLMClient for MockLLMClient {
+    async fn complete(&self, _request: CompletionRequest) -> LLMResult<CompletionResponse> {
+        unimplemented!()
+    }
+
+    async fn chat_complete(&self, request: ChatCompletionRequest) -> LLMResult<ChatCompletionResponse> {
+        let call = {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(request.clone());
+            requests.len()
+        };
+        if let Some(error) = self.errors.lock().unwrap().pop_front() {
+            return Err(error);
+        }
+
+        let reply = (self.responder)(&request, call);
+        Ok(ChatCompletionResponse {
+            request_id: request.request_id,
+            choices: vec![ChatChoice {
+                message: ChatMessage {
+                    role: MessageRole::Assistant,
+                    content: Some(reply),
+                    name: None,
+                    tool_call_id: None,
+                    tool_calls: None,
+                    parts: None,
+                },
+                index: 0,
+                finish_reason: FinishReason::Stop,
+                tool_calls: None,
+            }],
+            usage: self.usage,
+            model: request.model,
+            created_at: Utc::now(),
+            processing_time_ms: self.processing_time_ms,
+        })
+    }
+
+    async fn stream_complete(&self, _request: CompletionRequest) -> LLMResult<Box<dyn CompletionStream>> {
+        unimplemented!()
+    }
+
+    async fn stream_chat_complete(&self, _request: ChatCompletionRequest) -> LLMResult<Box<dyn ChatCompletionStream>> {
+        unimplemented!()
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> LLMResult<EmbeddingResponse> {
+        self.embed_requests.lock().unwrap().push(request.clone());
+        if let Some(error) = self.errors.lock().unwrap().pop_front() {
+            return Err(error);
+        }
+
+        Ok(EmbeddingResponse {
+            data: request.input
+                .iter()
+                .enumerate()
+                .map(|(index, text)| Embedding { embedding: vec![text.len() as f32], index })
+                .collect(),
+            model: request.model,
+            usage: EmbeddingUsage {
+                prompt_tokens: self.usage.prompt_tokens,
+                total_tokens: self.usage.prompt_tokens,
+            },
+        })
+    }
+
+    async fn list_models(&self) -> LLMResult<Vec<ModelInfo>> {
+        Ok(Vec::new())
+    }
+
+    async fn get_model_info(&self, model_id: &str) -> LLMResult<ModelInfo> {
+        Err(LLMError::ModelNotAvailable(model_id.to_string()))
+    }
+
+    async fn validate_credentials(&self) -> LLMResult<bool> {
+        Ok(true)
+    }
+
+    async fn get_usage(&self, _period: UsagePeriod) -> LLMResult<UsageStats> {
+        unimplemented!()
+    }
+
+    async fn cancel_request(&self, _request_id: &str) -> LLMResult<()> {
+        Ok(())
+    }
+}
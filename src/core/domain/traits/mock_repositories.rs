@@ -0,0 +1,176 @@
+//! In-memory repositories for tests.
+//!
+//! Only the lookups tests rely on are implemented; the rest are left
+//! `unimplemented!()`.
+
+use super::repository::{
+    FilterCriteria, PaginatedResult, Pagination, RepositoryError, RepositoryResult,
+    SensorDataRepository, SortCriteria, TwinRepository,
+};
+use crate::core::domain::models::{
+    DigitalTwin, SensorData, SensorDataId, SensorReading, TwinId, TwinState, TwinType,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Twin repository keeping twins in a map
+#[derive(Default)]
+pub struct InMemoryTwinRepository {
+    pub twins: Mutex<HashMap<TwinId, DigitalTwin>>,
+}
+
+#[async_trait]
+impl TwinRepository for InMemoryTwinRepository {
+    async fn create(&self, twin: DigitalTwin) -> RepositoryResult<DigitalTwin> {
+        self.twins.lock().unwrap().insert(twin.id, twin.clone());
+        Ok(twin)
+    }
+
+    async fn get_by_id(&self, id: TwinId) -> RepositoryResult<DigitalTwin> {
+        self.twins.lock().unwrap().get(&id).cloned()
+            .ok_or_else(|| RepositoryError::NotFound {
+                entity_type: "DigitalTwin".to_string(),
+                id: id.to_string(),
+            })
+    }
+
+    async fn update(&self, twin: DigitalTwin) -> RepositoryResult<DigitalTwin> {
+        self.create(twin).await
+    }
+
+    async fn delete(&self, id: TwinId) -> RepositoryResult<()> {
+        self.twins.lock().unwrap().remove(&id);
+        Ok(())
+    }
+
+    async fn find(
+        &self,
+        _filters: Vec<FilterCriteria>,
+        _sort: Vec<SortCriteria>,
+        _pagination: Pagination,
+    ) -> RepositoryResult<PaginatedResult<DigitalTwin>> {
+        unimplemented!()
+    }
+
+    async fn get_by_type(&self, _twin_type: &TwinType, _pagination: Pagination) -> RepositoryResult<PaginatedResult<DigitalTwin>> {
+        unimplemented!()
+    }
+
+    async fn get_by_state(&self, _state: TwinState, _pagination: Pagination) -> RepositoryResult<PaginatedResult<DigitalTwin>> {
+        unimplemented!()
+    }
+
+    async fn get_by_agent_id(&self, _agent_id: Uuid, _pagination: Pagination) -> RepositoryResult<PaginatedResult<DigitalTwin>> {
+        unimplemented!()
+    }
+
+    async fn update_state(&self, _id: TwinId, _state: TwinState) -> RepositoryResult<()> {
+        unimplemented!()
+    }
+
+    async fn update_properties(&self, _id: TwinId, _properties: HashMap<String, Value>) -> RepositoryResult<()> {
+        unimplemented!()
+    }
+
+    async fn mark_synchronized(&self, _id: TwinId, _timestamp: DateTime<Utc>) -> RepositoryResult<()> {
+        unimplemented!()
+    }
+
+    async fn get_twins_needing_sync(&self, _limit: usize) -> RepositoryResult<Vec<DigitalTwin>> {
+        unimplemented!()
+    }
+}
+
+/// Sensor data repository keeping series and their readings in a map
+#[derive(Default)]
+pub struct InMemorySensorRepository {
+    pub data: Mutex<HashMap<SensorDataId, SensorData>>,
+}
+
+#[async_trait]
+impl SensorDataRepository for InMemorySensorRepository {
+    async fn create(&self, sensor_data: SensorData) -> RepositoryResult<SensorData> {
+        self.data.lock().unwrap().insert(sensor_data.id, sensor_data.clone());
+        Ok(sensor_data)
+    }
+
+    async fn get_by_id(&self, id: SensorDataId) -> RepositoryResult<SensorData> {
+        self.data.lock().unwrap().get(&id).cloned().ok_or(RepositoryError::NotFound {
+            entity_type: "SensorData".to_string(),
+            id: id.to_string(),
+        })
+    }
+
+    async fn update(&self, sensor_data: SensorData) -> RepositoryResult<SensorData> {
+        self.create(sensor_data).await
+    }
+
+    async fn delete(&self, id: SensorDataId) -> RepositoryResult<()> {
+        self.data.lock().unwrap().remove(&id);
+        Ok(())
+    }
+
+    async fn get_by_twin_id(
+        &self,
+        twin_id: TwinId,
+        pagination: Pagination,
+    ) -> RepositoryResult<PaginatedResult<SensorData>> {
+        let items: Vec<_> = self.data.lock().unwrap().values()
+            .filter(|d| d.twin_id == twin_id)
+            .cloned()
+            .collect();
+        Ok(PaginatedResult {
+            total: items.len(),
+            items: items.into_iter().skip(pagination.offset).take(pagination.limit).collect(),
+            offset: pagination.offset,
+            limit: pagination.limit,
+        })
+    }
+
+    async fn add_reading(&self, id: SensorDataId, reading: SensorReading) -> RepositoryResult<()> {
+        self.data.lock().unwrap().get_mut(&id).unwrap().readings.push(reading);
+        Ok(())
+    }
+
+    async fn get_readings_in_range(
+        &self,
+        id: SensorDataId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        pagination: Pagination,
+    ) -> RepositoryResult<PaginatedResult<SensorReading>> {
+        let items: Vec<_> = self.data.lock().unwrap()[&id].readings.iter()
+            .filter(|r| r.timestamp >= start && r.timestamp <= end)
+            .cloned()
+            .collect();
+        Ok(PaginatedResult {
+            total: items.len(),
+            items: items.into_iter().skip(pagination.offset).take(pagination.limit).collect(),
+            offset: pagination.offset,
+            limit: pagination.limit,
+        })
+    }
+
+    async fn get_latest_reading(&self, id: SensorDataId) -> RepositoryResult<Option<SensorReading>> {
+        Ok(self.data.lock().unwrap()[&id].readings.last().cloned())
+    }
+
+    async fn get_aggregated_data(
+        &self,
+        _id: SensorDataId,
+        _start: DateTime<Utc>,
+        _end: DateTime<Utc>,
+        _interval: &str,
+        _aggregation: &str,
+    ) -> RepositoryResult<Vec<(DateTime<Utc>, f64)>> {
+        Ok(Vec::new())
+    }
+
+    async fn cleanup_old_readings(&self, _retention_days: u32) -> RepositoryResult<usize> {
+        Ok(0)
+    }
+}
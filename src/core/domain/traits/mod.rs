@@ -5,8 +5,18 @@
 //! between domain logic and external concerns.
 
 pub mod llm_client;
+pub mod prompt_template_store;
 pub mod repository;
+pub mod response_cache_store;
+pub mod summary_store;
 pub mod tool_executor;
+pub mod usage_store;
+pub mod vector_store;
+
+#[cfg(test)]
+pub mod mock_llm_client;
+#[cfg(test)]
+pub mod mock_repositories;
 
 // Re-export all repository traits
 pub use repository::{
@@ -18,17 +28,34 @@ pub use repository::{
 
 // Re-export LLM client traits and types
 pub use llm_client::{
-    ChatChoice, ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse,
+    AttachmentRendering, ChatChoice, ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse,
     ChatCompletionStream, ChatMessage, ChatMessageDelta, CompletionChunk,
     CompletionChoice, CompletionRequest, CompletionResponse, CompletionStream,
     ConversationContext, Embedding, EmbeddingRequest, EmbeddingResponse,
     EmbeddingUsage, EncodingFormat, FinishReason, FunctionCall, FunctionCallDelta,
     FunctionDefinition, LLMClient, LLMClientConfig, LLMClientFactory, LLMError,
-    LLMResult, LogProbs, MessageRole, ModelInfo, ModelPricing, ModelType, ModelUsage,
+    LLMResult, LogProbs, LOCAL_ONLY_PARAM, MessageRole, ModelInfo, ModelPricing, ModelType, ModelUsage,
     ResponseFormat, ResponseFormatType, ToolCall, ToolCallDelta, ToolChoice,
     ToolChoiceFunction, ToolDefinition, TokenUsage, UsagePeriod, UsageStats,
 };
 
+// Re-export memory storage traits and types
+pub use summary_store::{ConversationSummary, SummaryStore};
+pub use vector_store::{
+    cosine_similarity, ChunkSource, MemoryChunk, MemoryScope, RagConfig, ScoredChunk, VectorStore,
+};
+
+// Re-export prompt template storage traits and types
+pub use prompt_template_store::{PromptTemplate, PromptTemplateStore, TemplateVariable, VariableType};
+
+// Re-export response cache storage traits and types
+pub use response_cache_store::{CachedResponse, ResponseCacheConfig, ResponseCacheStore};
+
+// Re-export usage storage and metering traits and types
+pub use usage_store::{
+    AgentBudget, BudgetAction, PricingEntry, UsageFilter, UsageMetering, UsageRecord, UsageStore,
+};
+
 // Re-export tool executor traits and types
 pub use tool_executor::{
     ChunkData, ExecutionChunk, ExecutionContext, ExecutionOptions, ExecutionPriority,
@@ -0,0 +1,105 @@
+//! Prompt templates and their storage.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::core::domain::traits::repository::RepositoryResult;
+
+/// Type of a template variable
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    List,
+    Object,
+}
+
+impl VariableType {
+    /// Whether a JSON value has this type
+    pub fn accepts(&self, value: &Value) -> bool {
+        match self {
+            Self::String => value.is_string(),
+            Self::Number => value.is_number(),
+            Self::Integer => value.is_i64() || value.is_u64(),
+            Self::Boolean => value.is_boolean(),
+            Self::List => value.is_array(),
+            Self::Object => value.is_object(),
+        }
+    }
+}
+
+/// A variable declared by a template
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TemplateVariable {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub var_type: VariableType,
+    /// Rendering fails if a required variable has no value or default
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub default: Option<Value>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// A named, versioned prompt template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    /// Unique name, also used to include the template as a partial
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub body: String,
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+    /// Incremented on every save
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PromptTemplate {
+    /// Create an unsaved template
+    pub fn new(name: impl Into<String>, body: impl Into<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            name: name.into(),
+            description: String::new(),
+            body: body.into(),
+            variables: Vec::new(),
+            version: 0,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Declare a variable
+    pub fn with_variable(mut self, variable: TemplateVariable) -> Self {
+        self.variables.push(variable);
+        self
+    }
+}
+
+/// Storage for prompt templates
+#[async_trait]
+pub trait PromptTemplateStore: Send + Sync {
+    async fn get(&self, name: &str) -> RepositoryResult<Option<PromptTemplate>>;
+
+    /// All templates ordered by name
+    async fn list(&self) -> RepositoryResult<Vec<PromptTemplate>>;
+
+    /// Insert or replace a template by name
+    async fn save(&self, template: &PromptTemplate) -> RepositoryResult<()>;
+
+    /// Returns whether a template was deleted
+    async fn delete(&self, name: &str) -> RepositoryResult<bool>;
+}
@@ -12,7 +12,7 @@ use uuid::Uuid;
 use crate::core::domain::models::{
     Agent, AgentId, AgentState,
     Conversation, ConversationId, ConversationState, Message, MessageId,
-    DigitalTwin, TwinId, TwinState, TwinType,
+    DigitalTwin, Measurement, TwinId, TwinState, TwinType,
     SensorData, SensorDataId, SensorReading,
     Tool, ToolId, ToolResult, ExecutionId, ToolType,
 };
@@ -285,6 +285,27 @@ pub trait TwinRepository: Send + Sync {
         properties: HashMap<String, serde_json::Value>,
     ) -> RepositoryResult<()>;
     
+    /// Set measurements of a twin, leaving the rest of the twin untouched
+    ///
+    /// A stored measurement newer than the given one is kept.
+    async fn update_measurements(
+        &self,
+        id: TwinId,
+        measurements: HashMap<String, Measurement>,
+    ) -> RepositoryResult<()> {
+        let mut twin = self.get_by_id(id).await?;
+        for (name, measurement) in measurements {
+            let newer = twin.properties.measurements
+                .get(&name)
+                .is_some_and(|stored| stored.timestamp > measurement.timestamp);
+            if !newer {
+                twin.properties.measurements.insert(name, measurement);
+            }
+        }
+        self.update(twin).await?;
+        Ok(())
+    }
+    
     /// Mark twin as synchronized
     async fn mark_synchronized(
         &self,
@@ -328,6 +349,18 @@ pub trait SensorDataRepository: Send + Sync {
         reading: SensorReading,
     ) -> RepositoryResult<()>;
     
+    /// Add a batch of sensor readings
+    async fn add_readings(
+        &self,
+        sensor_data_id: SensorDataId,
+        readings: Vec<SensorReading>,
+    ) -> RepositoryResult<()> {
+        for reading in readings {
+            self.add_reading(sensor_data_id, reading).await?;
+        }
+        Ok(())
+    }
+    
     /// Get readings within time range
     async fn get_readings_in_range(
         &self,
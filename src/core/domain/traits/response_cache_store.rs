@@ -0,0 +1,61 @@
+//! Storage for cached LLM responses.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::core::domain::traits::llm_client::ChatCompletionResponse;
+use crate::core::domain::traits::repository::RepositoryResult;
+
+/// Response cache configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseCacheConfig {
+    /// Cache responses at all
+    pub enabled: bool,
+    /// Seconds a response stays valid
+    pub ttl_seconds: u64,
+    /// Responses kept; the least recently used are evicted
+    pub max_entries: usize,
+    /// Also cache requests with a non-zero or unset temperature
+    pub cache_nondeterministic: bool,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_seconds: 3600,
+            max_entries: 1000,
+            cache_nondeterministic: false,
+        }
+    }
+}
+
+/// A cached chat completion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub key: String,
+    pub model: String,
+    pub response: ChatCompletionResponse,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Storage for cached responses
+#[async_trait]
+pub trait ResponseCacheStore: Send + Sync {
+    /// An unexpired response; marks it as recently used
+    async fn get(&self, key: &str, now: DateTime<Utc>) -> RepositoryResult<Option<CachedResponse>>;
+
+    async fn put(&self, entry: &CachedResponse) -> RepositoryResult<()>;
+
+    /// Remove expired responses and the least recently used beyond
+    /// `max_entries`; returns the number removed
+    async fn prune(&self, max_entries: usize, now: DateTime<Utc>) -> RepositoryResult<usize>;
+
+    /// Remove every response; returns the number removed
+    async fn clear(&self) -> RepositoryResult<usize>;
+
+    async fn len(&self) -> RepositoryResult<usize>;
+}
@@ -0,0 +1,40 @@
+//! Storage for conversation summaries.
+//!
+//! Rolling summaries of older messages are kept behind [`SummaryStore`], so
+//! the application layer does not depend on how they are stored.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::core::domain::traits::repository::RepositoryResult;
+
+/// Summary standing in for the older messages of a conversation
+///
+/// Persisted in the `message_context` table; `message_id` there is the last
+/// message the summary covers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    /// Last message covered by the summary
+    pub through_message_id: Uuid,
+    /// Index of the first covered message
+    pub range_start: usize,
+    /// Index after the last covered message
+    pub range_end: usize,
+    pub content: String,
+    pub token_count: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Storage for conversation summaries
+#[async_trait]
+pub trait SummaryStore: Send + Sync {
+    /// The most recent summary of a conversation
+    async fn latest_summary(&self, conversation_id: Uuid) -> RepositoryResult<Option<ConversationSummary>>;
+
+    /// Store a new summary
+    async fn save_summary(&self, summary: &ConversationSummary) -> RepositoryResult<()>;
+}
@@ -0,0 +1,131 @@
+//! Storage and metering of LLM usage.
+//!
+//! Usage records, model prices and agent budgets are kept behind
+//! [`UsageStore`]. [`UsageMetering`] lets a client over several providers
+//! record the usage of each of them and compare their prices.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::core::domain::traits::llm_client::{LLMClient, LLMResult, TokenUsage, UsagePeriod, UsageStats};
+use crate::core::domain::traits::repository::RepositoryResult;
+
+/// Usage of a single LLM request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub id: Uuid,
+    pub request_id: String,
+    pub provider: String,
+    pub model: String,
+    pub agent_id: Option<Uuid>,
+    pub conversation_id: Option<Uuid>,
+    pub usage: TokenUsage,
+    pub cost: f64,
+    pub currency: String,
+    pub success: bool,
+    pub latency_ms: u64,
+    /// Token counts were estimated, as streams do not report usage
+    pub estimated: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Price of a provider's models
+///
+/// `model` matches model names by prefix, so `gpt-4o` also prices
+/// `gpt-4o-2024-08-06`; an empty model prices every model of the provider.
+/// The longest matching prefix wins.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PricingEntry {
+    pub provider: String,
+    pub model: String,
+    pub input_cost_per_1k: f64,
+    pub output_cost_per_1k: f64,
+    pub currency: String,
+}
+
+impl PricingEntry {
+    /// Cost of the tokens of a request
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        usage.prompt_tokens as f64 / 1000.0 * self.input_cost_per_1k
+            + usage.completion_tokens as f64 / 1000.0 * self.output_cost_per_1k
+    }
+}
+
+/// What happens to an agent's requests once its budget is spent
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BudgetAction {
+    /// Reject requests
+    Block,
+    /// Send requests to a cheaper model
+    Downgrade { model: String },
+}
+
+/// Monthly spending limit of an agent
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgentBudget {
+    pub agent_id: Uuid,
+    pub monthly_limit: f64,
+    pub currency: String,
+    #[serde(flatten)]
+    pub action: BudgetAction,
+}
+
+/// Restricts usage queries; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageFilter {
+    #[serde(default)]
+    pub agent_id: Option<Uuid>,
+    #[serde(default)]
+    pub conversation_id: Option<Uuid>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// Storage for usage records, prices and budgets
+#[async_trait]
+pub trait UsageStore: Send + Sync {
+    async fn record(&self, record: &UsageRecord) -> RepositoryResult<()>;
+
+    /// Records created between `start` and `end` inclusive, matching the filter
+    async fn records(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        filter: &UsageFilter,
+    ) -> RepositoryResult<Vec<UsageRecord>>;
+
+    /// Cost of an agent's requests since `since`
+    async fn agent_cost(&self, agent_id: Uuid, since: DateTime<Utc>) -> RepositoryResult<f64>;
+
+    async fn pricing(&self) -> RepositoryResult<Vec<PricingEntry>>;
+
+    async fn set_pricing(&self, entry: &PricingEntry) -> RepositoryResult<()>;
+
+    async fn budget(&self, agent_id: Uuid) -> RepositoryResult<Option<AgentBudget>>;
+
+    async fn budgets(&self) -> RepositoryResult<Vec<AgentBudget>>;
+
+    async fn set_budget(&self, budget: &AgentBudget) -> RepositoryResult<()>;
+
+    /// Returns false if the agent had no budget
+    async fn remove_budget(&self, agent_id: Uuid) -> RepositoryResult<bool>;
+}
+
+/// Records the usage of LLM providers
+#[async_trait]
+pub trait UsageMetering: Send + Sync {
+    /// `client` of `provider`, recording the usage of its requests
+    fn meter(self: Arc<Self>, provider: &str, client: Arc<dyn LLMClient>) -> Arc<dyn LLMClient>;
+
+    /// Usage recorded across all providers
+    async fn usage(&self, period: UsagePeriod) -> LLMResult<UsageStats>;
+
+    /// Blended price per 1K tokens of a provider's model, if it is priced
+    async fn cost_per_1k(&self, provider: &str, model: &str) -> Option<f64>;
+}
@@ -0,0 +1,114 @@
+//! Storage and similarity search for embedded text.
+//!
+//! Chunks of messages, attachments and twin documentation are kept behind
+//! [`VectorStore`], so the application layer does not depend on how they
+//! are stored or searched.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::core::domain::traits::repository::RepositoryResult;
+
+/// Retrieval configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RagConfig {
+    /// Chunks returned per query
+    pub top_k: usize,
+    /// Minimum cosine similarity of returned chunks
+    pub similarity_threshold: f32,
+    /// Chunks kept per scope; the oldest are pruned
+    pub max_entries: usize,
+    /// Chunk length in characters
+    pub chunk_size: usize,
+    /// Characters shared by consecutive chunks
+    pub chunk_overlap: usize,
+}
+
+impl Default for RagConfig {
+    fn default() -> Self {
+        Self {
+            top_k: 5,
+            similarity_threshold: 0.7,
+            max_entries: 10_000,
+            chunk_size: 1000,
+            chunk_overlap: 200,
+        }
+    }
+}
+
+/// What a chunk is searchable under
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum MemoryScope {
+    Conversation(Uuid),
+    Twin(Uuid),
+}
+
+/// Where a chunk's text came from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ChunkSource {
+    Message,
+    TwinDocumentation,
+    Attachment,
+}
+
+/// An embedded piece of text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryChunk {
+    pub id: Uuid,
+    pub scope: MemoryScope,
+    pub source: ChunkSource,
+    /// Message, twin or attachment the text belongs to
+    pub source_id: Uuid,
+    pub chunk_index: usize,
+    pub content: String,
+    pub embedding: Vec<f32>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A chunk returned by a search
+#[derive(Debug, Clone)]
+pub struct ScoredChunk {
+    pub chunk: MemoryChunk,
+    /// Cosine similarity to the query
+    pub score: f32,
+}
+
+/// Storage and similarity search for embedded chunks
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    /// Store chunks, replacing earlier chunks of the same sources
+    async fn upsert(&self, chunks: &[MemoryChunk]) -> RepositoryResult<()>;
+
+    /// The `limit` chunks in `scopes` most similar to `query`, with a
+    /// similarity of at least `min_score`, best first
+    async fn search(
+        &self,
+        scopes: &[MemoryScope],
+        query: &[f32],
+        limit: usize,
+        min_score: f32,
+    ) -> RepositoryResult<Vec<ScoredChunk>>;
+
+    /// Remove the chunks of a source
+    async fn remove_source(&self, source_id: Uuid) -> RepositoryResult<()>;
+
+    /// Remove the oldest chunks of a scope beyond `max_entries`
+    async fn prune(&self, scope: MemoryScope, max_entries: usize) -> RepositoryResult<usize>;
+}
+
+/// Cosine similarity; 0 for vectors of different length or zero norm
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
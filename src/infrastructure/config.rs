@@ -1,8 +1,13 @@
 use config::{Config, ConfigError, Environment, File};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::time::Duration;
 
+use crate::core::domain::traits::llm_client::LLMClientConfig;
+use crate::core::domain::traits::response_cache_store::ResponseCacheConfig;
+use crate::core::domain::traits::vector_store::RagConfig;
+
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
@@ -67,6 +72,112 @@ pub struct LLMConfig {
     
     /// LMStudio configuration
     pub lmstudio: LMStudioConfig,
+    
+    /// Routing across providers
+    #[serde(default)]
+    pub routing: RoutingConfig,
+    
+    /// Caching of deterministic chat completions
+    #[serde(default)]
+    pub cache: ResponseCacheConfig,
+}
+
+impl LLMConfig {
+    /// Client configuration for a provider, or `None` if it is unknown
+    pub fn client_config(&self, provider: &str) -> Option<LLMClientConfig> {
+        let (api_key, base_url, default_model, timeout_seconds) = match provider.to_lowercase().as_str() {
+            "openai" => (self.openai.api_key.clone(), None, &self.openai.default_model, self.openai.timeout_seconds),
+            "anthropic" => (self.anthropic.api_key.clone(), None, &self.anthropic.default_model, self.anthropic.timeout_seconds),
+            "openrouter" => (self.openrouter.api_key.clone(), None, &self.openrouter.default_model, self.openrouter.timeout_seconds),
+            "gemini" => (self.gemini.api_key.clone(), None, &self.gemini.default_model, self.gemini.timeout_seconds),
+            "huggingface" => (self.huggingface.api_key.clone(), None, &self.huggingface.default_model, self.huggingface.timeout_seconds),
+            "ollama" => (None, Some(self.ollama.base_url.clone()), &self.ollama.default_model, self.ollama.timeout_seconds),
+            "lmstudio" => (None, Some(self.lmstudio.base_url.clone()), &self.lmstudio.default_model, self.lmstudio.timeout_seconds),
+            _ => return None,
+        };
+
+        let mut extra_config = HashMap::new();
+        if provider.eq_ignore_ascii_case("openrouter") {
+            if let Some(site_url) = &self.openrouter.site_url {
+                extra_config.insert("site_url".to_string(), serde_json::json!(site_url));
+            }
+            if let Some(site_name) = &self.openrouter.site_name {
+                extra_config.insert("site_name".to_string(), serde_json::json!(site_name));
+            }
+        }
+
+        Some(LLMClientConfig {
+            api_key,
+            base_url,
+            organization_id: provider
+                .eq_ignore_ascii_case("openai")
+                .then(|| self.openai.organization_id.clone())
+                .flatten(),
+            default_model: Some(default_model.clone()),
+            timeout_seconds: Some(timeout_seconds),
+            max_retries: None,
+            headers: HashMap::new(),
+            proxy: None,
+            extra_config,
+        })
+    }
+}
+
+/// Routing across LLM providers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingConfig {
+    /// How providers are ordered for a request
+    #[serde(default)]
+    pub policy: RoutingPolicy,
+    
+    /// Providers tried after the default provider, in order
+    #[serde(default)]
+    pub fallback_providers: Vec<String>,
+    
+    /// Send every request to local providers (Ollama, LM Studio)
+    #[serde(default)]
+    pub local_only: bool,
+    
+    /// Consecutive failures before a provider's circuit opens
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    
+    /// Seconds an open circuit waits before a probe request
+    #[serde(default = "default_open_seconds")]
+    pub open_seconds: u64,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        Self {
+            policy: RoutingPolicy::default(),
+            fallback_providers: Vec::new(),
+            local_only: false,
+            failure_threshold: default_failure_threshold(),
+            open_seconds: default_open_seconds(),
+        }
+    }
+}
+
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+fn default_open_seconds() -> u64 {
+    30
+}
+
+/// Provider ordering policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingPolicy {
+    /// The default provider, then the fallbacks in order
+    #[default]
+    Priority,
+    /// Cheapest provider first, by model pricing
+    LowestCost,
+    /// Fastest provider first, by observed latency
+    LowestLatency,
 }
 
 /// OpenAI configuration
@@ -241,6 +352,54 @@ pub struct MqttToolConfig {
     
     /// Connection timeout in seconds
     pub timeout_seconds: u64,
+    
+    /// TLS configuration (plain TCP when absent)
+    pub tls: Option<MqttTlsConfig>,
+    
+    /// Last-will message registered with the broker
+    pub last_will: Option<MqttLastWillConfig>,
+    
+    /// Keep the broker session across reconnects
+    pub persistent_session: bool,
+    
+    /// Time to wait for PUBACK/PUBCOMP in seconds
+    pub ack_timeout_seconds: u64,
+    
+    /// Maximum number of publishes buffered while disconnected
+    pub offline_buffer_size: usize,
+    
+    /// Subscribe to `spBv1.0/#` at startup and ingest Sparkplug B metrics
+    #[serde(default)]
+    pub sparkplug_ingestion: bool,
+}
+
+/// MQTT TLS configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttTlsConfig {
+    /// CA certificate (PEM) path
+    pub ca_path: PathBuf,
+    
+    /// Client certificate (PEM) path for mutual TLS
+    pub client_cert_path: Option<PathBuf>,
+    
+    /// Client private key (PEM) path for mutual TLS
+    pub client_key_path: Option<PathBuf>,
+}
+
+/// MQTT last-will configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttLastWillConfig {
+    /// Topic the will is published on
+    pub topic: String,
+    
+    /// Will payload
+    pub payload: String,
+    
+    /// Will QoS (0, 1 or 2)
+    pub qos: u8,
+    
+    /// Whether the will is retained
+    pub retain: bool,
 }
 
 /// Security configuration
@@ -269,6 +428,10 @@ pub struct SecurityConfig {
     
     /// Permission configuration
     pub permissions: PermissionConfig,
+    
+    /// Operator approval for risky tool operations
+    #[serde(default)]
+    pub approval: ApprovalConfig,
 }
 
 /// API key configuration
@@ -292,6 +455,36 @@ pub struct EncryptionConfig {
     
     /// Key derivation iterations
     pub key_derivation_iterations: u32,
+    
+    /// Source of the master key that wraps the data keys
+    #[serde(default)]
+    pub master_key: MasterKeySource,
+}
+
+/// Where the master key (key-encryption key) is loaded from
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MasterKeySource {
+    /// Derived from `security.secret_key`
+    #[default]
+    SecretKey,
+    
+    /// Base64-encoded 32-byte key in an environment variable
+    Env {
+        variable: String,
+    },
+    
+    /// File holding a raw 32-byte key or its base64 encoding
+    File {
+        path: PathBuf,
+    },
+    
+    /// Derived from a passphrase entered by the user (PBKDF2-HMAC-SHA256);
+    /// the random salt is created on first use
+    Passphrase {
+        salt_path: PathBuf,
+        iterations: u32,
+    },
 }
 
 /// Sandbox configuration
@@ -311,6 +504,39 @@ pub struct SandboxConfig {
     
     /// Allow file system access
     pub allow_filesystem: bool,
+    
+    /// Run tool processes in a new user namespace (Linux only)
+    #[serde(default)]
+    pub user_namespace: bool,
+    
+    /// Run tool processes in a new mount namespace with only the allowed
+    /// directories mounted (Linux only)
+    #[serde(default)]
+    pub mount_namespace: bool,
+    
+    /// Run tool processes without network access in a new network
+    /// namespace (Linux only)
+    #[serde(default)]
+    pub network_namespace: bool,
+}
+
+/// Operator approval configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalConfig {
+    /// Seconds to wait for a decision before cancelling the operation
+    pub timeout_seconds: u64,
+    
+    /// Hold device writes as well as destructive operations
+    pub approve_device_writes: bool,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 300,
+            approve_device_writes: true,
+        }
+    }
 }
 
 /// Permission configuration
@@ -331,6 +557,64 @@ pub struct RateLimitConfig {
     
     /// Time window in seconds
     pub window_seconds: u64,
+    
+    /// Algorithm used to enforce the limit
+    #[serde(default)]
+    pub algorithm: RateLimitAlgorithm,
+    
+    /// Burst allowance (token bucket capacity, or requests per second
+    /// for the sliding algorithms)
+    #[serde(default)]
+    pub burst_size: Option<u32>,
+    
+    /// Seconds without requests before a key's state is dropped
+    #[serde(default = "default_idle_eviction_seconds")]
+    pub idle_eviction_seconds: u64,
+    
+    /// Limits for API keys, overriding the defaults
+    #[serde(default)]
+    pub api_key: Option<KeyRateLimit>,
+    
+    /// Limits for users, overriding the defaults
+    #[serde(default)]
+    pub user: Option<KeyRateLimit>,
+    
+    /// Limits for custom keys, overriding the defaults
+    #[serde(default)]
+    pub custom: Option<KeyRateLimit>,
+}
+
+fn default_idle_eviction_seconds() -> u64 {
+    3600
+}
+
+/// Rate limiting algorithm
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAlgorithm {
+    /// Tokens refill continuously; `burst_size` is the bucket capacity
+    #[default]
+    TokenBucket,
+    
+    /// Exact log of request times within the window
+    SlidingLog,
+    
+    /// Weighted counts of the current and previous windows
+    SlidingWindowCounter,
+}
+
+/// Rate limit for one key type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRateLimit {
+    /// Number of requests
+    pub requests: u32,
+    
+    /// Time window in seconds
+    pub window_seconds: u64,
+    
+    /// Burst allowance
+    #[serde(default)]
+    pub burst_size: Option<u32>,
 }
 
 /// Logging configuration
@@ -412,6 +696,17 @@ pub struct AgentLongTermMemoryConfig {
     pub max_entries: u32,
 }
 
+impl AgentLongTermMemoryConfig {
+    /// Retrieval settings for semantic memory
+    pub fn rag_config(&self) -> RagConfig {
+        RagConfig {
+            similarity_threshold: self.similarity_threshold,
+            max_entries: self.max_entries as usize,
+            ..RagConfig::default()
+        }
+    }
+}
+
 /// Agent rate limiting configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentRateLimitConfig {
@@ -445,6 +740,10 @@ impl AppConfig {
             .set_default("tools.modbus.max_retries", 3)?
             .set_default("tools.mqtt.broker_port", 1883)?
             .set_default("tools.mqtt.timeout_seconds", 30)?
+            .set_default("tools.mqtt.persistent_session", false)?
+            .set_default("tools.mqtt.ack_timeout_seconds", 10)?
+            .set_default("tools.mqtt.offline_buffer_size", 1000)?
+            .set_default("tools.mqtt.sparkplug_ingestion", false)?
             .set_default("security.token_expiration", 3600)?
             .set_default("logging.level", "info")?
             .set_default("logging.json_format", false)?
@@ -564,6 +863,8 @@ impl Default for AppConfig {
                     default_model: "local-model".to_string(),
                     timeout_seconds: 30,
                 },
+                routing: RoutingConfig::default(),
+                cache: ResponseCacheConfig::default(),
             },
             tools: ToolConfig {
                 file: FileToolConfig {
@@ -587,6 +888,12 @@ impl Default for AppConfig {
                     username: None,
                     password: None,
                     timeout_seconds: 30,
+                    tls: None,
+                    last_will: None,
+                    persistent_session: false,
+                    ack_timeout_seconds: 10,
+                    offline_buffer_size: 1000,
+                    sparkplug_ingestion: false,
                 },
             },
             security: SecurityConfig {
@@ -596,6 +903,12 @@ impl Default for AppConfig {
                 rate_limit: RateLimitConfig {
                     requests: 100,
                     window_seconds: 60,
+                    algorithm: RateLimitAlgorithm::TokenBucket,
+                    burst_size: None,
+                    idle_eviction_seconds: default_idle_eviction_seconds(),
+                    api_key: None,
+                    user: None,
+                    custom: None,
                 },
                 api_keys: ApiKeyConfig {
                     enabled: true,
@@ -605,6 +918,7 @@ impl Default for AppConfig {
                 encryption: EncryptionConfig {
                     algorithm: "ChaCha20-Poly1305".to_string(),
                     key_derivation_iterations: 10000,
+                    master_key: MasterKeySource::default(),
                 },
                 sandbox: SandboxConfig {
                     enabled: true,
@@ -612,11 +926,15 @@ impl Default for AppConfig {
                     max_memory_mb: 256,
                     allow_network: false,
                     allow_filesystem: false,
+                    user_namespace: false,
+                    mount_namespace: false,
+                    network_namespace: false,
                 },
                 permissions: PermissionConfig {
                     default_role: "user".to_string(),
                     strict_checking: true,
                 },
+                approval: ApprovalConfig::default(),
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
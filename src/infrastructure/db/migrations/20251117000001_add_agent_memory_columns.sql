@@ -6,8 +6,9 @@ ALTER TABLE agents ADD COLUMN instructions TEXT;
 ALTER TABLE agents ADD COLUMN prompt_version TEXT DEFAULT '1.0.0';
 
 -- Add agent_id to conversations table for direct agent relationship
-ALTER TABLE conversations ADD COLUMN agent_id TEXT;
-ALTER TABLE conversations ADD FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE SET NULL;
+-- (SQLite cannot add constraints to existing tables, so the reference is
+-- declared on the column itself)
+ALTER TABLE conversations ADD COLUMN agent_id TEXT REFERENCES agents(id) ON DELETE SET NULL;
 
 -- Add metadata columns to messages table for token counting and model tracking
 ALTER TABLE messages ADD COLUMN agent_id TEXT;
@@ -0,0 +1,11 @@
+-- Encrypted credentials referenced by data source connections
+
+CREATE TABLE IF NOT EXISTS secrets (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    fields TEXT NOT NULL, -- JSON array of credential field names
+    nonce BLOB NOT NULL,
+    ciphertext BLOB NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
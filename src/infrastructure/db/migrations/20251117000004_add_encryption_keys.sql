@@ -0,0 +1,13 @@
+-- Versioned data keys, stored wrapped with the master key
+
+CREATE TABLE IF NOT EXISTS encryption_keys (
+    id TEXT PRIMARY KEY NOT NULL,
+    version INTEGER NOT NULL UNIQUE,
+    master_key_id TEXT NOT NULL,
+    nonce BLOB NOT NULL,
+    ciphertext BLOB NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Key used to encrypt each secret; empty for secrets written before keys were versioned
+ALTER TABLE secrets ADD COLUMN key_id TEXT NOT NULL DEFAULT '';
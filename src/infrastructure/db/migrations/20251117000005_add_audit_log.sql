@@ -0,0 +1,30 @@
+-- Hash-chained audit log
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    sequence INTEGER PRIMARY KEY NOT NULL,
+    timestamp DATETIME NOT NULL,
+    category TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT,
+    details TEXT NOT NULL, -- JSON
+    outcome TEXT NOT NULL, -- JSON
+    previous_hash TEXT NOT NULL,
+    hash TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_actor ON audit_log(actor);
+CREATE INDEX IF NOT EXISTS idx_audit_log_target ON audit_log(target);
+
+-- The log is append-only
+CREATE TRIGGER IF NOT EXISTS audit_log_no_update
+BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit log is append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_log_no_delete
+BEFORE DELETE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit log is append-only');
+END;
@@ -0,0 +1,9 @@
+-- Rolling conversation summaries
+
+-- A row with a summary covers messages context_window_start..context_window_end,
+-- ending with message_id
+ALTER TABLE message_context ADD COLUMN summary TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_message_context_summary
+    ON message_context(conversation_id, context_window_end)
+    WHERE summary IS NOT NULL;
@@ -0,0 +1,17 @@
+-- Embedded text chunks for semantic memory
+
+CREATE TABLE IF NOT EXISTS memory_chunks (
+    id TEXT PRIMARY KEY NOT NULL,
+    scope_type TEXT NOT NULL, -- conversation | twin
+    scope_id TEXT NOT NULL,
+    source_type TEXT NOT NULL, -- message | twin_documentation | attachment
+    source_id TEXT NOT NULL,
+    chunk_index INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    embedding BLOB NOT NULL, -- little-endian f32 values
+    dimensions INTEGER NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_memory_chunks_scope ON memory_chunks(scope_type, scope_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_memory_chunks_source_id ON memory_chunks(source_id);
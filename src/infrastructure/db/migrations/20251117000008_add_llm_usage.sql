@@ -0,0 +1,61 @@
+-- LLM usage accounting, model pricing and agent budgets
+
+CREATE TABLE IF NOT EXISTS llm_usage (
+    id TEXT PRIMARY KEY NOT NULL,
+    request_id TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    agent_id TEXT,
+    conversation_id TEXT,
+    prompt_tokens INTEGER NOT NULL DEFAULT 0,
+    completion_tokens INTEGER NOT NULL DEFAULT 0,
+    cached_tokens INTEGER NOT NULL DEFAULT 0,
+    cost REAL NOT NULL DEFAULT 0,
+    currency TEXT NOT NULL DEFAULT 'USD',
+    success INTEGER NOT NULL,
+    latency_ms INTEGER NOT NULL DEFAULT 0,
+    estimated INTEGER NOT NULL DEFAULT 0, -- token counts estimated for streams
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_llm_usage_created_at ON llm_usage(created_at);
+CREATE INDEX IF NOT EXISTS idx_llm_usage_agent ON llm_usage(agent_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_llm_usage_conversation ON llm_usage(conversation_id, created_at);
+
+-- Prices per 1K tokens. `model` matches model names by prefix; an empty
+-- model applies to every model of the provider.
+CREATE TABLE IF NOT EXISTS llm_pricing (
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    input_cost_per_1k REAL NOT NULL,
+    output_cost_per_1k REAL NOT NULL,
+    currency TEXT NOT NULL DEFAULT 'USD',
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (provider, model)
+);
+
+INSERT OR IGNORE INTO llm_pricing (provider, model, input_cost_per_1k, output_cost_per_1k) VALUES
+    ('openai', 'gpt-4o-mini', 0.00015, 0.0006),
+    ('openai', 'gpt-4o', 0.0025, 0.01),
+    ('openai', 'gpt-4-turbo', 0.01, 0.03),
+    ('openai', 'gpt-4', 0.03, 0.06),
+    ('openai', 'gpt-3.5-turbo', 0.0005, 0.0015),
+    ('openai', 'text-embedding-3-small', 0.00002, 0),
+    ('openai', 'text-embedding-3-large', 0.00013, 0),
+    ('anthropic', 'claude-3-5-haiku', 0.0008, 0.004),
+    ('anthropic', 'claude-3-5-sonnet', 0.003, 0.015),
+    ('anthropic', 'claude-3-opus', 0.015, 0.075),
+    ('anthropic', 'claude-3-haiku', 0.00025, 0.00125),
+    ('gemini', 'gemini-1.5-flash', 0.000075, 0.0003),
+    ('gemini', 'gemini-1.5-pro', 0.00125, 0.005),
+    ('ollama', '', 0, 0),
+    ('lmstudio', '', 0, 0);
+
+CREATE TABLE IF NOT EXISTS agent_budgets (
+    agent_id TEXT PRIMARY KEY NOT NULL,
+    monthly_limit REAL NOT NULL,
+    currency TEXT NOT NULL DEFAULT 'USD',
+    action TEXT NOT NULL, -- block | downgrade
+    downgrade_model TEXT,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
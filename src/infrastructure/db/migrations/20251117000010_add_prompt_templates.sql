@@ -0,0 +1,11 @@
+-- Prompt templates with typed variables, rendered with twin context
+
+CREATE TABLE IF NOT EXISTS prompt_templates (
+    name TEXT PRIMARY KEY NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    body TEXT NOT NULL,
+    variables TEXT NOT NULL DEFAULT '[]', -- JSON array of declared variables
+    version INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
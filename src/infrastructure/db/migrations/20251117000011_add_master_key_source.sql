@@ -0,0 +1,8 @@
+-- Master key source recorded by the last master key rotation; it takes
+-- precedence over the configured source when the data keys are unlocked
+
+CREATE TABLE IF NOT EXISTS master_key_source (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id = 1),
+    source TEXT NOT NULL, -- JSON
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,4 @@
+-- Data sources of a twin, with their connections and mappings; credentials
+-- are kept in the vault and only referenced here
+
+ALTER TABLE digital_twins ADD COLUMN data_sources TEXT NOT NULL DEFAULT '[]'; -- JSON
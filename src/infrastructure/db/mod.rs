@@ -1,23 +1,30 @@
 // Database infrastructure implementation
 
-use std::sync::Arc;
+pub mod repositories;
+pub mod sqlite;
+
+pub use sqlite::{SqliteConfig, SqliteManager};
+
 use async_trait::async_trait;
 use anyhow::Result;
-use rusqlite::{Connection, params};
-use tokio::sync::Mutex;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
 use crate::core::domain::traits::DigitalTwinRepository;
 use crate::core::domain::models::DigitalTwin;
 
 pub struct SqliteRepository {
-    conn: Arc<Mutex<Connection>>,
+    pool: SqlitePool,
 }
 
 impl SqliteRepository {
-    pub fn new(db_path: &str) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
+    pub async fn new(db_path: &str) -> Result<Self> {
+        let pool = SqlitePool::connect_with(
+            SqliteConnectOptions::new()
+                .filename(db_path)
+                .create_if_missing(true)
+        ).await?;
         
         // Create tables if they don't exist
-        conn.execute(
+        sqlx::query(
             "CREATE TABLE IF NOT EXISTS digital_twins (
                 id TEXT PRIMARY KEY,
                 name TEXT NOT NULL,
@@ -25,35 +32,33 @@ impl SqliteRepository {
                 configuration TEXT,
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL
-            )",
-            params![],
-        )?;
+            )"
+        )
+        .execute(&pool)
+        .await?;
         
-        Ok(Self {
-            conn: Arc::new(Mutex::new(conn)),
-        })
+        Ok(Self { pool })
     }
 }
 
 #[async_trait]
 impl DigitalTwinRepository for SqliteRepository {
     async fn create(&self, twin: &DigitalTwin) -> Result<()> {
-        let conn = self.conn.lock().await;
         let twin_type = serde_json::to_string(&twin.twin_type)?;
         let configuration = serde_json::to_string(&twin.configuration)?;
         
-        conn.execute(
+        sqlx::query(
             "INSERT INTO digital_twins (id, name, twin_type, configuration, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![
-                twin.id,
-                twin.name,
-                twin_type,
-                configuration,
-                twin.created_at.to_rfc3339(),
-                twin.updated_at.to_rfc3339(),
-            ],
-        )?;
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+        )
+        .bind(twin.id.to_string())
+        .bind(&twin.name)
+        .bind(twin_type)
+        .bind(configuration)
+        .bind(twin.created_at.to_rfc3339())
+        .bind(twin.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
         
         Ok(())
     }
@@ -0,0 +1,149 @@
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Pool, Row, Sqlite};
+
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+use crate::infrastructure::security::{AuditEntry, AuditStore};
+
+/// SQLite storage for the audit chain
+///
+/// The table rejects updates and deletes; entries can only be appended.
+pub struct SqliteAuditStore {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteAuditStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+const SELECT_ENTRY: &str =
+    "SELECT sequence, timestamp, category, actor, action, target, details, outcome, previous_hash, hash
+     FROM audit_log";
+
+#[async_trait]
+impl AuditStore for SqliteAuditStore {
+    async fn append(&self, entry: &AuditEntry) -> RepositoryResult<()> {
+        let serialization_error = |e: serde_json::Error| RepositoryError::SerializationError(e.to_string());
+
+        sqlx::query(
+            "INSERT INTO audit_log
+                 (sequence, timestamp, category, actor, action, target, details, outcome, previous_hash, hash)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(entry.sequence as i64)
+        // Stored as text so the hashed representation survives the round trip
+        .bind(entry.timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true))
+        .bind(serde_json::to_string(&entry.category).map_err(serialization_error)?)
+        .bind(&entry.actor)
+        .bind(&entry.action)
+        .bind(&entry.target)
+        .bind(serde_json::to_string(&entry.details).map_err(serialization_error)?)
+        .bind(serde_json::to_string(&entry.outcome).map_err(serialization_error)?)
+        .bind(&entry.previous_hash)
+        .bind(&entry.hash)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db) if db.message().contains("UNIQUE") => RepositoryError::AlreadyExists {
+                entity_type: "AuditEntry".to_string(),
+                id: entry.sequence.to_string(),
+            },
+            _ => RepositoryError::DatabaseError(e.to_string()),
+        })?;
+
+        Ok(())
+    }
+
+    async fn last_entry(&self) -> RepositoryResult<Option<AuditEntry>> {
+        sqlx::query(&format!("{} ORDER BY sequence DESC LIMIT 1", SELECT_ENTRY))
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?
+            .as_ref()
+            .map(entry_from_row)
+            .transpose()
+    }
+
+    async fn list_entries(&self, after: u64, limit: usize) -> RepositoryResult<Vec<AuditEntry>> {
+        sqlx::query(&format!("{} WHERE sequence > ? ORDER BY sequence LIMIT ?", SELECT_ENTRY))
+            .bind(after as i64)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?
+            .iter()
+            .map(entry_from_row)
+            .collect()
+    }
+}
+
+fn entry_from_row(row: &SqliteRow) -> RepositoryResult<AuditEntry> {
+    let db_error = |e: sqlx::Error| RepositoryError::DatabaseError(e.to_string());
+    let serialization_error = |e: serde_json::Error| RepositoryError::SerializationError(e.to_string());
+
+    let sequence: i64 = row.try_get("sequence").map_err(db_error)?;
+    let timestamp: String = row.try_get("timestamp").map_err(db_error)?;
+    let category: String = row.try_get("category").map_err(db_error)?;
+    let details: String = row.try_get("details").map_err(db_error)?;
+    let outcome: String = row.try_get("outcome").map_err(db_error)?;
+
+    Ok(AuditEntry {
+        sequence: sequence as u64,
+        timestamp: DateTime::parse_from_rfc3339(&timestamp)
+            .map_err(|e| RepositoryError::SerializationError(e.to_string()))?
+            .with_timezone(&Utc),
+        category: serde_json::from_str(&category).map_err(serialization_error)?,
+        actor: row.try_get("actor").map_err(db_error)?,
+        action: row.try_get("action").map_err(db_error)?,
+        target: row.try_get("target").map_err(db_error)?,
+        details: serde_json::from_str(&details).map_err(serialization_error)?,
+        outcome: serde_json::from_str(&outcome).map_err(serialization_error)?,
+        previous_hash: row.try_get("previous_hash").map_err(db_error)?,
+        hash: row.try_get("hash").map_err(db_error)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::security::{AuditCategory, AuditEvent, AuditLog};
+    use serde_json::json;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_chain_round_trip_and_append_only() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::Executor::execute(&pool, include_str!("../migrations/20251117000005_add_audit_log.sql"))
+            .await
+            .unwrap();
+        let log = AuditLog::new(Arc::new(SqliteAuditStore::new(pool.clone())));
+
+        for value in [1, 2, 3] {
+            log.record(
+                AuditEvent::new(AuditCategory::ToolExecution, "alice", "execute_tool")
+                    .with_target("modbus_write")
+                    .with_details(json!({ "value": value, "register": 40001 }))
+            ).await.unwrap();
+        }
+
+        // Hashes still match after loading from the database
+        assert!(log.verify().await.unwrap().valid);
+
+        // A fresh log continues the stored chain
+        let reopened = AuditLog::new(Arc::new(SqliteAuditStore::new(pool.clone())));
+        let entry = reopened.record(AuditEvent::new(AuditCategory::Authentication, "bob", "login")).await.unwrap();
+        assert_eq!(entry.sequence, 4);
+        assert!(reopened.verify().await.unwrap().valid);
+
+        assert!(sqlx::query("UPDATE audit_log SET actor = 'mallory'").execute(&pool).await.is_err());
+        assert!(sqlx::query("DELETE FROM audit_log").execute(&pool).await.is_err());
+    }
+}
@@ -5,6 +5,7 @@ use uuid::Uuid;
 use anyhow::Result;
 use serde_json::Value;
 
+use crate::core::domain::services::TokenCounter;
 use crate::core::domain::{
     models::{Conversation, ConversationId, ConversationState, Message, MessageId},
     traits::repository::{
@@ -48,6 +49,13 @@ impl SqliteConversationRepository {
     }
 }
 
+/// Token count stored with a message, counted for its model when unset
+fn message_token_count(message: &Message) -> i64 {
+    message.metadata.token_count.unwrap_or_else(|| {
+        TokenCounter::for_model(message.metadata.model.as_deref()).count_message(&message.content)
+    }) as i64
+}
+
 #[async_trait]
 impl ConversationRepository for SqliteConversationRepository {
     async fn create(&self, conversation: Conversation) -> RepositoryResult<Conversation> {
@@ -82,7 +90,7 @@ impl ConversationRepository for SqliteConversationRepository {
             .bind(&message.role)
             .bind(&message.content)
             .bind(agent_id)
-            .bind(message.metadata.token_count)
+            .bind(message_token_count(&message))
             .bind(&message.metadata.model)
             .bind(format!("{:?}", message.content_type))
             .bind(serde_json::to_string(&message.metadata).unwrap_or("{}".to_string()))
@@ -234,7 +242,7 @@ impl ConversationRepository for SqliteConversationRepository {
         .bind(&message.role)
         .bind(&message.content)
         .bind(agent_id)
-        .bind(message.metadata.token_count)
+        .bind(message_token_count(&message))
         .bind(&message.metadata.model)
         .bind(format!("{:?}", message.content_type))
         .bind(serde_json::to_string(&message.metadata).unwrap_or("{}".to_string()))
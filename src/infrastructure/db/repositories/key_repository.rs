@@ -0,0 +1,159 @@
+use async_trait::async_trait;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Pool, Row, Sqlite, Transaction};
+
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+use crate::infrastructure::config::MasterKeySource;
+use crate::infrastructure::security::{KeyStore, WrappedKey};
+
+/// SQLite storage for wrapped data encryption keys
+pub struct SqliteKeyStore {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteKeyStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl KeyStore for SqliteKeyStore {
+    async fn list_keys(&self) -> RepositoryResult<Vec<WrappedKey>> {
+        sqlx::query(
+            "SELECT id, version, master_key_id, nonce, ciphertext, created_at
+             FROM encryption_keys ORDER BY version"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?
+        .iter()
+        .map(key_from_row)
+        .collect()
+    }
+
+    async fn save_keys(&self, keys: &[WrappedKey]) -> RepositoryResult<()> {
+        let mut tx = self.pool.begin().await
+            .map_err(|e| RepositoryError::TransactionError(e.to_string()))?;
+
+        upsert_keys(&mut tx, keys).await?;
+
+        tx.commit().await
+            .map_err(|e| RepositoryError::TransactionError(e.to_string()))
+    }
+
+    async fn master_key_source(&self) -> RepositoryResult<Option<MasterKeySource>> {
+        let source: Option<String> = sqlx::query_scalar("SELECT source FROM master_key_source WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        source
+            .map(|json| serde_json::from_str(&json)
+                .map_err(|e| RepositoryError::SerializationError(e.to_string())))
+            .transpose()
+    }
+
+    async fn save_rewrapped_keys(&self, keys: &[WrappedKey], source: &MasterKeySource) -> RepositoryResult<()> {
+        let source = serde_json::to_string(source)
+            .map_err(|e| RepositoryError::SerializationError(e.to_string()))?;
+
+        let mut tx = self.pool.begin().await
+            .map_err(|e| RepositoryError::TransactionError(e.to_string()))?;
+
+        upsert_keys(&mut tx, keys).await?;
+        sqlx::query(
+            "INSERT INTO master_key_source (id, source, updated_at) VALUES (1, ?, CURRENT_TIMESTAMP)
+             ON CONFLICT(id) DO UPDATE SET source = excluded.source, updated_at = excluded.updated_at"
+        )
+        .bind(source)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        tx.commit().await
+            .map_err(|e| RepositoryError::TransactionError(e.to_string()))
+    }
+}
+
+async fn upsert_keys(tx: &mut Transaction<'_, Sqlite>, keys: &[WrappedKey]) -> RepositoryResult<()> {
+    for key in keys {
+        sqlx::query(
+            "INSERT INTO encryption_keys (id, version, master_key_id, nonce, ciphertext, created_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+                 master_key_id = excluded.master_key_id,
+                 nonce = excluded.nonce,
+                 ciphertext = excluded.ciphertext"
+        )
+        .bind(&key.id)
+        .bind(key.version as i64)
+        .bind(&key.master_key_id)
+        .bind(&key.nonce)
+        .bind(&key.ciphertext)
+        .bind(key.created_at)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+fn key_from_row(row: &SqliteRow) -> RepositoryResult<WrappedKey> {
+    let db_error = |e: sqlx::Error| RepositoryError::DatabaseError(e.to_string());
+    let version: i64 = row.try_get("version").map_err(db_error)?;
+
+    Ok(WrappedKey {
+        id: row.try_get("id").map_err(db_error)?,
+        version: version as u32,
+        master_key_id: row.try_get("master_key_id").map_err(db_error)?,
+        nonce: row.try_get("nonce").map_err(db_error)?,
+        ciphertext: row.try_get("ciphertext").map_err(db_error)?,
+        created_at: row.try_get("created_at").map_err(db_error)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::security::{KeyManager, MasterKey, KEY_LEN};
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_keys_survive_reopen_and_master_rotation() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        for migration in [
+            include_str!("../migrations/20251117000003_add_secrets_table.sql"),
+            include_str!("../migrations/20251117000004_add_encryption_keys.sql"),
+            include_str!("../migrations/20251117000011_add_master_key_source.sql"),
+        ] {
+            sqlx::Executor::execute(&pool, migration).await.unwrap();
+        }
+        let store: Arc<dyn KeyStore> = Arc::new(SqliteKeyStore::new(pool));
+
+        let manager = KeyManager::open(MasterKey::from_bytes(&[1; KEY_LEN]).unwrap(), store.clone())
+            .await
+            .unwrap();
+        manager.rotate_data_key().await.unwrap();
+        let encrypted = manager.encryption().encrypt(b"reading").unwrap();
+        let source = MasterKeySource::Env { variable: "NEW_MASTER_KEY".to_string() };
+        manager.rotate_master_key(MasterKey::from_bytes(&[2; KEY_LEN]).unwrap(), &source).await.unwrap();
+
+        let keys = store.list_keys().await.unwrap();
+        assert_eq!(keys.iter().map(|k| k.version).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(matches!(
+            store.master_key_source().await.unwrap(),
+            Some(MasterKeySource::Env { variable }) if variable == "NEW_MASTER_KEY"
+        ));
+
+        let reopened = KeyManager::open(MasterKey::from_bytes(&[2; KEY_LEN]).unwrap(), store)
+            .await
+            .unwrap();
+        assert_eq!(reopened.encryption().decrypt(&encrypted).unwrap(), b"reading");
+    }
+}
@@ -1,16 +1,34 @@
 //! Repository implementations for SQLite database.
 
 mod agent_repository;
+mod audit_repository;
 mod conversation_repository;
+mod key_repository;
+mod prompt_template_repository;
+mod response_cache_repository;
+mod secret_repository;
+mod security_repository;
 mod sensor_data_repository;
+mod summary_repository;
 mod tool_repository;
 mod twin_repository;
+mod usage_repository;
+mod vector_repository;
 
 pub use agent_repository::SqliteAgentRepository;
+pub use audit_repository::SqliteAuditStore;
 pub use conversation_repository::SqliteConversationRepository;
+pub use key_repository::SqliteKeyStore;
+pub use prompt_template_repository::SqlitePromptTemplateStore;
+pub use response_cache_repository::SqliteResponseCacheStore;
+pub use secret_repository::SqliteSecretRepository;
+pub use security_repository::SqliteSecurityRepository;
 pub use sensor_data_repository::SqliteSensorDataRepository;
+pub use summary_repository::SqliteSummaryStore;
 pub use tool_repository::SqliteToolRepository;
 pub use twin_repository::SqliteTwinRepository;
+pub use usage_repository::SqliteUsageStore;
+pub use vector_repository::SqliteVectorStore;
 
 use async_trait::async_trait;
 use sqlx::{Pool, Sqlite};
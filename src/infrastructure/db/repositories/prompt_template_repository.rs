@@ -0,0 +1,157 @@
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Pool, Row, Sqlite};
+
+use crate::core::domain::traits::prompt_template_store::{PromptTemplate, PromptTemplateStore};
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+
+/// SQLite storage for prompt templates
+pub struct SqlitePromptTemplateStore {
+    pool: Pool<Sqlite>,
+}
+
+impl SqlitePromptTemplateStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+fn db_error(e: sqlx::Error) -> RepositoryError {
+    RepositoryError::DatabaseError(e.to_string())
+}
+
+#[async_trait]
+impl PromptTemplateStore for SqlitePromptTemplateStore {
+    async fn get(&self, name: &str) -> RepositoryResult<Option<PromptTemplate>> {
+        let row = sqlx::query(
+            "SELECT name, description, body, variables, version, created_at, updated_at
+             FROM prompt_templates WHERE name = ?"
+        )
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        row.as_ref().map(template_from_row).transpose()
+    }
+
+    async fn list(&self) -> RepositoryResult<Vec<PromptTemplate>> {
+        let rows = sqlx::query(
+            "SELECT name, description, body, variables, version, created_at, updated_at
+             FROM prompt_templates ORDER BY name"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter().map(template_from_row).collect()
+    }
+
+    async fn save(&self, template: &PromptTemplate) -> RepositoryResult<()> {
+        let variables = serde_json::to_string(&template.variables)
+            .map_err(|e| RepositoryError::SerializationError(e.to_string()))?;
+
+        sqlx::query(
+            "INSERT INTO prompt_templates
+                 (name, description, body, variables, version, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT (name) DO UPDATE SET
+                 description = excluded.description,
+                 body = excluded.body,
+                 variables = excluded.variables,
+                 version = excluded.version,
+                 updated_at = excluded.updated_at"
+        )
+        .bind(&template.name)
+        .bind(&template.description)
+        .bind(&template.body)
+        .bind(variables)
+        .bind(template.version as i64)
+        .bind(template.created_at.to_rfc3339_opts(SecondsFormat::Micros, true))
+        .bind(template.updated_at.to_rfc3339_opts(SecondsFormat::Micros, true))
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, name: &str) -> RepositoryResult<bool> {
+        let result = sqlx::query("DELETE FROM prompt_templates WHERE name = ?")
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn template_from_row(row: &SqliteRow) -> RepositoryResult<PromptTemplate> {
+    let invalid = |message: String| RepositoryError::SerializationError(message);
+    let time = |column: &str| -> RepositoryResult<DateTime<Utc>> {
+        let value: String = row.try_get(column).map_err(db_error)?;
+        Ok(DateTime::parse_from_rfc3339(&value)
+            .map_err(|e| invalid(e.to_string()))?
+            .with_timezone(&Utc))
+    };
+    let variables: String = row.try_get("variables").map_err(db_error)?;
+    let version: i64 = row.try_get("version").map_err(db_error)?;
+
+    Ok(PromptTemplate {
+        name: row.try_get("name").map_err(db_error)?,
+        description: row.try_get("description").map_err(db_error)?,
+        body: row.try_get("body").map_err(db_error)?,
+        variables: serde_json::from_str(&variables).map_err(|e| invalid(e.to_string()))?,
+        version: version as u32,
+        created_at: time("created_at")?,
+        updated_at: time("updated_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::traits::prompt_template_store::{TemplateVariable, VariableType};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn test_save_list_and_delete() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::Executor::execute(&pool, include_str!("../migrations/20251117000010_add_prompt_templates.sql"))
+            .await
+            .unwrap();
+        let store = SqlitePromptTemplateStore::new(pool);
+
+        let mut template = PromptTemplate::new("maintenance", "Inspect {{asset}}.").with_variable(TemplateVariable {
+            name: "asset".to_string(),
+            var_type: VariableType::String,
+            required: true,
+            default: None,
+            description: Some("Asset to inspect".to_string()),
+        });
+        template.version = 1;
+        store.save(&template).await.unwrap();
+        store.save(&PromptTemplate::new("basics", "Be brief.")).await.unwrap();
+
+        template.body = "Inspect {{asset}} today.".to_string();
+        template.version = 2;
+        store.save(&template).await.unwrap();
+
+        let loaded = store.get("maintenance").await.unwrap().unwrap();
+        assert_eq!(loaded.body, "Inspect {{asset}} today.");
+        assert_eq!(loaded.version, 2);
+        assert_eq!(loaded.variables, template.variables);
+
+        let names: Vec<_> = store.list().await.unwrap().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, vec!["basics", "maintenance"]);
+        assert!(store.delete("basics").await.unwrap());
+        assert!(!store.delete("basics").await.unwrap());
+        assert!(store.get("basics").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,192 @@
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Pool, Row, Sqlite};
+
+use crate::core::domain::traits::response_cache_store::{CachedResponse, ResponseCacheStore};
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+
+/// SQLite storage for cached chat completions
+pub struct SqliteResponseCacheStore {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteResponseCacheStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+fn db_error(e: sqlx::Error) -> RepositoryError {
+    RepositoryError::DatabaseError(e.to_string())
+}
+
+/// Timestamps are stored in a fixed format so they compare as text
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+#[async_trait]
+impl ResponseCacheStore for SqliteResponseCacheStore {
+    async fn get(&self, key: &str, now: DateTime<Utc>) -> RepositoryResult<Option<CachedResponse>> {
+        let row = sqlx::query(
+            "UPDATE llm_response_cache SET last_used_at = ?, hits = hits + 1
+             WHERE cache_key = ? AND expires_at > ?
+             RETURNING cache_key, model, response, created_at, expires_at"
+        )
+        .bind(timestamp(now))
+        .bind(key)
+        .bind(timestamp(now))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        row.as_ref().map(entry_from_row).transpose()
+    }
+
+    async fn put(&self, entry: &CachedResponse) -> RepositoryResult<()> {
+        let response = serde_json::to_string(&entry.response)
+            .map_err(|e| RepositoryError::SerializationError(e.to_string()))?;
+
+        sqlx::query(
+            "INSERT INTO llm_response_cache
+                 (cache_key, model, response, created_at, expires_at, last_used_at, hits)
+             VALUES (?, ?, ?, ?, ?, ?, 0)
+             ON CONFLICT (cache_key) DO UPDATE SET
+                 model = excluded.model,
+                 response = excluded.response,
+                 created_at = excluded.created_at,
+                 expires_at = excluded.expires_at,
+                 last_used_at = excluded.last_used_at,
+                 hits = 0"
+        )
+        .bind(&entry.key)
+        .bind(&entry.model)
+        .bind(response)
+        .bind(timestamp(entry.created_at))
+        .bind(timestamp(entry.expires_at))
+        .bind(timestamp(entry.created_at))
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn prune(&self, max_entries: usize, now: DateTime<Utc>) -> RepositoryResult<usize> {
+        let expired = sqlx::query("DELETE FROM llm_response_cache WHERE expires_at <= ?")
+            .bind(timestamp(now))
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        let overflow = sqlx::query(
+            "DELETE FROM llm_response_cache WHERE cache_key IN (
+                 SELECT cache_key FROM llm_response_cache
+                 ORDER BY last_used_at DESC
+                 LIMIT -1 OFFSET ?
+             )"
+        )
+        .bind(max_entries as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok((expired.rows_affected() + overflow.rows_affected()) as usize)
+    }
+
+    async fn clear(&self) -> RepositoryResult<usize> {
+        let result = sqlx::query("DELETE FROM llm_response_cache")
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() as usize)
+    }
+
+    async fn len(&self) -> RepositoryResult<usize> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM llm_response_cache")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(count as usize)
+    }
+}
+
+fn entry_from_row(row: &SqliteRow) -> RepositoryResult<CachedResponse> {
+    let invalid = |message: String| RepositoryError::SerializationError(message);
+    let time = |column: &str| -> RepositoryResult<DateTime<Utc>> {
+        let value: String = row.try_get(column).map_err(db_error)?;
+        Ok(DateTime::parse_from_rfc3339(&value)
+            .map_err(|e| invalid(e.to_string()))?
+            .with_timezone(&Utc))
+    };
+    let response: String = row.try_get("response").map_err(db_error)?;
+
+    Ok(CachedResponse {
+        key: row.try_get("cache_key").map_err(db_error)?,
+        model: row.try_get("model").map_err(db_error)?,
+        response: serde_json::from_str(&response).map_err(|e| invalid(e.to_string()))?,
+        created_at: time("created_at")?,
+        expires_at: time("expires_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::traits::llm_client::{ChatCompletionResponse, TokenUsage};
+    use chrono::Duration;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    fn entry(key: &str, created_at: DateTime<Utc>, ttl: Duration) -> CachedResponse {
+        CachedResponse {
+            key: key.to_string(),
+            model: "gpt-4o".to_string(),
+            response: ChatCompletionResponse {
+                request_id: "r1".to_string(),
+                choices: Vec::new(),
+                usage: TokenUsage {
+                    prompt_tokens: 10,
+                    completion_tokens: 2,
+                    total_tokens: 12,
+                    cached_tokens: None,
+                },
+                model: "gpt-4o".to_string(),
+                created_at,
+                processing_time_ms: 800,
+            },
+            created_at,
+            expires_at: created_at + ttl,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_expiry_and_least_recently_used_eviction() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::Executor::execute(&pool, include_str!("../migrations/20251117000009_add_response_cache.sql"))
+            .await
+            .unwrap();
+        let store = SqliteResponseCacheStore::new(pool);
+        let now = Utc::now();
+
+        store.put(&entry("a", now - Duration::seconds(30), Duration::hours(1))).await.unwrap();
+        store.put(&entry("b", now - Duration::seconds(20), Duration::hours(1))).await.unwrap();
+        store.put(&entry("c", now - Duration::seconds(10), Duration::seconds(5))).await.unwrap();
+
+        let hit = store.get("a", now).await.unwrap().unwrap();
+        assert_eq!(hit.response.processing_time_ms, 800);
+        assert!(store.get("c", now).await.unwrap().is_none());
+
+        // "c" has expired and "b" is the least recently used
+        assert_eq!(store.prune(1, now).await.unwrap(), 2);
+        assert_eq!(store.len().await.unwrap(), 1);
+        assert!(store.get("a", now).await.unwrap().is_some());
+        assert_eq!(store.clear().await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,176 @@
+use async_trait::async_trait;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Pool, Row, Sqlite};
+use uuid::Uuid;
+
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+use crate::infrastructure::security::{EncryptedData, SecretRecord, SecretRepository};
+
+/// SQLite storage for encrypted vault secrets
+pub struct SqliteSecretRepository {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteSecretRepository {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+const UPSERT_SECRET: &str =
+    "INSERT INTO secrets (id, name, fields, key_id, nonce, ciphertext, created_at, updated_at)
+     VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+     ON CONFLICT(id) DO UPDATE SET
+         name = excluded.name,
+         fields = excluded.fields,
+         key_id = excluded.key_id,
+         nonce = excluded.nonce,
+         ciphertext = excluded.ciphertext,
+         updated_at = excluded.updated_at";
+
+fn bind_secret<'q>(
+    query: sqlx::query::Query<'q, Sqlite, sqlx::sqlite::SqliteArguments<'q>>,
+    secret: &'q SecretRecord,
+) -> RepositoryResult<sqlx::query::Query<'q, Sqlite, sqlx::sqlite::SqliteArguments<'q>>> {
+    let fields = serde_json::to_string(&secret.fields)
+        .map_err(|e| RepositoryError::SerializationError(e.to_string()))?;
+
+    Ok(query
+        .bind(secret.id.to_string())
+        .bind(&secret.name)
+        .bind(fields)
+        .bind(&secret.data.key_id)
+        .bind(&secret.data.nonce)
+        .bind(&secret.data.ciphertext)
+        .bind(secret.created_at)
+        .bind(secret.updated_at))
+}
+
+#[async_trait]
+impl SecretRepository for SqliteSecretRepository {
+    async fn save_secret(&self, secret: &SecretRecord) -> RepositoryResult<()> {
+        bind_secret(sqlx::query(UPSERT_SECRET), secret)?
+            .execute(&self.pool)
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn get_secret(&self, id: Uuid) -> RepositoryResult<Option<SecretRecord>> {
+        sqlx::query(
+            "SELECT id, name, fields, key_id, nonce, ciphertext, created_at, updated_at
+             FROM secrets WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?
+        .as_ref()
+        .map(secret_from_row)
+        .transpose()
+    }
+
+    async fn delete_secret(&self, id: Uuid) -> RepositoryResult<bool> {
+        let result = sqlx::query("DELETE FROM secrets WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_secrets(&self) -> RepositoryResult<Vec<SecretRecord>> {
+        sqlx::query(
+            "SELECT id, name, fields, key_id, nonce, ciphertext, created_at, updated_at
+             FROM secrets ORDER BY created_at"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?
+        .iter()
+        .map(secret_from_row)
+        .collect()
+    }
+
+    async fn save_secrets(&self, secrets: &[SecretRecord]) -> RepositoryResult<()> {
+        let mut tx = self.pool.begin().await
+            .map_err(|e| RepositoryError::TransactionError(e.to_string()))?;
+
+        for secret in secrets {
+            bind_secret(sqlx::query(UPSERT_SECRET), secret)?
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        }
+
+        tx.commit().await
+            .map_err(|e| RepositoryError::TransactionError(e.to_string()))
+    }
+}
+
+fn secret_from_row(row: &SqliteRow) -> RepositoryResult<SecretRecord> {
+    let db_error = |e: sqlx::Error| RepositoryError::DatabaseError(e.to_string());
+    let id: String = row.try_get("id").map_err(db_error)?;
+    let fields: String = row.try_get("fields").map_err(db_error)?;
+
+    Ok(SecretRecord {
+        id: Uuid::parse_str(&id).map_err(|e| RepositoryError::SerializationError(e.to_string()))?,
+        name: row.try_get("name").map_err(db_error)?,
+        fields: serde_json::from_str(&fields)
+            .map_err(|e| RepositoryError::SerializationError(e.to_string()))?,
+        data: EncryptedData {
+            key_id: row.try_get("key_id").map_err(db_error)?,
+            nonce: row.try_get("nonce").map_err(db_error)?,
+            ciphertext: row.try_get("ciphertext").map_err(db_error)?,
+        },
+        created_at: row.try_get("created_at").map_err(db_error)?,
+        updated_at: row.try_get("updated_at").map_err(db_error)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn test_secret_round_trip() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        for migration in [
+            include_str!("../migrations/20251117000003_add_secrets_table.sql"),
+            include_str!("../migrations/20251117000004_add_encryption_keys.sql"),
+        ] {
+            sqlx::Executor::execute(&pool, migration).await.unwrap();
+        }
+        let repo = SqliteSecretRepository::new(pool);
+
+        let mut secret = SecretRecord {
+            id: Uuid::new_v4(),
+            name: "Broker".to_string(),
+            fields: vec!["password".to_string(), "username".to_string()],
+            data: EncryptedData { key_id: "dek-1".to_string(), nonce: vec![1; 12], ciphertext: vec![2; 32] },
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        repo.save_secret(&secret).await.unwrap();
+
+        secret.data.ciphertext = vec![3; 32];
+        repo.save_secrets(&[secret.clone()]).await.unwrap();
+
+        let loaded = repo.get_secret(secret.id).await.unwrap().unwrap();
+        assert_eq!(loaded.fields, secret.fields);
+        assert_eq!(loaded.data.key_id, "dek-1");
+        assert_eq!(loaded.data.ciphertext, vec![3; 32]);
+        assert_eq!(repo.list_secrets().await.unwrap().len(), 1);
+
+        assert!(repo.delete_secret(secret.id).await.unwrap());
+        assert!(!repo.delete_secret(secret.id).await.unwrap());
+    }
+}
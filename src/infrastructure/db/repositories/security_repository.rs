@@ -0,0 +1,568 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Pool, Row, Sqlite};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+use crate::infrastructure::security::{
+    ApiKey, Permission, Role, SecurityRepository, User, UserPermissions,
+};
+
+/// SQLite storage for users, API keys, roles, permissions and grants
+pub struct SqliteSecurityRepository {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteSecurityRepository {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+
+    async fn load_user(&self, row: Option<SqliteRow>) -> RepositoryResult<Option<User>> {
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let id: String = row.try_get("id").map_err(db_error)?;
+        let roles = sqlx::query(
+            "SELECT r.id, r.name, r.description, r.permissions
+             FROM roles r JOIN user_roles ur ON ur.role_id = r.id
+             WHERE ur.user_id = ?
+             ORDER BY r.id"
+        )
+        .bind(&id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?
+        .iter()
+        .map(role_from_row)
+        .collect::<RepositoryResult<Vec<_>>>()?;
+
+        let permissions = sqlx::query(
+            "SELECT p.id, p.name, p.description, p.resource_type, p.action
+             FROM permissions p JOIN user_permissions up ON up.permission_id = p.id
+             WHERE up.user_id = ?
+             ORDER BY p.id"
+        )
+        .bind(&id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?
+        .iter()
+        .map(permission_from_row)
+        .collect::<RepositoryResult<Vec<_>>>()?;
+
+        Ok(Some(User {
+            id: parse_uuid(&id)?,
+            username: row.try_get("username").map_err(db_error)?,
+            email: row.try_get("email").map_err(db_error)?,
+            password_hash: row.try_get("password_hash").map_err(db_error)?,
+            roles,
+            permissions,
+            created_at: row.try_get("created_at").map_err(db_error)?,
+            last_login_at: row.try_get("last_login_at").map_err(db_error)?,
+        }))
+    }
+}
+
+#[async_trait]
+impl SecurityRepository for SqliteSecurityRepository {
+    async fn save_user(&self, user: &User) -> RepositoryResult<()> {
+        sqlx::query(
+            "INSERT INTO users (id, username, email, password_hash, created_at, last_login_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+                 username = excluded.username,
+                 email = excluded.email,
+                 password_hash = excluded.password_hash,
+                 last_login_at = excluded.last_login_at"
+        )
+        .bind(user.id.to_string())
+        .bind(&user.username)
+        .bind(&user.email)
+        .bind(&user.password_hash)
+        .bind(user.created_at)
+        .bind(user.last_login_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| unique_error(e, "User", &user.username))?;
+
+        Ok(())
+    }
+
+    async fn get_user(&self, id: Uuid) -> RepositoryResult<Option<User>> {
+        let row = sqlx::query(
+            "SELECT id, username, email, password_hash, created_at, last_login_at
+             FROM users WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        self.load_user(row).await
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> RepositoryResult<Option<User>> {
+        let row = sqlx::query(
+            "SELECT id, username, email, password_hash, created_at, last_login_at
+             FROM users WHERE username = ?"
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        self.load_user(row).await
+    }
+
+    async fn delete_user(&self, id: Uuid) -> RepositoryResult<()> {
+        let mut tx = self.pool.begin().await.map_err(|e| RepositoryError::TransactionError(e.to_string()))?;
+
+        for statement in [
+            "DELETE FROM user_roles WHERE user_id = ?",
+            "DELETE FROM user_permissions WHERE user_id = ?",
+            "DELETE FROM resource_grants WHERE user_id = ?",
+            "DELETE FROM users WHERE id = ?",
+        ] {
+            sqlx::query(statement)
+                .bind(id.to_string())
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+
+        tx.commit().await.map_err(|e| RepositoryError::TransactionError(e.to_string()))
+    }
+
+    async fn save_permission(&self, permission: &Permission) -> RepositoryResult<()> {
+        sqlx::query(
+            "INSERT INTO permissions (id, name, description, resource_type, action)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+                 name = excluded.name,
+                 description = excluded.description,
+                 resource_type = excluded.resource_type,
+                 action = excluded.action"
+        )
+        .bind(&permission.id)
+        .bind(&permission.name)
+        .bind(&permission.description)
+        .bind(&permission.resource_type)
+        .bind(&permission.action)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn list_permissions(&self) -> RepositoryResult<Vec<Permission>> {
+        sqlx::query("SELECT id, name, description, resource_type, action FROM permissions ORDER BY id")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?
+            .iter()
+            .map(permission_from_row)
+            .collect()
+    }
+
+    async fn save_role(&self, role: &Role) -> RepositoryResult<()> {
+        let permissions = serde_json::to_string(&role.permissions)
+            .map_err(|e| RepositoryError::SerializationError(e.to_string()))?;
+
+        sqlx::query(
+            "INSERT INTO roles (id, name, description, permissions)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+                 name = excluded.name,
+                 description = excluded.description,
+                 permissions = excluded.permissions"
+        )
+        .bind(&role.id)
+        .bind(&role.name)
+        .bind(&role.description)
+        .bind(permissions)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn list_roles(&self) -> RepositoryResult<Vec<Role>> {
+        sqlx::query("SELECT id, name, description, permissions FROM roles ORDER BY id")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?
+            .iter()
+            .map(role_from_row)
+            .collect()
+    }
+
+    async fn get_user_permissions(&self, user_id: &str) -> RepositoryResult<Option<UserPermissions>> {
+        let roles: Vec<String> = sqlx::query_scalar("SELECT role_id FROM user_roles WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        let permissions: Vec<String> = sqlx::query_scalar("SELECT permission_id FROM user_permissions WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        if roles.is_empty() && permissions.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(UserPermissions {
+            user_id: user_id.to_string(),
+            roles: roles.into_iter().collect(),
+            permissions: permissions.into_iter().collect(),
+        }))
+    }
+
+    async fn add_user_role(&self, user_id: &str, role_id: &str) -> RepositoryResult<()> {
+        sqlx::query("INSERT OR IGNORE INTO user_roles (user_id, role_id) VALUES (?, ?)")
+            .bind(user_id)
+            .bind(role_id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_user_role(&self, user_id: &str, role_id: &str) -> RepositoryResult<()> {
+        sqlx::query("DELETE FROM user_roles WHERE user_id = ? AND role_id = ?")
+            .bind(user_id)
+            .bind(role_id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn add_user_permission(&self, user_id: &str, permission_id: &str) -> RepositoryResult<()> {
+        sqlx::query("INSERT OR IGNORE INTO user_permissions (user_id, permission_id) VALUES (?, ?)")
+            .bind(user_id)
+            .bind(permission_id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_user_permission(&self, user_id: &str, permission_id: &str) -> RepositoryResult<()> {
+        sqlx::query("DELETE FROM user_permissions WHERE user_id = ? AND permission_id = ?")
+            .bind(user_id)
+            .bind(permission_id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn get_resource_grants(&self, resource_key: &str) -> RepositoryResult<HashSet<String>> {
+        let users: Vec<String> = sqlx::query_scalar("SELECT user_id FROM resource_grants WHERE resource_key = ?")
+            .bind(resource_key)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(users.into_iter().collect())
+    }
+
+    async fn add_resource_grant(&self, resource_key: &str, user_id: &str) -> RepositoryResult<()> {
+        sqlx::query("INSERT OR IGNORE INTO resource_grants (resource_key, user_id) VALUES (?, ?)")
+            .bind(resource_key)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_resource_grant(&self, resource_key: &str, user_id: &str) -> RepositoryResult<()> {
+        sqlx::query("DELETE FROM resource_grants WHERE resource_key = ? AND user_id = ?")
+            .bind(resource_key)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn save_api_key(&self, api_key: &ApiKey) -> RepositoryResult<()> {
+        let permissions = serde_json::to_string(&api_key.permissions)
+            .map_err(|e| RepositoryError::SerializationError(e.to_string()))?;
+
+        sqlx::query(
+            "INSERT INTO api_keys
+                 (id, name, prefix, hash, user_id, created_at, expires_at, revoked_at, last_used_at, permissions)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+                 name = excluded.name,
+                 expires_at = excluded.expires_at,
+                 revoked_at = excluded.revoked_at,
+                 last_used_at = COALESCE(excluded.last_used_at, api_keys.last_used_at),
+                 permissions = excluded.permissions"
+        )
+        .bind(api_key.id.to_string())
+        .bind(&api_key.name)
+        .bind(&api_key.prefix)
+        .bind(&api_key.hash)
+        .bind(api_key.user_id.to_string())
+        .bind(api_key.created_at)
+        .bind(api_key.expires_at)
+        .bind(api_key.revoked_at)
+        .bind(api_key.last_used_at)
+        .bind(permissions)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| unique_error(e, "ApiKey", &api_key.prefix))?;
+
+        Ok(())
+    }
+
+    async fn get_api_key(&self, id: Uuid) -> RepositoryResult<Option<ApiKey>> {
+        sqlx::query(
+            "SELECT id, name, prefix, hash, user_id, created_at, expires_at, revoked_at, last_used_at, permissions
+             FROM api_keys WHERE id = ?"
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?
+        .as_ref()
+        .map(api_key_from_row)
+        .transpose()
+    }
+
+    async fn find_api_key_by_hash(&self, hash: &str) -> RepositoryResult<Option<ApiKey>> {
+        sqlx::query(
+            "SELECT id, name, prefix, hash, user_id, created_at, expires_at, revoked_at, last_used_at, permissions
+             FROM api_keys WHERE hash = ?"
+        )
+        .bind(hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?
+        .as_ref()
+        .map(api_key_from_row)
+        .transpose()
+    }
+
+    async fn list_api_keys(&self, user_id: Uuid) -> RepositoryResult<Vec<ApiKey>> {
+        sqlx::query(
+            "SELECT id, name, prefix, hash, user_id, created_at, expires_at, revoked_at, last_used_at, permissions
+             FROM api_keys WHERE user_id = ?
+             ORDER BY created_at"
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?
+        .iter()
+        .map(api_key_from_row)
+        .collect()
+    }
+
+    async fn touch_api_keys(&self, usages: &[(Uuid, DateTime<Utc>)]) -> RepositoryResult<()> {
+        let mut tx = self.pool.begin().await.map_err(|e| RepositoryError::TransactionError(e.to_string()))?;
+
+        for (id, used_at) in usages {
+            sqlx::query(
+                "UPDATE api_keys SET last_used_at = ?
+                 WHERE id = ? AND (last_used_at IS NULL OR last_used_at < ?)"
+            )
+            .bind(used_at)
+            .bind(id.to_string())
+            .bind(used_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        }
+
+        tx.commit().await.map_err(|e| RepositoryError::TransactionError(e.to_string()))
+    }
+}
+
+fn db_error(e: sqlx::Error) -> RepositoryError {
+    RepositoryError::DatabaseError(e.to_string())
+}
+
+/// Map unique constraint violations to `AlreadyExists`
+fn unique_error(e: sqlx::Error, entity_type: &str, id: &str) -> RepositoryError {
+    match &e {
+        sqlx::Error::Database(db) if db.message().contains("UNIQUE") => RepositoryError::AlreadyExists {
+            entity_type: entity_type.to_string(),
+            id: id.to_string(),
+        },
+        _ => db_error(e),
+    }
+}
+
+fn parse_uuid(value: &str) -> RepositoryResult<Uuid> {
+    Uuid::parse_str(value).map_err(|e| RepositoryError::SerializationError(e.to_string()))
+}
+
+fn permission_from_row(row: &SqliteRow) -> RepositoryResult<Permission> {
+    Ok(Permission {
+        id: row.try_get("id").map_err(db_error)?,
+        name: row.try_get("name").map_err(db_error)?,
+        description: row.try_get("description").map_err(db_error)?,
+        resource_type: row.try_get("resource_type").map_err(db_error)?,
+        action: row.try_get("action").map_err(db_error)?,
+    })
+}
+
+fn role_from_row(row: &SqliteRow) -> RepositoryResult<Role> {
+    let permissions: String = row.try_get("permissions").map_err(db_error)?;
+
+    Ok(Role {
+        id: row.try_get("id").map_err(db_error)?,
+        name: row.try_get("name").map_err(db_error)?,
+        description: row.try_get("description").map_err(db_error)?,
+        permissions: serde_json::from_str(&permissions)
+            .map_err(|e| RepositoryError::SerializationError(e.to_string()))?,
+    })
+}
+
+fn api_key_from_row(row: &SqliteRow) -> RepositoryResult<ApiKey> {
+    let id: String = row.try_get("id").map_err(db_error)?;
+    let user_id: String = row.try_get("user_id").map_err(db_error)?;
+    let permissions: String = row.try_get("permissions").map_err(db_error)?;
+
+    Ok(ApiKey {
+        id: parse_uuid(&id)?,
+        name: row.try_get("name").map_err(db_error)?,
+        prefix: row.try_get("prefix").map_err(db_error)?,
+        hash: row.try_get("hash").map_err(db_error)?,
+        user_id: parse_uuid(&user_id)?,
+        created_at: row.try_get("created_at").map_err(db_error)?,
+        expires_at: row.try_get("expires_at").map_err(db_error)?,
+        revoked_at: row.try_get("revoked_at").map_err(db_error)?,
+        last_used_at: row.try_get("last_used_at").map_err(db_error)?,
+        permissions: serde_json::from_str(&permissions)
+            .map_err(|e| RepositoryError::SerializationError(e.to_string()))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn create_test_repository() -> SqliteSecurityRepository {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::Executor::execute(&pool, include_str!("../migrations/20251117000002_add_security_tables.sql"))
+            .await
+            .unwrap();
+
+        SqliteSecurityRepository::new(pool)
+    }
+
+    fn test_user(username: &str) -> User {
+        User {
+            id: Uuid::new_v4(),
+            username: username.to_string(),
+            email: format!("{}@example.com", username),
+            password_hash: "hash".to_string(),
+            roles: vec![],
+            permissions: vec![],
+            created_at: Utc::now(),
+            last_login_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_with_assignments_round_trip() {
+        let repo = create_test_repository().await;
+        let user = test_user("alice");
+
+        repo.save_role(&Role::user()).await.unwrap();
+        repo.save_permission(&Permission::write("twin")).await.unwrap();
+        repo.save_user(&user).await.unwrap();
+        repo.add_user_role(&user.id.to_string(), "user").await.unwrap();
+        repo.add_user_permission(&user.id.to_string(), "twin:write").await.unwrap();
+
+        let loaded = repo.get_user_by_username("alice").await.unwrap().unwrap();
+        assert_eq!(loaded.id, user.id);
+        assert_eq!(loaded.roles, vec![Role::user()]);
+        assert_eq!(loaded.permissions, vec![Permission::write("twin")]);
+
+        let assignments = repo.get_user_permissions(&user.id.to_string()).await.unwrap().unwrap();
+        assert!(assignments.has_role("user"));
+        assert!(assignments.has_direct_permission("twin:write"));
+
+        // Usernames are unique
+        let duplicate = repo.save_user(&test_user("alice")).await;
+        assert!(matches!(duplicate, Err(RepositoryError::AlreadyExists { .. })));
+
+        repo.delete_user(user.id).await.unwrap();
+        assert!(repo.get_user(user.id).await.unwrap().is_none());
+        assert!(repo.get_user_permissions(&user.id.to_string()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_api_key_round_trip_and_touch() {
+        let repo = create_test_repository().await;
+        let api_key = ApiKey {
+            id: Uuid::new_v4(),
+            name: "CI".to_string(),
+            prefix: "abcdefgh".to_string(),
+            hash: "hash-value".to_string(),
+            user_id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            expires_at: None,
+            revoked_at: None,
+            last_used_at: None,
+            permissions: vec![Permission::read("twin")],
+        };
+
+        repo.save_api_key(&api_key).await.unwrap();
+        let found = repo.find_api_key_by_hash("hash-value").await.unwrap().unwrap();
+        assert_eq!(found.id, api_key.id);
+        assert_eq!(found.permissions, vec![Permission::read("twin")]);
+
+        let used_at = Utc::now();
+        repo.touch_api_keys(&[(api_key.id, used_at)]).await.unwrap();
+        let touched = repo.get_api_key(api_key.id).await.unwrap().unwrap();
+        assert_eq!(touched.last_used_at.map(|t| t.timestamp_millis()), Some(used_at.timestamp_millis()));
+
+        // Revoking keeps the recorded use
+        let mut revoked = api_key.clone();
+        revoked.revoked_at = Some(Utc::now());
+        repo.save_api_key(&revoked).await.unwrap();
+        let stored = repo.list_api_keys(api_key.user_id).await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert!(stored[0].revoked_at.is_some());
+        assert!(stored[0].last_used_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_resource_grants() {
+        let repo = create_test_repository().await;
+
+        repo.add_resource_grant("twin:twin123", "user2").await.unwrap();
+        repo.add_resource_grant("twin:twin123", "user2").await.unwrap();
+        assert_eq!(repo.get_resource_grants("twin:twin123").await.unwrap().len(), 1);
+
+        repo.remove_resource_grant("twin:twin123", "user2").await.unwrap();
+        assert!(repo.get_resource_grants("twin:twin123").await.unwrap().is_empty());
+    }
+}
@@ -5,7 +5,7 @@ use uuid::Uuid;
 use anyhow::Result;
 
 use crate::core::domain::{
-    models::{SensorData, SensorDataId, TwinId, SensorReading},
+    models::{SensorData, SensorDataId, TwinId, SensorReading, SensorValue, ReadingQuality},
     traits::repository::{
         SensorDataRepository, RepositoryResult, RepositoryError,
         FilterCriteria, SortCriteria, Pagination, PaginatedResult,
@@ -127,18 +127,33 @@ impl SensorDataRepository for SqliteSensorDataRepository {
         sensor_data_id: SensorDataId,
         reading: SensorReading,
     ) -> RepositoryResult<()> {
-        sqlx::query(
-            "INSERT INTO sensor_readings (id, sensor_data_id, value, timestamp, metadata) 
-             VALUES (?, ?, ?, ?, ?)"
-        )
-        .bind(Uuid::new_v4().to_string())
-        .bind(sensor_data_id.to_string())
-        .bind(reading.value)
-        .bind(reading.timestamp)
-        .bind(serde_json::to_string(&reading.metadata).unwrap_or("{}".to_string()))
-        .execute(&self.pool)
-        .await
-        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        insert_reading(sensor_data_id, &reading)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn add_readings(
+        &self,
+        sensor_data_id: SensorDataId,
+        readings: Vec<SensorReading>,
+    ) -> RepositoryResult<()> {
+        let mut tx = self.pool.begin()
+            .await
+            .map_err(|e| RepositoryError::TransactionError(e.to_string()))?;
+
+        for reading in &readings {
+            insert_reading(sensor_data_id, reading)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| RepositoryError::TransactionError(e.to_string()))?;
 
         Ok(())
     }
@@ -272,6 +287,31 @@ struct SensorDataRow {
     metadata: String,
 }
 
+/// Insert statement for one reading.
+///
+/// The `value` column holds the numeric value used by range queries; the
+/// full reading (value, quality, context, alerts) is kept as JSON metadata.
+fn insert_reading(
+    sensor_data_id: SensorDataId,
+    reading: &SensorReading,
+) -> sqlx::query::Query<'static, Sqlite, sqlx::sqlite::SqliteArguments<'static>> {
+    let value = match &reading.value {
+        SensorValue::Numeric(v) => *v,
+        SensorValue::Boolean(b) => if *b { 1.0 } else { 0.0 },
+        _ => 0.0,
+    };
+
+    sqlx::query(
+        "INSERT INTO sensor_readings (id, sensor_data_id, value, timestamp, metadata) 
+         VALUES (?, ?, ?, ?, ?)"
+    )
+    .bind(reading.id.to_string())
+    .bind(sensor_data_id.to_string())
+    .bind(value)
+    .bind(reading.timestamp)
+    .bind(serde_json::to_string(reading).unwrap_or_else(|_| "{}".to_string()))
+}
+
 #[derive(sqlx::FromRow)]
 struct ReadingRow {
     id: String,
@@ -296,11 +336,15 @@ impl From<SensorDataRow> for SensorData {
 
 impl From<ReadingRow> for SensorReading {
     fn from(row: ReadingRow) -> Self {
-        Self {
-            value: row.value,
+        // Rows written without the reading JSON only carry the numeric value
+        serde_json::from_str(&row.metadata).unwrap_or_else(|_| Self {
+            id: Uuid::parse_str(&row.id).unwrap_or_else(|_| Uuid::new_v4()),
+            value: SensorValue::Numeric(row.value),
             timestamp: row.timestamp,
-            metadata: serde_json::from_str(&row.metadata).unwrap_or_default(),
-        }
+            quality: ReadingQuality::default(),
+            context: None,
+            alerts: Vec::new(),
+        })
     }
 }
 
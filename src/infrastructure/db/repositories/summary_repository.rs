@@ -0,0 +1,162 @@
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Pool, Row, Sqlite};
+use uuid::Uuid;
+
+use crate::core::domain::traits::summary_store::{ConversationSummary, SummaryStore};
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+
+/// SQLite storage for conversation summaries in the `message_context` table
+pub struct SqliteSummaryStore {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteSummaryStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SummaryStore for SqliteSummaryStore {
+    async fn latest_summary(&self, conversation_id: Uuid) -> RepositoryResult<Option<ConversationSummary>> {
+        sqlx::query(
+            "SELECT id, conversation_id, message_id, context_window_start, context_window_end,
+                    token_count, summary, created_at
+             FROM message_context
+             WHERE conversation_id = ? AND summary IS NOT NULL
+             ORDER BY context_window_end DESC, created_at DESC
+             LIMIT 1"
+        )
+        .bind(conversation_id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?
+        .as_ref()
+        .map(summary_from_row)
+        .transpose()
+    }
+
+    async fn save_summary(&self, summary: &ConversationSummary) -> RepositoryResult<()> {
+        sqlx::query(
+            "INSERT INTO message_context
+                 (id, conversation_id, message_id, context_window_start, context_window_end,
+                  token_count, summary, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(summary.id.to_string())
+        .bind(summary.conversation_id.to_string())
+        .bind(summary.through_message_id.to_string())
+        .bind(summary.range_start as i64)
+        .bind(summary.range_end as i64)
+        .bind(summary.token_count as i64)
+        .bind(&summary.content)
+        .bind(summary.created_at.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+fn summary_from_row(row: &SqliteRow) -> RepositoryResult<ConversationSummary> {
+    let db_error = |e: sqlx::Error| RepositoryError::DatabaseError(e.to_string());
+    let uuid = |column: &str| -> RepositoryResult<Uuid> {
+        let value: String = row.try_get(column).map_err(db_error)?;
+        Uuid::parse_str(&value).map_err(|e| RepositoryError::SerializationError(e.to_string()))
+    };
+
+    let range_start: i64 = row.try_get("context_window_start").map_err(db_error)?;
+    let range_end: i64 = row.try_get("context_window_end").map_err(db_error)?;
+    let token_count: i64 = row.try_get("token_count").map_err(db_error)?;
+    let created_at: String = row.try_get("created_at").map_err(db_error)?;
+
+    Ok(ConversationSummary {
+        id: uuid("id")?,
+        conversation_id: uuid("conversation_id")?,
+        through_message_id: uuid("message_id")?,
+        range_start: range_start as usize,
+        range_end: range_end as usize,
+        content: row.try_get("summary").map_err(db_error)?,
+        token_count: token_count as u32,
+        created_at: DateTime::parse_from_rfc3339(&created_at)
+            .map_err(|e| RepositoryError::SerializationError(e.to_string()))?
+            .with_timezone(&Utc),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn test_latest_summary_round_trip() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::Executor::execute(&pool, include_str!("../migrations/20251117000000_initial_schema.sql"))
+            .await
+            .unwrap();
+        // The message_context table as created by the agent memory migration
+        sqlx::Executor::execute(&pool, "
+            CREATE TABLE message_context (
+                id TEXT PRIMARY KEY NOT NULL,
+                conversation_id TEXT NOT NULL,
+                message_id TEXT NOT NULL,
+                context_window_start INTEGER,
+                context_window_end INTEGER,
+                token_count INTEGER,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE,
+                FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+            );
+        ")
+        .await
+        .unwrap();
+        sqlx::Executor::execute(&pool, include_str!("../migrations/20251117000006_add_conversation_summaries.sql"))
+            .await
+            .unwrap();
+
+        let conversation_id = Uuid::new_v4();
+        let message_ids = [Uuid::new_v4(), Uuid::new_v4()];
+        sqlx::query("INSERT INTO conversations (id, title, state) VALUES (?, 'Pump', 'Active')")
+            .bind(conversation_id.to_string())
+            .execute(&pool)
+            .await
+            .unwrap();
+        for id in message_ids {
+            sqlx::query("INSERT INTO messages (id, conversation_id, role, content) VALUES (?, ?, 'user', 'hi')")
+                .bind(id.to_string())
+                .bind(conversation_id.to_string())
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let store = SqliteSummaryStore::new(pool);
+        assert!(store.latest_summary(conversation_id).await.unwrap().is_none());
+
+        for (end, message_id) in [(4, message_ids[0]), (9, message_ids[1])] {
+            store.save_summary(&ConversationSummary {
+                id: Uuid::new_v4(),
+                conversation_id,
+                through_message_id: message_id,
+                range_start: 0,
+                range_end: end,
+                content: format!("summary through {}", end),
+                token_count: 5,
+                created_at: Utc::now(),
+            }).await.unwrap();
+        }
+
+        let latest = store.latest_summary(conversation_id).await.unwrap().unwrap();
+        assert_eq!(latest.range_end, 9);
+        assert_eq!(latest.through_message_id, message_ids[1]);
+        assert_eq!(latest.content, "summary through 9");
+    }
+}
@@ -7,7 +7,7 @@ use anyhow::Result;
 use serde_json::Value;
 
 use crate::core::domain::{
-    models::{DigitalTwin, TwinId, TwinState, TwinType, AgentId},
+    models::{DigitalTwin, Measurement, TwinId, TwinState, TwinType, AgentId},
     traits::repository::{
         TwinRepository, RepositoryResult, RepositoryError,
         FilterCriteria, SortCriteria, Pagination, PaginatedResult,
@@ -54,8 +54,8 @@ impl TwinRepository for SqliteTwinRepository {
     async fn create(&self, twin: DigitalTwin) -> RepositoryResult<DigitalTwin> {
         sqlx::query(
             "INSERT INTO digital_twins 
-             (id, name, description, twin_type, state, properties, agent_id, metadata) 
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+             (id, name, description, twin_type, state, properties, agent_id, metadata, data_sources) 
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(twin.id.to_string())
         .bind(&twin.name)
@@ -65,6 +65,7 @@ impl TwinRepository for SqliteTwinRepository {
         .bind(serde_json::to_string(&twin.properties).unwrap_or("{}".to_string()))
         .bind(twin.agent_id.map(|id| id.to_string()))
         .bind(serde_json::to_string(&twin.metadata).unwrap_or("{}".to_string()))
+        .bind(serde_json::to_string(&twin.data_sources).unwrap_or("[]".to_string()))
         .execute(&self.pool)
         .await
         .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
@@ -76,7 +77,7 @@ impl TwinRepository for SqliteTwinRepository {
         let twin = sqlx::query_as!(
             TwinRow,
             "SELECT id, name, description, twin_type, state, properties, agent_id, 
-                    last_sync, created_at, updated_at, metadata 
+                    last_sync, created_at, updated_at, metadata, data_sources 
              FROM digital_twins 
              WHERE id = ?",
             id.to_string()
@@ -96,7 +97,8 @@ impl TwinRepository for SqliteTwinRepository {
         sqlx::query(
             "UPDATE digital_twins 
              SET name = ?, description = ?, twin_type = ?, state = ?, 
-                 properties = ?, agent_id = ?, updated_at = CURRENT_TIMESTAMP, metadata = ?
+                 properties = ?, agent_id = ?, updated_at = CURRENT_TIMESTAMP, metadata = ?,
+                 data_sources = ?
              WHERE id = ?"
         )
         .bind(&twin.name)
@@ -106,6 +108,7 @@ impl TwinRepository for SqliteTwinRepository {
         .bind(serde_json::to_string(&twin.properties).unwrap_or("{}".to_string()))
         .bind(twin.agent_id.map(|id| id.to_string()))
         .bind(serde_json::to_string(&twin.metadata).unwrap_or("{}".to_string()))
+        .bind(serde_json::to_string(&twin.data_sources).unwrap_or("[]".to_string()))
         .bind(twin.id.to_string())
         .execute(&self.pool)
         .await
@@ -132,7 +135,7 @@ impl TwinRepository for SqliteTwinRepository {
     ) -> RepositoryResult<PaginatedResult<DigitalTwin>> {
         let mut query = String::from(
             "SELECT id, name, description, twin_type, state, properties, 
-                    agent_id, last_sync, created_at, updated_at, metadata 
+                    agent_id, last_sync, created_at, updated_at, metadata, data_sources 
              FROM digital_twins"
         );
         let params = Self::build_filters(&mut query, &filters).await;
@@ -179,7 +182,7 @@ impl TwinRepository for SqliteTwinRepository {
         let twins = sqlx::query_as!(
             TwinRow,
             "SELECT id, name, description, twin_type, state, properties, 
-                    agent_id, last_sync, created_at, updated_at, metadata 
+                    agent_id, last_sync, created_at, updated_at, metadata, data_sources 
              FROM digital_twins 
              WHERE twin_type = ? 
              LIMIT ? OFFSET ?",
@@ -215,7 +218,7 @@ impl TwinRepository for SqliteTwinRepository {
         let twins = sqlx::query_as!(
             TwinRow,
             "SELECT id, name, description, twin_type, state, properties, 
-                    agent_id, last_sync, created_at, updated_at, metadata 
+                    agent_id, last_sync, created_at, updated_at, metadata, data_sources 
              FROM digital_twins 
              WHERE state = ? 
              LIMIT ? OFFSET ?",
@@ -251,7 +254,7 @@ impl TwinRepository for SqliteTwinRepository {
         let twins = sqlx::query_as!(
             TwinRow,
             "SELECT id, name, description, twin_type, state, properties, 
-                    agent_id, last_sync, created_at, updated_at, metadata 
+                    agent_id, last_sync, created_at, updated_at, metadata, data_sources 
              FROM digital_twins 
              WHERE agent_id = ? 
              LIMIT ? OFFSET ?",
@@ -313,6 +316,42 @@ impl TwinRepository for SqliteTwinRepository {
         Ok(())
     }
 
+    async fn update_measurements(
+        &self,
+        id: TwinId,
+        measurements: HashMap<String, Measurement>,
+    ) -> RepositoryResult<()> {
+        // Each measurement is set in place, so concurrent ingestion and
+        // edits of other properties are not overwritten
+        let mut tx = self.pool.begin()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        for (name, measurement) in measurements {
+            let path = format!("$.measurements.\"{}\"", name.replace('"', "\\\""));
+            let value = serde_json::to_string(&measurement)
+                .map_err(|e| RepositoryError::SerializationError(e.to_string()))?;
+            sqlx::query(
+                "UPDATE digital_twins 
+                 SET properties = json_set(properties, ?1, json(?2)), updated_at = CURRENT_TIMESTAMP 
+                 WHERE id = ?3 
+                   AND COALESCE(julianday(json_extract(properties, ?1 || '.timestamp')) <= julianday(?4), 1)"
+            )
+            .bind(&path)
+            .bind(value)
+            .bind(id.to_string())
+            .bind(measurement.timestamp.to_rfc3339())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
     async fn mark_synchronized(
         &self,
         id: TwinId,
@@ -336,7 +375,7 @@ impl TwinRepository for SqliteTwinRepository {
         let twins = sqlx::query_as!(
             TwinRow,
             "SELECT id, name, description, twin_type, state, properties, 
-                    agent_id, last_sync, created_at, updated_at, metadata 
+                    agent_id, last_sync, created_at, updated_at, metadata, data_sources 
              FROM digital_twins 
              WHERE last_sync IS NULL OR 
                    (state = ? AND datetime(last_sync) < datetime('now', '-1 hour')) 
@@ -367,6 +406,7 @@ struct TwinRow {
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
     metadata: String,
+    data_sources: String,
 }
 
 impl From<TwinRow> for DigitalTwin {
@@ -383,6 +423,7 @@ impl From<TwinRow> for DigitalTwin {
             created_at: row.created_at,
             updated_at: row.updated_at,
             metadata: serde_json::from_str(&row.metadata).unwrap_or_default(),
+            data_sources: serde_json::from_str(&row.data_sources).unwrap_or_default(),
         }
     }
 }
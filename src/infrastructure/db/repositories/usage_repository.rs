@@ -0,0 +1,363 @@
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Pool, QueryBuilder, Row, Sqlite};
+use uuid::Uuid;
+
+use crate::core::domain::traits::usage_store::{
+    AgentBudget, BudgetAction, PricingEntry, UsageFilter, UsageRecord, UsageStore,
+};
+use crate::core::domain::traits::llm_client::TokenUsage;
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+
+/// SQLite storage for LLM usage records, pricing and agent budgets
+pub struct SqliteUsageStore {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteUsageStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+fn db_error(e: sqlx::Error) -> RepositoryError {
+    RepositoryError::DatabaseError(e.to_string())
+}
+
+/// Timestamps are stored in a fixed format so they compare as text
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+#[async_trait]
+impl UsageStore for SqliteUsageStore {
+    async fn record(&self, record: &UsageRecord) -> RepositoryResult<()> {
+        sqlx::query(
+            "INSERT INTO llm_usage
+                 (id, request_id, provider, model, agent_id, conversation_id, prompt_tokens,
+                  completion_tokens, cached_tokens, cost, currency, success, latency_ms,
+                  estimated, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(record.id.to_string())
+        .bind(&record.request_id)
+        .bind(&record.provider)
+        .bind(&record.model)
+        .bind(record.agent_id.map(|id| id.to_string()))
+        .bind(record.conversation_id.map(|id| id.to_string()))
+        .bind(record.usage.prompt_tokens as i64)
+        .bind(record.usage.completion_tokens as i64)
+        .bind(record.usage.cached_tokens.unwrap_or(0) as i64)
+        .bind(record.cost)
+        .bind(&record.currency)
+        .bind(record.success)
+        .bind(record.latency_ms as i64)
+        .bind(record.estimated)
+        .bind(timestamp(record.created_at))
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn records(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        filter: &UsageFilter,
+    ) -> RepositoryResult<Vec<UsageRecord>> {
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "SELECT id, request_id, provider, model, agent_id, conversation_id, prompt_tokens,
+                    completion_tokens, cached_tokens, cost, currency, success, latency_ms,
+                    estimated, created_at
+             FROM llm_usage WHERE created_at >= "
+        );
+        builder
+            .push_bind(timestamp(start))
+            .push(" AND created_at <= ")
+            .push_bind(timestamp(end));
+        if let Some(agent_id) = filter.agent_id {
+            builder.push(" AND agent_id = ").push_bind(agent_id.to_string());
+        }
+        if let Some(conversation_id) = filter.conversation_id {
+            builder.push(" AND conversation_id = ").push_bind(conversation_id.to_string());
+        }
+        if let Some(provider) = &filter.provider {
+            builder.push(" AND provider = ").push_bind(provider.clone());
+        }
+        if let Some(model) = &filter.model {
+            builder.push(" AND model = ").push_bind(model.clone());
+        }
+        builder.push(" ORDER BY created_at");
+
+        builder
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?
+            .iter()
+            .map(record_from_row)
+            .collect()
+    }
+
+    async fn agent_cost(&self, agent_id: Uuid, since: DateTime<Utc>) -> RepositoryResult<f64> {
+        sqlx::query_scalar(
+            "SELECT COALESCE(SUM(cost), 0.0) FROM llm_usage WHERE agent_id = ? AND created_at >= ?"
+        )
+        .bind(agent_id.to_string())
+        .bind(timestamp(since))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)
+    }
+
+    async fn pricing(&self) -> RepositoryResult<Vec<PricingEntry>> {
+        let rows = sqlx::query(
+            "SELECT provider, model, input_cost_per_1k, output_cost_per_1k, currency
+             FROM llm_pricing ORDER BY provider, model"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter()
+            .map(|row| Ok(PricingEntry {
+                provider: row.try_get("provider").map_err(db_error)?,
+                model: row.try_get("model").map_err(db_error)?,
+                input_cost_per_1k: row.try_get("input_cost_per_1k").map_err(db_error)?,
+                output_cost_per_1k: row.try_get("output_cost_per_1k").map_err(db_error)?,
+                currency: row.try_get("currency").map_err(db_error)?,
+            }))
+            .collect()
+    }
+
+    async fn set_pricing(&self, entry: &PricingEntry) -> RepositoryResult<()> {
+        sqlx::query(
+            "INSERT INTO llm_pricing (provider, model, input_cost_per_1k, output_cost_per_1k, currency, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT (provider, model) DO UPDATE SET
+                 input_cost_per_1k = excluded.input_cost_per_1k,
+                 output_cost_per_1k = excluded.output_cost_per_1k,
+                 currency = excluded.currency,
+                 updated_at = excluded.updated_at"
+        )
+        .bind(entry.provider.to_lowercase())
+        .bind(&entry.model)
+        .bind(entry.input_cost_per_1k)
+        .bind(entry.output_cost_per_1k)
+        .bind(&entry.currency)
+        .bind(timestamp(Utc::now()))
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn budget(&self, agent_id: Uuid) -> RepositoryResult<Option<AgentBudget>> {
+        sqlx::query(
+            "SELECT agent_id, monthly_limit, currency, action, downgrade_model
+             FROM agent_budgets WHERE agent_id = ?"
+        )
+        .bind(agent_id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?
+        .as_ref()
+        .map(budget_from_row)
+        .transpose()
+    }
+
+    async fn budgets(&self) -> RepositoryResult<Vec<AgentBudget>> {
+        sqlx::query("SELECT agent_id, monthly_limit, currency, action, downgrade_model FROM agent_budgets")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?
+            .iter()
+            .map(budget_from_row)
+            .collect()
+    }
+
+    async fn set_budget(&self, budget: &AgentBudget) -> RepositoryResult<()> {
+        let (action, downgrade_model) = match &budget.action {
+            BudgetAction::Block => ("block", None),
+            BudgetAction::Downgrade { model } => ("downgrade", Some(model.as_str())),
+        };
+        sqlx::query(
+            "INSERT INTO agent_budgets (agent_id, monthly_limit, currency, action, downgrade_model, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT (agent_id) DO UPDATE SET
+                 monthly_limit = excluded.monthly_limit,
+                 currency = excluded.currency,
+                 action = excluded.action,
+                 downgrade_model = excluded.downgrade_model,
+                 updated_at = excluded.updated_at"
+        )
+        .bind(budget.agent_id.to_string())
+        .bind(budget.monthly_limit)
+        .bind(&budget.currency)
+        .bind(action)
+        .bind(downgrade_model)
+        .bind(timestamp(Utc::now()))
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_budget(&self, agent_id: Uuid) -> RepositoryResult<bool> {
+        let result = sqlx::query("DELETE FROM agent_budgets WHERE agent_id = ?")
+            .bind(agent_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn parse_uuid(value: &str) -> RepositoryResult<Uuid> {
+    Uuid::parse_str(value).map_err(|e| RepositoryError::SerializationError(e.to_string()))
+}
+
+fn record_from_row(row: &SqliteRow) -> RepositoryResult<UsageRecord> {
+    let id: String = row.try_get("id").map_err(db_error)?;
+    let agent_id: Option<String> = row.try_get("agent_id").map_err(db_error)?;
+    let conversation_id: Option<String> = row.try_get("conversation_id").map_err(db_error)?;
+    let prompt_tokens: i64 = row.try_get("prompt_tokens").map_err(db_error)?;
+    let completion_tokens: i64 = row.try_get("completion_tokens").map_err(db_error)?;
+    let cached_tokens: i64 = row.try_get("cached_tokens").map_err(db_error)?;
+    let latency_ms: i64 = row.try_get("latency_ms").map_err(db_error)?;
+    let created_at: String = row.try_get("created_at").map_err(db_error)?;
+
+    Ok(UsageRecord {
+        id: parse_uuid(&id)?,
+        request_id: row.try_get("request_id").map_err(db_error)?,
+        provider: row.try_get("provider").map_err(db_error)?,
+        model: row.try_get("model").map_err(db_error)?,
+        agent_id: agent_id.as_deref().map(parse_uuid).transpose()?,
+        conversation_id: conversation_id.as_deref().map(parse_uuid).transpose()?,
+        usage: TokenUsage {
+            prompt_tokens: prompt_tokens as u32,
+            completion_tokens: completion_tokens as u32,
+            total_tokens: (prompt_tokens + completion_tokens) as u32,
+            cached_tokens: (cached_tokens > 0).then_some(cached_tokens as u32),
+        },
+        cost: row.try_get("cost").map_err(db_error)?,
+        currency: row.try_get("currency").map_err(db_error)?,
+        success: row.try_get("success").map_err(db_error)?,
+        latency_ms: latency_ms as u64,
+        estimated: row.try_get("estimated").map_err(db_error)?,
+        created_at: DateTime::parse_from_rfc3339(&created_at)
+            .map_err(|e| RepositoryError::SerializationError(e.to_string()))?
+            .with_timezone(&Utc),
+    })
+}
+
+fn budget_from_row(row: &SqliteRow) -> RepositoryResult<AgentBudget> {
+    let agent_id: String = row.try_get("agent_id").map_err(db_error)?;
+    let action: String = row.try_get("action").map_err(db_error)?;
+    let downgrade_model: Option<String> = row.try_get("downgrade_model").map_err(db_error)?;
+
+    let action = match (action.as_str(), downgrade_model) {
+        ("block", _) => BudgetAction::Block,
+        ("downgrade", Some(model)) => BudgetAction::Downgrade { model },
+        (other, _) => return Err(RepositoryError::SerializationError(
+            format!("invalid budget action '{}'", other)
+        )),
+    };
+
+    Ok(AgentBudget {
+        agent_id: parse_uuid(&agent_id)?,
+        monthly_limit: row.try_get("monthly_limit").map_err(db_error)?,
+        currency: row.try_get("currency").map_err(db_error)?,
+        action,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    fn record(agent_id: Option<Uuid>, cost: f64, created_at: DateTime<Utc>) -> UsageRecord {
+        UsageRecord {
+            id: Uuid::new_v4(),
+            request_id: Uuid::new_v4().to_string(),
+            provider: "openai".to_string(),
+            model: "gpt-4o".to_string(),
+            agent_id,
+            conversation_id: None,
+            usage: TokenUsage {
+                prompt_tokens: 100,
+                completion_tokens: 50,
+                total_tokens: 150,
+                cached_tokens: None,
+            },
+            cost,
+            currency: "USD".to_string(),
+            success: true,
+            latency_ms: 420,
+            estimated: false,
+            created_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_usage_pricing_and_budgets() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::Executor::execute(&pool, include_str!("../migrations/20251117000008_add_llm_usage.sql"))
+            .await
+            .unwrap();
+        let store = SqliteUsageStore::new(pool);
+        let agent_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        store.record(&record(Some(agent_id), 0.5, now - Duration::days(40))).await.unwrap();
+        store.record(&record(Some(agent_id), 0.25, now)).await.unwrap();
+        store.record(&record(None, 1.0, now)).await.unwrap();
+
+        let recent = store.records(now - Duration::days(1), now, &UsageFilter::default()).await.unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].usage.total_tokens, 150);
+        assert_eq!(recent[0].latency_ms, 420);
+
+        let filter = UsageFilter { agent_id: Some(agent_id), ..UsageFilter::default() };
+        let all = store.records(now - Duration::days(60), now, &filter).await.unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(store.agent_cost(agent_id, now - Duration::days(1)).await.unwrap(), 0.25);
+
+        // Seeded prices can be overridden
+        let pricing = store.pricing().await.unwrap();
+        assert!(pricing.iter().any(|p| p.provider == "ollama" && p.model.is_empty()));
+        store.set_pricing(&PricingEntry {
+            provider: "openai".to_string(),
+            model: "gpt-4o".to_string(),
+            input_cost_per_1k: 0.002,
+            output_cost_per_1k: 0.008,
+            currency: "USD".to_string(),
+        }).await.unwrap();
+        let pricing = store.pricing().await.unwrap();
+        let gpt4o = pricing.iter().find(|p| p.provider == "openai" && p.model == "gpt-4o").unwrap();
+        assert_eq!(gpt4o.input_cost_per_1k, 0.002);
+
+        let budget = AgentBudget {
+            agent_id,
+            monthly_limit: 20.0,
+            currency: "USD".to_string(),
+            action: BudgetAction::Downgrade { model: "gpt-4o-mini".to_string() },
+        };
+        store.set_budget(&budget).await.unwrap();
+        assert_eq!(store.budget(agent_id).await.unwrap(), Some(budget));
+        assert!(store.remove_budget(agent_id).await.unwrap());
+        assert!(store.budget(agent_id).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,335 @@
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Pool, QueryBuilder, Row, Sqlite};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use uuid::Uuid;
+
+use crate::core::domain::traits::vector_store::{
+    cosine_similarity, ChunkSource, MemoryChunk, MemoryScope, ScoredChunk, VectorStore,
+};
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+
+/// SQLite vector store with brute-force cosine search
+///
+/// Embeddings are stored as little-endian `f32` blobs. A search scans the
+/// chunks of the requested scopes, which is fast enough for the per-scope
+/// limits of a desktop install.
+pub struct SqliteVectorStore {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteVectorStore {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl VectorStore for SqliteVectorStore {
+    async fn upsert(&self, chunks: &[MemoryChunk]) -> RepositoryResult<()> {
+        let db_error = |e: sqlx::Error| RepositoryError::DatabaseError(e.to_string());
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let sources: HashSet<Uuid> = chunks.iter().map(|c| c.source_id).collect();
+        for source_id in sources {
+            sqlx::query("DELETE FROM memory_chunks WHERE source_id = ?")
+                .bind(source_id.to_string())
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+
+        for chunk in chunks {
+            let (scope_type, scope_id) = scope_columns(chunk.scope);
+            sqlx::query(
+                "INSERT INTO memory_chunks
+                     (id, scope_type, scope_id, source_type, source_id, chunk_index,
+                      content, embedding, dimensions, created_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(chunk.id.to_string())
+            .bind(scope_type)
+            .bind(scope_id.to_string())
+            .bind(source_column(chunk.source))
+            .bind(chunk.source_id.to_string())
+            .bind(chunk.chunk_index as i64)
+            .bind(&chunk.content)
+            .bind(encode_embedding(&chunk.embedding))
+            .bind(chunk.embedding.len() as i64)
+            .bind(chunk.created_at.to_rfc3339_opts(SecondsFormat::Micros, true))
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        }
+
+        tx.commit().await.map_err(db_error)
+    }
+
+    async fn search(
+        &self,
+        scopes: &[MemoryScope],
+        query: &[f32],
+        limit: usize,
+        min_score: f32,
+    ) -> RepositoryResult<Vec<ScoredChunk>> {
+        if scopes.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "SELECT id, scope_type, scope_id, source_type, source_id, chunk_index, content, embedding, created_at
+             FROM memory_chunks WHERE dimensions = "
+        );
+        builder.push_bind(query.len() as i64).push(" AND (");
+        for (i, scope) in scopes.iter().enumerate() {
+            let (scope_type, scope_id) = scope_columns(*scope);
+            if i > 0 {
+                builder.push(" OR ");
+            }
+            builder
+                .push("(scope_type = ")
+                .push_bind(scope_type)
+                .push(" AND scope_id = ")
+                .push_bind(scope_id.to_string())
+                .push(")");
+        }
+        builder.push(")");
+
+        let rows = builder
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        // Keep the best `limit` matches in a min-heap
+        let mut best = BinaryHeap::with_capacity(limit + 1);
+        for row in &rows {
+            let embedding: Vec<u8> = row.try_get("embedding")
+                .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+            let score = cosine_similarity(&decode_embedding(&embedding), query);
+            if score < min_score {
+                continue;
+            }
+            best.push(Candidate { score, row });
+            if best.len() > limit {
+                best.pop();
+            }
+        }
+
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|candidate| Ok(ScoredChunk {
+                chunk: chunk_from_row(candidate.row)?,
+                score: candidate.score,
+            }))
+            .collect()
+    }
+
+    async fn remove_source(&self, source_id: Uuid) -> RepositoryResult<()> {
+        sqlx::query("DELETE FROM memory_chunks WHERE source_id = ?")
+            .bind(source_id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn prune(&self, scope: MemoryScope, max_entries: usize) -> RepositoryResult<usize> {
+        let (scope_type, scope_id) = scope_columns(scope);
+        let result = sqlx::query(
+            "DELETE FROM memory_chunks WHERE id IN (
+                 SELECT id FROM memory_chunks
+                 WHERE scope_type = ? AND scope_id = ?
+                 ORDER BY created_at DESC, chunk_index DESC
+                 LIMIT -1 OFFSET ?
+             )"
+        )
+        .bind(scope_type)
+        .bind(scope_id.to_string())
+        .bind(max_entries as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(result.rows_affected() as usize)
+    }
+}
+
+/// Search candidate, ordered so the heap pops the worst match first
+struct Candidate<'a> {
+    score: f32,
+    row: &'a SqliteRow,
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.score.total_cmp(&self.score)
+    }
+}
+
+fn scope_columns(scope: MemoryScope) -> (&'static str, Uuid) {
+    match scope {
+        MemoryScope::Conversation(id) => ("conversation", id),
+        MemoryScope::Twin(id) => ("twin", id),
+    }
+}
+
+fn source_column(source: ChunkSource) -> &'static str {
+    match source {
+        ChunkSource::Message => "message",
+        ChunkSource::TwinDocumentation => "twin_documentation",
+        ChunkSource::Attachment => "attachment",
+    }
+}
+
+fn encode_embedding(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode_embedding(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+fn chunk_from_row(row: &SqliteRow) -> RepositoryResult<MemoryChunk> {
+    let db_error = |e: sqlx::Error| RepositoryError::DatabaseError(e.to_string());
+    let invalid = |message: String| RepositoryError::SerializationError(message);
+    let uuid = |column: &str| -> RepositoryResult<Uuid> {
+        let value: String = row.try_get(column).map_err(db_error)?;
+        Uuid::parse_str(&value).map_err(|e| invalid(e.to_string()))
+    };
+
+    let scope_type: String = row.try_get("scope_type").map_err(db_error)?;
+    let scope_id = uuid("scope_id")?;
+    let scope = match scope_type.as_str() {
+        "conversation" => MemoryScope::Conversation(scope_id),
+        "twin" => MemoryScope::Twin(scope_id),
+        other => return Err(invalid(format!("unknown memory scope '{}'", other))),
+    };
+
+    let source_type: String = row.try_get("source_type").map_err(db_error)?;
+    let source = match source_type.as_str() {
+        "message" => ChunkSource::Message,
+        "twin_documentation" => ChunkSource::TwinDocumentation,
+        "attachment" => ChunkSource::Attachment,
+        other => return Err(invalid(format!("unknown chunk source '{}'", other))),
+    };
+
+    let chunk_index: i64 = row.try_get("chunk_index").map_err(db_error)?;
+    let embedding: Vec<u8> = row.try_get("embedding").map_err(db_error)?;
+    let created_at: String = row.try_get("created_at").map_err(db_error)?;
+
+    Ok(MemoryChunk {
+        id: uuid("id")?,
+        scope,
+        source,
+        source_id: uuid("source_id")?,
+        chunk_index: chunk_index as usize,
+        content: row.try_get("content").map_err(db_error)?,
+        embedding: decode_embedding(&embedding),
+        created_at: DateTime::parse_from_rfc3339(&created_at)
+            .map_err(|e| invalid(e.to_string()))?
+            .with_timezone(&Utc),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    const VOCABULARY: [&str; 10] = [
+        "replace", "compressor", "filter", "hours", "site",
+        "office", "opens", "replaced", "yesterday", "when",
+    ];
+
+    /// Bag-of-words embedding over a fixed vocabulary
+    fn embed(text: &str) -> Vec<f32> {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .map(str::to_lowercase)
+            .collect();
+        VOCABULARY
+            .iter()
+            .map(|term| words.iter().filter(|word| word == term).count() as f32)
+            .collect()
+    }
+
+    fn chunk(scope: MemoryScope, text: &str) -> MemoryChunk {
+        MemoryChunk {
+            id: Uuid::new_v4(),
+            scope,
+            source: ChunkSource::Message,
+            source_id: Uuid::new_v4(),
+            chunk_index: 0,
+            content: text.to_string(),
+            embedding: embed(text),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_upsert_and_prune() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::Executor::execute(&pool, include_str!("../migrations/20251117000007_add_memory_chunks.sql"))
+            .await
+            .unwrap();
+        let store = SqliteVectorStore::new(pool);
+        let twin = MemoryScope::Twin(Uuid::new_v4());
+        let conversation = MemoryScope::Conversation(Uuid::new_v4());
+
+        let manual = chunk(twin, "Replace the compressor filter every 500 hours");
+        store.upsert(&[
+            manual.clone(),
+            chunk(twin, "The site office opens at eight"),
+            chunk(conversation, "Compressor filter replaced yesterday"),
+        ]).await.unwrap();
+
+        let query = embed("when to replace the compressor filter");
+        let results = store.search(&[twin], &query, 5, 0.3).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk.id, manual.id);
+        assert_eq!(results[0].chunk.embedding, manual.embedding);
+        assert_eq!(results[0].chunk.scope, twin);
+
+        let results = store.search(&[twin, conversation], &query, 5, 0.3).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].score >= results[1].score);
+
+        // Re-indexing a source replaces its chunks
+        let revised = MemoryChunk {
+            id: Uuid::new_v4(),
+            content: "Replace the compressor filter every 400 hours".to_string(),
+            ..manual.clone()
+        };
+        store.upsert(&[revised.clone()]).await.unwrap();
+        let results = store.search(&[twin], &query, 5, 0.3).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chunk.id, revised.id);
+
+        assert_eq!(store.prune(twin, 1).await.unwrap(), 1);
+        assert_eq!(store.prune(conversation, 1).await.unwrap(), 0);
+    }
+}
@@ -113,4 +113,28 @@ mod tests {
         let manager = SqliteManager::new(config).await.unwrap();
         assert!(manager.check_health().await.unwrap());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_fresh_database_migrates() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("fresh.db");
+
+        let manager = SqliteManager::new(SqliteConfig {
+            database_path: db_path.to_str().unwrap().to_string(),
+            ..Default::default()
+        }).await.unwrap();
+        manager.run_migrations().await.unwrap();
+
+        // Conversations reference their agent once migration 0001 applied
+        let references: Vec<(String,)> = sqlx::query_as(
+            "SELECT \"table\" FROM pragma_foreign_key_list('conversations')"
+        )
+        .fetch_all(manager.pool())
+        .await
+        .unwrap();
+        assert!(references.iter().any(|(table,)| table == "agents"));
+
+        // Running again is a no-op
+        manager.run_migrations().await.unwrap();
+    }
+}
@@ -0,0 +1,980 @@
+//! Historical data import.
+//!
+//! Bulk-loads readings from CSV (and, with the `parquet-import` feature,
+//! Parquet) files into sensor series of a digital twin. Each mapped column
+//! becomes a `SensorData` collection (reusing an existing one with the same
+//! sensor id), timestamps are parsed with a configurable format and
+//! timezone, and readings are written in batches. Imports run as background
+//! jobs that can be cancelled, publish progress events and collect
+//! row-level errors instead of aborting on the first bad row. Rows can also
+//! be run through the mappings of one of the twin's data sources; mapping
+//! failures are reported as row errors.
+
+mod sources;
+
+pub use sources::{CsvSource, RawRow, RawValue, RowParser, RowSource};
+#[cfg(feature = "parquet-import")]
+pub use sources::ParquetSource;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::core::domain::{
+    models::{
+        ReadingQuality, SensorData, SensorDataId, SensorInfo, SensorReading,
+        SensorSpecifications, SensorStatus, SensorType, SensorValue, TwinId,
+    },
+    traits::repository::{Pagination, RepositoryError, SensorDataRepository},
+};
+use crate::infrastructure::transform::{self, MappingIngestor, SourceSelector};
+
+/// Import job identifier
+pub type ImportJobId = Uuid;
+
+/// Result type for import operations
+pub type ImportResult<T> = Result<T, ImportError>;
+
+/// Maximum number of row errors kept in a report
+const MAX_REPORTED_ERRORS: usize = 1000;
+
+/// Page size used when scanning existing sensor data
+const SCAN_PAGE_SIZE: usize = 1000;
+
+/// Collection method recorded on imported sensor data
+const COLLECTION_METHOD: &str = "historical_import";
+
+/// Default number of finished jobs whose reports are kept
+const DEFAULT_FINISHED_JOB_RETENTION: usize = 100;
+
+/// Import error types
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    /// Request is invalid
+    #[error("Invalid import request: {0}")]
+    InvalidRequest(String),
+
+    /// File could not be read
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// CSV error
+    #[error("CSV error: {0}")]
+    Csv(String),
+
+    /// Parquet error
+    #[error("Parquet error: {0}")]
+    Parquet(String),
+
+    /// Too many rows failed
+    #[error("Aborted after {0} row errors")]
+    TooManyErrors(usize),
+
+    /// Job does not exist
+    #[error("Unknown import job: {0}")]
+    UnknownJob(ImportJobId),
+
+    /// Repository error
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+}
+
+/// Input file format
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    Csv,
+    Parquet,
+}
+
+impl ImportFormat {
+    /// Infer the format from a file extension
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" | "txt" => Some(Self::Csv),
+            "parquet" | "pq" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+}
+
+/// How timestamps are encoded in the file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TimestampFormat {
+    /// RFC 3339, common naive layouts or unix seconds/milliseconds
+    Auto,
+
+    /// RFC 3339 with offset
+    Rfc3339,
+
+    /// Seconds since the unix epoch
+    UnixSeconds,
+
+    /// Milliseconds since the unix epoch
+    UnixMillis,
+
+    /// chrono `strftime` pattern
+    Pattern { pattern: String },
+}
+
+impl Default for TimestampFormat {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+/// Mapping of a file column onto a sensor series
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    /// Column name in the file
+    pub column: String,
+
+    /// Sensor identifier on the twin
+    pub sensor_id: String,
+
+    /// Display name for a newly created sensor
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Declared unit of the column
+    #[serde(default)]
+    pub unit: Option<String>,
+
+    /// Value = raw * scale + offset
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+
+    /// Value = raw * scale + offset
+    #[serde(default)]
+    pub offset: f64,
+
+    /// Append to this sensor data collection instead of resolving by sensor id
+    #[serde(default)]
+    pub sensor_data_id: Option<SensorDataId>,
+}
+
+/// Historical import request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRequest {
+    /// Twin receiving the data
+    pub twin_id: TwinId,
+
+    /// File to import
+    pub path: PathBuf,
+
+    /// File format, inferred from the extension when absent
+    #[serde(default)]
+    pub format: Option<ImportFormat>,
+
+    /// Column holding the timestamps
+    pub timestamp_column: String,
+
+    /// Timestamp encoding
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
+
+    /// IANA timezone for timestamps without an offset (default UTC)
+    #[serde(default)]
+    pub timezone: Option<String>,
+
+    /// Columns to import
+    pub columns: Vec<ColumnMapping>,
+
+    /// CSV delimiter
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+
+    /// Readings written per batch
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+
+    /// Skip readings whose timestamp already exists for the sensor
+    #[serde(default = "default_deduplicate")]
+    pub deduplicate: bool,
+
+    /// Abort the job once this many row errors occurred
+    #[serde(default)]
+    pub max_row_errors: Option<usize>,
+
+    /// Data source of the twin whose mappings are applied to each row,
+    /// with the row's values under their column names
+    #[serde(default)]
+    pub data_source_id: Option<Uuid>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_batch_size() -> usize {
+    1000
+}
+
+fn default_deduplicate() -> bool {
+    true
+}
+
+impl ImportRequest {
+    /// Check the request and resolve its format and timezone
+    pub fn validate(&self) -> ImportResult<(ImportFormat, Tz)> {
+        if self.columns.is_empty() {
+            return Err(ImportError::InvalidRequest("No columns mapped".to_string()));
+        }
+        if self.batch_size == 0 {
+            return Err(ImportError::InvalidRequest("batch_size must be positive".to_string()));
+        }
+
+        let mut sensor_ids = HashSet::new();
+        for column in &self.columns {
+            if !sensor_ids.insert(&column.sensor_id) {
+                return Err(ImportError::InvalidRequest(
+                    format!("Sensor mapped more than once: {}", column.sensor_id)
+                ));
+            }
+            if column.scale == 0.0 || !column.scale.is_finite() {
+                return Err(ImportError::InvalidRequest(
+                    format!("Invalid scale for column {}", column.column)
+                ));
+            }
+        }
+
+        let format = self.format
+            .or_else(|| ImportFormat::from_path(&self.path))
+            .ok_or_else(|| ImportError::InvalidRequest(
+                format!("Cannot infer format of {}", self.path.display())
+            ))?;
+
+        let timezone = match &self.timezone {
+            Some(name) => name.parse::<Tz>()
+                .map_err(|e| ImportError::InvalidRequest(format!("Unknown timezone {}: {}", name, e)))?,
+            None => Tz::UTC,
+        };
+
+        Ok((format, timezone))
+    }
+
+    /// Open the row source for the request's file
+    fn open_source(&self, format: ImportFormat) -> ImportResult<Box<dyn RowSource>> {
+        match format {
+            ImportFormat::Csv => Ok(Box::new(CsvSource::open(
+                &self.path,
+                self.delimiter,
+                &self.timestamp_column,
+                &self.columns,
+            )?)),
+            #[cfg(feature = "parquet-import")]
+            ImportFormat::Parquet => Ok(Box::new(ParquetSource::open(
+                &self.path,
+                &self.timestamp_column,
+                &self.columns,
+            )?)),
+            #[cfg(not(feature = "parquet-import"))]
+            ImportFormat::Parquet => Err(ImportError::InvalidRequest(
+                "Parquet support requires the parquet-import feature".to_string()
+            )),
+        }
+    }
+}
+
+/// Error affecting a single row or cell
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowError {
+    /// Line number for CSV, 1-based row index for Parquet
+    pub row: u64,
+
+    /// Column, when the error is limited to one cell
+    pub column: Option<String>,
+
+    /// Error description
+    pub message: String,
+}
+
+/// Import job status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+/// Progress event published while a job runs and when it finishes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportProgress {
+    pub job_id: ImportJobId,
+    pub status: ImportStatus,
+    pub progress: f32,
+    pub rows_read: u64,
+    pub readings_imported: u64,
+    pub duplicates_skipped: u64,
+    pub error_count: u64,
+}
+
+/// Outcome of an import job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub job_id: ImportJobId,
+    pub twin_id: TwinId,
+    pub path: PathBuf,
+    pub status: ImportStatus,
+
+    /// Fraction of the input consumed
+    pub progress: f32,
+
+    pub rows_read: u64,
+    pub readings_imported: u64,
+    pub duplicates_skipped: u64,
+
+    /// Total number of row errors
+    pub error_count: u64,
+
+    /// First row errors, capped to keep reports small
+    pub errors: Vec<RowError>,
+
+    /// Reason a failed job stopped
+    pub failure: Option<String>,
+
+    /// Sensor data collection per sensor id
+    pub sensors: HashMap<String, SensorDataId>,
+
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl ImportReport {
+    fn new(job_id: ImportJobId, request: &ImportRequest) -> Self {
+        Self {
+            job_id,
+            twin_id: request.twin_id,
+            path: request.path.clone(),
+            status: ImportStatus::Running,
+            progress: 0.0,
+            rows_read: 0,
+            readings_imported: 0,
+            duplicates_skipped: 0,
+            error_count: 0,
+            errors: Vec::new(),
+            failure: None,
+            sensors: HashMap::new(),
+            started_at: Utc::now(),
+            finished_at: None,
+        }
+    }
+
+    fn record_errors(&mut self, errors: Vec<RowError>) {
+        self.error_count += errors.len() as u64;
+        let room = MAX_REPORTED_ERRORS.saturating_sub(self.errors.len());
+        self.errors.extend(errors.into_iter().take(room));
+    }
+
+    /// Progress event for the current state
+    pub fn progress_event(&self) -> ImportProgress {
+        ImportProgress {
+            job_id: self.job_id,
+            status: self.status,
+            progress: self.progress,
+            rows_read: self.rows_read,
+            readings_imported: self.readings_imported,
+            duplicates_skipped: self.duplicates_skipped,
+            error_count: self.error_count,
+        }
+    }
+}
+
+/// Rows parsed by the reader thread
+struct ParsedBatch {
+    /// Row number, timestamp and values of each parsed row
+    rows: Vec<(u64, DateTime<Utc>, Vec<(usize, SensorValue)>)>,
+    errors: Vec<RowError>,
+    rows_read: u64,
+    progress: f32,
+}
+
+/// Book-keeping for a running job
+struct ImportJobEntry {
+    cancelled: Arc<AtomicBool>,
+    report: Arc<Mutex<ImportReport>>,
+}
+
+/// Runs and tracks historical import jobs
+pub struct ImportJobManager {
+    sensor_repository: Arc<dyn SensorDataRepository>,
+    jobs: RwLock<HashMap<ImportJobId, ImportJobEntry>>,
+    events: broadcast::Sender<ImportProgress>,
+    finished_job_retention: usize,
+    mapping: Option<Arc<MappingIngestor>>,
+}
+
+impl ImportJobManager {
+    /// Create a new import job manager
+    pub fn new(sensor_repository: Arc<dyn SensorDataRepository>) -> Self {
+        let (events, _) = broadcast::channel(256);
+        Self {
+            sensor_repository,
+            jobs: RwLock::new(HashMap::new()),
+            events,
+            finished_job_retention: DEFAULT_FINISHED_JOB_RETENTION,
+            mapping: None,
+        }
+    }
+
+    /// Apply data source mappings for requests naming a `data_source_id`
+    pub fn with_mapping(mut self, ingestor: Arc<MappingIngestor>) -> Self {
+        self.mapping = Some(ingestor);
+        self
+    }
+
+    /// Set how many finished jobs are kept for reporting
+    pub fn with_finished_job_retention(mut self, retention: usize) -> Self {
+        self.finished_job_retention = retention;
+        self
+    }
+
+    /// Subscribe to progress events of all jobs
+    pub fn subscribe(&self) -> broadcast::Receiver<ImportProgress> {
+        self.events.subscribe()
+    }
+
+    /// Validate the request, open the file and start the import in the background
+    pub fn start(&self, request: ImportRequest) -> ImportResult<ImportJobId> {
+        let (format, timezone) = request.validate()?;
+        if request.data_source_id.is_some() && self.mapping.is_none() {
+            return Err(ImportError::InvalidRequest("Data source mappings are not available".to_string()));
+        }
+        let source = request.open_source(format)?;
+
+        let job_id = Uuid::new_v4();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let report = Arc::new(Mutex::new(ImportReport::new(job_id, &request)));
+
+        {
+            let mut jobs = self.jobs.write().unwrap();
+            self.prune_finished(&mut jobs);
+            jobs.insert(job_id, ImportJobEntry {
+                cancelled: cancelled.clone(),
+                report: report.clone(),
+            });
+        }
+
+        let job = ImportJob {
+            request,
+            timezone,
+            repository: self.sensor_repository.clone(),
+            cancelled,
+            report,
+            events: self.events.clone(),
+            mapping: self.mapping.clone(),
+        };
+
+        info!("Starting historical import {} from {}", job_id, job.request.path.display());
+        tokio::spawn(job.run(source));
+
+        Ok(job_id)
+    }
+
+    /// Request cancellation; readings already written are kept
+    pub fn cancel(&self, job_id: ImportJobId) -> ImportResult<()> {
+        let jobs = self.jobs.read().unwrap();
+        let entry = jobs.get(&job_id).ok_or(ImportError::UnknownJob(job_id))?;
+        entry.cancelled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Current report of a job
+    pub fn report(&self, job_id: ImportJobId) -> ImportResult<ImportReport> {
+        let jobs = self.jobs.read().unwrap();
+        let entry = jobs.get(&job_id).ok_or(ImportError::UnknownJob(job_id))?;
+        let report = entry.report.lock().unwrap().clone();
+        Ok(report)
+    }
+
+    /// Reports of all known jobs
+    pub fn list(&self) -> Vec<ImportReport> {
+        self.jobs.read().unwrap()
+            .values()
+            .map(|entry| entry.report.lock().unwrap().clone())
+            .collect()
+    }
+
+    /// Forget the oldest finished jobs beyond the retention limit
+    fn prune_finished(&self, jobs: &mut HashMap<ImportJobId, ImportJobEntry>) {
+        let mut finished: Vec<(DateTime<Utc>, ImportJobId)> = jobs.iter()
+            .filter_map(|(id, entry)| {
+                entry.report.lock().unwrap().finished_at.map(|at| (at, *id))
+            })
+            .collect();
+
+        if finished.len() <= self.finished_job_retention {
+            return;
+        }
+
+        finished.sort();
+        let excess = finished.len() - self.finished_job_retention;
+        for (_, id) in finished.into_iter().take(excess) {
+            jobs.remove(&id);
+        }
+    }
+}
+
+/// A single import run
+struct ImportJob {
+    request: ImportRequest,
+    timezone: Tz,
+    repository: Arc<dyn SensorDataRepository>,
+    cancelled: Arc<AtomicBool>,
+    report: Arc<Mutex<ImportReport>>,
+    events: broadcast::Sender<ImportProgress>,
+    mapping: Option<Arc<MappingIngestor>>,
+}
+
+impl ImportJob {
+    async fn run(self, source: Box<dyn RowSource>) {
+        let result = self.execute(source).await;
+
+        let event = {
+            let mut report = self.report.lock().unwrap();
+            report.finished_at = Some(Utc::now());
+            report.status = match result {
+                Ok(()) if self.cancelled.load(Ordering::SeqCst) => ImportStatus::Cancelled,
+                Ok(()) => {
+                    report.progress = 1.0;
+                    ImportStatus::Completed
+                },
+                Err(e) => {
+                    warn!("Historical import {} failed: {}", report.job_id, e);
+                    report.failure = Some(e.to_string());
+                    ImportStatus::Failed
+                },
+            };
+            info!(
+                "Historical import {} {:?}: {} readings, {} duplicates, {} errors",
+                report.job_id, report.status, report.readings_imported,
+                report.duplicates_skipped, report.error_count,
+            );
+            report.progress_event()
+        };
+
+        // Nobody listening is not an error
+        let _ = self.events.send(event);
+    }
+
+    async fn execute(&self, source: Box<dyn RowSource>) -> ImportResult<()> {
+        let (sensor_ids, existing) = self.resolve_sensors().await?;
+        let sensor_count = sensor_ids.len();
+
+        // Parse on a blocking thread so file I/O does not stall the runtime
+        let (tx, mut rx) = mpsc::channel::<ParsedBatch>(4);
+        let reader = tokio::task::spawn_blocking({
+            let parser = RowParser::new(
+                self.request.timestamp_format.clone(),
+                self.timezone,
+                &self.request.columns,
+            );
+            let timestamp_column = self.request.timestamp_column.clone();
+            let batch_size = self.request.batch_size;
+            let cancelled = self.cancelled.clone();
+            move || read_batches(source, parser, timestamp_column, batch_size, cancelled, tx)
+        });
+
+        let mut seen: Vec<HashSet<i64>> = vec![HashSet::new(); sensor_count];
+        let mut pending: Vec<Vec<SensorReading>> = vec![Vec::new(); sensor_count];
+
+        while let Some(batch) = rx.recv().await {
+            if self.cancelled.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let mapping_errors = self.apply_mappings(&batch.rows).await?;
+
+            let mut duplicates = 0;
+            for (_, timestamp, values) in batch.rows {
+                for (index, value) in values {
+                    if self.request.deduplicate && !seen[index].insert(timestamp.timestamp_micros()) {
+                        duplicates += 1;
+                        continue;
+                    }
+                    pending[index].push(reading(value, timestamp));
+                }
+            }
+
+            {
+                let mut report = self.report.lock().unwrap();
+                report.rows_read += batch.rows_read;
+                report.progress = batch.progress;
+                report.duplicates_skipped += duplicates;
+                report.record_errors(batch.errors);
+                report.record_errors(mapping_errors);
+
+                if let Some(max) = self.request.max_row_errors {
+                    if report.error_count as usize > max {
+                        return Err(ImportError::TooManyErrors(report.error_count as usize));
+                    }
+                }
+            }
+
+            for index in 0..sensor_count {
+                if pending[index].len() >= self.request.batch_size {
+                    let readings = std::mem::take(&mut pending[index]);
+                    self.flush(sensor_ids[index], existing[index], readings).await?;
+                }
+            }
+
+            let event = self.report.lock().unwrap().progress_event();
+            let _ = self.events.send(event);
+        }
+
+        drop(rx);
+        let _ = reader.await;
+
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        for (index, readings) in pending.into_iter().enumerate() {
+            if !readings.is_empty() {
+                self.flush(sensor_ids[index], existing[index], readings).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run parsed rows through the selected data source's mappings,
+    /// returning the failures as row errors
+    async fn apply_mappings(
+        &self,
+        rows: &[(u64, DateTime<Utc>, Vec<(usize, SensorValue)>)],
+    ) -> ImportResult<Vec<RowError>> {
+        let (Some(mapping), Some(source_id)) = (&self.mapping, self.request.data_source_id) else {
+            return Ok(Vec::new());
+        };
+
+        let records: Vec<_> = rows.iter()
+            .map(|(_, timestamp, values)| {
+                let record: serde_json::Map<String, serde_json::Value> = values.iter()
+                    .map(|(index, value)| (self.request.columns[*index].column.clone(), transform::record_value(value)))
+                    .collect();
+                (*timestamp, serde_json::Value::Object(record))
+            })
+            .collect();
+
+        let outcome = mapping.ingest_batch(self.request.twin_id, SourceSelector::Id(source_id), &records).await?;
+        Ok(outcome.failures
+            .into_iter()
+            .map(|(index, failure)| RowError {
+                row: rows[index].0,
+                column: None,
+                message: failure.message,
+            })
+            .collect())
+    }
+
+    /// Find or create the sensor data collection of every mapped column.
+    ///
+    /// Returns the collection ids and whether each one existed before the import.
+    async fn resolve_sensors(&self) -> ImportResult<(Vec<SensorDataId>, Vec<bool>)> {
+        let mut by_sensor_id = HashMap::new();
+        let mut offset = 0;
+        loop {
+            let page = self.repository.get_by_twin_id(
+                self.request.twin_id,
+                Pagination { offset, limit: SCAN_PAGE_SIZE },
+            ).await?;
+            let count = page.items.len();
+            for data in page.items {
+                by_sensor_id.insert(data.sensor.sensor_id.clone(), data.id);
+            }
+            offset += count;
+            if count < SCAN_PAGE_SIZE || offset >= page.total {
+                break;
+            }
+        }
+
+        let mut ids = Vec::new();
+        let mut existing = Vec::new();
+
+        for column in &self.request.columns {
+            let (id, found) = match column.sensor_data_id.or_else(|| by_sensor_id.get(&column.sensor_id).copied()) {
+                Some(id) => (self.repository.get_by_id(id).await?.id, true),
+                None => {
+                    let mut data = SensorData::new(self.request.twin_id, sensor_info(column));
+                    data.metadata.source_id = self.request.path.display().to_string();
+                    data.metadata.collection_method = COLLECTION_METHOD.to_string();
+                    (self.repository.create(data).await?.id, false)
+                },
+            };
+
+            self.report.lock().unwrap().sensors.insert(column.sensor_id.clone(), id);
+            ids.push(id);
+            existing.push(found);
+        }
+
+        Ok((ids, existing))
+    }
+
+    /// Write a batch, dropping readings already stored for existing sensors
+    async fn flush(
+        &self,
+        sensor_data_id: SensorDataId,
+        existing: bool,
+        mut readings: Vec<SensorReading>,
+    ) -> ImportResult<()> {
+        if existing && self.request.deduplicate {
+            let stored = self.stored_timestamps(sensor_data_id, &readings).await?;
+            let before = readings.len();
+            readings.retain(|r| !stored.contains(&r.timestamp.timestamp_micros()));
+            self.report.lock().unwrap().duplicates_skipped += (before - readings.len()) as u64;
+        }
+
+        if readings.is_empty() {
+            return Ok(());
+        }
+
+        let count = readings.len() as u64;
+        self.repository.add_readings(sensor_data_id, readings).await?;
+        self.report.lock().unwrap().readings_imported += count;
+        Ok(())
+    }
+
+    /// Timestamps already stored within the time span of a batch
+    async fn stored_timestamps(
+        &self,
+        sensor_data_id: SensorDataId,
+        readings: &[SensorReading],
+    ) -> ImportResult<HashSet<i64>> {
+        let start = readings.iter().map(|r| r.timestamp).min().unwrap();
+        let end = readings.iter().map(|r| r.timestamp).max().unwrap();
+
+        let mut stored = HashSet::new();
+        let mut offset = 0;
+        loop {
+            let page = self.repository.get_readings_in_range(
+                sensor_data_id,
+                start,
+                end,
+                Pagination { offset, limit: SCAN_PAGE_SIZE },
+            ).await?;
+            let count = page.items.len();
+            stored.extend(page.items.iter().map(|r| r.timestamp.timestamp_micros()));
+            offset += count;
+            if count < SCAN_PAGE_SIZE || offset >= page.total {
+                break;
+            }
+        }
+
+        Ok(stored)
+    }
+}
+
+/// Read and parse rows in batches until the input ends or the job is cancelled
+fn read_batches(
+    mut source: Box<dyn RowSource>,
+    parser: RowParser,
+    timestamp_column: String,
+    batch_size: usize,
+    cancelled: Arc<AtomicBool>,
+    tx: mpsc::Sender<ParsedBatch>,
+) {
+    loop {
+        let mut batch = ParsedBatch {
+            rows: Vec::with_capacity(batch_size),
+            errors: Vec::new(),
+            rows_read: 0,
+            progress: 0.0,
+        };
+
+        let mut finished = false;
+        while batch.rows_read < batch_size as u64 {
+            if cancelled.load(Ordering::SeqCst) {
+                return;
+            }
+
+            match source.next_row() {
+                None => {
+                    finished = true;
+                    break;
+                },
+                Some(Err(error)) => {
+                    batch.rows_read += 1;
+                    batch.errors.push(error);
+                },
+                Some(Ok(row)) => {
+                    batch.rows_read += 1;
+                    match parser.parse(&row, &timestamp_column) {
+                        Ok((timestamp, values, errors)) => {
+                            batch.errors.extend(errors);
+                            batch.rows.push((row.row, timestamp, values));
+                        },
+                        Err(error) => batch.errors.push(error),
+                    }
+                },
+            }
+        }
+
+        batch.progress = source.progress();
+        if tx.blocking_send(batch).is_err() || finished {
+            return;
+        }
+    }
+}
+
+fn reading(value: SensorValue, timestamp: DateTime<Utc>) -> SensorReading {
+    SensorReading {
+        id: Uuid::new_v4(),
+        value,
+        timestamp,
+        quality: ReadingQuality::default(),
+        context: None,
+        alerts: Vec::new(),
+    }
+}
+
+fn sensor_info(column: &ColumnMapping) -> SensorInfo {
+    SensorInfo {
+        sensor_id: column.sensor_id.clone(),
+        name: column.name.clone().unwrap_or_else(|| column.column.clone()),
+        sensor_type: SensorType::Custom {
+            category: COLLECTION_METHOD.to_string(),
+            measurement_unit: column.unit.clone().unwrap_or_default(),
+        },
+        location: None,
+        specifications: SensorSpecifications {
+            range: None,
+            accuracy: None,
+            resolution: None,
+            sampling_rate: None,
+            response_time_ms: None,
+            operating_temp_range: None,
+            power_consumption: None,
+            protocol: None,
+            manufacturer: None,
+        },
+        status: SensorStatus::Offline,
+        calibration: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::traits::mock_repositories::InMemorySensorRepository;
+    use std::io::Write;
+
+    fn request(path: PathBuf, twin_id: TwinId) -> ImportRequest {
+        serde_json::from_value(serde_json::json!({
+            "twin_id": twin_id,
+            "path": path,
+            "timestamp_column": "time",
+            "timezone": "Europe/Berlin",
+            "columns": [
+                { "column": "temp", "sensor_id": "temp", "unit": "°C" },
+                { "column": "flow_ml", "sensor_id": "flow", "unit": "l/min", "scale": 0.001 },
+            ],
+            "batch_size": 2,
+        })).unwrap()
+    }
+
+    async fn wait(manager: &ImportJobManager, job_id: ImportJobId) -> ImportReport {
+        for _ in 0..200 {
+            let report = manager.report(job_id).unwrap();
+            if report.status != ImportStatus::Running {
+                return report;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("import did not finish");
+    }
+
+    #[tokio::test]
+    async fn test_csv_import_with_errors_and_dedup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.csv");
+        let mut file = std::fs::File::create(&path).unwrap();
+        writeln!(file, "time,temp,flow_ml").unwrap();
+        writeln!(file, "2024-01-15 12:00:00,20.5,1500").unwrap();
+        writeln!(file, "2024-01-15 12:01:00,bad,1600").unwrap();
+        writeln!(file, "not a time,21.0,1700").unwrap();
+        writeln!(file, "2024-01-15 12:02:00,21.5,").unwrap();
+        writeln!(file, "2024-01-15 12:02:00,21.5,1800").unwrap();
+        drop(file);
+
+        let repository = Arc::new(InMemorySensorRepository::default());
+        let manager = ImportJobManager::new(repository.clone());
+        let twin_id = Uuid::new_v4();
+
+        let job_id = manager.start(request(path.clone(), twin_id)).unwrap();
+        let report = wait(&manager, job_id).await;
+
+        assert_eq!(report.status, ImportStatus::Completed);
+        assert_eq!(report.rows_read, 5);
+        assert_eq!(report.error_count, 2);
+        assert_eq!(report.errors[0].row, 3);
+        assert_eq!(report.errors[0].column.as_deref(), Some("temp"));
+        assert_eq!(report.errors[1].column.as_deref(), Some("time"));
+        // temp at 12:02 appears twice
+        assert_eq!(report.duplicates_skipped, 1);
+        assert_eq!(report.readings_imported, 5);
+
+        let temp = repository.get_by_id(report.sensors["temp"]).await.unwrap();
+        assert_eq!(temp.readings.len(), 2);
+        assert_eq!(temp.readings[0].timestamp.to_rfc3339(), "2024-01-15T11:00:00+00:00");
+
+        let flow = repository.get_by_id(report.sensors["flow"]).await.unwrap();
+        assert_eq!(flow.readings[0].value, SensorValue::Numeric(1.5));
+
+        // Importing the same file again reuses the sensors and skips everything
+        let job_id = manager.start(request(path, twin_id)).unwrap();
+        let again = wait(&manager, job_id).await;
+        assert_eq!(again.sensors, report.sensors);
+        assert_eq!(again.readings_imported, 0);
+        assert_eq!(again.duplicates_skipped, 6);
+    }
+
+    #[tokio::test]
+    async fn test_finished_jobs_are_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.csv");
+        std::fs::write(&path, "time,temp,flow_ml\n2024-01-15 12:00:00,20.5,1500\n").unwrap();
+
+        let repository = Arc::new(InMemorySensorRepository::default());
+        let manager = ImportJobManager::new(repository).with_finished_job_retention(1);
+        let twin_id = Uuid::new_v4();
+
+        let first = manager.start(request(path.clone(), twin_id)).unwrap();
+        wait(&manager, first).await;
+        let second = manager.start(request(path.clone(), twin_id)).unwrap();
+        wait(&manager, second).await;
+        let third = manager.start(request(path, twin_id)).unwrap();
+
+        // The oldest finished job made room for the new one
+        assert!(matches!(manager.report(first), Err(ImportError::UnknownJob(_))));
+        assert!(manager.report(second).is_ok());
+        assert!(manager.report(third).is_ok());
+        wait(&manager, third).await;
+    }
+
+    #[test]
+    fn test_request_validation() {
+        let mut req = request(PathBuf::from("data.xlsx"), Uuid::new_v4());
+        assert!(matches!(req.validate(), Err(ImportError::InvalidRequest(_))));
+
+        req.path = PathBuf::from("data.csv");
+        assert!(req.validate().is_ok());
+
+        req.timezone = Some("Mars/Olympus".to_string());
+        assert!(req.validate().is_err());
+    }
+}
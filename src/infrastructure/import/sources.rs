@@ -0,0 +1,474 @@
+//! Row sources and cell parsing for historical imports.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fs::File;
+use std::path::Path;
+
+use crate::core::domain::models::SensorValue;
+use super::{ColumnMapping, ImportError, ImportResult, RowError, TimestampFormat};
+
+/// Naive layouts tried by [`TimestampFormat::Auto`]
+const AUTO_PATTERNS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%d.%m.%Y %H:%M:%S",
+];
+
+/// Unix timestamps above this are taken as milliseconds by `Auto`
+const AUTO_MILLIS_THRESHOLD: f64 = 1e11;
+
+/// Cell value as read from the file
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawValue {
+    Null,
+    Text(String),
+    Number(f64),
+    Bool(bool),
+    Timestamp(DateTime<Utc>),
+}
+
+/// Row read from the file, restricted to the mapped columns
+#[derive(Debug, Clone)]
+pub struct RawRow {
+    /// Line number for CSV, 1-based row index for Parquet
+    pub row: u64,
+
+    /// Timestamp cell
+    pub timestamp: RawValue,
+
+    /// Cells in the order of the column mappings
+    pub values: Vec<RawValue>,
+}
+
+/// Sequential reader over the rows of an import file
+pub trait RowSource: Send {
+    /// Next row, a row-level error, or `None` at end of input
+    fn next_row(&mut self) -> Option<Result<RawRow, RowError>>;
+
+    /// Fraction of the input consumed so far
+    fn progress(&self) -> f32;
+}
+
+/// CSV row source
+pub struct CsvSource {
+    reader: csv::Reader<File>,
+    timestamp_index: usize,
+    value_indices: Vec<usize>,
+    total_bytes: u64,
+    finished: bool,
+}
+
+impl CsvSource {
+    /// Open a CSV file and resolve the mapped columns from its header
+    pub fn open(
+        path: &Path,
+        delimiter: char,
+        timestamp_column: &str,
+        columns: &[ColumnMapping],
+    ) -> ImportResult<Self> {
+        if !delimiter.is_ascii() {
+            return Err(ImportError::InvalidRequest(
+                format!("Delimiter must be an ASCII character: {:?}", delimiter)
+            ));
+        }
+
+        let total_bytes = std::fs::metadata(path)?.len();
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter as u8)
+            .has_headers(true)
+            .flexible(true)
+            .trim(csv::Trim::Headers)
+            .from_path(path)
+            .map_err(|e| ImportError::Csv(e.to_string()))?;
+
+        let headers = reader.headers()
+            .map_err(|e| ImportError::Csv(e.to_string()))?
+            .clone();
+        let index_of = |name: &str| headers.iter()
+            .position(|h| h == name)
+            .ok_or_else(|| ImportError::InvalidRequest(format!("Column not found: {}", name)));
+
+        let timestamp_index = index_of(timestamp_column)?;
+        let value_indices = columns.iter()
+            .map(|c| index_of(&c.column))
+            .collect::<ImportResult<Vec<_>>>()?;
+
+        Ok(Self {
+            reader,
+            timestamp_index,
+            value_indices,
+            total_bytes,
+            finished: false,
+        })
+    }
+}
+
+impl RowSource for CsvSource {
+    fn next_row(&mut self) -> Option<Result<RawRow, RowError>> {
+        if self.finished {
+            return None;
+        }
+
+        let mut record = csv::StringRecord::new();
+        match self.reader.read_record(&mut record) {
+            Ok(false) => {
+                self.finished = true;
+                None
+            },
+            Ok(true) => {
+                let row = record.position().map_or(0, |p| p.line());
+                let cell = |index: usize| record.get(index)
+                    .map(|s| s.trim())
+                    .filter(|s| !s.is_empty())
+                    .map_or(RawValue::Null, |s| RawValue::Text(s.to_string()));
+
+                Some(Ok(RawRow {
+                    row,
+                    timestamp: cell(self.timestamp_index),
+                    values: self.value_indices.iter().map(|i| cell(*i)).collect(),
+                }))
+            },
+            Err(e) => {
+                let row = e.position().map_or(0, |p| p.line());
+                // I/O errors cannot be recovered from; malformed records can
+                if matches!(e.kind(), csv::ErrorKind::Io(_)) {
+                    self.finished = true;
+                }
+                Some(Err(RowError {
+                    row,
+                    column: None,
+                    message: e.to_string(),
+                }))
+            },
+        }
+    }
+
+    fn progress(&self) -> f32 {
+        if self.total_bytes == 0 {
+            return 1.0;
+        }
+        (self.reader.position().byte() as f64 / self.total_bytes as f64).min(1.0) as f32
+    }
+}
+
+/// Parquet row source
+#[cfg(feature = "parquet-import")]
+pub struct ParquetSource {
+    rows: parquet::record::reader::RowIter<'static>,
+    timestamp_column: String,
+    columns: Vec<String>,
+    total_rows: u64,
+    read: u64,
+}
+
+#[cfg(feature = "parquet-import")]
+impl ParquetSource {
+    /// Open a Parquet file and check the mapped columns against its schema
+    pub fn open(
+        path: &Path,
+        timestamp_column: &str,
+        columns: &[ColumnMapping],
+    ) -> ImportResult<Self> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let reader = SerializedFileReader::new(File::open(path)?)
+            .map_err(|e| ImportError::Parquet(e.to_string()))?;
+
+        let metadata = reader.metadata().file_metadata();
+        let fields = metadata.schema().get_fields();
+        let has_field = |name: &str| fields.iter().any(|f| f.name() == name);
+
+        for name in std::iter::once(timestamp_column).chain(columns.iter().map(|c| c.column.as_str())) {
+            if !has_field(name) {
+                return Err(ImportError::InvalidRequest(format!("Column not found: {}", name)));
+            }
+        }
+
+        let total_rows = metadata.num_rows().max(0) as u64;
+
+        Ok(Self {
+            rows: reader.into_iter(),
+            timestamp_column: timestamp_column.to_string(),
+            columns: columns.iter().map(|c| c.column.clone()).collect(),
+            total_rows,
+            read: 0,
+        })
+    }
+}
+
+#[cfg(feature = "parquet-import")]
+impl RowSource for ParquetSource {
+    fn next_row(&mut self) -> Option<Result<RawRow, RowError>> {
+        use parquet::record::Field;
+
+        let next = self.rows.next()?;
+        self.read += 1;
+
+        let row = match next {
+            Ok(row) => row,
+            Err(e) => return Some(Err(RowError {
+                row: self.read,
+                column: None,
+                message: e.to_string(),
+            })),
+        };
+
+        let mut timestamp = RawValue::Null;
+        let mut values = vec![RawValue::Null; self.columns.len()];
+
+        for (name, field) in row.get_column_iter() {
+            let value = match field {
+                Field::Null => RawValue::Null,
+                Field::Bool(b) => RawValue::Bool(*b),
+                Field::Byte(v) => RawValue::Number(*v as f64),
+                Field::Short(v) => RawValue::Number(*v as f64),
+                Field::Int(v) => RawValue::Number(*v as f64),
+                Field::Long(v) => RawValue::Number(*v as f64),
+                Field::UByte(v) => RawValue::Number(*v as f64),
+                Field::UShort(v) => RawValue::Number(*v as f64),
+                Field::UInt(v) => RawValue::Number(*v as f64),
+                Field::ULong(v) => RawValue::Number(*v as f64),
+                Field::Float(v) => RawValue::Number(*v as f64),
+                Field::Double(v) => RawValue::Number(*v),
+                Field::Str(s) => RawValue::Text(s.clone()),
+                Field::TimestampMillis(ms) => Utc.timestamp_millis_opt(*ms).single()
+                    .map_or(RawValue::Null, RawValue::Timestamp),
+                Field::TimestampMicros(us) => Utc.timestamp_micros(*us).single()
+                    .map_or(RawValue::Null, RawValue::Timestamp),
+                Field::Date(days) => NaiveDate::from_num_days_from_ce_opt(*days + 719_163)
+                    .and_then(|d| d.and_hms_opt(0, 0, 0))
+                    .map_or(RawValue::Null, |d| RawValue::Timestamp(Utc.from_utc_datetime(&d))),
+                other => RawValue::Text(other.to_string()),
+            };
+
+            if *name == self.timestamp_column {
+                timestamp = value.clone();
+            }
+            for (i, column) in self.columns.iter().enumerate() {
+                if column == name {
+                    values[i] = value.clone();
+                }
+            }
+        }
+
+        Some(Ok(RawRow {
+            row: self.read,
+            timestamp,
+            values,
+        }))
+    }
+
+    fn progress(&self) -> f32 {
+        if self.total_rows == 0 {
+            return 1.0;
+        }
+        (self.read as f64 / self.total_rows as f64).min(1.0) as f32
+    }
+}
+
+/// Converts raw cells into timestamps and sensor values
+pub struct RowParser {
+    format: TimestampFormat,
+    timezone: Tz,
+    scales: Vec<(f64, f64)>,
+    columns: Vec<String>,
+}
+
+impl RowParser {
+    /// Create a parser for the given timestamp handling and column mappings
+    pub fn new(format: TimestampFormat, timezone: Tz, columns: &[ColumnMapping]) -> Self {
+        Self {
+            format,
+            timezone,
+            scales: columns.iter().map(|c| (c.scale, c.offset)).collect(),
+            columns: columns.iter().map(|c| c.column.clone()).collect(),
+        }
+    }
+
+    /// Parse a row into its timestamp and the values present per mapping.
+    ///
+    /// A bad timestamp rejects the whole row; a bad value only rejects that
+    /// cell and is reported alongside the remaining values.
+    pub fn parse(
+        &self,
+        row: &RawRow,
+        timestamp_column: &str,
+    ) -> Result<(DateTime<Utc>, Vec<(usize, SensorValue)>, Vec<RowError>), RowError> {
+        let timestamp = self.parse_timestamp(&row.timestamp).map_err(|message| RowError {
+            row: row.row,
+            column: Some(timestamp_column.to_string()),
+            message,
+        })?;
+
+        let mut values = Vec::new();
+        let mut errors = Vec::new();
+        for (index, raw) in row.values.iter().enumerate() {
+            match self.parse_value(index, raw) {
+                Ok(Some(value)) => values.push((index, value)),
+                Ok(None) => {},
+                Err(message) => errors.push(RowError {
+                    row: row.row,
+                    column: Some(self.columns[index].clone()),
+                    message,
+                }),
+            }
+        }
+
+        Ok((timestamp, values, errors))
+    }
+
+    /// Parse a timestamp cell according to the configured format
+    pub fn parse_timestamp(&self, raw: &RawValue) -> Result<DateTime<Utc>, String> {
+        let text = match raw {
+            RawValue::Timestamp(ts) => return Ok(*ts),
+            RawValue::Number(n) => return self.parse_unix(*n),
+            RawValue::Text(text) => text.as_str(),
+            RawValue::Null => return Err("Missing timestamp".to_string()),
+            RawValue::Bool(_) => return Err("Timestamp cannot be a boolean".to_string()),
+        };
+
+        match &self.format {
+            TimestampFormat::Rfc3339 => DateTime::parse_from_rfc3339(text)
+                .map(|ts| ts.with_timezone(&Utc))
+                .map_err(|e| format!("Invalid RFC 3339 timestamp '{}': {}", text, e)),
+            TimestampFormat::UnixSeconds | TimestampFormat::UnixMillis => text.parse::<f64>()
+                .map_err(|_| format!("Invalid unix timestamp '{}'", text))
+                .and_then(|n| self.parse_unix(n)),
+            TimestampFormat::Pattern { pattern } => {
+                if let Ok(ts) = DateTime::parse_from_str(text, pattern) {
+                    return Ok(ts.with_timezone(&Utc));
+                }
+                if let Ok(naive) = NaiveDateTime::parse_from_str(text, pattern) {
+                    return self.localize(naive);
+                }
+                NaiveDate::parse_from_str(text, pattern)
+                    .map_err(|e| format!("Timestamp '{}' does not match '{}': {}", text, pattern, e))
+                    .and_then(|date| self.localize(date.and_hms_opt(0, 0, 0).unwrap()))
+            },
+            TimestampFormat::Auto => {
+                if let Ok(ts) = DateTime::parse_from_rfc3339(text) {
+                    return Ok(ts.with_timezone(&Utc));
+                }
+                if let Some(naive) = AUTO_PATTERNS.iter()
+                    .find_map(|p| NaiveDateTime::parse_from_str(text, p).ok())
+                {
+                    return self.localize(naive);
+                }
+                text.parse::<f64>()
+                    .map_err(|_| format!("Unrecognized timestamp '{}'", text))
+                    .and_then(|n| self.parse_unix(n))
+            },
+        }
+    }
+
+    fn parse_unix(&self, value: f64) -> Result<DateTime<Utc>, String> {
+        let millis = match self.format {
+            TimestampFormat::UnixMillis => value,
+            TimestampFormat::Auto if value.abs() >= AUTO_MILLIS_THRESHOLD => value,
+            TimestampFormat::UnixSeconds | TimestampFormat::Auto => value * 1000.0,
+            _ => return Err(format!("Numeric timestamp {} does not match the configured format", value)),
+        };
+
+        if !millis.is_finite() {
+            return Err("Timestamp is not finite".to_string());
+        }
+        Utc.timestamp_millis_opt(millis.round() as i64)
+            .single()
+            .ok_or_else(|| format!("Timestamp {} is out of range", value))
+    }
+
+    /// Interpret a naive timestamp in the configured timezone
+    fn localize(&self, naive: NaiveDateTime) -> Result<DateTime<Utc>, String> {
+        self.timezone.from_local_datetime(&naive)
+            .earliest()
+            .map(|ts| ts.with_timezone(&Utc))
+            .ok_or_else(|| format!("{} does not exist in timezone {}", naive, self.timezone))
+    }
+
+    fn parse_value(&self, index: usize, raw: &RawValue) -> Result<Option<SensorValue>, String> {
+        let (scale, offset) = self.scales[index];
+        let number = match raw {
+            RawValue::Null => return Ok(None),
+            RawValue::Bool(b) => return Ok(Some(SensorValue::Boolean(*b))),
+            RawValue::Timestamp(_) => return Err("Expected a value, found a timestamp".to_string()),
+            RawValue::Number(n) => *n,
+            RawValue::Text(text) => match text.to_ascii_lowercase().as_str() {
+                "true" => return Ok(Some(SensorValue::Boolean(true))),
+                "false" => return Ok(Some(SensorValue::Boolean(false))),
+                _ => text.parse::<f64>().map_err(|_| format!("'{}' is not a number", text))?,
+            },
+        };
+
+        if !number.is_finite() {
+            return Err(format!("{} is not a finite number", number));
+        }
+        Ok(Some(SensorValue::Numeric(number * scale + offset)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(column: &str) -> ColumnMapping {
+        ColumnMapping {
+            column: column.to_string(),
+            sensor_id: column.to_string(),
+            name: None,
+            unit: None,
+            scale: 1.0,
+            offset: 0.0,
+            sensor_data_id: None,
+        }
+    }
+
+    #[test]
+    fn test_timestamp_formats_and_timezone() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        let parser = RowParser::new(TimestampFormat::Auto, berlin, &[]);
+
+        let ts = parser.parse_timestamp(&RawValue::Text("2024-01-15 12:00:00".to_string())).unwrap();
+        assert_eq!(ts.to_rfc3339(), "2024-01-15T11:00:00+00:00");
+
+        let ts = parser.parse_timestamp(&RawValue::Text("2024-01-15T12:00:00Z".to_string())).unwrap();
+        assert_eq!(ts.to_rfc3339(), "2024-01-15T12:00:00+00:00");
+
+        let ts = parser.parse_timestamp(&RawValue::Text("1705320000000".to_string())).unwrap();
+        assert_eq!(ts.timestamp(), 1_705_320_000);
+
+        // Skipped by the spring-forward transition
+        assert!(parser.parse_timestamp(&RawValue::Text("2024-03-31 02:30:00".to_string())).is_err());
+
+        let parser = RowParser::new(
+            TimestampFormat::Pattern { pattern: "%d/%m/%Y %H:%M".to_string() },
+            Tz::UTC,
+            &[],
+        );
+        let ts = parser.parse_timestamp(&RawValue::Text("15/01/2024 08:30".to_string())).unwrap();
+        assert_eq!(ts.to_rfc3339(), "2024-01-15T08:30:00+00:00");
+    }
+
+    #[test]
+    fn test_value_parsing_reports_cell_errors() {
+        let mut scaled = mapping("pressure");
+        scaled.scale = 0.001;
+        let parser = RowParser::new(TimestampFormat::Auto, Tz::UTC, &[mapping("temp"), scaled]);
+
+        let row = RawRow {
+            row: 7,
+            timestamp: RawValue::Text("2024-01-15T12:00:00Z".to_string()),
+            values: vec![RawValue::Text("n/a".to_string()), RawValue::Text("2500".to_string())],
+        };
+        let (_, values, errors) = parser.parse(&row, "time").unwrap();
+        assert_eq!(values, vec![(1, SensorValue::Numeric(2.5))]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].row, 7);
+        assert_eq!(errors[0].column.as_deref(), Some("temp"));
+
+        let bad = RawRow { timestamp: RawValue::Null, ..row };
+        assert!(parser.parse(&bad, "time").is_err());
+    }
+}
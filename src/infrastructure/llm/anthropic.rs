@@ -9,7 +9,7 @@ use crate::core::domain::traits::llm_client::{
     LLMClient, LLMResult, LLMError, CompletionRequest, CompletionResponse,
     ChatCompletionRequest, ChatCompletionResponse, CompletionStream,
     ChatCompletionStream, EmbeddingRequest, EmbeddingResponse, ModelInfo,
-    UsagePeriod, UsageStats, LLMClientConfig,
+    UsagePeriod, UsageStats, LLMClientConfig, ChatMessage, ContentPart, ImageSource,
 };
 
 const API_URL: &str = "https://api.anthropic.com/v1";
@@ -141,7 +141,7 @@ impl LLMClient for AnthropicClient {
                 MessageRole::Assistant => "assistant",
                 _ => "user", // Default to user for other roles
             }.to_string(),
-            content: m.content.clone().unwrap_or_default(),
+            content: anthropic_content(m),
         }).collect();
 
         let request_body = serde_json::json!({
@@ -184,6 +184,7 @@ impl LLMClient for AnthropicClient {
                     name: None,
                     tool_call_id: None,
                     tool_calls: None,
+                    parts: None,
                 },
                 index: 0,
                 finish_reason: chat_response.stop_reason.into(),
@@ -294,10 +295,36 @@ impl LLMClient for AnthropicClient {
     }
 }
 
+/// Message content as a string, or as content blocks when it has parts
+fn anthropic_content(message: &ChatMessage) -> serde_json::Value {
+    let parts = match &message.parts {
+        Some(parts) if !parts.is_empty() => parts,
+        _ => return serde_json::Value::String(message.content.clone().unwrap_or_default()),
+    };
+
+    let mut blocks: Vec<serde_json::Value> = message.content
+        .iter()
+        .filter(|text| !text.is_empty())
+        .map(|text| serde_json::json!({ "type": "text", "text": text }))
+        .collect();
+    blocks.extend(parts.iter().map(|part| match part {
+        ContentPart::Image { source: ImageSource::Base64 { media_type, data } } => serde_json::json!({
+            "type": "image",
+            "source": { "type": "base64", "media_type": media_type, "data": data },
+        }),
+        ContentPart::Image { source: ImageSource::Url { url } } => serde_json::json!({
+            "type": "image",
+            "source": { "type": "url", "url": url },
+        }),
+        _ => serde_json::json!({ "type": "text", "text": part.to_text() }),
+    }));
+    serde_json::Value::Array(blocks)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct AnthropicMessage {
     role: String,
-    content: String,
+    content: serde_json::Value,
 }
 
 #[derive(Debug, Deserialize)]
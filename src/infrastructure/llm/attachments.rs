@@ -0,0 +1,647 @@
+//! Rendering of message attachments into content parts for LLM requests.
+//!
+//! Images become image parts (local files inline as base64), files become
+//! text excerpts, and sensor data references become a compact table of the
+//! referenced series with, optionally, a PNG line chart of it. Local files
+//! are only read from the attachment store, and only up to a size limit.
+
+use async_trait::async_trait;
+use base64::Engine as _;
+use chrono::{DateTime, Duration, Utc};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::io::AsyncReadExt;
+use tracing::warn;
+
+use crate::core::domain::models::conversation::{Attachment, AttachmentType, Message, MessageSender};
+use crate::core::domain::models::{SensorDataId, SensorReading, SensorType, SensorValue};
+use crate::core::domain::traits::llm_client::{
+    AttachmentRendering, ChatMessage, ContentPart, ImageSource, MessageRole,
+};
+use crate::core::domain::traits::repository::{Pagination, RepositoryError, SensorDataRepository};
+
+/// Bytes of a local file read for an attachment
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+const CHART_WIDTH: u32 = 640;
+const CHART_HEIGHT: u32 = 240;
+const CHART_MARGIN: u32 = 12;
+
+/// Attachment rendering error types
+#[derive(Debug, Error)]
+pub enum AttachmentError {
+    /// The attachment's file could not be read
+    #[error("Failed to read attachment '{name}': {message}")]
+    Io { name: String, message: String },
+
+    /// The attachment has no URL or path to load it from
+    #[error("Attachment '{0}' has no URL or path")]
+    MissingSource(String),
+
+    /// The attachment's path leads out of the attachment store
+    #[error("Attachment '{0}' is outside the attachment store")]
+    OutsideStore(String),
+
+    /// The attachment's image is too large to inline
+    #[error("Attachment '{name}' has {size} bytes, more than the limit of {max}")]
+    TooLarge { name: String, size: u64, max: u64 },
+
+    /// Referenced sensor data could not be loaded
+    #[error("Failed to load sensor data: {0}")]
+    Repository(#[from] RepositoryError),
+
+    /// The chart could not be encoded
+    #[error("Failed to encode chart: {0}")]
+    Chart(String),
+}
+
+/// Result type for attachment rendering
+pub type AttachmentResult<T> = Result<T, AttachmentError>;
+
+/// Renders attachments into content parts
+pub struct AttachmentRenderer {
+    sensor_data: Arc<dyn SensorDataRepository>,
+    store_dir: PathBuf,
+    max_file_bytes: u64,
+    window: Duration,
+    max_points: usize,
+    max_rows: usize,
+    excerpt_chars: usize,
+    charts: bool,
+}
+
+impl AttachmentRenderer {
+    /// Renders attachments whose local files are in `store_dir`
+    pub fn new(sensor_data: Arc<dyn SensorDataRepository>, store_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            sensor_data,
+            store_dir: store_dir.into(),
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            window: Duration::hours(24),
+            max_points: 500,
+            max_rows: 24,
+            excerpt_chars: 4000,
+            charts: true,
+        }
+    }
+
+    /// Bytes read of a local file; larger images are not sent and larger
+    /// files are cut off
+    pub fn with_max_file_bytes(mut self, max_file_bytes: u64) -> Self {
+        self.max_file_bytes = max_file_bytes;
+        self
+    }
+
+    /// Time span of a referenced series, ending at its latest reading
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Readings loaded per series; the most recent are kept
+    pub fn with_max_points(mut self, max_points: usize) -> Self {
+        self.max_points = max_points.max(2);
+        self
+    }
+
+    /// Rows of the table a series is summarized in
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows.max(2);
+        self
+    }
+
+    /// Characters of a text file included in its excerpt
+    pub fn with_excerpt_chars(mut self, excerpt_chars: usize) -> Self {
+        self.excerpt_chars = excerpt_chars;
+        self
+    }
+
+    /// Whether series are also sent as a PNG chart, for vision models
+    pub fn with_charts(mut self, charts: bool) -> Self {
+        self.charts = charts;
+        self
+    }
+
+    /// A conversation message with its attachments as content parts
+    pub async fn chat_message(&self, message: &Message) -> ChatMessage {
+        let role = match message.sender {
+            MessageSender::User { .. } => MessageRole::User,
+            MessageSender::Agent { .. } => MessageRole::Assistant,
+            MessageSender::System => MessageRole::System,
+        };
+        let parts = self.render_all(&message.attachments).await;
+
+        ChatMessage {
+            role,
+            content: Some(message.content.clone()),
+            name: None,
+            tool_call_id: None,
+            tool_calls: None,
+            parts: if parts.is_empty() { None } else { Some(parts) },
+        }
+    }
+
+    /// Content parts of several attachments
+    ///
+    /// An attachment that fails to render is replaced by a note, so the
+    /// model knows it was there.
+    pub async fn render_all(&self, attachments: &[Attachment]) -> Vec<ContentPart> {
+        let mut parts = Vec::new();
+        for attachment in attachments {
+            parts.extend(self.render_attachment(attachment).await);
+        }
+        parts
+    }
+
+    /// Content parts of one attachment
+    pub async fn render(&self, attachment: &Attachment) -> AttachmentResult<Vec<ContentPart>> {
+        match &attachment.attachment_type {
+            AttachmentType::Image => Ok(vec![self.render_image(attachment).await?]),
+            AttachmentType::File => Ok(vec![self.render_file(attachment).await?]),
+            AttachmentType::Link => Ok(vec![ContentPart::Text {
+                text: format!("Link: {} ({})", attachment.name, attachment.url.as_deref().unwrap_or("no URL")),
+            }]),
+            AttachmentType::ConversationReference { conversation_id } => Ok(vec![ContentPart::Text {
+                text: format!("Referenced conversation: {} ({})", attachment.name, conversation_id),
+            }]),
+            AttachmentType::SensorDataReference { sensor_data_id } => {
+                self.render_sensor_data(*sensor_data_id).await
+            }
+        }
+    }
+
+    async fn render_image(&self, attachment: &Attachment) -> AttachmentResult<ContentPart> {
+        let url = attachment.url.as_deref()
+            .ok_or_else(|| AttachmentError::MissingSource(attachment.name.clone()))?;
+
+        let source = if let Some(inline) = url.strip_prefix("data:") {
+            match inline.split_once(";base64,") {
+                Some((media_type, data)) => ImageSource::Base64 {
+                    media_type: media_type.to_string(),
+                    data: data.to_string(),
+                },
+                None => return Err(AttachmentError::Io {
+                    name: attachment.name.clone(),
+                    message: "data URL is not base64-encoded".to_string(),
+                }),
+            }
+        } else if url.starts_with("http://") || url.starts_with("https://") {
+            ImageSource::Url { url: url.to_string() }
+        } else {
+            let (bytes, _) = self.read(attachment, url, false).await?;
+            ImageSource::Base64 {
+                media_type: attachment.mime_type.clone()
+                    .unwrap_or_else(|| image_mime_type(url).to_string()),
+                data: base64::engine::general_purpose::STANDARD.encode(bytes),
+            }
+        };
+
+        Ok(ContentPart::Image { source })
+    }
+
+    async fn render_file(&self, attachment: &Attachment) -> AttachmentResult<ContentPart> {
+        let url = attachment.url.as_deref()
+            .ok_or_else(|| AttachmentError::MissingSource(attachment.name.clone()))?;
+
+        // Remote files are not fetched; the model gets the reference
+        if url.starts_with("http://") || url.starts_with("https://") {
+            return Ok(ContentPart::Text {
+                text: format!("File {} at {}", attachment.name, url),
+            });
+        }
+
+        let (bytes, size) = self.read(attachment, url, true).await?;
+        let text = match std::str::from_utf8(&bytes) {
+            Ok(text) => Some(text),
+            // A file cut off at the limit may end inside a character
+            Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok(),
+            Err(_) => None,
+        };
+        let excerpt = match text {
+            Some(text) if size > bytes.len() as u64 => format!(
+                "{}\n[file cut off after {} of {} bytes]",
+                excerpt(text, self.excerpt_chars),
+                bytes.len(),
+                size
+            ),
+            Some(text) => excerpt(text, self.excerpt_chars),
+            None => format!("[binary file, {} bytes]", size),
+        };
+
+        Ok(ContentPart::File {
+            name: attachment.name.clone(),
+            mime_type: attachment.mime_type.clone(),
+            excerpt,
+        })
+    }
+
+    async fn render_sensor_data(&self, id: SensorDataId) -> AttachmentResult<Vec<ContentPart>> {
+        let data = self.sensor_data.get_by_id(id).await?;
+        let unit = unit(&data.sensor.sensor_type);
+        let title = format!(
+            "Sensor {} ({}){}",
+            data.sensor.name,
+            data.sensor.sensor_id,
+            unit.as_deref().map(|u| format!(", unit {}", u)).unwrap_or_default()
+        );
+
+        let readings = self.readings(id).await?;
+        let points: Vec<(DateTime<Utc>, f64)> = readings
+            .iter()
+            .filter_map(|reading| numeric(&reading.value).map(|value| (reading.timestamp, value)))
+            .collect();
+        if points.is_empty() {
+            return Ok(vec![ContentPart::Text {
+                text: format!("{}: no numeric readings", title),
+            }]);
+        }
+
+        let mut parts = vec![ContentPart::Text { text: series_table(&title, &points, self.max_rows) }];
+        if self.charts && points.len() > 1 {
+            parts.push(ContentPart::Image {
+                source: ImageSource::Base64 {
+                    media_type: "image/png".to_string(),
+                    data: base64::engine::general_purpose::STANDARD.encode(render_chart(&points)?),
+                },
+            });
+        }
+        Ok(parts)
+    }
+
+    /// The most recent readings within the window, oldest first
+    async fn readings(&self, id: SensorDataId) -> AttachmentResult<Vec<SensorReading>> {
+        let end = match self.sensor_data.get_latest_reading(id).await? {
+            Some(latest) => latest.timestamp,
+            None => return Ok(Vec::new()),
+        };
+        let start = end - self.window;
+
+        let mut page = self.sensor_data
+            .get_readings_in_range(id, start, end, Pagination { offset: 0, limit: self.max_points })
+            .await?;
+        if page.total > page.items.len() {
+            let offset = page.total - self.max_points;
+            page = self.sensor_data
+                .get_readings_in_range(id, start, end, Pagination { offset, limit: self.max_points })
+                .await?;
+        }
+
+        let mut readings = page.items;
+        readings.sort_by_key(|reading| reading.timestamp);
+        Ok(readings)
+    }
+
+    /// A file of the attachment store and its size
+    ///
+    /// Relative paths are resolved against the store. Files larger than
+    /// `max_file_bytes` are cut off if `truncate` is set, and rejected
+    /// otherwise.
+    async fn read(&self, attachment: &Attachment, url: &str, truncate: bool) -> AttachmentResult<(Vec<u8>, u64)> {
+        let io = |e: std::io::Error| AttachmentError::Io {
+            name: attachment.name.clone(),
+            message: e.to_string(),
+        };
+
+        // Resolve links and `..` before checking the path is in the store
+        let store = tokio::fs::canonicalize(&self.store_dir).await.map_err(io)?;
+        let path = tokio::fs::canonicalize(store.join(url.strip_prefix("file://").unwrap_or(url)))
+            .await
+            .map_err(io)?;
+        if !path.starts_with(&store) {
+            return Err(AttachmentError::OutsideStore(attachment.name.clone()));
+        }
+
+        let file = tokio::fs::File::open(&path).await.map_err(io)?;
+        let size = file.metadata().await.map_err(io)?.len();
+        if size > self.max_file_bytes && !truncate {
+            return Err(AttachmentError::TooLarge {
+                name: attachment.name.clone(),
+                size,
+                max: self.max_file_bytes,
+            });
+        }
+
+        let mut bytes = Vec::new();
+        file.take(self.max_file_bytes).read_to_end(&mut bytes).await.map_err(io)?;
+        Ok((bytes, size))
+    }
+}
+
+#[async_trait]
+impl AttachmentRendering for AttachmentRenderer {
+    async fn render_attachment(&self, attachment: &Attachment) -> Vec<ContentPart> {
+        match self.render(attachment).await {
+            Ok(parts) => parts,
+            Err(e) => {
+                warn!("Failed to render attachment {}: {}", attachment.id, e);
+                vec![ContentPart::Text {
+                    text: format!("[Attachment '{}' could not be loaded]", attachment.name),
+                }]
+            }
+        }
+    }
+}
+
+fn image_mime_type(path: &str) -> &'static str {
+    match Path::new(path).extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "image/png",
+    }
+}
+
+fn excerpt(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}\n[... {} more characters]", &text[..end], text[end..].chars().count()),
+        None => text.to_string(),
+    }
+}
+
+fn unit(sensor_type: &SensorType) -> Option<String> {
+    match sensor_type {
+        SensorType::Temperature { unit } => Some(format!("{:?}", unit)),
+        SensorType::Pressure { unit } => Some(format!("{:?}", unit)),
+        SensorType::Flow { unit, .. } => Some(format!("{:?}", unit)),
+        SensorType::Custom { measurement_unit, .. } if !measurement_unit.is_empty() => {
+            Some(measurement_unit.clone())
+        }
+        _ => None,
+    }
+}
+
+fn numeric(value: &SensorValue) -> Option<f64> {
+    match value {
+        SensorValue::Numeric(value) => Some(*value),
+        SensorValue::Boolean(value) => Some(if *value { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+/// Summary statistics and a table of the series, averaged into at most
+/// `max_rows` equal-count buckets
+fn series_table(title: &str, points: &[(DateTime<Utc>, f64)], max_rows: usize) -> String {
+    let values = points.iter().map(|(_, value)| *value);
+    let min = values.clone().fold(f64::INFINITY, f64::min);
+    let max = values.clone().fold(f64::NEG_INFINITY, f64::max);
+    let mean = values.sum::<f64>() / points.len() as f64;
+    let (first, _) = points[0];
+    let (last, last_value) = points[points.len() - 1];
+
+    let mut table = format!(
+        "{}: {} readings from {} to {}\nmin {} | mean {} | max {} | last {}\n",
+        title,
+        points.len(),
+        first.to_rfc3339(),
+        last.to_rfc3339(),
+        format_value(min),
+        format_value(mean),
+        format_value(max),
+        format_value(last_value),
+    );
+
+    let bucket = (points.len() + max_rows - 1) / max_rows;
+    table.push_str(if bucket > 1 { "time | mean value\n" } else { "time | value\n" });
+    for chunk in points.chunks(bucket) {
+        let (time, _) = chunk[0];
+        let value = chunk.iter().map(|(_, value)| value).sum::<f64>() / chunk.len() as f64;
+        table.push_str(&format!("{} | {}\n", time.format("%Y-%m-%d %H:%M:%S"), format_value(value)));
+    }
+    table
+}
+
+fn format_value(value: f64) -> String {
+    let formatted = format!("{:.3}", value);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// A line chart of the series as PNG, scaled to fill the plot area
+///
+/// The chart has no labels; the accompanying table gives the ranges.
+fn render_chart(points: &[(DateTime<Utc>, f64)]) -> AttachmentResult<Vec<u8>> {
+    let (width, height) = (CHART_WIDTH, CHART_HEIGHT);
+    let mut pixels = vec![255u8; (width * height * 3) as usize];
+    let mut plot = |x: i64, y: i64, color: [u8; 3]| {
+        if x >= 0 && y >= 0 && (x as u32) < width && (y as u32) < height {
+            let index = ((y as u32 * width + x as u32) * 3) as usize;
+            pixels[index..index + 3].copy_from_slice(&color);
+        }
+    };
+
+    let (left, top) = (CHART_MARGIN as i64, CHART_MARGIN as i64);
+    let (right, bottom) = ((width - CHART_MARGIN) as i64, (height - CHART_MARGIN) as i64);
+
+    // Frame and quartile grid lines
+    for quarter in 0..=4 {
+        let y = top + (bottom - top) * quarter / 4;
+        let color = if quarter == 0 || quarter == 4 { [160, 160, 160] } else { [225, 225, 225] };
+        for x in left..=right {
+            plot(x, y, color);
+        }
+    }
+    for y in top..=bottom {
+        plot(left, y, [160, 160, 160]);
+        plot(right, y, [160, 160, 160]);
+    }
+
+    let start = points[0].0.timestamp_millis();
+    let span = (points[points.len() - 1].0.timestamp_millis() - start).max(1) as f64;
+    let mut min = points.iter().map(|(_, value)| *value).fold(f64::INFINITY, f64::min);
+    let mut max = points.iter().map(|(_, value)| *value).fold(f64::NEG_INFINITY, f64::max);
+    if max - min < f64::EPSILON {
+        let pad = (min.abs() * 0.05).max(1.0);
+        min -= pad;
+        max += pad;
+    }
+
+    let to_pixel = |(time, value): &(DateTime<Utc>, f64)| {
+        let x = left + ((time.timestamp_millis() - start) as f64 / span * (right - left) as f64).round() as i64;
+        let y = bottom - ((value - min) / (max - min) * (bottom - top) as f64).round() as i64;
+        (x, y)
+    };
+
+    let line = [31, 119, 180];
+    for pair in points.windows(2) {
+        let ((x0, y0), (x1, y1)) = (to_pixel(&pair[0]), to_pixel(&pair[1]));
+        // Bresenham, two pixels thick
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+        let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+        loop {
+            plot(x, y, line);
+            plot(x, y + 1, line);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += sx;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(|e| AttachmentError::Chart(e.to_string()))?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::models::{
+        ReadingQuality, SensorData, SensorInfo, SensorSpecifications, SensorStatus,
+    };
+    use crate::core::domain::traits::mock_repositories::InMemorySensorRepository;
+    use uuid::Uuid;
+
+    fn sensor_data(readings: usize) -> SensorData {
+        let mut data = SensorData::new(Uuid::new_v4(), SensorInfo {
+            sensor_id: "pump-1-bearing".to_string(),
+            name: "Bearing temperature".to_string(),
+            sensor_type: SensorType::Custom {
+                category: "temperature".to_string(),
+                measurement_unit: "°C".to_string(),
+            },
+            location: None,
+            specifications: SensorSpecifications {
+                range: None,
+                accuracy: None,
+                resolution: None,
+                sampling_rate: None,
+                response_time_ms: None,
+                operating_temp_range: None,
+                power_consumption: None,
+                protocol: None,
+                manufacturer: None,
+            },
+            status: SensorStatus::Offline,
+            calibration: None,
+        });
+        let start = Utc::now() - Duration::hours(2);
+        data.readings = (0..readings)
+            .map(|i| SensorReading {
+                id: Uuid::new_v4(),
+                value: SensorValue::Numeric(60.0 + i as f64),
+                timestamp: start + Duration::minutes(i as i64),
+                quality: ReadingQuality::default(),
+                context: None,
+                alerts: Vec::new(),
+            })
+            .collect();
+        data
+    }
+
+    fn attachment(attachment_type: AttachmentType, url: Option<&str>) -> Attachment {
+        Attachment {
+            id: Uuid::new_v4(),
+            attachment_type,
+            name: "attachment".to_string(),
+            size: None,
+            mime_type: None,
+            url: url.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sensor_series_as_table_and_chart() {
+        let repository = Arc::new(InMemorySensorRepository::default());
+        let data = repository.create(sensor_data(100)).await.unwrap();
+        let renderer = AttachmentRenderer::new(repository, std::env::temp_dir())
+            .with_max_points(60)
+            .with_max_rows(6);
+
+        let parts = renderer
+            .render(&attachment(AttachmentType::SensorDataReference { sensor_data_id: data.id }, None))
+            .await
+            .unwrap();
+        assert_eq!(parts.len(), 2);
+
+        // Only the 60 most recent readings, in 6 rows of 10
+        let table = parts[0].to_text();
+        assert!(table.starts_with("Sensor Bearing temperature (pump-1-bearing), unit °C: 60 readings"));
+        assert!(table.contains("min 100 | mean 129.5 | max 159 | last 159"));
+        assert_eq!(table.lines().count(), 2 + 1 + 6);
+
+        match &parts[1] {
+            ContentPart::Image { source: ImageSource::Base64 { media_type, data } } => {
+                assert_eq!(media_type, "image/png");
+                let png = base64::engine::general_purpose::STANDARD.decode(data).unwrap();
+                assert_eq!(&png[1..4], b"PNG");
+            }
+            other => panic!("unexpected part: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_files_and_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let renderer = AttachmentRenderer::new(Arc::new(InMemorySensorRepository::default()), dir.path())
+            .with_excerpt_chars(5);
+        std::fs::write(dir.path().join("notes.txt"), "maintenance log").unwrap();
+
+        let parts = renderer
+            .render(&attachment(AttachmentType::File, Some("notes.txt")))
+            .await
+            .unwrap();
+        assert_eq!(parts, vec![ContentPart::File {
+            name: "attachment".to_string(),
+            mime_type: None,
+            excerpt: "maint\n[... 10 more characters]".to_string(),
+        }]);
+
+        let parts = renderer
+            .render(&attachment(AttachmentType::Image, Some("data:image/jpeg;base64,/9j/4A==")))
+            .await
+            .unwrap();
+        assert_eq!(parts, vec![ContentPart::Image {
+            source: ImageSource::Base64 { media_type: "image/jpeg".to_string(), data: "/9j/4A==".to_string() },
+        }]);
+
+        // A missing file leaves a note instead of failing the message
+        let missing = attachment(AttachmentType::File, Some("missing.txt"));
+        let parts = renderer.render_all(&[missing]).await;
+        assert_eq!(parts.len(), 1);
+        assert!(parts[0].to_text().contains("could not be loaded"));
+    }
+
+    #[tokio::test]
+    async fn test_reads_are_confined_and_capped() {
+        let root = tempfile::tempdir().unwrap();
+        let store = root.path().join("attachments");
+        std::fs::create_dir(&store).unwrap();
+        std::fs::write(root.path().join("secret.txt"), "outside").unwrap();
+        std::fs::write(store.join("log.txt"), "pump ok\n".repeat(4)).unwrap();
+        std::fs::write(store.join("photo.png"), [0u8; 64]).unwrap();
+        let renderer = AttachmentRenderer::new(Arc::new(InMemorySensorRepository::default()), &store)
+            .with_max_file_bytes(16);
+
+        for url in ["../secret.txt", root.path().join("secret.txt").to_str().unwrap()] {
+            assert!(matches!(
+                renderer.render(&attachment(AttachmentType::File, Some(url))).await,
+                Err(AttachmentError::OutsideStore(_))
+            ));
+        }
+
+        let parts = renderer
+            .render(&attachment(AttachmentType::File, Some("file://log.txt")))
+            .await
+            .unwrap();
+        assert_eq!(parts[0].to_text(), "File attachment:\npump ok\npump ok\n\n[file cut off after 16 of 32 bytes]");
+
+        assert!(matches!(
+            renderer.render(&attachment(AttachmentType::Image, Some("photo.png"))).await,
+            Err(AttachmentError::TooLarge { size: 64, max: 16, .. })
+        ));
+    }
+}
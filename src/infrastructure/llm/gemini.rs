@@ -11,6 +11,7 @@ use crate::core::domain::traits::llm_client::{
     ChatCompletionStream, EmbeddingRequest, EmbeddingResponse, ModelInfo,
     UsagePeriod, UsageStats, LLMClientConfig, CompletionChoice, ChatChoice,
     ChatMessage, ToolCall, FunctionCall, TokenUsage, Embedding, ModelType,
+    ResponseFormatType, ContentPart, ImageSource,
 };
 
 const API_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
@@ -132,12 +133,10 @@ impl LLMClient for GeminiClient {
                 "assistant" => "model".to_string(),
                 _ => "user".to_string(),
             },
-            parts: vec![GeminiPart {
-                text: m.content.clone(),
-            }],
+            parts: gemini_parts(m),
         }).collect();
 
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "contents": contents,
             "generationConfig": {
                 "maxOutputTokens": request.max_tokens.unwrap_or(100),
@@ -147,6 +146,12 @@ impl LLMClient for GeminiClient {
             }
         });
 
+        // Gemini's response schemas are an OpenAPI subset, so only the JSON
+        // MIME type is requested and the schema is left to the prompt
+        if request.response_format.as_ref().is_some_and(|f| f.format_type != ResponseFormatType::Text) {
+            request_body["generationConfig"]["responseMimeType"] = serde_json::json!("application/json");
+        }
+
         let response = self.client
             .post(&url)
             .json(&request_body)
@@ -183,6 +188,7 @@ impl LLMClient for GeminiClient {
                     name: None,
                     tool_call_id: None,
                     tool_calls: None,
+                    parts: None,
                 },
                 index: 0,
                 finish_reason: chat_response.candidates
@@ -339,9 +345,80 @@ struct GeminiContent {
     parts: Vec<GeminiPart>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Message content as Gemini parts; images are sent inline or by URI
+fn gemini_parts(message: &ChatMessage) -> Vec<GeminiPart> {
+    let mut parts: Vec<GeminiPart> = message.content
+        .iter()
+        .map(|text| GeminiPart::text(text.clone()))
+        .collect();
+    for part in message.parts.iter().flatten() {
+        parts.push(match part {
+            ContentPart::Image { source: ImageSource::Base64 { media_type, data } } => GeminiPart {
+                inline_data: Some(GeminiBlob {
+                    mime_type: media_type.clone(),
+                    data: Some(data.clone()),
+                    file_uri: None,
+                }),
+                ..GeminiPart::default()
+            },
+            ContentPart::Image { source: ImageSource::Url { url } } => GeminiPart {
+                file_data: Some(GeminiBlob {
+                    mime_type: mime_type_of(url).to_string(),
+                    data: None,
+                    file_uri: Some(url.clone()),
+                }),
+                ..GeminiPart::default()
+            },
+            _ => GeminiPart::text(part.to_text()),
+        });
+    }
+    if parts.is_empty() {
+        parts.push(GeminiPart::text(String::new()));
+    }
+    parts
+}
+
+/// Image MIME type from a URL's extension, as file data requires one
+fn mime_type_of(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+    if path.ends_with(".jpg") || path.ends_with(".jpeg") {
+        "image/jpeg"
+    } else if path.ends_with(".webp") {
+        "image/webp"
+    } else if path.ends_with(".gif") {
+        "image/gif"
+    } else {
+        "image/png"
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct GeminiPart {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     text: Option<String>,
+    #[serde(rename = "inlineData", default, skip_serializing_if = "Option::is_none")]
+    inline_data: Option<GeminiBlob>,
+    #[serde(rename = "fileData", default, skip_serializing_if = "Option::is_none")]
+    file_data: Option<GeminiBlob>,
+}
+
+impl GeminiPart {
+    fn text(text: String) -> Self {
+        Self {
+            text: Some(text),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiBlob {
+    #[serde(rename = "mimeType")]
+    mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+    #[serde(rename = "fileUri", default, skip_serializing_if = "Option::is_none")]
+    file_uri: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -125,7 +125,7 @@ impl LLMClient for HuggingFaceClient {
 
         // Convert messages to a prompt format
         let prompt = request.messages.iter()
-            .map(|m| format!("{}: {}", m.role, m.content.as_deref().unwrap_or("")))
+            .map(|m| format!("{}: {}", m.role, m.text()))
             .collect::<Vec<_>>()
             .join("\n");
 
@@ -175,6 +175,7 @@ impl LLMClient for HuggingFaceClient {
                     name: None,
                     tool_call_id: None,
                     tool_calls: None,
+                    parts: None,
                 },
                 index: 0,
                 finish_reason: Some("length".to_string()),
@@ -105,11 +105,11 @@ impl LLMClient for LMStudioClient {
 
         let messages: Vec<LMStudioChatMessage> = request.messages.iter().map(|m| LMStudioChatMessage {
             role: m.role.clone(),
-            content: m.content.clone(),
+            content: super::openai_content(m),
             name: m.name.clone(),
         }).collect();
 
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "model": model,
             "messages": messages,
             "max_tokens": request.max_tokens.unwrap_or(100),
@@ -121,6 +121,10 @@ impl LLMClient for LMStudioClient {
             "n": request.n.unwrap_or(1),
         });
 
+        if let Some(format) = request.response_format.as_ref().and_then(super::openai_response_format) {
+            request_body["response_format"] = format;
+        }
+
         let response = self.client
             .post(&url)
             .json(&request_body)
@@ -288,7 +292,7 @@ struct LMStudioChatResponse {
 #[derive(Debug, Serialize, Deserialize)]
 struct LMStudioChatMessage {
     role: String,
-    content: Option<String>,
+    content: Option<serde_json::Value>,
     name: Option<String>,
 }
 
@@ -347,10 +351,11 @@ impl From<LMStudioChatChoice> for ChatChoice {
         Self {
             message: ChatMessage {
                 role: choice.message.role,
-                content: choice.message.content,
+                content: choice.message.content.and_then(super::openai_content_text),
                 name: choice.message.name,
                 tool_call_id: None,
                 tool_calls: None,
+                parts: None,
             },
             index: choice.index,
             finish_reason: choice.finish_reason.into(),
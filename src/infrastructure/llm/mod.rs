@@ -1,28 +1,90 @@
 //! LLM client implementations.
 
 mod anthropic;
+mod attachments;
 mod openai;
 mod openrouter;
 mod gemini;
 mod huggingface;
 mod ollama;
 mod lmstudio;
+mod router;
 
 pub use anthropic::AnthropicClient;
+pub use attachments::{AttachmentRenderer, AttachmentError, AttachmentResult};
 pub use openai::OpenAIClient;
 pub use openrouter::OpenRouterClient;
 pub use gemini::GeminiClient;
 pub use huggingface::HuggingFaceClient;
 pub use ollama::OllamaClient;
 pub use lmstudio::LMStudioClient;
+pub use router::{LLMRouter, ProviderRoute, ProviderHealth, CircuitState, LOCAL_ONLY_PARAM};
 
 use async_trait::async_trait;
 use anyhow::Result;
 
 use crate::core::domain::traits::llm_client::{
     LLMClient, LLMResult, LLMError, LLMClientConfig, LLMClientFactory,
+    ResponseFormat, ResponseFormatType, ChatMessage, ContentPart,
 };
 
+/// `response_format` parameter of OpenAI-compatible chat APIs
+pub(crate) fn openai_response_format(format: &ResponseFormat) -> Option<serde_json::Value> {
+    match (format.format_type, &format.json_schema) {
+        (ResponseFormatType::Text, _) => None,
+        (ResponseFormatType::JsonSchema, Some(schema)) => Some(serde_json::json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": "response",
+                "schema": schema,
+                // Strict mode rejects schemas with optional properties
+                "strict": false,
+            },
+        })),
+        _ => Some(serde_json::json!({ "type": "json_object" })),
+    }
+}
+
+/// `content` of a message in OpenAI-compatible chat APIs
+///
+/// Messages with content parts are sent as an array of text and
+/// `image_url` parts; file excerpts are sent as text.
+pub(crate) fn openai_content(message: &ChatMessage) -> Option<serde_json::Value> {
+    let parts = match &message.parts {
+        Some(parts) if !parts.is_empty() => parts,
+        _ => return message.content.clone().map(serde_json::Value::String),
+    };
+
+    let mut content: Vec<serde_json::Value> = message.content
+        .iter()
+        .filter(|text| !text.is_empty())
+        .map(|text| serde_json::json!({ "type": "text", "text": text }))
+        .collect();
+    content.extend(parts.iter().map(|part| match part {
+        ContentPart::Image { source } => serde_json::json!({
+            "type": "image_url",
+            "image_url": { "url": source.to_url() },
+        }),
+        _ => serde_json::json!({ "type": "text", "text": part.to_text() }),
+    }));
+    Some(serde_json::Value::Array(content))
+}
+
+/// Text of a reply's `content` in OpenAI-compatible chat APIs
+pub(crate) fn openai_content_text(content: serde_json::Value) -> Option<String> {
+    match content {
+        serde_json::Value::String(text) => Some(text),
+        serde_json::Value::Array(parts) => Some(
+            parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(|text| text.as_str()))
+                .collect::<Vec<_>>()
+                .join(""),
+        ),
+        _ => None,
+    }
+}
+
 /// Factory for creating LLM clients
 pub struct DefaultLLMClientFactory;
 
@@ -98,4 +160,33 @@ mod tests {
             assert!(client.validate_credentials().await.unwrap());
         }
     }
+
+    #[test]
+    fn test_openai_content_parts() {
+        use crate::core::domain::traits::llm_client::{ImageSource, MessageRole};
+
+        let mut message = ChatMessage {
+            role: MessageRole::User,
+            content: Some("Why is the pump hot?".to_string()),
+            name: None,
+            tool_call_id: None,
+            tool_calls: None,
+            parts: None,
+        };
+        assert_eq!(openai_content(&message), Some(serde_json::json!("Why is the pump hot?")));
+
+        message.parts = Some(vec![ContentPart::Image {
+            source: ImageSource::Base64 { media_type: "image/png".to_string(), data: "iVBORw==".to_string() },
+        }]);
+        assert_eq!(openai_content(&message), Some(serde_json::json!([
+            { "type": "text", "text": "Why is the pump hot?" },
+            { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw==" } },
+        ])));
+
+        message.content = Some(String::new());
+        assert_eq!(openai_content(&message), Some(serde_json::json!([
+            { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw==" } },
+        ])));
+        assert_eq!(openai_content_text(serde_json::json!([{ "type": "text", "text": "Bearing wear" }])), Some("Bearing wear".to_string()));
+    }
 }
\ No newline at end of file
@@ -11,6 +11,7 @@ use crate::core::domain::traits::llm_client::{
     ChatCompletionStream, EmbeddingRequest, EmbeddingResponse, ModelInfo,
     UsagePeriod, UsageStats, LLMClientConfig, CompletionChoice, ChatChoice,
     ChatMessage, ToolCall, FunctionCall, TokenUsage, Embedding, ModelType,
+    ResponseFormatType, ContentPart, ImageSource,
 };
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
@@ -113,12 +114,9 @@ impl LLMClient for OllamaClient {
 
         let url = format!("{}/api/chat", self.base_url);
 
-        let messages: Vec<OllamaChatMessage> = request.messages.iter().map(|m| OllamaChatMessage {
-            role: m.role.clone(),
-            content: m.content.clone().unwrap_or_default(),
-        }).collect();
+        let messages: Vec<OllamaChatMessage> = request.messages.iter().map(ollama_message).collect();
 
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "model": model,
             "messages": messages,
             "stream": false,
@@ -130,6 +128,15 @@ impl LLMClient for OllamaClient {
             }
         });
 
+        // Ollama takes "json" or a JSON Schema as the output format
+        if let Some(format) = &request.response_format {
+            match (format.format_type, &format.json_schema) {
+                (ResponseFormatType::Text, _) => {}
+                (ResponseFormatType::JsonSchema, Some(schema)) => request_body["format"] = schema.clone(),
+                _ => request_body["format"] = serde_json::json!("json"),
+            }
+        }
+
         let response = self.client
             .post(&url)
             .json(&request_body)
@@ -160,6 +167,7 @@ impl LLMClient for OllamaClient {
                     name: None,
                     tool_call_id: None,
                     tool_calls: None,
+                    parts: None,
                 },
                 index: 0,
                 finish_reason: Some("stop".to_string()),
@@ -307,6 +315,35 @@ struct OllamaGenerateResponse {
 struct OllamaChatMessage {
     role: String,
     content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+}
+
+/// Ollama takes text content and base64 images; vision models read the
+/// images, and image URLs, which it cannot fetch, are named in the text
+fn ollama_message(message: &ChatMessage) -> OllamaChatMessage {
+    let mut content = message.content.clone().unwrap_or_default();
+    let mut images = Vec::new();
+    for part in message.parts.iter().flatten() {
+        let text = match part {
+            ContentPart::Image { source: ImageSource::Base64 { data, .. } } => {
+                images.push(data.clone());
+                continue;
+            }
+            ContentPart::Image { source: ImageSource::Url { url } } => format!("[image: {}]", url),
+            _ => part.to_text(),
+        };
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+        content.push_str(&text);
+    }
+
+    OllamaChatMessage {
+        role: message.role.clone(),
+        content,
+        images,
+    }
 }
 
 #[derive(Debug, Deserialize)]
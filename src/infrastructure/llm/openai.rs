@@ -126,12 +126,12 @@ impl LLMClient for OpenAIClient {
 
         let messages: Vec<OpenAIChatMessage> = request.messages.iter().map(|m| OpenAIChatMessage {
             role: m.role.into(),
-            content: m.content.clone(),
+            content: super::openai_content(m),
             name: m.name.clone(),
             function_call: None,
         }).collect();
 
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "model": model,
             "messages": messages,
             "max_tokens": request.max_tokens.unwrap_or(100),
@@ -148,6 +148,10 @@ impl LLMClient for OpenAIClient {
             "function_call": request.tool_choice,
         });
 
+        if let Some(format) = request.response_format.as_ref().and_then(super::openai_response_format) {
+            request_body["response_format"] = format;
+        }
+
         let response = self.client
             .post(&url)
             .json(&request_body)
@@ -334,7 +338,7 @@ struct OpenAIChatResponse {
 #[derive(Debug, Serialize, Deserialize)]
 struct OpenAIChatMessage {
     role: String,
-    content: Option<String>,
+    content: Option<serde_json::Value>,
     name: Option<String>,
     function_call: Option<OpenAIFunctionCall>,
 }
@@ -426,7 +430,7 @@ impl From<OpenAIChatChoice> for ChatChoice {
         Self {
             message: ChatMessage {
                 role: choice.message.role.into(),
-                content: choice.message.content,
+                content: choice.message.content.and_then(super::openai_content_text),
                 name: choice.message.name,
                 tool_call_id: None,
                 tool_calls: choice.message.function_call.map(|f| vec![ToolCall {
@@ -437,6 +441,7 @@ impl From<OpenAIChatChoice> for ChatChoice {
                         arguments: f.arguments,
                     },
                 }]),
+                parts: None,
             },
             index: choice.index,
             finish_reason: choice.finish_reason.into(),
@@ -128,7 +128,7 @@ impl LLMClient for OpenRouterClient {
 
         let messages: Vec<OpenRouterChatMessage> = request.messages.iter().map(|m| OpenRouterChatMessage {
             role: m.role.into(),
-            content: m.content.clone(),
+            content: super::openai_content(m),
             name: m.name.clone(),
         }).collect();
 
@@ -150,6 +150,9 @@ impl LLMClient for OpenRouterClient {
         if let Some(site_name) = &self.site_name {
             request_body["site_name"] = serde_json::json!(site_name);
         }
+        if let Some(format) = request.response_format.as_ref().and_then(super::openai_response_format) {
+            request_body["response_format"] = format;
+        }
 
         let response = self.client
             .post(&url)
@@ -290,7 +293,7 @@ struct OpenRouterChatResponse {
 #[derive(Debug, Serialize, Deserialize)]
 struct OpenRouterChatMessage {
     role: String,
-    content: Option<String>,
+    content: Option<serde_json::Value>,
     name: Option<String>,
 }
 
@@ -352,10 +355,11 @@ impl From<OpenRouterChatChoice> for ChatChoice {
         Self {
             message: ChatMessage {
                 role: choice.message.role.into(),
-                content: choice.message.content,
+                content: choice.message.content.and_then(super::openai_content_text),
                 name: choice.message.name,
                 tool_call_id: None,
                 tool_calls: None,
+                parts: None,
             },
             index: choice.index,
             finish_reason: choice.finish_reason.into(),
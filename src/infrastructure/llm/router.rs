@@ -0,0 +1,843 @@
+//! Routing and failover across LLM providers.
+//!
+//! [`LLMRouter`] is an [`LLMClient`] over several provider clients. Each
+//! request goes to the providers in policy order, moving on when one fails
+//! with a rate limit, timeout or outage. A circuit breaker per provider
+//! skips providers that keep failing until a cool-down has passed, then
+//! lets a single probe request through.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::core::domain::traits::llm_client::{
+    ChatCompletionRequest, ChatCompletionResponse, ChatCompletionStream, CompletionRequest,
+    CompletionResponse, CompletionStream, EmbeddingRequest, EmbeddingResponse, LLMClient,
+    LLMClientFactory, LLMError, LLMResult, ModelInfo, UsagePeriod, UsageStats,
+};
+pub use crate::core::domain::traits::llm_client::LOCAL_ONLY_PARAM;
+use crate::core::domain::traits::usage_store::UsageMetering;
+use crate::infrastructure::config::{LLMConfig, RoutingPolicy};
+
+/// Providers that run on this machine
+pub const LOCAL_PROVIDERS: &[&str] = &["ollama", "lmstudio"];
+
+/// Weight of the newest sample in the latency average
+const LATENCY_SMOOTHING: f64 = 0.3;
+
+/// A provider the router can send requests to
+pub struct ProviderRoute {
+    name: String,
+    client: Arc<dyn LLMClient>,
+    model: Option<String>,
+    /// Model the provider is priced by when no model is pinned
+    default_model: Option<String>,
+    local: bool,
+    standby: bool,
+    cost_per_1k: Option<f64>,
+}
+
+impl ProviderRoute {
+    pub fn new(name: impl Into<String>, client: Arc<dyn LLMClient>) -> Self {
+        let name = name.into();
+        let local = LOCAL_PROVIDERS.contains(&name.to_lowercase().as_str());
+        Self {
+            name,
+            client,
+            model: None,
+            default_model: None,
+            local,
+            standby: false,
+            // Local inference has no per-token price
+            cost_per_1k: local.then_some(0.0),
+        }
+    }
+
+    /// Use `model` on this provider instead of the requested model
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Price the provider by `model` when no model is pinned
+    pub fn with_default_model(mut self, model: impl Into<String>) -> Self {
+        self.default_model = Some(model.into());
+        self
+    }
+
+    /// Mark the provider as local or remote
+    pub fn with_local(mut self, local: bool) -> Self {
+        self.local = local;
+        self
+    }
+
+    /// Only use this provider for requests pinned to local providers
+    pub fn with_standby(mut self, standby: bool) -> Self {
+        self.standby = standby;
+        self
+    }
+
+    /// Blended price per 1K tokens, for cost-aware routing
+    pub fn with_cost_per_1k(mut self, cost: f64) -> Self {
+        self.cost_per_1k = Some(cost);
+        self
+    }
+}
+
+/// Circuit breaker state of a provider
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Requests flow normally
+    Closed,
+    /// Requests skip the provider until the cool-down ends
+    Open,
+    /// One probe request is allowed through
+    HalfOpen,
+}
+
+/// Health of a provider as seen by the router
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    pub provider: String,
+    pub local: bool,
+    pub standby: bool,
+    pub circuit: CircuitState,
+    pub consecutive_failures: u32,
+    pub total_requests: u64,
+    pub total_failures: u64,
+    /// Smoothed latency of successful requests
+    pub average_latency_ms: Option<f64>,
+    pub cost_per_1k: Option<f64>,
+    pub last_error: Option<String>,
+    /// Time until an open circuit allows a probe
+    pub retry_in_ms: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct RouteHealth {
+    open_until: Option<Instant>,
+    probing: bool,
+    consecutive_failures: u32,
+    total_requests: u64,
+    total_failures: u64,
+    latency_ms: Option<f64>,
+    cost_per_1k: Option<f64>,
+    last_error: Option<String>,
+}
+
+struct RouteState {
+    route: ProviderRoute,
+    health: Mutex<RouteHealth>,
+}
+
+impl RouteState {
+    fn circuit(health: &RouteHealth, now: Instant) -> CircuitState {
+        match health.open_until {
+            Some(until) if until > now => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+            None => CircuitState::Closed,
+        }
+    }
+
+    /// Permission for a request to use this provider now, if it may
+    fn try_acquire(&self, now: Instant) -> Option<Permit<'_>> {
+        let mut health = self.health.lock().unwrap();
+        match Self::circuit(&health, now) {
+            CircuitState::Closed => Some(Permit { state: self, probe: false }),
+            CircuitState::Open => None,
+            CircuitState::HalfOpen if health.probing => None,
+            CircuitState::HalfOpen => {
+                health.probing = true;
+                Some(Permit { state: self, probe: true })
+            }
+        }
+    }
+
+    fn record_success(&self, latency: Duration) {
+        let mut health = self.health.lock().unwrap();
+        let latency = latency.as_secs_f64() * 1000.0;
+        health.total_requests += 1;
+        health.consecutive_failures = 0;
+        health.open_until = None;
+        health.probing = false;
+        health.latency_ms = Some(match health.latency_ms {
+            Some(average) => average + LATENCY_SMOOTHING * (latency - average),
+            None => latency,
+        });
+    }
+
+    fn record_failure(&self, error: &LLMError, breaker: &BreakerConfig) {
+        let mut health = self.health.lock().unwrap();
+        let now = Instant::now();
+        health.total_requests += 1;
+        health.total_failures += 1;
+        health.consecutive_failures += 1;
+        health.last_error = Some(error.to_string());
+
+        let retry_after = match error {
+            LLMError::RateLimitError { retry_after: Some(seconds), .. } => Some(Duration::from_secs(*seconds)),
+            _ => None,
+        };
+        // A failed probe reopens the circuit at once
+        if health.probing || health.consecutive_failures >= breaker.failure_threshold || retry_after.is_some() {
+            health.open_until = Some(now + retry_after.unwrap_or(breaker.open_duration).max(breaker.open_duration));
+        }
+        health.probing = false;
+    }
+
+    /// The provider answered, even if it rejected the request
+    fn record_response(&self) {
+        let mut health = self.health.lock().unwrap();
+        health.total_requests += 1;
+        health.probing = false;
+        health.open_until = None;
+        health.consecutive_failures = 0;
+    }
+
+    fn cost(&self) -> Option<f64> {
+        self.health.lock().unwrap().cost_per_1k.or(self.route.cost_per_1k)
+    }
+
+    fn latency(&self) -> Option<f64> {
+        self.health.lock().unwrap().latency_ms
+    }
+}
+
+/// A request's use of a provider, settled by recording its outcome
+///
+/// A half-open circuit lets a single probe through. If the probe is
+/// dropped unsettled, e.g. because the caller's future was cancelled, the
+/// circuit lets the next request probe instead.
+struct Permit<'a> {
+    state: &'a RouteState,
+    probe: bool,
+}
+
+impl Permit<'_> {
+    fn record_success(mut self, latency: Duration) {
+        self.probe = false;
+        self.state.record_success(latency);
+    }
+
+    fn record_failure(mut self, error: &LLMError, breaker: &BreakerConfig) {
+        self.probe = false;
+        self.state.record_failure(error, breaker);
+    }
+
+    fn record_response(mut self) {
+        self.probe = false;
+        self.state.record_response();
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.state.health.lock().unwrap().probing = false;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BreakerConfig {
+    failure_threshold: u32,
+    open_duration: Duration,
+}
+
+/// Whether an error should send the request to the next provider
+///
+/// Rate limits, timeouts, network and provider errors, unavailable models,
+/// rejected credentials and unreadable responses are specific to one
+/// provider. Invalid or filtered requests would fail anywhere.
+pub fn is_failover_error(error: &LLMError) -> bool {
+    matches!(
+        error,
+        LLMError::RateLimitError { .. }
+            | LLMError::Timeout(_)
+            | LLMError::NetworkError(_)
+            | LLMError::ProviderError { .. }
+            | LLMError::ModelNotAvailable(_)
+            | LLMError::AuthError(_)
+            | LLMError::ParseError(_)
+    )
+}
+
+/// LLM client routing requests across providers
+pub struct LLMRouter {
+    routes: Vec<RouteState>,
+    policy: RoutingPolicy,
+    local_only: bool,
+    breaker: BreakerConfig,
+    metering: Option<Arc<dyn UsageMetering>>,
+}
+
+impl LLMRouter {
+    /// Create a router trying `routes` in the given order
+    pub fn new(routes: Vec<ProviderRoute>) -> Self {
+        Self {
+            routes: routes
+                .into_iter()
+                .map(|route| RouteState { route, health: Mutex::new(RouteHealth::default()) })
+                .collect(),
+            policy: RoutingPolicy::Priority,
+            local_only: false,
+            breaker: BreakerConfig {
+                failure_threshold: 3,
+                open_duration: Duration::from_secs(30),
+            },
+            metering: None,
+        }
+    }
+
+    /// Record the usage of every provider and enforce agent budgets
+    pub fn with_usage_accounting(mut self, metering: Arc<dyn UsageMetering>) -> Self {
+        for state in &mut self.routes {
+            let route = &mut state.route;
+            route.client = metering.clone().meter(&route.name, route.client.clone());
+        }
+        self.metering = Some(metering);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: RoutingPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Pin every request to local providers
+    pub fn with_local_only(mut self, local_only: bool) -> Self {
+        self.local_only = local_only;
+        self
+    }
+
+    /// Open a provider's circuit after `failure_threshold` consecutive
+    /// failures, for `open_duration`
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, open_duration: Duration) -> Self {
+        self.breaker = BreakerConfig {
+            failure_threshold: failure_threshold.max(1),
+            open_duration,
+        };
+        self
+    }
+
+    /// Build a router from the LLM configuration
+    ///
+    /// The default provider comes first, followed by the configured
+    /// fallbacks, which use their own default models. Local providers not
+    /// listed are added on standby for local-only requests. Providers whose
+    /// client cannot be created are skipped.
+    pub async fn from_config(config: &LLMConfig, factory: &dyn LLMClientFactory) -> LLMResult<Self> {
+        let mut names: Vec<String> = Vec::new();
+        for name in std::iter::once(&config.default_provider).chain(&config.routing.fallback_providers) {
+            let name = name.to_lowercase();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        let listed = names.len();
+        for local in LOCAL_PROVIDERS {
+            if !names.iter().any(|n| n == local) {
+                names.push(local.to_string());
+            }
+        }
+
+        let mut routes = Vec::new();
+        for (position, name) in names.into_iter().enumerate() {
+            let Some(client_config) = config.client_config(&name) else {
+                continue;
+            };
+            let model = client_config.default_model.clone();
+            match factory.create_client(&name, client_config).await {
+                Ok(client) => {
+                    let mut route = ProviderRoute::new(name, Arc::from(client)).with_standby(position >= listed);
+                    if let Some(model) = model {
+                        route = if position > 0 { route.with_model(model) } else { route.with_default_model(model) };
+                    }
+                    routes.push(route);
+                }
+                Err(e) => tracing::warn!("LLM provider '{}' is not available for routing: {}", name, e),
+            }
+        }
+
+        if routes.is_empty() {
+            return Err(LLMError::Other("No LLM provider could be configured".to_string()));
+        }
+
+        let routing = &config.routing;
+        Ok(Self::new(routes)
+            .with_policy(routing.policy)
+            .with_local_only(routing.local_only)
+            .with_circuit_breaker(routing.failure_threshold, Duration::from_secs(routing.open_seconds)))
+    }
+
+    /// Health of every provider
+    pub fn health(&self) -> Vec<ProviderHealth> {
+        let now = Instant::now();
+        self.routes
+            .iter()
+            .map(|state| {
+                let health = state.health.lock().unwrap();
+                ProviderHealth {
+                    provider: state.route.name.clone(),
+                    local: state.route.local,
+                    standby: state.route.standby,
+                    circuit: RouteState::circuit(&health, now),
+                    consecutive_failures: health.consecutive_failures,
+                    total_requests: health.total_requests,
+                    total_failures: health.total_failures,
+                    average_latency_ms: health.latency_ms,
+                    cost_per_1k: health.cost_per_1k.or(state.route.cost_per_1k),
+                    last_error: health.last_error.clone(),
+                    retry_in_ms: health.open_until
+                        .filter(|until| *until > now)
+                        .map(|until| (until - now).as_millis() as u64),
+                }
+            })
+            .collect()
+    }
+
+    /// Close a provider's circuit; returns false if there is no such provider
+    pub fn reset_provider(&self, provider: &str) -> bool {
+        match self.routes.iter().find(|s| s.route.name.eq_ignore_ascii_case(provider)) {
+            Some(state) => {
+                let mut health = state.health.lock().unwrap();
+                health.open_until = None;
+                health.probing = false;
+                health.consecutive_failures = 0;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Look up model prices for cost-aware routing
+    ///
+    /// Prices set in usage accounting take precedence over those the
+    /// providers report.
+    pub async fn refresh_costs(&self) {
+        for state in &self.routes {
+            let route = &state.route;
+            let Some(model) = route.model.as_ref().or(route.default_model.as_ref()) else { continue };
+
+            let mut cost = match &self.metering {
+                Some(metering) => metering.cost_per_1k(&route.name, model).await,
+                None => None,
+            };
+            if cost.is_none() {
+                if let Ok(ModelInfo { pricing: Some(pricing), .. }) = route.client.get_model_info(model).await {
+                    cost = Some((pricing.input_cost_per_1k + pricing.output_cost_per_1k) / 2.0);
+                }
+            }
+            if cost.is_some() {
+                state.health.lock().unwrap().cost_per_1k = cost;
+            }
+        }
+    }
+
+    /// Providers eligible for a request, in policy order
+    fn candidates(&self, local_only: bool) -> Vec<&RouteState> {
+        let local_only = local_only || self.local_only;
+        let mut candidates: Vec<&RouteState> = self.routes
+            .iter()
+            .filter(|s| if local_only { s.route.local } else { !s.route.standby })
+            .collect();
+
+        // Unknown costs and latencies sort last and first respectively, so
+        // unmeasured providers get measured
+        match self.policy {
+            RoutingPolicy::Priority => {}
+            RoutingPolicy::LowestCost => candidates.sort_by(|a, b| {
+                a.cost().unwrap_or(f64::MAX).total_cmp(&b.cost().unwrap_or(f64::MAX))
+            }),
+            RoutingPolicy::LowestLatency => candidates.sort_by(|a, b| {
+                a.latency().unwrap_or(0.0).total_cmp(&b.latency().unwrap_or(0.0))
+            }),
+        }
+        candidates
+    }
+
+    /// Run `call` on each eligible provider until one succeeds
+    async fn route<T, F, Fut>(&self, local_only: bool, call: F) -> LLMResult<T>
+    where
+        F: Fn(Arc<dyn LLMClient>, Option<String>) -> Fut,
+        Fut: Future<Output = LLMResult<T>>,
+    {
+        let candidates = self.candidates(local_only);
+        if candidates.is_empty() {
+            return Err(LLMError::ProviderError {
+                provider: "router".to_string(),
+                message: if local_only || self.local_only {
+                    "No local provider is configured".to_string()
+                } else {
+                    "No provider is configured".to_string()
+                },
+            });
+        }
+
+        let mut last_error = None;
+        for state in candidates {
+            let Some(permit) = state.try_acquire(Instant::now()) else {
+                continue;
+            };
+
+            let started = Instant::now();
+            match call(state.route.client.clone(), state.route.model.clone()).await {
+                Ok(result) => {
+                    permit.record_success(started.elapsed());
+                    return Ok(result);
+                }
+                Err(e) if is_failover_error(&e) => {
+                    tracing::warn!("LLM provider '{}' failed, trying the next: {}", state.route.name, e);
+                    permit.record_failure(&e, &self.breaker);
+                    last_error = Some(e);
+                }
+                Err(e) => {
+                    permit.record_response();
+                    return Err(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| LLMError::ProviderError {
+            provider: "router".to_string(),
+            message: "All providers are unavailable".to_string(),
+        }))
+    }
+}
+
+fn pinned_local(extra_params: &std::collections::HashMap<String, serde_json::Value>) -> bool {
+    extra_params.get(LOCAL_ONLY_PARAM).and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+#[async_trait]
+impl LLMClient for LLMRouter {
+    async fn complete(&self, request: CompletionRequest) -> LLMResult<CompletionResponse> {
+        self.route(pinned_local(&request.extra_params), |client, model| {
+            let mut request = request.clone();
+            if let Some(model) = model {
+                request.model = model;
+            }
+            async move { client.complete(request).await }
+        }).await
+    }
+
+    async fn chat_complete(&self, request: ChatCompletionRequest) -> LLMResult<ChatCompletionResponse> {
+        self.route(pinned_local(&request.extra_params), |client, model| {
+            let mut request = request.clone();
+            if let Some(model) = model {
+                request.model = model;
+            }
+            async move { client.chat_complete(request).await }
+        }).await
+    }
+
+    /// Fails over only until a stream has started
+    async fn stream_complete(&self, request: CompletionRequest) -> LLMResult<Box<dyn CompletionStream>> {
+        self.route(pinned_local(&request.extra_params), |client, model| {
+            let mut request = request.clone();
+            if let Some(model) = model {
+                request.model = model;
+            }
+            async move { client.stream_complete(request).await }
+        }).await
+    }
+
+    /// Fails over only until a stream has started
+    async fn stream_chat_complete(&self, request: ChatCompletionRequest) -> LLMResult<Box<dyn ChatCompletionStream>> {
+        self.route(pinned_local(&request.extra_params), |client, model| {
+            let mut request = request.clone();
+            if let Some(model) = model {
+                request.model = model;
+            }
+            async move { client.stream_chat_complete(request).await }
+        }).await
+    }
+
+    /// Embeddings are not portable across models, so the requested model is
+    /// kept on every provider
+    async fn embed(&self, request: EmbeddingRequest) -> LLMResult<EmbeddingResponse> {
+        self.route(pinned_local(&request.extra_params), |client, _| {
+            let request = request.clone();
+            async move { client.embed(request).await }
+        }).await
+    }
+
+    async fn list_models(&self) -> LLMResult<Vec<ModelInfo>> {
+        let mut models = Vec::new();
+        let mut last_error = None;
+        for state in self.candidates(false) {
+            match state.route.client.list_models().await {
+                Ok(mut provider_models) => models.append(&mut provider_models),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) if models.is_empty() => Err(e),
+            _ => Ok(models),
+        }
+    }
+
+    async fn get_model_info(&self, model_id: &str) -> LLMResult<ModelInfo> {
+        self.route(false, |client, _| {
+            let model_id = model_id.to_string();
+            async move { client.get_model_info(&model_id).await }
+        }).await
+    }
+
+    /// Valid if any eligible provider accepts its credentials
+    async fn validate_credentials(&self) -> LLMResult<bool> {
+        for state in self.candidates(false) {
+            if let Ok(true) = state.route.client.validate_credentials().await {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Usage across all providers when accounting is enabled
+    async fn get_usage(&self, period: UsagePeriod) -> LLMResult<UsageStats> {
+        match &self.metering {
+            Some(metering) => metering.usage(period).await,
+            None => self.route(false, |client, _| async move { client.get_usage(period).await }).await,
+        }
+    }
+
+    /// Cancels on every provider, as any of them may hold the request
+    async fn cancel_request(&self, request_id: &str) -> LLMResult<()> {
+        for state in &self.routes {
+            let _ = state.route.client.cancel_request(request_id).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::traits::mock_llm_client::MockLLMClient;
+    use std::collections::HashMap;
+
+    /// A provider failing with `errors`, then replying with its own name
+    fn provider(name: &'static str, errors: Vec<LLMError>) -> Arc<MockLLMClient> {
+        Arc::new(MockLLMClient::replying(vec![name]).with_errors(errors).with_usage(1, 1))
+    }
+
+    fn request(extra_params: HashMap<String, serde_json::Value>) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            request_id: "r1".to_string(),
+            messages: Vec::new(),
+            model: "gpt-4o".to_string(),
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop_sequences: None,
+            n: None,
+            system: None,
+            tools: None,
+            tool_choice: None,
+            response_format: None,
+            user_id: None,
+            conversation_context: None,
+            extra_params,
+        }
+    }
+
+    async fn answer(router: &LLMRouter, extra_params: HashMap<String, serde_json::Value>) -> LLMResult<String> {
+        let response = router.chat_complete(request(extra_params)).await?;
+        Ok(response.choices[0].message.content.clone().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_on_provider_errors_only() {
+        let openai = provider("openai", vec![
+            LLMError::RateLimitError { message: "slow down".to_string(), retry_after: None },
+            LLMError::InvalidRequest("bad schema".to_string()),
+        ]);
+        let anthropic = provider("anthropic", Vec::new());
+        let router = LLMRouter::new(vec![
+            ProviderRoute::new("openai", openai.clone()),
+            ProviderRoute::new("anthropic", anthropic.clone()).with_model("claude-3-5-sonnet-latest"),
+        ]);
+
+        assert_eq!(answer(&router, HashMap::new()).await.unwrap(), "anthropic");
+        assert_eq!(anthropic.requests()[0].model, "claude-3-5-sonnet-latest");
+
+        // A request the provider rejects is not retried elsewhere
+        assert!(matches!(answer(&router, HashMap::new()).await, Err(LLMError::InvalidRequest(_))));
+        assert_eq!(anthropic.calls(), 1);
+        assert_eq!(openai.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_and_recovers() {
+        let timeouts = (0..3).map(|_| LLMError::Timeout(30)).collect();
+        let openai = provider("openai", timeouts);
+        let ollama = provider("ollama", Vec::new());
+        let router = LLMRouter::new(vec![
+            ProviderRoute::new("openai", openai.clone()),
+            ProviderRoute::new("ollama", ollama.clone()),
+        ])
+        .with_circuit_breaker(2, Duration::from_millis(50));
+
+        for _ in 0..2 {
+            assert_eq!(answer(&router, HashMap::new()).await.unwrap(), "ollama");
+        }
+        let health = router.health();
+        assert_eq!(health[0].circuit, CircuitState::Open);
+        assert_eq!(health[0].total_failures, 2);
+        assert!(health[0].last_error.as_deref().unwrap().contains("timeout"));
+
+        // Open: skipped without a call
+        assert_eq!(answer(&router, HashMap::new()).await.unwrap(), "ollama");
+        assert_eq!(openai.calls(), 2);
+
+        // After the cool-down one probe goes through; it fails and reopens
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(router.health()[0].circuit, CircuitState::HalfOpen);
+        assert_eq!(answer(&router, HashMap::new()).await.unwrap(), "ollama");
+        assert_eq!(openai.calls(), 3);
+        assert_eq!(router.health()[0].circuit, CircuitState::Open);
+
+        // The next probe succeeds and closes the circuit
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(answer(&router, HashMap::new()).await.unwrap(), "openai");
+        assert_eq!(router.health()[0].circuit, CircuitState::Closed);
+        assert_eq!(router.health()[0].consecutive_failures, 0);
+    }
+
+    #[test]
+    fn test_dropped_probe_releases_half_open_circuit() {
+        let state = RouteState {
+            route: ProviderRoute::new("openai", provider("openai", Vec::new())),
+            health: Mutex::new(RouteHealth::default()),
+        };
+        let now = Instant::now();
+        state.health.lock().unwrap().open_until = Some(now);
+
+        // One probe at a time
+        let probe = state.try_acquire(now).unwrap();
+        assert!(state.try_acquire(now).is_none());
+
+        // A probe whose request was cancelled lets the next one through
+        drop(probe);
+        let probe = state.try_acquire(now).unwrap();
+        probe.record_failure(&LLMError::Timeout(30), &BreakerConfig {
+            failure_threshold: 1,
+            open_duration: Duration::from_secs(60),
+        });
+        assert!(state.try_acquire(Instant::now()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_local_only_pins_to_local_providers() {
+        let openai = provider("openai", Vec::new());
+        let lmstudio = provider("lmstudio", Vec::new());
+        let router = LLMRouter::new(vec![
+            ProviderRoute::new("openai", openai.clone()),
+            ProviderRoute::new("lmstudio", lmstudio.clone()).with_standby(true),
+        ]);
+
+        assert_eq!(answer(&router, HashMap::new()).await.unwrap(), "openai");
+
+        let pinned = HashMap::from([(LOCAL_ONLY_PARAM.to_string(), serde_json::Value::Bool(true))]);
+        assert_eq!(answer(&router, pinned).await.unwrap(), "lmstudio");
+
+        // Local providers failing does not leak the request to a remote one
+        let router = LLMRouter::new(vec![
+            ProviderRoute::new("openai", openai.clone()),
+            ProviderRoute::new("lmstudio", provider("lmstudio", vec![LLMError::NetworkError("refused".to_string())])),
+        ])
+        .with_local_only(true);
+        assert!(matches!(answer(&router, HashMap::new()).await, Err(LLMError::NetworkError(_))));
+        assert_eq!(openai.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_local_only_pins_embeddings() {
+        let openai = provider("openai", Vec::new());
+        let ollama = provider("ollama", Vec::new());
+        let router = LLMRouter::new(vec![
+            ProviderRoute::new("openai", openai.clone()),
+            ProviderRoute::new("ollama", ollama.clone()).with_standby(true),
+        ]);
+        let embedding = |extra_params| EmbeddingRequest {
+            input: vec!["boiler manual".to_string()],
+            model: "nomic-embed-text".to_string(),
+            encoding_format: None,
+            user_id: None,
+            extra_params,
+        };
+
+        router.embed(embedding(HashMap::new())).await.unwrap();
+        assert_eq!(openai.embed_requests().len(), 1);
+
+        let pinned = HashMap::from([(LOCAL_ONLY_PARAM.to_string(), serde_json::Value::Bool(true))]);
+        router.embed(embedding(pinned)).await.unwrap();
+        assert_eq!(ollama.embed_requests().len(), 1);
+
+        let router = LLMRouter::new(vec![
+            ProviderRoute::new("openai", openai.clone()),
+            ProviderRoute::new("ollama", ollama.clone()),
+        ])
+        .with_local_only(true);
+        router.embed(embedding(HashMap::new())).await.unwrap();
+        assert_eq!(ollama.embed_requests().len(), 2);
+        assert_eq!(openai.embed_requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lowest_cost_policy() {
+        let openai = provider("openai", Vec::new());
+        let openrouter = provider("openrouter", Vec::new());
+        let router = LLMRouter::new(vec![
+            ProviderRoute::new("openai", openai).with_cost_per_1k(0.01),
+            ProviderRoute::new("openrouter", openrouter).with_cost_per_1k(0.002),
+        ])
+        .with_policy(RoutingPolicy::LowestCost);
+
+        assert_eq!(answer(&router, HashMap::new()).await.unwrap(), "openrouter");
+    }
+
+    /// Prices by provider, without recording usage
+    struct Prices(HashMap<&'static str, f64>);
+
+    #[async_trait]
+    impl UsageMetering for Prices {
+        fn meter(self: Arc<Self>, _provider: &str, client: Arc<dyn LLMClient>) -> Arc<dyn LLMClient> {
+            client
+        }
+
+        async fn usage(&self, _period: UsagePeriod) -> LLMResult<UsageStats> {
+            unimplemented!()
+        }
+
+        async fn cost_per_1k(&self, provider: &str, _model: &str) -> Option<f64> {
+            self.0.get(provider).copied()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lowest_cost_uses_accounting_prices() {
+        let openai = provider("openai", Vec::new());
+        let anthropic = provider("anthropic", Vec::new());
+        let router = LLMRouter::new(vec![
+            ProviderRoute::new("openai", openai).with_default_model("gpt-4o"),
+            ProviderRoute::new("anthropic", anthropic).with_model("claude-3-5-haiku-latest"),
+        ])
+        .with_policy(RoutingPolicy::LowestCost)
+        .with_usage_accounting(Arc::new(Prices(HashMap::from([("openai", 0.00625), ("anthropic", 0.0024)]))));
+
+        // Unpriced remote providers keep their order
+        assert_eq!(answer(&router, HashMap::new()).await.unwrap(), "openai");
+
+        router.refresh_costs().await;
+        assert_eq!(router.health()[0].cost_per_1k, Some(0.00625));
+        assert_eq!(router.health()[1].cost_per_1k, Some(0.0024));
+        assert_eq!(answer(&router, HashMap::new()).await.unwrap(), "anthropic");
+    }
+}
@@ -4,16 +4,20 @@
 //! - Database access and repositories
 //! - LLM client implementations
 //! - Tool executors
+//! - Historical data import
 //! - Configuration management
 //! - Logging infrastructure
 //! - Security utilities
+//! - Data mapping transforms
 
 pub mod config;
 pub mod db;
+pub mod import;
 pub mod llm;
 pub mod tools;
 pub mod logging;
 pub mod security;
+pub mod transform;
 
 // Re-export commonly used types
 pub use config::AppConfig;
@@ -0,0 +1,450 @@
+//! Tamper-evident audit log
+//!
+//! Tool executions, configuration changes and authentication events are
+//! appended to a hash chain: every entry stores the SHA-256 hash of the
+//! previous entry and a hash over its own contents. Editing, removing or
+//! reordering stored entries breaks the chain, which [`AuditLog::verify`]
+//! reports.
+
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::RwLock as StdRwLock;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+
+/// Previous hash of the first entry in the chain
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Number of entries read at a time during verification
+const VERIFY_BATCH_SIZE: usize = 500;
+
+/// Audit error types
+#[derive(Debug, Error)]
+pub enum AuditError {
+    /// Entry could not be serialized for hashing
+    #[error("Audit serialization error: {0}")]
+    Serialization(String),
+
+    /// Storage error
+    #[error("Audit storage error: {0}")]
+    Repository(#[from] RepositoryError),
+}
+
+/// Result type for audit operations
+pub type AuditResult<T> = Result<T, AuditError>;
+
+/// Kind of audited event
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditCategory {
+    /// A tool was executed
+    ToolExecution,
+
+    /// Twin, agent or tool configuration was changed
+    ConfigChange,
+
+    /// Login attempt or credential use
+    Authentication,
+
+    /// Operator decision on a held tool operation
+    Approval,
+}
+
+/// Outcome of an audited event
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+pub enum AuditOutcome {
+    /// Completed successfully
+    Success,
+
+    /// Failed or was rejected
+    Failure(String),
+}
+
+/// Event to be recorded
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub category: AuditCategory,
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub details: Value,
+    pub outcome: AuditOutcome,
+}
+
+impl AuditEvent {
+    /// Create a successful event
+    pub fn new(category: AuditCategory, actor: impl Into<String>, action: impl Into<String>) -> Self {
+        Self {
+            category,
+            actor: actor.into(),
+            action: action.into(),
+            target: None,
+            details: Value::Null,
+            outcome: AuditOutcome::Success,
+        }
+    }
+
+    /// Set the affected object (tool, twin, agent or user ID)
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Set event details such as parameters or changed fields
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = details;
+        self
+    }
+
+    /// Set the outcome from an operation result
+    pub fn with_result<T, E: std::fmt::Display>(mut self, result: &Result<T, E>) -> Self {
+        self.outcome = match result {
+            Ok(_) => AuditOutcome::Success,
+            Err(e) => AuditOutcome::Failure(e.to_string()),
+        };
+        self
+    }
+}
+
+/// Entry in the audit chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the chain, starting at 1
+    pub sequence: u64,
+
+    /// Time the event was recorded
+    pub timestamp: DateTime<Utc>,
+
+    pub category: AuditCategory,
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub details: Value,
+    pub outcome: AuditOutcome,
+
+    /// Hash of the previous entry ([`GENESIS_HASH`] for the first)
+    pub previous_hash: String,
+
+    /// Hex SHA-256 over the fields above
+    pub hash: String,
+}
+
+/// Fields covered by an entry's hash
+#[derive(Serialize)]
+struct HashedFields<'a> {
+    sequence: u64,
+    timestamp: String,
+    category: AuditCategory,
+    actor: &'a str,
+    action: &'a str,
+    target: &'a Option<String>,
+    details: &'a Value,
+    outcome: &'a AuditOutcome,
+    previous_hash: &'a str,
+}
+
+impl AuditEntry {
+    /// Compute the hash of this entry's contents
+    pub fn compute_hash(&self) -> AuditResult<String> {
+        let fields = HashedFields {
+            sequence: self.sequence,
+            timestamp: self.timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            category: self.category,
+            actor: &self.actor,
+            action: &self.action,
+            target: &self.target,
+            details: &self.details,
+            outcome: &self.outcome,
+            previous_hash: &self.previous_hash,
+        };
+        let bytes = serde_json::to_vec(&fields)
+            .map_err(|e| AuditError::Serialization(e.to_string()))?;
+
+        Ok(digest(&SHA256, &bytes).as_ref().iter().map(|b| format!("{:02x}", b)).collect())
+    }
+}
+
+/// Result of verifying the audit chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditVerification {
+    /// Whether the whole chain is intact
+    pub valid: bool,
+
+    /// Number of entries checked
+    pub entries_checked: u64,
+
+    /// Sequence number of the first entry that failed verification
+    pub first_invalid_sequence: Option<u64>,
+
+    /// Description of the problem
+    pub error: Option<String>,
+}
+
+/// Append-only storage for audit entries
+#[async_trait]
+pub trait AuditStore: Send + Sync {
+    /// Append an entry; fails if the sequence number is already taken
+    async fn append(&self, entry: &AuditEntry) -> RepositoryResult<()>;
+
+    /// Get the entry with the highest sequence number
+    async fn last_entry(&self) -> RepositoryResult<Option<AuditEntry>>;
+
+    /// List entries with a sequence number above `after`, in order
+    async fn list_entries(&self, after: u64, limit: usize) -> RepositoryResult<Vec<AuditEntry>>;
+}
+
+/// In-memory audit storage
+#[derive(Default)]
+pub struct InMemoryAuditStore {
+    entries: StdRwLock<Vec<AuditEntry>>,
+}
+
+impl InMemoryAuditStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace a stored entry, bypassing the chain (for tamper tests)
+    #[cfg(test)]
+    fn overwrite(&self, entry: AuditEntry) {
+        let mut entries = self.entries.write().unwrap();
+        if let Some(existing) = entries.iter_mut().find(|e| e.sequence == entry.sequence) {
+            *existing = entry;
+        }
+    }
+}
+
+#[async_trait]
+impl AuditStore for InMemoryAuditStore {
+    async fn append(&self, entry: &AuditEntry) -> RepositoryResult<()> {
+        let mut entries = self.entries.write().unwrap();
+        if entries.iter().any(|e| e.sequence == entry.sequence) {
+            return Err(RepositoryError::AlreadyExists {
+                entity_type: "AuditEntry".to_string(),
+                id: entry.sequence.to_string(),
+            });
+        }
+        entries.push(entry.clone());
+        Ok(())
+    }
+
+    async fn last_entry(&self) -> RepositoryResult<Option<AuditEntry>> {
+        Ok(self.entries.read().unwrap().iter().max_by_key(|e| e.sequence).cloned())
+    }
+
+    async fn list_entries(&self, after: u64, limit: usize) -> RepositoryResult<Vec<AuditEntry>> {
+        let mut entries: Vec<_> = self.entries.read().unwrap()
+            .iter()
+            .filter(|e| e.sequence > after)
+            .cloned()
+            .collect();
+        entries.sort_by_key(|e| e.sequence);
+        entries.truncate(limit);
+        Ok(entries)
+    }
+}
+
+/// Hash-chained audit log
+pub struct AuditLog {
+    /// Entry storage
+    store: Arc<dyn AuditStore>,
+
+    /// Sequence and hash of the last entry written; loaded on first use
+    /// and held while appending so entries are chained in order
+    tail: Mutex<Option<(u64, String)>>,
+}
+
+impl AuditLog {
+    /// Create an audit log over a store
+    pub fn new(store: Arc<dyn AuditStore>) -> Self {
+        Self {
+            store,
+            tail: Mutex::new(None),
+        }
+    }
+
+    /// Append an event to the chain
+    pub async fn record(&self, event: AuditEvent) -> AuditResult<AuditEntry> {
+        let mut tail = self.tail.lock().await;
+        let (last_sequence, previous_hash) = match tail.as_ref() {
+            Some(tail) => tail.clone(),
+            None => match self.store.last_entry().await? {
+                Some(last) => (last.sequence, last.hash),
+                None => (0, GENESIS_HASH.to_string()),
+            },
+        };
+
+        let mut entry = AuditEntry {
+            sequence: last_sequence + 1,
+            timestamp: Utc::now(),
+            category: event.category,
+            actor: event.actor,
+            action: event.action,
+            target: event.target,
+            details: event.details,
+            outcome: event.outcome,
+            previous_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash()?;
+
+        self.store.append(&entry).await?;
+        *tail = Some((entry.sequence, entry.hash.clone()));
+
+        Ok(entry)
+    }
+
+    /// Record an event, logging instead of failing if it cannot be stored
+    pub async fn record_or_log(&self, event: AuditEvent) {
+        let action = event.action.clone();
+        if let Err(e) = self.record(event).await {
+            tracing::error!("Failed to write audit entry for {}: {}", action, e);
+        }
+    }
+
+    /// List entries with a sequence number above `after`
+    pub async fn list(&self, after: u64, limit: usize) -> AuditResult<Vec<AuditEntry>> {
+        Ok(self.store.list_entries(after, limit).await?)
+    }
+
+    /// Walk the whole chain and check every link and hash
+    pub async fn verify(&self) -> AuditResult<AuditVerification> {
+        // Hold the tail so no entries are appended during verification
+        let tail = self.tail.lock().await;
+        let mut expected_sequence = 1;
+        let mut previous_hash = GENESIS_HASH.to_string();
+
+        loop {
+            let batch = self.store.list_entries(expected_sequence - 1, VERIFY_BATCH_SIZE).await?;
+            if batch.is_empty() {
+                break;
+            }
+
+            for entry in &batch {
+                let error = if entry.sequence != expected_sequence {
+                    Some(format!("Expected entry {}, found {}", expected_sequence, entry.sequence))
+                } else if entry.previous_hash != previous_hash {
+                    Some("Previous hash does not match the preceding entry".to_string())
+                } else if entry.compute_hash()? != entry.hash {
+                    Some("Entry contents do not match its hash".to_string())
+                } else {
+                    None
+                };
+
+                if let Some(error) = error {
+                    return Ok(AuditVerification {
+                        valid: false,
+                        entries_checked: expected_sequence - 1,
+                        first_invalid_sequence: Some(expected_sequence),
+                        error: Some(error),
+                    });
+                }
+
+                previous_hash = entry.hash.clone();
+                expected_sequence += 1;
+            }
+        }
+
+        let entries_checked = expected_sequence - 1;
+
+        // Entries removed from the end leave a valid but shorter chain
+        if let Some((last_sequence, _)) = tail.as_ref() {
+            if *last_sequence > entries_checked {
+                return Ok(AuditVerification {
+                    valid: false,
+                    entries_checked,
+                    first_invalid_sequence: Some(entries_checked + 1),
+                    error: Some(format!("Entries after {} are missing", entries_checked)),
+                });
+            }
+        }
+
+        Ok(AuditVerification {
+            valid: true,
+            entries_checked,
+            first_invalid_sequence: None,
+            error: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn create_log() -> (AuditLog, Arc<InMemoryAuditStore>) {
+        let store = Arc::new(InMemoryAuditStore::new());
+        let log = AuditLog::new(store.clone());
+
+        log.record(AuditEvent::new(AuditCategory::Authentication, "alice", "login")).await.unwrap();
+        log.record(
+            AuditEvent::new(AuditCategory::ToolExecution, "alice", "execute_tool")
+                .with_target("modbus_write")
+                .with_details(json!({ "register": 40001, "value": 12 }))
+        ).await.unwrap();
+        log.record(
+            AuditEvent::new(AuditCategory::ConfigChange, "bob", "update_twin")
+                .with_result(&Err::<(), _>("twin not found"))
+        ).await.unwrap();
+
+        (log, store)
+    }
+
+    #[tokio::test]
+    async fn test_entries_are_chained() {
+        let (log, _) = create_log().await;
+        let entries = log.list(0, 10).await.unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].previous_hash, GENESIS_HASH);
+        assert_eq!(entries[1].previous_hash, entries[0].hash);
+        assert_eq!(entries[2].previous_hash, entries[1].hash);
+        assert_eq!(entries[2].outcome, AuditOutcome::Failure("twin not found".to_string()));
+
+        let verification = log.verify().await.unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.entries_checked, 3);
+    }
+
+    #[tokio::test]
+    async fn test_chain_continues_across_instances() {
+        let (_, store) = create_log().await;
+
+        let log = AuditLog::new(store);
+        let entry = log.record(AuditEvent::new(AuditCategory::Authentication, "carol", "login")).await.unwrap();
+
+        assert_eq!(entry.sequence, 4);
+        assert!(log.verify().await.unwrap().valid);
+    }
+
+    #[tokio::test]
+    async fn test_verify_detects_tampering() {
+        let (log, store) = create_log().await;
+
+        // Change the logged parameters without updating the hash
+        let mut entry = log.list(1, 1).await.unwrap().remove(0);
+        entry.details = json!({ "register": 40001, "value": 0 });
+        store.overwrite(entry.clone());
+
+        let verification = log.verify().await.unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.first_invalid_sequence, Some(2));
+
+        // Recomputing the hash breaks the link to the next entry instead
+        entry.hash = entry.compute_hash().unwrap();
+        store.overwrite(entry);
+
+        let verification = log.verify().await.unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.first_invalid_sequence, Some(3));
+    }
+}
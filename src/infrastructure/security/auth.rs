@@ -8,12 +8,16 @@ use chrono::{DateTime, Utc};
 use ring::rand::{SecureRandom, SystemRandom};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 
+use crate::core::domain::traits::repository::RepositoryError;
 use crate::infrastructure::config::SecurityConfig;
 use crate::infrastructure::security::encryption::EncryptionService;
 use crate::infrastructure::security::permissions::{Permission, Role};
+use crate::infrastructure::security::store::{InMemorySecurityRepository, SecurityRepository};
 
 /// Authentication error types
 #[derive(Debug, thiserror::Error)]
@@ -38,10 +42,18 @@ pub enum AuthError {
     #[error("User not found")]
     UserNotFound,
     
+    /// Username already taken
+    #[error("User already exists: {0}")]
+    UserAlreadyExists(String),
+    
     /// Unauthorized
     #[error("Unauthorized")]
     Unauthorized,
     
+    /// Storage error
+    #[error("Storage error: {0}")]
+    Storage(#[from] RepositoryError),
+    
     /// Internal error
     #[error("Internal error: {0}")]
     Internal(String),
@@ -110,29 +122,48 @@ pub struct User {
 }
 
 /// API key manager
+///
+/// Keys are stored hashed in the security repository and cached by hash
+/// after first use. Last-use timestamps are collected in memory and
+/// written in batches by [`ApiKeyManager::flush_last_used`].
 pub struct ApiKeyManager {
     /// Encryption service
     encryption: Arc<EncryptionService>,
     
-    /// API keys (in-memory cache)
+    /// API keys by hash (in-memory cache)
     api_keys: RwLock<HashMap<String, ApiKey>>,
     
+    /// Last use times not yet written to the repository
+    pending_last_used: Mutex<HashMap<Uuid, DateTime<Utc>>>,
+    
+    /// Persistent storage
+    repository: Arc<dyn SecurityRepository>,
+    
     /// Random number generator
     rng: SystemRandom,
 }
 
 impl ApiKeyManager {
-    /// Create a new API key manager
+    /// Create a new API key manager backed by in-memory storage
     pub fn new(encryption: Arc<EncryptionService>) -> Self {
         Self {
             encryption,
             api_keys: RwLock::new(HashMap::new()),
+            pending_last_used: Mutex::new(HashMap::new()),
+            repository: Arc::new(InMemorySecurityRepository::new()),
             rng: SystemRandom::new(),
         }
     }
     
+    /// Use a persistent repository
+    pub fn with_repository(mut self, repository: Arc<dyn SecurityRepository>) -> Self {
+        self.repository = repository;
+        self.invalidate_all();
+        self
+    }
+    
     /// Generate a new API key
-    pub fn generate_api_key(&self, name: &str, user_id: Uuid, expires_at: Option<DateTime<Utc>>, permissions: Vec<Permission>) -> Result<(String, ApiKey)> {
+    pub async fn generate_api_key(&self, name: &str, user_id: Uuid, expires_at: Option<DateTime<Utc>>, permissions: Vec<Permission>) -> Result<(String, ApiKey)> {
         // Generate a random API key
         let mut key_bytes = [0u8; 32];
         self.rng.fill(&mut key_bytes).map_err(|e| anyhow!("Failed to generate random bytes: {}", e))?;
@@ -158,22 +189,27 @@ impl ApiKeyManager {
         };
         
         // Store the API key
-        let mut api_keys = self.api_keys.write().unwrap();
-        api_keys.insert(api_key.id.to_string(), api_key.clone());
+        self.repository.save_api_key(&api_key).await?;
+        self.api_keys.write().unwrap().insert(api_key.hash.clone(), api_key.clone());
         
         Ok((key, api_key))
     }
     
     /// Verify an API key
-    pub fn verify_api_key(&self, key: &str) -> Result<ApiKey, AuthError> {
+    pub async fn verify_api_key(&self, key: &str) -> Result<ApiKey, AuthError> {
         let hash = self.hash_api_key(key).map_err(|e| AuthError::Internal(e.to_string()))?;
         
         // Find the API key by hash
-        let api_keys = self.api_keys.read().unwrap();
-        let api_key = api_keys.values()
-            .find(|k| k.hash == hash)
-            .cloned()
-            .ok_or(AuthError::ApiKeyNotFound)?;
+        let cached = self.api_keys.read().unwrap().get(&hash).cloned();
+        let api_key = match cached {
+            Some(api_key) => api_key,
+            None => {
+                let api_key = self.repository.find_api_key_by_hash(&hash).await?
+                    .ok_or(AuthError::ApiKeyNotFound)?;
+                self.api_keys.write().unwrap().insert(hash.clone(), api_key.clone());
+                api_key
+            }
+        };
         
         // Check if the API key is expired
         if let Some(expires_at) = api_key.expires_at {
@@ -187,33 +223,98 @@ impl ApiKeyManager {
             return Err(AuthError::ApiKeyRevoked);
         }
         
-        // Update last used time
-        drop(api_keys);
-        let mut api_keys = self.api_keys.write().unwrap();
-        if let Some(key) = api_keys.get_mut(&api_key.id.to_string()) {
-            key.last_used_at = Some(Utc::now());
+        // Update last used time; persisted on the next flush
+        let now = Utc::now();
+        if let Some(key) = self.api_keys.write().unwrap().get_mut(&hash) {
+            key.last_used_at = Some(now);
         }
+        self.pending_last_used.lock().unwrap().insert(api_key.id, now);
         
         Ok(api_key)
     }
     
     /// Revoke an API key
-    pub fn revoke_api_key(&self, key_id: Uuid) -> Result<(), AuthError> {
-        let mut api_keys = self.api_keys.write().unwrap();
-        let api_key = api_keys.get_mut(&key_id.to_string()).ok_or(AuthError::ApiKeyNotFound)?;
+    pub async fn revoke_api_key(&self, key_id: Uuid) -> Result<(), AuthError> {
+        let mut api_key = self.repository.get_api_key(key_id).await?
+            .ok_or(AuthError::ApiKeyNotFound)?;
         
         api_key.revoked_at = Some(Utc::now());
+        self.repository.save_api_key(&api_key).await?;
+        self.invalidate(key_id);
         
         Ok(())
     }
     
     /// List API keys for a user
-    pub fn list_api_keys(&self, user_id: Uuid) -> Vec<ApiKey> {
-        let api_keys = self.api_keys.read().unwrap();
-        api_keys.values()
-            .filter(|k| k.user_id == user_id)
-            .cloned()
-            .collect()
+    pub async fn list_api_keys(&self, user_id: Uuid) -> Result<Vec<ApiKey>, AuthError> {
+        let mut api_keys = self.repository.list_api_keys(user_id).await?;
+        
+        // Include last use times not yet flushed
+        let pending = self.pending_last_used.lock().unwrap();
+        for api_key in &mut api_keys {
+            if let Some(used_at) = pending.get(&api_key.id) {
+                api_key.last_used_at = Some(*used_at);
+            }
+        }
+        
+        Ok(api_keys)
+    }
+    
+    /// Write pending last use times to the repository
+    ///
+    /// Returns the number of keys updated. On failure the pending times
+    /// are kept for the next flush.
+    pub async fn flush_last_used(&self) -> Result<usize, AuthError> {
+        let pending = std::mem::take(&mut *self.pending_last_used.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        
+        let usages: Vec<(Uuid, DateTime<Utc>)> = pending.into_iter().collect();
+        if let Err(e) = self.repository.touch_api_keys(&usages).await {
+            let mut pending = self.pending_last_used.lock().unwrap();
+            for (id, used_at) in usages {
+                let entry = pending.entry(id).or_insert(used_at);
+                if *entry < used_at {
+                    *entry = used_at;
+                }
+            }
+            return Err(e.into());
+        }
+        
+        Ok(usages.len())
+    }
+    
+    /// Periodically flush last use times in the background
+    ///
+    /// The task stops once the manager has been dropped.
+    pub fn start_last_used_flusher(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let manager = Arc::downgrade(self);
+        
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            
+            loop {
+                ticker.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                if let Err(e) = manager.flush_last_used().await {
+                    tracing::warn!("Failed to record API key usage: {}", e);
+                }
+            }
+        })
+    }
+    
+    /// Drop a cached API key
+    pub fn invalidate(&self, key_id: Uuid) {
+        self.api_keys.write().unwrap().retain(|_, k| k.id != key_id);
+    }
+    
+    /// Drop all cached API keys
+    pub fn invalidate_all(&self) {
+        self.api_keys.write().unwrap().clear();
     }
     
     /// Hash an API key
@@ -240,23 +341,38 @@ pub struct AuthService {
     /// Users (in-memory cache)
     users: RwLock<HashMap<Uuid, User>>,
     
+    /// Persistent storage
+    repository: Arc<dyn SecurityRepository>,
+    
     /// Security configuration
     config: SecurityConfig,
 }
 
 impl AuthService {
-    /// Create a new authentication service
+    /// Create a new authentication service backed by in-memory storage
     pub fn new(api_key_manager: Arc<ApiKeyManager>, encryption: Arc<EncryptionService>, config: SecurityConfig) -> Self {
         Self {
             api_key_manager,
             encryption,
             users: RwLock::new(HashMap::new()),
+            repository: Arc::new(InMemorySecurityRepository::new()),
             config,
         }
     }
     
+    /// Use a persistent repository
+    pub fn with_repository(mut self, repository: Arc<dyn SecurityRepository>) -> Self {
+        self.repository = repository;
+        self.invalidate_all();
+        self
+    }
+    
     /// Register a new user
-    pub fn register_user(&self, username: &str, email: &str, password: &str, roles: Vec<Role>) -> Result<User, AuthError> {
+    pub async fn register_user(&self, username: &str, email: &str, password: &str, roles: Vec<Role>) -> Result<User, AuthError> {
+        if self.repository.get_user_by_username(username).await?.is_some() {
+            return Err(AuthError::UserAlreadyExists(username.to_string()));
+        }
+        
         // Hash the password
         let password_hash = self.hash_password(password)?;
         
@@ -272,21 +388,32 @@ impl AuthService {
             last_login_at: None,
         };
         
-        // Store the user
-        let mut users = self.users.write().unwrap();
-        users.insert(user.id, user.clone());
+        // Store the user and their role assignments
+        self.repository.save_user(&user).await?;
+        let stored_roles = self.repository.list_roles().await?;
+        for role in &user.roles {
+            if !stored_roles.iter().any(|r| r.id == role.id) {
+                self.repository.save_role(role).await?;
+            }
+            self.repository.add_user_role(&user.id.to_string(), &role.id).await?;
+        }
+        
+        self.users.write().unwrap().insert(user.id, user.clone());
         
         Ok(user)
     }
     
     /// Authenticate a user
-    pub fn authenticate(&self, username: &str, password: &str) -> Result<User, AuthError> {
+    pub async fn authenticate(&self, username: &str, password: &str) -> Result<User, AuthError> {
         // Find the user
-        let users = self.users.read().unwrap();
-        let user = users.values()
+        let cached = self.users.read().unwrap().values()
             .find(|u| u.username == username)
-            .cloned()
-            .ok_or(AuthError::UserNotFound)?;
+            .cloned();
+        let mut user = match cached {
+            Some(user) => user,
+            None => self.repository.get_user_by_username(username).await?
+                .ok_or(AuthError::UserNotFound)?,
+        };
         
         // Verify the password
         if !self.verify_password(password, &user.password_hash)? {
@@ -294,27 +421,47 @@ impl AuthService {
         }
         
         // Update last login time
-        drop(users);
-        let mut users = self.users.write().unwrap();
-        if let Some(user) = users.get_mut(&user.id) {
-            user.last_login_at = Some(Utc::now());
-        }
+        user.last_login_at = Some(Utc::now());
+        self.repository.save_user(&user).await?;
+        self.users.write().unwrap().insert(user.id, user.clone());
         
         Ok(user)
     }
     
     /// Authenticate with API key
-    pub fn authenticate_with_api_key(&self, api_key: &str) -> Result<(User, ApiKey), AuthError> {
+    pub async fn authenticate_with_api_key(&self, api_key: &str) -> Result<(User, ApiKey), AuthError> {
         // Verify the API key
-        let api_key = self.api_key_manager.verify_api_key(api_key)?;
+        let api_key = self.api_key_manager.verify_api_key(api_key).await?;
         
         // Find the user
-        let users = self.users.read().unwrap();
-        let user = users.get(&api_key.user_id).cloned().ok_or(AuthError::UserNotFound)?;
+        let user = self.get_user(api_key.user_id).await?;
         
         Ok((user, api_key))
     }
     
+    /// Get a user by ID
+    pub async fn get_user(&self, user_id: Uuid) -> Result<User, AuthError> {
+        if let Some(user) = self.users.read().unwrap().get(&user_id).cloned() {
+            return Ok(user);
+        }
+        
+        let user = self.repository.get_user(user_id).await?
+            .ok_or(AuthError::UserNotFound)?;
+        self.users.write().unwrap().insert(user.id, user.clone());
+        
+        Ok(user)
+    }
+    
+    /// Drop a cached user, e.g. after their role assignments changed
+    pub fn invalidate_user(&self, user_id: Uuid) {
+        self.users.write().unwrap().remove(&user_id);
+    }
+    
+    /// Drop all cached users
+    pub fn invalidate_all(&self) {
+        self.users.write().unwrap().clear();
+    }
+    
     /// Hash a password
     fn hash_password(&self, password: &str) -> Result<String, AuthError> {
         use argon2::{
@@ -365,52 +512,132 @@ mod tests {
         }
     }
     
-    #[test]
-    fn test_api_key_generation_and_verification() {
+    #[tokio::test]
+    async fn test_api_key_generation_and_verification() {
         let encryption = Arc::new(EncryptionService::new("test-key").unwrap());
         let api_key_manager = ApiKeyManager::new(encryption.clone());
         
         let user_id = Uuid::new_v4();
-        let (key, api_key) = api_key_manager.generate_api_key("Test Key", user_id, None, vec![]).unwrap();
+        let (key, api_key) = api_key_manager.generate_api_key("Test Key", user_id, None, vec![]).await.unwrap();
         
         // Verify the API key
-        let verified_key = api_key_manager.verify_api_key(&key).unwrap();
+        let verified_key = api_key_manager.verify_api_key(&key).await.unwrap();
         assert_eq!(verified_key.id, api_key.id);
         assert_eq!(verified_key.user_id, user_id);
     }
     
-    #[test]
-    fn test_api_key_revocation() {
+    #[tokio::test]
+    async fn test_api_key_revocation() {
         let encryption = Arc::new(EncryptionService::new("test-key").unwrap());
         let api_key_manager = ApiKeyManager::new(encryption.clone());
         
         let user_id = Uuid::new_v4();
-        let (key, api_key) = api_key_manager.generate_api_key("Test Key", user_id, None, vec![]).unwrap();
+        let (key, api_key) = api_key_manager.generate_api_key("Test Key", user_id, None, vec![]).await.unwrap();
         
         // Revoke the API key
-        api_key_manager.revoke_api_key(api_key.id).unwrap();
+        api_key_manager.revoke_api_key(api_key.id).await.unwrap();
         
         // Verify the API key (should fail)
-        let result = api_key_manager.verify_api_key(&key);
+        let result = api_key_manager.verify_api_key(&key).await;
         assert!(matches!(result, Err(AuthError::ApiKeyRevoked)));
     }
     
-    #[test]
-    fn test_user_registration_and_authentication() {
+    #[tokio::test]
+    async fn test_user_registration_and_authentication() {
         let encryption = Arc::new(EncryptionService::new("test-key").unwrap());
         let api_key_manager = Arc::new(ApiKeyManager::new(encryption.clone()));
         let config = create_test_config();
         let auth_service = AuthService::new(api_key_manager, encryption, config);
         
         // Register a user
-        let user = auth_service.register_user("testuser", "test@example.com", "password123", vec![]).unwrap();
+        let user = auth_service.register_user("testuser", "test@example.com", "password123", vec![]).await.unwrap();
         
         // Authenticate the user
-        let authenticated_user = auth_service.authenticate("testuser", "password123").unwrap();
+        let authenticated_user = auth_service.authenticate("testuser", "password123").await.unwrap();
         assert_eq!(authenticated_user.id, user.id);
         
         // Authenticate with wrong password (should fail)
-        let result = auth_service.authenticate("testuser", "wrongpassword");
+        let result = auth_service.authenticate("testuser", "wrongpassword").await;
         assert!(matches!(result, Err(AuthError::InvalidCredentials)));
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_last_used_is_flushed_in_batches() {
+        let encryption = Arc::new(EncryptionService::new("test-key").unwrap());
+        let repository: Arc<dyn SecurityRepository> = Arc::new(InMemorySecurityRepository::new());
+        let api_key_manager = ApiKeyManager::new(encryption).with_repository(repository.clone());
+        
+        let user_id = Uuid::new_v4();
+        let (key, api_key) = api_key_manager.generate_api_key("Test Key", user_id, None, vec![]).await.unwrap();
+        let (other_key, _) = api_key_manager.generate_api_key("Other Key", user_id, None, vec![]).await.unwrap();
+        
+        api_key_manager.verify_api_key(&key).await.unwrap();
+        api_key_manager.verify_api_key(&key).await.unwrap();
+        api_key_manager.verify_api_key(&other_key).await.unwrap();
+        
+        // Not yet written to the repository
+        let stored = repository.get_api_key(api_key.id).await.unwrap().unwrap();
+        assert!(stored.last_used_at.is_none());
+        
+        // One write per key
+        assert_eq!(api_key_manager.flush_last_used().await.unwrap(), 2);
+        assert_eq!(api_key_manager.flush_last_used().await.unwrap(), 0);
+        
+        let stored = repository.get_api_key(api_key.id).await.unwrap().unwrap();
+        assert!(stored.last_used_at.is_some());
+    }
+    
+    #[tokio::test]
+    async fn test_state_persists_across_services() {
+        let encryption = Arc::new(EncryptionService::new("test-key").unwrap());
+        let repository: Arc<dyn SecurityRepository> = Arc::new(InMemorySecurityRepository::new());
+        let config = create_test_config();
+        
+        let api_key_manager = Arc::new(ApiKeyManager::new(encryption.clone()).with_repository(repository.clone()));
+        let auth_service = AuthService::new(api_key_manager.clone(), encryption.clone(), config.clone())
+            .with_repository(repository.clone());
+        let user = auth_service.register_user("testuser", "test@example.com", "password123", vec![Role::user()]).await.unwrap();
+        let (key, _) = api_key_manager.generate_api_key("Test Key", user.id, None, vec![]).await.unwrap();
+        
+        // Duplicate usernames are rejected
+        let result = auth_service.register_user("testuser", "other@example.com", "password456", vec![]).await;
+        assert!(matches!(result, Err(AuthError::UserAlreadyExists(_))));
+        
+        // A fresh service over the same repository sees the user and key
+        let api_key_manager = Arc::new(ApiKeyManager::new(encryption.clone()).with_repository(repository.clone()));
+        let auth_service = AuthService::new(api_key_manager, encryption, config)
+            .with_repository(repository);
+        let (authenticated_user, _) = auth_service.authenticate_with_api_key(&key).await.unwrap();
+        assert_eq!(authenticated_user.id, user.id);
+        assert_eq!(authenticated_user.roles, vec![Role::user()]);
+        assert!(auth_service.authenticate("testuser", "password123").await.is_ok());
+    }
+    
+    #[tokio::test]
+    async fn test_role_changes_refresh_cached_users() {
+        use crate::infrastructure::security::permissions::PermissionManager;
+        
+        let encryption = Arc::new(EncryptionService::new("test-key").unwrap());
+        let repository: Arc<dyn SecurityRepository> = Arc::new(InMemorySecurityRepository::new());
+        let api_key_manager = Arc::new(ApiKeyManager::new(encryption.clone()).with_repository(repository.clone()));
+        let auth_service = Arc::new(
+            AuthService::new(api_key_manager, encryption, create_test_config()).with_repository(repository.clone())
+        );
+        let permissions = PermissionManager::new()
+            .with_repository(repository)
+            .with_auth_service(auth_service.clone());
+        permissions.load().await.unwrap();
+        
+        let user = auth_service.register_user("testuser", "test@example.com", "password123", vec![Role::user()]).await.unwrap();
+        assert_eq!(auth_service.get_user(user.id).await.unwrap().roles, vec![Role::user()]);
+        
+        // Assigning a role drops the cached user
+        permissions.assign_role(&user.id.to_string(), "admin").await.unwrap();
+        let roles = auth_service.get_user(user.id).await.unwrap().roles;
+        assert!(roles.iter().any(|r| r.id == "admin"));
+        
+        permissions.revoke_role(&user.id.to_string(), "admin").await.unwrap();
+        let roles = auth_service.get_user(user.id).await.unwrap().roles;
+        assert!(!roles.iter().any(|r| r.id == "admin"));
+    }
+}
@@ -1,14 +1,19 @@
 //! Data encryption utilities using ring
 //!
 //! This module provides functionality for encrypting and decrypting data
-//! using the ring cryptography library.
+//! using the ring cryptography library. Keys are versioned; see
+//! `key_management` for how data keys are generated and protected.
 
 use anyhow::{Result, anyhow};
-use ring::aead::{Aad, BoundKey, Nonce, NonceSequence, OpeningKey, SealingKey, UnboundKey, CHACHA20_POLY1305};
-use ring::error::Unspecified;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
 use ring::rand::{SecureRandom, SystemRandom};
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::RwLock;
+
+/// Length of data and master keys in bytes
+pub const KEY_LEN: usize = 32;
 
 /// Encryption error types
 #[derive(Debug, thiserror::Error)]
@@ -32,47 +37,32 @@ pub enum EncryptionError {
     /// Invalid data
     #[error("Invalid data")]
     InvalidData,
+    
+    /// No key with the given ID
+    #[error("Encryption key not found: {0}")]
+    KeyNotFound(String),
+    
+    /// Key storage error
+    #[error("Key storage error: {0}")]
+    Storage(String),
 }
 
-/// Nonce sequence for encryption
-struct NonceGen {
-    /// Current nonce value
-    current: Mutex<[u8; 12]>,
-}
+/// Key ID used for data written before keys were versioned
+pub const LEGACY_KEY_ID: &str = "";
 
-impl NonceGen {
-    /// Create a new nonce generator with a random initial value
-    fn new() -> Result<Self, Unspecified> {
-        let rng = SystemRandom::new();
-        let mut nonce = [0u8; 12];
-        rng.fill(&mut nonce)?;
-        
-        Ok(Self {
-            current: Mutex::new(nonce),
-        })
-    }
-}
+/// Salt used to derive keys from key material
+const KEY_MATERIAL_SALT: &[u8] = b"digital-twin-desktop-salt";
 
-impl NonceSequence for NonceGen {
-    fn advance(&self) -> Result<Nonce, Unspecified> {
-        let mut nonce = self.current.lock().unwrap();
-        
-        // Increment the last 4 bytes as a 32-bit counter
-        let mut counter = u32::from_be_bytes([nonce[8], nonce[9], nonce[10], nonce[11]]);
-        counter = counter.wrapping_add(1);
-        let counter_bytes = counter.to_be_bytes();
-        nonce[8] = counter_bytes[0];
-        nonce[9] = counter_bytes[1];
-        nonce[10] = counter_bytes[2];
-        nonce[11] = counter_bytes[3];
-        
-        Nonce::try_assume_unique_for_key(&*nonce)
-    }
-}
+/// PBKDF2 iterations used to derive keys from key material
+const KEY_MATERIAL_ITERATIONS: u32 = 10000;
 
 /// Encrypted data
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EncryptedData {
+    /// ID of the key used for encryption (empty for legacy data)
+    #[serde(default)]
+    pub key_id: String,
+    
     /// Nonce used for encryption
     pub nonce: Vec<u8>,
     
@@ -80,69 +70,133 @@ pub struct EncryptedData {
     pub ciphertext: Vec<u8>,
 }
 
+/// Keys known to the service
+struct KeySet {
+    /// Key used for new encryptions
+    active: String,
+    
+    /// Key used for data without a key ID
+    legacy: Option<String>,
+    
+    /// Keys by ID
+    keys: HashMap<String, LessSafeKey>,
+}
+
 /// Encryption service
+///
+/// Holds a set of versioned data keys. New data is encrypted with the
+/// active key and the key ID is stored in the envelope (and bound as
+/// associated data), so older data stays readable after rotation.
 pub struct EncryptionService {
     /// Random number generator
     rng: SystemRandom,
     
-    /// Encryption key
-    key: UnboundKey,
-    
-    /// Nonce generator
-    nonce_gen: NonceGen,
+    /// Data keys
+    keys: RwLock<KeySet>,
 }
 
 impl EncryptionService {
-    /// Create a new encryption service with the given key
+    /// Create a new encryption service with a key derived from key material
     pub fn new(key_material: &str) -> Result<Self> {
-        // Derive a key from the key material
-        let key = Self::derive_key(key_material)?;
+        let key = derive_key_bytes(key_material.as_bytes(), KEY_MATERIAL_SALT, KEY_MATERIAL_ITERATIONS)?;
+        let key_id = key_fingerprint(&key);
         
-        // Create a nonce generator
-        let nonce_gen = NonceGen::new().map_err(|e| anyhow!("Failed to create nonce generator: {}", e))?;
+        let service = Self::with_key(&key_id, &key)?;
+        service.keys.write().unwrap().legacy = Some(key_id);
+        
+        Ok(service)
+    }
+    
+    /// Create a new encryption service with a single active key
+    pub fn with_key(key_id: &str, key: &[u8]) -> Result<Self> {
+        let mut keys = HashMap::new();
+        keys.insert(key_id.to_string(), Self::aead_key(key)?);
         
         Ok(Self {
             rng: SystemRandom::new(),
-            key,
-            nonce_gen,
+            keys: RwLock::new(KeySet {
+                active: key_id.to_string(),
+                legacy: None,
+                keys,
+            }),
         })
     }
     
+    /// Add a key, optionally making it the active key
+    pub fn add_key(&self, key_id: &str, key: &[u8], activate: bool) -> Result<()> {
+        let aead_key = Self::aead_key(key)?;
+        let mut keys = self.keys.write().unwrap();
+        
+        keys.keys.insert(key_id.to_string(), aead_key);
+        if activate {
+            keys.active = key_id.to_string();
+        }
+        
+        Ok(())
+    }
+    
+    /// Accept data without a key ID, decrypting it with a key derived from
+    /// the key material previously passed to [`EncryptionService::new`]
+    pub fn set_legacy_key(&self, key_material: &str) -> Result<()> {
+        let key = derive_key_bytes(key_material.as_bytes(), KEY_MATERIAL_SALT, KEY_MATERIAL_ITERATIONS)?;
+        let key_id = key_fingerprint(&key);
+        
+        self.add_key(&key_id, &key, false)?;
+        self.keys.write().unwrap().legacy = Some(key_id);
+        
+        Ok(())
+    }
+    
+    /// ID of the key used for new encryptions
+    pub fn active_key_id(&self) -> String {
+        self.keys.read().unwrap().active.clone()
+    }
+    
     /// Encrypt data
     pub fn encrypt(&self, data: &[u8]) -> Result<EncryptedData> {
-        // Create a sealing key
-        let mut sealing_key = SealingKey::new(self.key.clone(), &self.nonce_gen);
-        
-        // Encrypt the data
-        let mut in_out = data.to_vec();
-        let aad = Aad::empty();
+        let keys = self.keys.read().unwrap();
+        let key_id = keys.active.clone();
+        let key = keys.keys.get(&key_id)
+            .ok_or_else(|| EncryptionError::KeyNotFound(key_id.clone()))?;
         
-        sealing_key.seal_in_place_append_tag(aad, &mut in_out)
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng.fill(&mut nonce)
             .map_err(|e| EncryptionError::Encryption(e.to_string()))?;
         
-        // Get the nonce
-        let nonce = self.nonce_gen.current.lock().unwrap().clone();
+        // Encrypt the data, binding the key ID
+        let mut in_out = data.to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(key_id.as_bytes()),
+            &mut in_out,
+        )
+        .map_err(|e| EncryptionError::Encryption(e.to_string()))?;
         
         Ok(EncryptedData {
+            key_id,
             nonce: nonce.to_vec(),
             ciphertext: in_out,
         })
     }
     
-    /// Decrypt data
+    /// Decrypt data with the key named in the envelope
     pub fn decrypt(&self, encrypted_data: &EncryptedData) -> Result<Vec<u8>> {
-        // Create a nonce from the stored value
+        let keys = self.keys.read().unwrap();
+        
+        // Legacy data carries no key ID and no associated data
+        let (key_id, aad) = if encrypted_data.key_id == LEGACY_KEY_ID {
+            (keys.legacy.clone().unwrap_or_else(|| keys.active.clone()), Aad::empty())
+        } else {
+            (encrypted_data.key_id.clone(), Aad::from(encrypted_data.key_id.as_bytes()))
+        };
+        let key = keys.keys.get(&key_id)
+            .ok_or_else(|| EncryptionError::KeyNotFound(key_id.clone()))?;
+        
         let nonce = Nonce::try_assume_unique_for_key(&encrypted_data.nonce)
             .map_err(|e| EncryptionError::Decryption(e.to_string()))?;
         
-        // Create an opening key
-        let mut opening_key = OpeningKey::new(self.key.clone(), OneNonceSequence::new(nonce));
-        
-        // Decrypt the data
         let mut in_out = encrypted_data.ciphertext.clone();
-        let aad = Aad::empty();
-        
-        let decrypted_data = opening_key.open_in_place(aad, &mut in_out)
+        let decrypted_data = key.open_in_place(nonce, aad, &mut in_out)
             .map_err(|e| EncryptionError::Decryption(e.to_string()))?;
         
         Ok(decrypted_data.to_vec())
@@ -171,56 +225,31 @@ impl EncryptionService {
             .map_err(|e| anyhow!("Failed to convert decrypted data to string: {}", e))
     }
     
-    /// Derive a key from key material
-    fn derive_key(key_material: &str) -> Result<UnboundKey> {
-        use ring::pbkdf2;
-        
-        let salt = b"digital-twin-desktop-salt";
-        let iterations = 10000;
-        
-        let mut key = [0u8; 32]; // ChaCha20-Poly1305 uses 256-bit keys
-        
-        pbkdf2::derive(
-            pbkdf2::PBKDF2_HMAC_SHA256,
-            iterations.try_into().unwrap(),
-            salt,
-            key_material.as_bytes(),
-            &mut key,
-        );
-        
-        UnboundKey::new(&CHACHA20_POLY1305, &key)
-            .map_err(|e| EncryptionError::KeyDerivation(e.to_string()).into())
+    /// Build an AEAD key
+    fn aead_key(key: &[u8]) -> Result<LessSafeKey> {
+        let unbound = UnboundKey::new(&CHACHA20_POLY1305, key)
+            .map_err(|_| EncryptionError::InvalidKey)?;
+        Ok(LessSafeKey::new(unbound))
     }
 }
 
-/// One-time nonce sequence for decryption
-struct OneNonceSequence {
-    nonce: Nonce,
-    used: bool,
+/// Derive a 256-bit key with PBKDF2-HMAC-SHA256
+pub fn derive_key_bytes(secret: &[u8], salt: &[u8], iterations: u32) -> Result<[u8; KEY_LEN]> {
+    use ring::pbkdf2;
+    
+    let iterations = NonZeroU32::new(iterations)
+        .ok_or_else(|| EncryptionError::KeyDerivation("iterations must be positive".to_string()))?;
+    
+    let mut key = [0u8; KEY_LEN]; // ChaCha20-Poly1305 uses 256-bit keys
+    pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt, secret, &mut key);
+    
+    Ok(key)
 }
 
-impl OneNonceSequence {
-    fn new(nonce: Nonce) -> Self {
-        Self {
-            nonce,
-            used: false,
-        }
-    }
-}
-
-impl NonceSequence for OneNonceSequence {
-    fn advance(&self) -> Result<Nonce, Unspecified> {
-        if self.used {
-            return Err(Unspecified);
-        }
-        
-        let mut this = self as *const Self as *mut Self;
-        unsafe {
-            (*this).used = true;
-        }
-        
-        Ok(self.nonce.clone())
-    }
+/// Short, stable identifier for a key (not secret: a truncated SHA-256)
+pub fn key_fingerprint(key: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, key);
+    digest.as_ref()[..8].iter().map(|b| format!("{:02x}", b)).collect()
 }
 
 #[cfg(test)]
@@ -261,4 +290,52 @@ mod tests {
         let result = service2.decrypt(&encrypted);
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_key_id_selects_decryption_key() {
+        let service = EncryptionService::with_key("dek-1", &[1u8; KEY_LEN]).unwrap();
+        let old = service.encrypt(b"old").unwrap();
+        assert_eq!(old.key_id, "dek-1");
+        
+        service.add_key("dek-2", &[2u8; KEY_LEN], true).unwrap();
+        let new = service.encrypt(b"new").unwrap();
+        assert_eq!(new.key_id, "dek-2");
+        
+        // Both remain readable
+        assert_eq!(service.decrypt(&old).unwrap(), b"old");
+        assert_eq!(service.decrypt(&new).unwrap(), b"new");
+        
+        // The key ID is authenticated
+        let mut tampered = new.clone();
+        tampered.key_id = "dek-1".to_string();
+        assert!(service.decrypt(&tampered).is_err());
+        
+        let mut unknown = new;
+        unknown.key_id = "dek-9".to_string();
+        assert!(service.decrypt(&unknown).is_err());
+    }
+    
+    #[test]
+    fn test_legacy_data_is_readable() {
+        let legacy = EncryptionService::new("test-key").unwrap();
+        let mut encrypted = legacy.encrypt(b"Hello, world!").unwrap();
+        
+        // Data written before key versioning has no key ID or AAD
+        let key = derive_key_bytes(b"test-key", KEY_MATERIAL_SALT, KEY_MATERIAL_ITERATIONS).unwrap();
+        let aead = EncryptionService::aead_key(&key).unwrap();
+        let mut in_out = b"Hello, world!".to_vec();
+        aead.seal_in_place_append_tag(
+            Nonce::try_assume_unique_for_key(&encrypted.nonce).unwrap(),
+            Aad::empty(),
+            &mut in_out,
+        ).unwrap();
+        encrypted.key_id = LEGACY_KEY_ID.to_string();
+        encrypted.ciphertext = in_out;
+        
+        let service = EncryptionService::with_key("dek-1", &[1u8; KEY_LEN]).unwrap();
+        assert!(service.decrypt(&encrypted).is_err());
+        
+        service.set_legacy_key("test-key").unwrap();
+        assert_eq!(service.decrypt(&encrypted).unwrap(), b"Hello, world!");
+    }
+}
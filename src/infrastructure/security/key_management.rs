@@ -0,0 +1,501 @@
+//! Key management for the encryption service
+//!
+//! Data is encrypted with data keys (DEKs) held by `EncryptionService`.
+//! DEKs are random, versioned, and stored only in wrapped form, encrypted
+//! with a master key (KEK) loaded from the environment, a key file, a
+//! passphrase or the configured secret key. Rotating the data key adds a
+//! new active DEK; rotating the master key re-wraps every stored DEK and
+//! records the new key source with them, so the next start unlocks the
+//! store with the right key.
+
+use async_trait::async_trait;
+use base64::Engine as _;
+use chrono::{DateTime, Utc};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::path::Path;
+use std::sync::{Arc, RwLock as StdRwLock};
+use tokio::sync::Mutex;
+
+use crate::core::domain::traits::repository::RepositoryResult;
+use crate::infrastructure::config::MasterKeySource;
+use crate::infrastructure::security::encryption::{
+    derive_key_bytes, key_fingerprint, EncryptionError, EncryptionService, KEY_LEN,
+};
+
+/// Length of the passphrase salt in bytes
+const SALT_LEN: usize = 16;
+
+/// Data key wrapped with a master key
+#[derive(Debug, Clone)]
+pub struct WrappedKey {
+    /// Data key ID (stored in every envelope it encrypts)
+    pub id: String,
+
+    /// Version; the highest version is the active key
+    pub version: u32,
+
+    /// ID of the master key that wrapped this key
+    pub master_key_id: String,
+
+    /// Nonce used for wrapping
+    pub nonce: Vec<u8>,
+
+    /// Wrapped key material
+    pub ciphertext: Vec<u8>,
+
+    /// Creation date
+    pub created_at: DateTime<Utc>,
+}
+
+/// Storage for wrapped data keys
+#[async_trait]
+pub trait KeyStore: Send + Sync {
+    /// List all wrapped keys
+    async fn list_keys(&self) -> RepositoryResult<Vec<WrappedKey>>;
+
+    /// Insert or update wrapped keys atomically
+    async fn save_keys(&self, keys: &[WrappedKey]) -> RepositoryResult<()>;
+
+    /// Master key source recorded by the last master key rotation
+    async fn master_key_source(&self) -> RepositoryResult<Option<MasterKeySource>>;
+
+    /// Store keys re-wrapped with a new master key together with its source
+    /// in one transaction
+    async fn save_rewrapped_keys(&self, keys: &[WrappedKey], source: &MasterKeySource) -> RepositoryResult<()>;
+}
+
+/// In-memory key storage
+#[derive(Default)]
+pub struct InMemoryKeyStore {
+    keys: StdRwLock<Vec<WrappedKey>>,
+    source: StdRwLock<Option<MasterKeySource>>,
+}
+
+impl InMemoryKeyStore {
+    /// Create an empty key store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl KeyStore for InMemoryKeyStore {
+    async fn list_keys(&self) -> RepositoryResult<Vec<WrappedKey>> {
+        Ok(self.keys.read().unwrap().clone())
+    }
+
+    async fn save_keys(&self, keys: &[WrappedKey]) -> RepositoryResult<()> {
+        let mut stored = self.keys.write().unwrap();
+        for key in keys {
+            match stored.iter_mut().find(|k| k.id == key.id) {
+                Some(existing) => *existing = key.clone(),
+                None => stored.push(key.clone()),
+            }
+        }
+        Ok(())
+    }
+
+    async fn master_key_source(&self) -> RepositoryResult<Option<MasterKeySource>> {
+        Ok(self.source.read().unwrap().clone())
+    }
+
+    async fn save_rewrapped_keys(&self, keys: &[WrappedKey], source: &MasterKeySource) -> RepositoryResult<()> {
+        self.save_keys(keys).await?;
+        *self.source.write().unwrap() = Some(source.clone());
+        Ok(())
+    }
+}
+
+/// Master key (key-encryption key)
+pub struct MasterKey {
+    id: String,
+    key: LessSafeKey,
+}
+
+impl MasterKey {
+    /// Create a master key from raw key bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EncryptionError> {
+        if bytes.len() != KEY_LEN {
+            return Err(EncryptionError::InvalidKey);
+        }
+
+        let unbound = UnboundKey::new(&CHACHA20_POLY1305, bytes)
+            .map_err(|_| EncryptionError::InvalidKey)?;
+
+        Ok(Self {
+            id: key_fingerprint(bytes),
+            key: LessSafeKey::new(unbound),
+        })
+    }
+
+    /// Create a master key from its base64 encoding
+    pub fn from_base64(encoded: &str) -> Result<Self, EncryptionError> {
+        let bytes = base64::engine::general_purpose::STANDARD.decode(encoded.trim())
+            .map_err(|_| EncryptionError::InvalidKey)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Derive a master key from a passphrase
+    pub fn from_passphrase(passphrase: &str, salt: &[u8], iterations: u32) -> Result<Self, EncryptionError> {
+        let key = derive_key_bytes(passphrase.as_bytes(), salt, iterations)
+            .map_err(|e| EncryptionError::KeyDerivation(e.to_string()))?;
+        Self::from_bytes(&key)
+    }
+
+    /// Load the master key from its configured source
+    ///
+    /// `secret_key` is used for [`MasterKeySource::SecretKey`] and
+    /// `passphrase` for [`MasterKeySource::Passphrase`].
+    pub fn load(
+        source: &MasterKeySource,
+        secret_key: &str,
+        passphrase: Option<&str>,
+    ) -> Result<Self, EncryptionError> {
+        match source {
+            MasterKeySource::SecretKey => {
+                let key = derive_key_bytes(secret_key.as_bytes(), b"digital-twin-desktop-master-key", 100_000)
+                    .map_err(|e| EncryptionError::KeyDerivation(e.to_string()))?;
+                Self::from_bytes(&key)
+            }
+            MasterKeySource::Env { variable } => {
+                let encoded = std::env::var(variable).map_err(|_| {
+                    EncryptionError::KeyDerivation(format!("Environment variable {} is not set", variable))
+                })?;
+                Self::from_base64(&encoded)
+            }
+            MasterKeySource::File { path } => {
+                let contents = std::fs::read(path).map_err(|e| {
+                    EncryptionError::KeyDerivation(format!("Failed to read {}: {}", path.display(), e))
+                })?;
+                if contents.len() == KEY_LEN {
+                    Self::from_bytes(&contents)
+                } else {
+                    Self::from_base64(&String::from_utf8_lossy(&contents))
+                }
+            }
+            MasterKeySource::Passphrase { salt_path, iterations } => {
+                let passphrase = passphrase.ok_or_else(|| {
+                    EncryptionError::KeyDerivation("A passphrase is required to unlock the master key".to_string())
+                })?;
+                let salt = load_or_create_salt(salt_path)?;
+                Self::from_passphrase(passphrase, &salt, *iterations)
+            }
+        }
+    }
+
+    /// Master key ID (a fingerprint, safe to store)
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Wrap a data key
+    fn wrap(&self, id: &str, version: u32, key: &[u8; KEY_LEN], rng: &SystemRandom) -> Result<WrappedKey, EncryptionError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut nonce).map_err(|e| EncryptionError::Encryption(e.to_string()))?;
+
+        let mut in_out = key.to_vec();
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(id.as_bytes()), &mut in_out)
+            .map_err(|e| EncryptionError::Encryption(e.to_string()))?;
+
+        Ok(WrappedKey {
+            id: id.to_string(),
+            version,
+            master_key_id: self.id.clone(),
+            nonce: nonce.to_vec(),
+            ciphertext: in_out,
+            created_at: Utc::now(),
+        })
+    }
+
+    /// Unwrap a data key
+    fn unwrap(&self, wrapped: &WrappedKey) -> Result<[u8; KEY_LEN], EncryptionError> {
+        if wrapped.master_key_id != self.id {
+            return Err(EncryptionError::Decryption(format!(
+                "Data key {} is wrapped with master key {}, not {}",
+                wrapped.id, wrapped.master_key_id, self.id
+            )));
+        }
+
+        let nonce = Nonce::try_assume_unique_for_key(&wrapped.nonce)
+            .map_err(|e| EncryptionError::Decryption(e.to_string()))?;
+        let mut in_out = wrapped.ciphertext.clone();
+        let plaintext = self.key
+            .open_in_place(nonce, Aad::from(wrapped.id.as_bytes()), &mut in_out)
+            .map_err(|e| EncryptionError::Decryption(e.to_string()))?;
+
+        plaintext.try_into().map_err(|_| EncryptionError::InvalidData)
+    }
+}
+
+/// Read the passphrase salt, creating it on first use
+fn load_or_create_salt(path: &Path) -> Result<Vec<u8>, EncryptionError> {
+    if let Ok(salt) = std::fs::read(path) {
+        return Ok(salt);
+    }
+
+    let mut salt = [0u8; SALT_LEN];
+    SystemRandom::new().fill(&mut salt)
+        .map_err(|e| EncryptionError::KeyDerivation(e.to_string()))?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| EncryptionError::KeyDerivation(format!("Failed to create {}: {}", parent.display(), e)))?;
+    }
+    std::fs::write(path, salt)
+        .map_err(|e| EncryptionError::KeyDerivation(format!("Failed to write {}: {}", path.display(), e)))?;
+
+    Ok(salt.to_vec())
+}
+
+/// Manages data keys and the master key that protects them
+pub struct KeyManager {
+    /// Current master key; also serializes rotations
+    master: Mutex<MasterKey>,
+
+    /// Wrapped key storage
+    store: Arc<dyn KeyStore>,
+
+    /// Encryption service holding the unwrapped data keys
+    encryption: Arc<EncryptionService>,
+
+    /// Random number generator
+    rng: SystemRandom,
+}
+
+impl KeyManager {
+    /// Source of the master key that unlocks the store: the one recorded by
+    /// the last master key rotation, otherwise the configured one
+    pub async fn master_key_source(
+        store: &dyn KeyStore,
+        configured: &MasterKeySource,
+    ) -> Result<MasterKeySource, EncryptionError> {
+        let recorded = store.master_key_source().await
+            .map_err(|e| EncryptionError::Storage(e.to_string()))?;
+        Ok(recorded.unwrap_or_else(|| configured.clone()))
+    }
+
+    /// Unwrap the stored data keys, creating the first one if none exist
+    pub async fn open(master: MasterKey, store: Arc<dyn KeyStore>) -> Result<Self, EncryptionError> {
+        let rng = SystemRandom::new();
+        let mut wrapped = store.list_keys().await
+            .map_err(|e| EncryptionError::Storage(e.to_string()))?;
+
+        if wrapped.is_empty() {
+            let key = Self::generate_key(&rng)?;
+            let first = master.wrap(&data_key_id(1), 1, &key, &rng)?;
+            store.save_keys(std::slice::from_ref(&first)).await
+                .map_err(|e| EncryptionError::Storage(e.to_string()))?;
+            wrapped.push(first);
+        }
+        wrapped.sort_by_key(|k| k.version);
+
+        // The highest version is created first so it becomes the active key
+        let active = wrapped.last().expect("at least one data key");
+        let encryption = EncryptionService::with_key(&active.id, &master.unwrap(active)?)
+            .map_err(|e| EncryptionError::KeyDerivation(e.to_string()))?;
+        for key in &wrapped[..wrapped.len() - 1] {
+            encryption.add_key(&key.id, &master.unwrap(key)?, false)
+                .map_err(|e| EncryptionError::KeyDerivation(e.to_string()))?;
+        }
+
+        Ok(Self {
+            master: Mutex::new(master),
+            store,
+            encryption: Arc::new(encryption),
+            rng,
+        })
+    }
+
+    /// Encryption service using the managed data keys
+    pub fn encryption(&self) -> Arc<EncryptionService> {
+        self.encryption.clone()
+    }
+
+    /// ID of the current master key
+    pub async fn master_key_id(&self) -> String {
+        self.master.lock().await.id().to_string()
+    }
+
+    /// Create a new data key and make it active
+    ///
+    /// Existing data stays readable with the previous keys; re-encrypt it
+    /// to retire them. Returns the new key ID.
+    pub async fn rotate_data_key(&self) -> Result<String, EncryptionError> {
+        let master = self.master.lock().await;
+        let existing = self.store.list_keys().await
+            .map_err(|e| EncryptionError::Storage(e.to_string()))?;
+        let version = existing.iter().map(|k| k.version).max().unwrap_or(0) + 1;
+        let id = data_key_id(version);
+
+        let key = Self::generate_key(&self.rng)?;
+        let wrapped = master.wrap(&id, version, &key, &self.rng)?;
+        self.store.save_keys(std::slice::from_ref(&wrapped)).await
+            .map_err(|e| EncryptionError::Storage(e.to_string()))?;
+
+        self.encryption.add_key(&id, &key, true)
+            .map_err(|e| EncryptionError::KeyDerivation(e.to_string()))?;
+
+        tracing::info!("Rotated data key, now using {}", id);
+        Ok(id)
+    }
+
+    /// Re-wrap every data key with a new master key loaded from `source`
+    ///
+    /// Encrypted data is untouched. The source is stored with the re-wrapped
+    /// keys and takes precedence over the configured one on the next start
+    /// (see [`KeyManager::master_key_source`]). Returns the number of keys
+    /// re-wrapped.
+    pub async fn rotate_master_key(
+        &self,
+        new_master: MasterKey,
+        source: &MasterKeySource,
+    ) -> Result<usize, EncryptionError> {
+        let mut master = self.master.lock().await;
+        let existing = self.store.list_keys().await
+            .map_err(|e| EncryptionError::Storage(e.to_string()))?;
+
+        let mut rewrapped = Vec::with_capacity(existing.len());
+        for wrapped in &existing {
+            let key = master.unwrap(wrapped)?;
+            let mut key_rewrapped = new_master.wrap(&wrapped.id, wrapped.version, &key, &self.rng)?;
+            key_rewrapped.created_at = wrapped.created_at;
+            rewrapped.push(key_rewrapped);
+        }
+
+        self.store.save_rewrapped_keys(&rewrapped, source).await
+            .map_err(|e| EncryptionError::Storage(e.to_string()))?;
+        *master = new_master;
+
+        tracing::info!("Re-wrapped {} data keys with master key {}", rewrapped.len(), master.id());
+        Ok(rewrapped.len())
+    }
+
+    /// Generate random data key material
+    fn generate_key(rng: &SystemRandom) -> Result<[u8; KEY_LEN], EncryptionError> {
+        let mut key = [0u8; KEY_LEN];
+        rng.fill(&mut key).map_err(|e| EncryptionError::KeyDerivation(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+/// ID of a data key version
+fn data_key_id(version: u32) -> String {
+    format!("dek-{}", version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master(byte: u8) -> MasterKey {
+        MasterKey::from_bytes(&[byte; KEY_LEN]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_open_creates_and_reloads_data_key() {
+        let store: Arc<dyn KeyStore> = Arc::new(InMemoryKeyStore::new());
+
+        let manager = KeyManager::open(master(1), store.clone()).await.unwrap();
+        let encrypted = manager.encryption().encrypt(b"reading").unwrap();
+        assert_eq!(encrypted.key_id, "dek-1");
+
+        // Reopening with the same master key recovers the data key
+        let reopened = KeyManager::open(master(1), store.clone()).await.unwrap();
+        assert_eq!(reopened.encryption().decrypt(&encrypted).unwrap(), b"reading");
+
+        // A different master key cannot unwrap it
+        assert!(KeyManager::open(master(2), store).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_data_key_keeps_old_data_readable() {
+        let store: Arc<dyn KeyStore> = Arc::new(InMemoryKeyStore::new());
+        let manager = KeyManager::open(master(1), store.clone()).await.unwrap();
+        let encryption = manager.encryption();
+
+        let old = encryption.encrypt(b"old").unwrap();
+        assert_eq!(manager.rotate_data_key().await.unwrap(), "dek-2");
+        let new = encryption.encrypt(b"new").unwrap();
+        assert_eq!(new.key_id, "dek-2");
+
+        let reopened = KeyManager::open(master(1), store).await.unwrap();
+        assert_eq!(reopened.encryption().active_key_id(), "dek-2");
+        assert_eq!(reopened.encryption().decrypt(&old).unwrap(), b"old");
+        assert_eq!(reopened.encryption().decrypt(&new).unwrap(), b"new");
+    }
+
+    #[tokio::test]
+    async fn test_rotate_master_key_rewraps_data_keys() {
+        let store: Arc<dyn KeyStore> = Arc::new(InMemoryKeyStore::new());
+        let manager = KeyManager::open(master(1), store.clone()).await.unwrap();
+        manager.rotate_data_key().await.unwrap();
+        let encrypted = manager.encryption().encrypt(b"secret").unwrap();
+
+        let source = MasterKeySource::Env { variable: "NEW_MASTER_KEY".to_string() };
+        assert_eq!(manager.rotate_master_key(master(2), &source).await.unwrap(), 2);
+        assert_eq!(manager.master_key_id().await, master(2).id());
+
+        // Only the new master key opens the store now
+        assert!(KeyManager::open(master(1), store.clone()).await.is_err());
+        let reopened = KeyManager::open(master(2), store).await.unwrap();
+        assert_eq!(reopened.encryption().decrypt(&encrypted).unwrap(), b"secret");
+    }
+
+    #[tokio::test]
+    async fn test_reopen_after_master_key_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn KeyStore> = Arc::new(InMemoryKeyStore::new());
+        let configured = MasterKeySource::SecretKey;
+
+        let source = KeyManager::master_key_source(store.as_ref(), &configured).await.unwrap();
+        let manager = KeyManager::open(MasterKey::load(&source, "secret", None).unwrap(), store.clone())
+            .await
+            .unwrap();
+        let encrypted = manager.encryption().encrypt(b"secret").unwrap();
+
+        let path = dir.path().join("master.key");
+        std::fs::write(&path, [9u8; KEY_LEN]).unwrap();
+        let new_source = MasterKeySource::File { path };
+        manager.rotate_master_key(MasterKey::load(&new_source, "secret", None).unwrap(), &new_source)
+            .await
+            .unwrap();
+
+        // The next start unlocks with the rotated key although the
+        // configuration still names the old source
+        let source = KeyManager::master_key_source(store.as_ref(), &configured).await.unwrap();
+        assert!(matches!(source, MasterKeySource::File { .. }));
+        let reopened = KeyManager::open(MasterKey::load(&source, "secret", None).unwrap(), store)
+            .await
+            .unwrap();
+        assert_eq!(reopened.encryption().decrypt(&encrypted).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_passphrase_master_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = MasterKeySource::Passphrase {
+            salt_path: dir.path().join("master.salt"),
+            iterations: 1000,
+        };
+
+        let first = MasterKey::load(&source, "", Some("correct horse")).unwrap();
+        let second = MasterKey::load(&source, "", Some("correct horse")).unwrap();
+        let other = MasterKey::load(&source, "", Some("battery staple")).unwrap();
+
+        assert_eq!(first.id(), second.id());
+        assert_ne!(first.id(), other.id());
+        assert!(MasterKey::load(&source, "", None).is_err());
+    }
+
+    #[test]
+    fn test_file_master_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("master.key");
+        std::fs::write(&path, base64::engine::general_purpose::STANDARD.encode([7u8; KEY_LEN])).unwrap();
+
+        let key = MasterKey::load(&MasterKeySource::File { path }, "", None).unwrap();
+        assert_eq!(key.id(), master(7).id());
+    }
+}
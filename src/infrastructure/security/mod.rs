@@ -1,19 +1,39 @@
 //! Security module for the Digital Twin Desktop application
 //! 
 //! This module provides authentication, encryption, rate limiting,
-//! sandboxed execution, and permission management functionality.
+//! sandboxed execution, permission management, key management,
+//! credential vault and audit logging functionality.
 
+mod audit;
 mod auth;
 mod encryption;
+mod key_management;
 mod rate_limiter;
 mod sandbox;
 mod permissions;
+mod store;
+mod vault;
 
-pub use auth::{ApiKeyManager, AuthService, AuthError};
-pub use encryption::{EncryptionService, EncryptionError};
-pub use rate_limiter::{RateLimiter, RateLimitError};
-pub use sandbox::{Sandbox, SandboxError};
-pub use permissions::{PermissionManager, Permission, Role, PermissionError};
+pub use audit::{
+    AuditCategory, AuditEntry, AuditError, AuditEvent, AuditLog, AuditOutcome, AuditResult,
+    AuditStore, AuditVerification, InMemoryAuditStore, GENESIS_HASH,
+};
+pub use auth::{ApiKey, ApiKeyManager, AuthService, AuthError, User};
+pub use encryption::{
+    derive_key_bytes, key_fingerprint, EncryptedData, EncryptionService, EncryptionError, KEY_LEN,
+    LEGACY_KEY_ID,
+};
+pub use key_management::{InMemoryKeyStore, KeyManager, KeyStore, MasterKey, WrappedKey};
+pub use rate_limiter::{RateLimiter, RateLimitError, RateLimitInfo, RateLimitKey};
+pub use sandbox::{
+    CommandOptions, ExecutionResult, ExecutionStats, IsolationConfig, Sandbox, SandboxConfig, SandboxError,
+};
+pub use permissions::{PermissionManager, Permission, Role, PermissionError, UserPermissions};
+pub use store::{SecurityRepository, InMemorySecurityRepository};
+pub use vault::{
+    CredentialVault, Credentials, InMemorySecretRepository, SecretMetadata, SecretRecord,
+    SecretRepository, VaultError, VaultResult,
+};
 
 /// Initialize the security module
 pub fn init() -> anyhow::Result<()> {
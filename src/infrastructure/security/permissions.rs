@@ -3,14 +3,19 @@
 //! This module provides functionality for managing permissions,
 //! roles, and access control for the Digital Twin Desktop application.
 
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 use thiserror::Error;
+
 use uuid::Uuid;
 
+use crate::core::domain::traits::repository::RepositoryError;
+use crate::infrastructure::security::auth::AuthService;
+use crate::infrastructure::security::store::{InMemorySecurityRepository, SecurityRepository};
+
 /// Permission error types
 #[derive(Debug, Error)]
 pub enum PermissionError {
@@ -108,6 +113,16 @@ impl Permission {
         )
     }
     
+    /// Create a permission for deciding on tool operations held for approval
+    pub fn approve_tools() -> Self {
+        Self::new(
+            "tool",
+            "approve",
+            "Approve tool operations",
+            "Permission to approve or reject held tool operations",
+        )
+    }
+    
     /// Create a permission for administering the system
     pub fn admin() -> Self {
         Self::new(
@@ -197,6 +212,16 @@ impl Role {
             ],
         )
     }
+    
+    /// Create an approver role for deciding on held tool operations
+    pub fn approver() -> Self {
+        Self::new(
+            "approver",
+            "Approver",
+            "Approve or reject tool operations held for approval",
+            vec![Permission::approve_tools().id],
+        )
+    }
 }
 
 /// User permissions
@@ -254,6 +279,11 @@ impl UserPermissions {
 }
 
 /// Permission manager
+///
+/// Roles and permissions are held in memory; user assignments and
+/// resource grants are loaded lazily from the security repository and
+/// cached until invalidated. All changes are written through to the
+/// repository before the cache is updated.
 pub struct PermissionManager {
     /// Available permissions
     permissions: RwLock<HashMap<String, Permission>>,
@@ -261,21 +291,63 @@ pub struct PermissionManager {
     /// Available roles
     roles: RwLock<HashMap<String, Role>>,
     
-    /// User permissions
+    /// User permissions (cache)
     user_permissions: DashMap<String, UserPermissions>,
     
-    /// Resource permissions
+    /// Resource permissions (cache)
     resource_permissions: DashMap<String, HashSet<String>>,
+    
+    /// Persistent storage
+    repository: Arc<dyn SecurityRepository>,
+    
+    /// Authentication service whose cached users carry role assignments
+    auth_service: Option<Arc<AuthService>>,
 }
 
 impl PermissionManager {
-    /// Create a new permission manager
+    /// Create a new permission manager backed by in-memory storage
     pub fn new() -> Self {
         let mut permissions = HashMap::new();
         let mut roles = HashMap::new();
         
-        // Add default permissions
-        let default_permissions = vec![
+        for permission in Self::default_permissions() {
+            permissions.insert(permission.id.clone(), permission);
+        }
+        
+        for role in Self::default_roles() {
+            roles.insert(role.id.clone(), role);
+        }
+        
+        Self {
+            permissions: RwLock::new(permissions),
+            roles: RwLock::new(roles),
+            user_permissions: DashMap::new(),
+            resource_permissions: DashMap::new(),
+            repository: Arc::new(InMemorySecurityRepository::new()),
+            auth_service: None,
+        }
+    }
+    
+    /// Use a persistent repository
+    ///
+    /// Call [`PermissionManager::load`] afterwards to seed the default
+    /// roles and permissions and pick up those already stored.
+    pub fn with_repository(mut self, repository: Arc<dyn SecurityRepository>) -> Self {
+        self.repository = repository;
+        self.invalidate_all();
+        self
+    }
+    
+    /// Drop the users cached by the authentication service whenever role
+    /// assignments or role definitions change
+    pub fn with_auth_service(mut self, auth_service: Arc<AuthService>) -> Self {
+        self.auth_service = Some(auth_service);
+        self
+    }
+    
+    /// Default permissions
+    fn default_permissions() -> Vec<Permission> {
+        vec![
             Permission::admin(),
             Permission::read("twin"),
             Permission::write("twin"),
@@ -292,231 +364,306 @@ impl PermissionManager {
             Permission::execute_tool("modbus_tool"),
             Permission::execute_tool("mqtt_tool"),
             Permission::execute_tool("twin_tool"),
-        ];
-        
-        for permission in default_permissions {
-            permissions.insert(permission.id.clone(), permission);
-        }
-        
-        // Add default roles
-        let default_roles = vec![
+            Permission::approve_tools(),
+        ]
+    }
+    
+    /// Default roles
+    fn default_roles() -> Vec<Role> {
+        vec![
             Role::admin(),
             Role::user(),
             Role::guest(),
             Role::tool_user(),
-        ];
+            Role::approver(),
+        ]
+    }
+    
+    /// Load roles and permissions from the repository
+    ///
+    /// Default roles and permissions missing from the repository are
+    /// stored first, so stored customisations take precedence.
+    pub async fn load(&self) -> Result<()> {
+        let stored_permissions = self.repository.list_permissions().await.map_err(Self::storage_error)?;
+        for permission in Self::default_permissions() {
+            if !stored_permissions.iter().any(|p| p.id == permission.id) {
+                self.repository.save_permission(&permission).await.map_err(Self::storage_error)?;
+            }
+        }
         
-        for role in default_roles {
-            roles.insert(role.id.clone(), role);
+        let stored_roles = self.repository.list_roles().await.map_err(Self::storage_error)?;
+        for role in Self::default_roles() {
+            if !stored_roles.iter().any(|r| r.id == role.id) {
+                self.repository.save_role(&role).await.map_err(Self::storage_error)?;
+            }
         }
         
-        Self {
-            permissions: RwLock::new(permissions),
-            roles: RwLock::new(roles),
-            user_permissions: DashMap::new(),
-            resource_permissions: DashMap::new(),
+        self.reload().await
+    }
+    
+    /// Reload roles and permissions and drop cached assignments
+    pub async fn reload(&self) -> Result<()> {
+        let permissions = self.repository.list_permissions().await.map_err(Self::storage_error)?;
+        let roles = self.repository.list_roles().await.map_err(Self::storage_error)?;
+        
+        {
+            let mut cached = self.permissions.write().unwrap();
+            for permission in permissions {
+                cached.insert(permission.id.clone(), permission);
+            }
+        }
+        {
+            let mut cached = self.roles.write().unwrap();
+            for role in roles {
+                cached.insert(role.id.clone(), role);
+            }
+        }
+        
+        self.invalidate_all();
+        Ok(())
+    }
+    
+    /// Drop the cached assignments of a user
+    pub fn invalidate_user(&self, user_id: &str) {
+        self.user_permissions.remove(user_id);
+        if let (Some(auth_service), Ok(id)) = (&self.auth_service, Uuid::parse_str(user_id)) {
+            auth_service.invalidate_user(id);
+        }
+    }
+    
+    /// Drop the cached grants of a resource
+    pub fn invalidate_resource(&self, resource_type: &str, resource_id: &str) {
+        self.resource_permissions.remove(&format!("{}:{}", resource_type, resource_id));
+    }
+    
+    /// Drop all cached assignments and grants
+    pub fn invalidate_all(&self) {
+        self.user_permissions.clear();
+        self.resource_permissions.clear();
+        if let Some(auth_service) = &self.auth_service {
+            auth_service.invalidate_all();
         }
     }
     
     /// Register a new permission
-    pub fn register_permission(&self, permission: Permission) -> Result<()> {
+    pub async fn register_permission(&self, permission: Permission) -> Result<()> {
+        self.repository.save_permission(&permission).await.map_err(Self::storage_error)?;
         let mut permissions = self.permissions.write().unwrap();
         permissions.insert(permission.id.clone(), permission);
         Ok(())
     }
     
-    /// Register a new role
-    pub fn register_role(&self, role: Role) -> Result<()> {
-        let mut roles = self.roles.write().unwrap();
-        roles.insert(role.id.clone(), role);
+    /// Register a new role or update an existing one
+    pub async fn register_role(&self, role: Role) -> Result<()> {
+        self.repository.save_role(&role).await.map_err(Self::storage_error)?;
+        self.roles.write().unwrap().insert(role.id.clone(), role);
+        // Cached users hold copies of the role definitions
+        self.invalidate_all();
         Ok(())
     }
     
     /// Assign a role to a user
-    pub fn assign_role(&self, user_id: &str, role_id: &str) -> Result<()> {
+    pub async fn assign_role(&self, user_id: &str, role_id: &str) -> Result<()> {
         // Check if the role exists
-        let roles = self.roles.read().unwrap();
-        if !roles.contains_key(role_id) {
+        if !self.roles.read().unwrap().contains_key(role_id) {
             return Err(PermissionError::RoleNotFound(role_id.to_string()).into());
         }
         
-        // Get or create user permissions
-        let mut user_perms = self.user_permissions
-            .entry(user_id.to_string())
-            .or_insert_with(|| UserPermissions::new(user_id));
-        
-        user_perms.add_role(role_id);
+        self.repository.add_user_role(user_id, role_id).await.map_err(Self::storage_error)?;
+        self.invalidate_user(user_id);
         
         Ok(())
     }
     
     /// Revoke a role from a user
-    pub fn revoke_role(&self, user_id: &str, role_id: &str) -> Result<()> {
-        if let Some(mut user_perms) = self.user_permissions.get_mut(user_id) {
-            user_perms.remove_role(role_id);
-            Ok(())
-        } else {
-            Err(PermissionError::UserNotFound(user_id.to_string()).into())
+    pub async fn revoke_role(&self, user_id: &str, role_id: &str) -> Result<()> {
+        if self.user_entry(user_id).await?.is_none() {
+            return Err(PermissionError::UserNotFound(user_id.to_string()).into());
         }
+        
+        self.repository.remove_user_role(user_id, role_id).await.map_err(Self::storage_error)?;
+        self.invalidate_user(user_id);
+        
+        Ok(())
     }
     
     /// Grant a permission to a user
-    pub fn grant_permission(&self, user_id: &str, permission_id: &str) -> Result<()> {
+    pub async fn grant_permission(&self, user_id: &str, permission_id: &str) -> Result<()> {
         // Check if the permission exists
-        let permissions = self.permissions.read().unwrap();
-        if !permissions.contains_key(permission_id) {
+        if !self.permissions.read().unwrap().contains_key(permission_id) {
             return Err(PermissionError::PermissionNotFound(permission_id.to_string()).into());
         }
         
-        // Get or create user permissions
-        let mut user_perms = self.user_permissions
-            .entry(user_id.to_string())
-            .or_insert_with(|| UserPermissions::new(user_id));
-        
-        user_perms.add_permission(permission_id);
+        self.repository.add_user_permission(user_id, permission_id).await.map_err(Self::storage_error)?;
+        self.invalidate_user(user_id);
         
         Ok(())
     }
     
     /// Revoke a permission from a user
-    pub fn revoke_permission(&self, user_id: &str, permission_id: &str) -> Result<()> {
-        if let Some(mut user_perms) = self.user_permissions.get_mut(user_id) {
-            user_perms.remove_permission(permission_id);
-            Ok(())
-        } else {
-            Err(PermissionError::UserNotFound(user_id.to_string()).into())
+    pub async fn revoke_permission(&self, user_id: &str, permission_id: &str) -> Result<()> {
+        if self.user_entry(user_id).await?.is_none() {
+            return Err(PermissionError::UserNotFound(user_id.to_string()).into());
         }
+        
+        self.repository.remove_user_permission(user_id, permission_id).await.map_err(Self::storage_error)?;
+        self.invalidate_user(user_id);
+        
+        Ok(())
     }
     
     /// Grant permission to execute a tool
-    pub fn grant_tool_permission(&self, user_id: &str, tool_id: &str) -> Result<()> {
+    pub async fn grant_tool_permission(&self, user_id: &str, tool_id: &str) -> Result<()> {
         let permission_id = format!("tool:{}", tool_id);
-        self.grant_permission(user_id, &permission_id)
+        self.grant_permission(user_id, &permission_id).await
     }
     
     /// Check if a user has a permission
-    pub fn has_permission(&self, user_id: &str, permission_id: &str) -> bool {
+    pub async fn has_permission(&self, user_id: &str, permission_id: &str) -> bool {
+        let user_perms = match self.user_entry(user_id).await {
+            Ok(Some(user_perms)) => user_perms,
+            Ok(None) => return false,
+            Err(e) => {
+                tracing::warn!("Failed to load permissions for user {}: {}", user_id, e);
+                return false;
+            }
+        };
+        
         // Check if the user has the admin permission
-        if self.has_admin_permission(user_id) {
+        if Self::is_admin(&user_perms) {
             return true;
         }
         
         // Check if the user has the permission directly
-        if let Some(user_perms) = self.user_permissions.get(user_id) {
-            if user_perms.has_direct_permission(permission_id) {
-                return true;
-            }
-            
-            // Check if the user has the permission through a role
-            let roles = self.roles.read().unwrap();
-            for role_id in &user_perms.roles {
-                if let Some(role) = roles.get(role_id) {
-                    if role.permissions.contains(&permission_id.to_string()) {
-                        return true;
-                    }
-                }
-            }
+        if user_perms.has_direct_permission(permission_id) {
+            return true;
         }
         
-        false
+        // Check if the user has the permission through a role
+        let roles = self.roles.read().unwrap();
+        user_perms.roles.iter()
+            .filter_map(|role_id| roles.get(role_id))
+            .any(|role| role.permissions.iter().any(|p| p == permission_id))
     }
     
     /// Check if a user has admin permission
-    pub fn has_admin_permission(&self, user_id: &str) -> bool {
-        if let Some(user_perms) = self.user_permissions.get(user_id) {
-            // Check for direct admin permission
-            if user_perms.has_direct_permission("system:admin") {
-                return true;
-            }
-            
-            // Check for admin role
-            if user_perms.has_role("admin") {
-                return true;
+    pub async fn has_admin_permission(&self, user_id: &str) -> bool {
+        match self.user_entry(user_id).await {
+            Ok(Some(user_perms)) => Self::is_admin(&user_perms),
+            Ok(None) => false,
+            Err(e) => {
+                tracing::warn!("Failed to load permissions for user {}: {}", user_id, e);
+                false
             }
         }
-        
-        false
     }
     
     /// Check if a user can execute a tool
-    pub fn can_execute_tool(&self, user_id: &str, tool_id: &str) -> bool {
+    pub async fn can_execute_tool(&self, user_id: &str, tool_id: &str) -> bool {
         let permission_id = format!("tool:{}", tool_id);
-        self.has_permission(user_id, &permission_id)
+        self.has_permission(user_id, &permission_id).await
     }
     
     /// Check if a user can access a resource
-    pub fn can_access_resource(&self, user_id: &str, resource_type: &str, resource_id: &str, action: &str) -> bool {
+    pub async fn can_access_resource(&self, user_id: &str, resource_type: &str, resource_id: &str, action: &str) -> bool {
         // Check for general permission
         let general_permission_id = format!("{}:{}", resource_type, action);
-        if self.has_permission(user_id, &general_permission_id) {
+        if self.has_permission(user_id, &general_permission_id).await {
             return true;
         }
         
         // Check for specific resource permission
         let resource_key = format!("{}:{}", resource_type, resource_id);
-        if let Some(allowed_users) = self.resource_permissions.get(&resource_key) {
-            if allowed_users.contains(user_id) {
-                return true;
+        match self.resource_entry(&resource_key).await {
+            Ok(allowed_users) => allowed_users.contains(user_id),
+            Err(e) => {
+                tracing::warn!("Failed to load grants for resource {}: {}", resource_key, e);
+                false
             }
         }
-        
-        false
     }
     
     /// Grant permission to access a specific resource
-    pub fn grant_resource_permission(&self, user_id: &str, resource_type: &str, resource_id: &str) -> Result<()> {
+    pub async fn grant_resource_permission(&self, user_id: &str, resource_type: &str, resource_id: &str) -> Result<()> {
         let resource_key = format!("{}:{}", resource_type, resource_id);
         
-        let mut allowed_users = self.resource_permissions
-            .entry(resource_key)
-            .or_insert_with(HashSet::new);
-        
-        allowed_users.insert(user_id.to_string());
+        self.repository.add_resource_grant(&resource_key, user_id).await.map_err(Self::storage_error)?;
+        self.resource_permissions.remove(&resource_key);
         
         Ok(())
     }
     
     /// Revoke permission to access a specific resource
-    pub fn revoke_resource_permission(&self, user_id: &str, resource_type: &str, resource_id: &str) -> Result<()> {
+    pub async fn revoke_resource_permission(&self, user_id: &str, resource_type: &str, resource_id: &str) -> Result<()> {
         let resource_key = format!("{}:{}", resource_type, resource_id);
         
-        if let Some(mut allowed_users) = self.resource_permissions.get_mut(&resource_key) {
-            allowed_users.remove(user_id);
-            Ok(())
-        } else {
-            Ok(()) // Resource doesn't exist, so no permissions to revoke
-        }
+        self.repository.remove_resource_grant(&resource_key, user_id).await.map_err(Self::storage_error)?;
+        self.resource_permissions.remove(&resource_key);
+        
+        Ok(())
     }
     
     /// Get all permissions for a user
-    pub fn get_user_permissions(&self, user_id: &str) -> Result<HashSet<String>> {
-        let mut all_permissions = HashSet::new();
+    pub async fn get_user_permissions(&self, user_id: &str) -> Result<HashSet<String>> {
+        let user_perms = self.user_entry(user_id).await?
+            .ok_or_else(|| PermissionError::UserNotFound(user_id.to_string()))?;
         
-        if let Some(user_perms) = self.user_permissions.get(user_id) {
-            // Add direct permissions
-            all_permissions.extend(user_perms.permissions.clone());
-            
-            // Add permissions from roles
-            let roles = self.roles.read().unwrap();
-            for role_id in &user_perms.roles {
-                if let Some(role) = roles.get(role_id) {
-                    all_permissions.extend(role.permissions.clone());
-                }
+        // Add direct permissions
+        let mut all_permissions = user_perms.permissions.clone();
+        
+        // Add permissions from roles
+        let roles = self.roles.read().unwrap();
+        for role_id in &user_perms.roles {
+            if let Some(role) = roles.get(role_id) {
+                all_permissions.extend(role.permissions.clone());
             }
-            
-            Ok(all_permissions)
-        } else {
-            Err(PermissionError::UserNotFound(user_id.to_string()).into())
         }
+        
+        Ok(all_permissions)
     }
     
     /// Get all roles for a user
-    pub fn get_user_roles(&self, user_id: &str) -> Result<HashSet<String>> {
+    pub async fn get_user_roles(&self, user_id: &str) -> Result<HashSet<String>> {
+        let user_perms = self.user_entry(user_id).await?
+            .ok_or_else(|| PermissionError::UserNotFound(user_id.to_string()))?;
+        
+        Ok(user_perms.roles)
+    }
+    
+    /// Get the assignments of a user from the cache or the repository
+    async fn user_entry(&self, user_id: &str) -> Result<Option<UserPermissions>> {
         if let Some(user_perms) = self.user_permissions.get(user_id) {
-            Ok(user_perms.roles.clone())
-        } else {
-            Err(PermissionError::UserNotFound(user_id.to_string()).into())
+            return Ok(Some(user_perms.clone()));
         }
+        
+        let loaded = self.repository.get_user_permissions(user_id).await.map_err(Self::storage_error)?;
+        if let Some(user_perms) = &loaded {
+            self.user_permissions.insert(user_id.to_string(), user_perms.clone());
+        }
+        
+        Ok(loaded)
+    }
+    
+    /// Get the grants of a resource from the cache or the repository
+    async fn resource_entry(&self, resource_key: &str) -> Result<HashSet<String>> {
+        if let Some(allowed_users) = self.resource_permissions.get(resource_key) {
+            return Ok(allowed_users.clone());
+        }
+        
+        let loaded = self.repository.get_resource_grants(resource_key).await.map_err(Self::storage_error)?;
+        self.resource_permissions.insert(resource_key.to_string(), loaded.clone());
+        
+        Ok(loaded)
+    }
+    
+    /// Check assignments for the admin role or permission
+    fn is_admin(user_perms: &UserPermissions) -> bool {
+        user_perms.has_direct_permission("system:admin") || user_perms.has_role("admin")
+    }
+    
+    /// Map a repository error
+    fn storage_error(err: RepositoryError) -> anyhow::Error {
+        PermissionError::Internal(err.to_string()).into()
     }
 }
 
@@ -546,61 +693,84 @@ mod tests {
         assert!(role.permissions.contains(&"twin:read".to_string()));
     }
     
-    #[test]
-    fn test_permission_assignment() {
+    #[tokio::test]
+    async fn test_permission_assignment() {
         let manager = PermissionManager::new();
         
         // Assign roles
-        manager.assign_role("user1", "user").unwrap();
-        manager.assign_role("admin1", "admin").unwrap();
+        manager.assign_role("user1", "user").await.unwrap();
+        manager.assign_role("admin1", "admin").await.unwrap();
         
         // Grant direct permissions
-        manager.grant_permission("user1", "twin:read").unwrap();
+        manager.grant_permission("user1", "twin:read").await.unwrap();
         
         // Check permissions
-        assert!(manager.has_permission("user1", "twin:read"));
-        assert!(manager.has_permission("user1", "conversation:read")); // From user role
-        assert!(!manager.has_permission("user1", "twin:delete")); // Not granted
+        assert!(manager.has_permission("user1", "twin:read").await);
+        assert!(manager.has_permission("user1", "conversation:read").await); // From user role
+        assert!(!manager.has_permission("user1", "twin:delete").await); // Not granted
         
-        assert!(manager.has_permission("admin1", "twin:delete")); // Admin has all permissions
-        assert!(manager.has_admin_permission("admin1"));
-        assert!(!manager.has_admin_permission("user1"));
+        assert!(manager.has_permission("admin1", "twin:delete").await); // Admin has all permissions
+        assert!(manager.has_admin_permission("admin1").await);
+        assert!(!manager.has_admin_permission("user1").await);
     }
     
-    #[test]
-    fn test_tool_permissions() {
+    #[tokio::test]
+    async fn test_tool_permissions() {
         let manager = PermissionManager::new();
         
         // Grant tool permission
-        manager.grant_tool_permission("user1", "file_tool").unwrap();
+        manager.grant_tool_permission("user1", "file_tool").await.unwrap();
         
         // Check permissions
-        assert!(manager.can_execute_tool("user1", "file_tool"));
-        assert!(!manager.can_execute_tool("user1", "dangerous_tool"));
+        assert!(manager.can_execute_tool("user1", "file_tool").await);
+        assert!(!manager.can_execute_tool("user1", "dangerous_tool").await);
         
         // Assign tool user role
-        manager.assign_role("user2", "tool_user").unwrap();
+        manager.assign_role("user2", "tool_user").await.unwrap();
         
         // Check permissions
-        assert!(manager.can_execute_tool("user2", "file_tool"));
-        assert!(manager.can_execute_tool("user2", "web_tool"));
+        assert!(manager.can_execute_tool("user2", "file_tool").await);
+        assert!(manager.can_execute_tool("user2", "web_tool").await);
     }
     
-    #[test]
-    fn test_resource_permissions() {
+    #[tokio::test]
+    async fn test_resource_permissions() {
         let manager = PermissionManager::new();
         
         // Grant general permission
-        manager.grant_permission("user1", "twin:read").unwrap();
+        manager.grant_permission("user1", "twin:read").await.unwrap();
         
         // Grant specific resource permission
-        manager.grant_resource_permission("user2", "twin", "twin123").unwrap();
+        manager.grant_resource_permission("user2", "twin", "twin123").await.unwrap();
         
         // Check permissions
-        assert!(manager.can_access_resource("user1", "twin", "twin123", "read"));
-        assert!(manager.can_access_resource("user1", "twin", "twin456", "read")); // Any twin
+        assert!(manager.can_access_resource("user1", "twin", "twin123", "read").await);
+        assert!(manager.can_access_resource("user1", "twin", "twin456", "read").await); // Any twin
         
-        assert!(manager.can_access_resource("user2", "twin", "twin123", "read")); // Specific twin
-        assert!(!manager.can_access_resource("user2", "twin", "twin456", "read")); // Different twin
+        assert!(manager.can_access_resource("user2", "twin", "twin123", "read").await); // Specific twin
+        assert!(!manager.can_access_resource("user2", "twin", "twin456", "read").await); // Different twin
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_assignments_persist_across_managers() {
+        let repository: Arc<dyn SecurityRepository> = Arc::new(InMemorySecurityRepository::new());
+        
+        let manager = PermissionManager::new().with_repository(repository.clone());
+        manager.load().await.unwrap();
+        manager.register_role(Role::new("viewer", "Viewer", "Can view twins", vec!["twin:read".to_string()])).await.unwrap();
+        manager.assign_role("user1", "viewer").await.unwrap();
+        manager.grant_resource_permission("user2", "twin", "twin123").await.unwrap();
+        
+        // A fresh manager sees the stored role, assignment and grant
+        let reloaded = PermissionManager::new().with_repository(repository.clone());
+        reloaded.load().await.unwrap();
+        assert!(reloaded.has_permission("user1", "twin:read").await);
+        assert!(reloaded.can_access_resource("user2", "twin", "twin123", "read").await);
+        
+        // Changes made through one manager are visible after invalidation
+        manager.revoke_role("user1", "viewer").await.unwrap();
+        assert!(reloaded.has_permission("user1", "twin:read").await); // Still cached
+        reloaded.invalidate_user("user1");
+        assert!(!reloaded.has_permission("user1", "twin:read").await);
+    }
+}
@@ -1,17 +1,21 @@
 //! Rate limiting for API calls
 //!
 //! This module provides functionality for rate limiting API calls
-//! to prevent abuse and ensure fair usage of resources.
+//! to prevent abuse and ensure fair usage of resources. Limits are
+//! enforced with a token bucket, a sliding log or a sliding window
+//! counter, selected by `RateLimitConfig.algorithm`.
 
 use anyhow::Result;
 use chrono::{DateTime, Duration, Utc};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::net::IpAddr;
 use std::sync::Arc;
 use thiserror::Error;
+use tokio::task::JoinHandle;
 
-use crate::infrastructure::config::SecurityConfig;
+use crate::infrastructure::config::{KeyRateLimit, RateLimitAlgorithm, RateLimitConfig, SecurityConfig};
 
 /// Rate limit error types
 #[derive(Debug, Error)]
@@ -68,96 +72,291 @@ impl ToString for RateLimitKey {
     }
 }
 
+/// Limit applied to one key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Limit {
+    /// Number of requests allowed in the window
+    requests: u32,
+    
+    /// Time window in seconds
+    window_seconds: u64,
+    
+    /// Burst allowance
+    burst_size: Option<u32>,
+}
+
+impl Limit {
+    fn window(&self) -> Duration {
+        Duration::seconds(self.window_seconds.max(1) as i64)
+    }
+}
+
+impl From<&KeyRateLimit> for Limit {
+    fn from(limit: &KeyRateLimit) -> Self {
+        Self {
+            requests: limit.requests,
+            window_seconds: limit.window_seconds,
+            burst_size: limit.burst_size,
+        }
+    }
+}
+
+/// Algorithm-specific bucket state
+#[derive(Debug, Clone)]
+enum BucketState {
+    /// Tokens available and when they were last refilled
+    TokenBucket {
+        tokens: f64,
+        last_refill: DateTime<Utc>,
+    },
+    
+    /// Times of requests within the window
+    SlidingLog {
+        requests: VecDeque<DateTime<Utc>>,
+    },
+    
+    /// Request counts of the current and previous fixed windows
+    SlidingWindowCounter {
+        window_start: DateTime<Utc>,
+        current: u32,
+        previous: u32,
+    },
+}
+
 /// Rate limit bucket
 #[derive(Debug, Clone)]
 struct RateLimitBucket {
-    /// Number of requests allowed in the window
-    limit: u32,
+    /// Limit applied to the bucket
+    limit: Limit,
     
-    /// Number of requests made in the current window
-    count: u32,
+    /// Algorithm state
+    state: BucketState,
     
-    /// Start time of the current window
-    window_start: DateTime<Utc>,
+    /// Start of the current one-second burst window (sliding algorithms)
+    burst_start: DateTime<Utc>,
     
-    /// Time window in seconds
-    window_seconds: u64,
+    /// Requests made in the current burst window
+    burst_count: u32,
+    
+    /// Time of the last request, used for idle eviction
+    last_seen: DateTime<Utc>,
 }
 
 impl RateLimitBucket {
     /// Create a new rate limit bucket
-    fn new(limit: u32, window_seconds: u64) -> Self {
+    fn new(limit: Limit, algorithm: RateLimitAlgorithm, now: DateTime<Utc>) -> Self {
+        let state = match algorithm {
+            RateLimitAlgorithm::TokenBucket => BucketState::TokenBucket {
+                tokens: Self::capacity(&limit),
+                last_refill: now,
+            },
+            RateLimitAlgorithm::SlidingLog => BucketState::SlidingLog {
+                requests: VecDeque::new(),
+            },
+            RateLimitAlgorithm::SlidingWindowCounter => BucketState::SlidingWindowCounter {
+                window_start: now,
+                current: 0,
+                previous: 0,
+            },
+        };
+        
         Self {
             limit,
-            count: 0,
-            window_start: Utc::now(),
-            window_seconds,
+            state,
+            burst_start: now,
+            burst_count: 0,
+            last_seen: now,
         }
     }
     
-    /// Check if the bucket is expired
-    fn is_expired(&self) -> bool {
-        let now = Utc::now();
-        let window_duration = Duration::seconds(self.window_seconds as i64);
-        now > self.window_start + window_duration
+    /// Token bucket capacity
+    fn capacity(limit: &Limit) -> f64 {
+        limit.burst_size.unwrap_or(limit.requests).max(1) as f64
     }
     
-    /// Reset the bucket
-    fn reset(&mut self) {
-        self.count = 0;
-        self.window_start = Utc::now();
+    /// Tokens added per millisecond
+    fn refill_rate(limit: &Limit) -> f64 {
+        limit.requests as f64 / limit.window().num_milliseconds() as f64
     }
     
-    /// Get the time until reset
-    fn time_until_reset(&self) -> u64 {
-        let now = Utc::now();
-        let window_duration = Duration::seconds(self.window_seconds as i64);
-        let reset_time = self.window_start + window_duration;
+    /// Bring the state up to date without recording a request
+    fn advance(&mut self, now: DateTime<Utc>) {
+        let limit = self.limit;
+        let window = limit.window();
         
-        if now >= reset_time {
-            0
-        } else {
-            (reset_time - now).num_seconds() as u64
+        match &mut self.state {
+            BucketState::TokenBucket { tokens, last_refill } => {
+                let elapsed = (now - *last_refill).num_milliseconds().max(0) as f64;
+                *tokens = (*tokens + elapsed * Self::refill_rate(&limit)).min(Self::capacity(&limit));
+                *last_refill = now;
+            }
+            BucketState::SlidingLog { requests } => {
+                while requests.front().is_some_and(|t| now - *t >= window) {
+                    requests.pop_front();
+                }
+            }
+            BucketState::SlidingWindowCounter { window_start, current, previous } => {
+                let elapsed = (now - *window_start).num_milliseconds().max(0);
+                let windows = elapsed / window.num_milliseconds();
+                if windows >= 1 {
+                    *previous = if windows == 1 { *current } else { 0 };
+                    *current = 0;
+                    *window_start = *window_start + Duration::milliseconds(windows * window.num_milliseconds());
+                }
+            }
+        }
+        
+        if now - self.burst_start >= Duration::seconds(1) {
+            self.burst_start = now;
+            self.burst_count = 0;
         }
     }
     
+    /// Weighted request count for the sliding window counter
+    fn estimate(window_start: DateTime<Utc>, current: u32, previous: u32, window: Duration, now: DateTime<Utc>) -> f64 {
+        let elapsed = (now - window_start).num_milliseconds() as f64 / window.num_milliseconds() as f64;
+        previous as f64 * (1.0 - elapsed).max(0.0) + current as f64
+    }
+    
+    /// Record a request if allowed, otherwise return the milliseconds to wait
+    fn try_acquire(&mut self, now: DateTime<Utc>) -> Result<(), i64> {
+        self.advance(now);
+        self.last_seen = now;
+        
+        let limit = self.limit;
+        let window = limit.window();
+        let uses_burst = !matches!(self.state, BucketState::TokenBucket { .. });
+        
+        if uses_burst {
+            if let Some(burst) = limit.burst_size {
+                if self.burst_count >= burst {
+                    return Err((self.burst_start + Duration::seconds(1) - now).num_milliseconds());
+                }
+            }
+        }
+        
+        match &mut self.state {
+            BucketState::TokenBucket { tokens, .. } => {
+                if *tokens < 1.0 {
+                    return Err(((1.0 - *tokens) / Self::refill_rate(&limit)).ceil() as i64);
+                }
+                *tokens -= 1.0;
+            }
+            BucketState::SlidingLog { requests } => {
+                if requests.len() as u32 >= limit.requests {
+                    let oldest = requests.front().copied().unwrap_or(now);
+                    return Err((oldest + window - now).num_milliseconds());
+                }
+                requests.push_back(now);
+            }
+            BucketState::SlidingWindowCounter { window_start, current, previous } => {
+                let estimate = Self::estimate(*window_start, *current, *previous, window, now);
+                if estimate + 1.0 > limit.requests as f64 {
+                    let window_end = *window_start + window;
+                    let wait = if *current < limit.requests && *previous > 0 {
+                        // Wait until the previous window's weight has decayed enough
+                        let weight = (limit.requests - *current - 1) as f64 / *previous as f64;
+                        let at = *window_start + Duration::milliseconds(
+                            ((1.0 - weight) * window.num_milliseconds() as f64).ceil() as i64,
+                        );
+                        at.min(window_end)
+                    } else {
+                        window_end
+                    };
+                    return Err((wait - now).num_milliseconds());
+                }
+                *current += 1;
+            }
+        }
+        
+        self.burst_count += 1;
+        Ok(())
+    }
+    
     /// Get rate limit information
-    fn get_info(&self) -> RateLimitInfo {
-        let window_duration = Duration::seconds(self.window_seconds as i64);
-        let reset_at = self.window_start + window_duration;
+    fn get_info(&self, now: DateTime<Utc>) -> RateLimitInfo {
+        let limit = self.limit;
+        let window = limit.window();
+        
+        let (total, remaining, reset_at) = match &self.state {
+            BucketState::TokenBucket { tokens, .. } => {
+                let capacity = Self::capacity(&limit);
+                let refill_ms = ((capacity - tokens) / Self::refill_rate(&limit)).ceil() as i64;
+                (capacity as u32, tokens.floor() as u32, now + Duration::milliseconds(refill_ms))
+            }
+            BucketState::SlidingLog { requests } => {
+                let reset_at = requests.front().map(|t| *t + window).unwrap_or(now);
+                (limit.requests, limit.requests.saturating_sub(requests.len() as u32), reset_at)
+            }
+            BucketState::SlidingWindowCounter { window_start, current, previous } => {
+                let estimate = Self::estimate(*window_start, *current, *previous, window, now);
+                let remaining = (limit.requests as f64 - estimate).max(0.0).floor() as u32;
+                (limit.requests, remaining, *window_start + window)
+            }
+        };
         
         RateLimitInfo {
-            limit: self.limit,
-            remaining: self.limit.saturating_sub(self.count),
+            limit: total,
+            remaining,
             reset_at,
-            window_seconds: self.window_seconds,
+            window_seconds: limit.window_seconds,
         }
     }
 }
 
+/// Round a wait in milliseconds up to whole seconds
+fn wait_seconds(wait_ms: i64) -> u64 {
+    (wait_ms.max(0) as u64).div_ceil(1000)
+}
+
 /// Rate limiter for API calls
 pub struct RateLimiter {
     /// Rate limit buckets
     buckets: DashMap<String, RateLimitBucket>,
     
-    /// Default request limit
-    default_limit: u32,
+    /// Algorithm used for new buckets
+    algorithm: RateLimitAlgorithm,
+    
+    /// Default limit
+    default_limit: Limit,
     
-    /// Default time window in seconds
-    default_window: u64,
+    /// Limits for API keys
+    api_key_limit: Option<Limit>,
     
-    /// Custom limits for specific keys
+    /// Limits for users
+    user_limit: Option<Limit>,
+    
+    /// Limits for custom keys
+    custom_key_limit: Option<Limit>,
+    
+    /// Custom limits for specific key prefixes
     custom_limits: DashMap<String, (u32, u64)>,
+    
+    /// Idle time after which a bucket is evicted
+    idle_timeout: Duration,
 }
 
 impl RateLimiter {
     /// Create a new rate limiter
     pub fn new(config: &SecurityConfig) -> Self {
+        Self::from_config(&config.rate_limit)
+    }
+    
+    /// Create a rate limiter from rate limiting configuration
+    pub fn from_config(config: &RateLimitConfig) -> Self {
         Self {
             buckets: DashMap::new(),
-            default_limit: config.rate_limit.requests,
-            default_window: config.rate_limit.window_seconds,
+            algorithm: config.algorithm,
+            default_limit: Limit {
+                requests: config.requests,
+                window_seconds: config.window_seconds,
+                burst_size: config.burst_size,
+            },
+            api_key_limit: config.api_key.as_ref().map(Limit::from),
+            user_limit: config.user.as_ref().map(Limit::from),
+            custom_key_limit: config.custom.as_ref().map(Limit::from),
             custom_limits: DashMap::new(),
+            idle_timeout: Duration::seconds(config.idle_eviction_seconds as i64),
         }
     }
     
@@ -168,81 +367,102 @@ impl RateLimiter {
     
     /// Check if a request is allowed
     pub fn check(&self, key: &RateLimitKey) -> Result<RateLimitInfo, RateLimitError> {
-        let key_str = key.to_string();
-        
-        // Get or create the bucket
-        let mut bucket = self.get_or_create_bucket(&key_str);
-        
-        // Check if the bucket is expired
-        if bucket.is_expired() {
-            bucket.reset();
-        }
-        
-        // Check if the limit is exceeded
-        if bucket.count >= bucket.limit {
-            return Err(RateLimitError::RateLimitExceeded(bucket.time_until_reset()));
-        }
+        self.check_at(key, Utc::now())
+    }
+    
+    fn check_at(&self, key: &RateLimitKey, now: DateTime<Utc>) -> Result<RateLimitInfo, RateLimitError> {
+        let limit = self.get_limit_for_key(key);
+        let mut bucket = self.buckets
+            .entry(key.to_string())
+            .or_insert_with(|| RateLimitBucket::new(limit, self.algorithm, now));
         
-        // Increment the counter
-        bucket.count += 1;
+        // Limits may have changed since the bucket was created
+        bucket.limit = limit;
         
-        // Update the bucket
-        self.buckets.insert(key_str, bucket.clone());
+        bucket.try_acquire(now)
+            .map_err(|wait_ms| RateLimitError::RateLimitExceeded(wait_seconds(wait_ms)))?;
         
-        Ok(bucket.get_info())
+        Ok(bucket.get_info(now))
     }
     
     /// Get rate limit information without incrementing the counter
     pub fn get_info(&self, key: &RateLimitKey) -> RateLimitInfo {
-        let key_str = key.to_string();
+        let now = Utc::now();
+        let limit = self.get_limit_for_key(key);
         
-        // Get or create the bucket
-        let bucket = self.get_or_create_bucket(&key_str);
+        let mut bucket = self.buckets
+            .get(&key.to_string())
+            .map(|b| b.clone())
+            .unwrap_or_else(|| RateLimitBucket::new(limit, self.algorithm, now));
+        bucket.limit = limit;
+        bucket.advance(now);
         
-        bucket.get_info()
+        bucket.get_info(now)
     }
     
     /// Reset rate limit for a key
     pub fn reset(&self, key: &RateLimitKey) {
-        let key_str = key.to_string();
-        
-        if let Some(mut entry) = self.buckets.get_mut(&key_str) {
-            entry.reset();
-        }
+        self.buckets.remove(&key.to_string());
     }
     
-    /// Get or create a rate limit bucket
-    fn get_or_create_bucket(&self, key: &str) -> RateLimitBucket {
-        // Check if there's a custom limit for this key
-        let (limit, window) = self.get_limits_for_key(key);
+    /// Drop buckets that have not been used within the idle timeout
+    ///
+    /// Returns the number of buckets evicted.
+    pub fn evict_idle(&self) -> usize {
+        self.evict_idle_at(Utc::now())
+    }
+    
+    fn evict_idle_at(&self, now: DateTime<Utc>) -> usize {
+        let before = self.buckets.len();
+        self.buckets.retain(|_, bucket| now - bucket.last_seen < self.idle_timeout);
+        before - self.buckets.len()
+    }
+    
+    /// Periodically evict idle buckets in the background
+    ///
+    /// The task stops once the limiter has been dropped.
+    pub fn start_idle_eviction(self: &Arc<Self>, interval: std::time::Duration) -> JoinHandle<()> {
+        let limiter = Arc::downgrade(self);
         
-        // Get or create the bucket
-        if let Some(bucket) = self.buckets.get(key) {
-            // Update the bucket if the limits have changed
-            if bucket.limit != limit || bucket.window_seconds != window {
-                let mut new_bucket = bucket.clone();
-                new_bucket.limit = limit;
-                new_bucket.window_seconds = window;
-                return new_bucket;
-            }
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
             
-            bucket.clone()
-        } else {
-            RateLimitBucket::new(limit, window)
-        }
+            loop {
+                ticker.tick().await;
+                let Some(limiter) = limiter.upgrade() else {
+                    break;
+                };
+                let evicted = limiter.evict_idle();
+                if evicted > 0 {
+                    tracing::debug!("Evicted {} idle rate limit buckets", evicted);
+                }
+            }
+        })
     }
     
-    /// Get limits for a key
-    fn get_limits_for_key(&self, key: &str) -> (u32, u64) {
+    /// Get the limit for a key
+    fn get_limit_for_key(&self, key: &RateLimitKey) -> Limit {
+        let key_str = key.to_string();
+        
         // Check if there's a custom limit for this key
         for entry in self.custom_limits.iter() {
-            if key.starts_with(entry.key()) {
-                return *entry.value();
+            if key_str.starts_with(entry.key()) {
+                let (requests, window_seconds) = *entry.value();
+                return Limit { requests, window_seconds, burst_size: None };
             }
         }
         
+        // Then limits for the key type
+        let key_type_limit = match key {
+            RateLimitKey::IpAddress(_) => None,
+            RateLimitKey::ApiKey(_) => self.api_key_limit,
+            RateLimitKey::UserId(_) => self.user_limit,
+            RateLimitKey::Custom(_) => self.custom_key_limit,
+        };
+        
         // Use default limits
-        (self.default_limit, self.default_window)
+        key_type_limit.unwrap_or(self.default_limit)
     }
 }
 
@@ -268,85 +488,154 @@ impl RateLimiterMiddleware {
 mod tests {
     use super::*;
     use std::net::Ipv4Addr;
-    use std::thread::sleep;
-    use std::time::Duration as StdDuration;
     
-    fn create_test_config() -> SecurityConfig {
-        use crate::infrastructure::config::RateLimitConfig;
-        
-        SecurityConfig {
-            secret_key: "test-secret-key".to_string(),
-            token_expiration: 3600,
-            cors_origins: vec!["*".to_string()],
-            rate_limit: RateLimitConfig {
-                requests: 5,
-                window_seconds: 1,
-            },
+    fn create_test_config(algorithm: RateLimitAlgorithm) -> RateLimitConfig {
+        RateLimitConfig {
+            requests: 5,
+            window_seconds: 1,
+            algorithm,
+            burst_size: None,
+            idle_eviction_seconds: 60,
+            api_key: None,
+            user: None,
+            custom: None,
         }
     }
     
+    fn ip_key() -> RateLimitKey {
+        RateLimitKey::IpAddress(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)))
+    }
+    
     #[test]
     fn test_rate_limiting() {
-        let config = create_test_config();
-        let limiter = RateLimiter::new(&config);
-        
-        let key = RateLimitKey::IpAddress(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
-        
-        // Make 5 requests (should succeed)
-        for i in 0..5 {
-            let result = limiter.check(&key);
-            assert!(result.is_ok(), "Request {} should be allowed", i);
+        for algorithm in [
+            RateLimitAlgorithm::TokenBucket,
+            RateLimitAlgorithm::SlidingLog,
+            RateLimitAlgorithm::SlidingWindowCounter,
+        ] {
+            let limiter = RateLimiter::from_config(&create_test_config(algorithm));
+            let key = ip_key();
+            let start = Utc::now();
+            
+            // Make 5 requests (should succeed)
+            for i in 0..5 {
+                let info = limiter.check_at(&key, start)
+                    .unwrap_or_else(|_| panic!("{:?}: request {} should be allowed", algorithm, i));
+                assert_eq!(info.limit, 5);
+                assert_eq!(info.remaining, 5 - (i + 1));
+            }
+            
+            // Make another request (should fail)
+            match limiter.check_at(&key, start) {
+                Err(RateLimitError::RateLimitExceeded(seconds)) => assert!(seconds <= 1),
+                other => panic!("{:?}: expected RateLimitExceeded, got {:?}", algorithm, other),
+            }
             
-            let info = result.unwrap();
-            assert_eq!(info.limit, 5);
-            assert_eq!(info.remaining, 5 - (i + 1));
+            // Allowed again once the earlier requests no longer count
+            assert!(limiter.check_at(&key, start + Duration::milliseconds(2001)).is_ok());
         }
+    }
+    
+    #[test]
+    fn test_no_doubling_at_window_boundary() {
+        for algorithm in [RateLimitAlgorithm::SlidingLog, RateLimitAlgorithm::SlidingWindowCounter] {
+            let mut config = create_test_config(algorithm);
+            config.window_seconds = 10;
+            let limiter = RateLimiter::from_config(&config);
+            let key = ip_key();
+            let start = Utc::now();
+            
+            // Use the whole allowance late in the first window
+            for _ in 0..5 {
+                limiter.check_at(&key, start + Duration::seconds(9)).unwrap();
+            }
+            
+            // Just after the boundary the previous requests still count
+            assert!(
+                limiter.check_at(&key, start + Duration::seconds(11)).is_err(),
+                "{:?} allowed a doubled rate at the window boundary", algorithm
+            );
+        }
+    }
+    
+    #[test]
+    fn test_token_bucket_burst_and_refill() {
+        let mut config = create_test_config(RateLimitAlgorithm::TokenBucket);
+        config.requests = 60;
+        config.window_seconds = 60;
+        config.burst_size = Some(3);
+        let limiter = RateLimiter::from_config(&config);
+        let key = ip_key();
+        let start = Utc::now();
         
-        // Make another request (should fail)
-        let result = limiter.check(&key);
-        assert!(result.is_err(), "Request should be rate limited");
-        
-        if let Err(RateLimitError::RateLimitExceeded(seconds)) = result {
-            assert!(seconds <= 1, "Rate limit should reset in <= 1 second");
-        } else {
-            panic!("Expected RateLimitExceeded error");
+        for _ in 0..3 {
+            limiter.check_at(&key, start).unwrap();
         }
+        assert!(matches!(
+            limiter.check_at(&key, start),
+            Err(RateLimitError::RateLimitExceeded(1))
+        ));
         
-        // Wait for the rate limit to reset
-        sleep(StdDuration::from_secs(1));
+        // One token per second
+        let info = limiter.check_at(&key, start + Duration::seconds(1)).unwrap();
+        assert_eq!(info.limit, 3);
+        assert_eq!(info.remaining, 0);
+    }
+    
+    #[test]
+    fn test_sliding_burst() {
+        let mut config = create_test_config(RateLimitAlgorithm::SlidingLog);
+        config.requests = 10;
+        config.window_seconds = 60;
+        config.burst_size = Some(2);
+        let limiter = RateLimiter::from_config(&config);
+        let key = ip_key();
+        let start = Utc::now();
         
-        // Make another request (should succeed)
-        let result = limiter.check(&key);
-        assert!(result.is_ok(), "Request should be allowed after reset");
+        limiter.check_at(&key, start).unwrap();
+        limiter.check_at(&key, start).unwrap();
+        assert!(limiter.check_at(&key, start).is_err());
+        assert!(limiter.check_at(&key, start + Duration::seconds(1)).is_ok());
     }
     
     #[test]
     fn test_custom_limits() {
-        let config = create_test_config();
-        let limiter = RateLimiter::new(&config);
+        let mut config = create_test_config(RateLimitAlgorithm::TokenBucket);
+        config.user = Some(KeyRateLimit { requests: 3, window_seconds: 1, burst_size: None });
+        let limiter = RateLimiter::from_config(&config);
         
         // Set a custom limit for API keys
         limiter.set_custom_limit("api:", 2, 1);
         
-        let ip_key = RateLimitKey::IpAddress(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
         let api_key = RateLimitKey::ApiKey("test-key".to_string());
         
         // IP address should have the default limit (5)
-        let info = limiter.get_info(&ip_key);
-        assert_eq!(info.limit, 5);
+        assert_eq!(limiter.get_info(&ip_key()).limit, 5);
+        
+        // Users have their key type limit (3)
+        assert_eq!(limiter.get_info(&RateLimitKey::UserId("u1".to_string())).limit, 3);
         
         // API key should have the custom limit (2)
-        let info = limiter.get_info(&api_key);
-        assert_eq!(info.limit, 2);
+        assert_eq!(limiter.get_info(&api_key).limit, 2);
         
         // Make 2 requests with the API key (should succeed)
         for _ in 0..2 {
-            let result = limiter.check(&api_key);
-            assert!(result.is_ok());
+            assert!(limiter.check(&api_key).is_ok());
         }
         
         // Make another request with the API key (should fail)
-        let result = limiter.check(&api_key);
-        assert!(result.is_err());
+        assert!(limiter.check(&api_key).is_err());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_idle_eviction() {
+        let limiter = RateLimiter::from_config(&create_test_config(RateLimitAlgorithm::SlidingLog));
+        let start = Utc::now();
+        
+        limiter.check_at(&ip_key(), start).unwrap();
+        limiter.check_at(&RateLimitKey::Custom("recent".to_string()), start + Duration::seconds(50)).unwrap();
+        
+        assert_eq!(limiter.evict_idle_at(start + Duration::seconds(61)), 1);
+        assert_eq!(limiter.buckets.len(), 1);
+    }
+}
@@ -1,7 +1,9 @@
 //! Sandboxed execution environment for tools
 //!
 //! This module provides a sandboxed execution environment for running
-//! tools safely with resource limitations and isolation.
+//! tools safely with resource limitations and isolation. On Linux the
+//! limits are enforced on the child process (see the `linux` module);
+//! elsewhere only the execution timeout is.
 
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
@@ -16,6 +18,12 @@ use tokio::time::timeout;
 
 use crate::infrastructure::security::permissions::PermissionManager;
 
+#[cfg(target_os = "linux")]
+mod linux;
+
+/// Search path given to sandboxed commands
+const SANDBOX_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
 /// Sandbox error types
 #[derive(Debug, Error)]
 pub enum SandboxError {
@@ -109,6 +117,40 @@ pub struct SandboxConfig {
     
     /// Allow process execution
     pub allow_process_execution: bool,
+    
+    /// Kernel isolation applied to child processes (Linux only)
+    #[serde(default)]
+    pub isolation: IsolationConfig,
+}
+
+/// Kernel isolation for sandboxed processes
+///
+/// Mount and network namespaces need a user namespace unless the
+/// application runs as root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IsolationConfig {
+    /// Filter dangerous syscalls with seccomp
+    pub seccomp: bool,
+    
+    /// Run in a new user namespace
+    pub user_namespace: bool,
+    
+    /// Run in a new mount namespace with only the allowed directories mounted
+    pub mount_namespace: bool,
+    
+    /// Run in a new network namespace when network access is not allowed
+    pub network_namespace: bool,
+}
+
+impl Default for IsolationConfig {
+    fn default() -> Self {
+        Self {
+            seccomp: true,
+            user_namespace: false,
+            mount_namespace: false,
+            network_namespace: false,
+        }
+    }
 }
 
 impl Default for SandboxConfig {
@@ -128,10 +170,41 @@ impl Default for SandboxConfig {
             allow_network: false,
             allow_filesystem: false,
             allow_process_execution: false,
+            isolation: IsolationConfig::default(),
         }
     }
 }
 
+impl From<&crate::infrastructure::config::SandboxConfig> for SandboxConfig {
+    /// Sandbox for tool processes; a disabled sandbox runs no processes
+    /// rather than running them unconfined
+    fn from(config: &crate::infrastructure::config::SandboxConfig) -> Self {
+        let mut sandbox = Self::default();
+        sandbox.resource_limits.max_execution_time = config.max_execution_time;
+        sandbox.resource_limits.max_memory_mb = config.max_memory_mb;
+        sandbox.allow_network = config.allow_network;
+        sandbox.allow_filesystem = config.allow_filesystem;
+        sandbox.allow_process_execution = config.enabled;
+        sandbox.isolation.user_namespace = config.user_namespace;
+        sandbox.isolation.mount_namespace = config.mount_namespace;
+        sandbox.isolation.network_namespace = config.network_namespace;
+        sandbox
+    }
+}
+
+/// Per-execution options for a sandboxed command
+#[derive(Debug, Clone, Default)]
+pub struct CommandOptions {
+    /// Data written to the command's standard input
+    pub stdin: Option<Vec<u8>>,
+    
+    /// Timeout shorter than the sandbox's `max_execution_time`
+    pub timeout: Option<Duration>,
+    
+    /// Deny network access even if the sandbox allows it
+    pub deny_network: bool,
+}
+
 /// Execution statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionStats {
@@ -144,6 +217,10 @@ pub struct ExecutionStats {
     /// CPU usage in percentage
     pub cpu_usage_percent: f32,
     
+    /// CPU time (user and system) in milliseconds
+    #[serde(default)]
+    pub cpu_time_ms: u64,
+    
     /// Disk usage in MB
     pub disk_usage_mb: u64,
     
@@ -191,8 +268,20 @@ impl Sandbox {
     
     /// Execute a command in the sandbox
     pub async fn execute_command(&self, command: &str, args: &[&str], user_id: &str, tool_id: &str) -> Result<ExecutionResult, SandboxError> {
+        self.execute_command_with(command, args, CommandOptions::default(), user_id, tool_id).await
+    }
+    
+    /// Execute a command in the sandbox with per-execution options
+    pub async fn execute_command_with(
+        &self,
+        command: &str,
+        args: &[&str],
+        options: CommandOptions,
+        user_id: &str,
+        tool_id: &str,
+    ) -> Result<ExecutionResult, SandboxError> {
         // Check permissions
-        if !self.permission_manager.can_execute_tool(user_id, tool_id) {
+        if !self.permission_manager.can_execute_tool(user_id, tool_id).await {
             return Err(SandboxError::PermissionDenied(format!("User {} does not have permission to execute tool {}", user_id, tool_id)));
         }
         
@@ -206,7 +295,7 @@ impl Sandbox {
         self.track_execution(&execution_id).await?;
         
         // Execute the command with resource limits
-        let result = self.execute_with_limits(command, args).await;
+        let result = self.execute_with_limits(command, args, options).await;
         
         // Cleanup
         self.cleanup_execution(&execution_id).await;
@@ -221,7 +310,7 @@ impl Sandbox {
         T: Send + 'static,
     {
         // Check permissions
-        if !self.permission_manager.can_execute_tool(user_id, tool_id) {
+        if !self.permission_manager.can_execute_tool(user_id, tool_id).await {
             return Err(SandboxError::PermissionDenied(format!("User {} does not have permission to execute tool {}", user_id, tool_id)));
         }
         
@@ -293,60 +382,91 @@ impl Sandbox {
     }
     
     /// Execute a command with resource limits
-    async fn execute_with_limits(&self, command: &str, args: &[&str]) -> Result<ExecutionResult, SandboxError> {
-        let start_time = Instant::now();
+    async fn execute_with_limits(&self, command: &str, args: &[&str], options: CommandOptions) -> Result<ExecutionResult, SandboxError> {
+        let mut config = self.config.clone();
+        if options.deny_network {
+            config.allow_network = false;
+        }
+        
+        let mut max_time = Duration::from_secs(config.resource_limits.max_execution_time);
+        if let Some(limit) = options.timeout {
+            max_time = max_time.min(limit);
+        }
         
         // Prepare the command
         let mut cmd = Command::new(command);
         cmd.args(args)
-            .stdin(Stdio::null())
+            .stdin(if options.stdin.is_some() { Stdio::piped() } else { Stdio::null() })
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
         
-        // Set allowed environment variables
+        // Only pass allowed environment variables
         let mut env_vars = std::env::vars()
             .filter(|(key, _)| self.config.allowed_env_vars.contains(key))
             .collect::<HashMap<String, String>>();
         
+        // The inherited PATH may point at user-writable directories
+        env_vars.insert("PATH".to_string(), SANDBOX_PATH.to_string());
+        
         // Add resource limits as environment variables
         env_vars.insert("SANDBOX_MAX_MEMORY_MB".to_string(), self.config.resource_limits.max_memory_mb.to_string());
         env_vars.insert("SANDBOX_MAX_CPU_PERCENT".to_string(), self.config.resource_limits.max_cpu_percent.to_string());
         env_vars.insert("SANDBOX_MAX_DISK_MB".to_string(), self.config.resource_limits.max_disk_mb.to_string());
         
-        cmd.envs(env_vars);
+        cmd.env_clear().envs(env_vars);
         
-        // Execute the command with a timeout
-        let max_time = Duration::from_secs(self.config.resource_limits.max_execution_time);
-        let output = timeout(max_time, async {
-            cmd.output()
-                .map_err(|e| SandboxError::CommandExecution(e.to_string()))
-        })
-        .await
-        .map_err(|_| SandboxError::Timeout(self.config.resource_limits.max_execution_time))??;
+        #[cfg(target_os = "linux")]
+        return linux::run(cmd, &config, max_time, options.stdin).await;
         
-        let execution_time = start_time.elapsed();
+        #[cfg(not(target_os = "linux"))]
+        return self.execute_unconfined(cmd, max_time, options.stdin).await;
+    }
+    
+    /// Execute a command with only the timeout enforced
+    #[cfg(not(target_os = "linux"))]
+    async fn execute_unconfined(&self, cmd: Command, max_time: Duration, stdin: Option<Vec<u8>>) -> Result<ExecutionResult, SandboxError> {
+        use tokio::io::AsyncWriteExt;
+        
+        let start_time = Instant::now();
+        
+        let mut cmd = tokio::process::Command::from(cmd);
+        cmd.kill_on_drop(true);
+        
+        let mut child = cmd.spawn().map_err(|e| SandboxError::CommandExecution(e.to_string()))?;
+        if let (Some(mut pipe), Some(input)) = (child.stdin.take(), stdin) {
+            tokio::spawn(async move {
+                let _ = pipe.write_all(&input).await;
+            });
+        }
+        
+        let output = timeout(max_time, child.wait_with_output())
+            .await
+            .map_err(|_| SandboxError::Timeout(timeout_seconds(max_time)))?
+            .map_err(|e| SandboxError::CommandExecution(e.to_string()))?;
         
-        // Create execution statistics (in a real implementation, these would be measured)
         let stats = ExecutionStats {
-            execution_time_ms: execution_time.as_millis() as u64,
-            memory_usage_mb: 0, // Would be measured in a real implementation
-            cpu_usage_percent: 0.0, // Would be measured in a real implementation
-            disk_usage_mb: 0, // Would be measured in a real implementation
-            network_usage_kb: 0, // Would be measured in a real implementation
+            execution_time_ms: start_time.elapsed().as_millis() as u64,
+            memory_usage_mb: 0,
+            cpu_usage_percent: 0.0,
+            cpu_time_ms: 0,
+            disk_usage_mb: 0,
+            network_usage_kb: 0,
         };
         
-        // Create execution result
-        let result = ExecutionResult {
+        Ok(ExecutionResult {
             exit_code: output.status.code().unwrap_or(-1),
             stdout: String::from_utf8_lossy(&output.stdout).to_string(),
             stderr: String::from_utf8_lossy(&output.stderr).to_string(),
             stats,
-        };
-        
-        Ok(result)
+        })
     }
 }
 
+/// Timeout in whole seconds, as reported by [`SandboxError::Timeout`]
+fn timeout_seconds(max_time: Duration) -> u64 {
+    max_time.as_millis().div_ceil(1000) as u64
+}
+
 // Import for the UUID generation
 use uuid::Uuid;
 
@@ -370,7 +490,7 @@ mod tests {
         let sandbox = Sandbox::new(config, permission_manager);
         
         // Grant permission to the user
-        sandbox.permission_manager.grant_tool_permission("user1", "echo").unwrap();
+        sandbox.permission_manager.grant_tool_permission("user1", "echo").await.unwrap();
         
         // Execute a command
         let result = sandbox.execute_command("echo", &["Hello, world!"], "user1", "echo").await;
@@ -381,6 +501,21 @@ mod tests {
         assert_eq!(result.stdout.trim(), "Hello, world!");
     }
     
+    #[tokio::test]
+    async fn test_sandbox_uses_minimal_path() {
+        let permission_manager = Arc::new(PermissionManager::new());
+        
+        let mut config = SandboxConfig::default();
+        config.allow_process_execution = true;
+        
+        let sandbox = Sandbox::new(config, permission_manager);
+        sandbox.permission_manager.grant_tool_permission("user1", "sh").await.unwrap();
+        
+        let result = sandbox.execute_command("sh", &["-c", "echo $PATH"], "user1", "sh").await.unwrap();
+        
+        assert_eq!(result.stdout.trim(), SANDBOX_PATH);
+    }
+    
     #[tokio::test]
     async fn test_sandbox_permission_denied() {
         use crate::infrastructure::security::permissions::PermissionManager;
@@ -421,7 +556,7 @@ mod tests {
         let sandbox = Sandbox::new(config, permission_manager);
         
         // Grant permission to the user
-        sandbox.permission_manager.grant_tool_permission("user1", "sleep").unwrap();
+        sandbox.permission_manager.grant_tool_permission("user1", "sleep").await.unwrap();
         
         // Execute a command that will timeout
         let result = sandbox.execute_command("sleep", &["5"], "user1", "sleep").await;
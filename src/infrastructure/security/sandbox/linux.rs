@@ -0,0 +1,695 @@
+//! Linux enforcement for sandboxed processes
+//!
+//! The child runs in its own process group with rlimits derived from the
+//! sandbox's [`ResourceLimits`](super::ResourceLimits). Optionally it gets
+//! new user, mount and network namespaces, with a fresh root that only
+//! contains the system directories and the allowed directories bind
+//! mounted. A seccomp filter is installed last so the child cannot undo
+//! any of this. Everything the child needs is prepared before forking;
+//! the `pre_exec` hook only makes system calls.
+//!
+//! Network access is all or nothing: without it the child gets a network
+//! namespace with only a loopback device (when enabled) and cannot open
+//! IPv4/IPv6 sockets (with seccomp). `allowed_network_hosts` is not
+//! enforced at this level.
+
+use std::ffi::CString;
+use std::io::{self, Read};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::{timeout_seconds, ExecutionResult, ExecutionStats, SandboxConfig, SandboxError};
+
+const MIB: u64 = 1024 * 1024;
+
+/// Directories bind mounted read-only so ordinary programs can run
+const SYSTEM_DIRS: &[&str] = &["/usr", "/bin", "/sbin", "/lib", "/lib64", "/etc", "/dev"];
+
+// Classic BPF and seccomp constants (linux/filter.h, linux/seccomp.h)
+const BPF_LD_W_ABS: u16 = 0x00 | 0x00 | 0x20;
+const BPF_JMP_JEQ_K: u16 = 0x05 | 0x10 | 0x00;
+const BPF_JMP_JGE_K: u16 = 0x05 | 0x30 | 0x00;
+const BPF_JMP_JSET_K: u16 = 0x05 | 0x40 | 0x00;
+const BPF_RET_K: u16 = 0x06 | 0x00;
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_MODE_FILTER: libc::c_ulong = 2;
+
+// Offsets into struct seccomp_data
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+const SECCOMP_DATA_ARG0: u32 = 16;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_003E);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_00B7);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: Option<u32> = None;
+
+/// `clone` flags that create namespaces
+const CLONE_NAMESPACES: libc::c_int = libc::CLONE_NEWUSER
+    | libc::CLONE_NEWNS
+    | libc::CLONE_NEWNET
+    | libc::CLONE_NEWPID
+    | libc::CLONE_NEWIPC
+    | libc::CLONE_NEWUTS
+    | libc::CLONE_NEWCGROUP;
+
+/// Syscalls that could escape or tamper with the sandbox
+const BLOCKED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_ptrace,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+    libc::SYS_setsid,
+    libc::SYS_setpgid,
+    libc::SYS_reboot,
+    libc::SYS_kexec_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_userfaultfd,
+    libc::SYS_acct,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    libc::SYS_sethostname,
+    libc::SYS_setdomainname,
+    libc::SYS_syslog,
+    // io_uring operations are not checked by seccomp
+    libc::SYS_io_uring_setup,
+    libc::SYS_io_uring_enter,
+    libc::SYS_io_uring_register,
+];
+
+#[cfg(target_env = "gnu")]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(target_env = "gnu"))]
+type Resource = libc::c_int;
+
+/// A bind mount into the sandbox root
+struct BindMount {
+    source: CString,
+    target: CString,
+    read_only: bool,
+    /// Flags of the source mount that must be kept when remounting
+    locked_flags: libc::c_ulong,
+}
+
+/// New root filesystem for a mount namespace
+///
+/// The root is an empty host directory; its mount points are created
+/// before forking and the mounts themselves only exist in the child's
+/// namespace.
+struct RootPlan {
+    /// Host directory used as the root
+    host_dir: PathBuf,
+    root: CString,
+    tmp: CString,
+    binds: Vec<BindMount>,
+}
+
+/// Everything the child applies between fork and exec
+struct Plan {
+    rlimits: Vec<(Resource, libc::rlim_t, libc::rlim_t)>,
+    namespaces: libc::c_int,
+    /// Contents of uid_map and gid_map
+    id_maps: Option<(CString, CString)>,
+    root: Option<RootPlan>,
+    seccomp: Option<Vec<libc::sock_filter>>,
+}
+
+/// Run `command` under the sandbox configuration, killing it after `max_time`
+pub(super) async fn run(
+    mut command: Command,
+    config: &SandboxConfig,
+    max_time: Duration,
+    stdin: Option<Vec<u8>>,
+) -> Result<ExecutionResult, SandboxError> {
+    let limits = &config.resource_limits;
+    let cpu_seconds = cpu_time_limit(config);
+    let plan = Plan::new(config)?;
+    let host_dir = plan.root.as_ref().map(|root| root.host_dir.clone());
+
+    command.process_group(0);
+    unsafe {
+        command.pre_exec(move || plan.apply());
+    }
+
+    let start_time = Instant::now();
+    let spawned = command.spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            remove_root(host_dir.as_deref());
+            return Err(SandboxError::CommandExecution(e.to_string()));
+        }
+    };
+    let pid = child.id() as libc::pid_t;
+
+    if let (Some(mut pipe), Some(input)) = (child.stdin.take(), stdin) {
+        tokio::task::spawn_blocking(move || {
+            use std::io::Write;
+            // The child may exit without reading everything
+            let _ = pipe.write_all(&input);
+        });
+    }
+
+    let stdout = child.stdout.take().map(read_pipe);
+    let stderr = child.stderr.take().map(read_pipe);
+    let mut waiter = tokio::task::spawn_blocking(move || wait_for_group(pid));
+
+    let timed_out = tokio::time::timeout(max_time, &mut waiter).await.is_err();
+    if timed_out {
+        unsafe { libc::kill(-pid, libc::SIGKILL) };
+    }
+
+    let waited = waiter.await.map_err(|e| SandboxError::Internal(format!("Task join error: {}", e)));
+    let stdout = join_pipe(stdout).await;
+    let stderr = join_pipe(stderr).await;
+    remove_root(host_dir.as_deref());
+
+    let (status, usage) = waited?.map_err(|e| SandboxError::Internal(format!("wait4 failed: {}", e)))?;
+    if timed_out {
+        return Err(SandboxError::Timeout(timeout_seconds(max_time)));
+    }
+
+    let stats = execution_stats(start_time.elapsed(), &usage);
+
+    if libc::WIFSIGNALED(status) {
+        let signal = libc::WTERMSIG(status);
+        if signal == libc::SIGXCPU || (signal == libc::SIGKILL && stats.cpu_time_ms >= cpu_seconds * 1000) {
+            return Err(SandboxError::ResourceLimitExceeded(format!("CPU time limit of {}s", cpu_seconds)));
+        }
+        if signal == libc::SIGXFSZ {
+            return Err(SandboxError::ResourceLimitExceeded(format!("File size limit of {} MB", limits.max_disk_mb)));
+        }
+    }
+
+    Ok(ExecutionResult {
+        exit_code: if libc::WIFEXITED(status) { libc::WEXITSTATUS(status) } else { -1 },
+        stdout: String::from_utf8_lossy(&stdout).to_string(),
+        stderr: String::from_utf8_lossy(&stderr).to_string(),
+        stats,
+    })
+}
+
+/// CPU seconds allowed: the share of the wall-clock limit given by `max_cpu_percent`
+fn cpu_time_limit(config: &SandboxConfig) -> u64 {
+    let limits = &config.resource_limits;
+    let percent = limits.max_cpu_percent.clamp(1, 100) as u64;
+    (limits.max_execution_time * percent).div_ceil(100).max(1)
+}
+
+impl Plan {
+    fn new(config: &SandboxConfig) -> Result<Self, SandboxError> {
+        let limits = &config.resource_limits;
+        let isolation = &config.isolation;
+        let cpu_seconds = cpu_time_limit(config);
+
+        let mut rlimits = vec![
+            (libc::RLIMIT_AS, limits.max_memory_mb * MIB, limits.max_memory_mb * MIB),
+            // SIGXCPU at the soft limit, SIGKILL a second later
+            (libc::RLIMIT_CPU, cpu_seconds, cpu_seconds + 1),
+            (libc::RLIMIT_FSIZE, limits.max_disk_mb * MIB, limits.max_disk_mb * MIB),
+            (libc::RLIMIT_NOFILE, limits.max_file_descriptors as u64, limits.max_file_descriptors as u64),
+            (libc::RLIMIT_CORE, 0, 0),
+        ];
+
+        // RLIMIT_NPROC counts every process of the user, so it is only
+        // meaningful inside a user namespace where the count starts afresh
+        if isolation.user_namespace {
+            rlimits.push((libc::RLIMIT_NPROC, limits.max_processes as u64, limits.max_processes as u64));
+        }
+
+        let mut namespaces = 0;
+        if isolation.user_namespace {
+            namespaces |= libc::CLONE_NEWUSER;
+        }
+        if isolation.mount_namespace {
+            namespaces |= libc::CLONE_NEWNS;
+        }
+        if isolation.network_namespace && !config.allow_network {
+            namespaces |= libc::CLONE_NEWNET;
+        }
+
+        let id_maps = isolation.user_namespace.then(|| {
+            let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+            (c_string(format!("{0} {0} 1", uid)), c_string(format!("{0} {0} 1", gid)))
+        });
+
+        let root = if isolation.mount_namespace {
+            Some(RootPlan::new(config)?)
+        } else {
+            None
+        };
+
+        let seccomp = if isolation.seccomp {
+            match AUDIT_ARCH {
+                Some(arch) => Some(seccomp_filter(arch, !config.allow_network)),
+                None => {
+                    tracing::warn!("Seccomp filtering is not supported on this architecture");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        Ok(Self {
+            rlimits: rlimits.into_iter().map(|(r, soft, hard)| (r, soft as libc::rlim_t, hard as libc::rlim_t)).collect(),
+            namespaces,
+            id_maps,
+            root,
+            seccomp,
+        })
+    }
+
+    /// Applied in the child after fork
+    fn apply(&self) -> io::Result<()> {
+        if self.namespaces != 0 {
+            check(unsafe { libc::unshare(self.namespaces) })?;
+        }
+
+        if let Some((uid_map, gid_map)) = &self.id_maps {
+            write_file(b"/proc/self/setgroups\0", b"deny")?;
+            write_file(b"/proc/self/uid_map\0", uid_map.as_bytes())?;
+            write_file(b"/proc/self/gid_map\0", gid_map.as_bytes())?;
+        }
+
+        if let Some(root) = &self.root {
+            root.apply()?;
+        }
+
+        for (resource, soft, hard) in &self.rlimits {
+            let limit = libc::rlimit { rlim_cur: *soft, rlim_max: *hard };
+            check(unsafe { libc::setrlimit(*resource, &limit) })?;
+        }
+
+        if let Some(filter) = &self.seccomp {
+            let program = libc::sock_fprog {
+                len: filter.len() as u16,
+                filter: filter.as_ptr() as *mut libc::sock_filter,
+            };
+            check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong) })?;
+            check(unsafe {
+                libc::prctl(libc::PR_SET_SECCOMP, SECCOMP_MODE_FILTER, &program as *const libc::sock_fprog)
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RootPlan {
+    fn new(config: &SandboxConfig) -> Result<Self, SandboxError> {
+        let host_dir = std::env::temp_dir().join(format!("dt-sandbox-{}", Uuid::new_v4()));
+        let plan = Self::prepare(&host_dir, config);
+        if plan.is_err() {
+            remove_root(Some(&host_dir));
+        }
+        plan.map_err(|e| SandboxError::Internal(format!("Failed to prepare sandbox root: {}", e)))
+    }
+
+    fn prepare(host_dir: &Path, config: &SandboxConfig) -> io::Result<Self> {
+        std::fs::create_dir(host_dir)?;
+        std::fs::create_dir(host_dir.join("tmp"))?;
+
+        let mut mounts: Vec<(PathBuf, bool)> = SYSTEM_DIRS.iter().map(|d| (PathBuf::from(d), true)).collect();
+        if config.allow_filesystem {
+            mounts.extend(config.allowed_read_dirs.iter().map(|d| (d.clone(), true)));
+            mounts.extend(config.allowed_write_dirs.iter().map(|d| (d.clone(), false)));
+        }
+
+        let mut binds = Vec::new();
+        for (source, read_only) in mounts {
+            if !source.is_absolute() {
+                continue;
+            }
+            let target = host_dir.join(source.strip_prefix("/").unwrap_or(&source));
+
+            // Symlinks such as /lib -> usr/lib on merged-usr systems are recreated as-is
+            match std::fs::symlink_metadata(&source) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    if let Some(parent) = target.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::os::unix::fs::symlink(std::fs::read_link(&source)?, &target)?;
+                }
+                Ok(metadata) if metadata.is_dir() => {
+                    std::fs::create_dir_all(&target)?;
+                    binds.push(BindMount {
+                        locked_flags: locked_mount_flags(&source),
+                        source: path_c_string(&source),
+                        target: path_c_string(&target),
+                        read_only,
+                    });
+                }
+                _ => continue,
+            }
+        }
+
+        Ok(Self {
+            root: path_c_string(host_dir),
+            tmp: path_c_string(&host_dir.join("tmp")),
+            host_dir: host_dir.to_path_buf(),
+            binds,
+        })
+    }
+
+    fn apply(&self) -> io::Result<()> {
+        let null = std::ptr::null::<libc::c_char>();
+
+        // Keep our mounts out of the host's mount namespace
+        check(unsafe {
+            libc::mount(null, b"/\0".as_ptr().cast(), null, libc::MS_REC | libc::MS_PRIVATE, std::ptr::null())
+        })?;
+
+        for bind in &self.binds {
+            check(unsafe {
+                libc::mount(bind.source.as_ptr(), bind.target.as_ptr(), null, libc::MS_BIND | libc::MS_REC, std::ptr::null())
+            })?;
+            if bind.read_only {
+                let flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | bind.locked_flags;
+                check(unsafe { libc::mount(null, bind.target.as_ptr(), null, flags, std::ptr::null()) })?;
+            }
+        }
+
+        let tmpfs = b"tmpfs\0".as_ptr().cast();
+        check(unsafe {
+            libc::mount(tmpfs, self.tmp.as_ptr(), tmpfs, libc::MS_NOSUID | libc::MS_NODEV, std::ptr::null())
+        })?;
+
+        check(unsafe { libc::chdir(self.root.as_ptr()) })?;
+        check(unsafe { libc::chroot(b".\0".as_ptr().cast()) })?;
+        check(unsafe { libc::chdir(b"/\0".as_ptr().cast()) })?;
+
+        Ok(())
+    }
+}
+
+/// Remove the host directory used as the sandbox root
+fn remove_root(host_dir: Option<&Path>) {
+    if let Some(dir) = host_dir {
+        if let Err(e) = std::fs::remove_dir_all(dir) {
+            tracing::warn!("Failed to remove sandbox root {}: {}", dir.display(), e);
+        }
+    }
+}
+
+/// Build the seccomp program
+///
+/// Syscalls from another ABI kill the process; blocked syscalls and `clone`
+/// with namespace flags fail with EPERM. `clone3` passes its flags in
+/// memory the filter cannot read, so it fails with ENOSYS and libc falls
+/// back to `clone`. Without network access, IPv4 and IPv6 sockets fail
+/// with EACCES.
+fn seccomp_filter(arch: u32, block_network: bool) -> Vec<libc::sock_filter> {
+    let stmt = |code, k| libc::sock_filter { code, jt: 0, jf: 0, k };
+    let jump = |k, jt, jf| libc::sock_filter { code: BPF_JMP_JEQ_K, jt, jf, k };
+
+    let mut filter = vec![
+        stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+        jump(arch, 1, 0),
+        stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+        stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+    ];
+
+    // x32 syscalls share the x86_64 audit arch
+    if cfg!(target_arch = "x86_64") {
+        filter.push(libc::sock_filter { code: BPF_JMP_JGE_K, jt: 0, jf: 1, k: 0x4000_0000 });
+        filter.push(stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS));
+    }
+
+    for syscall in BLOCKED_SYSCALLS {
+        filter.push(jump(*syscall as u32, 0, 1));
+        filter.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32));
+    }
+
+    filter.push(jump(libc::SYS_clone3 as u32, 0, 1));
+    filter.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::ENOSYS as u32));
+
+    filter.push(jump(libc::SYS_clone as u32, 0, 3));
+    filter.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG0));
+    filter.push(libc::sock_filter { code: BPF_JMP_JSET_K, jt: 0, jf: 1, k: CLONE_NAMESPACES as u32 });
+    filter.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32));
+    filter.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR));
+
+    if block_network {
+        filter.push(jump(libc::SYS_socket as u32, 0, 4));
+        filter.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG0));
+        filter.push(jump(libc::AF_INET as u32, 1, 0));
+        filter.push(jump(libc::AF_INET6 as u32, 0, 1));
+        filter.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EACCES as u32));
+    }
+
+    filter.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    filter
+}
+
+/// Wait for the child to exit, kill the rest of its process group, then reap it
+///
+/// Reaping last keeps the group id from being reused before the kill.
+fn wait_for_group(pid: libc::pid_t) -> io::Result<(libc::c_int, libc::rusage)> {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    loop {
+        let rc = unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, libc::WEXITED | libc::WNOWAIT) };
+        if rc == 0 {
+            break;
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+
+    unsafe { libc::kill(-pid, libc::SIGKILL) };
+
+    let mut status = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        if unsafe { libc::wait4(pid, &mut status, 0, &mut usage) } == pid {
+            return Ok((status, usage));
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// Statistics from the child's resource usage
+fn execution_stats(elapsed: Duration, usage: &libc::rusage) -> ExecutionStats {
+    let to_ms = |t: libc::timeval| t.tv_sec as u64 * 1000 + t.tv_usec as u64 / 1000;
+    let cpu_time_ms = to_ms(usage.ru_utime) + to_ms(usage.ru_stime);
+    let execution_time_ms = elapsed.as_millis() as u64;
+
+    ExecutionStats {
+        execution_time_ms,
+        // ru_maxrss is in kilobytes on Linux
+        memory_usage_mb: usage.ru_maxrss as u64 / 1024,
+        cpu_usage_percent: if execution_time_ms == 0 {
+            0.0
+        } else {
+            cpu_time_ms as f32 / execution_time_ms as f32 * 100.0
+        },
+        cpu_time_ms,
+        // Blocks written, in 512-byte units
+        disk_usage_mb: usage.ru_oublock as u64 * 512 / MIB,
+        network_usage_kb: 0,
+    }
+}
+
+fn read_pipe<R: Read + Send + 'static>(mut pipe: R) -> tokio::task::JoinHandle<Vec<u8>> {
+    tokio::task::spawn_blocking(move || {
+        let mut buffer = Vec::new();
+        let _ = pipe.read_to_end(&mut buffer);
+        buffer
+    })
+}
+
+async fn join_pipe(pipe: Option<tokio::task::JoinHandle<Vec<u8>>>) -> Vec<u8> {
+    match pipe {
+        Some(handle) => handle.await.unwrap_or_default(),
+        None => Vec::new(),
+    }
+}
+
+/// Mount flags the kernel refuses to clear on an unprivileged remount
+fn locked_mount_flags(path: &Path) -> libc::c_ulong {
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path_c_string(path).as_ptr(), &mut stat) } != 0 {
+        return 0;
+    }
+
+    [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ]
+    .into_iter()
+    .filter(|(st, _)| stat.f_flag & *st != 0)
+    .fold(0, |flags, (_, ms)| flags | ms)
+}
+
+/// Write to a file given a NUL-terminated path, without allocating
+fn write_file(path: &[u8], bytes: &[u8]) -> io::Result<()> {
+    let fd = unsafe { libc::open(path.as_ptr().cast(), libc::O_WRONLY | libc::O_CLOEXEC) };
+    check(fd)?;
+    let written = unsafe { libc::write(fd, bytes.as_ptr() as *const libc::c_void, bytes.len()) };
+    unsafe { libc::close(fd) };
+    if written != bytes.len() as isize {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn check(rc: libc::c_int) -> io::Result<()> {
+    if rc < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn c_string(value: String) -> CString {
+    CString::new(value).expect("no interior NUL")
+}
+
+fn path_c_string(path: &Path) -> CString {
+    CString::new(path.as_os_str().as_bytes()).expect("path without NUL")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+
+    fn command(script: &str) -> Command {
+        let mut command = Command::new("/bin/sh");
+        command.args(["-c", script])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        command
+    }
+
+    #[tokio::test]
+    async fn test_rlimits_applied() {
+        let config = SandboxConfig::default();
+        let result = run(command("ulimit -v; ulimit -n; ulimit -t"), &config, Duration::from_secs(30), None).await.unwrap();
+
+        let limits: Vec<&str> = result.stdout.lines().collect();
+        assert_eq!(limits, vec!["262144", "100", "15"]);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_process_group() {
+        let mut config = SandboxConfig::default();
+        config.resource_limits.max_execution_time = 1;
+        config.resource_limits.max_cpu_percent = 100;
+
+        let started = Instant::now();
+        let result = run(command("sleep 30 & sleep 30"), &config, Duration::from_secs(1), None).await;
+
+        assert!(matches!(result, Err(SandboxError::Timeout(1))));
+        // The background sleep holds stdout; it must have been killed too
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_cpu_limit_and_stats() {
+        let mut config = SandboxConfig::default();
+        config.resource_limits.max_execution_time = 10;
+        config.resource_limits.max_cpu_percent = 10;
+
+        let result = run(command("while :; do :; done"), &config, Duration::from_secs(10), None).await;
+        assert!(matches!(result, Err(SandboxError::ResourceLimitExceeded(_))));
+
+        let result = run(command("i=0; while [ $i -lt 100000 ]; do i=$((i+1)); done"), &config, Duration::from_secs(10), None).await.unwrap();
+        assert_eq!(result.exit_code, 0);
+        assert!(result.stats.cpu_time_ms > 0);
+        assert!(result.stats.memory_usage_mb > 0);
+    }
+
+    #[tokio::test]
+    async fn test_stdin_is_passed() {
+        let config = SandboxConfig::default();
+        let input = b"{\"value\": 42}".to_vec();
+        let mut cat = command("cat");
+        cat.stdin(Stdio::piped());
+        let result = run(cat, &config, Duration::from_secs(10), Some(input)).await.unwrap();
+
+        assert_eq!(result.stdout, "{\"value\": 42}");
+    }
+
+    #[tokio::test]
+    async fn test_seccomp_blocks_escape_syscalls() {
+        let mut config = SandboxConfig::default();
+        config.isolation.seccomp = true;
+
+        // setsid(1) would leave the process group
+        let result = run(command("setsid true && echo escaped || echo blocked"), &config, Duration::from_secs(10), None).await.unwrap();
+        assert_eq!(result.stdout.trim(), "blocked");
+    }
+
+    #[tokio::test]
+    async fn test_seccomp_blocks_namespace_clones() {
+        let mut config = SandboxConfig::default();
+        config.isolation.seccomp = true;
+
+        let script = format!(
+            "perl -e 'print syscall({clone}, {flags}, 0, 0, 0, 0) == -1 ? $!+0 : q(cloned), q( ), syscall({clone3}, 0, 0) == -1 ? $!+0 : q(cloned)'",
+            clone = libc::SYS_clone,
+            flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS,
+            clone3 = libc::SYS_clone3,
+        );
+        let result = run(command(&script), &config, Duration::from_secs(10), None).await.unwrap();
+        assert_eq!(result.stdout.trim(), format!("{} {}", libc::EPERM, libc::ENOSYS));
+
+        // Plain forks still work
+        let result = run(command("(echo forked)"), &config, Duration::from_secs(10), None).await.unwrap();
+        assert_eq!(result.stdout.trim(), "forked");
+    }
+
+    #[tokio::test]
+    async fn test_mount_namespace_only_exposes_allowed_dirs() {
+        let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut config = SandboxConfig::default();
+        config.isolation.user_namespace = true;
+        config.isolation.mount_namespace = true;
+        config.allow_filesystem = true;
+        config.allowed_read_dirs = vec![crate_dir.join("src")];
+
+        let script = format!(
+            "test -e {inside} && echo present || echo missing; test -e {outside} && echo present || echo missing",
+            inside = crate_dir.join("src/lib.rs").display(),
+            outside = crate_dir.join("Cargo.toml").display(),
+        );
+        let result = run(command(&script), &config, Duration::from_secs(10), None).await.unwrap();
+
+        let lines: Vec<&str> = result.stdout.lines().collect();
+        assert_eq!(lines, vec!["present", "missing"]);
+    }
+}
@@ -0,0 +1,257 @@
+//! Persistence for authentication and authorization state
+//!
+//! This module defines the repository used by `ApiKeyManager`,
+//! `AuthService` and `PermissionManager` to persist users, hashed API
+//! keys, roles, permissions, role/permission assignments and resource
+//! grants, together with an in-memory implementation used when no
+//! database is configured and in tests.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+use crate::infrastructure::security::auth::{ApiKey, User};
+use crate::infrastructure::security::permissions::{Permission, Role, UserPermissions};
+
+/// Repository for users, API keys, roles, permissions and grants
+#[async_trait]
+pub trait SecurityRepository: Send + Sync {
+    /// Insert or update a user (roles and permissions are stored as assignments)
+    async fn save_user(&self, user: &User) -> RepositoryResult<()>;
+
+    /// Get a user with resolved roles and permissions
+    async fn get_user(&self, id: Uuid) -> RepositoryResult<Option<User>>;
+
+    /// Get a user by username
+    async fn get_user_by_username(&self, username: &str) -> RepositoryResult<Option<User>>;
+
+    /// Delete a user and their assignments
+    async fn delete_user(&self, id: Uuid) -> RepositoryResult<()>;
+
+    /// Insert or update a permission
+    async fn save_permission(&self, permission: &Permission) -> RepositoryResult<()>;
+
+    /// List all permissions
+    async fn list_permissions(&self) -> RepositoryResult<Vec<Permission>>;
+
+    /// Insert or update a role
+    async fn save_role(&self, role: &Role) -> RepositoryResult<()>;
+
+    /// List all roles
+    async fn list_roles(&self) -> RepositoryResult<Vec<Role>>;
+
+    /// Get the role and direct permission assignments of a user
+    async fn get_user_permissions(&self, user_id: &str) -> RepositoryResult<Option<UserPermissions>>;
+
+    /// Assign a role to a user
+    async fn add_user_role(&self, user_id: &str, role_id: &str) -> RepositoryResult<()>;
+
+    /// Remove a role from a user
+    async fn remove_user_role(&self, user_id: &str, role_id: &str) -> RepositoryResult<()>;
+
+    /// Grant a direct permission to a user
+    async fn add_user_permission(&self, user_id: &str, permission_id: &str) -> RepositoryResult<()>;
+
+    /// Revoke a direct permission from a user
+    async fn remove_user_permission(&self, user_id: &str, permission_id: &str) -> RepositoryResult<()>;
+
+    /// Users granted access to a resource (`resource_type:resource_id`)
+    async fn get_resource_grants(&self, resource_key: &str) -> RepositoryResult<HashSet<String>>;
+
+    /// Grant a user access to a resource
+    async fn add_resource_grant(&self, resource_key: &str, user_id: &str) -> RepositoryResult<()>;
+
+    /// Revoke a user's access to a resource
+    async fn remove_resource_grant(&self, resource_key: &str, user_id: &str) -> RepositoryResult<()>;
+
+    /// Insert or update an API key
+    async fn save_api_key(&self, api_key: &ApiKey) -> RepositoryResult<()>;
+
+    /// Get an API key by ID
+    async fn get_api_key(&self, id: Uuid) -> RepositoryResult<Option<ApiKey>>;
+
+    /// Get an API key by its hash
+    async fn find_api_key_by_hash(&self, hash: &str) -> RepositoryResult<Option<ApiKey>>;
+
+    /// List the API keys of a user
+    async fn list_api_keys(&self, user_id: Uuid) -> RepositoryResult<Vec<ApiKey>>;
+
+    /// Record last use times for a batch of API keys
+    async fn touch_api_keys(&self, usages: &[(Uuid, DateTime<Utc>)]) -> RepositoryResult<()>;
+}
+
+/// In-memory security repository
+#[derive(Default)]
+pub struct InMemorySecurityRepository {
+    users: RwLock<HashMap<Uuid, User>>,
+    permissions: RwLock<HashMap<String, Permission>>,
+    roles: RwLock<HashMap<String, Role>>,
+    user_permissions: RwLock<HashMap<String, UserPermissions>>,
+    resource_grants: RwLock<HashMap<String, HashSet<String>>>,
+    api_keys: RwLock<HashMap<Uuid, ApiKey>>,
+}
+
+impl InMemorySecurityRepository {
+    /// Create an empty repository
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve role and permission assignments onto a user
+    fn resolve(&self, mut user: User) -> User {
+        let key = user.id.to_string();
+        let assignments = self.user_permissions.read().unwrap().get(&key).cloned();
+        let roles = self.roles.read().unwrap();
+        let permissions = self.permissions.read().unwrap();
+
+        if let Some(assignments) = assignments {
+            user.roles = assignments.roles.iter()
+                .filter_map(|id| roles.get(id).cloned())
+                .collect();
+            user.permissions = assignments.permissions.iter()
+                .filter_map(|id| permissions.get(id).cloned())
+                .collect();
+        } else {
+            user.roles = Vec::new();
+            user.permissions = Vec::new();
+        }
+        user
+    }
+}
+
+#[async_trait]
+impl SecurityRepository for InMemorySecurityRepository {
+    async fn save_user(&self, user: &User) -> RepositoryResult<()> {
+        let mut users = self.users.write().unwrap();
+        if users.values().any(|u| u.username == user.username && u.id != user.id) {
+            return Err(RepositoryError::AlreadyExists {
+                entity_type: "User".to_string(),
+                id: user.username.clone(),
+            });
+        }
+        users.insert(user.id, user.clone());
+        Ok(())
+    }
+
+    async fn get_user(&self, id: Uuid) -> RepositoryResult<Option<User>> {
+        let user = self.users.read().unwrap().get(&id).cloned();
+        Ok(user.map(|u| self.resolve(u)))
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> RepositoryResult<Option<User>> {
+        let user = self.users.read().unwrap().values()
+            .find(|u| u.username == username)
+            .cloned();
+        Ok(user.map(|u| self.resolve(u)))
+    }
+
+    async fn delete_user(&self, id: Uuid) -> RepositoryResult<()> {
+        self.users.write().unwrap().remove(&id);
+        self.user_permissions.write().unwrap().remove(&id.to_string());
+        Ok(())
+    }
+
+    async fn save_permission(&self, permission: &Permission) -> RepositoryResult<()> {
+        self.permissions.write().unwrap().insert(permission.id.clone(), permission.clone());
+        Ok(())
+    }
+
+    async fn list_permissions(&self) -> RepositoryResult<Vec<Permission>> {
+        Ok(self.permissions.read().unwrap().values().cloned().collect())
+    }
+
+    async fn save_role(&self, role: &Role) -> RepositoryResult<()> {
+        self.roles.write().unwrap().insert(role.id.clone(), role.clone());
+        Ok(())
+    }
+
+    async fn list_roles(&self) -> RepositoryResult<Vec<Role>> {
+        Ok(self.roles.read().unwrap().values().cloned().collect())
+    }
+
+    async fn get_user_permissions(&self, user_id: &str) -> RepositoryResult<Option<UserPermissions>> {
+        Ok(self.user_permissions.read().unwrap().get(user_id).cloned())
+    }
+
+    async fn add_user_role(&self, user_id: &str, role_id: &str) -> RepositoryResult<()> {
+        self.user_permissions.write().unwrap()
+            .entry(user_id.to_string())
+            .or_insert_with(|| UserPermissions::new(user_id))
+            .add_role(role_id);
+        Ok(())
+    }
+
+    async fn remove_user_role(&self, user_id: &str, role_id: &str) -> RepositoryResult<()> {
+        if let Some(assignments) = self.user_permissions.write().unwrap().get_mut(user_id) {
+            assignments.remove_role(role_id);
+        }
+        Ok(())
+    }
+
+    async fn add_user_permission(&self, user_id: &str, permission_id: &str) -> RepositoryResult<()> {
+        self.user_permissions.write().unwrap()
+            .entry(user_id.to_string())
+            .or_insert_with(|| UserPermissions::new(user_id))
+            .add_permission(permission_id);
+        Ok(())
+    }
+
+    async fn remove_user_permission(&self, user_id: &str, permission_id: &str) -> RepositoryResult<()> {
+        if let Some(assignments) = self.user_permissions.write().unwrap().get_mut(user_id) {
+            assignments.remove_permission(permission_id);
+        }
+        Ok(())
+    }
+
+    async fn get_resource_grants(&self, resource_key: &str) -> RepositoryResult<HashSet<String>> {
+        Ok(self.resource_grants.read().unwrap().get(resource_key).cloned().unwrap_or_default())
+    }
+
+    async fn add_resource_grant(&self, resource_key: &str, user_id: &str) -> RepositoryResult<()> {
+        self.resource_grants.write().unwrap()
+            .entry(resource_key.to_string())
+            .or_default()
+            .insert(user_id.to_string());
+        Ok(())
+    }
+
+    async fn remove_resource_grant(&self, resource_key: &str, user_id: &str) -> RepositoryResult<()> {
+        if let Some(users) = self.resource_grants.write().unwrap().get_mut(resource_key) {
+            users.remove(user_id);
+        }
+        Ok(())
+    }
+
+    async fn save_api_key(&self, api_key: &ApiKey) -> RepositoryResult<()> {
+        self.api_keys.write().unwrap().insert(api_key.id, api_key.clone());
+        Ok(())
+    }
+
+    async fn get_api_key(&self, id: Uuid) -> RepositoryResult<Option<ApiKey>> {
+        Ok(self.api_keys.read().unwrap().get(&id).cloned())
+    }
+
+    async fn find_api_key_by_hash(&self, hash: &str) -> RepositoryResult<Option<ApiKey>> {
+        Ok(self.api_keys.read().unwrap().values().find(|k| k.hash == hash).cloned())
+    }
+
+    async fn list_api_keys(&self, user_id: Uuid) -> RepositoryResult<Vec<ApiKey>> {
+        Ok(self.api_keys.read().unwrap().values()
+            .filter(|k| k.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn touch_api_keys(&self, usages: &[(Uuid, DateTime<Utc>)]) -> RepositoryResult<()> {
+        let mut api_keys = self.api_keys.write().unwrap();
+        for (id, used_at) in usages {
+            if let Some(key) = api_keys.get_mut(id) {
+                key.last_used_at = Some(*used_at);
+            }
+        }
+        Ok(())
+    }
+}
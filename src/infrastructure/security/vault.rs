@@ -0,0 +1,461 @@
+//! Encrypted credential vault
+//!
+//! Connection credentials are encrypted with the `EncryptionService` and
+//! kept in a dedicated store; data sources only carry the secret ID in
+//! `ConnectionConfig::credentials_secret_id`. Plaintext is produced by
+//! [`CredentialVault::reveal`] and [`CredentialVault::resolve`], which are
+//! meant to be called by protocol drivers while connecting and nowhere else.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock as StdRwLock};
+use thiserror::Error;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::core::domain::models::ConnectionConfig;
+use crate::core::domain::traits::repository::{RepositoryError, RepositoryResult};
+use crate::infrastructure::security::encryption::{EncryptedData, EncryptionService};
+
+/// Vault error types
+#[derive(Debug, Error)]
+pub enum VaultError {
+    /// Secret not found
+    #[error("Secret not found: {0}")]
+    NotFound(Uuid),
+
+    /// Encryption or decryption failed
+    #[error("Vault encryption error: {0}")]
+    Encryption(String),
+
+    /// Invalid secret contents
+    #[error("Invalid secret: {0}")]
+    InvalidSecret(String),
+
+    /// Storage error
+    #[error("Vault storage error: {0}")]
+    Repository(#[from] RepositoryError),
+}
+
+/// Result type for vault operations
+pub type VaultResult<T> = Result<T, VaultError>;
+
+/// Encrypted secret as stored
+#[derive(Debug, Clone)]
+pub struct SecretRecord {
+    /// Secret ID
+    pub id: Uuid,
+
+    /// Display name
+    pub name: String,
+
+    /// Names of the credential fields (e.g. `username`, `password`)
+    pub fields: Vec<String>,
+
+    /// Encrypted credential values
+    pub data: EncryptedData,
+
+    /// Creation date
+    pub created_at: DateTime<Utc>,
+
+    /// Last update date
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Secret information that is safe to show or export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretMetadata {
+    /// Secret ID
+    pub id: Uuid,
+
+    /// Display name
+    pub name: String,
+
+    /// Names of the credential fields
+    pub fields: Vec<String>,
+
+    /// Creation date
+    pub created_at: DateTime<Utc>,
+
+    /// Last update date
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<&SecretRecord> for SecretMetadata {
+    fn from(record: &SecretRecord) -> Self {
+        Self {
+            id: record.id,
+            name: record.name.clone(),
+            fields: record.fields.clone(),
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        }
+    }
+}
+
+/// Decrypted credentials
+///
+/// Deliberately not serializable; `Debug` only lists the field names.
+#[derive(Clone)]
+pub struct Credentials {
+    values: HashMap<String, String>,
+}
+
+impl Credentials {
+    /// Wrap credential values
+    pub fn new(values: HashMap<String, String>) -> Self {
+        Self { values }
+    }
+
+    /// Get a credential field
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.values.get(field).map(String::as_str)
+    }
+
+    /// The `username` field
+    pub fn username(&self) -> Option<&str> {
+        self.get("username")
+    }
+
+    /// The `password` field
+    pub fn password(&self) -> Option<&str> {
+        self.get("password")
+    }
+
+    /// Sorted field names
+    pub fn fields(&self) -> Vec<String> {
+        let mut fields: Vec<String> = self.values.keys().cloned().collect();
+        fields.sort();
+        fields
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("fields", &self.fields())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Storage for encrypted secrets
+#[async_trait]
+pub trait SecretRepository: Send + Sync {
+    /// Insert or update a secret
+    async fn save_secret(&self, secret: &SecretRecord) -> RepositoryResult<()>;
+
+    /// Get a secret by ID
+    async fn get_secret(&self, id: Uuid) -> RepositoryResult<Option<SecretRecord>>;
+
+    /// Delete a secret, returning whether it existed
+    async fn delete_secret(&self, id: Uuid) -> RepositoryResult<bool>;
+
+    /// List all secrets
+    async fn list_secrets(&self) -> RepositoryResult<Vec<SecretRecord>>;
+
+    /// Update several secrets atomically
+    async fn save_secrets(&self, secrets: &[SecretRecord]) -> RepositoryResult<()>;
+}
+
+/// In-memory secret storage
+#[derive(Default)]
+pub struct InMemorySecretRepository {
+    secrets: StdRwLock<HashMap<Uuid, SecretRecord>>,
+}
+
+impl InMemorySecretRepository {
+    /// Create an empty repository
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SecretRepository for InMemorySecretRepository {
+    async fn save_secret(&self, secret: &SecretRecord) -> RepositoryResult<()> {
+        self.secrets.write().unwrap().insert(secret.id, secret.clone());
+        Ok(())
+    }
+
+    async fn get_secret(&self, id: Uuid) -> RepositoryResult<Option<SecretRecord>> {
+        Ok(self.secrets.read().unwrap().get(&id).cloned())
+    }
+
+    async fn delete_secret(&self, id: Uuid) -> RepositoryResult<bool> {
+        Ok(self.secrets.write().unwrap().remove(&id).is_some())
+    }
+
+    async fn list_secrets(&self) -> RepositoryResult<Vec<SecretRecord>> {
+        let mut secrets: Vec<SecretRecord> = self.secrets.read().unwrap().values().cloned().collect();
+        secrets.sort_by_key(|s| s.created_at);
+        Ok(secrets)
+    }
+
+    async fn save_secrets(&self, secrets: &[SecretRecord]) -> RepositoryResult<()> {
+        let mut stored = self.secrets.write().unwrap();
+        for secret in secrets {
+            stored.insert(secret.id, secret.clone());
+        }
+        Ok(())
+    }
+}
+
+/// Vault for data source credentials
+pub struct CredentialVault {
+    /// Current encryption service; held for writing during key rotation
+    encryption: RwLock<Arc<EncryptionService>>,
+
+    /// Encrypted secret storage
+    repository: Arc<dyn SecretRepository>,
+}
+
+impl CredentialVault {
+    /// Create a new vault
+    pub fn new(encryption: Arc<EncryptionService>, repository: Arc<dyn SecretRepository>) -> Self {
+        Self {
+            encryption: RwLock::new(encryption),
+            repository,
+        }
+    }
+
+    /// Encrypt and store credentials
+    pub async fn store(&self, name: &str, values: HashMap<String, String>) -> VaultResult<SecretMetadata> {
+        let encryption = self.encryption.read().await;
+        let now = Utc::now();
+        let credentials = Credentials::new(values);
+
+        let record = SecretRecord {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            fields: credentials.fields(),
+            data: Self::seal(&encryption, &credentials)?,
+            created_at: now,
+            updated_at: now,
+        };
+        self.repository.save_secret(&record).await?;
+
+        Ok(SecretMetadata::from(&record))
+    }
+
+    /// Replace the values of an existing secret
+    pub async fn update(&self, id: Uuid, values: HashMap<String, String>) -> VaultResult<SecretMetadata> {
+        let encryption = self.encryption.read().await;
+        let mut record = self.repository.get_secret(id).await?
+            .ok_or(VaultError::NotFound(id))?;
+        let credentials = Credentials::new(values);
+
+        record.fields = credentials.fields();
+        record.data = Self::seal(&encryption, &credentials)?;
+        record.updated_at = Utc::now();
+        self.repository.save_secret(&record).await?;
+
+        Ok(SecretMetadata::from(&record))
+    }
+
+    /// Delete a secret
+    pub async fn delete(&self, id: Uuid) -> VaultResult<()> {
+        if self.repository.delete_secret(id).await? {
+            Ok(())
+        } else {
+            Err(VaultError::NotFound(id))
+        }
+    }
+
+    /// Get secret metadata
+    pub async fn metadata(&self, id: Uuid) -> VaultResult<SecretMetadata> {
+        let record = self.repository.get_secret(id).await?
+            .ok_or(VaultError::NotFound(id))?;
+        Ok(SecretMetadata::from(&record))
+    }
+
+    /// List secret metadata
+    pub async fn list(&self) -> VaultResult<Vec<SecretMetadata>> {
+        let records = self.repository.list_secrets().await?;
+        Ok(records.iter().map(SecretMetadata::from).collect())
+    }
+
+    /// Decrypt a secret
+    ///
+    /// For use by protocol drivers when opening a connection.
+    pub async fn reveal(&self, id: Uuid) -> VaultResult<Credentials> {
+        let encryption = self.encryption.read().await;
+        let record = self.repository.get_secret(id).await?
+            .ok_or(VaultError::NotFound(id))?;
+        Self::open(&encryption, &record)
+    }
+
+    /// Decrypt the credentials referenced by a connection, if any
+    ///
+    /// For use by protocol drivers when opening a connection.
+    pub async fn resolve(&self, connection: &ConnectionConfig) -> VaultResult<Option<Credentials>> {
+        match connection.credentials_secret_id {
+            Some(id) => self.reveal(id).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Re-encrypt every secret with a new key
+    ///
+    /// All secrets are decrypted with the current key and written back in a
+    /// single batch; the vault switches to the new key only once the batch
+    /// has been stored. Writes are blocked while the rotation runs.
+    pub async fn rotate_key(&self, new_encryption: Arc<EncryptionService>) -> VaultResult<usize> {
+        let mut encryption = self.encryption.write().await;
+        let records = self.repository.list_secrets().await?;
+
+        let count = self.reseal(&encryption, &new_encryption, records).await?;
+        *encryption = new_encryption;
+
+        tracing::info!("Re-encrypted {} vault secrets with a new key", count);
+        Ok(count)
+    }
+
+    /// Re-encrypt secrets that are not under the active data key
+    ///
+    /// Run after rotating the data key so older keys can be retired.
+    pub async fn reencrypt(&self) -> VaultResult<usize> {
+        let encryption = self.encryption.write().await;
+        let active_key_id = encryption.active_key_id();
+
+        let records: Vec<_> = self.repository.list_secrets().await?
+            .into_iter()
+            .filter(|record| record.data.key_id != active_key_id)
+            .collect();
+
+        let count = self.reseal(&encryption, &encryption, records).await?;
+
+        tracing::info!("Re-encrypted {} vault secrets with key {}", count, active_key_id);
+        Ok(count)
+    }
+
+    /// Decrypt secrets with one service and store them encrypted with another
+    async fn reseal(
+        &self,
+        from: &EncryptionService,
+        to: &EncryptionService,
+        mut records: Vec<SecretRecord>,
+    ) -> VaultResult<usize> {
+        let now = Utc::now();
+        for record in &mut records {
+            let credentials = Self::open(from, record)?;
+            record.data = Self::seal(to, &credentials)?;
+            record.updated_at = now;
+        }
+
+        self.repository.save_secrets(&records).await?;
+        Ok(records.len())
+    }
+
+    /// Encrypt credential values
+    fn seal(encryption: &EncryptionService, credentials: &Credentials) -> VaultResult<EncryptedData> {
+        let plaintext = serde_json::to_vec(&credentials.values)
+            .map_err(|e| VaultError::InvalidSecret(e.to_string()))?;
+        encryption.encrypt(&plaintext)
+            .map_err(|e| VaultError::Encryption(e.to_string()))
+    }
+
+    /// Decrypt credential values
+    fn open(encryption: &EncryptionService, record: &SecretRecord) -> VaultResult<Credentials> {
+        let plaintext = encryption.decrypt(&record.data)
+            .map_err(|e| VaultError::Encryption(format!("secret {}: {}", record.id, e)))?;
+        let values = serde_json::from_slice(&plaintext)
+            .map_err(|e| VaultError::InvalidSecret(e.to_string()))?;
+        Ok(Credentials::new(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials() -> HashMap<String, String> {
+        HashMap::from([
+            ("username".to_string(), "operator".to_string()),
+            ("password".to_string(), "s3cret-pa55".to_string()),
+        ])
+    }
+
+    fn create_vault(key: &str) -> (CredentialVault, Arc<InMemorySecretRepository>) {
+        let repository = Arc::new(InMemorySecretRepository::new());
+        let encryption = Arc::new(EncryptionService::new(key).unwrap());
+        (CredentialVault::new(encryption, repository.clone()), repository)
+    }
+
+    #[tokio::test]
+    async fn test_store_and_reveal() {
+        let (vault, repository) = create_vault("vault-key");
+
+        let metadata = vault.store("Broker", credentials()).await.unwrap();
+        assert_eq!(metadata.fields, vec!["password".to_string(), "username".to_string()]);
+
+        // Neither the metadata nor the stored record contain plaintext
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert!(!json.contains("s3cret-pa55"));
+        let record = repository.get_secret(metadata.id).await.unwrap().unwrap();
+        assert!(!String::from_utf8_lossy(&record.data.ciphertext).contains("s3cret-pa55"));
+
+        let revealed = vault.reveal(metadata.id).await.unwrap();
+        assert_eq!(revealed.username(), Some("operator"));
+        assert_eq!(revealed.password(), Some("s3cret-pa55"));
+        assert!(!format!("{:?}", revealed).contains("s3cret-pa55"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_connection() {
+        let (vault, _) = create_vault("vault-key");
+        let metadata = vault.store("Broker", credentials()).await.unwrap();
+
+        let mut connection = ConnectionConfig {
+            endpoint: "mqtt://localhost:1883".to_string(),
+            credentials_secret_id: None,
+            timeout_seconds: 30,
+            retry_config: Default::default(),
+            custom_params: HashMap::new(),
+        };
+        assert!(vault.resolve(&connection).await.unwrap().is_none());
+
+        connection.credentials_secret_id = Some(metadata.id);
+        let resolved = vault.resolve(&connection).await.unwrap().unwrap();
+        assert_eq!(resolved.username(), Some("operator"));
+
+        vault.delete(metadata.id).await.unwrap();
+        assert!(matches!(vault.resolve(&connection).await, Err(VaultError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_key_rotation_re_encrypts_all_secrets() {
+        let (vault, repository) = create_vault("old-key");
+        let first = vault.store("Broker", credentials()).await.unwrap();
+        let second = vault.store("PLC", HashMap::from([("token".to_string(), "abc".to_string())])).await.unwrap();
+
+        let new_encryption = Arc::new(EncryptionService::new("new-key").unwrap());
+        assert_eq!(vault.rotate_key(new_encryption.clone()).await.unwrap(), 2);
+
+        // Stored data is now readable with the new key only
+        let old_encryption = EncryptionService::new("old-key").unwrap();
+        let record = repository.get_secret(first.id).await.unwrap().unwrap();
+        assert!(old_encryption.decrypt(&record.data).is_err());
+        assert!(new_encryption.decrypt(&record.data).is_ok());
+
+        assert_eq!(vault.reveal(first.id).await.unwrap().password(), Some("s3cret-pa55"));
+        assert_eq!(vault.reveal(second.id).await.unwrap().get("token"), Some("abc"));
+    }
+
+    #[tokio::test]
+    async fn test_reencrypt_moves_secrets_to_active_key() {
+        let (vault, repository) = create_vault("vault-key");
+        let encryption = vault.encryption.read().await.clone();
+        let metadata = vault.store("Broker", credentials()).await.unwrap();
+
+        encryption.add_key("dek-2", &[9; 32], true).unwrap();
+        assert_eq!(vault.reencrypt().await.unwrap(), 1);
+        assert_eq!(vault.reencrypt().await.unwrap(), 0);
+
+        let record = repository.get_secret(metadata.id).await.unwrap().unwrap();
+        assert_eq!(record.data.key_id, "dek-2");
+        assert_eq!(vault.reveal(metadata.id).await.unwrap().password(), Some("s3cret-pa55"));
+    }
+}
@@ -0,0 +1,550 @@
+//! Operator approval for risky tool operations
+//!
+//! Each tool operation is classified by a [`RiskPolicy`]. Operations at or
+//! above the approval threshold are held by [`ApprovalGatedExecutor`] with
+//! status `Pending` until an operator approves or rejects them through the
+//! [`ApprovalManager`], or the request times out. Decisions are written to
+//! the audit log together with the approver.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{broadcast, oneshot};
+use uuid::Uuid;
+
+use crate::core::domain::models::{AgentId, ExecutionStatus, ToolId, ToolResult};
+use crate::core::domain::traits::tool_executor::{
+    ExecutionContext, ExecutionRequest, ExecutionStatusInfo, ExecutionStream, ExecutorError,
+    ExecutorResult, ResourceUsage, ToolExecutor, ToolInfo, ValidationResult,
+};
+use crate::infrastructure::security::{AuditCategory, AuditEvent, AuditLog, InMemoryAuditStore};
+
+/// Actor recorded for decisions made by the system (timeouts)
+const SYSTEM_ACTOR: &str = "system";
+
+/// Approval error types
+#[derive(Debug, Error)]
+pub enum ApprovalError {
+    /// No pending request with this ID
+    #[error("Approval request not found: {0}")]
+    NotFound(Uuid),
+}
+
+/// Risk level of a tool operation
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskLevel {
+    /// Reads data without side effects
+    Read,
+
+    /// Writes to a device or external system
+    WriteToDevice,
+
+    /// Deletes or irreversibly changes data
+    Destructive,
+}
+
+/// Classifies tool operations by risk
+///
+/// The operation is read from one request parameter (e.g. `operation` or
+/// `action`); unknown operations get the default level.
+#[derive(Debug, Clone)]
+pub struct RiskPolicy {
+    parameter: String,
+    levels: HashMap<String, RiskLevel>,
+    default: RiskLevel,
+}
+
+impl RiskPolicy {
+    /// Create a policy keyed on a request parameter
+    pub fn new(parameter: impl Into<String>, default: RiskLevel) -> Self {
+        Self {
+            parameter: parameter.into(),
+            levels: HashMap::new(),
+            default,
+        }
+    }
+
+    /// Policy that assigns the same level to every operation
+    pub fn fixed(level: RiskLevel) -> Self {
+        Self::new("", level)
+    }
+
+    /// Set the level of an operation
+    pub fn with_operation(mut self, operation: impl Into<String>, level: RiskLevel) -> Self {
+        self.levels.insert(operation.into().to_lowercase(), level);
+        self
+    }
+
+    /// Policy for `ModbusToolExecutor`
+    pub fn modbus() -> Self {
+        Self::new("operation", RiskLevel::WriteToDevice)
+            .with_operation("read", RiskLevel::Read)
+            .with_operation("read_map", RiskLevel::Read)
+            .with_operation("write", RiskLevel::WriteToDevice)
+    }
+
+    /// Policy for `MqttToolExecutor`
+    ///
+    /// Publishing writes to the devices listening on the broker.
+    pub fn mqtt() -> Self {
+        Self::new("operation", RiskLevel::WriteToDevice)
+            .with_operation("publish", RiskLevel::WriteToDevice)
+            .with_operation("subscribe", RiskLevel::Read)
+            .with_operation("unsubscribe", RiskLevel::Read)
+    }
+
+    /// Policy for `CodeExecutionToolExecutor`
+    ///
+    /// Scripts have no operation to classify, so every run is held.
+    pub fn code_execution() -> Self {
+        Self::fixed(RiskLevel::WriteToDevice)
+    }
+
+    /// Policy for `FileToolExecutor`
+    pub fn file() -> Self {
+        Self::new("action", RiskLevel::Destructive)
+            .with_operation("read", RiskLevel::Read)
+            .with_operation("write", RiskLevel::WriteToDevice)
+            .with_operation("delete", RiskLevel::Destructive)
+    }
+
+    /// Classify a request's parameters
+    pub fn classify(&self, parameters: &HashMap<String, Value>) -> RiskLevel {
+        parameters.get(&self.parameter)
+            .and_then(Value::as_str)
+            .and_then(|operation| self.levels.get(&operation.to_lowercase()))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Request shown to the operator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    /// Request ID
+    pub id: Uuid,
+
+    /// Held execution
+    pub execution_id: Uuid,
+
+    pub tool_id: ToolId,
+    pub agent_id: AgentId,
+    pub user_id: Option<String>,
+    pub risk: RiskLevel,
+    pub parameters: HashMap<String, Value>,
+    pub requested_at: DateTime<Utc>,
+
+    /// When the request is cancelled if nobody decides
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Operator decision
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    Approved { approver: String },
+    Rejected { approver: String, reason: Option<String> },
+    TimedOut,
+}
+
+/// Event emitted to the UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ApprovalEvent {
+    /// An operation is waiting for approval
+    Requested(ApprovalRequest),
+
+    /// A request was decided or expired
+    Resolved { request_id: Uuid, decision: ApprovalDecision },
+}
+
+/// Pending request and the channel that resumes it
+struct PendingApproval {
+    request: ApprovalRequest,
+    decision: oneshot::Sender<ApprovalDecision>,
+}
+
+/// Tracks pending approvals and operator decisions
+pub struct ApprovalManager {
+    /// Pending requests by ID
+    pending: Mutex<HashMap<Uuid, PendingApproval>>,
+
+    /// Events for the UI
+    events: broadcast::Sender<ApprovalEvent>,
+
+    /// Lowest risk level that requires approval
+    threshold: RiskLevel,
+
+    /// How long to wait for a decision
+    timeout: Duration,
+
+    /// Where decisions are recorded
+    audit_log: Arc<AuditLog>,
+}
+
+impl ApprovalManager {
+    /// Create a manager requiring approval for device writes and above
+    pub fn new(timeout: Duration) -> Self {
+        let (events, _) = broadcast::channel(64);
+        Self {
+            pending: Mutex::new(HashMap::new()),
+            events,
+            threshold: RiskLevel::WriteToDevice,
+            timeout,
+            audit_log: Arc::new(AuditLog::new(Arc::new(InMemoryAuditStore::new()))),
+        }
+    }
+
+    /// Set the lowest risk level that requires approval
+    pub fn with_threshold(mut self, threshold: RiskLevel) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Record decisions in the given audit log
+    pub fn with_audit_log(mut self, audit_log: Arc<AuditLog>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Whether operations of this risk level need approval
+    pub fn requires_approval(&self, risk: RiskLevel) -> bool {
+        risk >= self.threshold
+    }
+
+    /// Subscribe to approval events
+    pub fn subscribe(&self) -> broadcast::Receiver<ApprovalEvent> {
+        self.events.subscribe()
+    }
+
+    /// List requests waiting for a decision
+    pub fn pending(&self) -> Vec<ApprovalRequest> {
+        let mut requests: Vec<_> = self.pending.lock().unwrap()
+            .values()
+            .map(|pending| pending.request.clone())
+            .collect();
+        requests.sort_by_key(|request| request.requested_at);
+        requests
+    }
+
+    /// Get a pending request for an execution
+    pub fn pending_for_execution(&self, execution_id: Uuid) -> Option<ApprovalRequest> {
+        self.pending.lock().unwrap()
+            .values()
+            .find(|pending| pending.request.execution_id == execution_id)
+            .map(|pending| pending.request.clone())
+    }
+
+    /// Ask for approval and wait for the decision or the timeout
+    pub async fn request_approval(
+        &self,
+        request: &ExecutionRequest,
+        risk: RiskLevel,
+    ) -> ApprovalDecision {
+        let now = Utc::now();
+        let approval = ApprovalRequest {
+            id: Uuid::new_v4(),
+            execution_id: request.execution_id,
+            tool_id: request.tool_id,
+            agent_id: request.context.agent_id,
+            user_id: request.context.user_id.clone(),
+            risk,
+            parameters: request.parameters.clone(),
+            requested_at: now,
+            expires_at: now + chrono::Duration::from_std(self.timeout).unwrap_or_else(|_| chrono::Duration::zero()),
+        };
+        let request_id = approval.id;
+
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request_id, PendingApproval {
+            request: approval.clone(),
+            decision: sender,
+        });
+        let _ = self.events.send(ApprovalEvent::Requested(approval));
+        tracing::info!("Tool execution {} is waiting for approval ({:?})", request.execution_id, risk);
+
+        match tokio::time::timeout(self.timeout, receiver).await {
+            Ok(Ok(decision)) => decision,
+            // Timed out, or the manager dropped the request
+            _ => {
+                let expired = self.pending.lock().unwrap().remove(&request_id);
+                if let Some(expired) = expired {
+                    self.resolve(expired.request, ApprovalDecision::TimedOut).await;
+                }
+                ApprovalDecision::TimedOut
+            }
+        }
+    }
+
+    /// Approve a pending request
+    pub async fn approve(&self, request_id: Uuid, approver: &str) -> Result<(), ApprovalError> {
+        self.decide(request_id, ApprovalDecision::Approved { approver: approver.to_string() }).await
+    }
+
+    /// Reject a pending request
+    pub async fn reject(
+        &self,
+        request_id: Uuid,
+        approver: &str,
+        reason: Option<String>,
+    ) -> Result<(), ApprovalError> {
+        self.decide(request_id, ApprovalDecision::Rejected { approver: approver.to_string(), reason }).await
+    }
+
+    /// Deliver a decision to the waiting execution
+    async fn decide(&self, request_id: Uuid, decision: ApprovalDecision) -> Result<(), ApprovalError> {
+        let pending = self.pending.lock().unwrap().remove(&request_id)
+            .ok_or(ApprovalError::NotFound(request_id))?;
+
+        // Record before resuming so the audit entry precedes the execution
+        self.resolve(pending.request, decision.clone()).await;
+        let _ = pending.decision.send(decision);
+        Ok(())
+    }
+
+    /// Audit and announce a decision
+    async fn resolve(&self, request: ApprovalRequest, decision: ApprovalDecision) {
+        let (actor, action, reason) = match &decision {
+            ApprovalDecision::Approved { approver } => (approver.as_str(), "approve_tool_execution", None),
+            ApprovalDecision::Rejected { approver, reason } => (approver.as_str(), "reject_tool_execution", reason.clone()),
+            ApprovalDecision::TimedOut => (SYSTEM_ACTOR, "approval_timed_out", None),
+        };
+
+        self.audit_log.record_or_log(
+            AuditEvent::new(AuditCategory::Approval, actor, action)
+                .with_target(request.tool_id.to_string())
+                .with_details(serde_json::json!({
+                    "request_id": request.id,
+                    "execution_id": request.execution_id,
+                    "agent_id": request.agent_id,
+                    "user_id": request.user_id,
+                    "risk": request.risk,
+                    "parameters": request.parameters,
+                    "reason": reason,
+                }))
+        ).await;
+
+        let _ = self.events.send(ApprovalEvent::Resolved { request_id: request.id, decision });
+    }
+}
+
+/// Executor wrapper that holds risky operations for approval
+pub struct ApprovalGatedExecutor {
+    inner: Arc<dyn ToolExecutor>,
+    policy: RiskPolicy,
+    approvals: Arc<ApprovalManager>,
+}
+
+impl ApprovalGatedExecutor {
+    /// Wrap an executor
+    pub fn new(inner: Arc<dyn ToolExecutor>, policy: RiskPolicy, approvals: Arc<ApprovalManager>) -> Self {
+        Self {
+            inner,
+            policy,
+            approvals,
+        }
+    }
+
+    /// Wait for approval if the request needs it
+    async fn check_approval(&self, request: &ExecutionRequest) -> ExecutorResult<()> {
+        let risk = self.policy.classify(&request.parameters);
+        if !self.approvals.requires_approval(risk) {
+            return Ok(());
+        }
+
+        match self.approvals.request_approval(request, risk).await {
+            ApprovalDecision::Approved { .. } => Ok(()),
+            ApprovalDecision::Rejected { approver, reason } => Err(ExecutorError::PermissionDenied(
+                match reason {
+                    Some(reason) => format!("Rejected by {}: {}", approver, reason),
+                    None => format!("Rejected by {}", approver),
+                }
+            )),
+            ApprovalDecision::TimedOut => Err(ExecutorError::Timeout(self.approvals.timeout.as_secs())),
+        }
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for ApprovalGatedExecutor {
+    async fn execute(&self, request: ExecutionRequest) -> ExecutorResult<ToolResult> {
+        self.check_approval(&request).await?;
+        self.inner.execute(request).await
+    }
+
+    async fn execute_streaming(&self, request: ExecutionRequest) -> ExecutorResult<Box<dyn ExecutionStream>> {
+        self.check_approval(&request).await?;
+        self.inner.execute_streaming(request).await
+    }
+
+    async fn validate_parameters(
+        &self,
+        tool_id: ToolId,
+        parameters: &HashMap<String, Value>,
+    ) -> ExecutorResult<ValidationResult> {
+        self.inner.validate_parameters(tool_id, parameters).await
+    }
+
+    async fn can_execute(&self, tool_id: ToolId, context: &ExecutionContext) -> ExecutorResult<bool> {
+        self.inner.can_execute(tool_id, context).await
+    }
+
+    async fn get_execution_status(&self, execution_id: Uuid) -> ExecutorResult<ExecutionStatusInfo> {
+        match self.approvals.pending_for_execution(execution_id) {
+            Some(request) => Ok(ExecutionStatusInfo {
+                execution_id,
+                status: ExecutionStatus::Pending,
+                progress: None,
+                message: Some(format!("Waiting for operator approval ({:?})", request.risk)),
+                started_at: request.requested_at,
+                estimated_completion: None,
+                resource_usage: ResourceUsage {
+                    memory_bytes: 0,
+                    cpu_ms: 0,
+                    disk_io_bytes: 0,
+                    network_io_bytes: 0,
+                },
+                partial_results: None,
+            }),
+            None => self.inner.get_execution_status(execution_id).await,
+        }
+    }
+
+    async fn cancel_execution(&self, execution_id: Uuid) -> ExecutorResult<()> {
+        self.inner.cancel_execution(execution_id).await
+    }
+
+    async fn list_available_tools(&self, context: &ExecutionContext) -> ExecutorResult<Vec<ToolInfo>> {
+        self.inner.list_available_tools(context).await
+    }
+
+    async fn prepare_tool(&self, tool_id: ToolId) -> ExecutorResult<()> {
+        self.inner.prepare_tool(tool_id).await
+    }
+
+    async fn cleanup(&self, execution_id: Uuid) -> ExecutorResult<()> {
+        self.inner.cleanup(execution_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::traits::tool_executor::SecurityContext;
+    use crate::infrastructure::tools::FileToolExecutor;
+    use tempfile::tempdir;
+
+    fn request(action: &str) -> ExecutionRequest {
+        ExecutionRequest {
+            execution_id: Uuid::new_v4(),
+            tool_id: Uuid::new_v4(),
+            parameters: serde_json::json!({
+                "action": action,
+                "path": "test.txt",
+                "content": "Hello, world!",
+            })
+            .as_object()
+            .unwrap()
+            .clone()
+            .into_iter()
+            .collect(),
+            context: ExecutionContext {
+                agent_id: Uuid::new_v4(),
+                user_id: None,
+                conversation_id: None,
+                session_id: "test".to_string(),
+                security: SecurityContext {
+                    auth_token: None,
+                    permissions: vec!["file:write".to_string()],
+                    ip_address: None,
+                    labels: Default::default(),
+                },
+                environment: Default::default(),
+                working_directory: None,
+                metadata: Default::default(),
+            },
+            options: Default::default(),
+            callback_url: None,
+        }
+    }
+
+    fn gated_executor(timeout: Duration) -> (ApprovalGatedExecutor, Arc<ApprovalManager>, tempfile::TempDir) {
+        let temp_dir = tempdir().unwrap();
+        let inner = Arc::new(FileToolExecutor::new(
+            temp_dir.path().to_path_buf(),
+            1024 * 1024,
+            vec!["txt".to_string()],
+        ));
+        let approvals = Arc::new(ApprovalManager::new(timeout));
+        let executor = ApprovalGatedExecutor::new(inner, RiskPolicy::file(), approvals.clone());
+        (executor, approvals, temp_dir)
+    }
+
+    #[test]
+    fn test_risk_classification() {
+        let modbus = RiskPolicy::modbus();
+        let operation = |op: &str| HashMap::from([("operation".to_string(), Value::from(op))]);
+
+        assert_eq!(modbus.classify(&operation("read")), RiskLevel::Read);
+        assert_eq!(modbus.classify(&operation("WRITE")), RiskLevel::WriteToDevice);
+        assert_eq!(modbus.classify(&operation("unknown")), RiskLevel::WriteToDevice);
+        assert_eq!(RiskPolicy::file().classify(&request("delete").parameters), RiskLevel::Destructive);
+
+        let mqtt = RiskPolicy::mqtt();
+        assert_eq!(mqtt.classify(&operation("publish")), RiskLevel::WriteToDevice);
+        assert_eq!(mqtt.classify(&operation("subscribe")), RiskLevel::Read);
+        assert_eq!(mqtt.classify(&operation("unsubscribe")), RiskLevel::Read);
+    }
+
+    #[tokio::test]
+    async fn test_write_waits_for_approval() {
+        let (executor, approvals, temp_dir) = gated_executor(Duration::from_secs(5));
+        let mut events = approvals.subscribe();
+        let write = request("write");
+        let execution_id = write.execution_id;
+
+        let execution = tokio::spawn(async move { executor.execute(write).await.map(|r| r.status) });
+
+        let pending = match events.recv().await.unwrap() {
+            ApprovalEvent::Requested(pending) => pending,
+            other => panic!("unexpected event: {:?}", other),
+        };
+        assert_eq!(pending.execution_id, execution_id);
+        assert_eq!(pending.risk, RiskLevel::WriteToDevice);
+        assert!(!temp_dir.path().join("test.txt").exists());
+
+        approvals.approve(pending.id, "operator").await.unwrap();
+        assert!(execution.await.unwrap().is_ok());
+        assert!(temp_dir.path().join("test.txt").exists());
+        assert!(approvals.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rejection_and_timeout_cancel_execution() {
+        let (executor, approvals, temp_dir) = gated_executor(Duration::from_millis(200));
+        let executor = Arc::new(executor);
+        let mut events = approvals.subscribe();
+
+        let gated = executor.clone();
+        let execution = tokio::spawn(async move { gated.execute(request("write")).await.map(|r| r.status) });
+        let ApprovalEvent::Requested(pending) = events.recv().await.unwrap() else {
+            panic!("expected an approval request");
+        };
+        approvals.reject(pending.id, "operator", Some("line is running".to_string())).await.unwrap();
+
+        let error = execution.await.unwrap().unwrap_err();
+        assert!(matches!(error, ExecutorError::PermissionDenied(ref msg) if msg.contains("line is running")));
+        assert!(approvals.approve(pending.id, "operator").await.is_err());
+
+        // Nobody answers this one
+        assert!(matches!(executor.execute(request("write")).await, Err(ExecutorError::Timeout(_))));
+        assert!(approvals.pending().is_empty());
+        assert!(!temp_dir.path().join("test.txt").exists());
+    }
+}
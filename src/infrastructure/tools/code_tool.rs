@@ -0,0 +1,544 @@
+//! Sandboxed code execution tool
+//!
+//! Runs agent-authored analysis scripts for `ToolType::CodeExecution`
+//! tools through the process [`Sandbox`]. The `input` parameter (e.g. twin
+//! data or a sensor export) is written to the script's standard input as
+//! JSON. Results carry stdout, stderr and the exit code, plus stdout parsed
+//! as JSON when the script prints a JSON document.
+//!
+//! `allowed_imports` and `blocked_functions` are applied by a small Python
+//! prelude. They keep scripts to the intended libraries but are not a
+//! security boundary; containment comes from the sandbox.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::core::domain::models::{
+    ExecutionMetrics, ExecutionStatus, SandboxConfig, Tool, ToolDiagnostic, ToolDiagnosticLevel, ToolId,
+    ToolOutput, ToolResult, ToolType,
+};
+use crate::core::domain::traits::repository::{Pagination, RepositoryError, ToolRepository};
+use crate::core::domain::traits::tool_executor::{
+    ExecutionContext, ExecutionRequest, ExecutionStatusInfo, ExecutionStream, ExecutorError, ExecutorResult,
+    ResourceRequirements, ToolExecutor, ToolInfo, ValidationError, ValidationResult,
+};
+use crate::infrastructure::security::{CommandOptions, Sandbox, SandboxError};
+
+/// Largest script accepted, in bytes (scripts are passed as an argument)
+const DEFAULT_MAX_CODE_BYTES: usize = 64 * 1024;
+
+/// Runs the script with import and builtin restrictions
+///
+/// The script is `sys.argv[1]`; it runs as `__main__` with line numbers
+/// matching the submitted code.
+const PYTHON_PRELUDE: &str = r#"
+import builtins, json, sys
+_code = sys.argv.pop(1)
+_allowed = set(json.loads(sys.argv.pop(1)))
+_blocked = json.loads(sys.argv.pop(1))
+_import = builtins.__import__
+_exec, _compile = exec, compile
+
+def _guarded_import(name, globals=None, locals=None, fromlist=(), level=0):
+    # Only the script's own imports are checked, not those of the libraries it uses
+    if _allowed and level == 0 and (globals or {}).get("__name__") == "__main__":
+        if name.split(".")[0] not in _allowed:
+            raise ImportError("import of '%s' is not allowed" % name)
+    return _import(name, globals, locals, fromlist, level)
+
+builtins.__import__ = _guarded_import
+for _name in _blocked:
+    if hasattr(builtins, _name):
+        delattr(builtins, _name)
+
+_exec(_compile(_code, "<script>", "exec"), {"__name__": "__main__", "__builtins__": builtins})
+"#;
+
+/// Executor for `ToolType::CodeExecution` tools
+pub struct CodeExecutionToolExecutor {
+    /// Sandbox the interpreter runs in
+    sandbox: Arc<Sandbox>,
+
+    /// Interpreter command per language
+    interpreters: HashMap<String, String>,
+
+    /// Tool definitions, read on every call so tools added or edited after
+    /// startup take effect without a restart
+    tools: Arc<dyn ToolRepository>,
+
+    /// Largest script accepted
+    max_code_bytes: usize,
+}
+
+impl CodeExecutionToolExecutor {
+    /// Create an executor that runs Python scripts with `python3`
+    pub fn new(sandbox: Arc<Sandbox>, tools: Arc<dyn ToolRepository>) -> Self {
+        Self {
+            sandbox,
+            interpreters: HashMap::from([("python".to_string(), "python3".to_string())]),
+            tools,
+            max_code_bytes: DEFAULT_MAX_CODE_BYTES,
+        }
+    }
+
+    /// Use a specific interpreter command for Python
+    pub fn with_python_interpreter(mut self, command: impl Into<String>) -> Self {
+        self.interpreters.insert("python".to_string(), command.into());
+        self
+    }
+
+    /// Change the largest script accepted
+    pub fn with_max_code_bytes(mut self, max_code_bytes: usize) -> Self {
+        self.max_code_bytes = max_code_bytes;
+        self
+    }
+
+    /// Interpreter command for a language
+    fn interpreter(&self, language: &str) -> Option<&String> {
+        let language = language.to_lowercase();
+        let language = match language.as_str() {
+            "python3" | "py" => "python",
+            other => other,
+        };
+        self.interpreters.get(language)
+    }
+
+    /// Current definition of an enabled code execution tool
+    async fn tool(&self, tool_id: ToolId) -> ExecutorResult<Tool> {
+        let tool = self.tools.get_by_id(tool_id).await.map_err(|e| match e {
+            RepositoryError::NotFound { .. } => ExecutorError::ToolNotFound(tool_id),
+            other => ExecutorError::Other(other.to_string()),
+        })?;
+
+        if !matches!(tool.tool_type, ToolType::CodeExecution { .. }) {
+            return Err(ExecutorError::ToolNotFound(tool_id));
+        }
+        if !tool.enabled {
+            return Err(ExecutorError::ToolUnavailable {
+                tool: tool.name,
+                reason: "Tool is disabled".to_string(),
+            });
+        }
+
+        Ok(tool)
+    }
+
+    /// Whether the tool's language has an interpreter
+    fn supports(&self, tool: &Tool) -> bool {
+        matches!(&tool.tool_type, ToolType::CodeExecution { language, .. } if self.interpreter(language).is_some())
+    }
+
+    /// Extract the script from the parameters
+    fn code<'a>(&self, parameters: &'a HashMap<String, Value>) -> ExecutorResult<&'a str> {
+        let code = parameters
+            .get("code")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ExecutorError::MissingParameter {
+                tool: "code_execution".to_string(),
+                parameter: "code".to_string(),
+            })?;
+
+        if code.len() > self.max_code_bytes {
+            return Err(ExecutorError::ValidationError {
+                parameter: "code".to_string(),
+                reason: format!("Script exceeds {} bytes", self.max_code_bytes),
+            });
+        }
+
+        Ok(code)
+    }
+}
+
+/// Map sandbox failures to executor errors
+fn executor_error(error: SandboxError) -> ExecutorError {
+    match error {
+        SandboxError::PermissionDenied(message) => ExecutorError::PermissionDenied(message),
+        SandboxError::Timeout(seconds) => ExecutorError::Timeout(seconds),
+        SandboxError::ResourceLimitExceeded(message) => ExecutorError::ResourceLimitExceeded {
+            resource: "sandbox".to_string(),
+            message,
+        },
+        SandboxError::CommandExecution(message) => ExecutorError::ExecutionFailed(message),
+        other => ExecutorError::SandboxViolation(other.to_string()),
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for CodeExecutionToolExecutor {
+    async fn execute(&self, request: ExecutionRequest) -> ExecutorResult<ToolResult> {
+        let started_at = Utc::now();
+        let tool = self.tool(request.tool_id).await?;
+        let ToolType::CodeExecution { language, sandbox_config } = &tool.tool_type else {
+            return Err(ExecutorError::ToolNotFound(request.tool_id));
+        };
+        let interpreter = self.interpreter(language).cloned().ok_or_else(|| ExecutorError::ToolUnavailable {
+            tool: tool.name.clone(),
+            reason: format!("Unsupported language: {}", language),
+        })?;
+
+        let code = self.code(&request.parameters)?;
+        let input = request.parameters.get("input").cloned().unwrap_or(Value::Null);
+        let stdin = serde_json::to_vec(&input).map_err(|e| ExecutorError::InvalidParameters(e.to_string()))?;
+
+        let mut timeout = Duration::from_millis(sandbox_config.max_execution_time_ms);
+        if let Some(limit) = request.options.timeout {
+            timeout = timeout.min(limit);
+        }
+
+        let allowed = serde_json::to_string(&sandbox_config.allowed_imports).unwrap_or_default();
+        let blocked = serde_json::to_string(&sandbox_config.blocked_functions).unwrap_or_default();
+        let args = ["-I", "-c", PYTHON_PRELUDE, code, allowed.as_str(), blocked.as_str()];
+
+        let options = CommandOptions {
+            stdin: Some(stdin),
+            timeout: Some(timeout),
+            deny_network: !sandbox_config.network_access,
+        };
+
+        // Agents act on behalf of a user; unattended agents are checked by their own ID
+        let user_id = request.context.user_id.clone().unwrap_or_else(|| request.context.agent_id.to_string());
+        let output = self.sandbox
+            .execute_command_with(&interpreter, &args, options, &user_id, &tool.id.to_string())
+            .await
+            .map_err(executor_error)?;
+
+        let result = serde_json::from_str::<Value>(output.stdout.trim()).ok();
+        let mut diagnostics = Vec::new();
+        if output.exit_code != 0 {
+            diagnostics.push(ToolDiagnostic {
+                level: ToolDiagnosticLevel::Error,
+                code: "SCRIPT_FAILED".to_string(),
+                message: output.stderr.lines().last().unwrap_or("Script failed").to_string(),
+                location: None,
+                context: HashMap::new(),
+            });
+        }
+
+        let metrics = ExecutionMetrics {
+            duration_ms: output.stats.execution_time_ms,
+            memory_bytes: Some(output.stats.memory_usage_mb * 1024 * 1024),
+            cpu_ms: Some(output.stats.cpu_time_ms),
+            ..Default::default()
+        };
+
+        Ok(ToolResult {
+            execution_id: request.execution_id,
+            tool_id: request.tool_id,
+            status: if output.exit_code == 0 { ExecutionStatus::Success } else { ExecutionStatus::Failed },
+            output: ToolOutput::Json(serde_json::json!({
+                "exit_code": output.exit_code,
+                "stdout": output.stdout,
+                "stderr": output.stderr,
+                "result": result,
+            })),
+            metrics,
+            diagnostics,
+            started_at,
+            completed_at: Some(Utc::now()),
+        })
+    }
+
+    async fn execute_streaming(&self, _request: ExecutionRequest) -> ExecutorResult<Box<dyn ExecutionStream>> {
+        Err(ExecutorError::ToolUnavailable {
+            tool: "code_execution".to_string(),
+            reason: "Streaming output is not supported".to_string(),
+        })
+    }
+
+    async fn validate_parameters(
+        &self,
+        tool_id: ToolId,
+        parameters: &HashMap<String, Value>,
+    ) -> ExecutorResult<ValidationResult> {
+        self.tool(tool_id).await?;
+
+        let errors = match self.code(parameters) {
+            Ok(_) => Vec::new(),
+            Err(e) => vec![ValidationError {
+                parameter: "code".to_string(),
+                code: "INVALID_CODE".to_string(),
+                message: e.to_string(),
+                expected: Some(format!("script of at most {} bytes", self.max_code_bytes)),
+                actual: None,
+            }],
+        };
+
+        Ok(ValidationResult {
+            valid: errors.is_empty(),
+            errors,
+            warnings: Vec::new(),
+            normalized_parameters: None,
+        })
+    }
+
+    async fn can_execute(&self, tool_id: ToolId, _context: &ExecutionContext) -> ExecutorResult<bool> {
+        match self.tool(tool_id).await {
+            Ok(tool) => Ok(self.supports(&tool)),
+            Err(ExecutorError::ToolNotFound(_) | ExecutorError::ToolUnavailable { .. }) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn get_execution_status(&self, execution_id: Uuid) -> ExecutorResult<ExecutionStatusInfo> {
+        // Scripts run to completion within `execute`
+        Err(ExecutorError::Other(format!("No running execution: {}", execution_id)))
+    }
+
+    async fn cancel_execution(&self, execution_id: Uuid) -> ExecutorResult<()> {
+        Err(ExecutorError::Other(format!("No running execution: {}", execution_id)))
+    }
+
+    async fn list_available_tools(&self, _context: &ExecutionContext) -> ExecutorResult<Vec<ToolInfo>> {
+        let mut tools = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = self.tools
+                .get_available(Pagination { offset, limit: 100 })
+                .await
+                .map_err(|e| ExecutorError::Other(e.to_string()))?;
+            offset += page.items.len();
+            let done = page.items.is_empty() || offset >= page.total;
+            tools.extend(page.items.into_iter().filter(|tool| self.supports(tool)));
+            if done {
+                break;
+            }
+        }
+
+        Ok(tools
+            .iter()
+            .map(|tool| {
+                let network = matches!(
+                    &tool.tool_type,
+                    ToolType::CodeExecution { sandbox_config: SandboxConfig { network_access: true, .. }, .. }
+                );
+                ToolInfo {
+                    id: tool.id,
+                    name: tool.name.clone(),
+                    description: tool.description.clone(),
+                    tool_type: tool.tool_type.clone(),
+                    available: true,
+                    estimated_duration_ms: None,
+                    required_permissions: vec![format!("tool:{}", tool.id)],
+                    resource_requirements: ResourceRequirements {
+                        min_memory_bytes: None,
+                        estimated_cpu_ms: None,
+                        requires_network: network,
+                        requires_filesystem: false,
+                        other: HashMap::new(),
+                    },
+                }
+            })
+            .collect())
+    }
+
+    async fn prepare_tool(&self, tool_id: ToolId) -> ExecutorResult<()> {
+        self.tool(tool_id).await.map(|_| ())
+    }
+
+    async fn cleanup(&self, _execution_id: Uuid) -> ExecutorResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::core::domain::models::ExecutionId;
+    use crate::core::domain::traits::repository::{FilterCriteria, PaginatedResult, RepositoryResult, SortCriteria};
+    use crate::core::domain::traits::tool_executor::SecurityContext;
+    use crate::infrastructure::security::{PermissionManager, SandboxConfig as ProcessConfig};
+    use std::sync::Mutex;
+
+    /// Tool definitions kept in memory
+    #[derive(Default)]
+    pub(crate) struct MemoryToolRepository {
+        tools: Mutex<HashMap<ToolId, Tool>>,
+    }
+
+    #[async_trait]
+    impl ToolRepository for MemoryToolRepository {
+        async fn create(&self, tool: Tool) -> RepositoryResult<Tool> {
+            self.tools.lock().unwrap().insert(tool.id, tool.clone());
+            Ok(tool)
+        }
+
+        async fn get_by_id(&self, id: ToolId) -> RepositoryResult<Tool> {
+            self.tools.lock().unwrap().get(&id).cloned().ok_or_else(|| RepositoryError::NotFound {
+                entity_type: "Tool".to_string(),
+                id: id.to_string(),
+            })
+        }
+
+        async fn update(&self, tool: Tool) -> RepositoryResult<Tool> {
+            self.create(tool).await
+        }
+
+        async fn delete(&self, id: ToolId) -> RepositoryResult<()> {
+            self.tools.lock().unwrap().remove(&id);
+            Ok(())
+        }
+
+        async fn find(&self, _filters: Vec<FilterCriteria>, _sort: Vec<SortCriteria>, _pagination: Pagination) -> RepositoryResult<PaginatedResult<Tool>> {
+            unimplemented!()
+        }
+
+        async fn get_by_type(&self, _tool_type: &ToolType, _pagination: Pagination) -> RepositoryResult<PaginatedResult<Tool>> {
+            unimplemented!()
+        }
+
+        async fn get_available(&self, pagination: Pagination) -> RepositoryResult<PaginatedResult<Tool>> {
+            let tools: Vec<Tool> = self.tools.lock().unwrap().values().filter(|tool| tool.enabled).cloned().collect();
+            Ok(PaginatedResult {
+                total: tools.len(),
+                items: tools.into_iter().skip(pagination.offset).take(pagination.limit).collect(),
+                offset: pagination.offset,
+                limit: pagination.limit,
+            })
+        }
+
+        async fn search(&self, _query: &str, _pagination: Pagination) -> RepositoryResult<PaginatedResult<Tool>> {
+            unimplemented!()
+        }
+
+        async fn save_execution_result(&self, _result: ToolResult) -> RepositoryResult<ToolResult> {
+            unimplemented!()
+        }
+
+        async fn get_execution_by_id(&self, _execution_id: ExecutionId) -> RepositoryResult<ToolResult> {
+            unimplemented!()
+        }
+
+        async fn get_executions_by_tool_id(&self, _tool_id: ToolId, _pagination: Pagination) -> RepositoryResult<PaginatedResult<ToolResult>> {
+            unimplemented!()
+        }
+
+        async fn update_usage_stats(&self, _tool_id: ToolId, _execution_time_ms: u64, _success: bool) -> RepositoryResult<()> {
+            unimplemented!()
+        }
+    }
+
+    async fn executor() -> (CodeExecutionToolExecutor, Arc<MemoryToolRepository>, Tool) {
+        let permissions = Arc::new(PermissionManager::new());
+        permissions.assign_role("analyst", "admin").await.unwrap();
+
+        let mut config = ProcessConfig::default();
+        config.allow_process_execution = true;
+        config.resource_limits.max_memory_mb = 1024;
+        let tools = Arc::new(MemoryToolRepository::default());
+        let executor = CodeExecutionToolExecutor::new(Arc::new(Sandbox::new(config, permissions)), tools.clone());
+
+        let tool = Tool::new(
+            "stats".to_string(),
+            "Ad-hoc statistics".to_string(),
+            ToolType::CodeExecution {
+                language: "python".to_string(),
+                sandbox_config: SandboxConfig {
+                    allowed_imports: vec!["json".to_string(), "statistics".to_string(), "sys".to_string()],
+                    blocked_functions: vec!["open".to_string()],
+                    filesystem_access: false,
+                    network_access: false,
+                    max_execution_time_ms: 5000,
+                },
+            },
+        );
+        tools.create(tool.clone()).await.unwrap();
+        (executor, tools, tool)
+    }
+
+    fn request(tool: &Tool, code: &str, input: Value) -> ExecutionRequest {
+        ExecutionRequest {
+            execution_id: Uuid::new_v4(),
+            tool_id: tool.id,
+            parameters: HashMap::from([("code".to_string(), Value::from(code)), ("input".to_string(), input)]),
+            context: ExecutionContext {
+                agent_id: Uuid::new_v4(),
+                user_id: Some("analyst".to_string()),
+                conversation_id: None,
+                session_id: "test".to_string(),
+                security: SecurityContext {
+                    auth_token: None,
+                    permissions: Vec::new(),
+                    ip_address: None,
+                    labels: HashMap::new(),
+                },
+                environment: HashMap::new(),
+                working_directory: None,
+                metadata: HashMap::new(),
+            },
+            options: Default::default(),
+            callback_url: None,
+        }
+    }
+
+    fn output(result: &ToolResult) -> &Value {
+        match &result.output {
+            ToolOutput::Json(value) => value,
+            other => panic!("unexpected output: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_statistics_over_input() {
+        let (executor, _, tool) = executor().await;
+        let code = "import json, statistics, sys\n\
+                    readings = json.load(sys.stdin)['readings']\n\
+                    print(json.dumps({'mean': statistics.mean(readings), 'max': max(readings)}))";
+
+        let result = executor
+            .execute(request(&tool, code, serde_json::json!({ "readings": [1.0, 2.0, 6.0] })))
+            .await
+            .unwrap();
+
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(output(&result)["exit_code"], 0);
+        assert_eq!(output(&result)["result"], serde_json::json!({ "mean": 3.0, "max": 6.0 }));
+    }
+
+    #[tokio::test]
+    async fn test_restrictions_reported_as_failures() {
+        let (executor, _, tool) = executor().await;
+
+        let result = executor.execute(request(&tool, "import socket", Value::Null)).await.unwrap();
+        assert_eq!(result.status, ExecutionStatus::Failed);
+        assert!(output(&result)["stderr"].as_str().unwrap().contains("import of 'socket' is not allowed"));
+
+        let result = executor.execute(request(&tool, "open('/etc/passwd')", Value::Null)).await.unwrap();
+        assert!(output(&result)["stderr"].as_str().unwrap().contains("NameError"));
+        assert_eq!(result.diagnostics.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_execution_time_enforced() {
+        let (executor, tools, mut tool) = executor().await;
+        if let ToolType::CodeExecution { sandbox_config, .. } = &mut tool.tool_type {
+            sandbox_config.max_execution_time_ms = 500;
+        }
+        tools.update(tool.clone()).await.unwrap();
+
+        let error = executor.execute(request(&tool, "while True: pass", Value::Null)).await.unwrap_err();
+        assert!(matches!(error, ExecutorError::Timeout(1)));
+    }
+
+    #[tokio::test]
+    async fn test_tools_added_after_startup_are_executed() {
+        let (executor, tools, tool) = executor().await;
+        let mut added = tool.clone();
+        added.id = Uuid::new_v4();
+        added.name = "added".to_string();
+
+        let error = executor.execute(request(&added, "print(1)", Value::Null)).await.unwrap_err();
+        assert!(matches!(error, ExecutorError::ToolNotFound(_)));
+
+        tools.create(added.clone()).await.unwrap();
+        let result = executor.execute(request(&added, "print(1)", Value::Null)).await.unwrap();
+        assert_eq!(output(&result)["result"], 1);
+
+        tools.delete(added.id).await.unwrap();
+        assert!(!executor.can_execute(added.id, &request(&added, "", Value::Null).context).await.unwrap());
+    }
+}
@@ -1,21 +1,40 @@
 //! Tool executor implementations.
 
+pub mod approval;
+mod code_tool;
 mod file_tool;
 mod web_tool;
 mod modbus_tool;
+pub mod modbus_registers;
+pub mod modbus_server;
 mod mqtt_tool;
 mod twin_tool;
+pub mod sparkplug;
+pub mod virtual_sensor;
 
+pub use approval::{
+    ApprovalDecision, ApprovalEvent, ApprovalGatedExecutor, ApprovalManager, ApprovalRequest, RiskLevel,
+    RiskPolicy,
+};
+pub use code_tool::CodeExecutionToolExecutor;
 pub use file_tool::FileToolExecutor;
 pub use web_tool::WebToolExecutor;
 pub use modbus_tool::ModbusToolExecutor;
+pub use modbus_registers::{RegisterMap, RegisterMapping};
+pub use modbus_server::{
+    ModbusSimulator, ModbusSimulatorHandle, ModbusSimulatorRegistry, PropertyWrite,
+};
 pub use mqtt_tool::MqttToolExecutor;
 pub use twin_tool::TwinToolExecutor;
+pub use sparkplug::{SparkplugIngestor, SparkplugSessionTracker, SparkplugTopic, SPARKPLUG_SOURCE_ID};
+pub use virtual_sensor::{VirtualSensor, VirtualSensorConfig, VirtualSensorDriver};
 
 use async_trait::async_trait;
-use std::{sync::Arc, collections::HashMap};
+use std::{sync::Arc, collections::HashMap, time::Duration};
 use anyhow::Result;
 
+use crate::infrastructure::config::ToolConfig;
+use crate::infrastructure::transform::MappingIngestor;
 use crate::core::domain::{
     models::ToolType,
     traits::tool_executor::{
@@ -24,6 +43,75 @@ use crate::core::domain::{
     },
 };
 
+/// Tool name of the file executor
+pub const FILE_TOOL: &str = "file_tool";
+
+/// Tool name of the web executor
+pub const WEB_TOOL: &str = "web_tool";
+
+/// Tool name of the Modbus executor
+pub const MODBUS_TOOL: &str = "modbus_tool";
+
+/// Tool name of the MQTT executor
+pub const MQTT_TOOL: &str = "mqtt_tool";
+
+/// Tool name of the code execution executor
+pub const CODE_TOOL: &str = "code_execution";
+
+/// Executors of the built-in tools by tool name
+///
+/// Modbus writes, MQTT publishes and file writes and deletes go through
+/// `approvals` before they reach the device, the broker or the disk. Scripts run in the `code` executor's
+/// sandbox and are always held for approval. Modbus reads of a twin's
+/// register map are applied to the twin through `mapping`, if given.
+pub fn builtin_executors(
+    config: &ToolConfig,
+    mqtt: Arc<MqttToolExecutor>,
+    code: Arc<CodeExecutionToolExecutor>,
+    mapping: Option<Arc<MappingIngestor>>,
+    approvals: Arc<ApprovalManager>,
+) -> ExecutorResult<HashMap<String, Arc<dyn ToolExecutor>>> {
+    let file = Arc::new(FileToolExecutor::new(
+        config.file.base_path.clone(),
+        config.file.max_file_size,
+        config.file.allowed_extensions.clone(),
+    ));
+    let mut modbus = ModbusToolExecutor::new(
+        Duration::from_secs(config.modbus.timeout_seconds),
+        config.modbus.max_retries,
+    );
+    if let Some(mapping) = mapping {
+        modbus = modbus.with_mapping(mapping);
+    }
+    let modbus = Arc::new(modbus);
+    let web = WebToolExecutor::new(
+        config.web.max_response_size,
+        config.web.allowed_domains.clone(),
+        Duration::from_secs(config.web.timeout_seconds),
+    ).map_err(|e| ExecutorError::Other(e.to_string()))?;
+
+    let mut executors: HashMap<String, Arc<dyn ToolExecutor>> = HashMap::new();
+    executors.insert(
+        FILE_TOOL.to_string(),
+        Arc::new(ApprovalGatedExecutor::new(file, RiskPolicy::file(), approvals.clone())),
+    );
+    executors.insert(
+        MODBUS_TOOL.to_string(),
+        Arc::new(ApprovalGatedExecutor::new(modbus, RiskPolicy::modbus(), approvals.clone())),
+    );
+    executors.insert(
+        CODE_TOOL.to_string(),
+        Arc::new(ApprovalGatedExecutor::new(code, RiskPolicy::code_execution(), approvals.clone())),
+    );
+    executors.insert(
+        MQTT_TOOL.to_string(),
+        Arc::new(ApprovalGatedExecutor::new(mqtt, RiskPolicy::mqtt(), approvals)),
+    );
+    executors.insert(WEB_TOOL.to_string(), Arc::new(web));
+
+    Ok(executors)
+}
+
 /// Default tool executor factory
 pub struct DefaultToolExecutorFactory {
     registry: Arc<DefaultToolExecutorRegistry>,
@@ -111,8 +199,14 @@ impl ToolExecutorRegistry for DefaultToolExecutorRegistry {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::code_tool::tests::MemoryToolRepository;
+    use crate::core::domain::traits::repository::ToolRepository;
+    use crate::core::domain::traits::tool_executor::{ExecutionContext, ExecutionRequest, SecurityContext};
+    use crate::infrastructure::config::AppConfig;
+    use crate::infrastructure::security::{PermissionManager, Sandbox, SandboxConfig};
     use std::time::Duration;
     use tempfile::tempdir;
+    use uuid::Uuid;
 
     #[tokio::test]
     async fn test_executor_registry() {
@@ -147,4 +241,105 @@ mod tests {
         assert!(registry.get_executor("web").await.is_ok());
         assert!(registry.get_executor("unknown").await.is_err());
     }
-}
\ No newline at end of file
+
+    fn request(tool_id: Uuid, parameters: &[(&str, serde_json::Value)]) -> ExecutionRequest {
+        ExecutionRequest {
+            execution_id: Uuid::new_v4(),
+            tool_id,
+            parameters: parameters.iter().map(|(k, v)| (k.to_string(), v.clone())).collect(),
+            context: ExecutionContext {
+                agent_id: Uuid::new_v4(),
+                user_id: None,
+                conversation_id: None,
+                session_id: "test".to_string(),
+                security: SecurityContext {
+                    auth_token: None,
+                    permissions: vec!["file:write".to_string()],
+                    ip_address: None,
+                    labels: Default::default(),
+                },
+                environment: Default::default(),
+                working_directory: None,
+                metadata: Default::default(),
+            },
+            options: Default::default(),
+            callback_url: None,
+        }
+    }
+
+    fn executors(base_path: &std::path::Path, approvals: Arc<ApprovalManager>) -> (HashMap<String, Arc<dyn ToolExecutor>>, Arc<MemoryToolRepository>) {
+        let mut config = AppConfig::default().tools;
+        config.file.base_path = base_path.to_path_buf();
+        config.file.allowed_extensions = vec!["txt".to_string()];
+        let mqtt = Arc::new(MqttToolExecutor::from_config(&config.mqtt));
+        let tools = Arc::new(MemoryToolRepository::default());
+        let code = Arc::new(CodeExecutionToolExecutor::new(
+            Arc::new(Sandbox::new(SandboxConfig::default(), Arc::new(PermissionManager::new()))),
+            tools.clone(),
+        ));
+
+        (builtin_executors(&config, mqtt, code, None, approvals).unwrap(), tools)
+    }
+
+    #[tokio::test]
+    async fn test_builtin_file_writes_wait_for_approval() {
+        let temp_dir = tempdir().unwrap();
+        let approvals = Arc::new(ApprovalManager::new(Duration::from_secs(5)));
+        let (executors, _) = executors(temp_dir.path(), approvals.clone());
+        let file = executors[FILE_TOOL].clone();
+        let mut events = approvals.subscribe();
+
+        let write = request(Uuid::new_v4(), &[
+            ("action", serde_json::json!("write")),
+            ("path", serde_json::json!("setpoints.txt")),
+            ("content", serde_json::json!("42")),
+        ]);
+        let execution = tokio::spawn(async move { file.execute(write).await.map(|r| r.status) });
+
+        let ApprovalEvent::Requested(pending) = events.recv().await.unwrap() else {
+            panic!("expected an approval request");
+        };
+        assert!(!temp_dir.path().join("setpoints.txt").exists());
+
+        approvals.approve(pending.id, "operator").await.unwrap();
+        assert!(execution.await.unwrap().is_ok());
+        assert!(temp_dir.path().join("setpoints.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_builtin_code_execution_waits_for_approval() {
+        use crate::core::domain::models::{SandboxConfig as ScriptSandbox, Tool};
+
+        let temp_dir = tempdir().unwrap();
+        let approvals = Arc::new(ApprovalManager::new(Duration::from_secs(5)));
+        let (executors, tools) = executors(temp_dir.path(), approvals.clone());
+        let tool = Tool::new(
+            "stats".to_string(),
+            "Ad-hoc statistics".to_string(),
+            ToolType::CodeExecution {
+                language: "python".to_string(),
+                sandbox_config: ScriptSandbox {
+                    allowed_imports: vec!["json".to_string()],
+                    blocked_functions: vec!["open".to_string()],
+                    filesystem_access: false,
+                    network_access: false,
+                    max_execution_time_ms: 5000,
+                },
+            },
+        );
+        let tool_id = tool.id;
+        tools.create(tool).await.unwrap();
+        let code = executors[CODE_TOOL].clone();
+        let mut events = approvals.subscribe();
+
+        let script = request(tool_id, &[("code", serde_json::json!("print(1)"))]);
+        let execution = tokio::spawn(async move { code.execute(script).await.map(|r| r.status) });
+
+        let ApprovalEvent::Requested(pending) = events.recv().await.unwrap() else {
+            panic!("expected an approval request");
+        };
+        approvals.reject(pending.id, "operator", None).await.unwrap();
+
+        assert!(matches!(execution.await.unwrap(), Err(ExecutorError::PermissionDenied(_))));
+    }
+}
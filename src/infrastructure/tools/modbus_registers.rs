@@ -0,0 +1,413 @@
+//! Modbus register maps.
+//!
+//! A register map describes how twin properties are laid out in a device's
+//! Modbus address space: which table and address each property lives at, how
+//! many words it spans, its word order and the linear scaling between raw
+//! register contents and engineering values. The same format is used by the
+//! polling client (`ModbusToolExecutor`'s `read_map` operation) and by the
+//! twin-driven Modbus server simulator, and is stored in a data source's
+//! `ConnectionConfig.custom_params["register_map"]`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Result type for register map operations
+pub type RegisterMapResult<T> = Result<T, RegisterMapError>;
+
+/// Register map error types
+#[derive(Debug, thiserror::Error)]
+pub enum RegisterMapError {
+    /// Register map could not be parsed
+    #[error("Invalid register map: {0}")]
+    InvalidMap(String),
+
+    /// Two mappings occupy the same register
+    #[error("Register overlap at {register_type:?} {address} between {first} and {second}")]
+    Overlap {
+        register_type: RegisterType,
+        address: u16,
+        first: String,
+        second: String,
+    },
+
+    /// A value cannot be represented by its mapping
+    #[error("Invalid value for {property}: {reason}")]
+    InvalidValue { property: String, reason: String },
+}
+
+/// Modbus register tables
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RegisterType {
+    /// Read/write single bits (function codes 1, 5, 15)
+    Coil,
+
+    /// Read-only single bits (function code 2)
+    DiscreteInput,
+
+    /// Read-only 16-bit words (function code 4)
+    InputRegister,
+
+    /// Read/write 16-bit words (function codes 3, 6, 16)
+    HoldingRegister,
+}
+
+impl RegisterType {
+    /// Whether the table holds single bits rather than words
+    pub fn is_bit(&self) -> bool {
+        matches!(self, Self::Coil | Self::DiscreteInput)
+    }
+
+    /// Whether Modbus clients may write to the table
+    pub fn is_writable(&self) -> bool {
+        matches!(self, Self::Coil | Self::HoldingRegister)
+    }
+}
+
+/// Encoding of a value in the register table
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegisterDataType {
+    Bool,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    F64,
+}
+
+impl RegisterDataType {
+    /// Number of 16-bit words (or bits for coils) the value occupies
+    pub fn word_count(&self) -> u16 {
+        match self {
+            Self::Bool | Self::U16 | Self::I16 => 1,
+            Self::U32 | Self::I32 | Self::F32 => 2,
+            Self::F64 => 4,
+        }
+    }
+}
+
+/// Order of 16-bit words for multi-word values
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WordOrder {
+    /// Most significant word first (Modbus convention)
+    BigEndian,
+
+    /// Least significant word first ("word swapped")
+    LittleEndian,
+}
+
+impl Default for WordOrder {
+    fn default() -> Self {
+        Self::BigEndian
+    }
+}
+
+/// Mapping of one twin property onto Modbus registers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterMapping {
+    /// Twin property name
+    pub property: String,
+
+    /// Register table
+    pub register_type: RegisterType,
+
+    /// Starting address (zero-based)
+    pub address: u16,
+
+    /// Value encoding
+    #[serde(default = "default_data_type")]
+    pub data_type: RegisterDataType,
+
+    /// Word order for multi-word values
+    #[serde(default)]
+    pub word_order: WordOrder,
+
+    /// Engineering value = raw * scale + offset
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+
+    /// Engineering value = raw * scale + offset
+    #[serde(default)]
+    pub offset: f64,
+
+    /// Whether Modbus clients may write this property
+    #[serde(default)]
+    pub writable: bool,
+
+    /// Engineering unit
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+/// Raw register contents for a mapping
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegisterValue {
+    Bit(bool),
+    Words(Vec<u16>),
+}
+
+/// Register map for a Modbus device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterMap {
+    /// Modbus unit (slave) identifier
+    #[serde(default = "default_unit_id")]
+    pub unit_id: u8,
+
+    /// Property mappings
+    pub mappings: Vec<RegisterMapping>,
+}
+
+fn default_data_type() -> RegisterDataType {
+    RegisterDataType::U16
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+fn default_unit_id() -> u8 {
+    1
+}
+
+impl RegisterMap {
+    /// Parse and validate a register map from JSON
+    pub fn from_value(value: &Value) -> RegisterMapResult<Self> {
+        let map: Self = serde_json::from_value(value.clone())
+            .map_err(|e| RegisterMapError::InvalidMap(e.to_string()))?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    /// Check data types against register tables and detect overlapping mappings
+    pub fn validate(&self) -> RegisterMapResult<()> {
+        let mut occupied: HashMap<(RegisterType, u16), &str> = HashMap::new();
+
+        for mapping in &self.mappings {
+            let bit_type = mapping.data_type == RegisterDataType::Bool;
+            if mapping.register_type.is_bit() != bit_type {
+                return Err(RegisterMapError::InvalidMap(format!(
+                    "{} uses {:?} on {:?}",
+                    mapping.property, mapping.data_type, mapping.register_type,
+                )));
+            }
+
+            if mapping.writable && !mapping.register_type.is_writable() {
+                return Err(RegisterMapError::InvalidMap(format!(
+                    "{} is marked writable but {:?} is read-only",
+                    mapping.property, mapping.register_type,
+                )));
+            }
+
+            if mapping.scale == 0.0 {
+                return Err(RegisterMapError::InvalidMap(format!(
+                    "{} has a zero scale", mapping.property,
+                )));
+            }
+
+            for address in mapping.addresses() {
+                if let Some(first) = occupied.insert((mapping.register_type, address), &mapping.property) {
+                    return Err(RegisterMapError::Overlap {
+                        register_type: mapping.register_type,
+                        address,
+                        first: first.to_string(),
+                        second: mapping.property.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find the mapping for a property
+    pub fn mapping(&self, property: &str) -> Option<&RegisterMapping> {
+        self.mappings.iter().find(|m| m.property == property)
+    }
+}
+
+impl RegisterMapping {
+    /// Addresses occupied by the mapping
+    pub fn addresses(&self) -> std::ops::Range<u16> {
+        self.address..self.address.saturating_add(self.data_type.word_count())
+    }
+
+    /// Encode an engineering value into raw register contents
+    pub fn encode(&self, value: &Value) -> RegisterMapResult<RegisterValue> {
+        if self.data_type == RegisterDataType::Bool {
+            let bit = match value {
+                Value::Bool(b) => *b,
+                Value::Number(n) => n.as_f64().map_or(false, |v| v != 0.0),
+                _ => return Err(self.invalid("expected a boolean")),
+            };
+            return Ok(RegisterValue::Bit(bit));
+        }
+
+        let engineering = match value {
+            Value::Number(n) => n.as_f64().ok_or_else(|| self.invalid("expected a number"))?,
+            Value::Bool(b) => if *b { 1.0 } else { 0.0 },
+            _ => return Err(self.invalid("expected a number")),
+        };
+        let raw = (engineering - self.offset) / self.scale;
+
+        let words = match self.data_type {
+            RegisterDataType::U16 => vec![self.integer::<u16>(raw, 0.0, u16::MAX as f64)? as u16],
+            RegisterDataType::I16 => vec![self.integer::<i16>(raw, i16::MIN as f64, i16::MAX as f64)? as i16 as u16],
+            RegisterDataType::U32 => {
+                let v = self.integer::<u32>(raw, 0.0, u32::MAX as f64)? as u32;
+                split_words(&v.to_be_bytes())
+            },
+            RegisterDataType::I32 => {
+                let v = self.integer::<i32>(raw, i32::MIN as f64, i32::MAX as f64)? as i32;
+                split_words(&v.to_be_bytes())
+            },
+            RegisterDataType::F32 => split_words(&(raw as f32).to_be_bytes()),
+            RegisterDataType::F64 => split_words(&raw.to_be_bytes()),
+            RegisterDataType::Bool => unreachable!(),
+        };
+
+        Ok(RegisterValue::Words(self.order(words)))
+    }
+
+    /// Decode raw register contents into an engineering value
+    pub fn decode(&self, raw: &RegisterValue) -> RegisterMapResult<Value> {
+        let words = match raw {
+            RegisterValue::Bit(bit) => return Ok(Value::Bool(*bit)),
+            RegisterValue::Words(words) => words,
+        };
+
+        if words.len() != self.data_type.word_count() as usize {
+            return Err(self.invalid(&format!(
+                "expected {} registers, got {}",
+                self.data_type.word_count(), words.len(),
+            )));
+        }
+
+        let bytes: Vec<u8> = self.order(words.clone())
+            .iter()
+            .flat_map(|w| w.to_be_bytes())
+            .collect();
+
+        let raw = match self.data_type {
+            RegisterDataType::U16 => u16::from_be_bytes([bytes[0], bytes[1]]) as f64,
+            RegisterDataType::I16 => i16::from_be_bytes([bytes[0], bytes[1]]) as f64,
+            RegisterDataType::U32 => u32::from_be_bytes(bytes[..4].try_into().unwrap()) as f64,
+            RegisterDataType::I32 => i32::from_be_bytes(bytes[..4].try_into().unwrap()) as f64,
+            RegisterDataType::F32 => f32::from_be_bytes(bytes[..4].try_into().unwrap()) as f64,
+            RegisterDataType::F64 => f64::from_be_bytes(bytes[..8].try_into().unwrap()),
+            RegisterDataType::Bool => unreachable!(),
+        };
+
+        let engineering = raw * self.scale + self.offset;
+        serde_json::Number::from_f64(engineering)
+            .map(Value::Number)
+            .ok_or_else(|| self.invalid("value is not finite"))
+    }
+
+    /// Convert between big-endian word order and the mapping's word order
+    fn order(&self, mut words: Vec<u16>) -> Vec<u16> {
+        if self.word_order == WordOrder::LittleEndian {
+            words.reverse();
+        }
+        words
+    }
+
+    fn integer<T>(&self, raw: f64, min: f64, max: f64) -> RegisterMapResult<f64> {
+        let rounded = raw.round();
+        if !rounded.is_finite() || rounded < min || rounded > max {
+            return Err(self.invalid(&format!(
+                "{} is outside the range of {}",
+                raw, std::any::type_name::<T>(),
+            )));
+        }
+        Ok(rounded)
+    }
+
+    fn invalid(&self, reason: &str) -> RegisterMapError {
+        RegisterMapError::InvalidValue {
+            property: self.property.clone(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+fn split_words(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(data_type: RegisterDataType, word_order: WordOrder) -> RegisterMapping {
+        RegisterMapping {
+            property: "temperature".to_string(),
+            register_type: RegisterType::HoldingRegister,
+            address: 10,
+            data_type,
+            word_order,
+            scale: 1.0,
+            offset: 0.0,
+            writable: false,
+            unit: None,
+        }
+    }
+
+    #[test]
+    fn test_float_round_trip_with_word_order() {
+        let big = mapping(RegisterDataType::F32, WordOrder::BigEndian);
+        let little = mapping(RegisterDataType::F32, WordOrder::LittleEndian);
+
+        let raw_big = big.encode(&serde_json::json!(21.5)).unwrap();
+        let raw_little = little.encode(&serde_json::json!(21.5)).unwrap();
+        assert_eq!(raw_big, RegisterValue::Words(vec![0x41AC, 0x0000]));
+        assert_eq!(raw_little, RegisterValue::Words(vec![0x0000, 0x41AC]));
+
+        assert_eq!(little.decode(&raw_little).unwrap(), serde_json::json!(21.5));
+    }
+
+    #[test]
+    fn test_scaled_integer() {
+        let mut scaled = mapping(RegisterDataType::I16, WordOrder::BigEndian);
+        scaled.scale = 0.1;
+
+        let raw = scaled.encode(&serde_json::json!(-12.3)).unwrap();
+        assert_eq!(raw, RegisterValue::Words(vec![(-123i16) as u16]));
+        assert_eq!(scaled.decode(&raw).unwrap().as_f64().unwrap().round(), -12.0);
+
+        assert!(scaled.encode(&serde_json::json!(5000.0)).is_err());
+    }
+
+    #[test]
+    fn test_register_map_validation() {
+        let map = RegisterMap::from_value(&serde_json::json!({
+            "mappings": [
+                { "property": "speed", "register_type": "holding_register", "address": 0, "data_type": "f32" },
+                { "property": "setpoint", "register_type": "holding_register", "address": 1 },
+            ]
+        }));
+        assert!(matches!(map, Err(RegisterMapError::Overlap { address: 1, .. })));
+
+        let map = RegisterMap::from_value(&serde_json::json!({
+            "mappings": [
+                { "property": "running", "register_type": "coil", "address": 0, "data_type": "u16" },
+            ]
+        }));
+        assert!(matches!(map, Err(RegisterMapError::InvalidMap(_))));
+
+        let map = RegisterMap::from_value(&serde_json::json!({
+            "unit_id": 3,
+            "mappings": [
+                { "property": "running", "register_type": "coil", "address": 0, "data_type": "bool", "writable": true },
+                { "property": "speed", "register_type": "input_register", "address": 0, "data_type": "f32" },
+            ]
+        })).unwrap();
+        assert_eq!(map.unit_id, 3);
+        assert_eq!(map.mapping("speed").unwrap().addresses(), 0..2);
+    }
+}
@@ -0,0 +1,719 @@
+//! Modbus TCP server simulator.
+//!
+//! Exposes a digital twin as a Modbus TCP slave so SCADA/HMI tools and the
+//! polling client can be exercised without real hardware. Register contents
+//! are driven by the twin's properties (or by simulation outputs) through a
+//! [`RegisterMap`], and client writes to writable coils/holding registers are
+//! decoded back into property values and published to subscribers.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, info, warn};
+
+use crate::core::domain::models::{DigitalTwin, TwinId};
+use super::modbus_registers::{
+    RegisterMap, RegisterMapping, RegisterMapResult, RegisterType, RegisterValue,
+};
+
+/// Maximum number of bits in a single read request
+const MAX_READ_BITS: u16 = 2000;
+
+/// Maximum number of registers in a single read request
+const MAX_READ_REGISTERS: u16 = 125;
+
+/// Maximum number of bits in a single write request
+const MAX_WRITE_BITS: u16 = 1968;
+
+/// Maximum number of registers in a single write request
+const MAX_WRITE_REGISTERS: u16 = 123;
+
+/// Modbus exception codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ModbusException {
+    IllegalFunction = 0x01,
+    IllegalDataAddress = 0x02,
+    IllegalDataValue = 0x03,
+    GatewayTargetFailed = 0x0B,
+}
+
+/// Property value written by a Modbus client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropertyWrite {
+    /// Twin the simulator serves
+    pub twin_id: TwinId,
+
+    /// Property that was written
+    pub property: String,
+
+    /// Decoded engineering value
+    pub value: Value,
+
+    /// Peer that performed the write
+    pub peer: Option<SocketAddr>,
+
+    /// Time of the write
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Register tables backing the simulator
+#[derive(Debug, Default)]
+struct RegisterBank {
+    coils: BTreeMap<u16, bool>,
+    discrete_inputs: BTreeMap<u16, bool>,
+    input_registers: BTreeMap<u16, u16>,
+    holding_registers: BTreeMap<u16, u16>,
+}
+
+impl RegisterBank {
+    fn from_map(map: &RegisterMap) -> Self {
+        let mut bank = Self::default();
+        for mapping in &map.mappings {
+            for address in mapping.addresses() {
+                match mapping.register_type {
+                    RegisterType::Coil => { bank.coils.insert(address, false); },
+                    RegisterType::DiscreteInput => { bank.discrete_inputs.insert(address, false); },
+                    RegisterType::InputRegister => { bank.input_registers.insert(address, 0); },
+                    RegisterType::HoldingRegister => { bank.holding_registers.insert(address, 0); },
+                }
+            }
+        }
+        bank
+    }
+
+    fn bits(&self, register_type: RegisterType) -> &BTreeMap<u16, bool> {
+        match register_type {
+            RegisterType::Coil => &self.coils,
+            _ => &self.discrete_inputs,
+        }
+    }
+
+    fn words(&self, register_type: RegisterType) -> &BTreeMap<u16, u16> {
+        match register_type {
+            RegisterType::HoldingRegister => &self.holding_registers,
+            _ => &self.input_registers,
+        }
+    }
+
+    fn read_bits(&self, register_type: RegisterType, address: u16, quantity: u16) -> Option<Vec<bool>> {
+        let table = self.bits(register_type);
+        (0..quantity)
+            .map(|i| address.checked_add(i).and_then(|a| table.get(&a).copied()))
+            .collect()
+    }
+
+    fn read_words(&self, register_type: RegisterType, address: u16, quantity: u16) -> Option<Vec<u16>> {
+        let table = self.words(register_type);
+        (0..quantity)
+            .map(|i| address.checked_add(i).and_then(|a| table.get(&a).copied()))
+            .collect()
+    }
+
+    fn store(&mut self, mapping: &RegisterMapping, value: RegisterValue) {
+        match value {
+            RegisterValue::Bit(bit) => {
+                let table = match mapping.register_type {
+                    RegisterType::Coil => &mut self.coils,
+                    _ => &mut self.discrete_inputs,
+                };
+                table.insert(mapping.address, bit);
+            },
+            RegisterValue::Words(words) => {
+                let table = match mapping.register_type {
+                    RegisterType::HoldingRegister => &mut self.holding_registers,
+                    _ => &mut self.input_registers,
+                };
+                for (address, word) in mapping.addresses().zip(words) {
+                    table.insert(address, word);
+                }
+            },
+        }
+    }
+
+    fn load(&self, mapping: &RegisterMapping) -> Option<RegisterValue> {
+        let count = mapping.data_type.word_count();
+        if mapping.register_type.is_bit() {
+            self.read_bits(mapping.register_type, mapping.address, 1)
+                .map(|bits| RegisterValue::Bit(bits[0]))
+        } else {
+            self.read_words(mapping.register_type, mapping.address, count)
+                .map(RegisterValue::Words)
+        }
+    }
+}
+
+/// Modbus TCP slave simulating a digital twin
+pub struct ModbusSimulator {
+    twin_id: TwinId,
+    register_map: RegisterMap,
+    bank: RwLock<RegisterBank>,
+    writes: broadcast::Sender<PropertyWrite>,
+}
+
+impl ModbusSimulator {
+    /// Create a simulator for a twin using the given register map
+    pub fn new(twin_id: TwinId, register_map: RegisterMap) -> RegisterMapResult<Self> {
+        register_map.validate()?;
+        let bank = RegisterBank::from_map(&register_map);
+        let (writes, _) = broadcast::channel(256);
+
+        Ok(Self {
+            twin_id,
+            register_map,
+            bank: RwLock::new(bank),
+            writes,
+        })
+    }
+
+    /// Twin the simulator serves
+    pub fn twin_id(&self) -> TwinId {
+        self.twin_id
+    }
+
+    /// Register map in use
+    pub fn register_map(&self) -> &RegisterMap {
+        &self.register_map
+    }
+
+    /// Subscribe to property writes made by Modbus clients
+    pub fn subscribe_writes(&self) -> broadcast::Receiver<PropertyWrite> {
+        self.writes.subscribe()
+    }
+
+    /// Refresh registers from the twin's current properties.
+    ///
+    /// Properties are looked up in measurements, then computed values, then
+    /// attributes. Returns the properties that could not be found or encoded.
+    pub fn apply_twin(&self, twin: &DigitalTwin) -> Vec<String> {
+        let mut skipped = Vec::new();
+        let mut bank = self.bank.write().unwrap();
+
+        for mapping in &self.register_map.mappings {
+            let value = twin.properties.measurements.get(&mapping.property)
+                .map(|m| m.value.clone())
+                .or_else(|| twin.properties.computed.get(&mapping.property).cloned())
+                .or_else(|| twin.properties.attributes.get(&mapping.property).cloned());
+
+            match value.map(|v| mapping.encode(&v)) {
+                Some(Ok(raw)) => bank.store(mapping, raw),
+                Some(Err(e)) => {
+                    warn!("Skipping {} for Modbus simulator: {}", mapping.property, e);
+                    skipped.push(mapping.property.clone());
+                },
+                None => skipped.push(mapping.property.clone()),
+            }
+        }
+
+        skipped
+    }
+
+    /// Refresh registers from property values, e.g. simulation outputs.
+    ///
+    /// Values for unmapped properties are ignored.
+    pub fn apply_values(&self, values: &HashMap<String, Value>) -> RegisterMapResult<()> {
+        let encoded = self.register_map.mappings.iter()
+            .filter_map(|m| values.get(&m.property).map(|v| m.encode(v).map(|raw| (m, raw))))
+            .collect::<RegisterMapResult<Vec<_>>>()?;
+
+        let mut bank = self.bank.write().unwrap();
+        for (mapping, raw) in encoded {
+            bank.store(mapping, raw);
+        }
+        Ok(())
+    }
+
+    /// Decode the current register contents into property values
+    pub fn property_values(&self) -> HashMap<String, Value> {
+        let bank = self.bank.read().unwrap();
+        self.register_map.mappings.iter()
+            .filter_map(|m| {
+                let raw = bank.load(m)?;
+                m.decode(&raw).ok().map(|v| (m.property.clone(), v))
+            })
+            .collect()
+    }
+
+    /// Start serving Modbus TCP on the given address
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> std::io::Result<ModbusSimulatorHandle> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        info!("Modbus simulator for twin {} listening on {}", self.twin_id, local_addr);
+
+        let task = tokio::spawn(async move {
+            // Client connections are aborted together with the accept loop
+            let mut connections = JoinSet::new();
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, peer)) => {
+                            debug!("Modbus client connected: {}", peer);
+                            let simulator = self.clone();
+                            connections.spawn(async move {
+                                if let Err(e) = simulator.handle_connection(stream, peer).await {
+                                    debug!("Modbus client {} disconnected: {}", peer, e);
+                                }
+                            });
+                        },
+                        Err(e) => warn!("Modbus simulator accept failed: {}", e),
+                    },
+                    Some(_) = connections.join_next(), if !connections.is_empty() => {},
+                }
+            }
+        });
+
+        Ok(ModbusSimulatorHandle { local_addr, task })
+    }
+
+    /// Serve requests from a single client until it disconnects
+    async fn handle_connection(&self, mut stream: TcpStream, peer: SocketAddr) -> std::io::Result<()> {
+        let mut header = [0u8; 7];
+        loop {
+            stream.read_exact(&mut header).await?;
+
+            let transaction_id = u16::from_be_bytes([header[0], header[1]]);
+            let protocol_id = u16::from_be_bytes([header[2], header[3]]);
+            let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+            let unit_id = header[6];
+
+            if protocol_id != 0 || length < 2 || length > 254 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "invalid MBAP header",
+                ));
+            }
+
+            let mut pdu = vec![0u8; length - 1];
+            stream.read_exact(&mut pdu).await?;
+
+            let response = if self.accepts_unit(unit_id) {
+                self.process_pdu(&pdu, Some(peer))
+            } else {
+                exception_response(pdu[0], ModbusException::GatewayTargetFailed)
+            };
+
+            let mut frame = Vec::with_capacity(7 + response.len());
+            frame.extend_from_slice(&transaction_id.to_be_bytes());
+            frame.extend_from_slice(&0u16.to_be_bytes());
+            frame.extend_from_slice(&((response.len() + 1) as u16).to_be_bytes());
+            frame.push(unit_id);
+            frame.extend_from_slice(&response);
+            stream.write_all(&frame).await?;
+        }
+    }
+
+    /// Unit 0 and 255 are commonly used by TCP clients to address "the device"
+    fn accepts_unit(&self, unit_id: u8) -> bool {
+        unit_id == self.register_map.unit_id || unit_id == 0 || unit_id == 0xFF
+    }
+
+    /// Process a request PDU and build the response PDU
+    fn process_pdu(&self, pdu: &[u8], peer: Option<SocketAddr>) -> Vec<u8> {
+        let function = pdu[0];
+        let result = match function {
+            0x01 => self.read_bits(RegisterType::Coil, pdu),
+            0x02 => self.read_bits(RegisterType::DiscreteInput, pdu),
+            0x03 => self.read_words(RegisterType::HoldingRegister, pdu),
+            0x04 => self.read_words(RegisterType::InputRegister, pdu),
+            0x05 => self.write_single_coil(pdu, peer),
+            0x06 => self.write_single_register(pdu, peer),
+            0x0F => self.write_multiple_coils(pdu, peer),
+            0x10 => self.write_multiple_registers(pdu, peer),
+            _ => Err(ModbusException::IllegalFunction),
+        };
+
+        result.unwrap_or_else(|exception| exception_response(function, exception))
+    }
+
+    fn read_bits(&self, register_type: RegisterType, pdu: &[u8]) -> Result<Vec<u8>, ModbusException> {
+        let (address, quantity) = address_and_quantity(pdu)?;
+        if quantity == 0 || quantity > MAX_READ_BITS {
+            return Err(ModbusException::IllegalDataValue);
+        }
+
+        let bits = self.bank.read().unwrap()
+            .read_bits(register_type, address, quantity)
+            .ok_or(ModbusException::IllegalDataAddress)?;
+
+        let packed = pack_bits(&bits);
+        let mut response = vec![pdu[0], packed.len() as u8];
+        response.extend_from_slice(&packed);
+        Ok(response)
+    }
+
+    fn read_words(&self, register_type: RegisterType, pdu: &[u8]) -> Result<Vec<u8>, ModbusException> {
+        let (address, quantity) = address_and_quantity(pdu)?;
+        if quantity == 0 || quantity > MAX_READ_REGISTERS {
+            return Err(ModbusException::IllegalDataValue);
+        }
+
+        let words = self.bank.read().unwrap()
+            .read_words(register_type, address, quantity)
+            .ok_or(ModbusException::IllegalDataAddress)?;
+
+        let mut response = vec![pdu[0], (words.len() * 2) as u8];
+        for word in words {
+            response.extend_from_slice(&word.to_be_bytes());
+        }
+        Ok(response)
+    }
+
+    fn write_single_coil(&self, pdu: &[u8], peer: Option<SocketAddr>) -> Result<Vec<u8>, ModbusException> {
+        let (address, raw) = address_and_quantity(pdu)?;
+        let bit = match raw {
+            0xFF00 => true,
+            0x0000 => false,
+            _ => return Err(ModbusException::IllegalDataValue),
+        };
+
+        self.write_bits(address, &[bit], peer)?;
+        Ok(pdu[..5].to_vec())
+    }
+
+    fn write_single_register(&self, pdu: &[u8], peer: Option<SocketAddr>) -> Result<Vec<u8>, ModbusException> {
+        let (address, word) = address_and_quantity(pdu)?;
+        self.write_words(address, &[word], peer)?;
+        Ok(pdu[..5].to_vec())
+    }
+
+    fn write_multiple_coils(&self, pdu: &[u8], peer: Option<SocketAddr>) -> Result<Vec<u8>, ModbusException> {
+        let (address, quantity) = address_and_quantity(pdu)?;
+        let byte_count = *pdu.get(5).ok_or(ModbusException::IllegalDataValue)? as usize;
+        if quantity == 0
+            || quantity > MAX_WRITE_BITS
+            || byte_count != (quantity as usize + 7) / 8
+            || pdu.len() != 6 + byte_count
+        {
+            return Err(ModbusException::IllegalDataValue);
+        }
+
+        let bits = (0..quantity as usize)
+            .map(|i| pdu[6 + i / 8] & (1 << (i % 8)) != 0)
+            .collect::<Vec<_>>();
+
+        self.write_bits(address, &bits, peer)?;
+        Ok(pdu[..5].to_vec())
+    }
+
+    fn write_multiple_registers(&self, pdu: &[u8], peer: Option<SocketAddr>) -> Result<Vec<u8>, ModbusException> {
+        let (address, quantity) = address_and_quantity(pdu)?;
+        let byte_count = *pdu.get(5).ok_or(ModbusException::IllegalDataValue)? as usize;
+        if quantity == 0
+            || quantity > MAX_WRITE_REGISTERS
+            || byte_count != quantity as usize * 2
+            || pdu.len() != 6 + byte_count
+        {
+            return Err(ModbusException::IllegalDataValue);
+        }
+
+        let words = pdu[6..]
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>();
+
+        self.write_words(address, &words, peer)?;
+        Ok(pdu[..5].to_vec())
+    }
+
+    fn write_bits(&self, address: u16, bits: &[bool], peer: Option<SocketAddr>) -> Result<(), ModbusException> {
+        let end = self.check_writable(RegisterType::Coil, address, bits.len() as u16)?;
+        {
+            let mut bank = self.bank.write().unwrap();
+            for (a, bit) in (address..end).zip(bits) {
+                bank.coils.insert(a, *bit);
+            }
+        }
+        self.publish_writes(RegisterType::Coil, address, end, peer);
+        Ok(())
+    }
+
+    fn write_words(&self, address: u16, words: &[u16], peer: Option<SocketAddr>) -> Result<(), ModbusException> {
+        let end = self.check_writable(RegisterType::HoldingRegister, address, words.len() as u16)?;
+        {
+            let mut bank = self.bank.write().unwrap();
+            for (a, word) in (address..end).zip(words) {
+                bank.holding_registers.insert(a, *word);
+            }
+        }
+        self.publish_writes(RegisterType::HoldingRegister, address, end, peer);
+        Ok(())
+    }
+
+    /// Every address in the range must belong to a writable mapping
+    fn check_writable(&self, register_type: RegisterType, address: u16, quantity: u16) -> Result<u16, ModbusException> {
+        let end = address.checked_add(quantity).ok_or(ModbusException::IllegalDataAddress)?;
+        let covered = (address..end).all(|a| {
+            self.register_map.mappings.iter().any(|m| {
+                m.writable && m.register_type == register_type && m.addresses().contains(&a)
+            })
+        });
+
+        if covered { Ok(end) } else { Err(ModbusException::IllegalDataAddress) }
+    }
+
+    /// Decode and broadcast every mapping touched by a write
+    fn publish_writes(&self, register_type: RegisterType, start: u16, end: u16, peer: Option<SocketAddr>) {
+        let bank = self.bank.read().unwrap();
+        for mapping in &self.register_map.mappings {
+            let range = mapping.addresses();
+            if mapping.register_type != register_type || range.end <= start || range.start >= end {
+                continue;
+            }
+
+            let value = match bank.load(mapping).map(|raw| mapping.decode(&raw)) {
+                Some(Ok(value)) => value,
+                _ => continue,
+            };
+
+            // Nobody listening is not an error
+            let _ = self.writes.send(PropertyWrite {
+                twin_id: self.twin_id,
+                property: mapping.property.clone(),
+                value,
+                peer,
+                timestamp: Utc::now(),
+            });
+        }
+    }
+}
+
+/// Handle to a running simulator; dropping it stops the server
+pub struct ModbusSimulatorHandle {
+    local_addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl ModbusSimulatorHandle {
+    /// Address the server is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting connections and close connected clients
+    pub fn shutdown(&self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for ModbusSimulatorHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Simulator running for a twin
+struct RunningSimulator {
+    simulator: Arc<ModbusSimulator>,
+    handle: ModbusSimulatorHandle,
+    refresh: Option<JoinHandle<()>>,
+}
+
+impl Drop for RunningSimulator {
+    fn drop(&mut self) {
+        if let Some(refresh) = &self.refresh {
+            refresh.abort();
+        }
+    }
+}
+
+/// Summary of a running simulator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulatorInfo {
+    pub twin_id: TwinId,
+    pub local_addr: SocketAddr,
+    pub properties: Vec<String>,
+}
+
+/// Simulators started from the application, at most one per twin
+#[derive(Default)]
+pub struct ModbusSimulatorRegistry {
+    running: tokio::sync::Mutex<HashMap<TwinId, RunningSimulator>>,
+}
+
+impl ModbusSimulatorRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track a running simulator, stopping any previous one for the same twin.
+    ///
+    /// `refresh` is an optional task keeping registers in sync with the twin;
+    /// it is aborted when the simulator is stopped.
+    pub async fn insert(
+        &self,
+        simulator: Arc<ModbusSimulator>,
+        handle: ModbusSimulatorHandle,
+        refresh: Option<JoinHandle<()>>,
+    ) -> SimulatorInfo {
+        let info = SimulatorInfo {
+            twin_id: simulator.twin_id(),
+            local_addr: handle.local_addr(),
+            properties: simulator.register_map().mappings.iter()
+                .map(|m| m.property.clone())
+                .collect(),
+        };
+
+        self.running.lock().await.insert(
+            simulator.twin_id(),
+            RunningSimulator { simulator, handle, refresh },
+        );
+        info
+    }
+
+    /// Get the simulator for a twin
+    pub async fn get(&self, twin_id: &TwinId) -> Option<Arc<ModbusSimulator>> {
+        self.running.lock().await.get(twin_id).map(|r| r.simulator.clone())
+    }
+
+    /// Stop the simulator for a twin; returns whether one was running
+    pub async fn stop(&self, twin_id: &TwinId) -> bool {
+        match self.running.lock().await.remove(twin_id) {
+            Some(running) => {
+                running.handle.shutdown();
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// List running simulators
+    pub async fn list(&self) -> Vec<SimulatorInfo> {
+        self.running.lock().await.values()
+            .map(|r| SimulatorInfo {
+                twin_id: r.simulator.twin_id(),
+                local_addr: r.handle.local_addr(),
+                properties: r.simulator.register_map().mappings.iter()
+                    .map(|m| m.property.clone())
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+fn address_and_quantity(pdu: &[u8]) -> Result<(u16, u16), ModbusException> {
+    if pdu.len() < 5 {
+        return Err(ModbusException::IllegalDataValue);
+    }
+    Ok((
+        u16::from_be_bytes([pdu[1], pdu[2]]),
+        u16::from_be_bytes([pdu[3], pdu[4]]),
+    ))
+}
+
+fn pack_bits(bits: &[bool]) -> Vec<u8> {
+    let mut packed = vec![0u8; (bits.len() + 7) / 8];
+    for (i, bit) in bits.iter().enumerate() {
+        if *bit {
+            packed[i / 8] |= 1 << (i % 8);
+        }
+    }
+    packed
+}
+
+fn exception_response(function: u8, exception: ModbusException) -> Vec<u8> {
+    vec![function | 0x80, exception as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn simulator() -> Arc<ModbusSimulator> {
+        let map = RegisterMap::from_value(&serde_json::json!({
+            "unit_id": 1,
+            "mappings": [
+                { "property": "temperature", "register_type": "input_register", "address": 0, "data_type": "f32" },
+                { "property": "setpoint", "register_type": "holding_register", "address": 10, "data_type": "i16", "scale": 0.1, "writable": true },
+                { "property": "running", "register_type": "coil", "address": 0, "data_type": "bool", "writable": true },
+            ]
+        })).unwrap();
+        Arc::new(ModbusSimulator::new(Uuid::new_v4(), map).unwrap())
+    }
+
+    async fn request(stream: &mut TcpStream, pdu: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x00, 0x01, 0x00, 0x00];
+        frame.extend_from_slice(&((pdu.len() + 1) as u16).to_be_bytes());
+        frame.push(1);
+        frame.extend_from_slice(pdu);
+        stream.write_all(&frame).await.unwrap();
+
+        let mut header = [0u8; 7];
+        stream.read_exact(&mut header).await.unwrap();
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let mut response = vec![0u8; length - 1];
+        stream.read_exact(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_read_registers_over_tcp() {
+        let simulator = simulator();
+        simulator.apply_values(&HashMap::from([
+            ("temperature".to_string(), serde_json::json!(21.5)),
+        ])).unwrap();
+
+        let handle = simulator.clone().serve("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let mut stream = TcpStream::connect(handle.local_addr()).await.unwrap();
+
+        let response = request(&mut stream, &[0x04, 0x00, 0x00, 0x00, 0x02]).await;
+        assert_eq!(response, vec![0x04, 0x04, 0x41, 0xAC, 0x00, 0x00]);
+
+        // Unmapped address
+        let response = request(&mut stream, &[0x03, 0x00, 0x64, 0x00, 0x01]).await;
+        assert_eq!(response, vec![0x83, ModbusException::IllegalDataAddress as u8]);
+
+        handle.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_client_writes_are_published() {
+        let simulator = simulator();
+        let mut writes = simulator.subscribe_writes();
+
+        let handle = simulator.clone().serve("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let mut stream = TcpStream::connect(handle.local_addr()).await.unwrap();
+
+        // setpoint = 45.0 (raw 450)
+        let response = request(&mut stream, &[0x06, 0x00, 0x0A, 0x01, 0xC2]).await;
+        assert_eq!(response, vec![0x06, 0x00, 0x0A, 0x01, 0xC2]);
+
+        let write = writes.recv().await.unwrap();
+        assert_eq!(write.property, "setpoint");
+        assert_eq!(write.value.as_f64().unwrap().round(), 45.0);
+
+        let response = request(&mut stream, &[0x05, 0x00, 0x00, 0xFF, 0x00]).await;
+        assert_eq!(response[0], 0x05);
+        assert_eq!(writes.recv().await.unwrap().value, serde_json::json!(true));
+
+        // Input registers are read-only
+        let response = request(&mut stream, &[0x06, 0x00, 0x00, 0x00, 0x01]).await;
+        assert_eq!(response, vec![0x86, ModbusException::IllegalDataAddress as u8]);
+
+        assert_eq!(simulator.property_values()["running"], serde_json::json!(true));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_client_connections() {
+        let simulator = simulator();
+        let handle = simulator.clone().serve("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let mut stream = TcpStream::connect(handle.local_addr()).await.unwrap();
+        request(&mut stream, &[0x04, 0x00, 0x00, 0x00, 0x02]).await;
+
+        handle.shutdown();
+
+        let mut buf = [0u8; 1];
+        let read = tokio::time::timeout(std::time::Duration::from_secs(2), stream.read(&mut buf))
+            .await
+            .expect("connection was not closed");
+        assert!(matches!(read, Ok(0) | Err(_)));
+    }
+}
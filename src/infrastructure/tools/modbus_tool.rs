@@ -1,10 +1,14 @@
 use async_trait::async_trait;
 use tokio_modbus::prelude::*;
 use tokio_serial::SerialStream;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use chrono::Utc;
 use tracing::{debug, error, info};
 
+use super::modbus_registers::{RegisterMap, RegisterMapping, RegisterType, RegisterValue};
 use crate::core::domain::{
     models::{Tool, ToolResult, ExecutionStatus, ExecutionMetrics},
     traits::tool_executor::{
@@ -12,11 +16,17 @@ use crate::core::domain::{
         ExecutionContext, ValidationResult, ValidationError,
     },
 };
+use crate::infrastructure::transform::{MappingIngestor, SourceSelector};
+
+/// Protocol of the twin sensor sources fed by `read_map`
+const MODBUS_PROTOCOL: &str = "modbus";
 
 /// Modbus protocol tool executor
 pub struct ModbusToolExecutor {
     timeout: Duration,
     max_retries: u32,
+    /// Maps polled register values onto twins
+    mapping: Option<Arc<MappingIngestor>>,
 }
 
 impl ModbusToolExecutor {
@@ -25,9 +35,44 @@ impl ModbusToolExecutor {
         Self {
             timeout,
             max_retries,
+            mapping: None,
         }
     }
 
+    /// Run `read_map` results through twin data source mappings
+    ///
+    /// Applies when the request names a `twin_id`: the mappings of its
+    /// `data_source_id`, or else of its Modbus sensor sources, read the
+    /// decoded values by property name.
+    pub fn with_mapping(mut self, ingestor: Arc<MappingIngestor>) -> Self {
+        self.mapping = Some(ingestor);
+        self
+    }
+
+    /// Store decoded values on the requested twin; returns the number of
+    /// properties updated
+    async fn map_values(&self, parameters: &HashMap<String, Value>, values: &Value) -> ExecutorResult<usize> {
+        let (Some(mapping), Some(twin_id)) = (&self.mapping, parameters.get("twin_id")) else {
+            return Ok(0);
+        };
+        let uuid = |name: &str, value: &Value| value.as_str()
+            .and_then(|s| uuid::Uuid::parse_str(s).ok())
+            .ok_or_else(|| ExecutorError::ValidationError {
+                parameter: name.to_string(),
+                reason: "Must be a UUID".to_string(),
+            });
+
+        let twin_id = uuid("twin_id", twin_id)?;
+        let selector = match parameters.get("data_source_id") {
+            Some(source_id) => SourceSelector::Id(uuid("data_source_id", source_id)?),
+            None => SourceSelector::Protocol(MODBUS_PROTOCOL),
+        };
+
+        let outcome = mapping.ingest(twin_id, selector, values, Utc::now()).await
+            .map_err(|e| ExecutorError::ExecutionFailed(format!("Failed to store mapped values: {}", e)))?;
+        Ok(outcome.updated)
+    }
+
     /// Create Modbus TCP client
     async fn create_tcp_client(
         &self,
@@ -87,6 +132,39 @@ impl ModbusToolExecutor {
         }
     }
 
+    /// Read the registers backing a register map entry
+    async fn read_mapping(
+        &self,
+        ctx: &mut client::Context,
+        mapping: &RegisterMapping,
+    ) -> ExecutorResult<RegisterValue> {
+        let quantity = mapping.data_type.word_count();
+        let mut retries = 0;
+        loop {
+            let result = match mapping.register_type {
+                RegisterType::Coil => ctx.read_coils(mapping.address, 1).await
+                    .map(|bits| RegisterValue::Bit(bits.first().copied().unwrap_or(false))),
+                RegisterType::DiscreteInput => ctx.read_discrete_inputs(mapping.address, 1).await
+                    .map(|bits| RegisterValue::Bit(bits.first().copied().unwrap_or(false))),
+                RegisterType::InputRegister => ctx.read_input_registers(mapping.address, quantity).await
+                    .map(RegisterValue::Words),
+                RegisterType::HoldingRegister => ctx.read_holding_registers(mapping.address, quantity).await
+                    .map(RegisterValue::Words),
+            };
+
+            match result {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    if retries >= self.max_retries {
+                        return Err(ExecutorError::ExecutionFailed(e.to_string()));
+                    }
+                    retries += 1;
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    }
+
     /// Write holding registers
     async fn write_holding_registers(
         &self,
@@ -220,6 +298,38 @@ impl ToolExecutor for ModbusToolExecutor {
                     "success": true,
                 })
             },
+            "read_map" => {
+                let register_map = request.parameters.get("register_map")
+                    .ok_or_else(|| ExecutorError::MissingParameter {
+                        tool: "modbus".to_string(),
+                        parameter: "register_map".to_string(),
+                    })
+                    .and_then(|v| RegisterMap::from_value(v).map_err(|e| ExecutorError::ValidationError {
+                        parameter: "register_map".to_string(),
+                        reason: e.to_string(),
+                    }))?;
+
+                let mut values = serde_json::Map::new();
+                for mapping in &register_map.mappings {
+                    let raw = self.read_mapping(&mut ctx, mapping).await?;
+                    metrics.bytes_read += match &raw {
+                        RegisterValue::Bit(_) => 1,
+                        RegisterValue::Words(words) => (words.len() * 2) as u64,
+                    };
+
+                    let value = mapping.decode(&raw)
+                        .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+                    values.insert(mapping.property.clone(), value);
+                }
+
+                let values = Value::Object(values);
+                let mapped = self.map_values(&request.parameters, &values).await?;
+
+                serde_json::json!({
+                    "values": values,
+                    "mapped_properties": mapped,
+                })
+            },
             _ => return Err(ExecutorError::ValidationError {
                 parameter: "operation".to_string(),
                 reason: format!("Invalid operation: {}", operation),
@@ -276,12 +386,12 @@ impl ToolExecutor for ModbusToolExecutor {
         // Validate operation
         match parameters.get("operation").and_then(Value::as_str) {
             Some(operation) => {
-                if !["read", "write"].contains(&operation.to_lowercase().as_str()) {
+                if !["read", "write", "read_map"].contains(&operation.to_lowercase().as_str()) {
                     errors.push(ValidationError {
                         parameter: "operation".to_string(),
                         code: "INVALID_OPERATION".to_string(),
                         message: format!("Invalid operation: {}", operation),
-                        expected: Some("read or write".to_string()),
+                        expected: Some("read, write or read_map".to_string()),
                         actual: Some(operation.to_string()),
                     });
                 }
@@ -350,4 +460,72 @@ mod tests {
         assert!(result.valid);
         assert!(result.errors.is_empty());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_read_map_against_simulator() {
+        use crate::core::domain::traits::tool_executor::SecurityContext;
+        use crate::infrastructure::tools::modbus_server::ModbusSimulator;
+        use std::sync::Arc;
+
+        let register_map = serde_json::json!({
+            "unit_id": 1,
+            "mappings": [
+                { "property": "flow", "register_type": "input_register", "address": 0, "data_type": "f32" },
+                { "property": "pressure", "register_type": "holding_register", "address": 4, "data_type": "u16", "scale": 0.01 },
+                { "property": "pump_on", "register_type": "coil", "address": 0, "data_type": "bool" },
+            ]
+        });
+
+        let simulator = Arc::new(ModbusSimulator::new(
+            Uuid::new_v4(),
+            RegisterMap::from_value(&register_map).unwrap(),
+        ).unwrap());
+        simulator.apply_values(&HashMap::from([
+            ("flow".to_string(), serde_json::json!(12.5)),
+            ("pressure".to_string(), serde_json::json!(3.2)),
+            ("pump_on".to_string(), serde_json::json!(true)),
+        ])).unwrap();
+        let handle = simulator.clone().serve("127.0.0.1:0".parse().unwrap()).await.unwrap();
+
+        let executor = ModbusToolExecutor::new(Duration::from_secs(1), 0);
+        let request = ExecutionRequest {
+            execution_id: Uuid::new_v4(),
+            tool_id: Uuid::new_v4(),
+            parameters: serde_json::json!({
+                "transport": "tcp",
+                "operation": "read_map",
+                "host": "127.0.0.1",
+                "port": handle.local_addr().port(),
+                "register_map": register_map,
+            })
+            .as_object()
+            .unwrap()
+            .clone()
+            .into_iter()
+            .collect(),
+            context: ExecutionContext {
+                agent_id: Uuid::new_v4(),
+                user_id: None,
+                conversation_id: None,
+                session_id: "test".to_string(),
+                security: SecurityContext {
+                    auth_token: None,
+                    permissions: vec!["modbus:read".to_string()],
+                    ip_address: None,
+                    labels: Default::default(),
+                },
+                environment: Default::default(),
+                working_directory: None,
+                metadata: Default::default(),
+            },
+            options: Default::default(),
+            callback_url: None,
+        };
+
+        let result = executor.execute(request).await.unwrap();
+        let values = &result.output.unwrap()["values"];
+        assert_eq!(values["flow"], serde_json::json!(12.5));
+        assert!((values["pressure"].as_f64().unwrap() - 3.2).abs() < 1e-9);
+        assert_eq!(values["pump_on"], serde_json::json!(true));
+    }
+}
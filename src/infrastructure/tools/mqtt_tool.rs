@@ -1,28 +1,248 @@
 use async_trait::async_trait;
-use rumqttc::{AsyncClient, MqttOptions, QoS};
+use rumqttc::{
+    AsyncClient, Event, EventLoop, LastWill, MqttOptions, Outgoing, Packet, Publish, QoS,
+    TlsConfiguration, Transport,
+};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
 use std::time::Duration;
 use chrono::Utc;
-use tracing::{debug, error, info};
-use tokio::sync::Mutex;
+use tracing::{debug, error, info, warn};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
 use std::sync::Arc;
 
+use super::sparkplug::{self, SparkplugIngestor, SparkplugTopic};
+
 use crate::core::domain::{
-    models::{Tool, ToolResult, ExecutionStatus, ExecutionMetrics},
+    models::{ConnectionConfig, DataSource, DigitalTwin, Tool, ToolResult, ExecutionStatus, ExecutionMetrics, TwinId},
     traits::tool_executor::{
         ToolExecutor, ExecutorResult, ExecutorError, ExecutionRequest,
         ExecutionContext, ValidationResult, ValidationError,
     },
 };
+use crate::infrastructure::config::MqttToolConfig;
+use crate::infrastructure::security::CredentialVault;
+use crate::infrastructure::transform::MappingIngestor;
+
+/// Default time to wait for PUBACK/PUBCOMP
+const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default number of publishes buffered while disconnected
+const DEFAULT_OFFLINE_BUFFER_SIZE: usize = 1000;
+
+/// Maximum delay between reconnect attempts
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Topic filter covering every Sparkplug B message
+const SPARKPLUG_TOPIC_FILTER: &str = "spBv1.0/#";
+
+/// Incoming publishes waiting for ingestion; later ones are dropped
+const INGEST_QUEUE_SIZE: usize = 1024;
+
+/// TLS settings for the broker connection
+#[derive(Debug, Clone)]
+pub struct MqttTlsOptions {
+    /// CA certificate (PEM) used to verify the broker
+    pub ca_path: PathBuf,
+
+    /// Client certificate (PEM) for mutual TLS
+    pub client_cert_path: Option<PathBuf>,
+
+    /// Client private key (PEM) for mutual TLS
+    pub client_key_path: Option<PathBuf>,
+}
+
+/// Message published by the broker when the client disconnects ungracefully
+#[derive(Debug, Clone)]
+pub struct MqttLastWill {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: QoS,
+    pub retain: bool,
+}
+
+/// Outcome of a publish request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublishOutcome {
+    /// Broker acknowledged the message (PUBACK for QoS 1, PUBCOMP for QoS 2)
+    Acknowledged { packet_id: u16 },
+
+    /// QoS 0 message was written to the connection
+    Sent,
+
+    /// Client was offline; the message will be replayed after reconnect
+    Buffered,
+}
+
+/// Publish waiting for broker acknowledgement
+struct PendingPublish {
+    qos: QoS,
+    sender: oneshot::Sender<u16>,
+}
+
+/// Publish held back while the client is disconnected
+#[derive(Debug, Clone)]
+struct BufferedPublish {
+    topic: String,
+    payload: Vec<u8>,
+    qos: QoS,
+    retain: bool,
+}
+
+/// Correlates publishes with the acknowledgements seen by the event loop.
+///
+/// rumqttc only reports the packet id of a publish once the event loop writes
+/// it to the connection, so waiters are queued in send order and bound to
+/// their packet id when its first `Outgoing::Publish` event is observed.
+/// From then on the waiter is found by packet id only; publishes written
+/// again after a reconnect keep their packet id and do not take a waiter.
+struct DeliveryTracker {
+    /// Serializes enqueueing and sending so queue order matches send order
+    send_lock: Mutex<()>,
+
+    /// Publishes handed to the client but not yet written
+    queued: std::sync::Mutex<VecDeque<PendingPublish>>,
+
+    /// Written publishes waiting for PUBACK/PUBCOMP, keyed by packet id
+    in_flight: std::sync::Mutex<HashMap<u16, PendingPublish>>,
+
+    /// Publishes held back while disconnected
+    offline: std::sync::Mutex<VecDeque<BufferedPublish>>,
+
+    /// Whether the client currently has a broker connection
+    connected: watch::Sender<bool>,
+}
+
+impl DeliveryTracker {
+    fn new() -> Self {
+        Self {
+            send_lock: Mutex::new(()),
+            queued: std::sync::Mutex::new(VecDeque::new()),
+            in_flight: std::sync::Mutex::new(HashMap::new()),
+            offline: std::sync::Mutex::new(VecDeque::new()),
+            connected: watch::channel(false).0,
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        *self.connected.borrow()
+    }
+
+    /// Hand a publish to the client and return a receiver for its acknowledgement
+    async fn send(
+        &self,
+        client: &AsyncClient,
+        topic: &str,
+        payload: Vec<u8>,
+        qos: QoS,
+        retain: bool,
+    ) -> ExecutorResult<oneshot::Receiver<u16>> {
+        let _guard = self.send_lock.lock().await;
+        let (sender, receiver) = oneshot::channel();
+        self.queued.lock().unwrap().push_back(PendingPublish { qos, sender });
+
+        if let Err(e) = client.publish(topic, qos, retain, payload).await {
+            self.queued.lock().unwrap().pop_back();
+            return Err(ExecutorError::ExecutionFailed(e.to_string()));
+        }
+
+        Ok(receiver)
+    }
+
+    /// Hand a publish to the client without waiting for channel capacity
+    fn try_send(
+        &self,
+        client: &AsyncClient,
+        topic: &str,
+        payload: Vec<u8>,
+        qos: QoS,
+    ) -> ExecutorResult<()> {
+        let _guard = self.send_lock.try_lock()
+            .map_err(|_| ExecutorError::ExecutionFailed("publish already in progress".to_string()))?;
+        let (sender, _) = oneshot::channel();
+        self.queued.lock().unwrap().push_back(PendingPublish { qos, sender });
+
+        if let Err(e) = client.try_publish(topic, qos, false, payload) {
+            self.queued.lock().unwrap().pop_back();
+            return Err(ExecutorError::ExecutionFailed(e.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Buffer a publish while offline, dropping the oldest when full
+    fn buffer(&self, publish: BufferedPublish, capacity: usize) {
+        let mut offline = self.offline.lock().unwrap();
+        if offline.len() >= capacity {
+            warn!("MQTT offline buffer full, dropping oldest message for {}", publish.topic);
+            offline.pop_front();
+        }
+        offline.push_back(publish);
+    }
+
+    /// A publish was written to the connection
+    fn on_outgoing_publish(&self, packet_id: u16) {
+        // QoS 0 publishes have no packet id and are never retransmitted
+        if packet_id != 0 && self.in_flight.lock().unwrap().contains_key(&packet_id) {
+            debug!("MQTT publish {} retransmitted", packet_id);
+            return;
+        }
+
+        let pending = match self.queued.lock().unwrap().pop_front() {
+            Some(pending) => pending,
+            None => return,
+        };
+
+        match pending.qos {
+            QoS::AtMostOnce => {
+                let _ = pending.sender.send(packet_id);
+            },
+            _ => {
+                self.in_flight.lock().unwrap().insert(packet_id, pending);
+            },
+        }
+    }
+
+    /// The broker acknowledged a publish
+    fn on_acknowledged(&self, packet_id: u16, qos: QoS) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.get(&packet_id).map_or(false, |p| p.qos == qos) {
+            if let Some(pending) = in_flight.remove(&packet_id) {
+                let _ = pending.sender.send(packet_id);
+            }
+        }
+    }
+}
+
+/// Broker connection owned by an executor
+///
+/// Dropping it, with the executor or on `disconnect`, ends its event loop.
+struct Connection {
+    client: AsyncClient,
+    _shutdown: oneshot::Sender<()>,
+}
 
 /// MQTT messaging tool executor
 pub struct MqttToolExecutor {
-    client: Arc<Mutex<Option<AsyncClient>>>,
+    client: Arc<Mutex<Option<Connection>>>,
     broker_url: String,
     broker_port: u16,
     client_id: String,
     username: Option<String>,
     password: Option<String>,
     timeout: Duration,
+    tls: Option<MqttTlsOptions>,
+    last_will: Option<MqttLastWill>,
+    persistent_session: bool,
+    ack_timeout: Duration,
+    offline_buffer_size: usize,
+    delivery: Arc<DeliveryTracker>,
+    /// Active subscriptions, restored when the broker did not keep the session
+    subscriptions: Arc<std::sync::Mutex<HashMap<String, QoS>>>,
+    sparkplug: Option<Arc<SparkplugIngestor>>,
+    sparkplug_seq: Arc<Mutex<u64>>,
+    /// Maps messages on twin data source topics onto the twins
+    mapping: Option<Arc<MappingIngestor>>,
 }
 
 impl MqttToolExecutor {
@@ -43,7 +263,214 @@ impl MqttToolExecutor {
             username,
             password,
             timeout,
+            tls: None,
+            last_will: None,
+            persistent_session: false,
+            ack_timeout: DEFAULT_ACK_TIMEOUT,
+            offline_buffer_size: DEFAULT_OFFLINE_BUFFER_SIZE,
+            delivery: Arc::new(DeliveryTracker::new()),
+            subscriptions: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            sparkplug: None,
+            sparkplug_seq: Arc::new(Mutex::new(0)),
+            mapping: None,
+        }
+    }
+
+    /// Create an MQTT tool executor from configuration
+    pub fn from_config(config: &MqttToolConfig) -> Self {
+        let mut executor = Self::new(
+            config.broker_url.clone(),
+            config.broker_port,
+            config.client_id.clone(),
+            config.username.clone(),
+            config.password.clone(),
+            Duration::from_secs(config.timeout_seconds),
+        )
+        .with_ack_timeout(Duration::from_secs(config.ack_timeout_seconds))
+        .with_offline_buffer(config.offline_buffer_size);
+
+        if config.persistent_session {
+            executor = executor.with_persistent_session();
+        }
+
+        if let Some(tls) = &config.tls {
+            executor = executor.with_tls(MqttTlsOptions {
+                ca_path: tls.ca_path.clone(),
+                client_cert_path: tls.client_cert_path.clone(),
+                client_key_path: tls.client_key_path.clone(),
+            });
+        }
+
+        if let Some(will) = &config.last_will {
+            executor = executor.with_last_will(MqttLastWill {
+                topic: will.topic.clone(),
+                payload: will.payload.clone().into_bytes(),
+                qos: qos_from_level(Some(will.qos as u64)),
+                retain: will.retain,
+            });
+        }
+
+        executor
+    }
+
+    /// Create an MQTT tool executor for a data source connection
+    ///
+    /// The endpoint is `host[:port]`, optionally prefixed with `mqtt://` or
+    /// `tcp://`, or with `mqtts://` or `ssl://` for TLS. TLS connections read
+    /// their PEM files from the `tls_ca_path`, `tls_client_cert_path` and
+    /// `tls_client_key_path` custom parameters; a CA is required. Credentials
+    /// referenced by the connection are decrypted from the vault here and
+    /// only kept inside the executor.
+    pub async fn from_connection(
+        client_id: String,
+        connection: &ConnectionConfig,
+        vault: &CredentialVault,
+    ) -> ExecutorResult<Self> {
+        let (host, port, tls) = parse_broker_endpoint(&connection.endpoint)?;
+        let tls = if tls { Some(tls_options(connection)?) } else { None };
+        let credentials = vault.resolve(connection).await
+            .map_err(|e| ExecutorError::ExecutionFailed(format!("Failed to resolve broker credentials: {}", e)))?;
+        let (username, password) = match &credentials {
+            Some(credentials) => (
+                credentials.username().map(str::to_string),
+                credentials.password().map(str::to_string),
+            ),
+            None => (None, None),
+        };
+
+        let mut executor = Self::new(
+            host,
+            port,
+            client_id,
+            username,
+            password,
+            Duration::from_secs(connection.timeout_seconds as u64),
+        );
+        if let Some(tls) = tls {
+            executor = executor.with_tls(tls);
         }
+
+        Ok(executor)
+    }
+
+    /// Connect to the broker over TLS
+    pub fn with_tls(mut self, tls: MqttTlsOptions) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Register a last-will message with the broker
+    pub fn with_last_will(mut self, last_will: MqttLastWill) -> Self {
+        self.last_will = Some(last_will);
+        self
+    }
+
+    /// Keep the broker session (subscriptions and in-flight QoS 1/2 messages)
+    /// across reconnects
+    pub fn with_persistent_session(mut self) -> Self {
+        self.persistent_session = true;
+        self
+    }
+
+    /// Set how long publishes wait for broker acknowledgement
+    pub fn with_ack_timeout(mut self, ack_timeout: Duration) -> Self {
+        self.ack_timeout = ack_timeout;
+        self
+    }
+
+    /// Set how many publishes are buffered while disconnected
+    pub fn with_offline_buffer(mut self, capacity: usize) -> Self {
+        self.offline_buffer_size = capacity;
+        self
+    }
+
+    /// Subscribe to Sparkplug B topics and route their messages to the
+    /// given ingestor
+    pub fn with_sparkplug(mut self, ingestor: Arc<SparkplugIngestor>) -> Self {
+        self.sparkplug = Some(ingestor);
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .insert(SPARKPLUG_TOPIC_FILTER.to_string(), QoS::AtLeastOnce);
+        self
+    }
+
+    /// Route messages on the topics of followed data sources through the
+    /// given ingestor
+    pub fn with_mapping(mut self, ingestor: Arc<MappingIngestor>) -> Self {
+        self.mapping = Some(ingestor);
+        self
+    }
+
+    /// Subscribe to a twin data source's topic and ingest its messages
+    ///
+    /// Sources other than active message queues are ignored.
+    pub async fn follow_source(&self, twin_id: TwinId, source: &DataSource) -> ExecutorResult<()> {
+        let Some(mapping) = &self.mapping else {
+            return Ok(());
+        };
+        match mapping.bind_source(twin_id, source) {
+            Some(topic) => self.subscribe(&topic, QoS::AtLeastOnce).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Follow every active message queue source of a twin
+    pub async fn follow_twin(&self, twin: &DigitalTwin) -> ExecutorResult<()> {
+        for source in twin.active_data_sources() {
+            self.follow_source(twin.id, source).await?;
+        }
+        Ok(())
+    }
+
+    /// Stop ingesting a data source's topic
+    pub async fn unfollow_source(&self, source_id: uuid::Uuid) -> ExecutorResult<()> {
+        let Some(mapping) = &self.mapping else {
+            return Ok(());
+        };
+        match mapping.unbind_source(source_id) {
+            Some(topic) => self.unsubscribe(&topic).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Connect to the broker now rather than on the first operation, so
+    /// subscriptions such as Sparkplug ingestion start receiving
+    pub async fn start(&self) -> ExecutorResult<()> {
+        self.ensure_connected().await.map(|_| ())
+    }
+
+    /// Disconnect from the broker and stop the event loop
+    ///
+    /// The next operation connects again.
+    pub async fn disconnect(&self) {
+        self.client.lock().await.take();
+    }
+
+    /// Build the TLS transport from the configured PEM files
+    async fn tls_transport(&self, tls: &MqttTlsOptions) -> ExecutorResult<Transport> {
+        let read = |path: PathBuf| async move {
+            tokio::fs::read(&path).await.map_err(|e| ExecutorError::ValidationError {
+                parameter: "tls".to_string(),
+                reason: format!("Failed to read {}: {}", path.display(), e),
+            })
+        };
+
+        let ca = read(tls.ca_path.clone()).await?;
+        let client_auth = match (&tls.client_cert_path, &tls.client_key_path) {
+            (Some(cert), Some(key)) => Some((read(cert.clone()).await?, read(key.clone()).await?)),
+            (None, None) => None,
+            _ => return Err(ExecutorError::ValidationError {
+                parameter: "tls".to_string(),
+                reason: "Client certificate and key must be configured together".to_string(),
+            }),
+        };
+
+        Ok(Transport::Tls(TlsConfiguration::Simple {
+            ca,
+            alpn: None,
+            client_auth,
+        }))
     }
 
     /// Connect to MQTT broker
@@ -56,26 +483,51 @@ impl MqttToolExecutor {
 
         mqtt_options
             .set_keep_alive(self.timeout)
-            .set_clean_session(true);
+            .set_clean_session(!self.persistent_session);
 
         if let (Some(username), Some(password)) = (&self.username, &self.password) {
             mqtt_options.set_credentials(username, password);
         }
 
-        let (client, mut eventloop) = AsyncClient::new(mqtt_options, 10);
-        
+        if let Some(tls) = &self.tls {
+            mqtt_options.set_transport(self.tls_transport(tls).await?);
+        }
+
+        if let Some(will) = &self.last_will {
+            mqtt_options.set_last_will(LastWill::new(
+                &will.topic,
+                will.payload.clone(),
+                will.qos,
+                will.retain,
+            ));
+        }
+
+        let (client, eventloop) = AsyncClient::new(mqtt_options, 10);
+
         // Start event loop in background
-        let client_clone = client.clone();
-        tokio::spawn(async move {
-            while let Ok(notification) = eventloop.poll().await {
-                debug!("MQTT Event: {:?}", notification);
-            }
-            debug!("MQTT event loop terminated");
-        });
+        let mut connected = self.delivery.connected.subscribe();
+        let (shutdown, shutdown_signal) = oneshot::channel();
+        tokio::spawn(run_event_loop(
+            eventloop,
+            shutdown_signal,
+            client.clone(),
+            self.delivery.clone(),
+            self.subscriptions.clone(),
+            self.sparkplug.clone(),
+            self.mapping.clone(),
+        ));
+
+        // Give the initial connection a chance before publishes start buffering
+        if tokio::time::timeout(self.timeout, connected.wait_for(|c| *c)).await.is_err() {
+            warn!(
+                "MQTT broker {}:{} not reachable yet, publishes will be buffered",
+                self.broker_url, self.broker_port,
+            );
+        }
 
         // Store client
         let mut client_guard = self.client.lock().await;
-        *client_guard = Some(client);
+        *client_guard = Some(Connection { client, _shutdown: shutdown });
 
         Ok(())
     }
@@ -90,24 +542,42 @@ impl MqttToolExecutor {
             client_guard = self.client.lock().await;
         }
 
-        Ok(client_guard.as_ref().unwrap().clone())
+        Ok(client_guard.as_ref().unwrap().client.clone())
     }
 
-    /// Publish message
+    /// Publish message and wait for the broker to acknowledge it
     async fn publish(
         &self,
         topic: &str,
         payload: &[u8],
         qos: QoS,
         retain: bool,
-    ) -> ExecutorResult<()> {
+    ) -> ExecutorResult<PublishOutcome> {
         let client = self.ensure_connected().await?;
 
-        client.publish(topic, qos, retain, payload)
-            .await
-            .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+        if !self.delivery.is_connected() {
+            self.delivery.buffer(BufferedPublish {
+                topic: topic.to_string(),
+                payload: payload.to_vec(),
+                qos,
+                retain,
+            }, self.offline_buffer_size);
+            return Ok(PublishOutcome::Buffered);
+        }
 
-        Ok(())
+        let receiver = self.delivery.send(&client, topic, payload.to_vec(), qos, retain).await?;
+
+        match tokio::time::timeout(self.ack_timeout, receiver).await {
+            Ok(Ok(packet_id)) if qos == QoS::AtMostOnce => {
+                debug!("MQTT publish to {} sent (packet {})", topic, packet_id);
+                Ok(PublishOutcome::Sent)
+            },
+            Ok(Ok(packet_id)) => Ok(PublishOutcome::Acknowledged { packet_id }),
+            Ok(Err(_)) => Err(ExecutorError::ExecutionFailed(
+                "MQTT connection closed before acknowledgement".to_string(),
+            )),
+            Err(_) => Err(ExecutorError::Timeout(self.ack_timeout.as_secs())),
+        }
     }
 
     /// Subscribe to topic
@@ -117,10 +587,14 @@ impl MqttToolExecutor {
         qos: QoS,
     ) -> ExecutorResult<()> {
         let client = self.ensure_connected().await?;
+        self.subscriptions.lock().unwrap().insert(topic.to_string(), qos);
 
-        client.subscribe(topic, qos)
-            .await
-            .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+        // Offline subscriptions are sent with the others on ConnAck
+        if self.delivery.is_connected() {
+            client.subscribe(topic, qos)
+                .await
+                .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+        }
 
         Ok(())
     }
@@ -128,6 +602,7 @@ impl MqttToolExecutor {
     /// Unsubscribe from topic
     async fn unsubscribe(&self, topic: &str) -> ExecutorResult<()> {
         let client = self.ensure_connected().await?;
+        self.subscriptions.lock().unwrap().remove(topic);
 
         client.unsubscribe(topic)
             .await
@@ -137,6 +612,249 @@ impl MqttToolExecutor {
     }
 }
 
+/// Map a numeric QoS level to rumqttc's QoS, defaulting to at-least-once
+fn qos_from_level(level: Option<u64>) -> QoS {
+    match level {
+        Some(0) => QoS::AtMostOnce,
+        Some(1) => QoS::AtLeastOnce,
+        Some(2) => QoS::ExactlyOnce,
+        _ => QoS::AtLeastOnce,
+    }
+}
+
+/// Split a broker endpoint into host, port and whether it uses TLS.
+///
+/// `mqtt://` and `tcp://` (or no scheme) connect in plain text on port 1883
+/// by default, `mqtts://` and `ssl://` over TLS on port 8883.
+fn parse_broker_endpoint(endpoint: &str) -> ExecutorResult<(String, u16, bool)> {
+    let invalid = || ExecutorError::ValidationError {
+        parameter: "endpoint".to_string(),
+        reason: format!("Invalid broker endpoint: {}", endpoint),
+    };
+
+    let (tls, address) = match endpoint.split_once("://") {
+        Some(("mqtt" | "tcp", address)) => (false, address),
+        Some(("mqtts" | "ssl", address)) => (true, address),
+        Some(_) => return Err(invalid()),
+        None => (false, endpoint),
+    };
+    let address = address.trim_end_matches('/');
+    let default_port = if tls { 8883 } else { 1883 };
+
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() => {
+            let port = port.parse().map_err(|_| invalid())?;
+            Ok((host.to_string(), port, tls))
+        }
+        Some(_) => Err(invalid()),
+        None if !address.is_empty() => Ok((address.to_string(), default_port, tls)),
+        None => Err(invalid()),
+    }
+}
+
+/// TLS options of a data source connection
+fn tls_options(connection: &ConnectionConfig) -> ExecutorResult<MqttTlsOptions> {
+    let path = |name: &str| {
+        connection.custom_params.get(name)
+            .and_then(|value| value.as_str())
+            .map(PathBuf::from)
+    };
+
+    let ca_path = path("tls_ca_path").ok_or_else(|| ExecutorError::ValidationError {
+        parameter: "tls_ca_path".to_string(),
+        reason: format!("TLS broker endpoint {} requires a CA certificate", connection.endpoint),
+    })?;
+
+    Ok(MqttTlsOptions {
+        ca_path,
+        client_cert_path: path("tls_client_cert_path"),
+        client_key_path: path("tls_client_key_path"),
+    })
+}
+
+/// Drive the MQTT event loop, reconnecting on errors, until the owning
+/// connection is dropped
+async fn run_event_loop(
+    mut eventloop: EventLoop,
+    mut shutdown: oneshot::Receiver<()>,
+    client: AsyncClient,
+    delivery: Arc<DeliveryTracker>,
+    subscriptions: Arc<std::sync::Mutex<HashMap<String, QoS>>>,
+    sparkplug: Option<Arc<SparkplugIngestor>>,
+    mapping: Option<Arc<MappingIngestor>>,
+) {
+    let mut reconnect_delay = Duration::from_secs(1);
+    let (ingest, queue) = mpsc::channel(INGEST_QUEUE_SIZE);
+    tokio::spawn(ingest_publishes(queue, client.clone(), delivery.clone(), sparkplug, mapping));
+
+    loop {
+        let polled = tokio::select! {
+            _ = &mut shutdown => break,
+            polled = eventloop.poll() => polled,
+        };
+        let notification = match polled {
+            Ok(notification) => notification,
+            Err(e) => {
+                if delivery.connected.send_replace(false) {
+                    warn!("MQTT connection lost: {}", e);
+                } else {
+                    debug!("MQTT reconnect failed: {}", e);
+                }
+                tokio::select! {
+                    _ = &mut shutdown => break,
+                    _ = tokio::time::sleep(reconnect_delay) => {},
+                }
+                reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+                continue;
+            },
+        };
+
+        debug!("MQTT Event: {:?}", notification);
+
+        match notification {
+            Event::Incoming(Packet::ConnAck(ack)) => {
+                info!("MQTT connected");
+                reconnect_delay = Duration::from_secs(1);
+                delivery.connected.send_replace(true);
+                // A clean session starts without subscriptions
+                if !ack.session_present {
+                    let topics: Vec<_> = subscriptions.lock().unwrap()
+                        .iter()
+                        .map(|(topic, qos)| (topic.clone(), *qos))
+                        .collect();
+                    tokio::spawn(resubscribe(client.clone(), topics));
+                }
+                tokio::spawn(replay_offline(client.clone(), delivery.clone()));
+            },
+            Event::Outgoing(Outgoing::Publish(packet_id)) => {
+                delivery.on_outgoing_publish(packet_id);
+            },
+            Event::Incoming(Packet::PubAck(ack)) => {
+                delivery.on_acknowledged(ack.pkid, QoS::AtLeastOnce);
+            },
+            Event::Incoming(Packet::PubComp(comp)) => {
+                delivery.on_acknowledged(comp.pkid, QoS::ExactlyOnce);
+            },
+            Event::Incoming(Packet::Publish(publish)) => {
+                // Ingestion writes to the database, so it runs off the
+                // event loop to keep keepalives and acks flowing
+                match ingest.try_send(publish) {
+                    Ok(()) => {},
+                    Err(mpsc::error::TrySendError::Full(publish)) => {
+                        warn!("MQTT ingestion queue full, dropping message on {}", publish.topic);
+                    },
+                    Err(mpsc::error::TrySendError::Closed(_)) => {},
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // Waiting publishes fail instead of timing out
+    delivery.connected.send_replace(false);
+    delivery.queued.lock().unwrap().clear();
+    delivery.in_flight.lock().unwrap().clear();
+    disconnect(&client, &mut eventloop).await;
+    debug!("MQTT event loop stopped");
+}
+
+/// Ingest publishes from `queue` until the event loop stops
+async fn ingest_publishes(
+    mut queue: mpsc::Receiver<Publish>,
+    client: AsyncClient,
+    delivery: Arc<DeliveryTracker>,
+    sparkplug: Option<Arc<SparkplugIngestor>>,
+    mapping: Option<Arc<MappingIngestor>>,
+) {
+    while let Some(publish) = queue.recv().await {
+        if !SparkplugTopic::is_sparkplug(&publish.topic) {
+            if let Some(mapping) = &mapping {
+                mapping.handle_publish(&publish.topic, &publish.payload).await;
+            }
+            continue;
+        }
+        let ingestor = match &sparkplug {
+            Some(ingestor) => ingestor,
+            None => continue,
+        };
+
+        match ingestor.handle_message(&publish.topic, &publish.payload).await {
+            Ok(update) if update.rebirth_required => {
+                let topic = SparkplugTopic::command(
+                    &update.topic.group_id,
+                    &update.topic.edge_node_id,
+                    None,
+                );
+                match sparkplug::encode_payload(&sparkplug::rebirth_request()) {
+                    Ok(payload) => {
+                        if let Err(e) = delivery.try_send(
+                            &client,
+                            &topic.to_topic_string(),
+                            payload,
+                            QoS::AtLeastOnce,
+                        ) {
+                            warn!("Failed to request Sparkplug rebirth: {}", e);
+                        }
+                    },
+                    Err(e) => warn!("Failed to encode Sparkplug rebirth request: {}", e),
+                }
+            },
+            Ok(_) => {},
+            Err(e) => warn!("Failed to ingest Sparkplug message on {}: {}", publish.topic, e),
+        }
+    }
+}
+
+/// Send DISCONNECT so the broker does not publish the last will
+async fn disconnect(client: &AsyncClient, eventloop: &mut EventLoop) {
+    if client.try_disconnect().is_err() {
+        return;
+    }
+
+    let _ = tokio::time::timeout(Duration::from_secs(1), async {
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(_) => return,
+                Ok(_) => {},
+            }
+        }
+    }).await;
+}
+
+/// Subscribe again after the broker started a new session
+async fn resubscribe(client: AsyncClient, topics: Vec<(String, QoS)>) {
+    for (topic, qos) in topics {
+        debug!("Subscribing to MQTT topic {}", topic);
+        if let Err(e) = client.subscribe(&topic, qos).await {
+            error!("Failed to subscribe to MQTT topic {}: {}", topic, e);
+        }
+    }
+}
+
+/// Replay publishes buffered while the client was offline
+async fn replay_offline(client: AsyncClient, delivery: Arc<DeliveryTracker>) {
+    loop {
+        if !delivery.is_connected() {
+            return;
+        }
+
+        let publish = match delivery.offline.lock().unwrap().pop_front() {
+            Some(publish) => publish,
+            None => return,
+        };
+
+        debug!("Replaying buffered MQTT publish to {}", publish.topic);
+        if let Err(e) = delivery
+            .send(&client, &publish.topic, publish.payload.clone(), publish.qos, publish.retain)
+            .await
+        {
+            error!("Failed to replay buffered MQTT publish to {}: {}", publish.topic, e);
+            delivery.offline.lock().unwrap().push_front(publish);
+            return;
+        }
+    }
+}
+
 #[async_trait]
 impl ToolExecutor for MqttToolExecutor {
     async fn execute(&self, request: ExecutionRequest) -> ExecutorResult<ToolResult> {
@@ -167,7 +885,24 @@ impl ToolExecutor for MqttToolExecutor {
                         parameter: "payload".to_string(),
                     })?;
 
-                let payload_bytes = if payload.is_string() {
+                let encoding = request.parameters.get("encoding")
+                    .and_then(Value::as_str)
+                    .unwrap_or("raw");
+
+                let payload_bytes = if encoding.eq_ignore_ascii_case("sparkplug_b") {
+                    let seq = {
+                        let mut seq = self.sparkplug_seq.lock().await;
+                        let current = *seq;
+                        *seq = (current + 1) % 256;
+                        current
+                    };
+                    sparkplug::payload_from_json(payload, Some(seq))
+                        .and_then(|p| sparkplug::encode_payload(&p))
+                        .map_err(|e| ExecutorError::ValidationError {
+                            parameter: "payload".to_string(),
+                            reason: e.to_string(),
+                        })?
+                } else if payload.is_string() {
                     payload.as_str().unwrap().as_bytes().to_vec()
                 } else {
                     serde_json::to_vec(payload)
@@ -177,23 +912,31 @@ impl ToolExecutor for MqttToolExecutor {
                         })?
                 };
 
-                let qos = match request.parameters.get("qos").and_then(Value::as_u64) {
-                    Some(0) => QoS::AtMostOnce,
-                    Some(1) => QoS::AtLeastOnce,
-                    Some(2) => QoS::ExactlyOnce,
-                    _ => QoS::AtLeastOnce,
-                };
+                let qos = qos_from_level(request.parameters.get("qos").and_then(Value::as_u64));
 
                 let retain = request.parameters.get("retain")
                     .and_then(Value::as_bool)
                     .unwrap_or(false);
 
-                self.publish(topic, &payload_bytes, qos, retain).await?;
+                let outcome = self.publish(topic, &payload_bytes, qos, retain).await?;
                 metrics.bytes_written = payload_bytes.len() as u64;
 
-                serde_json::json!({
-                    "success": true,
-                })
+                match outcome {
+                    PublishOutcome::Acknowledged { packet_id } => serde_json::json!({
+                        "success": true,
+                        "acknowledged": true,
+                        "packet_id": packet_id,
+                    }),
+                    PublishOutcome::Sent => serde_json::json!({
+                        "success": true,
+                        "acknowledged": false,
+                    }),
+                    PublishOutcome::Buffered => serde_json::json!({
+                        "success": true,
+                        "acknowledged": false,
+                        "buffered": true,
+                    }),
+                }
             },
             "subscribe" => {
                 let topic = request.parameters.get("topic")
@@ -203,12 +946,7 @@ impl ToolExecutor for MqttToolExecutor {
                         parameter: "topic".to_string(),
                     })?;
 
-                let qos = match request.parameters.get("qos").and_then(Value::as_u64) {
-                    Some(0) => QoS::AtMostOnce,
-                    Some(1) => QoS::AtLeastOnce,
-                    Some(2) => QoS::ExactlyOnce,
-                    _ => QoS::AtLeastOnce,
-                };
+                let qos = qos_from_level(request.parameters.get("qos").and_then(Value::as_u64));
 
                 self.subscribe(topic, qos).await?;
 
@@ -294,6 +1032,19 @@ impl ToolExecutor for MqttToolExecutor {
             });
         }
 
+        // Validate payload encoding
+        if let Some(encoding) = parameters.get("encoding").and_then(Value::as_str) {
+            if !["raw", "sparkplug_b"].contains(&encoding.to_lowercase().as_str()) {
+                errors.push(ValidationError {
+                    parameter: "encoding".to_string(),
+                    code: "INVALID_VALUE".to_string(),
+                    message: format!("Invalid payload encoding: {}", encoding),
+                    expected: Some("raw or sparkplug_b".to_string()),
+                    actual: Some(encoding.to_string()),
+                });
+            }
+        }
+
         // Validate QoS
         if let Some(qos) = parameters.get("qos").and_then(Value::as_u64) {
             if qos > 2 {
@@ -363,4 +1114,124 @@ mod tests {
         assert!(result.valid);
         assert!(result.errors.is_empty());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_delivery_tracker_acknowledgement() {
+        let tracker = DeliveryTracker::new();
+
+        let (qos1_sender, mut qos1) = oneshot::channel();
+        let (qos2_sender, mut qos2) = oneshot::channel();
+        {
+            let mut queued = tracker.queued.lock().unwrap();
+            queued.push_back(PendingPublish { qos: QoS::AtLeastOnce, sender: qos1_sender });
+            queued.push_back(PendingPublish { qos: QoS::ExactlyOnce, sender: qos2_sender });
+        }
+
+        tracker.on_outgoing_publish(7);
+        tracker.on_outgoing_publish(8);
+        assert!(qos1.try_recv().is_err());
+
+        tracker.on_acknowledged(7, QoS::AtLeastOnce);
+        assert_eq!(qos1.try_recv().unwrap(), 7);
+
+        // A PUBACK does not complete a QoS 2 exchange
+        tracker.on_acknowledged(8, QoS::AtLeastOnce);
+        assert!(qos2.try_recv().is_err());
+        tracker.on_acknowledged(8, QoS::ExactlyOnce);
+        assert_eq!(qos2.try_recv().unwrap(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_delivery_tracker_ignores_retransmissions() {
+        let tracker = DeliveryTracker::new();
+
+        let (first_sender, mut first) = oneshot::channel();
+        let (second_sender, mut second) = oneshot::channel();
+        {
+            let mut queued = tracker.queued.lock().unwrap();
+            queued.push_back(PendingPublish { qos: QoS::AtLeastOnce, sender: first_sender });
+            queued.push_back(PendingPublish { qos: QoS::AtLeastOnce, sender: second_sender });
+        }
+
+        tracker.on_outgoing_publish(1);
+        // Packet 1 is written again after a reconnect, before packet 2
+        tracker.on_outgoing_publish(1);
+        tracker.on_outgoing_publish(2);
+
+        tracker.on_acknowledged(2, QoS::AtLeastOnce);
+        assert_eq!(second.try_recv().unwrap(), 2);
+        assert!(first.try_recv().is_err());
+        tracker.on_acknowledged(1, QoS::AtLeastOnce);
+        assert_eq!(first.try_recv().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_offline_buffer_drops_oldest() {
+        let tracker = DeliveryTracker::new();
+
+        for i in 0..3 {
+            tracker.buffer(BufferedPublish {
+                topic: format!("test/{}", i),
+                payload: Vec::new(),
+                qos: QoS::AtLeastOnce,
+                retain: false,
+            }, 2);
+        }
+
+        let offline = tracker.offline.lock().unwrap();
+        assert_eq!(offline.len(), 2);
+        assert_eq!(offline[0].topic, "test/1");
+    }
+
+    #[test]
+    fn test_parse_broker_endpoint() {
+        assert_eq!(parse_broker_endpoint("mqtt://broker.local:8883").unwrap(), ("broker.local".to_string(), 8883, false));
+        assert_eq!(parse_broker_endpoint("broker.local").unwrap(), ("broker.local".to_string(), 1883, false));
+        assert_eq!(parse_broker_endpoint("mqtts://broker.local").unwrap(), ("broker.local".to_string(), 8883, true));
+        assert_eq!(parse_broker_endpoint("ssl://broker.local:9883").unwrap(), ("broker.local".to_string(), 9883, true));
+        assert!(parse_broker_endpoint("ws://broker.local").is_err());
+        assert!(parse_broker_endpoint("mqtt://:1883").is_err());
+        assert!(parse_broker_endpoint("broker.local:port").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_from_connection_resolves_vault_credentials() {
+        use crate::infrastructure::security::{EncryptionService, InMemorySecretRepository};
+
+        let vault = CredentialVault::new(
+            Arc::new(EncryptionService::new("vault-key").unwrap()),
+            Arc::new(InMemorySecretRepository::new()),
+        );
+        let secret = vault.store("Broker", HashMap::from([
+            ("username".to_string(), "operator".to_string()),
+            ("password".to_string(), "s3cret".to_string()),
+        ])).await.unwrap();
+
+        let connection = ConnectionConfig {
+            endpoint: "mqtt://broker.local:1884".to_string(),
+            credentials_secret_id: Some(secret.id),
+            timeout_seconds: 5,
+            retry_config: Default::default(),
+            custom_params: HashMap::new(),
+        };
+
+        let executor = MqttToolExecutor::from_connection("twin-1".to_string(), &connection, &vault).await.unwrap();
+        assert_eq!(executor.broker_url, "broker.local");
+        assert_eq!(executor.broker_port, 1884);
+        assert_eq!(executor.username.as_deref(), Some("operator"));
+        assert_eq!(executor.password.as_deref(), Some("s3cret"));
+        assert!(executor.tls.is_none());
+
+        // TLS endpoints need a CA and connect with it
+        let mut connection = ConnectionConfig {
+            endpoint: "mqtts://broker.local".to_string(),
+            ..connection
+        };
+        assert!(MqttToolExecutor::from_connection("twin-1".to_string(), &connection, &vault).await.is_err());
+
+        connection.custom_params.insert("tls_ca_path".to_string(), serde_json::json!("/etc/ssl/broker-ca.pem"));
+        let executor = MqttToolExecutor::from_connection("twin-1".to_string(), &connection, &vault).await.unwrap();
+        assert_eq!(executor.broker_port, 8883);
+        assert_eq!(executor.tls.unwrap().ca_path, PathBuf::from("/etc/ssl/broker-ca.pem"));
+    }
+}
@@ -0,0 +1,1442 @@
+//! Sparkplug B payload support for MQTT ingestion.
+//!
+//! Sparkplug B carries metrics in a protobuf `Payload` published on topics of
+//! the form `spBv1.0/{group_id}/{message_type}/{edge_node_id}[/{device_id}]`.
+//! This module decodes and encodes those payloads, tracks per-node session
+//! state (bdSeq, seq and the metric alias tables announced in BIRTH
+//! certificates) and maps decoded metrics onto the sensor data of the digital
+//! twin bound to each edge node or device.
+//!
+//! Bindings are kept as data sources of type
+//! `DataSourceType::Custom { source_id: "sparkplug_b" }` on the bound twin,
+//! with the edge node (and optionally the device) in
+//! `ConnectionConfig.custom_params`, so they are restored at startup.
+
+use base64::Engine as _;
+use chrono::{DateTime, TimeZone, Utc};
+use prost::Message;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::core::domain::{
+    models::{
+        DataSource, DataSourceType, DigitalTwin, SensorData, SensorDataId, SensorInfo,
+        SensorReading, SensorSpecifications, SensorStatus, SensorType, SensorValue,
+        ReadingQuality, TwinId,
+    },
+    traits::repository::{Pagination, RepositoryError, SensorDataRepository},
+};
+use crate::infrastructure::transform::{self, MappingIngestor, SourceSelector};
+
+/// Topic namespace used by Sparkplug B
+pub const SPARKPLUG_NAMESPACE: &str = "spBv1.0";
+
+/// Custom source identifier of Sparkplug bindings
+pub const SPARKPLUG_SOURCE_ID: &str = "sparkplug_b";
+
+/// Custom parameters of a binding data source
+const GROUP_ID_PARAM: &str = "group_id";
+const EDGE_NODE_ID_PARAM: &str = "edge_node_id";
+const DEVICE_ID_PARAM: &str = "device_id";
+
+/// Metric carrying the birth/death sequence number
+const BD_SEQ_METRIC: &str = "bdSeq";
+
+/// Metric used to request a rebirth from an edge node
+const REBIRTH_METRIC: &str = "Node Control/Rebirth";
+
+/// Sensors loaded per page when looking up those of an edge node
+const SENSOR_PAGE_SIZE: usize = 100;
+
+/// Result type for Sparkplug operations
+pub type SparkplugResult<T> = Result<T, SparkplugError>;
+
+/// Sparkplug error types
+#[derive(Debug, thiserror::Error)]
+pub enum SparkplugError {
+    /// Topic is not a valid Sparkplug B topic
+    #[error("Invalid Sparkplug topic: {0}")]
+    InvalidTopic(String),
+
+    /// Payload could not be decoded
+    #[error("Failed to decode Sparkplug payload: {0}")]
+    Decode(String),
+
+    /// Payload could not be encoded
+    #[error("Failed to encode Sparkplug payload: {0}")]
+    Encode(String),
+
+    /// Metric value does not match its declared data type
+    #[error("Invalid value for metric {metric}: {reason}")]
+    InvalidValue { metric: String, reason: String },
+
+    /// Binding data source without an edge node
+    #[error("Invalid Sparkplug binding: {0}")]
+    InvalidBinding(String),
+
+    /// Repository error while persisting sensor data
+    #[error("Repository error: {0}")]
+    Repository(#[from] RepositoryError),
+}
+
+/// Sparkplug B `Payload` protobuf message
+#[derive(Clone, PartialEq, Message)]
+pub struct Payload {
+    /// Milliseconds since epoch when the payload was created
+    #[prost(uint64, optional, tag = "1")]
+    pub timestamp: Option<u64>,
+
+    /// Metrics contained in the payload
+    #[prost(message, repeated, tag = "2")]
+    pub metrics: Vec<Metric>,
+
+    /// Message sequence number (0-255)
+    #[prost(uint64, optional, tag = "3")]
+    pub seq: Option<u64>,
+
+    /// Optional payload UUID
+    #[prost(string, optional, tag = "4")]
+    pub uuid: Option<String>,
+
+    /// Optional opaque body
+    #[prost(bytes = "vec", optional, tag = "5")]
+    pub body: Option<Vec<u8>>,
+}
+
+/// Sparkplug B `Payload.Metric` protobuf message
+#[derive(Clone, PartialEq, Message)]
+pub struct Metric {
+    /// Metric name (required in BIRTH, optional when an alias is used)
+    #[prost(string, optional, tag = "1")]
+    pub name: Option<String>,
+
+    /// Metric alias announced in BIRTH
+    #[prost(uint64, optional, tag = "2")]
+    pub alias: Option<u64>,
+
+    /// Milliseconds since epoch when the value was sampled
+    #[prost(uint64, optional, tag = "3")]
+    pub timestamp: Option<u64>,
+
+    /// Sparkplug data type code
+    #[prost(uint32, optional, tag = "4")]
+    pub datatype: Option<u32>,
+
+    /// Whether the value is historical rather than live
+    #[prost(bool, optional, tag = "5")]
+    pub is_historical: Option<bool>,
+
+    /// Whether the value should not be stored
+    #[prost(bool, optional, tag = "6")]
+    pub is_transient: Option<bool>,
+
+    /// Whether the value is null
+    #[prost(bool, optional, tag = "7")]
+    pub is_null: Option<bool>,
+
+    /// Metric properties (engineering units, ranges, ...)
+    #[prost(message, optional, tag = "9")]
+    pub properties: Option<PropertySet>,
+
+    /// Metric value
+    #[prost(oneof = "MetricValue", tags = "10, 11, 12, 13, 14, 15, 16")]
+    pub value: Option<MetricValue>,
+}
+
+/// Scalar metric values supported by this implementation
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum MetricValue {
+    #[prost(uint32, tag = "10")]
+    IntValue(u32),
+    #[prost(uint64, tag = "11")]
+    LongValue(u64),
+    #[prost(float, tag = "12")]
+    FloatValue(f32),
+    #[prost(double, tag = "13")]
+    DoubleValue(f64),
+    #[prost(bool, tag = "14")]
+    BooleanValue(bool),
+    #[prost(string, tag = "15")]
+    StringValue(String),
+    #[prost(bytes, tag = "16")]
+    BytesValue(Vec<u8>),
+}
+
+/// Sparkplug B `Payload.PropertySet` protobuf message
+#[derive(Clone, PartialEq, Message)]
+pub struct PropertySet {
+    /// Property names
+    #[prost(string, repeated, tag = "1")]
+    pub keys: Vec<String>,
+
+    /// Property values, positionally matching `keys`
+    #[prost(message, repeated, tag = "2")]
+    pub values: Vec<PropertyValue>,
+}
+
+/// Sparkplug B `Payload.PropertyValue` protobuf message
+#[derive(Clone, PartialEq, Message)]
+pub struct PropertyValue {
+    /// Sparkplug data type code
+    #[prost(uint32, optional, tag = "1")]
+    pub r#type: Option<u32>,
+
+    /// Whether the value is null
+    #[prost(bool, optional, tag = "2")]
+    pub is_null: Option<bool>,
+
+    /// Property value
+    #[prost(oneof = "PropertyValueKind", tags = "3, 4, 5, 6, 7, 8")]
+    pub value: Option<PropertyValueKind>,
+}
+
+/// Scalar property values
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum PropertyValueKind {
+    #[prost(uint32, tag = "3")]
+    IntValue(u32),
+    #[prost(uint64, tag = "4")]
+    LongValue(u64),
+    #[prost(float, tag = "5")]
+    FloatValue(f32),
+    #[prost(double, tag = "6")]
+    DoubleValue(f64),
+    #[prost(bool, tag = "7")]
+    BooleanValue(bool),
+    #[prost(string, tag = "8")]
+    StringValue(String),
+}
+
+/// Sparkplug B data types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SparkplugDataType {
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Float,
+    Double,
+    Boolean,
+    String,
+    DateTime,
+    Text,
+    Uuid,
+    Bytes,
+    Unknown,
+}
+
+impl SparkplugDataType {
+    /// Map a protobuf data type code to a data type
+    pub fn from_code(code: u32) -> Self {
+        match code {
+            1 => Self::Int8,
+            2 => Self::Int16,
+            3 => Self::Int32,
+            4 => Self::Int64,
+            5 => Self::UInt8,
+            6 => Self::UInt16,
+            7 => Self::UInt32,
+            8 => Self::UInt64,
+            9 => Self::Float,
+            10 => Self::Double,
+            11 => Self::Boolean,
+            12 => Self::String,
+            13 => Self::DateTime,
+            14 => Self::Text,
+            15 => Self::Uuid,
+            17 => Self::Bytes,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Protobuf data type code
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::Int8 => 1,
+            Self::Int16 => 2,
+            Self::Int32 => 3,
+            Self::Int64 => 4,
+            Self::UInt8 => 5,
+            Self::UInt16 => 6,
+            Self::UInt32 => 7,
+            Self::UInt64 => 8,
+            Self::Float => 9,
+            Self::Double => 10,
+            Self::Boolean => 11,
+            Self::String => 12,
+            Self::DateTime => 13,
+            Self::Text => 14,
+            Self::Uuid => 15,
+            Self::Bytes => 17,
+            Self::Unknown => 0,
+        }
+    }
+
+    /// Parse a data type from its Sparkplug name (case-insensitive)
+    pub fn from_name(name: &str) -> Option<Self> {
+        let data_type = match name.to_lowercase().as_str() {
+            "int8" => Self::Int8,
+            "int16" => Self::Int16,
+            "int32" => Self::Int32,
+            "int64" => Self::Int64,
+            "uint8" => Self::UInt8,
+            "uint16" => Self::UInt16,
+            "uint32" => Self::UInt32,
+            "uint64" => Self::UInt64,
+            "float" => Self::Float,
+            "double" => Self::Double,
+            "boolean" => Self::Boolean,
+            "string" => Self::String,
+            "datetime" => Self::DateTime,
+            "text" => Self::Text,
+            "uuid" => Self::Uuid,
+            "bytes" => Self::Bytes,
+            _ => return None,
+        };
+        Some(data_type)
+    }
+
+    /// Name used when mapping the metric to a sensor category
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Int8 => "Int8",
+            Self::Int16 => "Int16",
+            Self::Int32 => "Int32",
+            Self::Int64 => "Int64",
+            Self::UInt8 => "UInt8",
+            Self::UInt16 => "UInt16",
+            Self::UInt32 => "UInt32",
+            Self::UInt64 => "UInt64",
+            Self::Float => "Float",
+            Self::Double => "Double",
+            Self::Boolean => "Boolean",
+            Self::String => "String",
+            Self::DateTime => "DateTime",
+            Self::Text => "Text",
+            Self::Uuid => "UUID",
+            Self::Bytes => "Bytes",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
+/// Sparkplug B message types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SparkplugMessageType {
+    NBirth,
+    NDeath,
+    DBirth,
+    DDeath,
+    NData,
+    DData,
+    NCmd,
+    DCmd,
+    State,
+}
+
+impl SparkplugMessageType {
+    /// Parse a message type from its topic token
+    pub fn parse(token: &str) -> Option<Self> {
+        let message_type = match token {
+            "NBIRTH" => Self::NBirth,
+            "NDEATH" => Self::NDeath,
+            "DBIRTH" => Self::DBirth,
+            "DDEATH" => Self::DDeath,
+            "NDATA" => Self::NData,
+            "DDATA" => Self::DData,
+            "NCMD" => Self::NCmd,
+            "DCMD" => Self::DCmd,
+            "STATE" => Self::State,
+            _ => return None,
+        };
+        Some(message_type)
+    }
+
+    /// Topic token for the message type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NBirth => "NBIRTH",
+            Self::NDeath => "NDEATH",
+            Self::DBirth => "DBIRTH",
+            Self::DDeath => "DDEATH",
+            Self::NData => "NDATA",
+            Self::DData => "DDATA",
+            Self::NCmd => "NCMD",
+            Self::DCmd => "DCMD",
+            Self::State => "STATE",
+        }
+    }
+
+    /// Whether the message is addressed to a device rather than the node
+    pub fn is_device_message(&self) -> bool {
+        matches!(self, Self::DBirth | Self::DDeath | Self::DData | Self::DCmd)
+    }
+}
+
+/// Parsed Sparkplug B topic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparkplugTopic {
+    /// Sparkplug group ID
+    pub group_id: String,
+
+    /// Message type
+    pub message_type: SparkplugMessageType,
+
+    /// Edge node ID
+    pub edge_node_id: String,
+
+    /// Device ID for device-level messages
+    pub device_id: Option<String>,
+}
+
+impl SparkplugTopic {
+    /// Whether a topic lives in the Sparkplug B namespace
+    pub fn is_sparkplug(topic: &str) -> bool {
+        topic.starts_with(SPARKPLUG_NAMESPACE)
+    }
+
+    /// Parse a topic string
+    pub fn parse(topic: &str) -> SparkplugResult<Self> {
+        let parts: Vec<&str> = topic.split('/').collect();
+        if parts.len() < 4 || parts[0] != SPARKPLUG_NAMESPACE {
+            return Err(SparkplugError::InvalidTopic(topic.to_string()));
+        }
+
+        let message_type = SparkplugMessageType::parse(parts[2])
+            .ok_or_else(|| SparkplugError::InvalidTopic(topic.to_string()))?;
+
+        let device_id = match (message_type.is_device_message(), parts.len()) {
+            (true, 5) => Some(parts[4].to_string()),
+            (false, 4) => None,
+            _ => return Err(SparkplugError::InvalidTopic(topic.to_string())),
+        };
+
+        Ok(Self {
+            group_id: parts[1].to_string(),
+            message_type,
+            edge_node_id: parts[3].to_string(),
+            device_id,
+        })
+    }
+
+    /// Build a command topic for the given node or device
+    pub fn command(group_id: &str, edge_node_id: &str, device_id: Option<&str>) -> Self {
+        Self {
+            group_id: group_id.to_string(),
+            message_type: if device_id.is_some() {
+                SparkplugMessageType::DCmd
+            } else {
+                SparkplugMessageType::NCmd
+            },
+            edge_node_id: edge_node_id.to_string(),
+            device_id: device_id.map(str::to_string),
+        }
+    }
+
+    /// Key identifying the edge node (`group/edge`)
+    pub fn node_key(&self) -> String {
+        format!("{}/{}", self.group_id, self.edge_node_id)
+    }
+
+    /// Key identifying the node or device the message is about
+    pub fn scope_key(&self) -> String {
+        scope_key(&self.node_key(), self.device_id.as_deref())
+    }
+
+    /// Render the topic string
+    pub fn to_topic_string(&self) -> String {
+        match &self.device_id {
+            Some(device_id) => format!(
+                "{}/{}/{}/{}/{}",
+                SPARKPLUG_NAMESPACE, self.group_id, self.message_type.as_str(),
+                self.edge_node_id, device_id,
+            ),
+            None => format!(
+                "{}/{}/{}/{}",
+                SPARKPLUG_NAMESPACE, self.group_id, self.message_type.as_str(),
+                self.edge_node_id,
+            ),
+        }
+    }
+}
+
+fn scope_key(node_key: &str, device_id: Option<&str>) -> String {
+    match device_id {
+        Some(device_id) => format!("{}/{}", node_key, device_id),
+        None => node_key.to_string(),
+    }
+}
+
+/// A metric decoded from a Sparkplug payload
+#[derive(Debug, Clone)]
+pub struct DecodedMetric {
+    /// Metric name (resolved from the alias table if necessary)
+    pub name: String,
+
+    /// Metric alias, if any
+    pub alias: Option<u64>,
+
+    /// Declared data type
+    pub datatype: SparkplugDataType,
+
+    /// Sample timestamp
+    pub timestamp: DateTime<Utc>,
+
+    /// Decoded value, `None` for null metrics
+    pub value: Option<SensorValue>,
+
+    /// Engineering unit from the `engUnit` property
+    pub unit: Option<String>,
+
+    /// Whether the value is historical
+    pub is_historical: bool,
+}
+
+/// Decode a raw Sparkplug payload
+pub fn decode_payload(bytes: &[u8]) -> SparkplugResult<Payload> {
+    Payload::decode(bytes).map_err(|e| SparkplugError::Decode(e.to_string()))
+}
+
+/// Encode a Sparkplug payload
+pub fn encode_payload(payload: &Payload) -> SparkplugResult<Vec<u8>> {
+    let mut buffer = Vec::with_capacity(payload.encoded_len());
+    payload.encode(&mut buffer)
+        .map_err(|e| SparkplugError::Encode(e.to_string()))?;
+    Ok(buffer)
+}
+
+/// Build a payload from a JSON description of metrics.
+///
+/// The JSON must be an object with a `metrics` array whose entries carry a
+/// `name`, a `value` and optionally a `datatype` name. Data types are inferred
+/// from the JSON value when omitted.
+pub fn payload_from_json(json: &Value, seq: Option<u64>) -> SparkplugResult<Payload> {
+    let metrics = json.get("metrics")
+        .and_then(Value::as_array)
+        .ok_or_else(|| SparkplugError::Encode("payload must contain a metrics array".to_string()))?;
+
+    let now = Utc::now().timestamp_millis() as u64;
+    let mut encoded = Vec::with_capacity(metrics.len());
+
+    for metric in metrics {
+        let name = metric.get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| SparkplugError::Encode("metric name is required".to_string()))?;
+        let value = metric.get("value").unwrap_or(&Value::Null);
+
+        let datatype = match metric.get("datatype").and_then(Value::as_str) {
+            Some(type_name) => SparkplugDataType::from_name(type_name)
+                .ok_or_else(|| SparkplugError::InvalidValue {
+                    metric: name.to_string(),
+                    reason: format!("unknown data type {}", type_name),
+                })?,
+            None => infer_datatype(value),
+        };
+
+        encoded.push(Metric {
+            name: Some(name.to_string()),
+            timestamp: Some(now),
+            datatype: Some(datatype.code()),
+            is_null: value.is_null().then_some(true),
+            value: json_to_metric_value(name, datatype, value)?,
+            ..Default::default()
+        });
+    }
+
+    Ok(Payload {
+        timestamp: Some(now),
+        metrics: encoded,
+        seq,
+        ..Default::default()
+    })
+}
+
+/// Build an NCMD payload asking an edge node to republish its BIRTH certificates
+pub fn rebirth_request() -> Payload {
+    let now = Utc::now().timestamp_millis() as u64;
+    Payload {
+        timestamp: Some(now),
+        metrics: vec![Metric {
+            name: Some(REBIRTH_METRIC.to_string()),
+            timestamp: Some(now),
+            datatype: Some(SparkplugDataType::Boolean.code()),
+            value: Some(MetricValue::BooleanValue(true)),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+fn infer_datatype(value: &Value) -> SparkplugDataType {
+    match value {
+        Value::Bool(_) => SparkplugDataType::Boolean,
+        Value::Number(n) if n.is_i64() || n.is_u64() => SparkplugDataType::Int64,
+        Value::Number(_) => SparkplugDataType::Double,
+        _ => SparkplugDataType::String,
+    }
+}
+
+fn json_to_metric_value(
+    name: &str,
+    datatype: SparkplugDataType,
+    value: &Value,
+) -> SparkplugResult<Option<MetricValue>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+
+    let invalid = |reason: &str| SparkplugError::InvalidValue {
+        metric: name.to_string(),
+        reason: reason.to_string(),
+    };
+
+    let metric_value = match datatype {
+        SparkplugDataType::Int8 | SparkplugDataType::Int16 | SparkplugDataType::Int32 => {
+            let v = value.as_i64().ok_or_else(|| invalid("expected an integer"))?;
+            MetricValue::IntValue(v as i32 as u32)
+        },
+        SparkplugDataType::UInt8 | SparkplugDataType::UInt16 | SparkplugDataType::UInt32 => {
+            let v = value.as_u64().ok_or_else(|| invalid("expected an unsigned integer"))?;
+            MetricValue::IntValue(v as u32)
+        },
+        SparkplugDataType::Int64 => {
+            let v = value.as_i64().ok_or_else(|| invalid("expected an integer"))?;
+            MetricValue::LongValue(v as u64)
+        },
+        SparkplugDataType::UInt64 | SparkplugDataType::DateTime => {
+            let v = value.as_u64().ok_or_else(|| invalid("expected an unsigned integer"))?;
+            MetricValue::LongValue(v)
+        },
+        SparkplugDataType::Float => {
+            let v = value.as_f64().ok_or_else(|| invalid("expected a number"))?;
+            MetricValue::FloatValue(v as f32)
+        },
+        SparkplugDataType::Double => {
+            let v = value.as_f64().ok_or_else(|| invalid("expected a number"))?;
+            MetricValue::DoubleValue(v)
+        },
+        SparkplugDataType::Boolean => {
+            let v = value.as_bool().ok_or_else(|| invalid("expected a boolean"))?;
+            MetricValue::BooleanValue(v)
+        },
+        SparkplugDataType::Bytes => {
+            let v = value.as_str().ok_or_else(|| invalid("expected a base64 string"))?;
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(v)
+                .map_err(|e| invalid(&e.to_string()))?;
+            MetricValue::BytesValue(bytes)
+        },
+        SparkplugDataType::String | SparkplugDataType::Text | SparkplugDataType::Uuid => {
+            match value.as_str() {
+                Some(s) => MetricValue::StringValue(s.to_string()),
+                None => MetricValue::StringValue(value.to_string()),
+            }
+        },
+        SparkplugDataType::Unknown => return Err(invalid("unsupported data type")),
+    };
+
+    Ok(Some(metric_value))
+}
+
+fn metric_to_sensor_value(datatype: SparkplugDataType, value: &MetricValue) -> SensorValue {
+    match (datatype, value) {
+        (SparkplugDataType::Int8, MetricValue::IntValue(v)) => SensorValue::Numeric(*v as i8 as f64),
+        (SparkplugDataType::Int16, MetricValue::IntValue(v)) => SensorValue::Numeric(*v as i16 as f64),
+        (SparkplugDataType::Int32, MetricValue::IntValue(v)) => SensorValue::Numeric(*v as i32 as f64),
+        (_, MetricValue::IntValue(v)) => SensorValue::Numeric(*v as f64),
+        (SparkplugDataType::Int64, MetricValue::LongValue(v)) => SensorValue::Numeric(*v as i64 as f64),
+        (SparkplugDataType::DateTime, MetricValue::LongValue(v)) => {
+            SensorValue::String(millis_to_datetime(*v).to_rfc3339())
+        },
+        (_, MetricValue::LongValue(v)) => SensorValue::Numeric(*v as f64),
+        (_, MetricValue::FloatValue(v)) => SensorValue::Numeric(*v as f64),
+        (_, MetricValue::DoubleValue(v)) => SensorValue::Numeric(*v),
+        (_, MetricValue::BooleanValue(v)) => SensorValue::Boolean(*v),
+        (_, MetricValue::StringValue(v)) => SensorValue::String(v.clone()),
+        (_, MetricValue::BytesValue(v)) => SensorValue::Binary(base64::engine::general_purpose::STANDARD.encode(v)),
+    }
+}
+
+fn engineering_unit(properties: Option<&PropertySet>) -> Option<String> {
+    let properties = properties?;
+    let index = properties.keys.iter().position(|k| k == "engUnit")?;
+    match properties.values.get(index)?.value.as_ref()? {
+        PropertyValueKind::StringValue(unit) => Some(unit.clone()),
+        _ => None,
+    }
+}
+
+fn millis_to_datetime(millis: u64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(millis as i64)
+        .single()
+        .unwrap_or_else(Utc::now)
+}
+
+/// Metric registered in a BIRTH certificate
+#[derive(Debug, Clone)]
+struct BirthMetric {
+    device_id: Option<String>,
+    name: String,
+    datatype: SparkplugDataType,
+    unit: Option<String>,
+}
+
+/// Session state for one edge node
+#[derive(Debug, Default)]
+struct NodeSession {
+    /// bdSeq announced in the last NBIRTH
+    bd_seq: Option<u64>,
+
+    /// Last seq received from the node
+    last_seq: Option<u64>,
+
+    /// Whether the node is currently online
+    online: bool,
+
+    /// Alias table shared by the node and its devices
+    aliases: HashMap<u64, BirthMetric>,
+
+    /// Metrics announced per scope (node or device) keyed by name
+    births: HashMap<Option<String>, HashMap<String, BirthMetric>>,
+}
+
+impl NodeSession {
+    fn sensor_ids(&self, node_key: &str, device_id: Option<&Option<String>>) -> Vec<String> {
+        self.births.iter()
+            .filter(|(scope, _)| device_id.map_or(true, |d| *scope == d))
+            .flat_map(|(scope, metrics)| {
+                let prefix = scope_key(node_key, scope.as_deref());
+                metrics.keys()
+                    .map(move |name| format!("{}/{}", prefix, name))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// Result of processing a single Sparkplug message
+#[derive(Debug, Clone)]
+pub struct SparkplugUpdate {
+    /// Topic the message arrived on
+    pub topic: SparkplugTopic,
+
+    /// Sensors announced (or re-announced) by a BIRTH certificate
+    pub births: Vec<SensorInfo>,
+
+    /// Readings keyed by sensor ID
+    pub readings: Vec<(String, SensorReading)>,
+
+    /// Sensors that went offline because of a DEATH certificate
+    pub offline: Vec<String>,
+
+    /// Whether the node should be asked to rebirth (sequence gap, unknown alias
+    /// or data received before BIRTH)
+    pub rebirth_required: bool,
+}
+
+impl SparkplugUpdate {
+    fn new(topic: SparkplugTopic) -> Self {
+        Self {
+            topic,
+            births: Vec::new(),
+            readings: Vec::new(),
+            offline: Vec::new(),
+            rebirth_required: false,
+        }
+    }
+}
+
+/// Tracks Sparkplug session state and turns payloads into sensor updates
+#[derive(Debug, Default)]
+pub struct SparkplugSessionTracker {
+    sessions: HashMap<String, NodeSession>,
+}
+
+impl SparkplugSessionTracker {
+    /// Create a new session tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the given edge node is currently online
+    pub fn is_online(&self, node_key: &str) -> bool {
+        self.sessions.get(node_key).map_or(false, |s| s.online)
+    }
+
+    /// bdSeq of the current session of the given edge node
+    pub fn bd_seq(&self, node_key: &str) -> Option<u64> {
+        self.sessions.get(node_key).and_then(|s| s.bd_seq)
+    }
+
+    /// Process a message received on a Sparkplug topic
+    pub fn process(&mut self, topic: &str, bytes: &[u8]) -> SparkplugResult<SparkplugUpdate> {
+        let topic = SparkplugTopic::parse(topic)?;
+        let payload = decode_payload(bytes)?;
+        let mut update = SparkplugUpdate::new(topic.clone());
+        let node_key = topic.node_key();
+
+        match topic.message_type {
+            SparkplugMessageType::NBirth => {
+                let session = self.sessions.entry(node_key.clone()).or_default();
+                *session = NodeSession {
+                    bd_seq: find_bd_seq(&payload),
+                    last_seq: payload.seq,
+                    online: true,
+                    ..Default::default()
+                };
+                Self::register_births(session, &topic, &payload, &mut update);
+                info!("Sparkplug node {} born (bdSeq {:?})", node_key, session.bd_seq);
+            },
+            SparkplugMessageType::DBirth => {
+                let session = self.sessions.entry(node_key.clone()).or_default();
+                Self::check_sequence(session, &node_key, payload.seq, &mut update);
+                if !session.online {
+                    update.rebirth_required = true;
+                }
+                let device = topic.device_id.clone();
+                session.aliases.retain(|_, m| m.device_id != device);
+                session.births.remove(&device);
+                Self::register_births(session, &topic, &payload, &mut update);
+            },
+            SparkplugMessageType::NData | SparkplugMessageType::DData => {
+                let session = self.sessions.entry(node_key.clone()).or_default();
+                Self::check_sequence(session, &node_key, payload.seq, &mut update);
+                if !session.online {
+                    debug!("Sparkplug data from {} before NBIRTH", node_key);
+                    update.rebirth_required = true;
+                    return Ok(update);
+                }
+                for metric in &payload.metrics {
+                    match Self::resolve(session, &topic, metric) {
+                        Some(birth) => {
+                            if let Some(reading) = decode_metric(metric, &birth)
+                                .and_then(|m| to_reading(&m))
+                            {
+                                let sensor_id = format!("{}/{}", topic.scope_key(), birth.name);
+                                update.readings.push((sensor_id, reading));
+                            }
+                        },
+                        None => {
+                            warn!(
+                                "Unknown Sparkplug metric {:?} (alias {:?}) on {}",
+                                metric.name, metric.alias, topic.scope_key(),
+                            );
+                            update.rebirth_required = true;
+                        },
+                    }
+                }
+            },
+            SparkplugMessageType::NDeath => {
+                if let Some(session) = self.sessions.get_mut(&node_key) {
+                    let death_bd_seq = find_bd_seq(&payload);
+                    if session.bd_seq.is_some() && death_bd_seq.is_some() && session.bd_seq != death_bd_seq {
+                        debug!(
+                            "Ignoring stale NDEATH for {} (bdSeq {:?}, current {:?})",
+                            node_key, death_bd_seq, session.bd_seq,
+                        );
+                        return Ok(update);
+                    }
+                    update.offline = session.sensor_ids(&node_key, None);
+                    session.online = false;
+                    session.last_seq = None;
+                    session.aliases.clear();
+                    session.births.clear();
+                    info!("Sparkplug node {} died", node_key);
+                }
+            },
+            SparkplugMessageType::DDeath => {
+                if let Some(session) = self.sessions.get_mut(&node_key) {
+                    Self::check_sequence(session, &node_key, payload.seq, &mut update);
+                    let device = topic.device_id.clone();
+                    update.offline = session.sensor_ids(&node_key, Some(&device));
+                    session.aliases.retain(|_, m| m.device_id != device);
+                    session.births.remove(&device);
+                }
+            },
+            SparkplugMessageType::NCmd
+            | SparkplugMessageType::DCmd
+            | SparkplugMessageType::State => {},
+        }
+
+        Ok(update)
+    }
+
+    fn register_births(
+        session: &mut NodeSession,
+        topic: &SparkplugTopic,
+        payload: &Payload,
+        update: &mut SparkplugUpdate,
+    ) {
+        let scope = topic.scope_key();
+        let births = session.births.entry(topic.device_id.clone()).or_default();
+
+        for metric in &payload.metrics {
+            let name = match &metric.name {
+                Some(name) if name != BD_SEQ_METRIC && !name.starts_with("Node Control/") => name.clone(),
+                _ => continue,
+            };
+
+            let birth = BirthMetric {
+                device_id: topic.device_id.clone(),
+                name: name.clone(),
+                datatype: SparkplugDataType::from_code(metric.datatype.unwrap_or_default()),
+                unit: engineering_unit(metric.properties.as_ref()),
+            };
+
+            if let Some(alias) = metric.alias {
+                session.aliases.insert(alias, birth.clone());
+            }
+
+            let sensor_id = format!("{}/{}", scope, name);
+            update.births.push(sensor_info(&sensor_id, &birth));
+
+            if let Some(reading) = decode_metric(metric, &birth).and_then(|m| to_reading(&m)) {
+                update.readings.push((sensor_id, reading));
+            }
+
+            births.insert(name, birth);
+        }
+    }
+
+    fn resolve(session: &NodeSession, topic: &SparkplugTopic, metric: &Metric) -> Option<BirthMetric> {
+        if let Some(name) = &metric.name {
+            return session.births.get(&topic.device_id)
+                .and_then(|metrics| metrics.get(name))
+                .cloned();
+        }
+
+        metric.alias
+            .and_then(|alias| session.aliases.get(&alias))
+            .filter(|birth| birth.device_id == topic.device_id)
+            .cloned()
+    }
+
+    fn check_sequence(
+        session: &mut NodeSession,
+        node_key: &str,
+        seq: Option<u64>,
+        update: &mut SparkplugUpdate,
+    ) {
+        let seq = match seq {
+            Some(seq) => seq,
+            None => return,
+        };
+
+        if let Some(last) = session.last_seq {
+            let expected = (last + 1) % 256;
+            if seq != expected {
+                warn!(
+                    "Sparkplug sequence gap on {}: expected {}, got {}",
+                    node_key, expected, seq,
+                );
+                update.rebirth_required = true;
+            }
+        }
+
+        session.last_seq = Some(seq);
+    }
+}
+
+fn find_bd_seq(payload: &Payload) -> Option<u64> {
+    payload.metrics.iter()
+        .find(|m| m.name.as_deref() == Some(BD_SEQ_METRIC))
+        .and_then(|m| match m.value {
+            Some(MetricValue::LongValue(v)) => Some(v),
+            Some(MetricValue::IntValue(v)) => Some(v as u64),
+            _ => None,
+        })
+}
+
+fn decode_metric(metric: &Metric, birth: &BirthMetric) -> Option<DecodedMetric> {
+    if metric.is_transient.unwrap_or(false) {
+        return None;
+    }
+
+    let datatype = metric.datatype
+        .map(SparkplugDataType::from_code)
+        .unwrap_or(birth.datatype);
+
+    let value = if metric.is_null.unwrap_or(false) {
+        None
+    } else {
+        metric.value.as_ref().map(|v| metric_to_sensor_value(datatype, v))
+    };
+
+    Some(DecodedMetric {
+        name: birth.name.clone(),
+        alias: metric.alias,
+        datatype,
+        timestamp: metric.timestamp.map(millis_to_datetime).unwrap_or_else(Utc::now),
+        value,
+        unit: engineering_unit(metric.properties.as_ref()).or_else(|| birth.unit.clone()),
+        is_historical: metric.is_historical.unwrap_or(false),
+    })
+}
+
+fn to_reading(metric: &DecodedMetric) -> Option<SensorReading> {
+    let value = metric.value.clone()?;
+    let context = metric.is_historical.then(|| crate::core::domain::models::ReadingContext {
+        environment: None,
+        correlated_sensors: Vec::new(),
+        events: vec!["sparkplug_historical".to_string()],
+        custom_data: HashMap::new(),
+    });
+
+    Some(SensorReading {
+        id: Uuid::new_v4(),
+        value,
+        timestamp: metric.timestamp,
+        quality: ReadingQuality::default(),
+        context,
+        alerts: Vec::new(),
+    })
+}
+
+fn sensor_info(sensor_id: &str, birth: &BirthMetric) -> SensorInfo {
+    SensorInfo {
+        sensor_id: sensor_id.to_string(),
+        name: birth.name.clone(),
+        sensor_type: SensorType::Custom {
+            category: format!("sparkplug:{}", birth.datatype.name()),
+            measurement_unit: birth.unit.clone().unwrap_or_default(),
+        },
+        location: None,
+        specifications: SensorSpecifications {
+            range: None,
+            accuracy: None,
+            resolution: None,
+            sampling_rate: None,
+            response_time_ms: None,
+            operating_temp_range: None,
+            power_consumption: None,
+            protocol: Some("Sparkplug B".to_string()),
+            manufacturer: None,
+        },
+        status: SensorStatus::Online,
+        calibration: None,
+    }
+}
+
+/// Persists Sparkplug updates as sensor data on bound digital twins
+pub struct SparkplugIngestor {
+    sensor_repository: Arc<dyn SensorDataRepository>,
+    tracker: Mutex<SparkplugSessionTracker>,
+    bindings: RwLock<HashMap<String, TwinId>>,
+    /// Binding key of each attached data source
+    sources: RwLock<HashMap<Uuid, String>>,
+    sensors: Mutex<HashMap<String, SensorDataId>>,
+    /// Scopes whose stored sensors were loaded into `sensors`
+    loaded_scopes: Mutex<HashSet<String>>,
+    mapping: Option<Arc<MappingIngestor>>,
+}
+
+impl SparkplugIngestor {
+    /// Create a new Sparkplug ingestor
+    pub fn new(sensor_repository: Arc<dyn SensorDataRepository>) -> Self {
+        Self {
+            sensor_repository,
+            tracker: Mutex::new(SparkplugSessionTracker::new()),
+            bindings: RwLock::new(HashMap::new()),
+            sources: RwLock::new(HashMap::new()),
+            sensors: Mutex::new(HashMap::new()),
+            loaded_scopes: Mutex::new(HashSet::new()),
+            mapping: None,
+        }
+    }
+
+    /// Also run readings through the mappings of the twin's message queue
+    /// sources whose topic filter matches the Sparkplug topic
+    ///
+    /// The record holds each metric's value under its name.
+    pub fn with_mapping(mut self, ingestor: Arc<MappingIngestor>) -> Self {
+        self.mapping = Some(ingestor);
+        self
+    }
+
+    /// Bind an edge node (and all its devices) to a digital twin
+    pub async fn bind_node(&self, group_id: &str, edge_node_id: &str, twin_id: TwinId) {
+        let key = format!("{}/{}", group_id, edge_node_id);
+        self.bindings.write().await.insert(key, twin_id);
+    }
+
+    /// Bind a single device to a digital twin, overriding the node binding
+    pub async fn bind_device(
+        &self,
+        group_id: &str,
+        edge_node_id: &str,
+        device_id: &str,
+        twin_id: TwinId,
+    ) {
+        let key = format!("{}/{}/{}", group_id, edge_node_id, device_id);
+        self.bindings.write().await.insert(key, twin_id);
+    }
+
+    /// Custom parameters of a data source binding an edge node, or one of
+    /// its devices
+    pub fn binding_params(
+        group_id: &str,
+        edge_node_id: &str,
+        device_id: Option<&str>,
+    ) -> HashMap<String, Value> {
+        let mut params = HashMap::from([
+            (GROUP_ID_PARAM.to_string(), Value::from(group_id)),
+            (EDGE_NODE_ID_PARAM.to_string(), Value::from(edge_node_id)),
+        ]);
+        if let Some(device_id) = device_id {
+            params.insert(DEVICE_ID_PARAM.to_string(), Value::from(device_id));
+        }
+        params
+    }
+
+    /// Whether a data source is a Sparkplug binding
+    pub fn supports(source: &DataSource) -> bool {
+        matches!(
+            &source.source_type,
+            DataSourceType::Custom { source_id } if source_id == SPARKPLUG_SOURCE_ID
+        )
+    }
+
+    /// Bind the edge node or device of a binding data source to its twin
+    pub async fn attach(&self, twin_id: TwinId, source: &DataSource) -> SparkplugResult<()> {
+        let params = &source.connection_config.custom_params;
+        let param = |name: &str| params.get(name).and_then(Value::as_str);
+        let (Some(group_id), Some(edge_node_id)) = (param(GROUP_ID_PARAM), param(EDGE_NODE_ID_PARAM)) else {
+            return Err(SparkplugError::InvalidBinding(format!(
+                "data source {} has no {} and {}",
+                source.id, GROUP_ID_PARAM, EDGE_NODE_ID_PARAM
+            )));
+        };
+        let key = match param(DEVICE_ID_PARAM) {
+            Some(device_id) => format!("{}/{}/{}", group_id, edge_node_id, device_id),
+            None => format!("{}/{}", group_id, edge_node_id),
+        };
+        self.bindings.write().await.insert(key.clone(), twin_id);
+        self.sources.write().await.insert(source.id, key);
+        Ok(())
+    }
+
+    /// Restore every active binding data source of a twin
+    pub async fn attach_twin(&self, twin: &DigitalTwin) -> usize {
+        let mut attached = 0;
+        for source in twin.active_data_sources() {
+            if !Self::supports(source) {
+                continue;
+            }
+            match self.attach(twin.id, source).await {
+                Ok(()) => attached += 1,
+                Err(e) => warn!("Failed to restore Sparkplug binding of twin {}: {}", twin.id, e),
+            }
+        }
+        attached
+    }
+
+    /// Remove the binding of a data source; returns whether it was bound
+    pub async fn detach(&self, source_id: Uuid) -> bool {
+        let Some(key) = self.sources.write().await.remove(&source_id) else {
+            return false;
+        };
+        self.bindings.write().await.remove(&key);
+        true
+    }
+
+    /// Resolve the twin bound to a topic
+    async fn twin_for(&self, topic: &SparkplugTopic) -> Option<TwinId> {
+        let bindings = self.bindings.read().await;
+        bindings.get(&topic.scope_key())
+            .or_else(|| bindings.get(&topic.node_key()))
+            .copied()
+    }
+
+    /// Handle a message received on a Sparkplug topic
+    pub async fn handle_message(
+        &self,
+        topic: &str,
+        payload: &[u8],
+    ) -> SparkplugResult<SparkplugUpdate> {
+        let update = self.tracker.lock().await.process(topic, payload)?;
+
+        let twin_id = match self.twin_for(&update.topic).await {
+            Some(twin_id) => twin_id,
+            None => {
+                debug!("No twin bound to Sparkplug scope {}", update.topic.scope_key());
+                return Ok(update);
+            },
+        };
+
+        let mut sensors = self.sensors.lock().await;
+        let scope = update.topic.scope_key();
+        if !self.loaded_scopes.lock().await.contains(&scope) {
+            self.load_sensors(twin_id, &scope, &mut sensors).await?;
+            self.loaded_scopes.lock().await.insert(scope);
+        }
+
+        for info in &update.births {
+            match sensors.get(&info.sensor_id) {
+                Some(id) => {
+                    let mut data = self.sensor_repository.get_by_id(*id).await?;
+                    data.sensor = info.clone();
+                    self.sensor_repository.update(data).await?;
+                },
+                None => {
+                    let mut data = SensorData::new(twin_id, info.clone());
+                    data.metadata.source_id = update.topic.scope_key();
+                    data.metadata.collection_method = "sparkplug_b".to_string();
+                    let created = self.sensor_repository.create(data).await?;
+                    sensors.insert(info.sensor_id.clone(), created.id);
+                },
+            }
+        }
+
+        for (sensor_id, reading) in &update.readings {
+            if let Some(id) = sensors.get(sensor_id) {
+                self.sensor_repository.add_reading(*id, reading.clone()).await?;
+            }
+        }
+
+        for sensor_id in &update.offline {
+            if let Some(id) = sensors.get(sensor_id) {
+                let mut data = self.sensor_repository.get_by_id(*id).await?;
+                data.sensor.status = SensorStatus::Offline;
+                self.sensor_repository.update(data).await?;
+            }
+        }
+        drop(sensors);
+
+        if let (Some(mapping), Some(timestamp)) = (&self.mapping, update.readings.iter().map(|(_, r)| r.timestamp).max()) {
+            let prefix = format!("{}/", update.topic.scope_key());
+            let record: serde_json::Map<String, Value> = update.readings.iter()
+                .map(|(sensor_id, reading)| (
+                    sensor_id.strip_prefix(&prefix).unwrap_or(sensor_id).to_string(),
+                    transform::record_value(&reading.value),
+                ))
+                .collect();
+            let topic = update.topic.to_topic_string();
+            if let Err(e) = mapping.ingest(twin_id, SourceSelector::Topic(&topic), &Value::Object(record), timestamp).await {
+                warn!("Failed to store mapped Sparkplug values on twin {}: {}", twin_id, e);
+            }
+        }
+
+        Ok(update)
+    }
+
+    /// Add the sensors stored for `scope` by earlier sessions, so a restart
+    /// reuses them instead of creating duplicates
+    async fn load_sensors(
+        &self,
+        twin_id: TwinId,
+        scope: &str,
+        sensors: &mut HashMap<String, SensorDataId>,
+    ) -> SparkplugResult<()> {
+        let mut offset = 0;
+        loop {
+            let page = self.sensor_repository
+                .get_by_twin_id(twin_id, Pagination { offset, limit: SENSOR_PAGE_SIZE })
+                .await?;
+            let fetched = page.items.len();
+
+            for data in page.items {
+                if data.metadata.source_id == scope {
+                    sensors.entry(data.sensor.sensor_id).or_insert(data.id);
+                }
+            }
+
+            offset += fetched;
+            if fetched == 0 || offset >= page.total {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::models::{ConnectionConfig, TwinType};
+    use crate::core::domain::traits::mock_repositories::InMemorySensorRepository;
+
+    fn metric(name: &str, alias: Option<u64>, datatype: SparkplugDataType, value: MetricValue) -> Metric {
+        Metric {
+            name: Some(name.to_string()),
+            alias,
+            timestamp: Some(1_700_000_000_000),
+            datatype: Some(datatype.code()),
+            value: Some(value),
+            ..Default::default()
+        }
+    }
+
+    fn encode(metrics: Vec<Metric>, seq: u64) -> Vec<u8> {
+        encode_payload(&Payload {
+            timestamp: Some(1_700_000_000_000),
+            metrics,
+            seq: Some(seq),
+            ..Default::default()
+        }).unwrap()
+    }
+
+    fn nbirth(bd_seq: u64) -> Vec<u8> {
+        encode(vec![
+            metric(BD_SEQ_METRIC, None, SparkplugDataType::Int64, MetricValue::LongValue(bd_seq)),
+            metric("Temperature", Some(1), SparkplugDataType::Double, MetricValue::DoubleValue(21.5)),
+        ], 0)
+    }
+
+    #[test]
+    fn test_topic_parsing() {
+        let topic = SparkplugTopic::parse("spBv1.0/plant/DDATA/edge1/pump1").unwrap();
+        assert_eq!(topic.group_id, "plant");
+        assert_eq!(topic.message_type, SparkplugMessageType::DData);
+        assert_eq!(topic.device_id.as_deref(), Some("pump1"));
+        assert_eq!(topic.to_topic_string(), "spBv1.0/plant/DDATA/edge1/pump1");
+
+        assert!(SparkplugTopic::parse("spBv1.0/plant/NDATA/edge1/extra").is_err());
+        assert!(SparkplugTopic::parse("plant/NDATA/edge1").is_err());
+    }
+
+    #[test]
+    fn test_payload_round_trip() {
+        let payload = payload_from_json(&serde_json::json!({
+            "metrics": [
+                { "name": "Setpoint", "value": 42.5 },
+                { "name": "Enabled", "value": true },
+                { "name": "Offset", "value": -3, "datatype": "Int16" },
+            ]
+        }), Some(7)).unwrap();
+
+        let decoded = decode_payload(&encode_payload(&payload).unwrap()).unwrap();
+        assert_eq!(decoded, payload);
+        assert_eq!(decoded.seq, Some(7));
+
+        let offset = &decoded.metrics[2];
+        let value = metric_to_sensor_value(
+            SparkplugDataType::from_code(offset.datatype.unwrap()),
+            offset.value.as_ref().unwrap(),
+        );
+        assert_eq!(value, SensorValue::Numeric(-3.0));
+    }
+
+    #[test]
+    fn test_alias_resolution() {
+        let mut tracker = SparkplugSessionTracker::new();
+
+        let update = tracker.process("spBv1.0/plant/NBIRTH/edge1", &nbirth(3)).unwrap();
+        assert_eq!(update.births.len(), 1);
+        assert_eq!(update.births[0].sensor_id, "plant/edge1/Temperature");
+        assert_eq!(tracker.bd_seq("plant/edge1"), Some(3));
+
+        let data = encode(vec![Metric {
+            alias: Some(1),
+            timestamp: Some(1_700_000_001_000),
+            value: Some(MetricValue::DoubleValue(22.0)),
+            ..Default::default()
+        }], 1);
+        let update = tracker.process("spBv1.0/plant/NDATA/edge1", &data).unwrap();
+        assert!(!update.rebirth_required);
+        assert_eq!(update.readings.len(), 1);
+        assert_eq!(update.readings[0].0, "plant/edge1/Temperature");
+        assert_eq!(update.readings[0].1.as_numeric(), Some(22.0));
+    }
+
+    #[test]
+    fn test_sequence_gap_requests_rebirth() {
+        let mut tracker = SparkplugSessionTracker::new();
+        tracker.process("spBv1.0/plant/NBIRTH/edge1", &nbirth(0)).unwrap();
+
+        let data = encode(vec![
+            metric("Temperature", None, SparkplugDataType::Double, MetricValue::DoubleValue(1.0)),
+        ], 5);
+        let update = tracker.process("spBv1.0/plant/NDATA/edge1", &data).unwrap();
+        assert!(update.rebirth_required);
+    }
+
+    #[test]
+    fn test_ndeath_bd_seq_handling() {
+        let mut tracker = SparkplugSessionTracker::new();
+        tracker.process("spBv1.0/plant/NBIRTH/edge1", &nbirth(4)).unwrap();
+
+        // A death certificate from a previous session is ignored
+        let stale = encode(vec![
+            metric(BD_SEQ_METRIC, None, SparkplugDataType::Int64, MetricValue::LongValue(3)),
+        ], 0);
+        let update = tracker.process("spBv1.0/plant/NDEATH/edge1", &stale).unwrap();
+        assert!(update.offline.is_empty());
+        assert!(tracker.is_online("plant/edge1"));
+
+        let death = encode(vec![
+            metric(BD_SEQ_METRIC, None, SparkplugDataType::Int64, MetricValue::LongValue(4)),
+        ], 0);
+        let update = tracker.process("spBv1.0/plant/NDEATH/edge1", &death).unwrap();
+        assert_eq!(update.offline, vec!["plant/edge1/Temperature".to_string()]);
+        assert!(!tracker.is_online("plant/edge1"));
+    }
+
+    #[tokio::test]
+    async fn test_restart_reuses_stored_sensors() {
+        let repository = Arc::new(InMemorySensorRepository::default());
+        let twin_id = Uuid::new_v4();
+
+        for bd_seq in 0..2 {
+            // A new ingestor per session, as after a restart
+            let ingestor = SparkplugIngestor::new(repository.clone());
+            ingestor.bind_node("plant", "edge1", twin_id).await;
+            ingestor.handle_message("spBv1.0/plant/NBIRTH/edge1", &nbirth(bd_seq)).await.unwrap();
+        }
+
+        let data = repository.data.lock().unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data.values().next().unwrap().sensor.sensor_id, "plant/edge1/Temperature");
+    }
+
+    #[tokio::test]
+    async fn test_bindings_are_restored_from_twin_data_sources() {
+        let repository = Arc::new(InMemorySensorRepository::default());
+        let mut twin = DigitalTwin::new(
+            "Chiller 2".to_string(),
+            "Plant room chiller".to_string(),
+            TwinType::Device {
+                device_type: "chiller".to_string(),
+                manufacturer: None,
+                model: None,
+            },
+        );
+        let source = DataSource {
+            id: Uuid::new_v4(),
+            name: "Edge node 1".to_string(),
+            source_type: DataSourceType::Custom { source_id: SPARKPLUG_SOURCE_ID.to_string() },
+            connection_config: ConnectionConfig {
+                endpoint: "spBv1.0/plant/edge1".to_string(),
+                credentials_secret_id: None,
+                timeout_seconds: 30,
+                retry_config: Default::default(),
+                custom_params: SparkplugIngestor::binding_params("plant", "edge1", None),
+            },
+            mappings: Vec::new(),
+            active: true,
+            last_connected: None,
+        };
+        twin.data_sources.push(source.clone());
+
+        // A new ingestor, as after a restart, binds from the stored twin
+        let ingestor = SparkplugIngestor::new(repository.clone());
+        assert_eq!(ingestor.attach_twin(&twin).await, 1);
+        ingestor.handle_message("spBv1.0/plant/NBIRTH/edge1", &nbirth(0)).await.unwrap();
+        {
+            let data = repository.data.lock().unwrap();
+            assert_eq!(data.len(), 1);
+            assert_eq!(data.values().next().unwrap().twin_id, twin.id);
+        }
+
+        assert!(ingestor.detach(source.id).await);
+        assert!(!ingestor.detach(source.id).await);
+        let topic = SparkplugTopic::parse("spBv1.0/plant/NDATA/edge1").unwrap();
+        assert_eq!(ingestor.twin_for(&topic).await, None);
+    }
+}
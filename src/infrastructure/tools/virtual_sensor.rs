@@ -0,0 +1,843 @@
+//! Virtual sensors producing synthetic data streams.
+//!
+//! A data source of type `DataSourceType::Custom { source_id: "virtual_sensor" }`
+//! carries a list of [`VirtualSensorConfig`]s in
+//! `ConnectionConfig.custom_params["virtual_sensors"]`. Each virtual sensor
+//! combines a base signal model (constant, sine, random walk, step changes)
+//! with gaussian noise, linear drift and injected faults (spikes, stuck-at
+//! values, dropouts and `QualityIssueType` scenarios), and is sampled at its
+//! configured rate. Generators are seedable so regression tests of the
+//! anomaly/alert logic see the same stream on every run.
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::core::domain::{
+    models::{
+        DataSource, DataSourceType, DigitalTwin, IssueSeverity, NoiseLevel, QualityIssue,
+        QualityIssueType, ReadingQuality, SensorData, SensorDataId, SensorInfo,
+        SensorReading, SensorSpecifications, SensorStatus, SensorType, SensorValue,
+        TwinId,
+    },
+    traits::repository::{Pagination, RepositoryError, SensorDataRepository},
+};
+
+/// Custom source identifier handled by the virtual sensor driver
+pub const VIRTUAL_SENSOR_SOURCE_ID: &str = "virtual_sensor";
+
+/// Custom parameter holding the sensor configurations
+const SENSORS_PARAM: &str = "virtual_sensors";
+
+/// Result type for virtual sensor operations
+pub type VirtualSensorResult<T> = Result<T, VirtualSensorError>;
+
+/// Virtual sensor error types
+#[derive(Debug, thiserror::Error)]
+pub enum VirtualSensorError {
+    /// Sensor configuration is invalid
+    #[error("Invalid virtual sensor configuration: {0}")]
+    InvalidConfig(String),
+
+    /// Data source is not a virtual sensor source
+    #[error("Data source {0} is not a virtual sensor source")]
+    UnsupportedSource(Uuid),
+
+    /// Repository error
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+}
+
+/// Base signal model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SignalModel {
+    /// Constant value
+    Constant { value: f64 },
+
+    /// offset + amplitude * sin(2π t / period + phase)
+    Sine {
+        #[serde(default)]
+        offset: f64,
+        amplitude: f64,
+        period_seconds: f64,
+        #[serde(default)]
+        phase: f64,
+    },
+
+    /// Gaussian random walk, optionally bounded
+    RandomWalk {
+        initial: f64,
+        step_std_dev: f64,
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+    },
+
+    /// Cycles through levels, holding each for `interval_seconds`
+    Step {
+        levels: Vec<f64>,
+        interval_seconds: f64,
+    },
+}
+
+/// Fault injected into a virtual sensor stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FaultKind {
+    /// Add `magnitude` to the value
+    Spike { magnitude: f64 },
+
+    /// Repeat a fixed value, or the last good value when `value` is absent
+    StuckAt {
+        #[serde(default)]
+        value: Option<f64>,
+    },
+
+    /// Produce no reading
+    Dropout,
+
+    /// Reproduce a quality issue scenario and flag it on the reading
+    Quality {
+        issue: QualityIssueType,
+        #[serde(default = "default_severity")]
+        severity: IssueSeverity,
+        #[serde(default)]
+        magnitude: Option<f64>,
+    },
+}
+
+/// When and for how long a fault is active
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultInjection {
+    /// The fault to inject
+    pub fault: FaultKind,
+
+    /// Probability of the fault starting at any sample
+    #[serde(default)]
+    pub probability: f64,
+
+    /// Sample index at which the fault starts deterministically
+    #[serde(default)]
+    pub at_sample: Option<u64>,
+
+    /// Number of samples the fault stays active
+    #[serde(default = "default_duration_samples")]
+    pub duration_samples: u64,
+}
+
+/// Configuration of a single virtual sensor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VirtualSensorConfig {
+    /// Sensor identifier
+    pub sensor_id: String,
+
+    /// Human-readable name
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Engineering unit
+    #[serde(default)]
+    pub unit: Option<String>,
+
+    /// Samples per second
+    #[serde(default = "default_sampling_rate")]
+    pub sampling_rate_hz: f64,
+
+    /// Base signal
+    pub signal: SignalModel,
+
+    /// Standard deviation of gaussian noise added to every sample
+    #[serde(default)]
+    pub noise_std_dev: f64,
+
+    /// Linear drift in units per hour
+    #[serde(default)]
+    pub drift_per_hour: f64,
+
+    /// Expected measurement range, used for range checks and out-of-range faults
+    #[serde(default)]
+    pub range: Option<(f64, f64)>,
+
+    /// Injected faults
+    #[serde(default)]
+    pub faults: Vec<FaultInjection>,
+
+    /// Seed for reproducible streams
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+fn default_severity() -> IssueSeverity {
+    IssueSeverity::Medium
+}
+
+fn default_duration_samples() -> u64 {
+    1
+}
+
+fn default_sampling_rate() -> f64 {
+    1.0
+}
+
+impl VirtualSensorConfig {
+    /// Parse the sensor configurations of a virtual sensor data source
+    pub fn from_data_source(source: &DataSource) -> VirtualSensorResult<Vec<Self>> {
+        match &source.source_type {
+            DataSourceType::Custom { source_id } if source_id == VIRTUAL_SENSOR_SOURCE_ID => {},
+            _ => return Err(VirtualSensorError::UnsupportedSource(source.id)),
+        }
+
+        let value = source.connection_config.custom_params.get(SENSORS_PARAM)
+            .ok_or_else(|| VirtualSensorError::InvalidConfig(
+                format!("missing '{}' parameter", SENSORS_PARAM)
+            ))?;
+
+        let configs: Vec<Self> = serde_json::from_value(value.clone())
+            .map_err(|e| VirtualSensorError::InvalidConfig(e.to_string()))?;
+
+        for config in &configs {
+            config.validate()?;
+        }
+
+        Ok(configs)
+    }
+
+    /// Check the configuration for values the generator cannot use
+    pub fn validate(&self) -> VirtualSensorResult<()> {
+        let invalid = |reason: &str| Err(VirtualSensorError::InvalidConfig(
+            format!("{}: {}", self.sensor_id, reason)
+        ));
+
+        if !(self.sampling_rate_hz > 0.0 && self.sampling_rate_hz <= 1000.0) {
+            return invalid("sampling_rate_hz must be in (0, 1000]");
+        }
+        if self.noise_std_dev < 0.0 {
+            return invalid("noise_std_dev must not be negative");
+        }
+
+        match &self.signal {
+            SignalModel::Sine { period_seconds, .. } if *period_seconds <= 0.0 => {
+                return invalid("sine period_seconds must be positive");
+            },
+            SignalModel::Step { levels, interval_seconds } if levels.is_empty() || *interval_seconds <= 0.0 => {
+                return invalid("step requires levels and a positive interval_seconds");
+            },
+            SignalModel::RandomWalk { step_std_dev, .. } if *step_std_dev < 0.0 => {
+                return invalid("random walk step_std_dev must not be negative");
+            },
+            _ => {},
+        }
+
+        for fault in &self.faults {
+            if !(0.0..=1.0).contains(&fault.probability) {
+                return invalid("fault probability must be between 0 and 1");
+            }
+            if fault.duration_samples == 0 {
+                return invalid("fault duration_samples must be at least 1");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sampling interval
+    pub fn sample_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.sampling_rate_hz)
+    }
+
+    /// Sensor description registered for the stream
+    pub fn sensor_info(&self) -> SensorInfo {
+        SensorInfo {
+            sensor_id: self.sensor_id.clone(),
+            name: self.name.clone().unwrap_or_else(|| self.sensor_id.clone()),
+            sensor_type: SensorType::Custom {
+                category: "virtual".to_string(),
+                measurement_unit: self.unit.clone().unwrap_or_default(),
+            },
+            location: None,
+            specifications: SensorSpecifications {
+                range: self.range,
+                accuracy: None,
+                resolution: None,
+                sampling_rate: Some(self.sampling_rate_hz),
+                response_time_ms: None,
+                operating_temp_range: None,
+                power_consumption: None,
+                protocol: Some("virtual".to_string()),
+                manufacturer: None,
+            },
+            status: SensorStatus::Online,
+            calibration: None,
+        }
+    }
+}
+
+/// Generator state for one virtual sensor
+pub struct VirtualSensor {
+    config: VirtualSensorConfig,
+    rng: StdRng,
+    sample: u64,
+    /// Timestamp of the first sample; signal time is measured from here
+    origin: Option<DateTime<Utc>>,
+    walk: Option<f64>,
+    last_value: Option<f64>,
+    /// Remaining samples per active fault, indexed like `config.faults`
+    active: Vec<u64>,
+}
+
+impl VirtualSensor {
+    /// Create a generator from a validated configuration
+    pub fn new(config: VirtualSensorConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let active = vec![0; config.faults.len()];
+
+        Self {
+            config,
+            rng,
+            sample: 0,
+            origin: None,
+            walk: None,
+            last_value: None,
+            active,
+        }
+    }
+
+    /// Sensor configuration
+    pub fn config(&self) -> &VirtualSensorConfig {
+        &self.config
+    }
+
+    /// Produce the next sample; `None` means the sample was dropped.
+    ///
+    /// Signal shape and drift follow the sample timestamps, so skipped ticks
+    /// or a simulated clock advance the stream by the time that passed.
+    pub fn next_reading(&mut self, timestamp: DateTime<Utc>) -> Option<SensorReading> {
+        let index = self.sample;
+        self.sample += 1;
+        let origin = *self.origin.get_or_insert(timestamp);
+        let elapsed = (timestamp - origin).num_milliseconds().max(0) as f64 / 1000.0;
+
+        let mut value = self.base_value(elapsed)
+            + self.gaussian(self.config.noise_std_dev)
+            + self.config.drift_per_hour * elapsed / 3600.0;
+
+        self.schedule_faults(index);
+
+        let mut quality = ReadingQuality::default();
+        let mut events = Vec::new();
+
+        for (i, fault) in self.config.faults.clone().iter().enumerate() {
+            if self.active[i] == 0 {
+                continue;
+            }
+            self.active[i] -= 1;
+
+            match &fault.fault {
+                FaultKind::Spike { magnitude } => {
+                    value += magnitude;
+                    events.push("spike".to_string());
+                },
+                FaultKind::StuckAt { value: stuck } => {
+                    value = stuck.or(self.last_value).unwrap_or(value);
+                    events.push("stuck_at".to_string());
+                },
+                FaultKind::Dropout => return None,
+                FaultKind::Quality { issue, severity, magnitude } => {
+                    match self.apply_quality_issue(*issue, *magnitude, elapsed, value, &mut quality) {
+                        Some(v) => value = v,
+                        None => return None,
+                    }
+                    quality.issues.push(QualityIssue {
+                        issue_type: *issue,
+                        severity: *severity,
+                        description: format!("Injected {:?} scenario", issue),
+                        remediation: None,
+                    });
+                    quality.score = quality.score.min(match severity {
+                        IssueSeverity::Low => 0.8,
+                        IssueSeverity::Medium => 0.6,
+                        IssueSeverity::High => 0.3,
+                        IssueSeverity::Critical => 0.1,
+                    });
+                    events.push(format!("{:?}", issue));
+                },
+            }
+        }
+
+        if let Some((min, max)) = self.config.range {
+            quality.indicators.within_range &= value >= min && value <= max;
+        }
+
+        // Stuck-at without an explicit value repeats the last fault-free value
+        if events.is_empty() {
+            self.last_value = Some(value);
+        }
+
+        let context = (!events.is_empty()).then(|| crate::core::domain::models::ReadingContext {
+            environment: None,
+            correlated_sensors: Vec::new(),
+            events: events.into_iter().map(|e| format!("virtual_fault:{}", e)).collect(),
+            custom_data: HashMap::new(),
+        });
+
+        Some(SensorReading {
+            id: Uuid::new_v4(),
+            value: SensorValue::Numeric(value),
+            timestamp,
+            quality,
+            context,
+            alerts: Vec::new(),
+        })
+    }
+
+    /// Generate `count` consecutive samples starting at `start`
+    pub fn generate(&mut self, start: DateTime<Utc>, count: usize) -> Vec<SensorReading> {
+        let step = ChronoDuration::from_std(self.config.sample_interval())
+            .unwrap_or_else(|_| ChronoDuration::seconds(1));
+
+        (0..count)
+            .filter_map(|i| self.next_reading(start + step * i as i32))
+            .collect()
+    }
+
+    fn base_value(&mut self, elapsed: f64) -> f64 {
+        match self.config.signal.clone() {
+            SignalModel::Constant { value } => value,
+            SignalModel::Sine { offset, amplitude, period_seconds, phase } => {
+                offset + amplitude * (2.0 * PI * elapsed / period_seconds + phase).sin()
+            },
+            SignalModel::RandomWalk { initial, step_std_dev, min, max } => {
+                let next = match self.walk {
+                    Some(current) => current + self.gaussian(step_std_dev),
+                    None => initial,
+                };
+                let next = min.map_or(next, |m| next.max(m));
+                let next = max.map_or(next, |m| next.min(m));
+                self.walk = Some(next);
+                next
+            },
+            SignalModel::Step { levels, interval_seconds } => {
+                let index = (elapsed / interval_seconds) as usize % levels.len();
+                levels[index]
+            },
+        }
+    }
+
+    fn schedule_faults(&mut self, index: u64) {
+        for (i, fault) in self.config.faults.iter().enumerate() {
+            if self.active[i] > 0 {
+                continue;
+            }
+            let scheduled = fault.at_sample == Some(index);
+            let random = fault.probability > 0.0 && self.rng.gen_bool(fault.probability);
+            if scheduled || random {
+                debug!("Injecting {:?} into virtual sensor {}", fault.fault, self.config.sensor_id);
+                self.active[i] = fault.duration_samples;
+            }
+        }
+    }
+
+    /// Apply a quality issue scenario; `None` means no value is produced
+    fn apply_quality_issue(
+        &mut self,
+        issue: QualityIssueType,
+        magnitude: Option<f64>,
+        elapsed: f64,
+        value: f64,
+        quality: &mut ReadingQuality,
+    ) -> Option<f64> {
+        let span = self.config.range.map_or(1.0, |(min, max)| (max - min).abs().max(f64::EPSILON));
+        let magnitude = magnitude.unwrap_or(span * 0.1);
+
+        match issue {
+            QualityIssueType::OutOfRange => {
+                quality.indicators.within_range = false;
+                Some(self.config.range.map_or(value + magnitude, |(_, max)| max + magnitude))
+            },
+            QualityIssueType::SignalLoss => {
+                quality.indicators.signal_strength = Some(0.05);
+                quality.indicators.completeness = 0.0;
+                Some(self.last_value.unwrap_or(value))
+            },
+            QualityIssueType::Interference => {
+                quality.indicators.noise_level = NoiseLevel::Excessive;
+                Some(value + self.gaussian(magnitude))
+            },
+            QualityIssueType::Drift => {
+                Some(value + magnitude * elapsed / 3600.0)
+            },
+            QualityIssueType::Spike => {
+                quality.indicators.anomaly_score = Some(1.0);
+                Some(value + magnitude)
+            },
+            QualityIssueType::MissingData => None,
+            QualityIssueType::CalibrationNeeded => Some(value + magnitude),
+        }
+    }
+
+    /// Zero-mean gaussian sample (Box-Muller)
+    fn gaussian(&mut self, std_dev: f64) -> f64 {
+        if std_dev == 0.0 {
+            return 0.0;
+        }
+        let u1: f64 = self.rng.gen_range(f64::EPSILON..1.0);
+        let u2: f64 = self.rng.gen();
+        std_dev * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+}
+
+/// Reading published by a running virtual sensor
+#[derive(Debug, Clone)]
+pub struct VirtualReading {
+    pub twin_id: TwinId,
+    pub sensor_id: String,
+    pub reading: SensorReading,
+}
+
+/// Handle to the streams of a virtual sensor data source
+pub struct VirtualSensorHandle {
+    sensors: HashMap<String, SensorDataId>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl VirtualSensorHandle {
+    /// Sensor data collections created for each virtual sensor
+    pub fn sensor_data_ids(&self) -> &HashMap<String, SensorDataId> {
+        &self.sensors
+    }
+
+    /// Stop generating samples
+    pub fn stop(&self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for VirtualSensorHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Driver running virtual sensor data sources
+pub struct VirtualSensorDriver {
+    sensor_repository: Arc<dyn SensorDataRepository>,
+    readings: broadcast::Sender<VirtualReading>,
+    /// Running data sources by data source ID
+    running: Mutex<HashMap<Uuid, VirtualSensorHandle>>,
+}
+
+impl VirtualSensorDriver {
+    /// Create a new virtual sensor driver
+    pub fn new(sensor_repository: Arc<dyn SensorDataRepository>) -> Self {
+        let (readings, _) = broadcast::channel(1024);
+        Self {
+            sensor_repository,
+            readings,
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the driver handles the given data source
+    pub fn supports(source: &DataSource) -> bool {
+        matches!(
+            &source.source_type,
+            DataSourceType::Custom { source_id } if source_id == VIRTUAL_SENSOR_SOURCE_ID
+        )
+    }
+
+    /// Subscribe to generated readings, e.g. for live dashboards
+    pub fn subscribe(&self) -> broadcast::Receiver<VirtualReading> {
+        self.readings.subscribe()
+    }
+
+    /// Start a twin's data source and keep it running until detached.
+    ///
+    /// Restarting an attached source replaces its streams.
+    pub async fn attach(&self, twin_id: TwinId, source: &DataSource) -> VirtualSensorResult<()> {
+        let handle = self.start(twin_id, source).await?;
+        // The replaced handle stops its streams when dropped
+        self.running.lock().await.insert(source.id, handle);
+        Ok(())
+    }
+
+    /// Start every active virtual sensor data source of a twin
+    pub async fn attach_twin(&self, twin: &DigitalTwin) -> usize {
+        let mut started = 0;
+        for source in twin.active_data_sources() {
+            if !Self::supports(source) {
+                continue;
+            }
+            match self.attach(twin.id, source).await {
+                Ok(()) => started += 1,
+                Err(e) => warn!("Failed to start virtual sensors for twin {}: {}", twin.id, e),
+            }
+        }
+        started
+    }
+
+    /// Stop a running data source; returns whether it was running
+    pub async fn detach(&self, source_id: Uuid) -> bool {
+        self.running.lock().await.remove(&source_id).is_some()
+    }
+
+    /// Register the data source's sensors on the twin and start sampling.
+    ///
+    /// Sensor data collections left by an earlier run of the same source are
+    /// reused so history stays in one place across restarts.
+    pub async fn start(
+        &self,
+        twin_id: TwinId,
+        source: &DataSource,
+    ) -> VirtualSensorResult<VirtualSensorHandle> {
+        let configs = VirtualSensorConfig::from_data_source(source)?;
+        let existing = self.existing_collections(twin_id, source).await?;
+        let mut sensors = HashMap::new();
+        let mut tasks = Vec::new();
+
+        for config in configs {
+            let sensor_data_id = match existing.get(&config.sensor_id) {
+                Some(id) => *id,
+                None => {
+                    let mut data = SensorData::new(twin_id, config.sensor_info());
+                    data.metadata.source_id = source.id.to_string();
+                    data.metadata.collection_method = VIRTUAL_SENSOR_SOURCE_ID.to_string();
+                    self.sensor_repository.create(data).await?.id
+                },
+            };
+            sensors.insert(config.sensor_id.clone(), sensor_data_id);
+
+            tasks.push(tokio::spawn(run_sensor(
+                VirtualSensor::new(config),
+                twin_id,
+                sensor_data_id,
+                self.sensor_repository.clone(),
+                self.readings.clone(),
+            )));
+        }
+
+        info!("Started {} virtual sensors for twin {}", sensors.len(), twin_id);
+        Ok(VirtualSensorHandle { sensors, tasks })
+    }
+
+    /// Sensor data collections previously created for the data source
+    async fn existing_collections(
+        &self,
+        twin_id: TwinId,
+        source: &DataSource,
+    ) -> VirtualSensorResult<HashMap<String, SensorDataId>> {
+        let source_id = source.id.to_string();
+        let mut collections = HashMap::new();
+        let mut offset = 0;
+
+        loop {
+            let page = self.sensor_repository
+                .get_by_twin_id(twin_id, Pagination { offset, limit: 100 })
+                .await?;
+            let fetched = page.items.len();
+
+            collections.extend(
+                page.items.into_iter()
+                    .filter(|data| data.metadata.source_id == source_id)
+                    .map(|data| (data.sensor.sensor_id, data.id)),
+            );
+
+            offset += fetched;
+            if fetched == 0 || offset >= page.total {
+                return Ok(collections);
+            }
+        }
+    }
+}
+
+/// Sample a virtual sensor at its configured rate until aborted
+async fn run_sensor(
+    mut sensor: VirtualSensor,
+    twin_id: TwinId,
+    sensor_data_id: SensorDataId,
+    repository: Arc<dyn SensorDataRepository>,
+    readings: broadcast::Sender<VirtualReading>,
+) {
+    let mut interval = tokio::time::interval(sensor.config().sample_interval());
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+
+        let reading = match sensor.next_reading(Utc::now()) {
+            Some(reading) => reading,
+            None => continue,
+        };
+
+        if let Err(e) = repository.add_reading(sensor_data_id, reading.clone()).await {
+            warn!("Failed to store virtual reading for {}: {}", sensor.config().sensor_id, e);
+        }
+
+        // Nobody listening is not an error
+        let _ = readings.send(VirtualReading {
+            twin_id,
+            sensor_id: sensor.config().sensor_id.clone(),
+            reading,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(signal: SignalModel) -> VirtualSensorConfig {
+        VirtualSensorConfig {
+            sensor_id: "temp".to_string(),
+            name: None,
+            unit: Some("°C".to_string()),
+            sampling_rate_hz: 1.0,
+            signal,
+            noise_std_dev: 0.0,
+            drift_per_hour: 0.0,
+            range: Some((0.0, 100.0)),
+            faults: Vec::new(),
+            seed: Some(42),
+        }
+    }
+
+    fn values(readings: &[SensorReading]) -> Vec<f64> {
+        readings.iter().filter_map(SensorReading::as_numeric).collect()
+    }
+
+    #[test]
+    fn test_signal_models() {
+        let start = Utc::now();
+
+        let mut sine = VirtualSensor::new(config(SignalModel::Sine {
+            offset: 50.0, amplitude: 10.0, period_seconds: 4.0, phase: 0.0,
+        }));
+        let v = values(&sine.generate(start, 4));
+        assert!((v[0] - 50.0).abs() < 1e-9);
+        assert!((v[1] - 60.0).abs() < 1e-9);
+        assert!((v[3] - 40.0).abs() < 1e-9);
+
+        let mut step = VirtualSensor::new(config(SignalModel::Step {
+            levels: vec![1.0, 2.0], interval_seconds: 2.0,
+        }));
+        assert_eq!(values(&step.generate(start, 5)), vec![1.0, 1.0, 2.0, 2.0, 1.0]);
+
+        let mut walk = VirtualSensor::new(config(SignalModel::RandomWalk {
+            initial: 5.0, step_std_dev: 10.0, min: Some(0.0), max: Some(10.0),
+        }));
+        let v = values(&walk.generate(start, 100));
+        assert_eq!(v[0], 5.0);
+        assert!(v.iter().all(|x| (0.0..=10.0).contains(x)));
+    }
+
+    #[test]
+    fn test_drift_follows_sample_clock() {
+        let mut cfg = config(SignalModel::Constant { value: 20.0 });
+        cfg.drift_per_hour = 2.0;
+        let mut sensor = VirtualSensor::new(cfg);
+
+        // Two consecutive samples an hour apart, e.g. after a suspended tick
+        let start = Utc::now();
+        let first = sensor.next_reading(start).and_then(|r| r.as_numeric()).unwrap();
+        let second = sensor.next_reading(start + ChronoDuration::hours(1))
+            .and_then(|r| r.as_numeric())
+            .unwrap();
+
+        assert!((first - 20.0).abs() < 1e-9);
+        assert!((second - 22.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_seeded_streams_are_reproducible() {
+        let mut cfg = config(SignalModel::Constant { value: 20.0 });
+        cfg.noise_std_dev = 0.5;
+        cfg.faults.push(FaultInjection {
+            fault: FaultKind::Spike { magnitude: 30.0 },
+            probability: 0.1,
+            at_sample: None,
+            duration_samples: 1,
+        });
+
+        let start = Utc::now();
+        let a = values(&VirtualSensor::new(cfg.clone()).generate(start, 50));
+        let b = values(&VirtualSensor::new(cfg).generate(start, 50));
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_fault_injection() {
+        let mut cfg = config(SignalModel::Sine {
+            offset: 50.0, amplitude: 10.0, period_seconds: 8.0, phase: 0.0,
+        });
+        cfg.faults = vec![
+            FaultInjection {
+                fault: FaultKind::StuckAt { value: None },
+                probability: 0.0,
+                at_sample: Some(2),
+                duration_samples: 3,
+            },
+            FaultInjection {
+                fault: FaultKind::Dropout,
+                probability: 0.0,
+                at_sample: Some(6),
+                duration_samples: 1,
+            },
+            FaultInjection {
+                fault: FaultKind::Quality {
+                    issue: QualityIssueType::OutOfRange,
+                    severity: IssueSeverity::High,
+                    magnitude: Some(5.0),
+                },
+                probability: 0.0,
+                at_sample: Some(8),
+                duration_samples: 1,
+            },
+        ];
+
+        let readings = VirtualSensor::new(cfg).generate(Utc::now(), 10);
+        assert_eq!(readings.len(), 9);
+
+        let v = values(&readings);
+        // Stuck at the value of sample 1 for samples 2..=4
+        assert_eq!(v[2], v[1]);
+        assert_eq!(v[4], v[1]);
+
+        let out_of_range = &readings[7];
+        assert_eq!(out_of_range.as_numeric(), Some(105.0));
+        assert!(!out_of_range.quality.indicators.within_range);
+        assert_eq!(out_of_range.quality.issues[0].issue_type, QualityIssueType::OutOfRange);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let mut cfg = config(SignalModel::Step { levels: Vec::new(), interval_seconds: 1.0 });
+        assert!(cfg.validate().is_err());
+
+        cfg.signal = SignalModel::Constant { value: 1.0 };
+        cfg.sampling_rate_hz = 0.0;
+        assert!(cfg.validate().is_err());
+
+        let parsed: Vec<VirtualSensorConfig> = serde_json::from_value(serde_json::json!([{
+            "sensor_id": "flow",
+            "sampling_rate_hz": 10.0,
+            "signal": { "type": "sine", "amplitude": 2.0, "period_seconds": 60.0 },
+            "faults": [{ "fault": { "type": "quality", "issue": "SignalLoss" }, "probability": 0.01 }]
+        }])).unwrap();
+        assert!(parsed[0].validate().is_ok());
+        assert_eq!(parsed[0].sample_interval(), Duration::from_millis(100));
+    }
+}
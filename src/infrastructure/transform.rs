@@ -0,0 +1,844 @@
+//! Data mapping transforms applied during ingestion
+//!
+//! [`MappingTransformer`] applies the [`TransformRule`] of each
+//! [`DataMapping`] of a data source to incoming records. Script rules run
+//! in an embedded Rhai engine with a deliberately small API: a script sees
+//! the mapped input `value`, the property's `previous` value (or `()`), the
+//! record `timestamp` in Unix milliseconds and the twin's `attributes`,
+//! and returns the new value. Scripts have no I/O, run under an
+//! operation budget and size limits on strings, arrays and maps, and are
+//! compiled once per mapping.
+//!
+//! [`MappingIngestor`] runs ingested records (MQTT messages, Sparkplug
+//! metrics, Modbus register reads, imported rows) through the mappings of
+//! the twin's matching data sources and stores the results as twin
+//! measurements. Failed mappings are published to its subscribers.
+
+use chrono::{DateTime, Utc};
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::core::domain::models::{
+    DataMapping, DataSource, DataSourceType, DataType, DigitalTwin, Measurement, SensorValue, TransformRule,
+    TwinId,
+};
+use crate::core::domain::traits::repository::{RepositoryResult, TwinRepository};
+
+/// Script language accepted by [`TransformRule::Script`]
+pub const SCRIPT_LANGUAGE: &str = "rhai";
+
+/// Transform error types
+#[derive(Debug, Error)]
+pub enum TransformError {
+    /// The script language is not supported
+    #[error("Unsupported script language '{language}' for property '{property}'")]
+    UnsupportedLanguage { property: String, language: String },
+
+    /// The transform rule is not supported
+    #[error("Unsupported transform for property '{property}': {rule}")]
+    UnsupportedRule { property: String, rule: String },
+
+    /// The script failed to compile
+    #[error("Script for property '{property}' failed to compile{}: {message}", location(.line))]
+    Compile { property: String, message: String, line: Option<usize> },
+
+    /// The script failed while running
+    #[error("Script for property '{property}' failed{}: {message}", location(.line))]
+    Runtime { property: String, message: String, line: Option<usize> },
+
+    /// The script exceeded an operation or size limit
+    #[error("Script for property '{property}' exceeded its {limit} limit")]
+    LimitExceeded { property: String, limit: String },
+
+    /// The script returned a value of the wrong type
+    #[error("Script for property '{property}' returned {actual}, expected {expected:?}")]
+    InvalidResult { property: String, expected: DataType, actual: String },
+
+    /// The source field is missing from the record
+    #[error("Field '{field}' is missing for property '{property}'")]
+    MissingField { property: String, field: String },
+}
+
+impl TransformError {
+    /// Target property of the failed mapping
+    pub fn property(&self) -> &str {
+        match self {
+            TransformError::UnsupportedLanguage { property, .. }
+            | TransformError::UnsupportedRule { property, .. }
+            | TransformError::Compile { property, .. }
+            | TransformError::Runtime { property, .. }
+            | TransformError::LimitExceeded { property, .. }
+            | TransformError::InvalidResult { property, .. }
+            | TransformError::MissingField { property, .. } => property,
+        }
+    }
+}
+
+fn location(line: &Option<usize>) -> String {
+    line.map(|line| format!(" at line {}", line)).unwrap_or_default()
+}
+
+/// Result type for transform operations
+pub type TransformResult<T> = Result<T, TransformError>;
+
+/// Limits applied to every script run
+#[derive(Debug, Clone)]
+pub struct ScriptLimits {
+    /// Maximum operations per run
+    pub max_operations: u64,
+
+    /// Maximum length of a string in bytes
+    pub max_string_size: usize,
+
+    /// Maximum number of array elements
+    pub max_array_size: usize,
+
+    /// Maximum number of map entries
+    pub max_map_size: usize,
+
+    /// Maximum function call depth
+    pub max_call_levels: usize,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            max_operations: 100_000,
+            max_string_size: 64 * 1024,
+            max_array_size: 10_000,
+            max_map_size: 10_000,
+            max_call_levels: 32,
+        }
+    }
+}
+
+/// Inputs visible to a transform
+#[derive(Debug, Clone)]
+pub struct TransformContext<'a> {
+    /// Value read from the source field
+    pub value: Value,
+
+    /// Current value of the target property, if any
+    pub previous: Option<Value>,
+
+    /// Time of the record
+    pub timestamp: DateTime<Utc>,
+
+    /// Twin attributes
+    pub attributes: &'a HashMap<String, Value>,
+}
+
+/// Values produced from one record
+#[derive(Debug, Default)]
+pub struct MappingOutcome {
+    /// New values by target property
+    pub values: HashMap<String, Value>,
+
+    /// Mappings that failed; other mappings are still applied
+    pub errors: Vec<TransformError>,
+}
+
+/// Cache key: one compiled script per mapping
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct MappingKey {
+    source_id: Uuid,
+    target_property: String,
+}
+
+struct CachedScript {
+    code_hash: u64,
+    ast: Arc<AST>,
+}
+
+/// Applies data mapping transforms
+pub struct MappingTransformer {
+    engine: Engine,
+    scripts: RwLock<HashMap<MappingKey, CachedScript>>,
+}
+
+impl Default for MappingTransformer {
+    fn default() -> Self {
+        Self::new(ScriptLimits::default())
+    }
+}
+
+impl MappingTransformer {
+    /// Create a transformer whose scripts run under `limits`
+    pub fn new(limits: ScriptLimits) -> Self {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(limits.max_operations)
+            .set_max_string_size(limits.max_string_size)
+            .set_max_array_size(limits.max_array_size)
+            .set_max_map_size(limits.max_map_size)
+            .set_max_call_levels(limits.max_call_levels)
+            .set_strict_variables(true)
+            .on_print(|_| {})
+            .on_debug(|_, _, _| {});
+        engine.disable_symbol("eval");
+
+        Self {
+            engine,
+            scripts: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Apply every mapping of `source` to an incoming record
+    ///
+    /// Fields are looked up in `record` by dot-separated path. Failures are
+    /// logged and returned alongside the values of the mappings that
+    /// succeeded.
+    pub fn apply_mappings(
+        &self,
+        twin: &DigitalTwin,
+        source: &DataSource,
+        record: &Value,
+        timestamp: DateTime<Utc>,
+    ) -> MappingOutcome {
+        let mut outcome = MappingOutcome::default();
+
+        for mapping in &source.mappings {
+            let result = lookup(record, &mapping.source_field)
+                .cloned()
+                .ok_or_else(|| TransformError::MissingField {
+                    property: mapping.target_property.clone(),
+                    field: mapping.source_field.clone(),
+                })
+                .and_then(|value| {
+                    let context = TransformContext {
+                        value,
+                        previous: twin.properties.measurements
+                            .get(&mapping.target_property)
+                            .map(|m| m.value.clone()),
+                        timestamp,
+                        attributes: &twin.properties.attributes,
+                    };
+                    self.apply(source.id, mapping, context)
+                });
+
+            match result {
+                Ok(value) => {
+                    outcome.values.insert(mapping.target_property.clone(), value);
+                }
+                Err(e) => {
+                    tracing::warn!("Twin {} source '{}': {}", twin.id, source.name, e);
+                    outcome.errors.push(e);
+                }
+            }
+        }
+
+        outcome
+    }
+
+    /// Apply one mapping's transform
+    pub fn apply(&self, source_id: Uuid, mapping: &DataMapping, context: TransformContext<'_>) -> TransformResult<Value> {
+        let property = &mapping.target_property;
+
+        let value = match &mapping.transform {
+            None => context.value,
+            Some(TransformRule::Lookup { map }) => {
+                let key = match &context.value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                map.get(&key).cloned().unwrap_or(Value::Null)
+            }
+            Some(TransformRule::Script { language, code }) => {
+                if !language.eq_ignore_ascii_case(SCRIPT_LANGUAGE) {
+                    return Err(TransformError::UnsupportedLanguage {
+                        property: property.clone(),
+                        language: language.clone(),
+                    });
+                }
+                let key = MappingKey { source_id, target_property: property.clone() };
+                let ast = self.compiled(key, code)?;
+                self.run(&ast, property, context)?
+            }
+            Some(rule) => {
+                return Err(TransformError::UnsupportedRule {
+                    property: property.clone(),
+                    rule: format!("{:?}", rule),
+                });
+            }
+        };
+
+        coerce(property, mapping.data_type, value)
+    }
+
+    /// Drop cached scripts of a data source, e.g. after its mappings change
+    pub fn invalidate_source(&self, source_id: Uuid) {
+        self.scripts.write().unwrap().retain(|key, _| key.source_id != source_id);
+    }
+
+    /// The compiled script for a mapping, compiling it if the code changed
+    fn compiled(&self, key: MappingKey, code: &str) -> TransformResult<Arc<AST>> {
+        let mut hasher = DefaultHasher::new();
+        code.hash(&mut hasher);
+        let code_hash = hasher.finish();
+
+        if let Some(cached) = self.scripts.read().unwrap().get(&key) {
+            if cached.code_hash == code_hash {
+                return Ok(cached.ast.clone());
+            }
+        }
+
+        // Strict variables are resolved against a scope holding the same names
+        let names = script_scope(&key.target_property, &TransformContext {
+            value: Value::Null,
+            previous: None,
+            timestamp: Utc::now(),
+            attributes: &HashMap::new(),
+        })?;
+        let ast = self.engine
+            .compile_with_scope(&names, code)
+            .map(Arc::new)
+            .map_err(|e| TransformError::Compile {
+                property: key.target_property.clone(),
+                message: e.0.to_string(),
+                line: e.1.line(),
+            })?;
+
+        self.scripts.write().unwrap().insert(key, CachedScript { code_hash, ast: ast.clone() });
+        Ok(ast)
+    }
+
+    fn run(&self, ast: &AST, property: &str, context: TransformContext<'_>) -> TransformResult<Value> {
+        let mut scope = script_scope(property, &context)?;
+
+        let result = self.engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, ast)
+            .map_err(|e| runtime_error(property, *e))?;
+
+        rhai::serde::from_dynamic::<Value>(&result).map_err(|e| TransformError::Runtime {
+            property: property.to_string(),
+            message: format!("unsupported return value: {}", e),
+            line: None,
+        })
+    }
+}
+
+/// Data sources of a twin an ingested record belongs to
+#[derive(Debug, Clone, Copy)]
+pub enum SourceSelector<'a> {
+    /// One specific data source
+    Id(Uuid),
+
+    /// Message queue sources whose topic filter matches the topic
+    Topic(&'a str),
+
+    /// Sensor sources speaking the protocol
+    Protocol(&'a str),
+}
+
+impl SourceSelector<'_> {
+    fn matches(&self, source: &DataSource) -> bool {
+        match (self, &source.source_type) {
+            (SourceSelector::Id(id), _) => source.id == *id,
+            (SourceSelector::Topic(topic), DataSourceType::MessageQueue { topic: filter, .. }) => {
+                topic_matches(filter, topic)
+            }
+            (SourceSelector::Protocol(protocol), DataSourceType::Sensor { protocol: source_protocol, .. }) => {
+                source_protocol.eq_ignore_ascii_case(protocol)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A mapping that failed while ingesting a record
+#[derive(Debug, Clone, Serialize)]
+pub struct TransformFailure {
+    pub twin_id: TwinId,
+    pub source_id: Uuid,
+    pub source_name: String,
+    pub target_property: String,
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Result of ingesting records
+#[derive(Debug, Default)]
+pub struct IngestOutcome {
+    /// Properties that received a new value
+    pub updated: usize,
+
+    /// Failed mappings with the index of their record
+    pub failures: Vec<(usize, TransformFailure)>,
+}
+
+/// Applies data source mappings to ingested records and stores the
+/// results as twin measurements
+pub struct MappingIngestor {
+    twins: Arc<dyn TwinRepository>,
+    transformer: MappingTransformer,
+    failures: broadcast::Sender<TransformFailure>,
+    /// Topic filter and twin of each message queue source
+    routes: RwLock<HashMap<Uuid, (TwinId, String)>>,
+}
+
+impl MappingIngestor {
+    /// Create an ingestor storing measurements through `twins`
+    pub fn new(twins: Arc<dyn TwinRepository>) -> Self {
+        let (failures, _) = broadcast::channel(256);
+        Self {
+            twins,
+            transformer: MappingTransformer::default(),
+            failures,
+            routes: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Use a transformer with custom script limits
+    pub fn with_transformer(mut self, transformer: MappingTransformer) -> Self {
+        self.transformer = transformer;
+        self
+    }
+
+    /// Subscribe to mapping failures
+    pub fn subscribe(&self) -> broadcast::Receiver<TransformFailure> {
+        self.failures.subscribe()
+    }
+
+    /// Route messages on a source's topic to its twin
+    ///
+    /// Returns the topic filter to subscribe to, if the source is an active
+    /// message queue source.
+    pub fn bind_source(&self, twin_id: TwinId, source: &DataSource) -> Option<String> {
+        self.transformer.invalidate_source(source.id);
+        let mut routes = self.routes.write().unwrap();
+        routes.remove(&source.id);
+        match &source.source_type {
+            DataSourceType::MessageQueue { topic, .. } if source.active => {
+                routes.insert(source.id, (twin_id, topic.clone()));
+                Some(topic.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Route the topics of every active message queue source of a twin
+    pub fn bind_twin(&self, twin: &DigitalTwin) -> Vec<String> {
+        twin.active_data_sources()
+            .into_iter()
+            .filter_map(|source| self.bind_source(twin.id, source))
+            .collect()
+    }
+
+    /// Stop routing a source's topic
+    ///
+    /// Returns the topic filter when no other source uses it any more.
+    pub fn unbind_source(&self, source_id: Uuid) -> Option<String> {
+        self.transformer.invalidate_source(source_id);
+        let mut routes = self.routes.write().unwrap();
+        let (_, filter) = routes.remove(&source_id)?;
+        (!routes.values().any(|(_, other)| *other == filter)).then_some(filter)
+    }
+
+    /// Ingest an MQTT message for every twin with a source on its topic
+    ///
+    /// JSON payloads are used as the record; any other payload is
+    /// available as the `value` field.
+    pub async fn handle_publish(&self, topic: &str, payload: &[u8]) {
+        let twins: HashSet<TwinId> = self.routes.read().unwrap()
+            .values()
+            .filter(|(_, filter)| topic_matches(filter, topic))
+            .map(|(twin_id, _)| *twin_id)
+            .collect();
+        if twins.is_empty() {
+            return;
+        }
+
+        let record = serde_json::from_slice::<Value>(payload).unwrap_or_else(|_| {
+            serde_json::json!({ "value": String::from_utf8_lossy(payload) })
+        });
+
+        for twin_id in twins {
+            if let Err(e) = self.ingest(twin_id, SourceSelector::Topic(topic), &record, Utc::now()).await {
+                tracing::warn!("Failed to store mapped values of {} on twin {}: {}", topic, twin_id, e);
+            }
+        }
+    }
+
+    /// Apply the mappings of the selected sources of a twin to one record
+    pub async fn ingest(
+        &self,
+        twin_id: TwinId,
+        selector: SourceSelector<'_>,
+        record: &Value,
+        timestamp: DateTime<Utc>,
+    ) -> RepositoryResult<IngestOutcome> {
+        self.ingest_batch(twin_id, selector, &[(timestamp, record.clone())]).await
+    }
+
+    /// Apply the mappings of the selected sources of a twin to records in
+    /// time order, storing the updated measurements once
+    ///
+    /// A measurement is only replaced by a value at least as recent, so
+    /// historical records do not overwrite live values.
+    pub async fn ingest_batch(
+        &self,
+        twin_id: TwinId,
+        selector: SourceSelector<'_>,
+        records: &[(DateTime<Utc>, Value)],
+    ) -> RepositoryResult<IngestOutcome> {
+        let mut twin = self.twins.get_by_id(twin_id).await?;
+        let sources: Vec<DataSource> = twin.active_data_sources()
+            .into_iter()
+            .filter(|source| !source.mappings.is_empty() && selector.matches(source))
+            .cloned()
+            .collect();
+
+        let mut outcome = IngestOutcome::default();
+        let mut updated = HashSet::new();
+
+        for (index, (timestamp, record)) in records.iter().enumerate() {
+            for source in &sources {
+                let mapped = self.transformer.apply_mappings(&twin, source, record, *timestamp);
+
+                for error in mapped.errors {
+                    let failure = TransformFailure {
+                        twin_id,
+                        source_id: source.id,
+                        source_name: source.name.clone(),
+                        target_property: error.property().to_string(),
+                        message: error.to_string(),
+                        timestamp: *timestamp,
+                    };
+                    // Nobody listening is not an error
+                    let _ = self.failures.send(failure.clone());
+                    outcome.failures.push((index, failure));
+                }
+
+                for (property, value) in mapped.values {
+                    let measurement = twin.properties.measurements
+                        .entry(property.clone())
+                        .or_insert_with(|| Measurement {
+                            value: Value::Null,
+                            unit: None,
+                            quality: 1.0,
+                            timestamp: *timestamp,
+                            source_id: None,
+                        });
+                    if measurement.timestamp > *timestamp {
+                        continue;
+                    }
+                    measurement.value = value;
+                    measurement.quality = 1.0;
+                    measurement.timestamp = *timestamp;
+                    measurement.source_id = Some(source.id);
+                    updated.insert(property);
+                }
+            }
+        }
+
+        outcome.updated = updated.len();
+        if !updated.is_empty() {
+            let measurements = updated.into_iter()
+                .filter_map(|property| {
+                    let measurement = twin.properties.measurements.remove(&property)?;
+                    Some((property, measurement))
+                })
+                .collect();
+            self.twins.update_measurements(twin_id, measurements).await?;
+        }
+        Ok(outcome)
+    }
+}
+
+/// JSON form of a sensor value for use in a mapping record
+pub fn record_value(value: &SensorValue) -> Value {
+    match value {
+        SensorValue::Numeric(n) => Value::from(*n),
+        SensorValue::Boolean(b) => Value::from(*b),
+        SensorValue::String(s) | SensorValue::Binary(s) => Value::from(s.clone()),
+        SensorValue::Vector(values) => Value::from(values.clone()),
+        SensorValue::Json(json) => json.clone(),
+    }
+}
+
+/// Whether an MQTT topic filter (with `+` and `#` wildcards) matches a topic
+fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut topic_levels = topic.split('/');
+    for level in filter.split('/') {
+        match (level, topic_levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (level, Some(topic_level)) if level == topic_level => {}
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+/// Variables available to scripts (all constant)
+fn script_scope(property: &str, context: &TransformContext<'_>) -> TransformResult<Scope<'static>> {
+    let dynamic = |value: &Value| {
+        rhai::serde::to_dynamic(value).map_err(|e| TransformError::Runtime {
+            property: property.to_string(),
+            message: format!("cannot convert input: {}", e),
+            line: None,
+        })
+    };
+
+    let attributes: rhai::Map = context.attributes
+        .iter()
+        .map(|(key, value)| Ok((key.as_str().into(), dynamic(value)?)))
+        .collect::<TransformResult<_>>()?;
+
+    let mut scope = Scope::new();
+    scope.push_constant("value", dynamic(&context.value)?);
+    scope.push_constant("previous", context.previous.as_ref().map(dynamic).transpose()?.unwrap_or(Dynamic::UNIT));
+    scope.push_constant("timestamp", context.timestamp.timestamp_millis());
+    scope.push_constant("attributes", attributes);
+    Ok(scope)
+}
+
+fn runtime_error(property: &str, error: EvalAltResult) -> TransformError {
+    let property = property.to_string();
+    match error {
+        EvalAltResult::ErrorTooManyOperations(_) => TransformError::LimitExceeded {
+            property,
+            limit: "operation".to_string(),
+        },
+        EvalAltResult::ErrorDataTooLarge(what, _) => TransformError::LimitExceeded { property, limit: what },
+        EvalAltResult::ErrorStackOverflow(_) => TransformError::LimitExceeded {
+            property,
+            limit: "call depth".to_string(),
+        },
+        other => {
+            TransformError::Runtime {
+                property,
+                line: other.position().line(),
+                message: other.to_string(),
+            }
+        }
+    }
+}
+
+/// Check the value against the mapping's data type
+///
+/// Integers are accepted for float properties and nulls for any type.
+fn coerce(property: &str, data_type: DataType, value: Value) -> TransformResult<Value> {
+    let matches = match (&data_type, &value) {
+        (_, Value::Null) | (DataType::Json, _) => true,
+        (DataType::String, Value::String(_)) | (DataType::Boolean, Value::Bool(_)) => true,
+        (DataType::Integer, Value::Number(n)) => n.is_i64() || n.is_u64(),
+        (DataType::Float, Value::Number(n)) => return Ok(Value::from(n.as_f64().unwrap_or_default())),
+        (DataType::DateTime, Value::String(s)) => DateTime::parse_from_rfc3339(s).is_ok(),
+        _ => false,
+    };
+
+    if matches {
+        Ok(value)
+    } else {
+        Err(TransformError::InvalidResult {
+            property: property.to_string(),
+            expected: data_type,
+            actual: value.to_string(),
+        })
+    }
+}
+
+/// Look up a dot-separated path in a record
+fn lookup<'a>(record: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(record, |value, segment| match value {
+        Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => value.get(segment),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::domain::models::{ConnectionConfig, TwinRetryConfig, TwinType};
+    use crate::core::domain::traits::mock_repositories::InMemoryTwinRepository;
+
+    fn mapping(code: &str, data_type: DataType) -> DataMapping {
+        DataMapping {
+            source_field: "payload.temp_f".to_string(),
+            target_property: "temperature".to_string(),
+            transform: Some(TransformRule::Script {
+                language: "rhai".to_string(),
+                code: code.to_string(),
+            }),
+            data_type,
+        }
+    }
+
+    fn source(source_type: DataSourceType, mappings: Vec<DataMapping>) -> DataSource {
+        DataSource {
+            id: Uuid::new_v4(),
+            name: "plc".to_string(),
+            source_type,
+            connection_config: ConnectionConfig {
+                endpoint: "mqtt://localhost".to_string(),
+                credentials_secret_id: None,
+                timeout_seconds: 5,
+                retry_config: TwinRetryConfig {
+                    max_attempts: 1,
+                    initial_delay_ms: 0,
+                    backoff_multiplier: 1.0,
+                    max_delay_ms: 0,
+                },
+                custom_params: HashMap::new(),
+            },
+            mappings,
+            active: true,
+            last_connected: None,
+        }
+    }
+
+    fn twin() -> DigitalTwin {
+        DigitalTwin::new(
+            "Boiler".to_string(),
+            "Boiler room".to_string(),
+            TwinType::Device {
+                device_type: "boiler".to_string(),
+                manufacturer: None,
+                model: None,
+            },
+        )
+    }
+
+    fn context(value: Value, previous: Option<Value>, attributes: &HashMap<String, Value>) -> TransformContext<'_> {
+        TransformContext {
+            value,
+            previous,
+            timestamp: Utc::now(),
+            attributes,
+        }
+    }
+
+    #[test]
+    fn test_script_reads_inputs() {
+        let transformer = MappingTransformer::default();
+        let attributes = HashMap::from([("offset".to_string(), Value::from(0.5))]);
+        let source = Uuid::new_v4();
+        let script = mapping(
+            "let c = (value - 32.0) * 5.0 / 9.0 + attributes.offset;\n\
+             if previous == () { c } else { (c + previous) / 2.0 }",
+            DataType::Float,
+        );
+
+        let first = transformer.apply(source, &script, context(Value::from(212.0), None, &attributes)).unwrap();
+        assert_eq!(first, Value::from(100.5));
+
+        let smoothed = transformer
+            .apply(source, &script, context(Value::from(32.0), Some(first), &attributes))
+            .unwrap();
+        assert_eq!(smoothed, Value::from(50.5));
+        assert_eq!(transformer.scripts.read().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_errors_identify_mapping() {
+        let transformer = MappingTransformer::default();
+        let attributes = HashMap::new();
+        let source = Uuid::new_v4();
+
+        let error = transformer
+            .apply(source, &mapping("value +", DataType::Float), context(Value::from(1), None, &attributes))
+            .unwrap_err();
+        assert!(matches!(error, TransformError::Compile { ref property, .. } if property == "temperature"));
+
+        let error = transformer
+            .apply(source, &mapping("let x = 1;\nunknown_fn(x)", DataType::Float), context(Value::from(1), None, &attributes))
+            .unwrap_err();
+        assert!(matches!(error, TransformError::Runtime { line: Some(2), .. }), "{}", error);
+
+        let error = transformer
+            .apply(source, &mapping("loop { }", DataType::Float), context(Value::from(1), None, &attributes))
+            .unwrap_err();
+        assert!(matches!(error, TransformError::LimitExceeded { .. }));
+
+        let error = transformer
+            .apply(source, &mapping("\"hot\"", DataType::Float), context(Value::from(1), None, &attributes))
+            .unwrap_err();
+        assert!(matches!(error, TransformError::InvalidResult { .. }));
+    }
+
+    #[test]
+    fn test_apply_mappings_keeps_successful_values() {
+        let transformer = MappingTransformer::default();
+        let mut twin = twin();
+        twin.properties.attributes.insert("offset".to_string(), Value::from(0));
+
+        let source = source(
+            DataSourceType::Sensor {
+                protocol: "mqtt".to_string(),
+                sensor_type: "temperature".to_string(),
+            },
+            vec![
+                mapping("value * 2", DataType::Float),
+                DataMapping {
+                    source_field: "payload.missing".to_string(),
+                    target_property: "pressure".to_string(),
+                    transform: None,
+                    data_type: DataType::Float,
+                },
+            ],
+        );
+
+        let record = serde_json::json!({ "payload": { "temp_f": 21 } });
+        let outcome = transformer.apply_mappings(&twin, &source, &record, Utc::now());
+
+        assert_eq!(outcome.values.get("temperature"), Some(&Value::from(42.0)));
+        assert_eq!(outcome.errors.len(), 1);
+        assert!(matches!(outcome.errors[0], TransformError::MissingField { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_ingestor_routes_topics_and_reports_failures() {
+        let repository = Arc::new(InMemoryTwinRepository::default());
+        let mut twin = twin();
+        let mut broken = mapping("value +", DataType::Float);
+        broken.target_property = "broken".to_string();
+        twin.data_sources.push(source(
+            DataSourceType::MessageQueue {
+                queue_type: "mqtt".to_string(),
+                topic: "plant/+/boiler".to_string(),
+            },
+            vec![mapping("value * 2", DataType::Float), broken],
+        ));
+        let twin_id = twin.id;
+        repository.create(twin.clone()).await.unwrap();
+
+        let ingestor = MappingIngestor::new(repository.clone());
+        assert_eq!(ingestor.bind_twin(&twin), vec!["plant/+/boiler".to_string()]);
+        let mut failures = ingestor.subscribe();
+
+        ingestor.handle_publish("plant/a/boiler", br#"{"payload": {"temp_f": 21}}"#).await;
+        ingestor.handle_publish("plant/a/chiller", br#"{"payload": {"temp_f": 99}}"#).await;
+
+        let stored = repository.get_by_id(twin_id).await.unwrap();
+        assert_eq!(stored.properties.measurements["temperature"].value, Value::from(42.0));
+        let failure = failures.try_recv().unwrap();
+        assert_eq!(failure.target_property, "broken");
+        assert!(failures.try_recv().is_err());
+
+        // Older records do not replace newer values
+        let record = serde_json::json!({ "payload": { "temp_f": 1 } });
+        let earlier = Utc::now() - chrono::Duration::hours(1);
+        let outcome = ingestor.ingest(twin_id, SourceSelector::Topic("plant/a/boiler"), &record, earlier).await.unwrap();
+        assert_eq!(outcome.updated, 0);
+        assert_eq!(outcome.failures.len(), 1);
+
+        assert_eq!(ingestor.unbind_source(twin.data_sources[0].id), Some("plant/+/boiler".to_string()));
+    }
+
+    #[test]
+    fn test_topic_filters() {
+        assert!(topic_matches("plant/+/boiler", "plant/a/boiler"));
+        assert!(topic_matches("plant/#", "plant/a/boiler"));
+        assert!(!topic_matches("plant/+", "plant/a/boiler"));
+        assert!(!topic_matches("plant/+/boiler/x", "plant/a/boiler"));
+    }
+}
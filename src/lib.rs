@@ -5,6 +5,9 @@ mod infrastructure;
 use std::sync::Arc;
 use tauri::Manager;
 
+/// Environment variable holding the passphrase for a passphrase-derived master key
+const MASTER_KEY_PASSPHRASE_VAR: &str = "DIGITAL_TWIN_PASSPHRASE";
+
 pub fn run() {
     tauri::Builder::default()
         .setup(|app| {
@@ -39,16 +42,242 @@ pub fn run() {
             infrastructure::security::init()
                 .expect("Failed to initialize security module");
             
+            // Open the application database
+            let database = tauri::async_runtime::block_on(async {
+                let manager = infrastructure::db::SqliteManager::new(infrastructure::db::SqliteConfig {
+                    database_path: app_dir.join("digital_twin.db").to_string_lossy().to_string(),
+                    ..Default::default()
+                }).await?;
+                manager.run_migrations().await?;
+                anyhow::Ok(manager)
+            }).expect("Failed to initialize database");
+            let sensor_repository: Arc<dyn core::domain::traits::repository::SensorDataRepository> =
+                Arc::new(infrastructure::db::repositories::SqliteSensorDataRepository::new(database.pool().clone()));
+            
+            let security_repository: Arc<dyn infrastructure::security::SecurityRepository> =
+                Arc::new(infrastructure::db::repositories::SqliteSecurityRepository::new(database.pool().clone()));
+            
+            // Tool executions, configuration changes and logins are recorded here
+            let audit_log = Arc::new(infrastructure::security::AuditLog::new(
+                Arc::new(infrastructure::db::repositories::SqliteAuditStore::new(database.pool().clone())),
+            ));
+            
+            // Device writes and destructive tool operations wait for an operator
+            let approval_config = &config.security.approval;
+            let approvals = Arc::new(
+                infrastructure::tools::ApprovalManager::new(std::time::Duration::from_secs(approval_config.timeout_seconds))
+                    .with_threshold(if approval_config.approve_device_writes {
+                        infrastructure::tools::RiskLevel::WriteToDevice
+                    } else {
+                        infrastructure::tools::RiskLevel::Destructive
+                    })
+                    .with_audit_log(audit_log.clone())
+            );
+            
             // Initialize middleware
             let (auth_middleware, rate_limit_middleware, validation_middleware) =
-                api::middleware::init(&config.security);
+                tauri::async_runtime::block_on(api::middleware::init(&config.security, security_repository, audit_log.clone()))
+                    .expect("Failed to initialize middleware");
+            
+            // Unlock the data keys and initialize the credential vault; a
+            // master key rotation overrides the configured key source
+            let key_store: Arc<dyn infrastructure::security::KeyStore> =
+                Arc::new(infrastructure::db::repositories::SqliteKeyStore::new(database.pool().clone()));
+            let master_key_source = tauri::async_runtime::block_on(infrastructure::security::KeyManager::master_key_source(
+                key_store.as_ref(),
+                &config.security.encryption.master_key,
+            )).expect("Failed to read master key source");
+            let master_key = infrastructure::security::MasterKey::load(
+                &master_key_source,
+                &config.security.secret_key,
+                std::env::var(MASTER_KEY_PASSPHRASE_VAR).ok().as_deref(),
+            ).expect("Failed to load master key");
+            let key_manager = Arc::new(tauri::async_runtime::block_on(infrastructure::security::KeyManager::open(
+                master_key,
+                key_store,
+            )).expect("Failed to unlock data keys"));
+            let vault_encryption = key_manager.encryption();
+            // Secrets stored before keys were versioned use the secret key
+            vault_encryption.set_legacy_key(&config.security.secret_key)
+                .expect("Failed to initialize legacy vault key");
+            let credential_vault = Arc::new(infrastructure::security::CredentialVault::new(
+                vault_encryption,
+                Arc::new(infrastructure::db::repositories::SqliteSecretRepository::new(database.pool().clone())),
+            ));
             
             // Initialize services
             let conversation_service = Arc::new(core::application::services::ConversationService::new());
             let agent_service = Arc::new(core::application::services::AgentService::new());
             let twin_service = Arc::new(core::application::services::TwinService::new());
             let simulation_service = Arc::new(core::application::services::SimulationService::new());
-            let tool_service = Arc::new(core::application::services::ToolService::new());
+            let modbus_simulators = Arc::new(infrastructure::tools::ModbusSimulatorRegistry::new());
+            
+            // Data source mappings turn ingested values into twin properties;
+            // failed transforms are reported to subscribed windows
+            let mapping_ingestor = Arc::new(infrastructure::transform::MappingIngestor::new(
+                Arc::new(infrastructure::db::repositories::SqliteTwinRepository::new(database.pool().clone())),
+            ));
+            let imports = Arc::new(
+                infrastructure::import::ImportJobManager::new(sensor_repository.clone())
+                    .with_mapping(mapping_ingestor.clone())
+            );
+            
+            // Sparkplug B metrics published to the broker are stored as
+            // sensor data of the twins bound to their edge nodes
+            let sparkplug_ingestor = Arc::new(
+                infrastructure::tools::SparkplugIngestor::new(sensor_repository.clone())
+                    .with_mapping(mapping_ingestor.clone())
+            );
+            let mut mqtt_executor = infrastructure::tools::MqttToolExecutor::from_config(&config.tools.mqtt)
+                .with_mapping(mapping_ingestor.clone());
+            if config.tools.mqtt.sparkplug_ingestion {
+                mqtt_executor = mqtt_executor.with_sparkplug(sparkplug_ingestor.clone());
+            }
+            let mqtt_executor = Arc::new(mqtt_executor);
+            if config.tools.mqtt.sparkplug_ingestion {
+                let mqtt_executor = mqtt_executor.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = mqtt_executor.start().await {
+                        tracing::error!("Failed to start Sparkplug ingestion: {}", e);
+                    }
+                });
+            }
+            
+            // Agent scripts run in the process sandbox of the signed-in
+            // user's permissions
+            let code_executor = Arc::new(infrastructure::tools::CodeExecutionToolExecutor::new(
+                Arc::new(infrastructure::security::Sandbox::new(
+                    (&config.security.sandbox).into(),
+                    auth_middleware.permission_manager(),
+                )),
+                Arc::new(infrastructure::db::repositories::SqliteToolRepository::new(database.pool().clone())),
+            ));
+            
+            // Device writes, destructive file operations and scripts of the
+            // built-in tools are held for operator approval
+            let tool_service = Arc::new(core::application::services::ToolService::new(
+                Arc::new(infrastructure::db::repositories::SqliteToolRepository::new(database.pool().clone())),
+                infrastructure::tools::builtin_executors(
+                    &config.tools,
+                    mqtt_executor.clone(),
+                    code_executor,
+                    Some(mapping_ingestor.clone()),
+                    approvals.clone(),
+                )
+                    .expect("Failed to initialize tool executors"),
+            ));
+            
+            // Virtual sensor data sources stream, and message queue sources
+            // and Sparkplug bindings are followed, for as long as they are
+            // attached to a twin, including across restarts
+            let virtual_sensors = Arc::new(infrastructure::tools::VirtualSensorDriver::new(sensor_repository.clone()));
+            {
+                use core::domain::traits::repository::{Pagination, TwinRepository};
+                let virtual_sensors = virtual_sensors.clone();
+                let mqtt_executor = mqtt_executor.clone();
+                let sparkplug_ingestor = sparkplug_ingestor.clone();
+                let twins = infrastructure::db::repositories::SqliteTwinRepository::new(database.pool().clone());
+                tauri::async_runtime::spawn(async move {
+                    let mut offset = 0;
+                    loop {
+                        let page = match twins.find(Vec::new(), Vec::new(), Pagination { offset, limit: 100 }).await {
+                            Ok(page) => page,
+                            Err(e) => {
+                                tracing::error!("Failed to restore virtual sensors: {}", e);
+                                break;
+                            },
+                        };
+                        for twin in &page.items {
+                            virtual_sensors.attach_twin(twin).await;
+                            sparkplug_ingestor.attach_twin(twin).await;
+                            if let Err(e) = mqtt_executor.follow_twin(twin).await {
+                                tracing::warn!("Failed to follow data sources of twin {}: {}", twin.id, e);
+                            }
+                        }
+                        offset += page.items.len();
+                        if page.items.is_empty() || offset >= page.total {
+                            break;
+                        }
+                    }
+                });
+            }
+            
+            // Route LLM requests across the configured providers, recording
+            // the usage and cost of each request
+            let usage_accountant = Arc::new(core::application::services::UsageAccountant::new(
+                Arc::new(infrastructure::db::repositories::SqliteUsageStore::new(database.pool().clone())),
+            ));
+            let llm_router = Arc::new(tauri::async_runtime::block_on(infrastructure::llm::LLMRouter::from_config(
+                &config.llm,
+                &infrastructure::llm::DefaultLLMClientFactory::new(),
+            )).expect("Failed to initialize LLM providers").with_usage_accounting(usage_accountant.clone()));
+            {
+                let llm_router = llm_router.clone();
+                tauri::async_runtime::spawn(async move { llm_router.refresh_costs().await });
+            }
+            
+            // Repeated deterministic requests are answered from the cache,
+            // in front of the router so cache hits cost nothing
+            let llm_cache = Arc::new(core::application::services::CachingClient::new(
+                llm_router.clone(),
+                Arc::new(infrastructure::db::repositories::SqliteResponseCacheStore::new(database.pool().clone())),
+            ).with_config(config.llm.cache.clone()));
+            
+            // Messages and twin documentation are embedded into the local
+            // vector store for retrieval
+            let long_term_memory = &config.agent.long_term_memory;
+            let semantic_memory = Arc::new(core::application::services::SemanticMemory::new(
+                Arc::new(core::application::services::LLMEmbedder::new(
+                    llm_cache.clone(),
+                    long_term_memory.embedding_model.clone(),
+                )),
+                Arc::new(infrastructure::db::repositories::SqliteVectorStore::new(database.pool().clone())),
+            ).with_config(long_term_memory.rag_config()));
+            
+            // Each agent's memory configuration picks how its context is
+            // built: RAG agents see retrieved chunks when long-term memory is
+            // enabled, and summarizing agents fold older messages into stored
+            // summaries with their own model, unless memory compression is
+            // disabled
+            let mut conversation_memory = core::application::services::MemoryManager::new(
+                core::application::services::MemoryConfig {
+                    max_messages: config.agent.memory.max_messages as usize,
+                    compression_enabled: config.agent.memory.compression_enabled,
+                    ..Default::default()
+                },
+            )
+            .with_summarizer(llm_cache.clone(), config.agent.default_model.clone())
+            .with_summary_store(Arc::new(infrastructure::db::repositories::SqliteSummaryStore::new(database.pool().clone())));
+            if long_term_memory.enabled {
+                conversation_memory = conversation_memory.with_semantic_memory(semantic_memory.clone());
+            }
+            
+            // Attachments are read only from the attachment store
+            let attachment_dir = app_dir.join("attachments");
+            std::fs::create_dir_all(&attachment_dir)
+                .expect("Failed to create attachment directory");
+            let attachment_renderer = Arc::new(infrastructure::llm::AttachmentRenderer::new(
+                sensor_repository.clone(),
+                attachment_dir,
+            ));
+            
+            // Several agents of a conversation answer one user message
+            let agent_orchestrator = Arc::new(core::application::services::AgentOrchestrator::new(
+                Arc::new(infrastructure::db::repositories::SqliteConversationRepository::new(database.pool().clone())),
+                Arc::new(infrastructure::db::repositories::SqliteAgentRepository::new(database.pool().clone())),
+                llm_cache.clone(),
+            )
+            .with_memory(conversation_memory)
+            .with_twins(Arc::new(infrastructure::db::repositories::SqliteTwinRepository::new(database.pool().clone())))
+            .with_attachments(attachment_renderer));
+            
+            // Prompt templates render with the live state of a twin
+            let prompt_templates = Arc::new(core::application::services::PromptTemplates::new(
+                Arc::new(infrastructure::db::repositories::SqlitePromptTemplateStore::new(database.pool().clone())),
+            ).with_twin_context(
+                Arc::new(infrastructure::db::repositories::SqliteTwinRepository::new(database.pool().clone())),
+                sensor_repository.clone(),
+            ));
             
             // Register services and middleware as state
             app.manage(conversation_service);
@@ -56,6 +285,23 @@ pub fn run() {
             app.manage(twin_service);
             app.manage(simulation_service);
             app.manage(tool_service);
+            app.manage(modbus_simulators);
+            app.manage(imports);
+            app.manage(sparkplug_ingestor);
+            app.manage(mqtt_executor);
+            app.manage(mapping_ingestor);
+            app.manage(virtual_sensors);
+            app.manage(llm_router);
+            app.manage(llm_cache);
+            app.manage(semantic_memory);
+            app.manage(usage_accountant);
+            app.manage(agent_orchestrator);
+            app.manage(prompt_templates);
+            app.manage(audit_log);
+            app.manage(approvals);
+            app.manage(Arc::new(api::commands::approval_commands::ApprovalSubscriptions::default()));
+            app.manage(key_manager);
+            app.manage(credential_vault);
             app.manage(auth_middleware);
             app.manage(rate_limit_middleware);
             app.manage(validation_middleware);
@@ -70,6 +316,7 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             // Authentication middleware commands
             api::middleware::authenticate,
+            api::middleware::logout,
             api::middleware::require_permission,
             api::middleware::validate_input,
             api::middleware::rate_limit,
@@ -99,6 +346,14 @@ pub fn run() {
             api::commands::agent_commands::delete_agent,
             api::commands::agent_commands::assign_agent_to_conversation,
             api::commands::agent_commands::get_agent_metrics,
+            api::commands::orchestration_commands::run_agent_orchestration,
+            
+            // Prompt template commands
+            api::commands::prompt_template_commands::list_prompt_templates,
+            api::commands::prompt_template_commands::get_prompt_template,
+            api::commands::prompt_template_commands::save_prompt_template,
+            api::commands::prompt_template_commands::delete_prompt_template,
+            api::commands::prompt_template_commands::preview_prompt_template,
             
             // Twin commands
             api::commands::twin_commands::create_digital_twin,
@@ -108,11 +363,54 @@ pub fn run() {
             api::commands::twin_commands::delete_digital_twin,
             api::commands::twin_commands::add_data_source,
             api::commands::twin_commands::remove_data_source,
+            api::commands::twin_commands::subscribe_transform_failures,
             api::commands::twin_commands::configure_twin_sync,
             api::commands::twin_commands::sync_digital_twin,
             api::commands::twin_commands::get_twin_properties,
             api::commands::twin_commands::update_twin_property,
             api::commands::twin_commands::export_twin_model,
+            api::commands::twin_commands::start_modbus_simulator,
+            api::commands::twin_commands::stop_modbus_simulator,
+            api::commands::twin_commands::list_modbus_simulators,
+            api::commands::twin_commands::bind_sparkplug_source,
+            api::commands::import_commands::start_historical_import,
+            api::commands::import_commands::cancel_historical_import,
+            api::commands::import_commands::get_historical_import,
+            api::commands::import_commands::list_historical_imports,
+            
+            // Credential vault commands
+            api::commands::secret_commands::create_secret,
+            api::commands::secret_commands::update_secret,
+            api::commands::secret_commands::delete_secret,
+            api::commands::secret_commands::list_secrets,
+            api::commands::secret_commands::rotate_data_key,
+            api::commands::secret_commands::rotate_master_key,
+            
+            // Audit log commands
+            api::commands::audit_commands::list_audit_entries,
+            api::commands::audit_commands::verify_audit_log,
+            
+            // Tool approval commands
+            api::commands::approval_commands::subscribe_approval_requests,
+            api::commands::approval_commands::unsubscribe_approval_requests,
+            api::commands::approval_commands::list_pending_approvals,
+            api::commands::approval_commands::approve_tool_execution,
+            api::commands::approval_commands::reject_tool_execution,
+            
+            // LLM provider commands
+            api::commands::llm_commands::get_llm_provider_health,
+            api::commands::llm_commands::reset_llm_provider,
+            api::commands::llm_commands::get_llm_cache_stats,
+            api::commands::llm_commands::clear_llm_cache,
+            
+            // LLM usage and cost commands
+            api::commands::usage_commands::get_llm_usage,
+            api::commands::usage_commands::get_llm_costs_by_agent,
+            api::commands::usage_commands::list_llm_pricing,
+            api::commands::usage_commands::set_llm_pricing,
+            api::commands::usage_commands::get_agent_budget,
+            api::commands::usage_commands::set_agent_budget,
+            api::commands::usage_commands::remove_agent_budget,
             
             // Simulation commands
             api::commands::simulation_commands::create_simulation,